@@ -103,6 +103,121 @@ pub fn screenshot(device: Option<&str>) -> Result<Vec<u8>> {
     Ok(output.stdout)
 }
 
+/// How to grab the framebuffer.
+///
+/// `screencap` reads the composited layers and sometimes misses content in a
+/// `SurfaceView` or GPU-composited layer (Flutter, games), returning a blank
+/// or partial frame. `screenrecord` goes through the video encoder path,
+/// which does see those layers, at the cost of about a second per capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureStrategy {
+    Screencap,
+    ScreenRecord,
+}
+
+impl CaptureStrategy {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "screencap" => Ok(CaptureStrategy::Screencap),
+            "screenrecord" => Ok(CaptureStrategy::ScreenRecord),
+            other => bail!("Unknown capture strategy '{}'. Supported: screencap, screenrecord", other),
+        }
+    }
+}
+
+/// Take a screenshot with the given strategy and return PNG bytes.
+pub fn screenshot_with(strategy: CaptureStrategy, device: Option<&str>) -> Result<Vec<u8>> {
+    match strategy {
+        CaptureStrategy::Screencap => screenshot(device),
+        CaptureStrategy::ScreenRecord => screenrecord_frame(device),
+    }
+}
+
+/// Record one second of raw RGB frames and keep the last complete one.
+fn screenrecord_frame(device: Option<&str>) -> Result<Vec<u8>> {
+    let (w, h) = get_screen_size(device)?;
+    let size = format!("{}x{}", w, h);
+    let output = adb_exec(device, &[
+        "exec-out", "screenrecord", "--output-format=raw-frames",
+        "--time-limit", "1", "--size", &size, "-",
+    ], None)?;
+    if !output.status.success() {
+        bail!("adb screenrecord failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    let img = last_rgb_frame(&output.stdout, w, h)?;
+    let mut png = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// Split headerless RGB888 `raw-frames` output and decode the final frame.
+fn last_rgb_frame(raw: &[u8], width: u32, height: u32) -> Result<image::DynamicImage> {
+    let frame_len = width as usize * height as usize * 3;
+    let frames = raw.len() / frame_len.max(1);
+    if frames == 0 {
+        bail!("screenrecord produced no complete {}x{} frame ({} bytes)", width, height, raw.len());
+    }
+    let last = raw[(frames - 1) * frame_len..frames * frame_len].to_vec();
+    image::RgbImage::from_raw(width, height, last)
+        .map(image::DynamicImage::ImageRgb8)
+        .context("screenrecord frame has the wrong size")
+}
+
+/// A capture came back black because the focused window sets `FLAG_SECURE`.
+///
+/// Surfaced through `anyhow`; callers can `downcast_ref::<SecureWindow>()`
+/// to fall back to a hierarchy-based view instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecureWindow {
+    pub window: String,
+}
+
+impl std::fmt::Display for SecureWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Window '{}' sets FLAG_SECURE, so the screen capture is black. \
+             Use --allow-secure-bypass to render it from the UI hierarchy instead",
+            self.window
+        )
+    }
+}
+
+impl std::error::Error for SecureWindow {}
+
+/// Name of the focused window if it sets `FLAG_SECURE`.
+pub fn secure_focused_window(device: Option<&str>) -> Result<Option<String>> {
+    let output = adb_exec(device, &["shell", "dumpsys", "window", "windows"], None)?;
+    if !output.status.success() {
+        bail!("dumpsys window failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(parse_secure_focus(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Find `mCurrentFocus=Window{<hash> u0 <name>}` and check whether that
+/// window's `fl=` attribute line lists `SECURE`.
+fn parse_secure_focus(dumpsys: &str) -> Option<String> {
+    let focus = dumpsys.lines().find_map(|l| l.trim().strip_prefix("mCurrentFocus=Window{"))?;
+    let focus = focus.trim_end_matches('}');
+    let hash = focus.split_whitespace().next()?;
+    let name = focus.split_whitespace().last()?.to_string();
+
+    let header = format!("Window{{{} ", hash);
+    let mut in_focused = false;
+    for line in dumpsys.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("Window #") {
+            in_focused = trimmed.contains(&header);
+        } else if in_focused
+            && trimmed.starts_with("fl=")
+            && trimmed.split_whitespace().any(|f| f.trim_start_matches("fl=") == "SECURE")
+        {
+            return Some(name);
+        }
+    }
+    None
+}
+
 /// Tap at coordinates
 pub fn tap(x: i32, y: i32, device: Option<&str>) -> Result<()> {
     let output = adb_exec(device, &["shell", "input", "tap", &x.to_string(), &y.to_string()], None)?;
@@ -115,6 +230,33 @@ pub fn tap(x: i32, y: i32, device: Option<&str>) -> Result<()> {
     Ok(())
 }
 
+/// Hold this much longer than the system threshold: released right at the
+/// threshold, a press can still be taken for a tap.
+const LONG_PRESS_MARGIN_MS: u32 = 200;
+
+/// The system `long_press_timeout` when the setting can't be read.
+const DEFAULT_LONG_PRESS_TIMEOUT_MS: u32 = 400;
+
+/// How long to hold for a long press: `requested` if given, else the
+/// device's `long_press_timeout` (400ms unless changed in accessibility
+/// settings, and when it can't be read) plus a small margin.
+pub fn long_press_duration(requested: Option<u32>, device: Option<&str>) -> u32 {
+    requested.unwrap_or_else(|| {
+        let timeout = secure_setting("long_press_timeout", device)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_LONG_PRESS_TIMEOUT_MS);
+        timeout + LONG_PRESS_MARGIN_MS
+    })
+}
+
+/// Long press the center of the first element matching `query` (see
+/// [`find_element`]); `duration` defaults to [`long_press_duration`].
+pub fn long_press_element(query: &str, duration: Option<u32>, device: Option<&str>) -> Result<()> {
+    let (x, y) = find_element(query, device)?.with_context(|| format!("Element '{}' not found for long press", query))?;
+    long_press(x, y, long_press_duration(duration, device), device)
+}
+
 /// Long press at coordinates
 pub fn long_press(x: i32, y: i32, duration: u32, device: Option<&str>) -> Result<()> {
     let output = adb_exec(device, &[
@@ -200,6 +342,73 @@ pub fn input_text(text: &str, device: Option<&str>) -> Result<()> {
     Ok(())
 }
 
+/// One unit of [`type_text_slow`]: a character for `input text`, or a run
+/// of characters it cannot produce, pasted through the clipboard.
+#[derive(Debug, PartialEq, Eq)]
+enum SlowTextChunk {
+    Typed(char),
+    Pasted(String),
+}
+
+/// Split `text` for [`type_text_slow`]. `input text` only handles printable
+/// ASCII; anything else (accents, CJK, emoji) is grouped into paste runs.
+fn slow_text_chunks(text: &str) -> Vec<SlowTextChunk> {
+    let mut chunks = Vec::new();
+    for c in text.chars() {
+        if matches!(c, ' '..='~') {
+            chunks.push(SlowTextChunk::Typed(c));
+        } else if let Some(SlowTextChunk::Pasted(run)) = chunks.last_mut() {
+            run.push(c);
+        } else {
+            chunks.push(SlowTextChunk::Pasted(c.to_string()));
+        }
+    }
+    chunks
+}
+
+/// Input text one character at a time, waiting `delay_per_char` after each.
+///
+/// For fields that drop keystrokes when [`input_text`] delivers the whole
+/// string at once (search-as-you-type, OTP boxes). Characters `input text`
+/// cannot produce are put on the clipboard and pasted, one paste per run;
+/// the previous clip is put back afterwards.
+pub fn type_text_slow(text: &str, delay_per_char: std::time::Duration, device: Option<&str>) -> Result<()> {
+    let chunks = slow_text_chunks(text);
+    let saved_clip = if chunks.iter().any(|c| matches!(c, SlowTextChunk::Pasted(_))) {
+        Some(read_clipboard(device)?)
+    } else {
+        None
+    };
+    let typed = (|| -> Result<()> {
+        for chunk in &chunks {
+            let output = match chunk {
+                SlowTextChunk::Typed(c) => {
+                    let token = if *c == ' ' { "%s".to_string() } else { c.to_string() };
+                    let cmd = DeviceShellCmd::new().literal("input").literal("text").user_input(&token).render();
+                    adb_exec(device, &["shell", &cmd], None)?
+                }
+                SlowTextChunk::Pasted(run) => {
+                    write_clipboard(run, device)?;
+                    adb_exec(device, &["shell", "input", "keyevent", "KEYCODE_PASTE"], None)?
+                }
+            };
+            if !output.status.success() {
+                bail!("adb input failed at {:?}: {}", chunk, String::from_utf8_lossy(&output.stderr));
+            }
+            std::thread::sleep(delay_per_char);
+        }
+        Ok(())
+    })();
+    if let Some(clip) = saved_clip {
+        if let Err(e) = write_clipboard(&clip, device) {
+            eprintln!("Warning: could not restore the clipboard: {:#}", e);
+        }
+    }
+    typed?;
+    println!("Input text: {} ({} ms per character)", text, delay_per_char.as_millis());
+    Ok(())
+}
+
 /// Press a key
 pub fn press_key(key: &str, device: Option<&str>) -> Result<()> {
     let keycode = match key.to_lowercase().as_str() {
@@ -234,6 +443,360 @@ pub fn press_key(key: &str, device: Option<&str>) -> Result<()> {
     Ok(())
 }
 
+// ============== Multi-touch Gestures ==============
+//
+// `input swipe` only drives a single pointer, so pinch / rotate / two-finger
+// scroll are injected as raw Linux multi-touch (protocol B) events through
+// `sendevent`. Requirements and caveats:
+//
+//   * The touchscreen node (`/dev/input/eventN`) must be writable by the adb
+//     `shell` user. This holds on emulators and userdebug/eng builds, and on
+//     most retail devices where `shell` is in the `input` group. Some OEM
+//     ROMs restrict the node via SELinux — `sendevent` then fails with
+//     "Permission denied" and only a rooted device (`adb root`) works.
+//   * Touch coordinates are in the panel's ABS range, not screen pixels; we
+//     read the range from `getevent -pl` and scale accordingly.
+//   * The number of simultaneous pointers is capped by the panel's
+//     `ABS_MT_SLOT` max (reported max touch points).
+
+/// Linux input event constants used by the multi-touch injector.
+const EV_SYN: u16 = 0;
+const EV_KEY: u16 = 1;
+const EV_ABS: u16 = 3;
+const BTN_TOUCH: u16 = 330;
+const ABS_MT_SLOT: u16 = 0x2f;
+const ABS_MT_POSITION_X: u16 = 0x35;
+const ABS_MT_POSITION_Y: u16 = 0x36;
+const ABS_MT_TRACKING_ID: u16 = 0x39;
+
+/// Number of intermediate frames a multi-touch gesture is interpolated into.
+const MULTI_TOUCH_FRAMES: u32 = 12;
+
+/// Screen-pixel waypoints for one finger of a [`multi_touch`] gesture.
+///
+/// The finger goes down on the first point, moves through the rest, and lifts
+/// on the last one. A single-point path is a stationary press.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointerPath {
+    pub points: Vec<(i32, i32)>,
+}
+
+impl PointerPath {
+    /// Position at `t` in `[0, 1]`, linearly interpolated along the waypoints.
+    fn position_at(&self, t: f64) -> (i32, i32) {
+        let n = self.points.len();
+        if n == 1 {
+            return self.points[0];
+        }
+        let scaled = t.clamp(0.0, 1.0) * (n - 1) as f64;
+        let seg = (scaled.floor() as usize).min(n - 2);
+        let local = scaled - seg as f64;
+        let (x1, y1) = self.points[seg];
+        let (x2, y2) = self.points[seg + 1];
+        (
+            (x1 as f64 + (x2 - x1) as f64 * local).round() as i32,
+            (y1 as f64 + (y2 - y1) as f64 * local).round() as i32,
+        )
+    }
+}
+
+/// Multi-touch capable input node as reported by `getevent -pl`.
+#[derive(Debug, Clone, PartialEq)]
+struct TouchDevice {
+    path: String,
+    min_x: i32,
+    max_x: i32,
+    min_y: i32,
+    max_y: i32,
+    /// Max simultaneous pointers (`ABS_MT_SLOT` max + 1).
+    max_pointers: usize,
+}
+
+/// Parse `min`/`max` out of a `getevent -pl` axis line such as
+/// `ABS_MT_POSITION_X : value 0, min 0, max 1079, fuzz 0, flat 0, resolution 0`.
+fn parse_abs_range(line: &str) -> Option<(i32, i32)> {
+    let mut min = None;
+    let mut max = None;
+    for part in line.split(',') {
+        let part = part.trim();
+        if let Some(v) = part.strip_prefix("min ") {
+            min = v.trim().parse().ok();
+        } else if let Some(v) = part.strip_prefix("max ") {
+            max = v.trim().parse().ok();
+        }
+    }
+    Some((min?, max?))
+}
+
+/// Find the first input node exposing `ABS_MT_POSITION_X/Y`.
+fn parse_touch_device(getevent: &str) -> Option<TouchDevice> {
+    let mut current: Option<String> = None;
+    let mut x: Option<(i32, i32)> = None;
+    let mut y: Option<(i32, i32)> = None;
+    let mut slots: Option<usize> = None;
+
+    let finish = |path: &Option<String>, x: Option<(i32, i32)>, y: Option<(i32, i32)>, slots: Option<usize>| {
+        match (path, x, y) {
+            (Some(p), Some((min_x, max_x)), Some((min_y, max_y))) => Some(TouchDevice {
+                path: p.clone(),
+                min_x,
+                max_x,
+                min_y,
+                max_y,
+                max_pointers: slots.unwrap_or(1),
+            }),
+            _ => None,
+        }
+    };
+
+    for line in getevent.lines() {
+        let trimmed = line.trim();
+        if let Some(rest) = trimmed.strip_prefix("add device") {
+            if let Some(dev) = finish(&current, x, y, slots) {
+                return Some(dev);
+            }
+            current = rest.split(':').nth(1).map(|p| p.trim().to_string());
+            x = None;
+            y = None;
+            slots = None;
+        } else if trimmed.contains("ABS_MT_POSITION_X") {
+            x = parse_abs_range(trimmed);
+        } else if trimmed.contains("ABS_MT_POSITION_Y") {
+            y = parse_abs_range(trimmed);
+        } else if trimmed.contains("ABS_MT_SLOT") {
+            slots = parse_abs_range(trimmed).map(|(_, max)| (max.max(0) + 1) as usize);
+        }
+    }
+    finish(&current, x, y, slots)
+}
+
+/// Validate an input node path parsed from `getevent` (`/dev/input/eventN`).
+fn validate_input_device_path(path: &str) -> Result<()> {
+    let ok = path
+        .strip_prefix("/dev/input/event")
+        .map(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        .unwrap_or(false);
+    if !ok {
+        bail!("Unexpected input device path '{}'", path);
+    }
+    Ok(())
+}
+
+/// Locate the touchscreen node and its coordinate range.
+fn detect_touch_device(device: Option<&str>) -> Result<TouchDevice> {
+    let output = adb_exec(device, &["shell", "getevent", "-pl"], None)?;
+    if !output.status.success() {
+        bail!("getevent failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let touch = parse_touch_device(&text)
+        .context("No multi-touch input device found (no ABS_MT_POSITION_X/Y in getevent -pl)")?;
+    validate_input_device_path(&touch.path)?;
+    Ok(touch)
+}
+
+/// Map a screen-pixel coordinate onto the panel's ABS range.
+fn to_touch_coord(v: i32, screen: u32, min: i32, max: i32) -> i32 {
+    if screen == 0 {
+        return min;
+    }
+    let v = v.clamp(0, screen as i32 - 1) as i64;
+    (min as i64 + v * (max - min) as i64 / (screen as i64 - 1).max(1)) as i32
+}
+
+/// Build the `(type, code, value)` event stream for a multi-touch gesture.
+///
+/// Frame 0 puts every pointer down, frames `1..=frames` move them, and a
+/// final report lifts all pointers. A `None` entry marks a frame boundary
+/// (the caller inserts a sleep there).
+fn build_multi_touch_events(
+    touch: &TouchDevice,
+    screen: (u32, u32),
+    pointers: &[PointerPath],
+    frames: u32,
+) -> Vec<Option<(u16, u16, i32)>> {
+    let mut events = Vec::new();
+    let frames = frames.max(1);
+
+    for f in 0..=frames {
+        let t = f as f64 / frames as f64;
+        for (slot, pointer) in pointers.iter().enumerate() {
+            let (x, y) = pointer.position_at(t);
+            events.push(Some((EV_ABS, ABS_MT_SLOT, slot as i32)));
+            if f == 0 {
+                events.push(Some((EV_ABS, ABS_MT_TRACKING_ID, slot as i32)));
+            }
+            events.push(Some((EV_ABS, ABS_MT_POSITION_X, to_touch_coord(x, screen.0, touch.min_x, touch.max_x))));
+            events.push(Some((EV_ABS, ABS_MT_POSITION_Y, to_touch_coord(y, screen.1, touch.min_y, touch.max_y))));
+        }
+        if f == 0 {
+            events.push(Some((EV_KEY, BTN_TOUCH, 1)));
+        }
+        events.push(Some((EV_SYN, 0, 0)));
+        events.push(None);
+    }
+
+    for slot in 0..pointers.len() {
+        events.push(Some((EV_ABS, ABS_MT_SLOT, slot as i32)));
+        events.push(Some((EV_ABS, ABS_MT_TRACKING_ID, -1)));
+    }
+    events.push(Some((EV_KEY, BTN_TOUCH, 0)));
+    events.push(Some((EV_SYN, 0, 0)));
+    events
+}
+
+/// Run an event stream from [`build_multi_touch_events`] in one shell call.
+///
+/// Frame boundaries sleep `duration_ms / MULTI_TOUCH_FRAMES`; when `hold_ms`
+/// is set, the first boundary (right after touch-down) sleeps that long
+/// instead, which turns the gesture into a long-press followed by a drag.
+fn run_sendevent_script(
+    touch: &TouchDevice,
+    events: &[Option<(u16, u16, i32)>],
+    duration_ms: u32,
+    hold_ms: Option<u32>,
+    device: Option<&str>,
+) -> Result<()> {
+    let frame_secs = format!("{:.3}", duration_ms as f64 / 1000.0 / MULTI_TOUCH_FRAMES as f64);
+    let hold_secs = hold_ms.map(|ms| format!("{:.3}", ms as f64 / 1000.0));
+    let mut script: Vec<String> = Vec::new();
+    let mut first_boundary = true;
+    for event in events {
+        let line = match event {
+            Some((ty, code, value)) => DeviceShellCmd::new()
+                .literal("sendevent")
+                .validated(&touch.path, validate_input_device_path)?
+                .user_input(&ty.to_string())
+                .user_input(&code.to_string())
+                .user_input(&value.to_string())
+                .render(),
+            None => {
+                let secs = match (&hold_secs, first_boundary) {
+                    (Some(hold), true) => hold,
+                    _ => &frame_secs,
+                };
+                first_boundary = false;
+                DeviceShellCmd::new().literal("sleep").user_input(secs).render()
+            }
+        };
+        script.push(line);
+    }
+
+    let rendered = DeviceShellCmd::new().raw_trusted(script.join("; ")).render();
+    let output = adb_exec(device, &["shell", &rendered], None)?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() || stderr.contains("Permission denied") {
+        bail!(
+            "sendevent failed (touch node may need root on this build): {}",
+            stderr.trim()
+        );
+    }
+    Ok(())
+}
+
+/// Inject a gesture made of several simultaneous pointers via `sendevent`.
+///
+/// Every pointer is interpolated over the same `duration_ms`. Fails when the
+/// pointer count exceeds the panel's reported max touch points.
+pub fn multi_touch(pointers: &[PointerPath], duration_ms: u32, device: Option<&str>) -> Result<()> {
+    if pointers.is_empty() {
+        bail!("multi-touch requires at least one pointer");
+    }
+    if pointers.iter().any(|p| p.points.is_empty()) {
+        bail!("Every pointer path needs at least one point");
+    }
+
+    let touch = detect_touch_device(device)?;
+    if pointers.len() > touch.max_pointers {
+        bail!(
+            "{} pointers requested but {} supports at most {} touch points",
+            pointers.len(),
+            touch.path,
+            touch.max_pointers
+        );
+    }
+    let screen = get_screen_size(device)?;
+
+    let events = build_multi_touch_events(&touch, screen, pointers, MULTI_TOUCH_FRAMES);
+    run_sendevent_script(&touch, &events, duration_ms, None, device)?;
+
+    println!(
+        "Multi-touch: {} pointer(s) over {}ms on {}",
+        pointers.len(),
+        duration_ms,
+        touch.path
+    );
+    Ok(())
+}
+
+/// Two-finger pinch centred on `(cx, cy)`.
+///
+/// Fingers sit on a horizontal line `start_dist` pixels apart and move to
+/// `end_dist` apart: `end_dist > start_dist` zooms in, smaller zooms out.
+pub fn pinch(
+    cx: i32,
+    cy: i32,
+    start_dist: u32,
+    end_dist: u32,
+    duration_ms: u32,
+    device: Option<&str>,
+) -> Result<()> {
+    let (start_half, end_half) = ((start_dist / 2) as i32, (end_dist / 2) as i32);
+    let pointers = [
+        PointerPath { points: vec![(cx - start_half, cy), (cx - end_half, cy)] },
+        PointerPath { points: vec![(cx + start_half, cy), (cx + end_half, cy)] },
+    ];
+    multi_touch(&pointers, duration_ms, device)
+}
+
+/// Select the text between two screen points.
+///
+/// `KEYCODE_SELECT_ALL` can only grab everything, so this long-presses at
+/// `start` (which selects the word and starts selection mode) and, without
+/// lifting, drags the selection end to `end`. The gesture is followed by a UI
+/// dump that must show the floating selection toolbar (Copy / Cut / …).
+pub fn select_text_range(
+    start: (i32, i32),
+    end: (i32, i32),
+    hold_ms: u32,
+    drag_ms: u32,
+    device: Option<&str>,
+) -> Result<()> {
+    let touch = detect_touch_device(device)?;
+    let screen = get_screen_size(device)?;
+    let path = PointerPath { points: vec![start, end] };
+    let events = build_multi_touch_events(&touch, screen, &[path], MULTI_TOUCH_FRAMES);
+    run_sendevent_script(&touch, &events, drag_ms, Some(hold_ms), device)?;
+
+    // The toolbar animates in; give it a few tries before giving up.
+    for _ in 0..3 {
+        std::thread::sleep(Duration::from_millis(300));
+        if has_selection_toolbar(&get_ui_xml(device)?) {
+            println!(
+                "Selected text from ({}, {}) to ({}, {})",
+                start.0, start.1, end.0, end.1
+            );
+            return Ok(());
+        }
+    }
+    bail!(
+        "No text selection toolbar appeared after long-press at ({}, {}) — is there selectable text at the start point?",
+        start.0,
+        start.1
+    )
+}
+
+/// True when the UI hierarchy contains the floating text-selection toolbar.
+fn has_selection_toolbar(xml: &str) -> bool {
+    if xml.contains("floating_toolbar_menu_item") {
+        return true;
+    }
+    parse_ui_elements(xml).iter().any(|e| {
+        let label = if e.text.is_empty() { &e.content_desc } else { &e.text };
+        matches!(label.as_str(), "Copy" | "Cut" | "Select all")
+    })
+}
+
 // ============== UI Dump (shared implementation) ==============
 
 /// Get raw UI XML from device
@@ -256,7 +819,7 @@ fn get_ui_xml(device: Option<&str>) -> Result<String> {
     Ok(xml)
 }
 
-/// UI Element with parsed bounds
+/// UI Element with parsed bounds (shared by the Android and iOS hierarchy dumps)
 #[derive(Clone, Debug, Serialize)]
 pub struct UiElement {
     pub class: String,
@@ -278,18 +841,16 @@ impl UiElement {
         } else if !self.content_desc.is_empty() {
             self.content_desc.clone()
         } else if !self.resource_id.is_empty() {
-            self.resource_id.split('/').last().unwrap_or("").to_string()
+            self.resource_id.split('/').next_back().unwrap_or("").to_string()
         } else {
-            self.class.split('.').last().unwrap_or("").to_string()
+            self.class.split('.').next_back().unwrap_or("").to_string()
         }
     }
 
-    #[allow(dead_code)]
     pub fn width(&self) -> i32 {
         self.bounds.2 - self.bounds.0
     }
 
-    #[allow(dead_code)]
     pub fn height(&self) -> i32 {
         self.bounds.3 - self.bounds.1
     }
@@ -488,6 +1049,300 @@ pub fn find_element(query: &str, device: Option<&str>) -> Result<Option<(i32, i3
     Ok(None)
 }
 
+// ============== Element assertions ==============
+
+/// What [`assert_element`] checks on the matched element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ElementAssertion {
+    TextEquals(String),
+    TextContains(String),
+    Enabled(bool),
+    Checked(bool),
+    /// `false` passes when no element matches.
+    Visible(bool),
+}
+
+impl ElementAssertion {
+    /// `text-equals VALUE`, `text-contains VALUE`, `enabled`, `disabled`,
+    /// `checked`, `unchecked`, `visible` or `gone`.
+    pub fn parse(check: &str, value: Option<&str>) -> Result<Self> {
+        let value = |what: &str| value.map(str::to_string).with_context(|| format!("{} needs a value", what));
+        Ok(match check {
+            "text-equals" => ElementAssertion::TextEquals(value(check)?),
+            "text-contains" => ElementAssertion::TextContains(value(check)?),
+            "enabled" => ElementAssertion::Enabled(true),
+            "disabled" => ElementAssertion::Enabled(false),
+            "checked" => ElementAssertion::Checked(true),
+            "unchecked" => ElementAssertion::Checked(false),
+            "visible" => ElementAssertion::Visible(true),
+            "gone" => ElementAssertion::Visible(false),
+            other => bail!(
+                "Unknown element check '{}'. Supported: text-equals, text-contains, enabled, disabled, checked, unchecked, visible, gone",
+                other
+            ),
+        })
+    }
+
+    fn expected(&self) -> String {
+        match self {
+            ElementAssertion::TextEquals(t) => format!("text == \"{}\"", t),
+            ElementAssertion::TextContains(t) => format!("text contains \"{}\"", t),
+            ElementAssertion::Enabled(on) => format!("enabled={}", on),
+            ElementAssertion::Checked(on) => format!("checked={}", on),
+            ElementAssertion::Visible(true) => "visible".to_string(),
+            ElementAssertion::Visible(false) => "gone".to_string(),
+        }
+    }
+}
+
+/// Outcome of [`assert_element`], with expected and actual values for reports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ElementCheck {
+    pub query: String,
+    pub expected: String,
+    /// Whether any element matched the query.
+    pub found: bool,
+    /// The checked attribute as found (`None` when nothing matched).
+    pub actual: Option<String>,
+    pub passed: bool,
+}
+
+impl ElementCheck {
+    pub fn message(&self) -> String {
+        match (&self.actual, self.passed) {
+            (_, true) if !self.found => format!("No element matches '{}' (expected {})", self.query, self.expected),
+            (None, _) => format!("Element '{}' not found (expected {})", self.query, self.expected),
+            (Some(actual), true) => format!("Element '{}': {} (actual {})", self.query, self.expected, actual),
+            (Some(actual), false) => {
+                format!("Element '{}' found but expected {}, actual {}", self.query, self.expected, actual)
+            }
+        }
+    }
+}
+
+/// Value of `name="..."` in a hierarchy node, with XML entities decoded.
+fn node_attr<'a>(node: &'a str, name: &str) -> Option<std::borrow::Cow<'a, str>> {
+    let start = node.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let raw = node[start..].split('"').next()?;
+    Some(if raw.contains('&') { xml_unescape(raw).into() } else { raw.into() })
+}
+
+fn xml_unescape(value: &str) -> String {
+    value.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+/// Check `assertion` against the first node in `xml` whose text,
+/// resource-id or content-desc contains `query` (case-insensitive, as
+/// [`find_element`] matches).
+fn check_element(xml: &str, query: &str, assertion: &ElementAssertion) -> ElementCheck {
+    let query_lower = query.to_lowercase();
+    let node = node_regex().find_iter(xml).map(|m| m.as_str()).find(|node| {
+        ["text", "resource-id", "content-desc"]
+            .iter()
+            .any(|attr| node_attr(node, attr).is_some_and(|v| v.to_lowercase().contains(&query_lower)))
+    });
+    let mut check = ElementCheck { query: query.to_string(), expected: assertion.expected(), found: node.is_some(), actual: None, passed: false };
+    let Some(node) = node else {
+        check.passed = *assertion == ElementAssertion::Visible(false);
+        return check;
+    };
+    let flag = |name: &str| node_attr(node, name).as_deref() == Some("true");
+    let text = node_attr(node, "text").unwrap_or_default().into_owned();
+    let (actual, passed) = match assertion {
+        ElementAssertion::TextEquals(want) => (format!("\"{}\"", text), text == *want),
+        ElementAssertion::TextContains(want) => (format!("\"{}\"", text), text.contains(want.as_str())),
+        ElementAssertion::Enabled(want) => (format!("enabled={}", flag("enabled")), flag("enabled") == *want),
+        ElementAssertion::Checked(want) => (format!("checked={}", flag("checked")), flag("checked") == *want),
+        ElementAssertion::Visible(want) => {
+            // Dumps without `visible-to-user` only list on-screen nodes.
+            let visible = node_attr(node, "visible-to-user").is_none_or(|v| v == "true");
+            (if visible { "visible" } else { "not visible" }.to_string(), visible == *want)
+        }
+    };
+    check.actual = Some(actual);
+    check.passed = passed;
+    check
+}
+
+/// Check an element's text or state in the UI hierarchy, telling "not
+/// found" apart from "found, but the attribute differs" in the result.
+pub fn assert_element(query: &str, assertion: &ElementAssertion, device: Option<&str>) -> Result<ElementCheck> {
+    Ok(check_element(&get_ui_xml(device)?, query, assertion))
+}
+
+/// System dialogs [`DialogHandler`] recognizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SystemDialog {
+    /// "App isn't responding".
+    Anr,
+    /// "App keeps stopping".
+    Crash,
+    /// A runtime permission prompt.
+    Permission,
+    /// A Play Store update prompt.
+    Update,
+}
+
+impl SystemDialog {
+    const ALL: [SystemDialog; 4] = [SystemDialog::Anr, SystemDialog::Crash, SystemDialog::Permission, SystemDialog::Update];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SystemDialog::Anr => "anr",
+            SystemDialog::Crash => "crash",
+            SystemDialog::Permission => "permission",
+            SystemDialog::Update => "update",
+        }
+    }
+
+    /// Responses a [`DialogPolicy`] may pick, the default first.
+    fn responses(self) -> &'static [&'static str] {
+        match self {
+            SystemDialog::Anr => &["wait", "close", "ignore"],
+            SystemDialog::Crash => &["close", "ignore"],
+            SystemDialog::Permission => &["allow", "deny", "ignore"],
+            SystemDialog::Update => &["dismiss", "ignore"],
+        }
+    }
+}
+
+/// How [`DialogHandler`] answers each kind of system dialog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogPolicy {
+    responses: std::collections::HashMap<SystemDialog, &'static str>,
+}
+
+impl Default for DialogPolicy {
+    /// Wait on ANRs, close crash dialogs, allow permissions, dismiss updates.
+    fn default() -> Self {
+        Self { responses: SystemDialog::ALL.iter().map(|k| (*k, k.responses()[0])).collect() }
+    }
+}
+
+impl DialogPolicy {
+    /// `KIND=RESPONSE` pairs separated by commas, e.g. `anr=close,permission=deny`;
+    /// kinds not listed keep their default.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut policy = Self::default();
+        for pair in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (kind, response) = pair.split_once('=').with_context(|| format!("Expected KIND=RESPONSE, got '{}'", pair))?;
+            let kind = SystemDialog::ALL
+                .into_iter()
+                .find(|k| k.as_str() == kind.trim())
+                .with_context(|| format!("Unknown dialog kind '{}'. Supported: anr, crash, permission, update", kind.trim()))?;
+            let response = kind.responses().iter().find(|r| **r == response.trim()).with_context(|| {
+                format!("Unknown response '{}' for {} dialogs. Supported: {}", response.trim(), kind.as_str(), kind.responses().join(", "))
+            })?;
+            policy.responses.insert(kind, response);
+        }
+        Ok(policy)
+    }
+
+    fn response(&self, kind: SystemDialog) -> &'static str {
+        self.responses.get(&kind).copied().unwrap_or("ignore")
+    }
+}
+
+/// A system dialog [`DialogHandler`] answered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HandledDialog {
+    pub kind: SystemDialog,
+    pub response: &'static str,
+    /// Label of the button pressed.
+    pub button: String,
+    #[serde(skip)]
+    tap: (i32, i32),
+}
+
+/// Buttons of a Play Store update prompt that put it off.
+const UPDATE_DISMISS_LABELS: &[&str] = &["not now", "no thanks", "later", "skip"];
+
+/// Most dialogs answered in one [`DialogHandler::clear`]; a dialog that keeps
+/// coming back is left for the step to fail on.
+const MAX_DIALOGS_PER_CLEAR: usize = 3;
+
+/// Watches the hierarchy for ANR, crash, permission and update dialogs and
+/// answers them per its [`DialogPolicy`].
+#[derive(Debug, Clone, Default)]
+pub struct DialogHandler {
+    pub policy: DialogPolicy,
+}
+
+impl DialogHandler {
+    /// The dialog in `xml` and the button the policy presses, if any.
+    fn check(&self, xml: &str) -> Option<HandledDialog> {
+        let nodes: Vec<&str> = node_regex().find_iter(xml).map(|m| m.as_str()).collect();
+        let by_id = |suffix: &str| {
+            nodes
+                .iter()
+                .copied()
+                .find(|n| node_attr(n, "resource-id").is_some_and(|id| id.ends_with(&format!(":id/{}", suffix))))
+        };
+        let first_id = |suffixes: &[&str]| suffixes.iter().find_map(|s| by_id(s));
+
+        let (kind, button) = if by_id("aerr_wait").is_some() {
+            let button = match self.policy.response(SystemDialog::Anr) {
+                "wait" => by_id("aerr_wait"),
+                "close" => by_id("aerr_close"),
+                _ => None,
+            };
+            (SystemDialog::Anr, button)
+        } else if by_id("aerr_close").is_some() {
+            (SystemDialog::Crash, by_id("aerr_close").filter(|_| self.policy.response(SystemDialog::Crash) == "close"))
+        } else if first_id(&["permission_allow_button", "permission_allow_foreground_only_button", "permission_deny_button"]).is_some() {
+            let button = match self.policy.response(SystemDialog::Permission) {
+                "allow" => first_id(&[
+                    "permission_allow_foreground_only_button",
+                    "permission_allow_button",
+                    "permission_allow_one_time_button",
+                ]),
+                "deny" => first_id(&["permission_deny_button", "permission_deny_and_dont_ask_again_button"]),
+                _ => None,
+            };
+            (SystemDialog::Permission, button)
+        } else {
+            let text = |n: &str| node_attr(n, "text").unwrap_or_default().to_lowercase();
+            let store: Vec<&str> =
+                nodes.iter().copied().filter(|n| node_attr(n, "package").as_deref() == Some("com.android.vending")).collect();
+            let mentions_update = store.iter().any(|n| text(n).contains("update"));
+            let dismiss = store.iter().copied().find(|n| UPDATE_DISMISS_LABELS.contains(&text(n).as_str()));
+            match dismiss {
+                Some(button) if mentions_update => {
+                    (SystemDialog::Update, Some(button).filter(|_| self.policy.response(SystemDialog::Update) == "dismiss"))
+                }
+                _ => return None,
+            }
+        };
+        let button = button?;
+        let caps = bounds_regex().captures(button)?;
+        let n = |i: usize| caps[i].parse::<i32>().unwrap_or(0);
+        Some(HandledDialog {
+            kind,
+            response: self.policy.response(kind),
+            button: node_attr(button, "text").unwrap_or_default().into_owned(),
+            tap: ((n(1) + n(3)) / 2, (n(2) + n(4)) / 2),
+        })
+    }
+
+    /// Answer whatever system dialogs are showing; returns those handled.
+    pub fn clear(&self, device: Option<&str>) -> Result<Vec<HandledDialog>> {
+        let mut handled = Vec::new();
+        for _ in 0..MAX_DIALOGS_PER_CLEAR {
+            let Some(dialog) = self.check(&get_ui_xml(device)?) else { break };
+            let (x, y) = dialog.tap;
+            let output = adb_exec(device, &["shell", "input", "tap", &x.to_string(), &y.to_string()], None)?;
+            if !output.status.success() {
+                bail!("adb tap on {} dialog failed: {}", dialog.kind.as_str(), String::from_utf8_lossy(&output.stderr));
+            }
+            handled.push(dialog);
+            // Let the dialog animate away before looking again.
+            std::thread::sleep(Duration::from_millis(500));
+        }
+        Ok(handled)
+    }
+}
+
 /// Find a UI element matching any of the supplied criteria.
 ///
 /// All supplied criteria must match (logical AND). Matching is case-insensitive
@@ -573,45 +1428,750 @@ pub fn find_ui_element(
     Ok(None)
 }
 
-/// Tap element by text/resource-id
-pub fn tap_element(query: &str, device: Option<&str>) -> Result<()> {
-    if let Some((x, y)) = find_element(query, device)? {
-        tap(x, y, device)?;
-    } else {
-        bail!("Element '{}' not found", query);
-    }
-    Ok(())
+/// How [`wait_for_text`] found its text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextSource {
+    Hierarchy,
+    Ocr,
 }
 
-// ============== Device Management ==============
+impl TextSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TextSource::Hierarchy => "hierarchy",
+            TextSource::Ocr => "ocr",
+        }
+    }
+}
 
-#[derive(Serialize)]
-pub struct Device {
-    pub serial: String,
-    pub state: String,
-    pub model: Option<String>,
+/// Where [`wait_for_text`] found its text, as `(left, top, right, bottom)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextMatch {
+    pub bounds: (i32, i32, i32, i32),
+    pub source: TextSource,
 }
 
-/// List connected devices
-pub fn list_devices() -> Result<Vec<Device>> {
-    let output = Command::new("adb")
-        .args(["devices", "-l"])
-        .output()
-        .context("Failed to execute adb devices")?;
+impl TextMatch {
+    pub fn center(&self) -> (i32, i32) {
+        let (l, t, r, b) = self.bounds;
+        ((l + r) / 2, (t + b) / 2)
+    }
+}
 
-    if !output.status.success() {
-        bail!("adb devices failed: {}", String::from_utf8_lossy(&output.stderr));
+/// Bounds of the first node whose text or content-desc contains `text`
+/// (case-insensitive), and whether the hierarchy has any text at all.
+fn hierarchy_text(xml: &str, text: &str) -> (Option<(i32, i32, i32, i32)>, bool) {
+    let query = text.to_lowercase();
+    let mut any_text = false;
+    for node in node_regex().find_iter(xml).map(|m| m.as_str()) {
+        let labels = [node_attr(node, "text"), node_attr(node, "content-desc")];
+        let labels: Vec<String> = labels.into_iter().flatten().filter(|l| !l.is_empty()).map(|l| l.to_lowercase()).collect();
+        any_text |= !labels.is_empty();
+        if labels.iter().any(|l| l.contains(&query)) {
+            let Some(caps) = bounds_regex().captures(node) else { continue };
+            let n = |i: usize| caps[i].parse::<i32>().unwrap_or(0);
+            return (Some((n(1), n(2), n(3), n(4))), true);
+        }
     }
+    (None, any_text)
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut devices = Vec::new();
+/// One recognised word of tesseract TSV output.
+struct OcrWord {
+    line: (u32, u32, u32),
+    bounds: (i32, i32, i32, i32),
+    text: String,
+}
 
-    for line in stdout.lines().skip(1) {
-        if line.trim().is_empty() {
+/// Bounds of `text` (case-insensitive) in tesseract TSV output. Words are
+/// joined per line, so a phrase split over several words still matches;
+/// the bounds cover the words it spans.
+fn ocr_text_bounds(tsv: &str, text: &str) -> Option<(i32, i32, i32, i32)> {
+    let query = text.trim().to_lowercase();
+    if query.is_empty() {
+        return None;
+    }
+    let mut lines: Vec<Vec<OcrWord>> = Vec::new();
+    for row in tsv.lines().skip(1) {
+        let cols: Vec<&str> = row.split('\t').collect();
+        if cols.len() < 12 || cols[0] != "5" || cols[11].trim().is_empty() {
             continue;
         }
-
-        let parts: Vec<&str> = line.split_whitespace().collect();
+        let num = |i: usize| cols[i].trim().parse::<i32>().unwrap_or(0);
+        let word = OcrWord {
+            // (block, paragraph, line)
+            line: (num(2) as u32, num(3) as u32, num(4) as u32),
+            bounds: (num(6), num(7), num(6) + num(8), num(7) + num(9)),
+            text: cols[11].trim().to_lowercase(),
+        };
+        match lines.last_mut() {
+            Some(words) if words[0].line == word.line => words.push(word),
+            _ => lines.push(vec![word]),
+        }
+    }
+    for words in &lines {
+        let mut joined = String::new();
+        let mut spans = Vec::with_capacity(words.len());
+        for w in words {
+            if !joined.is_empty() {
+                joined.push(' ');
+            }
+            spans.push((joined.len(), joined.len() + w.text.len()));
+            joined.push_str(&w.text);
+        }
+        let Some(start) = joined.find(&query) else { continue };
+        let end = start + query.len();
+        let hit: Vec<_> =
+            words.iter().zip(&spans).filter(|(_, (s, e))| *s < end && *e > start).map(|(w, _)| w.bounds).collect();
+        return Some((
+            hit.iter().map(|b| b.0).min()?,
+            hit.iter().map(|b| b.1).min()?,
+            hit.iter().map(|b| b.2).max()?,
+            hit.iter().map(|b| b.3).max()?,
+        ));
+    }
+    None
+}
+
+/// OCR a capture with `tesseract` (on PATH) and look for `text`.
+fn ocr_find_text(text: &str, device: Option<&str>) -> Result<Option<(i32, i32, i32, i32)>> {
+    use std::io::Write;
+    let png = screenshot(device)?;
+    let mut child = Command::new("tesseract")
+        .args(["stdin", "stdout", "tsv"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("The hierarchy has no text (custom-rendered UI) and the OCR fallback needs `tesseract` on PATH")?;
+    child.stdin.take().context("tesseract stdin unavailable")?.write_all(&png)?;
+    let output = child.wait_with_output().context("Failed to run tesseract")?;
+    if !output.status.success() {
+        bail!("tesseract failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(ocr_text_bounds(&String::from_utf8_lossy(&output.stdout), text))
+}
+
+/// Wait until `text` is on screen. The accessibility hierarchy is polled
+/// first; while it has no text at all (canvas, Flutter, games) each poll
+/// OCRs a capture instead. The match says which of the two found it.
+///
+/// A failed dump (common mid-animation) counts as a round without a
+/// hierarchy: that poll tries OCR, and polling goes on until the deadline.
+pub fn wait_for_text(device: Option<&str>, text: &str, timeout: Duration) -> Result<TextMatch> {
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        let ocr = || ocr_find_text(text, device).map(|b| b.map(|bounds| TextMatch { bounds, source: TextSource::Ocr }));
+        let (found, dump_error) = match get_ui_xml(device) {
+            Ok(xml) => {
+                let found = match hierarchy_text(&xml, text) {
+                    (Some(bounds), _) => Some(TextMatch { bounds, source: TextSource::Hierarchy }),
+                    (None, false) => ocr()?,
+                    (None, true) => None,
+                };
+                (found, None)
+            }
+            Err(e) => (ocr().unwrap_or(None), Some(e)),
+        };
+        if let Some(m) = found {
+            return Ok(m);
+        }
+        if std::time::Instant::now() >= deadline {
+            match dump_error {
+                Some(e) => bail!("Text '{}' not found within {}ms (UI dump failed: {:#})", text, timeout.as_millis(), e),
+                None => bail!("Text '{}' not found within {}ms", text, timeout.as_millis()),
+            }
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+}
+
+/// Tap element by text/resource-id
+pub fn tap_element(query: &str, device: Option<&str>) -> Result<()> {
+    if let Some((x, y)) = find_element(query, device)? {
+        tap(x, y, device)?;
+    } else {
+        bail!("Element '{}' not found", query);
+    }
+    Ok(())
+}
+
+/// Tap the centre of the best on-screen match for `template`, for content
+/// with no accessibility nodes (canvas, games, some WebViews). Fails with
+/// the best score when no match reaches `threshold` (0.0-1.0).
+pub fn tap_image(
+    template: &image::DynamicImage,
+    threshold: f32,
+    device: Option<&str>,
+) -> Result<crate::screenshot::TemplateMatch> {
+    let screen = image::load_from_memory(&screenshot(device)?).context("Failed to decode screenshot")?;
+    let found = crate::screenshot::find_template(&screen, template).with_context(|| {
+        format!(
+            "Template ({}x{}) cannot be matched: larger than the {}x{} screen or a single flat colour",
+            template.width(),
+            template.height(),
+            screen.width(),
+            screen.height()
+        )
+    })?;
+    if found.score < threshold {
+        bail!(
+            "No match for template above threshold {:.2} (best {:.3} at {},{})",
+            threshold,
+            found.score,
+            found.x,
+            found.y
+        );
+    }
+    let (x, y) = found.center();
+    tap(x, y, device)?;
+    Ok(found)
+}
+
+// ============== Device Management ==============
+
+#[derive(Serialize)]
+pub struct Device {
+    pub serial: String,
+    pub state: String,
+    pub model: Option<String>,
+}
+
+/// What a device offers, for gating scenarios on their requirements.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceCapabilities {
+    pub sdk: u32,
+    pub width: u32,
+    pub height: u32,
+    /// System features from `pm list features`, e.g. `android.hardware.nfc`.
+    pub features: Vec<String>,
+    pub hardware: HardwareInfo,
+}
+
+pub fn device_capabilities(device: Option<&str>) -> Result<DeviceCapabilities> {
+    let sdk = sdk_level(device)?;
+    let (width, height) = get_screen_size(device)?;
+    let features = device_features(device)?;
+    let hardware = hardware_state(&features, device);
+    Ok(DeviceCapabilities { sdk, width, height, features, hardware })
+}
+
+fn device_features(device: Option<&str>) -> Result<Vec<String>> {
+    let output = adb_exec(device, &["shell", "pm", "list", "features"], None)?;
+    if !output.status.success() {
+        bail!("pm list features failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(parse_feature_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Full feature name: `nfc` is short for `android.hardware.nfc`.
+pub fn feature_name(feature: &str) -> String {
+    if feature.contains("android.") || feature.starts_with("com.") {
+        feature.to_string()
+    } else {
+        format!("android.hardware.{}", feature)
+    }
+}
+
+/// Whether the device declares `feature` (see [`feature_name`]).
+pub fn has_feature(device: Option<&str>, feature: &str) -> Result<bool> {
+    Ok(device_features(device)?.contains(&feature_name(feature)))
+}
+
+/// One piece of hardware: whether the device has it, and whether it is
+/// ready to use (a camera is listed, a biometric enrolled, NFC or location
+/// switched on). `available` is `None` when the state cannot be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct HardwareState {
+    pub present: bool,
+    pub available: Option<bool>,
+}
+
+impl HardwareState {
+    fn new(present: bool, available: Option<bool>) -> Self {
+        Self { present, available: if present { available } else { Some(false) } }
+    }
+
+    /// Present and not known to be unavailable.
+    pub fn usable(&self) -> bool {
+        self.present && self.available != Some(false)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct HardwareInfo {
+    pub camera: HardwareState,
+    pub fingerprint: HardwareState,
+    pub face: HardwareState,
+    pub nfc: HardwareState,
+    pub gps: HardwareState,
+}
+
+impl HardwareInfo {
+    /// Names accepted by [`HardwareInfo::get`] (and scenario `requires.hardware`).
+    pub const NAMES: &'static [&'static str] = &["camera", "fingerprint", "face", "nfc", "gps"];
+
+    pub fn get(&self, name: &str) -> Option<HardwareState> {
+        match name {
+            "camera" => Some(self.camera),
+            "fingerprint" => Some(self.fingerprint),
+            "face" => Some(self.face),
+            "nfc" => Some(self.nfc),
+            "gps" => Some(self.gps),
+            _ => None,
+        }
+    }
+}
+
+/// Camera, biometrics, NFC and GPS: presence from `pm list features`,
+/// availability from the matching dumpsys or setting.
+pub fn hardware_info(device: Option<&str>) -> Result<HardwareInfo> {
+    Ok(hardware_state(&device_features(device)?, device))
+}
+
+fn hardware_state(features: &[String], device: Option<&str>) -> HardwareInfo {
+    let shell = |args: &[&str]| {
+        let mut full = vec!["shell"];
+        full.extend_from_slice(args);
+        adb_exec(device, &full, None)
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+    };
+    let has = |names: &[&str]| names.iter().any(|n| features.iter().any(|f| f == n));
+    let camera = has(&["android.hardware.camera.any", "android.hardware.camera", "android.hardware.camera.front"]);
+    let fingerprint = has(&["android.hardware.fingerprint"]);
+    let face = has(&["android.hardware.biometrics.face"]);
+    let nfc = has(&["android.hardware.nfc"]);
+    let gps = has(&["android.hardware.location.gps"]);
+    // Only ask a service about hardware the device has.
+    let query = |present: bool, args: &[&str], parse: fn(&str) -> Option<bool>| {
+        present.then(|| shell(args)).flatten().and_then(|out| parse(&out))
+    };
+    HardwareInfo {
+        camera: HardwareState::new(camera, query(camera, &["dumpsys", "media.camera"], |o| camera_count(o).map(|n| n > 0))),
+        fingerprint: HardwareState::new(fingerprint, query(fingerprint, &["dumpsys", "fingerprint"], |o| enrolled_count(o).map(|n| n > 0))),
+        face: HardwareState::new(face, query(face, &["dumpsys", "face"], |o| enrolled_count(o).map(|n| n > 0))),
+        nfc: HardwareState::new(nfc, query(nfc, &["dumpsys", "nfc"], nfc_enabled)),
+        gps: HardwareState::new(gps, query(gps, &["settings", "get", "secure", "location_mode"], location_enabled)),
+    }
+}
+
+/// `Number of camera devices: 2` in `dumpsys media.camera`.
+fn camera_count(dumpsys: &str) -> Option<u32> {
+    dumpsys
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("Number of camera devices:"))
+        .and_then(|n| n.trim().parse().ok())
+}
+
+/// Enrolled templates in `dumpsys fingerprint` / `dumpsys face`, which print
+/// `"prints":[{"id":0,"count":1,...}]` per user; `None` if there is no such list.
+fn enrolled_count(dumpsys: &str) -> Option<u32> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r#""count":\s*(\d+)"#).unwrap());
+    dumpsys.contains("\"prints\"").then(|| re.captures_iter(dumpsys).filter_map(|c| c[1].parse::<u32>().ok()).sum())
+}
+
+/// `mState=on` in `dumpsys nfc`.
+fn nfc_enabled(dumpsys: &str) -> Option<bool> {
+    dumpsys
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("mState="))
+        .map(|state| state.trim().eq_ignore_ascii_case("on"))
+}
+
+/// `location_mode` is 0 when location is off.
+fn location_enabled(mode: &str) -> Option<bool> {
+    mode.trim().parse::<u32>().ok().map(|m| m != 0)
+}
+
+/// `feature:android.hardware.nfc` lines (some carry `=<version>`) -> names.
+fn parse_feature_list(out: &str) -> Vec<String> {
+    out.lines()
+        .filter_map(|l| l.trim().strip_prefix("feature:"))
+        .map(|f| f.split('=').next().unwrap_or(f).to_string())
+        .collect()
+}
+
+/// The device did not come back within the reconnect timeout.
+///
+/// Callers can `downcast_ref::<DeviceLost>()` to tell a lost device from an
+/// ordinary command failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceLost {
+    pub serial: Option<String>,
+    pub waited: Duration,
+}
+
+impl std::fmt::Display for DeviceLost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Device {} did not come back within {}s",
+            self.serial.as_deref().unwrap_or("(default)"),
+            self.waited.as_secs()
+        )
+    }
+}
+
+impl std::error::Error for DeviceLost {}
+
+/// `host:port` serials are adb-over-network devices that need `adb connect`.
+fn is_network_serial(serial: &str) -> bool {
+    serial.rsplit_once(':').is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+}
+
+/// Connected, online and finished booting.
+fn device_ready(device: Option<&str>) -> bool {
+    let online = adb_exec(device, &["get-state"], None)
+        .is_ok_and(|o| String::from_utf8_lossy(&o.stdout).trim() == "device");
+    online && getprop("sys.boot_completed", device).is_ok_and(|v| v == "1")
+}
+
+/// Wait until the device is back online and booted, trying to reconnect
+/// while it is missing (`adb -s <serial> reconnect`, `adb connect` for
+/// network serials). With `wake`, the screen is woken once it is back.
+///
+/// Returns immediately when the device is already there; after `timeout`
+/// the error is a [`DeviceLost`].
+pub fn wait_for_device(device: Option<&str>, timeout: Duration, wake: bool) -> Result<()> {
+    let start = std::time::Instant::now();
+    let mut announced = false;
+    while !device_ready(device) {
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            return Err(DeviceLost { serial: device.map(str::to_string), waited: timeout }.into());
+        }
+        if !announced {
+            eprintln!("Device {} unavailable, waiting up to {}s…", device.unwrap_or("(default)"), timeout.as_secs());
+            announced = true;
+        }
+        // Only this device: other offline devices on the host are left alone.
+        // Without a serial there is only the default device to reset.
+        let _ = match device {
+            Some(_) => adb_cmd(device).arg("reconnect").output(),
+            None => Command::new("adb").args(["reconnect", "offline"]).output(),
+        };
+        if let Some(serial) = device.filter(|s| is_network_serial(s)) {
+            let _ = Command::new("adb").args(["connect", serial]).output();
+        }
+        // `wait-for-device` blocks until adb sees the device; give it a slice
+        // of the remaining time, then go round the reconnect loop again.
+        let mut child = adb_cmd(device)
+            .arg("wait-for-device")
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .context("Failed to execute adb wait-for-device")?;
+        let slice_end = std::time::Instant::now() + Duration::from_secs(5).min(timeout - elapsed);
+        while child.try_wait()?.is_none() && std::time::Instant::now() < slice_end {
+            std::thread::sleep(Duration::from_millis(200));
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+        if device_ready(device) {
+            break;
+        }
+        std::thread::sleep(Duration::from_secs(1).min(timeout.saturating_sub(start.elapsed())));
+    }
+    if announced {
+        eprintln!("Device back after {}s", start.elapsed().as_secs());
+        if wake {
+            adb_exec(device, &["shell", "input", "keyevent", "KEYCODE_WAKEUP"], None)?;
+        }
+    }
+    Ok(())
+}
+
+/// Why an `adb connect` / `pair` / `disconnect` did not do its job.
+///
+/// Callers can `downcast_ref::<AdbNetworkError>()`; `AlreadyConnected` is
+/// usually fine to treat as success.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdbNetworkError {
+    AlreadyConnected(String),
+    UnableToConnect { target: String, reason: String },
+    PairingFailed { target: String, reason: String },
+    NotConnected(String),
+}
+
+impl std::fmt::Display for AdbNetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdbNetworkError::AlreadyConnected(t) => write!(f, "Already connected to {}", t),
+            AdbNetworkError::UnableToConnect { target, reason } => write!(f, "Unable to connect to {}: {}", target, reason),
+            AdbNetworkError::PairingFailed { target, reason } => write!(f, "Pairing with {} failed: {}", target, reason),
+            AdbNetworkError::NotConnected(t) => write!(f, "{} is not connected", t),
+        }
+    }
+}
+
+impl std::error::Error for AdbNetworkError {}
+
+/// `host:port`, after checking the host is a plain name or IP address.
+fn network_target(host: &str, port: u16) -> Result<String> {
+    let bare = host.trim_start_matches('[').trim_end_matches(']');
+    if bare.is_empty() || !bare.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':')) {
+        bail!("Invalid host '{}'", host);
+    }
+    // IPv6 literals need brackets so the port stays unambiguous.
+    Ok(if bare.contains(':') { format!("[{}]:{}", bare, port) } else { format!("{}:{}", bare, port) })
+}
+
+/// Run an adb host command and return stdout and stderr together: adb
+/// prints some failures on stdout and exits 0 anyway.
+fn adb_host_output(args: &[&str]) -> Result<String> {
+    let output = adb_exec(None, args, None)?;
+    Ok(format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr)))
+}
+
+fn parse_connect_output(target: &str, out: &str) -> Result<(), AdbNetworkError> {
+    let text = out.trim();
+    let lower = text.to_lowercase();
+    if lower.starts_with("already connected") {
+        Err(AdbNetworkError::AlreadyConnected(target.to_string()))
+    } else if lower.starts_with("connected to") {
+        Ok(())
+    } else {
+        // "failed to connect to '…': Connection refused", "cannot connect to …: No route to host (113)",
+        // "failed to authenticate to …"
+        let reason = text.rsplit_once(": ").map_or(text, |(_, r)| r).trim();
+        let reason = if reason.is_empty() { "no response from adb" } else { reason };
+        Err(AdbNetworkError::UnableToConnect { target: target.to_string(), reason: reason.to_string() })
+    }
+}
+
+fn parse_pair_output(target: &str, out: &str) -> Result<(), AdbNetworkError> {
+    let text = out.trim();
+    if text.starts_with("Successfully paired") {
+        return Ok(());
+    }
+    // "Failed: Wrong password or connection was dropped."
+    let reason = text.strip_prefix("Failed:").unwrap_or(text).trim().trim_start_matches("error:").trim();
+    let reason = if reason.is_empty() { "no response from adb" } else { reason };
+    Err(AdbNetworkError::PairingFailed { target: target.to_string(), reason: reason.to_string() })
+}
+
+fn parse_disconnect_output(target: &str, out: &str) -> Result<(), AdbNetworkError> {
+    if out.trim().starts_with("disconnected") {
+        Ok(())
+    } else {
+        // "error: no such device '…'"
+        Err(AdbNetworkError::NotConnected(target.to_string()))
+    }
+}
+
+/// `adb connect host:port` for a device with network debugging enabled.
+pub fn connect(host: &str, port: u16) -> Result<String> {
+    let target = network_target(host, port)?;
+    parse_connect_output(&target, &adb_host_output(&["connect", &target])?)?;
+    Ok(target)
+}
+
+/// `adb disconnect host:port`.
+pub fn disconnect(host: &str, port: u16) -> Result<String> {
+    let target = network_target(host, port)?;
+    parse_disconnect_output(&target, &adb_host_output(&["disconnect", &target])?)?;
+    Ok(target)
+}
+
+/// Android 11+ wireless debugging: pair with the six-digit code shown in
+/// *Developer options → Wireless debugging → Pair device with pairing
+/// code*. The pairing port differs from the one used to connect afterwards.
+pub fn pair(host: &str, port: u16, code: &str) -> Result<String> {
+    if code.is_empty() || !code.chars().all(|c| c.is_ascii_digit()) {
+        bail!("Pairing code must be digits, got '{}'", code);
+    }
+    let target = network_target(host, port)?;
+    parse_pair_output(&target, &adb_host_output(&["pair", &target, code])?)?;
+    Ok(target)
+}
+
+/// Which way a [`PortMapping`] carries connections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortDirection {
+    /// `adb forward`: host `local` → device `remote`.
+    Forward,
+    /// `adb reverse`: device `remote` → host `local`.
+    Reverse,
+}
+
+impl PortDirection {
+    fn command(self) -> &'static str {
+        match self {
+            PortDirection::Forward => "forward",
+            PortDirection::Reverse => "reverse",
+        }
+    }
+}
+
+/// Why an `adb forward` / `adb reverse` did not take.
+///
+/// `CannotBind` means the listening side's port is taken; `spec` is that side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdbPortError {
+    CannotBind { spec: String, reason: String },
+    Failed(String),
+}
+
+impl std::fmt::Display for AdbPortError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdbPortError::CannotBind { spec, reason } => write!(f, "Cannot bind {}: {}", spec, reason),
+            AdbPortError::Failed(reason) => write!(f, "adb port mapping failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for AdbPortError {}
+
+/// An established `adb forward` or `adb reverse`, removed again when
+/// dropped; call [`PortMapping::keep`] to leave it in place.
+#[derive(Debug)]
+pub struct PortMapping {
+    device: Option<String>,
+    direction: PortDirection,
+    local: String,
+    remote: String,
+    active: bool,
+}
+
+impl PortMapping {
+    /// Host side, e.g. `tcp:8080`; a requested `tcp:0` holds the port adb picked.
+    pub fn local(&self) -> &str {
+        &self.local
+    }
+
+    /// Device side, e.g. `tcp:8080`; a requested `tcp:0` holds the port adb picked.
+    pub fn remote(&self) -> &str {
+        &self.remote
+    }
+
+    /// Keep the mapping after the guard goes away.
+    pub fn keep(mut self) {
+        self.active = false;
+    }
+}
+
+impl Drop for PortMapping {
+    fn drop(&mut self) {
+        if !self.active {
+            return;
+        }
+        let listener = match self.direction {
+            PortDirection::Forward => &self.local,
+            PortDirection::Reverse => &self.remote,
+        };
+        if let Err(e) = remove_port_mapping(self.direction, listener, self.device.as_deref()) {
+            eprintln!("Warning: failed to remove adb {} {}: {:#}", self.direction.command(), listener, e);
+        }
+    }
+}
+
+/// Check an adb socket spec (`tcp:8080`, `localabstract:name`, `jdwp:1234`);
+/// `tcp` ports must be numbers.
+fn validate_socket_spec(spec: &str) -> Result<()> {
+    let valid = match spec.split_once(':') {
+        Some(("tcp", port)) => port.parse::<u16>().is_ok(),
+        Some((kind, name)) => {
+            !kind.is_empty()
+                && kind.chars().all(|c| c.is_ascii_lowercase())
+                && !name.is_empty()
+                && !name.chars().any(|c| c.is_whitespace() || c.is_control())
+        }
+        None => false,
+    };
+    if !valid {
+        bail!("Invalid adb socket spec '{}' (expected e.g. tcp:8080 or localabstract:name)", spec);
+    }
+    Ok(())
+}
+
+/// `adb forward`/`reverse` print nothing on success, or the chosen port for
+/// `tcp:0`; failures go to stderr with a non-zero exit.
+fn parse_port_output(listener: &str, success: bool, out: &str) -> Result<Option<u16>, AdbPortError> {
+    let text = out.trim();
+    if success {
+        return Ok(text.lines().last().and_then(|l| l.trim().parse().ok()));
+    }
+    // "adb: error: cannot bind listener: Address already in use",
+    // "adb: error: cannot bind listener: cannot bind to 127.0.0.1:8080: Address already in use (98)"
+    let message = text.trim_start_matches("adb: ").trim_start_matches("error: ").trim();
+    if message.contains("cannot bind") {
+        let reason = message.rsplit_once(": ").map_or(message, |(_, r)| r).trim();
+        return Err(AdbPortError::CannotBind { spec: listener.to_string(), reason: reason.to_string() });
+    }
+    let reason = if message.is_empty() { "no response from adb" } else { message };
+    Err(AdbPortError::Failed(reason.to_string()))
+}
+
+fn establish_port_mapping(direction: PortDirection, local: &str, remote: &str, device: Option<&str>) -> Result<PortMapping> {
+    validate_socket_spec(local)?;
+    validate_socket_spec(remote)?;
+    let (listener, target) = match direction {
+        PortDirection::Forward => (local, remote),
+        PortDirection::Reverse => (remote, local),
+    };
+    let output = adb_exec(device, &[direction.command(), listener, target], None)?;
+    let out = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    let chosen = parse_port_output(listener, output.status.success(), &out)?;
+    let listener = match chosen {
+        Some(port) if listener == "tcp:0" => format!("tcp:{}", port),
+        _ => listener.to_string(),
+    };
+    let (local, remote) = match direction {
+        PortDirection::Forward => (listener, remote.to_string()),
+        PortDirection::Reverse => (local.to_string(), listener),
+    };
+    Ok(PortMapping { device: device.map(str::to_string), direction, local, remote, active: true })
+}
+
+/// `adb forward local remote`: connections to `local` on the host reach
+/// `remote` on the device, e.g. an on-device debug server.
+pub fn forward(local: &str, remote: &str, device: Option<&str>) -> Result<PortMapping> {
+    establish_port_mapping(PortDirection::Forward, local, remote, device)
+}
+
+/// `adb reverse remote local`: connections to `remote` on the device reach
+/// `local` on the host, e.g. a mock server at `tcp:8080`.
+pub fn reverse(remote: &str, local: &str, device: Option<&str>) -> Result<PortMapping> {
+    establish_port_mapping(PortDirection::Reverse, local, remote, device)
+}
+
+/// `adb forward --remove` / `adb reverse --remove` for the mapping listening
+/// on `listener` (host side for forward, device side for reverse).
+pub fn remove_port_mapping(direction: PortDirection, listener: &str, device: Option<&str>) -> Result<()> {
+    validate_socket_spec(listener)?;
+    let output = adb_exec(device, &[direction.command(), "--remove", listener], None)?;
+    if !output.status.success() {
+        // "adb: error: listener 'tcp:8080' not found"
+        let err = String::from_utf8_lossy(&output.stderr);
+        bail!("adb {} --remove {} failed: {}", direction.command(), listener, err.trim().trim_start_matches("adb: "));
+    }
+    Ok(())
+}
+
+/// List connected devices
+pub fn list_devices() -> Result<Vec<Device>> {
+    let output = Command::new("adb")
+        .args(["devices", "-l"])
+        .output()
+        .context("Failed to execute adb devices")?;
+
+    if !output.status.success() {
+        bail!("adb devices failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut devices = Vec::new();
+
+    for line in stdout.lines().skip(1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
         if parts.len() >= 2 {
             let model = parts.iter()
                 .find(|p| p.starts_with("model:"))
@@ -651,7 +2211,7 @@ pub fn list_apps(filter: Option<&str>, device: Option<&str>) -> Result<()> {
         .lines()
         .filter_map(|line| line.strip_prefix("package:"))
         .filter(|pkg| {
-            filter.map_or(true, |f| pkg.to_lowercase().contains(&f.to_lowercase()))
+            filter.is_none_or(|f| pkg.to_lowercase().contains(&f.to_lowercase()))
         })
         .map(|s| s.to_string())
         .collect();
@@ -761,32 +2321,322 @@ pub fn uninstall_app(package: &str, device: Option<&str>) -> Result<()> {
     Ok(())
 }
 
-// ============== System Commands ==============
-
-/// Clear device logs
-pub fn clear_logs(device: Option<&str>) -> Result<()> {
-    let output = adb_exec(device, &["logcat", "-c"], None)?;
-
+/// Installed `versionName` of a package, or `None` when it is not installed.
+pub fn installed_version(package: &str, device: Option<&str>) -> Result<Option<String>> {
+    let cmd = DeviceShellCmd::new()
+        .literal("dumpsys")
+        .literal("package")
+        .validated(package, validate_package_name)?
+        .render();
+    let output = adb_exec(device, &["shell", &cmd], None)?;
     if !output.status.success() {
-        bail!("logcat clear failed: {}", String::from_utf8_lossy(&output.stderr));
+        bail!("dumpsys package failed: {}", String::from_utf8_lossy(&output.stderr));
     }
+    Ok(parse_version_name(&String::from_utf8_lossy(&output.stdout), package))
+}
 
-    println!("Logs cleared");
-    Ok(())
+/// `versionName` from the `Package [<package>] (<hash>):` block of
+/// `dumpsys package`. Other sections can mention the package too, so only
+/// the block header counts.
+fn parse_version_name(dumpsys: &str, package: &str) -> Option<String> {
+    let header = format!("Package [{}] (", package);
+    dumpsys
+        .lines()
+        .skip_while(|l| !l.trim().starts_with(&header))
+        .skip(1)
+        .take_while(|l| !l.trim().starts_with("Package ["))
+        .find_map(|l| l.trim().strip_prefix("versionName="))
+        .map(str::to_string)
 }
 
-/// Get system info (battery, memory)
-pub fn get_system_info(device: Option<&str>) -> Result<()> {
-    let battery = adb_exec(device, &["shell", "dumpsys", "battery"], None)?;
-    let battery_out = String::from_utf8_lossy(&battery.stdout);
+/// Make sure `package` is installed, without sideloading, and return its
+/// installed version.
+///
+/// Tries `cmd package install-existing` first (apps preinstalled on the image
+/// or installed for another user), then opens the Play Store listing, taps
+/// "Install" and waits up to `timeout` for the package to appear. The store
+/// only serves the latest release, so a `version` that differs from it fails
+/// after the install rather than waiting forever.
+pub fn ensure_installed(
+    package: &str,
+    version: Option<&str>,
+    timeout: Duration,
+    device: Option<&str>,
+) -> Result<String> {
+    validate_package_name(package)?;
+    let satisfied = |installed: &Option<String>| match (installed, version) {
+        (Some(have), Some(want)) => have == want,
+        (Some(_), None) => true,
+        (None, _) => false,
+    };
 
-    let mut battery_level = "unknown".to_string();
-    let mut battery_status = "unknown".to_string();
-    for line in battery_out.lines() {
-        if line.contains("level:") {
-            battery_level = line.split(':').nth(1).unwrap_or("").trim().to_string();
-        }
-        if line.contains("status:") {
+    let installed = installed_version(package, device)?;
+    if satisfied(&installed) {
+        return Ok(installed.unwrap_or_default());
+    }
+
+    if installed.is_none() {
+        let cmd = DeviceShellCmd::new()
+            .literal("cmd")
+            .literal("package")
+            .literal("install-existing")
+            .validated(package, validate_package_name)?
+            .render();
+        adb_exec(device, &["shell", &cmd], None)?;
+        let installed = installed_version(package, device)?;
+        if satisfied(&installed) {
+            println!("Installed {} from the system image", package);
+            return Ok(installed.unwrap_or_default());
+        }
+    }
+
+    let uri = format!("market://details?id={}", package);
+    let output = adb_exec(device, &[
+        "shell", "am", "start", "-a", "android.intent.action.VIEW",
+        "-d", &uri, "-p", "com.android.vending",
+    ], None)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() || stdout.contains("Error:") {
+        bail!("Cannot open Play Store for {}: {}", package, stdout.trim());
+    }
+    println!("Opened Play Store listing for {}, waiting for install…", package);
+
+    let start = std::time::Instant::now();
+    let mut tapped = false;
+    while start.elapsed() < timeout {
+        std::thread::sleep(Duration::from_secs(2));
+        // The button reads "Update" when an older version is already there.
+        if !tapped {
+            let button = get_ui_xml(device)
+                .ok()
+                .and_then(|xml| find_button(&xml, &["Install", "Update"]));
+            if let Some((x, y)) = button {
+                tap(x, y, device)?;
+                tapped = true;
+            }
+        }
+        let now = installed_version(package, device)?;
+        if now != installed && now.is_some() {
+            let have = now.unwrap_or_default();
+            if let Some(want) = version.filter(|want| *want != have) {
+                bail!("Play Store installed {} {}, but {} was required", package, have, want);
+            }
+            return Ok(have);
+        }
+    }
+    bail!(
+        "Timed out after {}s waiting for {} to install from the Play Store{}",
+        timeout.as_secs(),
+        package,
+        if tapped { "" } else { " (no Install button found; is an account signed in?)" }
+    )
+}
+
+/// Center of the first clickable Button whose whole text or content-desc
+/// equals one of `labels` (case-insensitively), tried in `labels` order.
+/// Unlike `find_element` this will not take "Uninstall" for "Install".
+fn find_button(xml: &str, labels: &[&str]) -> Option<(i32, i32)> {
+    let buttons: Vec<UiElement> = parse_ui_elements(xml)
+        .into_iter()
+        .filter(|el| el.clickable && el.class.ends_with("Button"))
+        .collect();
+    labels.iter().find_map(|label| {
+        buttons
+            .iter()
+            .find(|el| {
+                el.text.trim().eq_ignore_ascii_case(label)
+                    || el.content_desc.trim().eq_ignore_ascii_case(label)
+            })
+            .map(UiElement::center)
+    })
+}
+
+// ============== System Commands ==============
+
+fn sdk_level(device: Option<&str>) -> Result<u32> {
+    getprop("ro.build.version.sdk", device)?
+        .parse()
+        .context("Could not read ro.build.version.sdk")
+}
+
+/// Force the device into (or out of) deep Doze and return the resulting
+/// `deviceidle` deep state, e.g. `IDLE` or `ACTIVE`.
+///
+/// Doze only engages on battery, so turning it on also makes the device
+/// report itself unplugged; turning it off resets the battery state.
+/// Needs Android 6.0 (API 23) or newer.
+pub fn set_doze(on: bool, device: Option<&str>) -> Result<String> {
+    let sdk = sdk_level(device)?;
+    if sdk < 23 {
+        bail!("Doze needs Android 6.0 (API 23) or newer; device is API {}", sdk);
+    }
+    let steps: &[&[&str]] = if on {
+        &[&["dumpsys", "battery", "unplug"], &["dumpsys", "deviceidle", "force-idle"]]
+    } else {
+        &[&["dumpsys", "deviceidle", "unforce"], &["dumpsys", "battery", "reset"]]
+    };
+    for args in steps {
+        let output = adb_exec(device, &[&["shell"], *args].concat(), None)?;
+        let out = String::from_utf8_lossy(&output.stdout);
+        if !output.status.success() || out.contains("Unknown command") {
+            bail!("`{}` failed: {}{}", args.join(" "), out.trim(), String::from_utf8_lossy(&output.stderr).trim());
+        }
+    }
+    let output = adb_exec(device, &["shell", "dumpsys", "deviceidle", "get", "deep"], None)?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Do Not Disturb interruption filter, as `settings get global zen_mode` reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DndMode {
+    /// Everything interrupts (DND off).
+    Off,
+    /// Only priority notifications, alarms and calls.
+    Priority,
+    /// Only alarms.
+    Alarms,
+    /// Nothing interrupts, alarms included.
+    Silence,
+}
+
+impl DndMode {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "off" => Ok(DndMode::Off),
+            "priority" => Ok(DndMode::Priority),
+            "alarms" => Ok(DndMode::Alarms),
+            "none" => Ok(DndMode::Silence),
+            other => bail!("Unknown DND mode '{}'. Supported: off, priority, alarms, none", other),
+        }
+    }
+
+    /// The `cmd notification set_dnd` argument; also how the mode is printed.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DndMode::Off => "off",
+            DndMode::Priority => "priority",
+            DndMode::Alarms => "alarms",
+            DndMode::Silence => "none",
+        }
+    }
+
+    /// `Settings.Global.ZEN_MODE_*`: 0 off, 1 priority, 2 no interruptions, 3 alarms.
+    fn from_zen_mode(value: &str) -> Option<Self> {
+        match value.trim() {
+            "0" => Some(DndMode::Off),
+            "1" => Some(DndMode::Priority),
+            "2" => Some(DndMode::Silence),
+            "3" => Some(DndMode::Alarms),
+            _ => None,
+        }
+    }
+}
+
+/// Current Do Not Disturb mode.
+pub fn get_dnd(device: Option<&str>) -> Result<DndMode> {
+    let output = adb_exec(device, &["shell", "settings", "get", "global", "zen_mode"], None)?;
+    let value = String::from_utf8_lossy(&output.stdout);
+    DndMode::from_zen_mode(&value).with_context(|| format!("Unexpected zen_mode '{}'", value.trim()))
+}
+
+/// Switch Do Not Disturb with `cmd notification set_dnd` and return the
+/// mode read back. Needs Android 9 (API 28) or newer.
+pub fn set_dnd(mode: DndMode, device: Option<&str>) -> Result<DndMode> {
+    let sdk = sdk_level(device)?;
+    if sdk < 28 {
+        bail!("Setting DND needs Android 9 (API 28) or newer; device is API {}", sdk);
+    }
+    let output = adb_exec(device, &["shell", "cmd", "notification", "set_dnd", mode.as_str()], None)?;
+    let out = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() || out.contains("Unknown command") || out.contains("No shell command") {
+        bail!("`cmd notification set_dnd` failed: {}{}", out.trim(), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    let applied = get_dnd(device)?;
+    if applied != mode {
+        bail!("DND did not switch: asked for {}, device reports {}", mode.as_str(), applied.as_str());
+    }
+    Ok(applied)
+}
+
+/// App standby buckets and the numbers `am get-standby-bucket` prints.
+const STANDBY_BUCKETS: &[(&str, u32)] = &[
+    ("active", 10),
+    ("working_set", 20),
+    ("frequent", 30),
+    ("rare", 40),
+    ("restricted", 45),
+    ("never", 50),
+];
+
+fn standby_bucket_name(value: &str) -> String {
+    let value = value.trim();
+    value
+        .parse::<u32>()
+        .ok()
+        .and_then(|n| STANDBY_BUCKETS.iter().find(|(_, v)| *v == n))
+        .map_or_else(|| value.to_string(), |(name, _)| name.to_string())
+}
+
+/// Put `package` in an app-standby bucket and return the bucket it ended up
+/// in. Needs Android 9 (API 28); `restricted` needs Android 11 (API 30).
+pub fn set_standby_bucket(package: &str, bucket: &str, device: Option<&str>) -> Result<String> {
+    if !STANDBY_BUCKETS.iter().any(|(name, _)| *name == bucket) || bucket == "never" {
+        bail!("Unknown standby bucket '{}'. Supported: active, working_set, frequent, rare, restricted", bucket);
+    }
+    let sdk = sdk_level(device)?;
+    if sdk < 28 || (bucket == "restricted" && sdk < 30) {
+        bail!(
+            "Standby bucket '{}' needs Android {}; device is API {}",
+            bucket,
+            if bucket == "restricted" { "11 (API 30)" } else { "9 (API 28)" },
+            sdk
+        );
+    }
+    let cmd = DeviceShellCmd::new()
+        .literal("am")
+        .literal("set-standby-bucket")
+        .validated(package, validate_package_name)?
+        .raw_trusted(bucket.to_string())
+        .render();
+    let output = adb_exec(device, &["shell", &cmd], None)?;
+    let out = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() || out.contains("Exception") || out.contains("Error") {
+        bail!("am set-standby-bucket failed: {}{}", out.trim(), String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    let cmd = DeviceShellCmd::new()
+        .literal("am")
+        .literal("get-standby-bucket")
+        .validated(package, validate_package_name)?
+        .render();
+    let output = adb_exec(device, &["shell", &cmd], None)?;
+    Ok(standby_bucket_name(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Clear device logs
+pub fn clear_logs(device: Option<&str>) -> Result<()> {
+    let output = adb_exec(device, &["logcat", "-c"], None)?;
+
+    if !output.status.success() {
+        bail!("logcat clear failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    println!("Logs cleared");
+    Ok(())
+}
+
+/// Get system info (battery, memory)
+pub fn get_system_info(device: Option<&str>) -> Result<()> {
+    let battery = adb_exec(device, &["shell", "dumpsys", "battery"], None)?;
+    let battery_out = String::from_utf8_lossy(&battery.stdout);
+
+    let mut battery_level = "unknown".to_string();
+    let mut battery_status = "unknown".to_string();
+    for line in battery_out.lines() {
+        if line.contains("level:") {
+            battery_level = line.split(':').nth(1).unwrap_or("").trim().to_string();
+        }
+        if line.contains("status:") {
             let status_code = line.split(':').nth(1).unwrap_or("").trim();
             battery_status = match status_code {
                 "1" => "Unknown",
@@ -833,6 +2683,21 @@ pub fn get_current_activity(device: Option<&str>) -> Result<()> {
     Ok(())
 }
 
+/// Package owning the focused window, from `dumpsys window`.
+fn parse_focused_package(window_dump: &str) -> Option<String> {
+    // mCurrentFocus=Window{3c1a9e u0 com.example.app/com.example.app.MainActivity}
+    let line = window_dump.lines().find(|l| l.contains("mCurrentFocus="))?;
+    let component = line.rsplit(' ').next()?.trim_end_matches('}');
+    let package = component.split('/').next()?;
+    (component.contains('/') && validate_package_name(package).is_ok()).then(|| package.to_string())
+}
+
+/// Package of the app in the foreground, if a normal app window has focus.
+pub fn foreground_package(device: Option<&str>) -> Result<Option<String>> {
+    let output = adb_exec(device, &["shell", "dumpsys", "window"], None)?;
+    Ok(parse_focused_package(&String::from_utf8_lossy(&output.stdout)))
+}
+
 /// Get device logs
 pub fn get_logs(filter: Option<&str>, lines: usize, device: Option<&str>) -> Result<()> {
     let lines_str = lines.to_string();
@@ -853,6 +2718,48 @@ pub fn get_logs(filter: Option<&str>, lines: usize, device: Option<&str>) -> Res
     Ok(())
 }
 
+/// Full `adb bugreport` zip (Android 7+). Takes a minute or more and tens
+/// of megabytes, so callers should make it opt-in.
+pub fn capture_bugreport(device: Option<&str>, out_path: &str) -> Result<()> {
+    let output = adb_exec(device, &["bugreport", out_path], None)?;
+    if !output.status.success() {
+        bail!("adb bugreport failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(())
+}
+
+/// Logcat lines kept in a diagnostics bundle.
+const DIAGNOSTICS_LOG_LINES: &str = "2000";
+
+/// Zip recent logcat, `dumpsys activity activities`, the UI hierarchy and a
+/// screenshot into `out_path`. A part that cannot be captured (e.g. no
+/// hierarchy while a dialog animates) is replaced by a `<part>.error.txt`
+/// entry rather than failing the whole bundle.
+pub fn capture_diagnostics(device: Option<&str>, out_path: &std::path::Path) -> Result<()> {
+    let text = |args: &[&str]| -> Result<Vec<u8>> {
+        let output = adb_exec(device, args, None)?;
+        if !output.status.success() {
+            bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(output.stdout)
+    };
+    let parts: Vec<(&str, Result<Vec<u8>>)> = vec![
+        ("logcat.txt", text(&["logcat", "-d", "-v", "threadtime", "-t", DIAGNOSTICS_LOG_LINES])),
+        ("activity.txt", text(&["shell", "dumpsys", "activity", "activities"])),
+        ("window.txt", text(&["shell", "dumpsys", "window", "windows"])),
+        ("hierarchy.xml", get_ui_xml(device).map(String::into_bytes)),
+        ("screenshot.png", screenshot(device)),
+    ];
+    let entries: Vec<(String, Vec<u8>)> = parts
+        .into_iter()
+        .map(|(name, data)| match data {
+            Ok(bytes) => (name.to_string(), bytes),
+            Err(e) => (format!("{}.error.txt", name), format!("{:#}\n", e).into_bytes()),
+        })
+        .collect();
+    crate::utils::zip::write_stored_zip(out_path, &entries)
+}
+
 /// Reboot device
 pub fn reboot(device: Option<&str>) -> Result<()> {
     println!("Rebooting device...");
@@ -868,12 +2775,7 @@ pub fn reboot(device: Option<&str>) -> Result<()> {
 
 /// Turn screen on/off
 pub fn screen_power(on: bool, device: Option<&str>) -> Result<()> {
-    // First check screen state
-    let output = adb_exec(device, &["shell", "dumpsys", "power"], None)?;
-    let power_out = String::from_utf8_lossy(&output.stdout);
-
-    let is_screen_on = power_out.contains("mWakefulness=Awake") ||
-                       power_out.contains("Display Power: state=ON");
+    let is_screen_on = is_screen_on(device)?;
 
     if on && !is_screen_on {
         // Turn screen on
@@ -890,6 +2792,190 @@ pub fn screen_power(on: bool, device: Option<&str>) -> Result<()> {
     Ok(())
 }
 
+fn parse_screen_on(power_dump: &str) -> bool {
+    power_dump.contains("mWakefulness=Awake") || power_dump.contains("Display Power: state=ON")
+}
+
+/// Whether the display is on, from `dumpsys power`.
+pub fn is_screen_on(device: Option<&str>) -> Result<bool> {
+    let output = adb_exec(device, &["shell", "dumpsys", "power"], None)?;
+    Ok(parse_screen_on(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Turn the display on if it is off and wait until power reports it awake.
+pub fn wake(device: Option<&str>) -> Result<()> {
+    if is_screen_on(device)? {
+        return Ok(());
+    }
+    adb_exec(device, &["shell", "input", "keyevent", "KEYCODE_WAKEUP"], None)?;
+    for _ in 0..10 {
+        std::thread::sleep(Duration::from_millis(200));
+        if is_screen_on(device)? {
+            return Ok(());
+        }
+    }
+    bail!("Screen did not turn on after KEYCODE_WAKEUP")
+}
+
+/// Whether the keyguard is up, from the flags `dumpsys window` prints (their
+/// names vary by release). `None` when none of them appear.
+fn parse_keyguard_showing(window_dump: &str) -> Option<bool> {
+    const FLAGS: &[&str] =
+        &["mShowingLockscreen=", "mDreamingLockscreen=", "isStatusBarKeyguard=", "mKeyguardShowing=", "keyguardShowing="];
+    let mut seen = None;
+    for word in window_dump.split_whitespace() {
+        for flag in FLAGS {
+            if let Some(value) = word.strip_prefix(flag) {
+                let showing = value.trim_end_matches(',') == "true";
+                seen = Some(seen.unwrap_or(false) || showing);
+            }
+        }
+    }
+    seen
+}
+
+pub fn is_locked(device: Option<&str>) -> Result<bool> {
+    let output = adb_exec(device, &["shell", "dumpsys", "window"], None)?;
+    let dump = String::from_utf8_lossy(&output.stdout);
+    parse_keyguard_showing(&dump).context("Could not read the keyguard state from dumpsys window")
+}
+
+/// Secure lock configured for the current user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockType {
+    /// No lock or swipe-to-unlock.
+    None,
+    Pin,
+    Password,
+    Pattern,
+    Unknown,
+}
+
+/// `CredentialType: PIN` (Android 10+) or the DevicePolicyManager password
+/// quality (`Quality: 131072`) on older releases.
+fn parse_lock_type(lock_dump: &str) -> LockType {
+    for line in lock_dump.lines().map(str::trim) {
+        if let Some(kind) = line.strip_prefix("CredentialType:") {
+            return match kind.trim().to_uppercase().as_str() {
+                "NONE" => LockType::None,
+                "PIN" => LockType::Pin,
+                "PASSWORD" | "PASSWORD_OR_PIN" => LockType::Password,
+                "PATTERN" => LockType::Pattern,
+                _ => LockType::Unknown,
+            };
+        }
+        if let Some(quality) = line.strip_prefix("Quality:") {
+            return match quality.trim().parse::<u32>() {
+                Ok(0) => LockType::None,
+                Ok(0x10000) => LockType::Pattern,
+                Ok(0x20000 | 0x30000) => LockType::Pin,
+                Ok(0x40000..=0x60000) => LockType::Password,
+                _ => LockType::Unknown,
+            };
+        }
+    }
+    LockType::Unknown
+}
+
+fn validate_lock_secret(pin: &str) -> Result<()> {
+    if pin.len() < 4 || pin.len() > 16 || !pin.chars().all(|c| c.is_ascii_alphanumeric()) {
+        bail!("Unlock PIN/password must be 4-16 ASCII letters or digits");
+    }
+    Ok(())
+}
+
+/// Wake the device and dismiss the keyguard, entering `pin` for a PIN or
+/// password lock.
+///
+/// Returns `false` without touching the screen when the device was already
+/// unlocked. Pattern, biometric-only and other complex locks cannot be
+/// dismissed over adb and fail with an error saying so.
+pub fn unlock(pin: Option<&str>, device: Option<&str>) -> Result<bool> {
+    wake(device)?;
+    if !is_locked(device)? {
+        return Ok(false);
+    }
+
+    let output = adb_exec(device, &["shell", "dumpsys", "lock_settings"], None)?;
+    let lock = parse_lock_type(&String::from_utf8_lossy(&output.stdout));
+    match (lock, pin) {
+        (LockType::Pattern, _) => bail!("Device uses a pattern lock, which cannot be unlocked automatically; switch it to a PIN or none"),
+        (LockType::Pin | LockType::Password, None) => bail!("Device has a PIN/password lock; pass the PIN to unlock it"),
+        _ => {}
+    }
+
+    // Insecure keyguards go away with this alone (Android 8+).
+    adb_exec(device, &["shell", "wm", "dismiss-keyguard"], None)?;
+    std::thread::sleep(Duration::from_millis(500));
+    if is_locked(device)? {
+        // Swipe up to bring up the bouncer, then type the secret.
+        let (w, h) = get_screen_size(device)?;
+        let (x, y) = ((w / 2) as i32, h as i32);
+        swipe(x, y * 9 / 10, x, y * 3 / 10, 250, device)?;
+        std::thread::sleep(Duration::from_millis(500));
+        if let Some(pin) = pin {
+            let cmd = DeviceShellCmd::new().literal("input text").validated(pin, validate_lock_secret)?.render();
+            adb_exec(device, &["shell", &cmd], None)?;
+            adb_exec(device, &["shell", "input", "keyevent", "KEYCODE_ENTER"], None)?;
+        }
+    }
+
+    for _ in 0..10 {
+        std::thread::sleep(Duration::from_millis(300));
+        if !is_locked(device)? {
+            return Ok(true);
+        }
+    }
+    bail!(
+        "Device is still locked{}; a biometric-only or other complex lock blocks automated unlock",
+        if pin.is_some() { " after entering the PIN (wrong PIN?)" } else { "" }
+    )
+}
+
+/// Read the UI night mode via `cmd uimode night` (`Night mode: yes|no|auto`,
+/// or a custom schedule: `custom` before Android 13, `custom_schedule` or
+/// `custom_bedtime` since). Pass it to [`restore_night_mode`] to put the
+/// device back exactly as it was.
+pub fn night_mode(device: Option<&str>) -> Result<String> {
+    let output = adb_exec(device, &["shell", "cmd", "uimode", "night"], None)?;
+    if !output.status.success() {
+        bail!("cmd uimode night failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    parse_night_mode(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_night_mode(out: &str) -> Result<String> {
+    let mode = out.trim().rsplit(':').next().map(str::trim).unwrap_or_default();
+    if mode.is_empty() || !mode.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        bail!("Unexpected cmd uimode night output: {}", out.trim());
+    }
+    Ok(mode.to_string())
+}
+
+/// Force dark (`Some(true)`) or light (`Some(false)`) UI, or go back to the
+/// automatic schedule (`None`), via `cmd uimode night yes|no|auto`.
+pub fn set_night_mode(dark: Option<bool>, device: Option<&str>) -> Result<()> {
+    let value = match dark {
+        Some(true) => "yes",
+        Some(false) => "no",
+        None => "auto",
+    };
+    uimode_night(value, device)
+}
+
+/// Set a mode read with [`night_mode`] again, custom schedules included.
+pub fn restore_night_mode(mode: &str, device: Option<&str>) -> Result<()> {
+    uimode_night(&parse_night_mode(mode)?, device)
+}
+
+fn uimode_night(value: &str, device: Option<&str>) -> Result<()> {
+    let output = adb_exec(device, &["shell", "cmd", "uimode", "night", value], None)?;
+    if !output.status.success() {
+        bail!("cmd uimode night {} failed: {}", value, String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(())
+}
+
 /// Get screen resolution
 pub fn get_screen_size(device: Option<&str>) -> Result<(u32, u32)> {
     let output = adb_exec(device, &["shell", "wm", "size"], None)?;
@@ -979,7 +3065,7 @@ pub fn find_and_tap(description: &str, min_confidence: u32, device: Option<&str>
         let mut score: u32 = 0;
         let text_lower = elem.text.to_lowercase();
         let content_lower = elem.content_desc.to_lowercase();
-        let res_lower = elem.resource_id.to_lowercase().replace('_', " ").replace('/', " ");
+        let res_lower = elem.resource_id.to_lowercase().replace(['_', '/'], " ");
 
         // Exact text match
         if text_lower == desc_lower {
@@ -1011,139 +3097,1100 @@ pub fn find_and_tap(description: &str, min_confidence: u32, device: Option<&str>
             }
         }
 
-        // Bonus for clickable
-        if score > 0 && elem.clickable {
-            score = (score + 10).min(100);
-        }
+        // Bonus for clickable
+        if score > 0 && elem.clickable {
+            score = (score + 10).min(100);
+        }
+
+        if score > best_score {
+            best_score = score;
+            best_element = Some(elem);
+        }
+    }
+
+    if best_score >= min_confidence {
+        if let Some(elem) = best_element {
+            let (cx, cy) = elem.center();
+            println!("Found: \"{}\" (confidence: {}%)", elem.label(), best_score);
+            tap(cx, cy, device)?;
+            return Ok(());
+        }
+    }
+
+    bail!("No element matching '{}' found with confidence >= {}%", description, min_confidence);
+}
+
+/// Push file to device
+pub fn push_file(local: &str, remote: &str, device: Option<&str>) -> Result<()> {
+    let output = adb_exec(device, &["push", local, remote], None)?;
+    if !output.status.success() {
+        bail!("adb push failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    println!("Pushed {} -> {}", local, remote);
+    Ok(())
+}
+
+/// Pull file from device
+pub fn pull_file(remote: &str, local: &str, device: Option<&str>) -> Result<()> {
+    let output = adb_exec(device, &["pull", remote, local], None)?;
+    if !output.status.success() {
+        bail!("adb pull failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    println!("Pulled {} -> {}", remote, local);
+    Ok(())
+}
+
+/// Last release whose `service call clipboard` answers the shell. Android 10
+/// (API 29) hides the clipboard from apps that are not in the foreground or
+/// the default IME, and the shell is neither.
+const CLIPBOARD_SERVICE_CALL_MAX_SDK: u32 = 28;
+
+/// Output of `cmd clipboard` on releases that do not implement it.
+const CMD_CLIPBOARD_UNSUPPORTED: &[&str] =
+    &["No shell command implementation", "Unknown command", "Can't find service", "Unknown option"];
+
+/// Decode `cmd clipboard` output: `None` when the command is missing on
+/// this release, else the clip text (`null` is an empty clipboard).
+fn parse_cmd_clipboard(stdout: &str) -> Option<String> {
+    if CMD_CLIPBOARD_UNSUPPORTED.iter().any(|marker| stdout.contains(marker)) {
+        return None;
+    }
+    let text = stdout.trim_end_matches(['\r', '\n']);
+    if text.trim() == "null" {
+        return Some(String::new());
+    }
+    // `ClipData { text/plain "label" {T(5):hello} }` from a toString() dump.
+    if let Some(start) = text.find("{T") {
+        let body = &text[start..];
+        if let (Some(colon), Some(end)) = (body.find(':'), body.rfind("} }")) {
+            if colon < end {
+                return Some(body[colon + 1..end].to_string());
+            }
+        }
+    }
+    Some(text.to_string())
+}
+
+/// The clip text in a legacy `service call clipboard 2` reply: the string
+/// between single quotes of the ASCII column, else the raw reply.
+fn parse_service_call_clip(raw: &str) -> String {
+    if let Some(start) = raw.find('\'') {
+        if let Some(end) = raw[start + 1..].find('\'') {
+            return raw[start + 1..start + 1 + end].replace("\\n", "\n");
+        }
+    }
+    raw.to_string()
+}
+
+fn clipboard_unsupported(action: &str, sdk: u32) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Clipboard {} is unsupported on this OS (Android API {}): `cmd clipboard` is unavailable \
+         and service calls cannot reach the clipboard since Android 10",
+        action,
+        sdk
+    )
+}
+
+/// Run `cmd clipboard <subcommand> [text]`; `None` when this release has no
+/// clipboard shell command.
+fn cmd_clipboard(subcommand: &'static str, text: Option<&str>, device: Option<&str>) -> Result<Option<String>> {
+    let mut cmd = DeviceShellCmd::new().literal("cmd").literal("clipboard").literal(subcommand);
+    if let Some(text) = text {
+        cmd = cmd.user_input(text);
+    }
+    let output = adb_exec(device, &["shell", &cmd.render()], None)?;
+    let stdout = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(parse_cmd_clipboard(&stdout))
+}
+
+/// Clipper (`ca.zgrs.clipper`) broadcasts for devices without `cmd clipboard`.
+const CLIPPER_GET: &str = "clipper.get";
+const CLIPPER_SET: &str = "clipper.set";
+/// A broadcast nobody handles still succeeds; Clipper answers RESULT_OK.
+const CLIPPER_OK: i32 = -1;
+
+/// Read the clipboard: `cmd clipboard` where the release has it, then a
+/// Clipper receiver if one is installed, then `service call` on Android 9
+/// and older.
+fn read_clipboard(device: Option<&str>) -> Result<String> {
+    if let Some(text) = cmd_clipboard("get-primary-clip", None, device)? {
+        return Ok(text);
+    }
+    if let Ok(BroadcastReply { result: Some(CLIPPER_OK), data, .. }) = broadcast(CLIPPER_GET, None, device) {
+        return Ok(data.unwrap_or_default());
+    }
+    let sdk = sdk_level(device)?;
+    if sdk > CLIPBOARD_SERVICE_CALL_MAX_SDK {
+        return Err(clipboard_unsupported("read", sdk));
+    }
+    let reply = service_call(
+        "clipboard",
+        2,
+        &[ServiceCallArg::S16("com.android.shell".to_string())],
+        device,
+    )?;
+    Ok(parse_service_call_clip(&reply.raw))
+}
+
+/// Get clipboard content
+pub fn get_clipboard(device: Option<&str>) -> Result<()> {
+    println!("{}", read_clipboard(device)?);
+    Ok(())
+}
+
+/// Set clipboard content
+pub fn set_clipboard(text: &str, device: Option<&str>) -> Result<()> {
+    write_clipboard(text, device)?;
+    println!("Clipboard set");
+    Ok(())
+}
+
+/// Put `text` on the clipboard: `cmd clipboard` where the release has it,
+/// then a Clipper receiver if one is installed, then `service call` on
+/// Android 9 and older.
+fn write_clipboard(text: &str, device: Option<&str>) -> Result<()> {
+    if cmd_clipboard("set-primary-clip", Some(text), device)?.is_some() {
+        return Ok(());
+    }
+    let extras = serde_json::json!({ "text": text }).to_string();
+    if broadcast(CLIPPER_SET, Some(&extras), device).is_ok_and(|r| r.result == Some(CLIPPER_OK)) {
+        return Ok(());
+    }
+    let sdk = sdk_level(device)?;
+    if sdk > CLIPBOARD_SERVICE_CALL_MAX_SDK {
+        return Err(clipboard_unsupported("write", sdk));
+    }
+    let reply = service_call(
+        "clipboard",
+        1,
+        &[ServiceCallArg::S16("com.android.shell".to_string()), ServiceCallArg::S16(text.to_string())],
+        device,
+    )?;
+    if reply.exception != Some(0) {
+        bail!("service call clipboard 1 failed: {}", reply.raw.trim());
+    }
+    Ok(())
+}
+
+/// Execute an action command + UI dump in a single adb shell invocation (turbo fast-track).
+/// Returns (action_output, ui_xml). ui_xml may be empty if dump failed.
+///
+/// `shell_cmd` is a pre-composed, trusted shell fragment (produced by the
+/// turbo fast-track builders in `flow.rs`). It is joined with the literal
+/// `&& uiautomator dump /dev/tty` suffix via [`DeviceShellCmd::raw_trusted`]
+/// — DO NOT pass user input here.
+pub fn exec_with_ui_dump(shell_cmd: &str, device: Option<&str>) -> Result<(String, String)> {
+    let combined = DeviceShellCmd::new()
+        .raw_trusted(shell_cmd.to_string())
+        .literal("&&")
+        .literal("uiautomator")
+        .literal("dump")
+        .literal("/dev/tty")
+        .render();
+    let output = adb_exec(device, &["shell", &combined], None)?;
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+
+    // Split at XML boundary
+    let xml_start = stdout.find("<?xml").or_else(|| stdout.find("<hierarchy"));
+    match xml_start {
+        Some(idx) => {
+            let action_output = stdout[..idx].trim().to_string();
+            let mut ui_xml = stdout[idx..].to_string();
+            // Strip "UI hierachy dumped to: /dev/tty" prefix if present before <?xml
+            if let Some(xml_idx) = ui_xml.find("<?xml") {
+                if xml_idx > 0 {
+                    ui_xml = ui_xml[xml_idx..].to_string();
+                }
+            }
+            Ok((action_output, ui_xml))
+        }
+        None => Ok((stdout.trim().to_string(), String::new())),
+    }
+}
+
+/// Parse UI XML into compact one-line summary of interactive elements (reused by turbo fast-track).
+pub fn compact_ui_from_xml(xml: &str) -> String {
+    let elements = parse_ui_elements(xml);
+    let parts: Vec<String> = elements
+        .iter()
+        .take(20)
+        .map(|e| {
+            let short_class = e.class.split('.').next_back().unwrap_or(&e.class);
+            if !e.text.is_empty() {
+                format!("{} \"{}\"", short_class, e.text)
+            } else if !e.content_desc.is_empty() {
+                format!("{} \"{}\"", short_class, e.content_desc)
+            } else {
+                short_class.to_string()
+            }
+        })
+        .collect();
+    parts.join(" | ")
+}
+
+// ============== Font scale & display size ==============
+
+/// What a [`LayoutGuard`] puts back.
+#[derive(Debug, Clone, PartialEq)]
+enum LayoutSetting {
+    /// `font_scale` from `settings get system` ("" = unset, i.e. 1.0).
+    FontScale(String),
+    /// Override density from `wm density` (`None` = physical density).
+    Density(Option<u32>),
+}
+
+/// Restores the previous font scale or display size when dropped; call
+/// [`LayoutGuard::keep`] to leave the new one in place.
+#[derive(Debug)]
+pub struct LayoutGuard {
+    device: Option<String>,
+    previous: Option<LayoutSetting>,
+}
+
+impl LayoutGuard {
+    /// Keep the new setting after the guard goes away.
+    pub fn keep(mut self) {
+        self.previous = None;
+    }
+}
+
+impl LayoutSetting {
+    /// Shell command that puts this value back.
+    fn restore_cmd(&self) -> String {
+        match self {
+            LayoutSetting::FontScale(scale) => setting_cmd("system", "font_scale", scale),
+            LayoutSetting::Density(density) => density_cmd(*density),
+        }
+    }
+}
+
+impl Drop for LayoutGuard {
+    fn drop(&mut self) {
+        let Some(previous) = self.previous.take() else { return };
+        let result = adb_exec(self.device.as_deref(), &["shell", &previous.restore_cmd()], None);
+        match result {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => eprintln!(
+                "Warning: failed to restore display settings: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(e) => eprintln!("Warning: failed to restore display settings: {:#}", e),
+        }
+    }
+}
+
+fn font_scale(device: Option<&str>) -> Result<String> {
+    setting("system", "font_scale", device)
+}
+
+fn put_font_scale(scale: &str, device: Option<&str>) -> Result<()> {
+    put_system_setting("font_scale", scale, device)
+}
+
+/// Set the system font scale (1.3 = the "large text" many layouts break
+/// at) and check it took; with `package`, relaunch that app so it lays out
+/// again.
+pub fn set_font_scale(scale: f32, package: Option<&str>, device: Option<&str>) -> Result<LayoutGuard> {
+    if !(0.25..=3.0).contains(&scale) {
+        bail!("Font scale {} is out of range (0.25 to 3.0)", scale);
+    }
+    let guard = LayoutGuard { device: device.map(str::to_string), previous: Some(LayoutSetting::FontScale(font_scale(device)?)) };
+    put_font_scale(&scale.to_string(), device)?;
+    let applied = font_scale(device)?;
+    if applied.parse::<f32>().map_or(true, |s| (s - scale).abs() > 0.001) {
+        bail!("Font scale did not change: device reports '{}'", applied);
+    }
+    relaunch(package, device)?;
+    Ok(guard)
+}
+
+/// Physical and override values from `wm size` / `wm density` output
+/// ("Physical size: 1080x2400", "Override density: 480").
+fn parse_wm_value<'a>(out: &'a str, kind: &str) -> Option<&'a str> {
+    let prefix = format!("{} ", kind);
+    out.lines().find_map(|l| l.trim().strip_prefix(&prefix)).and_then(|rest| rest.split_once(':')).map(|(_, v)| v.trim())
+}
+
+/// The density that gives a smallest width of `dp` on a screen whose short
+/// side is `short_px` pixels (`dp = px * 160 / dpi`).
+fn density_for_width(short_px: u32, dp: u32) -> u32 {
+    ((short_px as f64 * 160.0) / dp as f64).round() as u32
+}
+
+fn wm(what: &str, device: Option<&str>) -> Result<String> {
+    let output = adb_exec(device, &["shell", "wm", what], None)?;
+    if !output.status.success() {
+        bail!("wm {} failed: {}", what, String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `wm density N`, or `wm density reset` for the physical density.
+fn density_cmd(density: Option<u32>) -> String {
+    format!("wm density {}", density.map_or_else(|| "reset".to_string(), |d| d.to_string()))
+}
+
+/// Override the display density, or reset it to the physical one.
+fn apply_density(density: Option<u32>, device: Option<&str>) -> Result<()> {
+    let cmd = density_cmd(density);
+    let output = adb_exec(device, &["shell", &cmd], None)?;
+    if !output.status.success() {
+        bail!("{} failed: {}", cmd, String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(())
+}
+
+/// Change the display size so the smallest screen width is `dp` (320 for a
+/// small phone, 600 for a 7" tablet), or reset it with `None`; check it
+/// took and relaunch `package` if given.
+///
+/// Works through `wm density`, like the "Display size" setting, so the
+/// panel resolution stays the same.
+pub fn set_display_size(dp: Option<u32>, package: Option<&str>, device: Option<&str>) -> Result<LayoutGuard> {
+    let density = wm("density", device)?;
+    let previous = parse_wm_value(&density, "Override").and_then(|v| v.parse().ok());
+    let target = match dp {
+        Some(dp) if !(200..=2000).contains(&dp) => bail!("Display width {}dp is out of range (200 to 2000)", dp),
+        Some(dp) => {
+            let size = wm("size", device)?;
+            let (w, h) = parse_wm_value(&size, "Physical")
+                .and_then(|v| v.split_once('x'))
+                .and_then(|(w, h)| Some((w.parse::<u32>().ok()?, h.parse::<u32>().ok()?)))
+                .with_context(|| format!("Unexpected `wm size` output: {}", size.trim()))?;
+            Some(density_for_width(w.min(h), dp))
+        }
+        None => None,
+    };
+    let guard = LayoutGuard { device: device.map(str::to_string), previous: Some(LayoutSetting::Density(previous)) };
+    apply_density(target, device)?;
+    let now = wm("density", device)?;
+    let applied: Option<u32> = parse_wm_value(&now, "Override").and_then(|v| v.parse().ok());
+    if applied != target {
+        bail!("Display density did not change: {}", now.trim());
+    }
+    relaunch(package, device)?;
+    Ok(guard)
+}
+
+fn relaunch(package: Option<&str>, device: Option<&str>) -> Result<()> {
+    if let Some(pkg) = package {
+        stop_app(pkg, device)?;
+        launch_app(pkg, device)?;
+    }
+    Ok(())
+}
+
+// ============== Locale ==============
+
+/// How long to wait for a locale change (including a framework restart).
+const LOCALE_TIMEOUT: Duration = Duration::from_secs(90);
+
+fn locale_tag_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^[a-z]{2,3}(-[A-Z][a-z]{3})?(-([A-Z]{2}|[0-9]{3}))?$").unwrap())
+}
+
+/// Accept BCP 47 tags of the form `de`, `de-DE`, `sr-Latn-RS`, `es-419`.
+fn validate_locale_tag(tag: &str) -> Result<()> {
+    if !locale_tag_regex().is_match(tag) {
+        bail!("Unsupported locale '{}': expected a BCP 47 tag like de-DE or pt-BR", tag);
+    }
+    Ok(())
+}
+
+/// Where a locale change was applied, and what to put back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LocaleScope {
+    /// `cmd locale set-app-locales` (Android 13+); no root needed.
+    App { package: String, previous: String },
+    /// `persist.sys.locale` plus a framework restart; needs root.
+    System { previous: String },
+}
+
+/// Restores the previous locale when dropped; call [`LocaleGuard::keep`]
+/// to leave the new one in place.
+#[derive(Debug)]
+pub struct LocaleGuard {
+    device: Option<String>,
+    scope: Option<LocaleScope>,
+}
+
+impl LocaleGuard {
+    /// Keep the new locale after the guard goes away.
+    pub fn keep(mut self) {
+        self.scope = None;
+    }
+}
+
+impl Drop for LocaleGuard {
+    fn drop(&mut self) {
+        let device = self.device.as_deref();
+        let result = match self.scope.take() {
+            Some(LocaleScope::App { package, previous }) => apply_app_locale(&package, &previous, device),
+            // An empty previous locale clears the property again.
+            Some(LocaleScope::System { previous }) => apply_system_locale(&previous, device),
+            None => Ok(()),
+        };
+        if let Err(e) = result {
+            eprintln!("Warning: failed to restore locale: {:#}", e);
+        }
+    }
+}
+
+/// Switch the locale and wait until it has taken effect.
+///
+/// With a `package` on Android 13+ this sets the per-app locale of that app
+/// (and relaunches it), which needs no root. Otherwise it changes the system
+/// locale via `setprop persist.sys.locale` and a framework restart, which
+/// needs a rooted device or `adb root`.
+pub fn set_locale(locale: &str, package: Option<&str>, device: Option<&str>) -> Result<LocaleGuard> {
+    validate_locale_tag(locale)?;
+    let sdk = sdk_level(device)?;
+
+    let scope = match package {
+        Some(pkg) if sdk >= 33 => {
+            let previous = app_locales(pkg, device)?;
+            apply_app_locale(pkg, locale, device)?;
+            LocaleScope::App { package: pkg.to_string(), previous }
+        }
+        _ => {
+            if root_prefix(device)?.is_none() {
+                bail!(
+                    "Changing the system locale needs root (or Android 13+ with --package for a per-app locale); SDK is {}",
+                    sdk
+                );
+            }
+            let previous = getprop("persist.sys.locale", device)?;
+            apply_system_locale(locale, device)?;
+            LocaleScope::System { previous }
+        }
+    };
+
+    if let Some(pkg) = package {
+        stop_app(pkg, device)?;
+        launch_app(pkg, device)?;
+    }
+    println!("Locale set to {}", locale);
+    Ok(LocaleGuard { device: device.map(str::to_string), scope: Some(scope) })
+}
+
+/// Per-app locales as a comma-separated tag list ("" = follow the system).
+fn app_locales(package: &str, device: Option<&str>) -> Result<String> {
+    let cmd = DeviceShellCmd::new()
+        .literal("cmd")
+        .literal("locale")
+        .literal("get-app-locales")
+        .validated(package, validate_package_name)?
+        .render();
+    let output = adb_exec(device, &["shell", &cmd], None)?;
+    Ok(parse_app_locales(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// `Locales for com.x for user 0 are [de-DE,fr-FR]` -> `de-DE,fr-FR`.
+fn parse_app_locales(out: &str) -> String {
+    out.rsplit_once('[')
+        .and_then(|(_, rest)| rest.split_once(']'))
+        .map(|(list, _)| list.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn apply_app_locale(package: &str, locales: &str, device: Option<&str>) -> Result<()> {
+    let cmd = DeviceShellCmd::new()
+        .literal("cmd")
+        .literal("locale")
+        .literal("set-app-locales")
+        .validated(package, validate_package_name)?
+        .literal("--locales")
+        .validated(locales, |l| l.split(',').filter(|t| !t.is_empty()).try_for_each(validate_locale_tag))?
+        .render();
+    let output = adb_exec(device, &["shell", &cmd], None)?;
+    if !output.status.success() || !output.stdout.is_empty() {
+        bail!(
+            "cmd locale set-app-locales failed: {}{}",
+            String::from_utf8_lossy(&output.stdout).trim(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let start = std::time::Instant::now();
+    while app_locales(package, device)? != locales {
+        if start.elapsed() > LOCALE_TIMEOUT {
+            bail!("Locale '{}' did not take for {}", locales, package);
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+    Ok(())
+}
+
+/// Shell command that sets `persist.sys.locale` and restarts the framework.
+/// An empty `locale` clears the property, so the build default applies.
+fn system_locale_cmd(locale: &str) -> Result<String> {
+    let cmd = DeviceShellCmd::new().literal("setprop").literal("persist.sys.locale");
+    let cmd = if locale.is_empty() { cmd.user_input("") } else { cmd.validated(locale, validate_locale_tag)? };
+    Ok(cmd.literal(";").literal("setprop").literal("ctl.restart").literal("zygote").render())
+}
+
+fn apply_system_locale(locale: &str, device: Option<&str>) -> Result<()> {
+    let prefix = root_prefix(device)?.context("Setting the system locale needs root")?;
+    let setprop = system_locale_cmd(locale)?;
+    let cmd = if prefix {
+        DeviceShellCmd::new().literal("su").literal("-c").user_input(&setprop).render()
+    } else {
+        setprop
+    };
+    adb_exec(device, &["shell", &cmd], None)?;
+
+    // The framework restarts; wait for it to come back with the new locale.
+    let start = std::time::Instant::now();
+    loop {
+        std::thread::sleep(Duration::from_secs(2));
+        let took = getprop("persist.sys.locale", device).is_ok_and(|l| l == locale);
+        let ready = adb_exec(device, &["shell", "service", "check", "activity"], None)
+            .is_ok_and(|o| String::from_utf8_lossy(&o.stdout).contains(": found"));
+        if took && ready {
+            return Ok(());
+        }
+        if start.elapsed() > LOCALE_TIMEOUT {
+            bail!("Locale '{}' did not take within {}s", locale, LOCALE_TIMEOUT.as_secs());
+        }
+    }
+}
+
+/// `Some(false)` if the adb shell already runs as root, `Some(true)` if
+/// `su -c` is needed, `None` without root.
+fn root_prefix(device: Option<&str>) -> Result<Option<bool>> {
+    let is_root = |o: std::process::Output| String::from_utf8_lossy(&o.stdout).contains("uid=0");
+    if is_root(adb_exec(device, &["shell", "id"], None)?) {
+        return Ok(Some(false));
+    }
+    if is_root(adb_exec(device, &["shell", "su", "-c", "id"], None)?) {
+        return Ok(Some(true));
+    }
+    Ok(None)
+}
+
+// ============== Accessibility ==============
+
+/// TalkBack's accessibility service component (Android Accessibility Suite).
+const TALKBACK_SERVICE: &str = "com.google.android.marvin.talkback/com.google.android.marvin.talkback.TalkBackService";
+
+/// How long to wait for the accessibility manager to (un)bind TalkBack.
+const TALKBACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `settings get <namespace> <name>`; an unset setting reads as "".
+fn setting(namespace: &'static str, name: &'static str, device: Option<&str>) -> Result<String> {
+    let output = adb_exec(device, &["shell", "settings", "get", namespace, name], None)?;
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(if value == "null" { String::new() } else { value })
+}
+
+/// `settings put <namespace> <name> <value>`, or `settings delete` for "".
+fn setting_cmd(namespace: &'static str, name: &'static str, value: &str) -> String {
+    let cmd = DeviceShellCmd::new()
+        .literal("settings")
+        .literal(if value.is_empty() { "delete" } else { "put" })
+        .literal(namespace)
+        .literal(name);
+    let cmd = if value.is_empty() { cmd } else { cmd.user_input(value) };
+    cmd.render()
+}
+
+fn put_setting(namespace: &'static str, name: &'static str, value: &str, device: Option<&str>) -> Result<()> {
+    let output = adb_exec(device, &["shell", &setting_cmd(namespace, name, value)], None)?;
+    if !output.status.success() {
+        bail!("settings {} failed: {}", name, String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(())
+}
+
+fn secure_setting(name: &'static str, device: Option<&str>) -> Result<String> {
+    setting("secure", name, device)
+}
+
+fn put_secure_setting(name: &'static str, value: &str, device: Option<&str>) -> Result<()> {
+    put_setting("secure", name, value, device)
+}
+
+fn put_system_setting(name: &'static str, value: &str, device: Option<&str>) -> Result<()> {
+    put_setting("system", name, value, device)
+}
+
+/// Add or remove `service` in a colon-separated `enabled_accessibility_services` list.
+fn toggle_service(list: &str, service: &str, on: bool) -> String {
+    let mut services: Vec<&str> = list.split(':').filter(|s| !s.is_empty() && *s != service).collect();
+    if on {
+        services.push(service);
+    }
+    services.join(":")
+}
+
+/// Whether `dumpsys accessibility` lists TalkBack as bound or enabled.
+fn talkback_bound(dump: &str) -> bool {
+    dump.lines().any(|l| {
+        let lower = l.to_lowercase();
+        (lower.contains("bound services") || lower.contains("enabled services")) && lower.contains("talkback")
+    })
+}
+
+/// Restores the previous accessibility services when dropped; call
+/// [`TalkBackGuard::keep`] to leave TalkBack as set.
+#[derive(Debug)]
+pub struct TalkBackGuard {
+    device: Option<String>,
+    /// `enabled_accessibility_services` and `accessibility_enabled` before the change.
+    previous: Option<(String, String)>,
+}
+
+impl TalkBackGuard {
+    /// Keep the new state after the guard goes away.
+    pub fn keep(mut self) {
+        self.previous = None;
+    }
+}
+
+impl Drop for TalkBackGuard {
+    fn drop(&mut self) {
+        let Some((services, enabled)) = self.previous.take() else { return };
+        let device = self.device.as_deref();
+        let result = put_secure_setting("enabled_accessibility_services", &services, device)
+            .and_then(|_| put_secure_setting("accessibility_enabled", &enabled, device));
+        if let Err(e) = result {
+            eprintln!("Warning: failed to restore accessibility services: {:#}", e);
+        }
+    }
+}
+
+/// Turn TalkBack on or off and wait until the accessibility manager agrees.
+///
+/// Other enabled accessibility services are left alone. Needs the Android
+/// Accessibility Suite (`com.google.android.marvin.talkback`) installed.
+pub fn set_talkback(on: bool, device: Option<&str>) -> Result<TalkBackGuard> {
+    if on && installed_version("com.google.android.marvin.talkback", device)?.is_none() {
+        bail!("TalkBack is not installed (install Android Accessibility Suite from Play)");
+    }
+    let services = secure_setting("enabled_accessibility_services", device)?;
+    let enabled = secure_setting("accessibility_enabled", device)?;
+    let guard = TalkBackGuard { device: device.map(str::to_string), previous: Some((services.clone(), enabled)) };
+
+    let wanted = toggle_service(&services, TALKBACK_SERVICE, on);
+    put_secure_setting("enabled_accessibility_services", &wanted, device)?;
+    put_secure_setting("accessibility_enabled", if wanted.is_empty() { "0" } else { "1" }, device)?;
+
+    let deadline = std::time::Instant::now() + TALKBACK_TIMEOUT;
+    loop {
+        let output = adb_exec(device, &["shell", "dumpsys", "accessibility"], None)?;
+        if talkback_bound(&String::from_utf8_lossy(&output.stdout)) == on {
+            return Ok(guard);
+        }
+        if std::time::Instant::now() >= deadline {
+            bail!("TalkBack did not turn {} within {}s", if on { "on" } else { "off" }, TALKBACK_TIMEOUT.as_secs());
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+}
+
+/// Collects what TalkBack speaks, from logcat since [`AnnouncementCapture::start`].
+///
+/// TalkBack only logs utterances with its developer setting "Log output
+/// level" at verbose or debug.
+#[derive(Debug, Clone)]
+pub struct AnnouncementCapture {
+    device: Option<String>,
+    /// Device clock at start, in the `sec.nsec` form `logcat -T` accepts.
+    since: String,
+}
+
+impl AnnouncementCapture {
+    pub fn start(device: Option<&str>) -> Result<Self> {
+        Ok(Self { device: device.map(str::to_string), since: format!("{}.0", device_epoch_secs(device)?) })
+    }
+
+    pub fn announcements(&self) -> Result<Vec<String>> {
+        let output = adb_exec(
+            self.device.as_deref(),
+            &["logcat", "-d", "-v", "brief", "-T", &self.since],
+            None,
+        )?;
+        if !output.status.success() {
+            bail!("logcat failed: {}", String::from_utf8_lossy(&output.stderr));
+        }
+        Ok(parse_talkback_log(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+fn utterance_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"(?i)(?:speak\w*|utterance\w*|text)\s*[:=]?\s*(?:"([^"]+)"|\[([^\]]+)\])"#).unwrap()
+    })
+}
+
+/// Spoken text from TalkBack log lines. The speech controller quotes the
+/// utterance (`Speaking: "Submit, Button"`) or brackets it (`text=[Submit]`)
+/// depending on the TalkBack version.
+fn parse_talkback_log(log: &str) -> Vec<String> {
+    log.lines()
+        .filter(|l| l.to_lowercase().contains("talkback") || l.contains("SpeechController"))
+        .filter_map(|l| utterance_regex().captures(l))
+        .filter_map(|c| c.get(1).or(c.get(2)).map(|m| m.as_str().trim().to_string()))
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+// ============== Accessibility tree snapshots ==============
+
+/// One node of an [`AccessibilityTree`]. Empty attributes are left out of
+/// the JSON so snapshots stay small and diff cleanly.
+#[derive(Debug, Clone, PartialEq, Serialize, serde::Deserialize)]
+pub struct TreeNode {
+    pub class: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub text: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub desc: String,
+    /// Set states among [`TREE_FLAGS`], plus `disabled`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<String>,
+    /// `[left, top, right, bottom]`, unless the snapshot strips coordinates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<[i32; 4]>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+}
+
+/// Normalized UI hierarchy: classes, labels and states without the dump's
+/// volatile parts (node indexes, and coordinates unless asked for).
+#[derive(Debug, Clone, PartialEq, Serialize, serde::Deserialize)]
+pub struct AccessibilityTree {
+    pub nodes: Vec<TreeNode>,
+}
 
-        if score > best_score {
-            best_score = score;
-            best_element = Some(elem);
-        }
+/// Boolean node attributes kept in [`TreeNode::flags`] when true.
+const TREE_FLAGS: &[&str] = &["checkable", "checked", "clickable", "focusable", "scrollable", "selected", "password"];
+
+fn tree_node(node: &str, keep_bounds: bool) -> TreeNode {
+    let attr = |name| node_attr(node, name).unwrap_or_default().into_owned();
+    let mut flags: Vec<String> =
+        TREE_FLAGS.iter().filter(|f| node_attr(node, f).as_deref() == Some("true")).map(|f| f.to_string()).collect();
+    if node_attr(node, "enabled").as_deref() == Some("false") {
+        flags.push("disabled".into());
+    }
+    let bounds = bounds_regex()
+        .captures(node)
+        .filter(|_| keep_bounds)
+        .map(|c| [1, 2, 3, 4].map(|i| c[i].parse().unwrap_or(0)));
+    TreeNode {
+        class: attr("class"),
+        id: attr("resource-id"),
+        text: attr("text"),
+        desc: attr("content-desc"),
+        flags,
+        bounds,
+        children: Vec::new(),
     }
+}
 
-    if best_score >= min_confidence {
-        if let Some(elem) = best_element {
-            let (cx, cy) = elem.center();
-            println!("Found: \"{}\" (confidence: {}%)", elem.label(), best_score);
-            tap(cx, cy, device)?;
-            return Ok(());
+/// Build the tree from a `uiautomator dump`, keeping node coordinates only
+/// if `keep_bounds`.
+pub fn parse_tree(xml: &str, keep_bounds: bool) -> Result<AccessibilityTree> {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let tag = TAG.get_or_init(|| Regex::new(r"<node\b[^>]*>|</node>").unwrap());
+    let (mut roots, mut open): (Vec<TreeNode>, Vec<TreeNode>) = (Vec::new(), Vec::new());
+    for m in tag.find_iter(xml) {
+        let node = match m.as_str() {
+            "</node>" => open.pop().context("UI dump closes a node that was never opened")?,
+            t if t.ends_with("/>") => tree_node(t, keep_bounds),
+            t => {
+                open.push(tree_node(t, keep_bounds));
+                continue;
+            }
+        };
+        match open.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => roots.push(node),
         }
     }
+    if !open.is_empty() {
+        bail!("UI dump ends with {} unclosed node(s)", open.len());
+    }
+    Ok(AccessibilityTree { nodes: roots })
+}
 
-    bail!("No element matching '{}' found with confidence >= {}%", description, min_confidence);
+/// Snapshot the current screen's hierarchy (see [`parse_tree`]).
+pub fn snapshot_tree(device: Option<&str>, keep_bounds: bool) -> Result<AccessibilityTree> {
+    parse_tree(&get_ui_xml(device)?, keep_bounds)
 }
 
-/// Push file to device
-pub fn push_file(local: &str, remote: &str, device: Option<&str>) -> Result<()> {
-    let output = adb_exec(device, &["push", local, remote], None)?;
-    if !output.status.success() {
-        bail!("adb push failed: {}", String::from_utf8_lossy(&output.stderr));
+/// A structural difference found by [`compare_tree`]. Paths name each node
+/// by its short class and resource id (`FrameLayout/Button#login`), or by
+/// its position among same-class siblings (`TextView[1]`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeChange {
+    /// In the baseline but not the snapshot (its subtree is not listed).
+    Missing(String),
+    /// In the snapshot but not the baseline.
+    Added(String),
+    Changed { path: String, field: &'static str, baseline: String, actual: String },
+}
+
+impl std::fmt::Display for TreeChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreeChange::Missing(path) => write!(f, "missing: {}", path),
+            TreeChange::Added(path) => write!(f, "added: {}", path),
+            TreeChange::Changed { path, field, baseline, actual } => {
+                write!(f, "changed: {} {} '{}' -> '{}'", path, field, baseline, actual)
+            }
+        }
     }
-    println!("Pushed {} -> {}", local, remote);
-    Ok(())
 }
 
-/// Pull file from device
-pub fn pull_file(remote: &str, local: &str, device: Option<&str>) -> Result<()> {
-    let output = adb_exec(device, &["pull", remote, local], None)?;
-    if !output.status.success() {
-        bail!("adb pull failed: {}", String::from_utf8_lossy(&output.stderr));
+/// Path of each of `nodes` under `parent`, by class and id, with the
+/// position among same-named siblings after the first.
+fn sibling_paths(nodes: &[TreeNode], parent: &str) -> Vec<String> {
+    let mut seen: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    nodes
+        .iter()
+        .map(|node| {
+            let class = node.class.rsplit('.').next().unwrap_or_default();
+            let key = match node.id.is_empty() {
+                true => class.to_string(),
+                false => format!("{}#{}", class, node.id.rsplit('/').next().unwrap_or_default()),
+            };
+            let n = seen.entry(key.clone()).or_default();
+            let name = if *n == 0 { key } else { format!("{}[{}]", key, n) };
+            *n += 1;
+            if parent.is_empty() { name } else { format!("{}/{}", parent, name) }
+        })
+        .collect()
+}
+
+/// Class and id, which a node must share with its counterpart.
+fn node_kind(node: &TreeNode) -> (&str, &str) {
+    (&node.class, &node.id)
+}
+
+/// Pair `baseline` siblings with `snapshot` ones of the same kind: first
+/// those with the same text and description, in order, then the rest by
+/// position. Returns the pairs and the unmatched indexes on each side.
+fn match_siblings(baseline: &[TreeNode], snapshot: &[TreeNode]) -> (Vec<(usize, usize)>, Vec<usize>, Vec<usize>) {
+    let mut taken = vec![false; snapshot.len()];
+    let mut pairs: Vec<Option<usize>> = vec![None; baseline.len()];
+    let same_content = |b: &TreeNode, a: &TreeNode| node_kind(b) == node_kind(a) && b.text == a.text && b.desc == a.desc;
+    let by_position = |b: &TreeNode, a: &TreeNode| node_kind(b) == node_kind(a);
+    for matches in [&same_content as &dyn Fn(&TreeNode, &TreeNode) -> bool, &by_position] {
+        for (i, base) in baseline.iter().enumerate() {
+            if pairs[i].is_some() {
+                continue;
+            }
+            if let Some(j) = (0..snapshot.len()).find(|&j| !taken[j] && matches(base, &snapshot[j])) {
+                taken[j] = true;
+                pairs[i] = Some(j);
+            }
+        }
     }
-    println!("Pulled {} -> {}", remote, local);
-    Ok(())
+    let missing = (0..baseline.len()).filter(|&i| pairs[i].is_none()).collect();
+    let added = (0..snapshot.len()).filter(|&j| !taken[j]).collect();
+    let pairs = pairs.into_iter().enumerate().filter_map(|(i, j)| Some((i, j?))).collect();
+    (pairs, missing, added)
 }
 
-/// Get clipboard content
-pub fn get_clipboard(device: Option<&str>) -> Result<()> {
-    let output = adb_exec(device, &["shell", "service", "call", "clipboard", "2", "s16", "com.android.shell"], None)?;
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    // Parse parcel response - extract string between single quotes
-    if let Some(start) = stdout.find('\'') {
-        if let Some(end) = stdout[start+1..].find('\'') {
-            let text = &stdout[start+1..start+1+end];
-            println!("{}", text.replace("\\n", "\n"));
-            return Ok(());
+fn compare_nodes(
+    baseline: &[TreeNode],
+    snapshot: &[TreeNode],
+    parent: (&str, &str),
+    out: &mut Vec<TreeChange>,
+) {
+    let base_paths = sibling_paths(baseline, parent.0);
+    let snap_paths = sibling_paths(snapshot, parent.1);
+    let (pairs, missing, added) = match_siblings(baseline, snapshot);
+    out.extend(missing.into_iter().map(|i| TreeChange::Missing(base_paths[i].clone())));
+    out.extend(added.into_iter().map(|j| TreeChange::Added(snap_paths[j].clone())));
+    for (i, j) in pairs {
+        let (base, node, path) = (&baseline[i], &snapshot[j], &base_paths[i]);
+        let mut changed = |field, baseline: String, actual: String| {
+            if baseline != actual {
+                out.push(TreeChange::Changed { path: path.clone(), field, baseline, actual });
+            }
+        };
+        changed("text", base.text.clone(), node.text.clone());
+        changed("desc", base.desc.clone(), node.desc.clone());
+        changed("flags", base.flags.join(","), node.flags.join(","));
+        if let (Some(b), Some(a)) = (base.bounds, node.bounds) {
+            changed("bounds", format!("{:?}", b), format!("{:?}", a));
         }
+        compare_nodes(&base.children, &node.children, (path, &snap_paths[j]), out);
     }
-    println!("{}", stdout);
-    Ok(())
 }
 
-/// Set clipboard content
-pub fn set_clipboard(text: &str, device: Option<&str>) -> Result<()> {
-    let cmd = DeviceShellCmd::new()
-        .literal("am")
-        .literal("broadcast")
-        .literal("-a")
-        .literal("clipper.set")
-        .literal("-e")
-        .literal("text")
-        .user_input(text)
-        .render();
-    let output = adb_exec(device, &["shell", &cmd], None)?;
-    if !output.status.success() {
-        // Fallback: try input method
-        let _ = adb_exec(device, &["shell", "service", "call", "clipboard", "1", "s16", "com.android.shell", "s16", text], None)?;
+/// Structural differences between `snapshot` and `baseline`: nodes that
+/// went missing or appeared, and changed text, content description or
+/// states. Bounds are compared only where both trees kept them.
+///
+/// Siblings of the same class and id are matched by content before
+/// position, so a node inserted ahead of its twins shows up as added
+/// rather than as every later twin changing. Missing and changed nodes are
+/// named by their baseline path, added ones by their snapshot path.
+pub fn compare_tree(snapshot: &AccessibilityTree, baseline: &AccessibilityTree) -> Vec<TreeChange> {
+    let mut changes = Vec::new();
+    compare_nodes(&baseline.nodes, &snapshot.nodes, ("", ""), &mut changes);
+    changes.sort_by_key(|c| match c {
+        TreeChange::Missing(_) => 0,
+        TreeChange::Added(_) => 1,
+        TreeChange::Changed { .. } => 2,
+    });
+    changes
+}
+
+// ============== Toasts ==============
+
+/// Resource ids of a toast's text view in a UI dump: the framework layout,
+/// and SystemUI's on Android 12+ where text toasts are drawn there. Dialog
+/// messages share `android:id/message`, so polling can also report those.
+const TOAST_TEXT_IDS: &[&str] = &["android:id/message", "com.android.systemui:id/text"];
+
+/// The text of a toast from one `uiautomator events` line.
+fn parse_toast_event(line: &str) -> Option<String> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    if !line.contains("TYPE_NOTIFICATION_STATE_CHANGED") || !line.contains("ClassName: android.widget.Toast") {
+        return None;
     }
-    println!("Clipboard set");
-    Ok(())
+    let re = RE.get_or_init(|| Regex::new(r"Text: \[(.*?)\];").unwrap());
+    let text = re.captures(line)?.get(1)?.as_str().trim();
+    (!text.is_empty()).then(|| text.to_string())
 }
 
-/// Execute an action command + UI dump in a single adb shell invocation (turbo fast-track).
-/// Returns (action_output, ui_xml). ui_xml may be empty if dump failed.
+#[derive(Default)]
+struct ToastWatchState {
+    seen: std::sync::Mutex<Vec<String>>,
+    child: std::sync::Mutex<Option<std::process::Child>>,
+}
+
+impl Drop for ToastWatchState {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.get_mut().ok().and_then(Option::take) {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Collects toast texts in the background from [`ToastWatch::start`] until
+/// the last clone is dropped.
 ///
-/// `shell_cmd` is a pre-composed, trusted shell fragment (produced by the
-/// turbo fast-track builders in `flow.rs`). It is joined with the literal
-/// `&& uiautomator dump /dev/tty` suffix via [`DeviceShellCmd::raw_trusted`]
-/// — DO NOT pass user input here.
-pub fn exec_with_ui_dump(shell_cmd: &str, device: Option<&str>) -> Result<(String, String)> {
-    let combined = DeviceShellCmd::new()
-        .raw_trusted(shell_cmd.to_string())
-        .literal("&&")
-        .literal("uiautomator")
-        .literal("dump")
-        .literal("/dev/tty")
-        .render();
-    let output = adb_exec(device, &["shell", &combined], None)?;
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+/// Toasts are read from the accessibility event stream (`uiautomator
+/// events`). Where that is unavailable the watch polls the UI hierarchy
+/// instead, which only sees toasts shown while a dump runs. Either way the
+/// watch holds the device's UiAutomation connection, so other UI dumps
+/// (tap-text, ui-dump) can fail while it runs.
+#[derive(Clone)]
+pub struct ToastWatch {
+    state: std::sync::Arc<ToastWatchState>,
+}
 
-    // Split at XML boundary
-    let xml_start = stdout.find("<?xml").or_else(|| stdout.find("<hierarchy"));
-    match xml_start {
-        Some(idx) => {
-            let action_output = stdout[..idx].trim().to_string();
-            let mut ui_xml = stdout[idx..].to_string();
-            // Strip "UI hierachy dumped to: /dev/tty" prefix if present before <?xml
-            if let Some(xml_idx) = ui_xml.find("<?xml") {
-                if xml_idx > 0 {
-                    ui_xml = ui_xml[xml_idx..].to_string();
+impl ToastWatch {
+    pub fn start(device: Option<&str>) -> Result<Self> {
+        let state = std::sync::Arc::new(ToastWatchState::default());
+        let mut child = adb_cmd(device)
+            .args(["shell", "uiautomator", "events"])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .context("Failed to start uiautomator events")?;
+        let stdout = child.stdout.take().context("uiautomator events has no stdout")?;
+        *state.child.lock().unwrap() = Some(child);
+
+        // The thread only holds a weak reference, so dropping the last
+        // `ToastWatch` kills the stream and ends it.
+        let weak = std::sync::Arc::downgrade(&state);
+        let device = device.map(str::to_string);
+        std::thread::spawn(move || {
+            use std::io::BufRead;
+            let mut got_events = false;
+            for line in std::io::BufReader::new(stdout).lines().map_while(Result::ok) {
+                let Some(state) = weak.upgrade() else { return };
+                got_events |= line.contains("EventType:");
+                if let Some(text) = parse_toast_event(&line) {
+                    state.seen.lock().unwrap().push(text);
                 }
             }
-            Ok((action_output, ui_xml))
+            // The stream ended on its own: events are not available here.
+            if !got_events {
+                poll_toasts(&weak, device.as_deref());
+            }
+        });
+        Ok(Self { state })
+    }
+
+    /// [`ToastWatch::start`], first recording the toasts already on screen:
+    /// for a toast raised by a step that had to dump the UI before the
+    /// watch could take uiautomator over.
+    pub fn start_after_dump(device: Option<&str>) -> Result<Self> {
+        let visible = visible_toasts(device).unwrap_or_default();
+        let watch = Self::start(device)?;
+        watch.state.seen.lock().unwrap().extend(visible);
+        Ok(watch)
+    }
+
+    /// Toast texts seen so far, oldest first.
+    pub fn toasts(&self) -> Vec<String> {
+        self.state.seen.lock().unwrap().clone()
+    }
+
+    /// Wait up to `timeout` for a toast containing `needle`.
+    pub fn wait_for(&self, needle: &str, timeout: Duration) -> Result<String> {
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            let seen = self.toasts();
+            if let Some(found) = seen.iter().find(|t| t.contains(needle)) {
+                return Ok(found.clone());
+            }
+            if std::time::Instant::now() >= deadline {
+                bail!(
+                    "No toast containing '{}' within {}ms (saw {}{}{})",
+                    needle,
+                    timeout.as_millis(),
+                    seen.len(),
+                    if seen.is_empty() { "" } else { ": " },
+                    seen.join(" | ")
+                );
+            }
+            std::thread::sleep(Duration::from_millis(100));
         }
-        None => Ok((stdout.trim().to_string(), String::new())),
     }
 }
 
-/// Parse UI XML into compact one-line summary of interactive elements (reused by turbo fast-track).
-pub fn compact_ui_from_xml(xml: &str) -> String {
-    let elements = parse_ui_elements(xml);
-    let parts: Vec<String> = elements
-        .iter()
-        .take(20)
-        .map(|e| {
-            let short_class = e.class.split('.').last().unwrap_or(&e.class);
-            if !e.text.is_empty() {
-                format!("{} \"{}\"", short_class, e.text)
-            } else if !e.content_desc.is_empty() {
-                format!("{} \"{}\"", short_class, e.content_desc)
-            } else {
-                short_class.to_string()
-            }
-        })
-        .collect();
-    parts.join(" | ")
+/// Fallback for [`ToastWatch`]: dump the UI in a tight loop and record
+/// toast texts as they appear.
+fn poll_toasts(state: &std::sync::Weak<ToastWatchState>, device: Option<&str>) {
+    let mut visible: Vec<String> = Vec::new();
+    while state.strong_count() > 0 {
+        let now = visible_toasts(device).unwrap_or_default();
+        let Some(state) = state.upgrade() else { return };
+        state.seen.lock().unwrap().extend(now.iter().filter(|t| !visible.contains(t)).cloned());
+        visible = now;
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Toast texts on screen in one UI dump.
+fn visible_toasts(device: Option<&str>) -> Result<Vec<String>> {
+    Ok(get_ui_elements(device)?
+        .into_iter()
+        .filter(|e| TOAST_TEXT_IDS.contains(&e.resource_id.as_str()) && !e.text.is_empty())
+        .map(|e| e.text)
+        .collect())
+}
+
+/// Toast texts shown during the next `duration`.
+pub fn capture_toasts(duration: Duration, device: Option<&str>) -> Result<Vec<String>> {
+    let watch = ToastWatch::start(device)?;
+    std::thread::sleep(duration);
+    Ok(watch.toasts())
 }
 
 // ============== Sensor Commands ==============
@@ -1268,83 +4315,157 @@ pub fn sensor_battery(
     Ok(())
 }
 
-/// Read active notifications from `dumpsys notification --noredact`.
-pub fn sensor_notifications(package: Option<&str>, device: Option<&str>) -> Result<()> {
+/// A posted notification from `dumpsys notification --noredact`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Notification {
+    pub package: String,
+    pub id: String,
+    pub tag: String,
+    pub title: String,
+    pub text: String,
+    pub channel: String,
+}
+
+/// Currently posted notifications, across all apps.
+pub fn active_notifications(device: Option<&str>) -> Result<Vec<Notification>> {
     let output = adb_exec(device, &["shell", "dumpsys", "notification", "--noredact"], None)?;
     if !output.status.success() {
         bail!("dumpsys notification failed: {}", String::from_utf8_lossy(&output.stderr));
     }
+    Ok(parse_notifications(&String::from_utf8_lossy(&output.stdout)))
+}
 
-    let text = String::from_utf8_lossy(&output.stdout);
-    let mut notifications: Vec<serde_json::Value> = Vec::new();
-
-    // Each notification block starts with "  NotificationRecord(" and ends at the next one
-    let mut in_record = false;
-    let mut pkg = String::new();
-    let mut notif_id = String::new();
-    let mut tag = String::new();
-    let mut title = String::new();
-    let mut body = String::new();
-    let mut channel = String::new();
-
-    let flush_record = |notifications: &mut Vec<serde_json::Value>,
-                         pkg: &str, notif_id: &str, tag: &str,
-                         title: &str, body: &str, channel: &str,
-                         filter: Option<&str>| {
-        if !pkg.is_empty() && filter.map_or(true, |f| pkg == f) {
-            notifications.push(serde_json::json!({
-                "package": pkg,
-                "id": notif_id,
-                "tag": tag,
-                "title": title,
-                "text": body,
-                "channel": channel,
-            }));
-        }
-    };
+/// Value of `key=` in a space-separated `a=b c=d` run, if present.
+fn inline_field<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    line.split_whitespace().find_map(|w| w.strip_prefix(key)?.strip_prefix('='))
+}
 
-    for line in text.lines() {
+/// Extras print as `android.title=Hello` on older releases and as
+/// `android.title=String (Hello)` (or `SpannableString (…)`) on newer ones.
+fn extra_value(line: &str, key: &str) -> Option<String> {
+    let value = line.split_once(&format!("{}=", key))?.1.trim();
+    let unwrapped = value
+        .split_once(" (")
+        .filter(|(ty, rest)| ty.ends_with("String") && rest.ends_with(')'))
+        .map(|(_, rest)| &rest[..rest.len() - 1]);
+    Some(unwrapped.unwrap_or(value).to_string())
+}
+
+/// Split the dump into `NotificationRecord(` blocks. The package and id
+/// are read from the record line itself (newer releases) or from their own
+/// `pkg=` / `id=` lines (older ones).
+fn parse_notifications(dump: &str) -> Vec<Notification> {
+    let mut out: Vec<Notification> = Vec::new();
+    let mut current: Option<Notification> = None;
+    for line in dump.lines() {
         let trimmed = line.trim();
-        if trimmed.starts_with("NotificationRecord(") {
-            if in_record {
-                flush_record(&mut notifications, &pkg, &notif_id, &tag, &title, &body, &channel, package);
-            }
-            in_record = true;
-            pkg.clear();
-            notif_id.clear();
-            tag.clear();
-            title.clear();
-            body.clear();
-            channel.clear();
-            continue;
-        }
-        if !in_record {
+        if let Some(rest) = trimmed.strip_prefix("NotificationRecord(") {
+            out.extend(current.take().filter(|n| !n.package.is_empty()));
+            current = Some(Notification {
+                package: inline_field(rest, "pkg").unwrap_or_default().to_string(),
+                id: inline_field(rest, "id").unwrap_or_default().to_string(),
+                tag: inline_field(rest, "tag").filter(|t| *t != "null").unwrap_or_default().to_string(),
+                ..Default::default()
+            });
             continue;
         }
-        if trimmed.starts_with("pkg=") {
-            pkg = trimmed.trim_start_matches("pkg=").trim().to_string();
-        } else if trimmed.starts_with("id=") {
-            notif_id = trimmed.trim_start_matches("id=").split_whitespace().next()
-                .unwrap_or("").to_string();
-        } else if trimmed.starts_with("tag=") {
-            tag = trimmed.trim_start_matches("tag=").trim().trim_matches('"').to_string();
-        } else if trimmed.contains("android.title=") {
-            title = trimmed.split("android.title=").nth(1).unwrap_or("").trim().to_string();
-        } else if trimmed.contains("android.text=") {
-            body = trimmed.split("android.text=").nth(1).unwrap_or("").trim().to_string();
-        } else if trimmed.starts_with("channel=Channel{") {
-            channel = trimmed
-                .split("id=").nth(1)
+        let Some(n) = current.as_mut() else { continue };
+        if let Some(pkg) = trimmed.strip_prefix("pkg=") {
+            n.package = pkg.split_whitespace().next().unwrap_or("").to_string();
+        } else if let Some(id) = trimmed.strip_prefix("id=") {
+            n.id = id.split_whitespace().next().unwrap_or("").to_string();
+        } else if let Some(tag) = trimmed.strip_prefix("tag=") {
+            n.tag = tag.trim().trim_matches('"').to_string();
+        } else if let Some(title) = extra_value(trimmed, "android.title") {
+            n.title = title;
+        } else if let Some(text) = extra_value(trimmed, "android.text") {
+            n.text = text;
+        } else if let Some(channel) = trimmed.strip_prefix("channel=Channel{") {
+            n.channel = channel
+                .split("id=")
+                .nth(1)
                 .and_then(|s| s.split(',').next())
-                .unwrap_or("").trim().to_string();
+                .unwrap_or("")
+                .trim()
+                .to_string();
         }
     }
+    out.extend(current.filter(|n| !n.package.is_empty()));
+    out
+}
+
+/// Read active notifications from `dumpsys notification --noredact`.
+pub fn sensor_notifications(package: Option<&str>, device: Option<&str>) -> Result<()> {
+    let notifications: Vec<Notification> = active_notifications(device)?
+        .into_iter()
+        .filter(|n| package.is_none_or(|p| n.package == p))
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&notifications)?);
+    Ok(())
+}
+
+/// A pending alarm from `dumpsys alarm`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Alarm {
+    /// `RTC_WAKEUP`, `RTC`, `ELAPSED_WAKEUP` or `ELAPSED`.
+    pub kind: String,
+    pub package: String,
+    /// Trigger time: epoch ms for RTC alarms, ms since boot for ELAPSED ones.
+    pub when: i64,
+    /// Alarm tag (e.g. `*walarm*:com.example/.Receiver`), if printed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
 
-    if in_record {
-        flush_record(&mut notifications, &pkg, &notif_id, &tag, &title, &body, &channel, package);
+pub fn scheduled_alarms(device: Option<&str>) -> Result<Vec<Alarm>> {
+    let output = adb_exec(device, &["shell", "dumpsys", "alarm"], None)?;
+    if !output.status.success() {
+        bail!("dumpsys alarm failed: {}", String::from_utf8_lossy(&output.stderr));
     }
+    Ok(parse_alarms(&String::from_utf8_lossy(&output.stdout)))
+}
 
-    println!("{}", serde_json::to_string_pretty(&notifications)?);
+fn alarm_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^(RTC_WAKEUP|RTC|ELAPSED_WAKEUP|ELAPSED|ELAPSED_REALTIME_WAKEUP|ELAPSED_REALTIME) #\d+: Alarm\{\S+ type \d+ (?:when|origWhen) (-?\d+)\b.*? (\S+)\}").unwrap()
+    })
+}
+
+/// Alarm headers look like `RTC_WAKEUP #0: Alarm{8e3c type 0 when 1700000000000 com.x}`
+/// (`origWhen` since Android 12); the `tag=` line follows the header.
+fn parse_alarms(dump: &str) -> Vec<Alarm> {
+    let mut alarms: Vec<Alarm> = Vec::new();
+    let mut awaiting_tag = false;
+    for line in dump.lines() {
+        let trimmed = line.trim();
+        if let Some(caps) = alarm_regex().captures(trimmed) {
+            alarms.push(Alarm {
+                kind: caps[1].replace("_REALTIME", ""),
+                package: caps[3].to_string(),
+                when: caps[2].parse().unwrap_or(0),
+                tag: None,
+            });
+            awaiting_tag = true;
+        } else if awaiting_tag {
+            if let Some(tag) = trimmed.strip_prefix("tag=") {
+                if let Some(alarm) = alarms.last_mut() {
+                    alarm.tag = Some(tag.to_string());
+                }
+                awaiting_tag = false;
+            }
+        }
+    }
+    alarms
+}
+
+/// Print pending alarms, optionally for one package.
+pub fn print_alarms(package: Option<&str>, device: Option<&str>) -> Result<()> {
+    let alarms: Vec<Alarm> = scheduled_alarms(device)?
+        .into_iter()
+        .filter(|a| package.is_none_or(|p| a.package == p))
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&alarms)?);
     Ok(())
 }
 
@@ -1541,6 +4662,126 @@ pub fn network_proxy(
     Ok(())
 }
 
+/// An HTTP call seen in the app's OkHttp log or a proxy's HAR export.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    /// Response code, when the response was logged too.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+}
+
+impl HttpRequest {
+    /// `method` matches case-insensitively (`*` = any); `url` is a substring.
+    pub fn matches(&self, method: &str, url: &str, status: Option<u16>) -> bool {
+        (method == "*" || self.method.eq_ignore_ascii_case(method))
+            && self.url.contains(url)
+            && (status.is_none() || self.status == status)
+    }
+}
+
+/// Collects the HTTP requests an app logs from a point in time onwards.
+///
+/// Requests are read from logcat lines written by OkHttp's
+/// `HttpLoggingInterceptor` (`--> POST https://…`, `<-- 200 OK https://…`),
+/// so the app build must have that interceptor at `BASIC` level or higher.
+/// Nothing is cleared: the capture only looks at lines newer than `start`.
+#[derive(Debug, Clone)]
+pub struct NetworkCapture {
+    device: Option<String>,
+    /// Device clock at start, in the `sec.nsec` form `logcat -T` accepts.
+    since: String,
+}
+
+impl NetworkCapture {
+    pub fn start(device: Option<&str>) -> Result<Self> {
+        Ok(Self::since(device_epoch_secs(device)?, device))
+    }
+
+    /// Capture from `secs` (device epoch seconds).
+    pub fn since(secs: u64, device: Option<&str>) -> Self {
+        Self { device: device.map(str::to_string), since: format!("{}.0", secs) }
+    }
+
+    pub fn captured_requests(&self) -> Result<Vec<HttpRequest>> {
+        let output = adb_exec(
+            self.device.as_deref(),
+            &["logcat", "-d", "-v", "brief", "-T", &self.since],
+            None,
+        )?;
+        if !output.status.success() {
+            bail!("logcat failed: {}", String::from_utf8_lossy(&output.stderr));
+        }
+        Ok(parse_okhttp_log(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+fn okhttp_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(-->|<--) (?:([A-Z]+) (https?://\S+)|(\d{3})\b.*? (https?://\S+))").unwrap()
+    })
+}
+
+/// Pair OkHttp request lines with their response lines (by URL, in order).
+fn parse_okhttp_log(log: &str) -> Vec<HttpRequest> {
+    let mut requests: Vec<HttpRequest> = Vec::new();
+    for caps in log.lines().filter_map(|l| okhttp_regex().captures(l)) {
+        match (&caps[1], caps.get(2), caps.get(3), caps.get(4), caps.get(5)) {
+            ("-->", Some(method), Some(url), _, _) => requests.push(HttpRequest {
+                method: method.as_str().to_string(),
+                url: url.as_str().to_string(),
+                status: None,
+            }),
+            ("<--", _, _, Some(code), Some(url)) => {
+                if let Some(req) = requests.iter_mut().find(|r| r.status.is_none() && r.url == url.as_str()) {
+                    req.status = code.as_str().parse().ok();
+                }
+            }
+            _ => {}
+        }
+    }
+    requests
+}
+
+/// Requests from a HAR file (the export format of mitmproxy, Charles, etc.).
+pub fn parse_har(json: &str) -> Result<Vec<HttpRequest>> {
+    let har: serde_json::Value = serde_json::from_str(json).context("Invalid HAR file")?;
+    let entries = har["log"]["entries"]
+        .as_array()
+        .context("HAR file has no log.entries")?;
+    Ok(entries
+        .iter()
+        .filter_map(|e| {
+            Some(HttpRequest {
+                method: e["request"]["method"].as_str()?.to_string(),
+                url: e["request"]["url"].as_str()?.to_string(),
+                status: e["response"]["status"].as_u64().and_then(|s| u16::try_from(s).ok()).filter(|s| *s > 0),
+            })
+        })
+        .collect())
+}
+
+/// Print the HTTP requests logged in the last `seconds`, or read from a HAR file.
+pub fn network_requests(seconds: u64, har: Option<&str>, device: Option<&str>) -> Result<()> {
+    let requests = match har {
+        Some(path) => {
+            parse_har(&std::fs::read_to_string(path).with_context(|| format!("Cannot read {}", path))?)?
+        }
+        None => {
+            let output = adb_exec(device, &["shell", "date", "+%s"], None)?;
+            let now: u64 = String::from_utf8_lossy(&output.stdout)
+                .trim()
+                .parse()
+                .context("Could not read the device clock")?;
+            NetworkCapture::since(now.saturating_sub(seconds), device).captured_requests()?
+        }
+    };
+    println!("{}", serde_json::to_string_pretty(&requests)?);
+    Ok(())
+}
+
 /// Enable or disable airplane mode.
 pub fn network_airplane(enabled: bool, device: Option<&str>) -> Result<()> {
     let value = if enabled { "1" } else { "0" };
@@ -1630,6 +4871,7 @@ pub fn permission_reset(package: &str, device: Option<&str>) -> Result<()> {
 // ============== Intent Commands ==============
 
 /// Start an activity via `am start`.
+#[allow(clippy::too_many_arguments)]
 pub fn intent_start(
     action: Option<&str>,
     component: Option<&str>,
@@ -1689,6 +4931,33 @@ pub fn intent_broadcast(
     extras: Option<&str>,
     device: Option<&str>,
 ) -> Result<()> {
+    let reply = run_broadcast(action, package, component, extras, device)?;
+    print!("{}", reply.raw);
+    Ok(())
+}
+
+/// Reply of `am broadcast`, with the final result code/data parsed out.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BroadcastReply {
+    pub raw: String,
+    /// `result=` from the "Broadcast completed" line.
+    pub result: Option<i32>,
+    /// `data="…"` from the "Broadcast completed" line, when a receiver set it.
+    pub data: Option<String>,
+}
+
+/// Send `action` via `am broadcast` with JSON `extras` and return the reply.
+pub fn broadcast(action: &str, extras: Option<&str>, device: Option<&str>) -> Result<BroadcastReply> {
+    run_broadcast(action, None, None, extras, device)
+}
+
+fn run_broadcast(
+    action: &str,
+    package: Option<&str>,
+    component: Option<&str>,
+    extras: Option<&str>,
+    device: Option<&str>,
+) -> Result<BroadcastReply> {
     let mut cmd = DeviceShellCmd::new()
         .literal("am")
         .literal("broadcast")
@@ -1710,11 +4979,172 @@ pub fn intent_broadcast(
     let rendered = cmd.render();
     let output = adb_exec(device, &["shell", &rendered], None)?;
 
-    if !output.status.success() {
-        bail!("am broadcast failed: {}", String::from_utf8_lossy(&output.stderr));
+    if !output.status.success() {
+        bail!("am broadcast failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    Ok(parse_broadcast_reply(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse `Broadcast completed: result=-1, data="…"` out of `am broadcast` output.
+fn parse_broadcast_reply(raw: &str) -> BroadcastReply {
+    let mut result = None;
+    let mut data = None;
+    if let Some(line) = raw.lines().find(|l| l.contains("Broadcast completed:")) {
+        if let Some(rest) = line.split("result=").nth(1) {
+            let num: String = rest
+                .chars()
+                .take_while(|c| c.is_ascii_digit() || *c == '-')
+                .collect();
+            result = num.parse().ok();
+        }
+        if let Some(rest) = line.split("data=\"").nth(1) {
+            data = rest.rfind('"').map(|end| rest[..end].to_string());
+        }
+    }
+    BroadcastReply { raw: raw.to_string(), result, data }
+}
+
+/// Typed argument for `service call <service> <code> …`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServiceCallArg {
+    I32(i32),
+    I64(i64),
+    F(f32),
+    D(f64),
+    S16(String),
+    Null,
+}
+
+impl ServiceCallArg {
+    /// Parse the CLI form `i32:5`, `s16:text`, `f:1.5`, `null`, …
+    pub fn parse(s: &str) -> Result<Self> {
+        if s == "null" {
+            return Ok(ServiceCallArg::Null);
+        }
+        let (kind, value) = s
+            .split_once(':')
+            .with_context(|| format!("Service-call arg '{}' must be TYPE:VALUE (i32, i64, f, d, s16) or null", s))?;
+        Ok(match kind {
+            "i32" => ServiceCallArg::I32(value.parse().with_context(|| format!("Invalid i32 '{}'", value))?),
+            "i64" => ServiceCallArg::I64(value.parse().with_context(|| format!("Invalid i64 '{}'", value))?),
+            "f" => ServiceCallArg::F(value.parse().with_context(|| format!("Invalid float '{}'", value))?),
+            "d" => ServiceCallArg::D(value.parse().with_context(|| format!("Invalid double '{}'", value))?),
+            "s16" => ServiceCallArg::S16(value.to_string()),
+            other => bail!("Unknown service-call arg type '{}' (use i32, i64, f, d, s16 or null)", other),
+        })
+    }
+}
+
+/// Reply of `service call`, decoded from the printed Parcel hex dump.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ServiceCallReply {
+    pub raw: String,
+    /// 32-bit words of the reply Parcel, in order.
+    pub words: Vec<u32>,
+    /// First word: 0 when the call returned without an exception.
+    pub exception: Option<i32>,
+    /// Second word interpreted as an `int` return value.
+    pub int_value: Option<i32>,
+    /// Reply interpreted as a `String16` return value.
+    pub string_value: Option<String>,
+}
+
+/// Invoke a binder method via `service call` and decode the reply Parcel.
+///
+/// Generalises the `service call clipboard 2 …` trick: the reply is parsed
+/// into words plus best-effort `int` / `String16` views.
+pub fn service_call(
+    service: &str,
+    code: u32,
+    args: &[ServiceCallArg],
+    device: Option<&str>,
+) -> Result<ServiceCallReply> {
+    let mut cmd = DeviceShellCmd::new()
+        .literal("service")
+        .literal("call")
+        .validated(service, validate_service_name)?
+        .user_input(&code.to_string());
+    for arg in args {
+        cmd = match arg {
+            ServiceCallArg::I32(v) => cmd.literal("i32").user_input(&v.to_string()),
+            ServiceCallArg::I64(v) => cmd.literal("i64").user_input(&v.to_string()),
+            ServiceCallArg::F(v) => cmd.literal("f").user_input(&v.to_string()),
+            ServiceCallArg::D(v) => cmd.literal("d").user_input(&v.to_string()),
+            ServiceCallArg::S16(v) => cmd.literal("s16").user_input(v),
+            ServiceCallArg::Null => cmd.literal("null"),
+        };
+    }
+
+    let rendered = cmd.render();
+    let output = adb_exec(device, &["shell", &rendered], None)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() || stdout.contains("Service not found") || stdout.contains("service: Service") {
+        bail!(
+            "service call {} {} failed: {}{}",
+            service,
+            code,
+            stdout.trim(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(parse_parcel_reply(&stdout))
+}
+
+/// Decode `Result: Parcel(0x00000000: 00000000 00000004 ... '....')` output.
+fn parse_parcel_reply(raw: &str) -> ServiceCallReply {
+    let mut words = Vec::new();
+    for line in raw.lines() {
+        // Drop the ASCII rendering, which may itself contain hex-looking text.
+        let hex_part = line.split('\'').next().unwrap_or("");
+        let hex_part = hex_part.split("Parcel(").last().unwrap_or(hex_part);
+        for token in hex_part.split_whitespace() {
+            if token.ends_with(':') {
+                continue; // 0x00000010: offset column
+            }
+            if token.len() == 8 && token.bytes().all(|b| b.is_ascii_hexdigit()) {
+                if let Ok(w) = u32::from_str_radix(token, 16) {
+                    words.push(w);
+                }
+            }
+        }
     }
 
-    print!("{}", String::from_utf8_lossy(&output.stdout));
+    let exception = words.first().map(|w| *w as i32);
+    let int_value = words.get(1).map(|w| *w as i32);
+    let string_value = decode_string16(&words);
+    ServiceCallReply { raw: raw.to_string(), words, exception, int_value, string_value }
+}
+
+/// Decode a `String16` return value: `[exception, len, utf16 chars…]`.
+fn decode_string16(words: &[u32]) -> Option<String> {
+    let len = *words.get(1)? as i32;
+    if len < 0 {
+        return None;
+    }
+    let len = len as usize;
+    let units: Vec<u16> = words
+        .get(2..)?
+        .iter()
+        .flat_map(|w| {
+            let b = w.to_le_bytes();
+            [u16::from_le_bytes([b[0], b[1]]), u16::from_le_bytes([b[2], b[3]])]
+        })
+        .collect();
+    if units.len() < len {
+        return None;
+    }
+    String::from_utf16(&units[..len]).ok()
+}
+
+/// Validate a binder service name (`clipboard`, `SurfaceFlinger`, `activity_task`).
+fn validate_service_name(s: &str) -> Result<()> {
+    if s.is_empty() || s.len() > 128 {
+        bail!("Service name must be 1-128 characters");
+    }
+    if !s.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-')) {
+        bail!("Invalid service name '{}': only alphanumerics, dots, underscores, and hyphens allowed", s);
+    }
     Ok(())
 }
 
@@ -1770,8 +5200,7 @@ pub fn intent_services(package: Option<&str>, device: Option<&str>) -> Result<()
     for line in text.lines() {
         let trimmed = line.trim();
         // Lines like: * ServiceRecord{deadbeef com.pkg/.MyService}
-        if trimmed.starts_with("* ServiceRecord{") {
-            let record = &trimmed["* ServiceRecord{".len()..];
+        if let Some(record) = trimmed.strip_prefix("* ServiceRecord{") {
             if let Some(close) = record.find('}') {
                 let inner = &record[..close];
                 let parts: Vec<&str> = inner.split_whitespace().collect();
@@ -2125,6 +5554,31 @@ fn parse_battery_level(battery: &str) -> u8 {
     0
 }
 
+/// Parse `(total_frames, janky_frames)` from `dumpsys gfxinfo <pkg>`.
+///
+/// Both counters are cumulative since the process started (or the last
+/// `gfxinfo reset`).
+fn parse_gfx_counters(gfxinfo: &str) -> (u64, u64) {
+    let mut total_frames: u64 = 0;
+    let mut janky_frames: u64 = 0;
+    for line in gfxinfo.lines() {
+        let t = line.trim();
+        if t.starts_with("Total frames rendered:") {
+            if let Some(v) = t.split(':').nth(1) {
+                total_frames = v.trim().parse().unwrap_or(0);
+            }
+        }
+        if t.starts_with("Janky frames:") {
+            if let Some(v) = t.split(':').nth(1) {
+                // "Janky frames: 123 (45.67%)"
+                janky_frames = v.split_whitespace().next().unwrap_or("0")
+                    .parse().unwrap_or(0);
+            }
+        }
+    }
+    (total_frames, janky_frames)
+}
+
 /// Collect a single performance snapshot for a package.
 /// Returns a `serde_json::Value` with the parsed metrics.
 fn collect_perf_snapshot_value(package: &str, device: Option<&str>) -> Result<serde_json::Value> {
@@ -2144,24 +5598,7 @@ fn collect_perf_snapshot_value(package: &str, device: Option<&str>) -> Result<se
     let cpu_percent = parse_cpu_percent(&cpuinfo_text, package);
     let battery_level = parse_battery_level(&battery_text);
 
-    // Parse janky frames from gfxinfo
-    let mut total_frames: u64 = 0;
-    let mut janky_frames: u64 = 0;
-    for line in gfxinfo_text.lines() {
-        let t = line.trim();
-        if t.starts_with("Total frames rendered:") {
-            if let Some(v) = t.split(':').nth(1) {
-                total_frames = v.trim().parse().unwrap_or(0);
-            }
-        }
-        if t.starts_with("Janky frames:") {
-            if let Some(v) = t.split(':').nth(1) {
-                // "Janky frames: 123 (45.67%)"
-                janky_frames = v.trim().split_whitespace().next().unwrap_or("0")
-                    .parse().unwrap_or(0);
-            }
-        }
-    }
+    let (total_frames, janky_frames) = parse_gfx_counters(&gfxinfo_text);
 
     let janky_percent = if total_frames > 0 {
         (janky_frames as f64 / total_frames as f64) * 100.0
@@ -2351,6 +5788,325 @@ pub fn perf_monitor(
     Ok(())
 }
 
+/// One raw reading taken by [`PerfSampler`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PerfReading {
+    memory_mb: f64,
+    cpu_percent: f64,
+    /// Cumulative gfxinfo counters at the time of the reading.
+    total_frames: u64,
+    janky_frames: u64,
+    battery_level: u8,
+}
+
+/// min / max / avg over the readings of one metric.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct MetricStats {
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+}
+
+impl MetricStats {
+    fn of(values: impl Iterator<Item = f64> + Clone) -> Self {
+        let n = values.clone().count().max(1) as f64;
+        MetricStats {
+            min: values.clone().fold(f64::INFINITY, f64::min),
+            max: values.clone().fold(f64::NEG_INFINITY, f64::max),
+            avg: values.sum::<f64>() / n,
+        }
+    }
+}
+
+/// Aggregated performance metrics for a package over a time window.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PerfSample {
+    pub package: String,
+    pub duration_ms: u64,
+    pub samples: usize,
+    pub memory_mb: MetricStats,
+    pub cpu_percent: MetricStats,
+    /// Frames rendered within the window (not since process start).
+    pub frames_rendered: u64,
+    pub janky_frames: u64,
+    pub janky_percent: f64,
+    /// Battery percentage points lost during the window.
+    pub battery_drop: i32,
+}
+
+fn read_perf(package: &str, device: Option<&str>) -> Result<PerfReading> {
+    let dumpsys = |args: &[&str]| -> Result<String> {
+        let mut full = vec!["shell", "dumpsys"];
+        full.extend_from_slice(args);
+        let out = adb_exec(device, &full, None)?;
+        Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+    };
+    let (memory_mb, _) = parse_meminfo(&dumpsys(&["meminfo", package])?);
+    let cpu_percent = parse_cpu_percent(&dumpsys(&["cpuinfo"])?, package);
+    let (total_frames, janky_frames) = parse_gfx_counters(&dumpsys(&["gfxinfo", package])?);
+    let battery_level = parse_battery_level(&dumpsys(&["battery"])?);
+    Ok(PerfReading { memory_mb, cpu_percent, total_frames, janky_frames, battery_level })
+}
+
+/// Fold readings into a [`PerfSample`].
+///
+/// gfxinfo frame counters are cumulative, so the first reading is only a
+/// baseline: frames are the delta between the first and last reading. If the
+/// counters went backwards (app restarted mid-window) the last reading is
+/// taken as-is.
+fn aggregate_perf(package: &str, readings: &[PerfReading], duration_ms: u64) -> PerfSample {
+    let first = readings.first().copied();
+    let last = readings.last().copied();
+    let (frames_rendered, janky_frames, battery_drop) = match (first, last) {
+        (Some(a), Some(b)) => {
+            let (frames, janky) = if b.total_frames >= a.total_frames && b.janky_frames >= a.janky_frames {
+                (b.total_frames - a.total_frames, b.janky_frames - a.janky_frames)
+            } else {
+                (b.total_frames, b.janky_frames)
+            };
+            (frames, janky, a.battery_level as i32 - b.battery_level as i32)
+        }
+        _ => (0, 0, 0),
+    };
+    let janky_percent = if frames_rendered > 0 {
+        janky_frames as f64 / frames_rendered as f64 * 100.0
+    } else {
+        0.0
+    };
+    PerfSample {
+        package: package.to_string(),
+        duration_ms,
+        samples: readings.len(),
+        memory_mb: MetricStats::of(readings.iter().map(|r| r.memory_mb)),
+        cpu_percent: MetricStats::of(readings.iter().map(|r| r.cpu_percent)),
+        frames_rendered,
+        janky_frames,
+        janky_percent,
+        battery_drop,
+    }
+}
+
+/// Background sampler that records perf readings while other work (a flow or
+/// scenario replay) drives the device.
+pub struct PerfSampler {
+    package: String,
+    device: Option<String>,
+    started: std::time::Instant,
+    stop: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    handle: std::thread::JoinHandle<Vec<PerfReading>>,
+}
+
+impl PerfSampler {
+    /// Take a baseline reading and start sampling every `interval_ms`.
+    pub fn start(package: &str, interval_ms: u64, device: Option<&str>) -> Result<Self> {
+        validate_package_name(package)?;
+        let baseline = read_perf(package, device)?;
+
+        let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let (pkg, dev, flag) = (package.to_string(), device.map(str::to_string), stop.clone());
+        let handle = std::thread::spawn(move || {
+            let mut readings = vec![baseline];
+            let tick = Duration::from_millis(interval_ms.max(100));
+            let mut next = std::time::Instant::now() + tick;
+            while !flag.load(std::sync::atomic::Ordering::Relaxed) {
+                if std::time::Instant::now() >= next {
+                    if let Ok(r) = read_perf(&pkg, dev.as_deref()) {
+                        readings.push(r);
+                    }
+                    next += tick;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            readings
+        });
+
+        Ok(PerfSampler {
+            package: package.to_string(),
+            device: device.map(str::to_string),
+            started: std::time::Instant::now(),
+            stop,
+            handle,
+        })
+    }
+
+    /// Stop sampling, take a closing reading and aggregate.
+    pub fn finish(self) -> Result<PerfSample> {
+        self.stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        let mut readings = self
+            .handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("perf sampler thread panicked"))?;
+        readings.push(read_perf(&self.package, self.device.as_deref())?);
+        Ok(aggregate_perf(&self.package, &readings, self.started.elapsed().as_millis() as u64))
+    }
+}
+
+/// Thresholds for a [`Watchdog`]; a `None` limit is not checked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchdogLimits {
+    /// CPU usage that counts as a hang once sustained for `cpu_window_ms`.
+    pub cpu_percent: Option<f64>,
+    pub cpu_window_ms: u64,
+    /// Memory cap (total PSS) that counts as a leak once a sample crosses it.
+    pub memory_mb: Option<f64>,
+}
+
+/// Peaks seen by a [`Watchdog`] and the first limit breached, if any.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchdogReport {
+    pub package: String,
+    pub peak_cpu_percent: f64,
+    pub peak_memory_mb: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub violation: Option<String>,
+}
+
+/// Threshold bookkeeping, separate from the sampling thread.
+struct WatchdogState {
+    limits: WatchdogLimits,
+    /// When CPU usage last rose above the limit, for the sustained check.
+    cpu_over_since: Option<u64>,
+    report: WatchdogReport,
+}
+
+impl WatchdogState {
+    fn observe(&mut self, at_ms: u64, cpu_percent: f64, memory_mb: f64) {
+        let report = &mut self.report;
+        report.peak_cpu_percent = report.peak_cpu_percent.max(cpu_percent);
+        report.peak_memory_mb = report.peak_memory_mb.max(memory_mb);
+        if report.violation.is_some() {
+            return;
+        }
+        if let Some(cap) = self.limits.memory_mb.filter(|cap| memory_mb > *cap) {
+            report.violation = Some(format!("memory {:.1}MB exceeded the {:.0}MB cap", memory_mb, cap));
+            return;
+        }
+        match self.limits.cpu_percent {
+            Some(limit) if cpu_percent > limit => {
+                let since = *self.cpu_over_since.get_or_insert(at_ms);
+                if at_ms - since >= self.limits.cpu_window_ms {
+                    report.violation = Some(format!(
+                        "CPU above {:.0}% for {:.1}s (peak {:.1}%)",
+                        limit,
+                        (at_ms - since) as f64 / 1000.0,
+                        report.peak_cpu_percent
+                    ));
+                }
+            }
+            _ => self.cpu_over_since = None,
+        }
+    }
+}
+
+/// Background monitor that flags runaway CPU or memory use by `package`
+/// while a step runs. CPU comes from the app's `/proc/<pid>/stat` on every
+/// tick, which costs one small shell call; memory needs `dumpsys meminfo`,
+/// which is heavy enough to slow the app under test, so it is only read
+/// every [`WATCHDOG_MEMINFO_INTERVAL`].
+pub struct Watchdog {
+    state: std::sync::Arc<std::sync::Mutex<WatchdogState>>,
+    stop: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    handle: std::thread::JoinHandle<()>,
+}
+
+impl Watchdog {
+    pub fn start(package: &str, limits: WatchdogLimits, interval_ms: u64, device: Option<&str>) -> Result<Self> {
+        validate_package_name(package)?;
+        let state = std::sync::Arc::new(std::sync::Mutex::new(WatchdogState {
+            limits,
+            cpu_over_since: None,
+            report: WatchdogReport { package: package.to_string(), ..Default::default() },
+        }));
+        let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let (pkg, dev, shared, flag) = (package.to_string(), device.map(str::to_string), state.clone(), stop.clone());
+        let handle = std::thread::spawn(move || {
+            let started = std::time::Instant::now();
+            let tick = Duration::from_millis(interval_ms.max(250));
+            let mut memory_mb = 0.0;
+            let mut next_meminfo = started;
+            // (pid, CPU ticks, when read) from the previous tick.
+            let mut last: Option<(u32, u64, std::time::Instant)> = None;
+            while !flag.load(std::sync::atomic::Ordering::Relaxed) {
+                let now = std::time::Instant::now();
+                if now >= next_meminfo {
+                    if let Ok(out) = adb_exec(dev.as_deref(), &["shell", "dumpsys", "meminfo", &pkg], None) {
+                        memory_mb = parse_meminfo(&String::from_utf8_lossy(&out.stdout)).0;
+                    }
+                    next_meminfo = now + WATCHDOG_MEMINFO_INTERVAL;
+                }
+                let current = read_cpu_ticks(&pkg, dev.as_deref());
+                if let (Some((pid, ticks)), Some((last_pid, last_ticks, at))) = (current, last) {
+                    let secs = now.duration_since(at).as_secs_f64();
+                    // A new pid means the app restarted; its ticks start over.
+                    if pid == last_pid && secs > 0.0 {
+                        let cpu_percent = ticks.saturating_sub(last_ticks) as f64 / CLOCK_TICKS_PER_SEC / secs * 100.0;
+                        if let Ok(mut s) = shared.lock() {
+                            s.observe(started.elapsed().as_millis() as u64, cpu_percent, memory_mb);
+                        }
+                    }
+                }
+                last = current.map(|(pid, ticks)| (pid, ticks, now));
+                let next = std::time::Instant::now() + tick;
+                while std::time::Instant::now() < next && !flag.load(std::sync::atomic::Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+            }
+        });
+        Ok(Watchdog { state, stop, handle })
+    }
+
+    /// The first limit breached so far.
+    pub fn violation(&self) -> Option<String> {
+        self.state.lock().ok().and_then(|s| s.report.violation.clone())
+    }
+
+    pub fn finish(self) -> WatchdogReport {
+        self.stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        let _ = self.handle.join();
+        self.state.lock().map(|s| s.report.clone()).unwrap_or_default()
+    }
+}
+
+/// How often a [`Watchdog`] reads `dumpsys meminfo`.
+pub const WATCHDOG_MEMINFO_INTERVAL: Duration = Duration::from_secs(5);
+
+/// `USER_HZ`, the unit of the times in `/proc/<pid>/stat`; fixed at 100 on
+/// Android.
+const CLOCK_TICKS_PER_SEC: f64 = 100.0;
+
+/// The running `package`'s pid and CPU time so far (user + system, in clock
+/// ticks), or `None` when it is not running. `package` must be validated.
+fn read_cpu_ticks(package: &str, device: Option<&str>) -> Option<(u32, u64)> {
+    let script = format!("pid=$(pidof -s {}) && echo $pid && cat /proc/$pid/stat", package);
+    let out = adb_exec(device, &["shell", &script], None).ok()?;
+    parse_cpu_ticks(&String::from_utf8_lossy(&out.stdout))
+}
+
+/// Parse the pid line and `/proc/<pid>/stat` printed by [`read_cpu_ticks`].
+fn parse_cpu_ticks(out: &str) -> Option<(u32, u64)> {
+    let (pid, stat) = out.trim().split_once('\n')?;
+    // The command name may contain spaces; fields after it start at field 3
+    // (state), so utime and stime (fields 14 and 15) are at 11 and 12.
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some((pid.trim().parse().ok()?, utime + stime))
+}
+
+/// Sample memory, CPU, frame and battery stats for `package` over `duration`.
+pub fn capture_perf(
+    package: &str,
+    duration: Duration,
+    interval_ms: u64,
+    device: Option<&str>,
+) -> Result<PerfSample> {
+    let sampler = PerfSampler::start(package, interval_ms, device)?;
+    std::thread::sleep(duration);
+    sampler.finish()
+}
+
 /// Extract recent crashes and ANRs from logcat.
 pub fn perf_crashes(package: Option<&str>, lines: usize, device: Option<&str>) -> Result<()> {
     if let Some(pkg) = package {
@@ -2462,7 +6218,7 @@ pub fn perf_framestats(package: &str, device: Option<&str>) -> Result<()> {
             }
         } else if t.starts_with("Janky frames:") {
             if let Some(v) = t.split(':').nth(1) {
-                janky_frames = v.trim().split_whitespace().next().unwrap_or("0")
+                janky_frames = v.split_whitespace().next().unwrap_or("0")
                     .parse().unwrap_or(0);
             }
         } else if t.starts_with("50th percentile:") {
@@ -2488,58 +6244,596 @@ pub fn perf_framestats(package: &str, device: Option<&str>) -> Result<()> {
             in_data_section = true;
             continue;
         }
-        // End of data section (empty line or "---" separator)
-        if in_data_section && (t.is_empty() || t.starts_with("---")) {
-            in_data_section = false;
+        // End of data section (empty line or "---" separator)
+        if in_data_section && (t.is_empty() || t.starts_with("---")) {
+            in_data_section = false;
+        }
+
+        if in_data_section {
+            let cols: Vec<&str> = t.split(',').collect();
+            if cols.len() >= 14 {
+                let intended: u64 = cols[0].trim().parse().unwrap_or(0);
+                let completed: u64 = cols[13].trim().parse().unwrap_or(0);
+                if completed > intended && intended > 0 {
+                    let duration_ns = completed - intended;
+                    frame_durations_ms.push(duration_ns as f64 / 1_000_000.0);
+                }
+            }
+        }
+    }
+
+    // If summary wasn't in dumpsys output, compute from raw frame data
+    if total_frames == 0 && !frame_durations_ms.is_empty() {
+        total_frames = frame_durations_ms.len() as u64;
+        // 16.67ms = 60fps target
+        janky_frames = frame_durations_ms.iter().filter(|&&d| d > 16.67).count() as u64;
+    }
+
+    let janky_percent = if total_frames > 0 {
+        (janky_frames as f64 / total_frames as f64) * 100.0
+    } else {
+        0.0
+    };
+
+    let result = serde_json::json!({
+        "package": package,
+        "totalFrames": total_frames,
+        "jankyFrames": janky_frames,
+        "jankyPercent": format!("{:.2}", janky_percent),
+        "percentiles": {
+            "p50ms": p50_ms,
+            "p90ms": p90_ms,
+            "p95ms": p95_ms,
+            "p99ms": p99_ms,
+        },
+        "rawFrameCount": frame_durations_ms.len(),
+        "note": "Janky = frame > 16.67ms (< 60 fps). Run app activity before capturing."
+    });
+
+    println!("{}", serde_json::to_string_pretty(&result)?);
+    Ok(())
+}
+
+/// Live instance counts by class from one heap dump of an app.
+#[derive(Debug, Clone, Serialize, serde::Deserialize)]
+pub struct HeapSnapshot {
+    pub package: String,
+    /// Unix time of the dump (seconds).
+    #[serde(rename = "takenAt")]
+    pub taken_at: u64,
+    pub instances: std::collections::HashMap<String, u64>,
+}
+
+/// How much a class's instance count grew between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HeapGrowth {
+    pub class: String,
+    pub before: u64,
+    pub after: u64,
+}
+
+impl HeapGrowth {
+    pub fn delta(&self) -> u64 {
+        self.after - self.before
+    }
+}
+
+/// A file `mktemp` created under `/data/local/tmp`, removed on drop. The
+/// name is unpredictable and the file exists before anything writes to it,
+/// so another shell user cannot pre-create or swap it.
+struct DeviceTempFile {
+    path: String,
+    device: Option<String>,
+}
+
+impl DeviceTempFile {
+    fn create(prefix: &str, device: Option<&str>) -> Result<Self> {
+        let template = format!("/data/local/tmp/{}-XXXXXXXXXX", prefix);
+        let cmd = DeviceShellCmd::new().literal("mktemp").user_input(&template).render();
+        let output = adb_exec(device, &["shell", &cmd], None)?;
+        let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !output.status.success() || !path.starts_with("/data/local/tmp/") {
+            bail!("mktemp failed on the device: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(DeviceTempFile { path, device: device.map(str::to_string) })
+    }
+}
+
+impl Drop for DeviceTempFile {
+    fn drop(&mut self) {
+        let cmd = DeviceShellCmd::new().literal("rm").literal("-f").user_input(&self.path).render();
+        let _ = adb_exec(self.device.as_deref(), &["shell", &cmd], None);
+    }
+}
+
+/// Dump `package`'s heap with `am dumpheap` (after a forced GC where the
+/// device supports it), pull the hprof and count instances per class.
+///
+/// Dumps often run to hundreds of MB; both copies are deleted afterwards,
+/// on errors too.
+pub fn heap_snapshot(package: &str, device: Option<&str>) -> Result<HeapSnapshot> {
+    validate_package_name(package)?;
+    let remote = DeviceTempFile::create("cim-heap", device)?;
+    let run = |cmd: DeviceShellCmd| -> Result<String> {
+        let output = adb_exec(device, &["shell", &cmd.render()], None)?;
+        Ok(format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr)))
+    };
+    let dump = |gc: bool| -> Result<String> {
+        let cmd = DeviceShellCmd::new().literal("am").literal("dumpheap");
+        let cmd = if gc { cmd.literal("-g") } else { cmd };
+        run(cmd.validated(package, validate_package_name)?.user_input(&remote.path))
+    };
+
+    // `-g` (GC first, so only live objects remain) is Android 10+; older
+    // `am` rejects the flag.
+    let mut out = dump(true)?;
+    if out.contains("Unknown option") {
+        out = dump(false)?;
+    }
+    if out.contains("Error") || out.contains("Exception") {
+        bail!("am dumpheap failed for {}: {}", package, out.trim());
+    }
+    // Older releases return before the dump is written; wait until the
+    // file stops growing.
+    let deadline = std::time::Instant::now() + Duration::from_secs(120);
+    let mut last_size = None;
+    loop {
+        std::thread::sleep(Duration::from_millis(500));
+        let size = run(DeviceShellCmd::new().literal("stat").literal("-c").literal("%s").user_input(&remote.path))?
+            .trim()
+            .parse::<u64>()
+            .ok();
+        if size.is_some_and(|n| n > 0) && size == last_size {
+            break;
+        }
+        if std::time::Instant::now() > deadline {
+            bail!("Timed out waiting for the heap dump of {}", package);
+        }
+        last_size = size;
+    }
+
+    let local = tempfile::Builder::new()
+        .prefix("cim-heap-")
+        .suffix(".hprof")
+        .tempfile()
+        .context("Cannot create a local file for the heap dump")?;
+    let local_str = local.path().display().to_string();
+    let pulled = adb_exec(device, &["pull", &remote.path, &local_str], None)?;
+    drop(remote);
+    if !pulled.status.success() {
+        bail!("adb pull failed: {}", String::from_utf8_lossy(&pulled.stderr));
+    }
+    let data = std::fs::read(local.path()).with_context(|| format!("Cannot read {}", local_str))?;
+    drop(local);
+    let instances = crate::utils::hprof::count_instances(&data)
+        .with_context(|| format!("Cannot parse heap dump of {}", package))?;
+    let taken_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    Ok(HeapSnapshot { package: package.to_string(), taken_at, instances })
+}
+
+/// Classes with more live instances in `after` than in `before`, largest
+/// growth first.
+pub fn compare_heaps(before: &HeapSnapshot, after: &HeapSnapshot) -> Vec<HeapGrowth> {
+    let mut grown: Vec<HeapGrowth> = after
+        .instances
+        .iter()
+        .map(|(class, &n)| HeapGrowth {
+            class: class.clone(),
+            before: before.instances.get(class).copied().unwrap_or(0),
+            after: n,
+        })
+        .filter(|g| g.after > g.before)
+        .collect();
+    grown.sort_by(|a, b| b.delta().cmp(&a.delta()).then_with(|| a.class.cmp(&b.class)));
+    grown
+}
+
+// ============== Frame Stream ==============
+//
+// A one-shot `screencap -p` pays for an adb process spawn, a device-side
+// `screencap` launch and PNG compression on every call. `FrameStream` keeps a
+// single `adb exec-out` pipeline running a `screencap` loop in *raw* mode and
+// decodes frames on a background thread, so `latest()` returns immediately.
+//
+// Tradeoffs:
+// - CPU: the device runs screencap back to back for as long as the stream is
+//   open (roughly one core), which can skew perf measurements and battery.
+// - Bandwidth: raw frames are width*height*4 bytes (~10 MB at 1080x2400), so
+//   the frame rate is bounded by the USB/TCP link, typically 5-15 fps.
+// - Buffering: only the newest frame is kept; older ones are dropped, so
+//   `latest()` can be up to one frame interval stale but memory stays flat.
+// - `screenrecord --output-format=raw-frames` was not used: after its first
+//   frame it only emits on screen changes, so a still screen looks the same
+//   as a stalled pipeline, and it stops after three minutes.
+
+/// Screencap raw pixel formats (`android.graphics.PixelFormat`).
+const RAW_FORMAT_RGBA_8888: u32 = 1;
+const RAW_FORMAT_RGBX_8888: u32 = 2;
+const RAW_FORMAT_BGRA_8888: u32 = 5;
+
+/// Raw screencap output starts with width, height and format (u32 LE); newer
+/// releases append a colour-space word. Work out which from a full frame.
+fn raw_header_len(frame: &[u8]) -> Result<usize> {
+    if frame.len() < 12 {
+        bail!("Raw screencap output too short ({} bytes)", frame.len());
+    }
+    let word = |i: usize| u32::from_le_bytes(frame[i * 4..i * 4 + 4].try_into().unwrap()) as usize;
+    let pixels = word(0) * word(1) * 4;
+    match frame.len().checked_sub(pixels) {
+        Some(n @ (12 | 16)) => Ok(n),
+        _ => bail!(
+            "Unexpected raw screencap size {} for {}x{} (format {})",
+            frame.len(), word(0), word(1), word(2)
+        ),
+    }
+}
+
+/// Decode raw screencap pixels into an image.
+fn decode_raw_frame(width: u32, height: u32, format: u32, mut pixels: Vec<u8>) -> Result<image::DynamicImage> {
+    match format {
+        RAW_FORMAT_RGBA_8888 => {}
+        RAW_FORMAT_RGBX_8888 => pixels.chunks_exact_mut(4).for_each(|p| p[3] = 255),
+        RAW_FORMAT_BGRA_8888 => pixels.chunks_exact_mut(4).for_each(|p| p.swap(0, 2)),
+        other => bail!("Unsupported raw screencap pixel format {}", other),
+    }
+    image::RgbaImage::from_raw(width, height, pixels)
+        .map(image::DynamicImage::ImageRgba8)
+        .context("Raw screencap frame has the wrong size")
+}
+
+/// Read one frame (header + pixels) from a raw screencap stream.
+/// Returns `Ok(None)` on a clean end of stream.
+fn read_raw_frame(reader: &mut impl std::io::Read, header_len: usize) -> Result<Option<image::DynamicImage>> {
+    let mut header = vec![0u8; header_len];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e).context("Failed to read frame header"),
+    }
+    let word = |i: usize| u32::from_le_bytes(header[i * 4..i * 4 + 4].try_into().unwrap());
+    let (width, height, format) = (word(0), word(1), word(2));
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    reader.read_exact(&mut pixels).context("Frame stream ended mid-frame")?;
+    decode_raw_frame(width, height, format, pixels).map(Some)
+}
+
+#[derive(Default)]
+struct FrameSlot {
+    frame: Option<std::sync::Arc<image::DynamicImage>>,
+    count: u64,
+    error: Option<String>,
+    ended: bool,
+}
+
+/// Continuous screen capture over one long-lived adb pipeline.
+pub struct FrameStream {
+    child: std::process::Child,
+    slot: std::sync::Arc<(std::sync::Mutex<FrameSlot>, std::sync::Condvar)>,
+    reader: Option<std::thread::JoinHandle<()>>,
+}
+
+impl FrameStream {
+    /// How long `latest()` waits for the first frame after `start`.
+    const FIRST_FRAME_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Probe the raw frame layout and start streaming.
+    pub fn start(device: Option<&str>) -> Result<Self> {
+        let probe = adb_exec(device, &["exec-out", "screencap"], None)?;
+        if !probe.status.success() {
+            bail!("adb screencap failed: {}", String::from_utf8_lossy(&probe.stderr));
+        }
+        let header_len = raw_header_len(&probe.stdout)?;
+
+        let mut child = adb_cmd(device)
+            .args(["exec-out", "while true; do screencap; done"])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .context("Failed to start adb frame stream")?;
+        let mut stdout = child.stdout.take().context("adb frame stream has no stdout")?;
+
+        let slot = std::sync::Arc::new((std::sync::Mutex::new(FrameSlot::default()), std::sync::Condvar::new()));
+        let shared = slot.clone();
+        let reader = std::thread::spawn(move || {
+            let (lock, cvar) = &*shared;
+            loop {
+                let result = read_raw_frame(&mut stdout, header_len);
+                let mut s = lock.lock().unwrap();
+                match result {
+                    Ok(Some(frame)) => {
+                        s.frame = Some(std::sync::Arc::new(frame));
+                        s.count += 1;
+                    }
+                    Ok(None) => s.ended = true,
+                    Err(e) => {
+                        s.error = Some(format!("{:#}", e));
+                        s.ended = true;
+                    }
+                }
+                cvar.notify_all();
+                if s.ended {
+                    return;
+                }
+            }
+        });
+
+        Ok(FrameStream { child, slot, reader: Some(reader) })
+    }
+
+    /// The most recent frame. Blocks only until the first frame arrives.
+    pub fn latest(&self) -> Result<std::sync::Arc<image::DynamicImage>> {
+        let (lock, cvar) = &*self.slot;
+        let (s, _) = cvar
+            .wait_timeout_while(lock.lock().unwrap(), Self::FIRST_FRAME_TIMEOUT, |s| {
+                s.frame.is_none() && !s.ended
+            })
+            .unwrap();
+        if s.ended {
+            bail!(
+                "Frame stream stopped: {}",
+                s.error.as_deref().unwrap_or("adb exited (device disconnected?)")
+            );
+        }
+        s.frame.clone().context("No frame received from the device within 10s")
+    }
+
+    /// Number of frames decoded since `start`.
+    pub fn frame_count(&self) -> u64 {
+        self.slot.0.lock().unwrap().count
+    }
+}
+
+impl Drop for FrameStream {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+// ============== WebView DevTools ==============
+//
+// A debuggable WebView listens on an abstract unix socket
+// (`webview_devtools_remote_<pid>`) speaking the Chrome DevTools protocol.
+// `eval_js` forwards it to a local port, picks a page from `/json/list` and
+// runs `Runtime.evaluate` over a WebSocket. The WebSocket client is the bare
+// minimum for one request/response: text frames, client masking, no
+// extensions.
+
+/// How long to wait for the WebView to answer one evaluation.
+const DEVTOOLS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// WebView DevTools sockets in `/proc/net/unix` output, optionally only
+/// `pid`'s; with `browser`, Chrome's own sockets (`chrome_devtools_remote`)
+/// instead, which belong to the browser rather than an app.
+fn devtools_sockets(proc_net_unix: &str, pid: Option<&str>, browser: bool) -> Vec<String> {
+    proc_net_unix
+        .lines()
+        .filter_map(|line| line.split_whitespace().last()?.strip_prefix('@'))
+        .filter(|name| match browser {
+            false => name.starts_with("webview_devtools_remote"),
+            true => !name.starts_with("webview_") && name.ends_with("_devtools_remote"),
+        })
+        .filter(|name| browser || pid.is_none_or(|p| name.rsplit('_').next() == Some(p)))
+        .map(str::to_owned)
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// One client-to-server WebSocket text frame; clients must mask.
+fn ws_text_frame(payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+    let mut frame = vec![0x81];
+    match payload.len() {
+        n if n < 126 => frame.push(0x80 | n as u8),
+        n if n <= u16::MAX as usize => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        n => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(n as u64).to_be_bytes());
         }
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    frame
+}
 
-        if in_data_section {
-            let cols: Vec<&str> = t.split(',').collect();
-            if cols.len() >= 14 {
-                let intended: u64 = cols[0].trim().parse().unwrap_or(0);
-                let completed: u64 = cols[13].trim().parse().unwrap_or(0);
-                if completed > intended && intended > 0 {
-                    let duration_ns = completed - intended;
-                    frame_durations_ms.push(duration_ns as f64 / 1_000_000.0);
-                }
+/// Read one whole WebSocket message (joining continuation frames).
+/// Control frames other than close are skipped.
+fn ws_read_message(reader: &mut impl std::io::Read) -> Result<String> {
+    let mut message = Vec::new();
+    loop {
+        let mut head = [0u8; 2];
+        reader.read_exact(&mut head).context("WebView DevTools connection closed")?;
+        let (fin, opcode) = (head[0] & 0x80 != 0, head[0] & 0x0f);
+        let len = match head[1] & 0x7f {
+            126 => {
+                let mut b = [0u8; 2];
+                reader.read_exact(&mut b)?;
+                u16::from_be_bytes(b) as u64
+            }
+            127 => {
+                let mut b = [0u8; 8];
+                reader.read_exact(&mut b)?;
+                u64::from_be_bytes(b)
             }
+            n => n as u64,
+        };
+        let mut mask = [0u8; 4];
+        if head[1] & 0x80 != 0 {
+            reader.read_exact(&mut mask)?;
+        }
+        let mut payload = vec![0u8; len as usize];
+        reader.read_exact(&mut payload)?;
+        payload.iter_mut().enumerate().for_each(|(i, b)| *b ^= mask[i % 4]);
+        match opcode {
+            0x8 => bail!("WebView DevTools closed the connection"),
+            0x9 | 0xA => continue,
+            _ => message.extend_from_slice(&payload),
+        }
+        if fin {
+            return Ok(String::from_utf8_lossy(&message).into_owned());
         }
     }
+}
 
-    // If summary wasn't in dumpsys output, compute from raw frame data
-    if total_frames == 0 && !frame_durations_ms.is_empty() {
-        total_frames = frame_durations_ms.len() as u64;
-        // 16.67ms = 60fps target
-        janky_frames = frame_durations_ms.iter().filter(|&&d| d > 16.67).count() as u64;
+/// The value of a `Runtime.evaluate` reply, or the page's exception.
+fn devtools_eval_value(reply: &serde_json::Value) -> Result<serde_json::Value> {
+    if let Some(error) = reply.get("error") {
+        bail!("DevTools error: {}", error["message"].as_str().unwrap_or("unknown"));
+    }
+    let result = &reply["result"];
+    if let Some(details) = result.get("exceptionDetails") {
+        let text = details["exception"]["description"].as_str().or(details["text"].as_str()).unwrap_or("exception");
+        bail!("JavaScript threw: {}", text);
     }
+    Ok(result["result"].get("value").cloned().unwrap_or(serde_json::Value::Null))
+}
 
-    let janky_percent = if total_frames > 0 {
-        (janky_frames as f64 / total_frames as f64) * 100.0
-    } else {
-        0.0
+/// Evaluate `expression` in the app's WebView and return its JSON value.
+/// `package` narrows the search to that app's WebViews and `page` to a page
+/// whose URL or title contains it; otherwise the first page is used. With
+/// `browser`, the page is looked for in Chrome instead.
+pub fn eval_js(
+    device: Option<&str>,
+    expression: &str,
+    package: Option<&str>,
+    page: Option<&str>,
+    browser: bool,
+) -> Result<serde_json::Value> {
+    use base64::Engine as _;
+    use std::io::Write;
+
+    let pid = match package.filter(|_| !browser) {
+        Some(pkg) => {
+            let cmd = DeviceShellCmd::new().literal("pidof").validated(pkg, validate_package_name)?.render();
+            let out = adb_exec(device, &["shell", &cmd], None)?;
+            let pid = String::from_utf8_lossy(&out.stdout).split_whitespace().next().map(str::to_owned);
+            Some(pid.with_context(|| format!("{} is not running", pkg))?)
+        }
+        None => None,
+    };
+    let unix = adb_exec(device, &["shell", "cat", "/proc/net/unix"], None)?;
+    let sockets = devtools_sockets(&String::from_utf8_lossy(&unix.stdout), pid.as_deref(), browser);
+    let Some(socket) = sockets.first() else {
+        if browser {
+            bail!("Chrome is not running or has no DevTools socket");
+        }
+        bail!(
+            "No debuggable WebView{} found. WebView debugging must be on: call \
+             WebView.setWebContentsDebuggingEnabled(true) in the app (debug builds of most hybrid frameworks do), \
+             then open a screen with a WebView",
+            package.map(|p| format!(" in {}", p)).unwrap_or_default()
+        );
     };
 
-    let result = serde_json::json!({
-        "package": package,
-        "totalFrames": total_frames,
-        "jankyFrames": janky_frames,
-        "jankyPercent": format!("{:.2}", janky_percent),
-        "percentiles": {
-            "p50ms": p50_ms,
-            "p90ms": p90_ms,
-            "p95ms": p95_ms,
-            "p99ms": p99_ms,
-        },
-        "rawFrameCount": frame_durations_ms.len(),
-        "note": "Janky = frame > 16.67ms (< 60 fps). Run app activity before capturing."
-    });
+    // The guard removes the forward however the evaluation ends.
+    let mapping = forward("tcp:0", &format!("localabstract:{}", socket), device)?;
+    let port: u16 = mapping.local().trim_start_matches("tcp:").parse()?;
+    (|| -> Result<serde_json::Value> {
+        let client = reqwest::blocking::Client::builder().timeout(DEVTOOLS_TIMEOUT).build()?;
+        let pages: Vec<serde_json::Value> = client
+            .get(format!("http://127.0.0.1:{}/json/list", port))
+            .send()
+            .and_then(|r| r.json())
+            .context("WebView DevTools did not list its pages")?;
+        let matches = |p: &&serde_json::Value| {
+            p["type"] == "page"
+                && page.is_none_or(|q| [&p["url"], &p["title"]].iter().any(|v| v.as_str().is_some_and(|s| s.contains(q))))
+        };
+        let target = pages.iter().find(matches).with_context(|| match page {
+            Some(q) => format!("No WebView page whose URL or title contains '{}'", q),
+            None => "The WebView has no page loaded".to_owned(),
+        })?;
+        let ws_url = target["webSocketDebuggerUrl"]
+            .as_str()
+            .context("WebView page is already being debugged (close chrome://inspect and retry)")?;
+        let path = ws_url.split_once("://").and_then(|(_, rest)| rest.find('/').map(|i| &rest[i..])).unwrap_or("/");
+
+        let mut stream = std::net::TcpStream::connect(("127.0.0.1", port))?;
+        stream.set_read_timeout(Some(DEVTOOLS_TIMEOUT))?;
+        let nonce = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos().to_le_bytes();
+        let key = base64::engine::general_purpose::STANDARD.encode(nonce);
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: 127.0.0.1:{}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            path, port, key
+        )?;
+        let mut head = Vec::new();
+        let mut byte = [0u8; 1];
+        while !head.ends_with(b"\r\n\r\n") {
+            std::io::Read::read_exact(&mut stream, &mut byte).context("WebView DevTools handshake failed")?;
+            head.push(byte[0]);
+        }
+        let status = String::from_utf8_lossy(&head);
+        if !status.lines().next().is_some_and(|l| l.contains(" 101")) {
+            bail!("WebView DevTools refused the connection: {}", status.lines().next().unwrap_or(""));
+        }
 
-    println!("{}", serde_json::to_string_pretty(&result)?);
-    Ok(())
+        let request = serde_json::json!({
+            "id": 1,
+            "method": "Runtime.evaluate",
+            "params": { "expression": expression, "returnByValue": true, "awaitPromise": true },
+        });
+        stream.write_all(&ws_text_frame(request.to_string().as_bytes(), [nonce[0], nonce[3], nonce[5], nonce[7]]))?;
+        loop {
+            let reply: serde_json::Value = serde_json::from_str(&ws_read_message(&mut stream)?)?;
+            // Events without an id may arrive before the reply.
+            if reply["id"] == 1 {
+                return devtools_eval_value(&reply);
+            }
+        }
+    })()
+}
+
+/// JavaScript that is `true` when the page has an element matching `selector`.
+pub fn selector_exists_js(selector: &str) -> String {
+    format!("document.querySelector({}) !== null", serde_json::Value::String(selector.to_owned()))
 }
 
 // ============== Shared Helpers ==============
 
+/// The device clock, in epoch seconds (for `logcat -T`).
+fn device_epoch_secs(device: Option<&str>) -> Result<u64> {
+    let output = adb_exec(device, &["shell", "date", "+%s"], None)?;
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .context("Could not read the device clock")
+}
+
+/// Read a system property; unset properties read as "".
+/// Serial and model of `device` (the default device when `None`).
+pub fn device_identity(device: Option<&str>) -> Result<(String, String)> {
+    let serial = match device {
+        Some(s) => s.to_string(),
+        None => {
+            let output = adb_exec(None, &["get-serialno"], None)?;
+            if !output.status.success() {
+                bail!("adb get-serialno failed: {}", String::from_utf8_lossy(&output.stderr));
+            }
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        }
+    };
+    Ok((serial, getprop("ro.product.model", device)?))
+}
+
+fn getprop(name: &'static str, device: Option<&str>) -> Result<String> {
+    let output = adb_exec(device, &["shell", "getprop", name], None)?;
+    if !output.status.success() {
+        bail!("getprop {} failed: {}", name, String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
 /// Validate that a string looks like an Android package name (e.g. com.example.app).
 fn validate_package_name(package: &str) -> Result<()> {
     if package.is_empty() {
@@ -2604,6 +6898,22 @@ fn append_extras_to_cmd(mut cmd: DeviceShellCmd, extras_json: &str) -> Result<De
 mod tests {
     use super::*;
 
+    #[test]
+    fn find_button_matches_whole_labels_on_clickable_buttons() {
+        let xml = r#"
+            <node class="android.widget.Button" text="Uninstall" bounds="[0,0][100,50]" clickable="true" resource-id="" content-desc=""/>
+            <node class="android.widget.TextView" text="Install" bounds="[0,50][100,100]" clickable="false" resource-id="" content-desc=""/>
+            <node class="android.widget.Button" text="UPDATE" bounds="[100,0][300,50]" clickable="true" resource-id="" content-desc=""/>
+        "#;
+        assert_eq!(find_button(xml, &["Install"]), None);
+        assert_eq!(find_button(xml, &["Install", "Update"]), Some((200, 25)));
+
+        let disabled = r#"<node class="android.widget.Button" text="Install" bounds="[0,0][100,50]" clickable="false" resource-id="" content-desc=""/>"#;
+        assert_eq!(find_button(disabled, &["Install"]), None);
+        let by_desc = r#"<node class="android.widget.Button" text="" bounds="[0,0][100,50]" clickable="true" resource-id="" content-desc="Install"/>"#;
+        assert_eq!(find_button(by_desc, &["Install"]), Some((50, 25)));
+    }
+
     #[test]
     fn test_parse_ui_elements() {
         let xml = r#"
@@ -2718,4 +7028,756 @@ mod tests {
         assert!(validate_package_name("com.example/app").is_err());
         assert!(validate_package_name("../etc/passwd").is_err());
     }
+
+    // ===== Multi-touch tests =====
+
+    const GETEVENT_SAMPLE: &str = "add device 1: /dev/input/event3\n  name:     \"gpio-keys\"\n  events:\n    KEY (0001): KEY_VOLUMEDOWN\nadd device 2: /dev/input/event2\n  name:     \"virtio_input_multi_touch_6\"\n  events:\n    ABS (0003): ABS_MT_SLOT           : value 0, min 0, max 9, fuzz 0, flat 0, resolution 0\n                ABS_MT_POSITION_X     : value 0, min 0, max 32767, fuzz 0, flat 0, resolution 0\n                ABS_MT_POSITION_Y     : value 0, min 0, max 32767, fuzz 0, flat 0, resolution 0\n                ABS_MT_TRACKING_ID    : value 0, min 0, max 10, fuzz 0, flat 0, resolution 0\n";
+
+    #[test]
+    fn test_parse_touch_device() {
+        let touch = parse_touch_device(GETEVENT_SAMPLE).expect("touch device");
+        assert_eq!(touch.path, "/dev/input/event2");
+        assert_eq!((touch.min_x, touch.max_x), (0, 32767));
+        assert_eq!(touch.max_pointers, 10);
+        assert!(parse_touch_device("add device 1: /dev/input/event0\n  name: \"keys\"\n").is_none());
+    }
+
+    #[test]
+    fn test_validate_input_device_path() {
+        assert!(validate_input_device_path("/dev/input/event2").is_ok());
+        assert!(validate_input_device_path("/dev/input/event").is_err());
+        assert!(validate_input_device_path("/dev/input/event2; reboot").is_err());
+    }
+
+    #[test]
+    fn test_to_touch_coord_scales_to_abs_range() {
+        assert_eq!(to_touch_coord(0, 1080, 0, 32767), 0);
+        assert_eq!(to_touch_coord(1079, 1080, 0, 32767), 32767);
+        assert_eq!(to_touch_coord(5000, 1080, 0, 1079), 1079);
+    }
+
+    #[test]
+    fn test_pointer_path_interpolation() {
+        let path = PointerPath { points: vec![(0, 0), (100, 0), (100, 100)] };
+        assert_eq!(path.position_at(0.0), (0, 0));
+        assert_eq!(path.position_at(0.5), (100, 0));
+        assert_eq!(path.position_at(0.75), (100, 50));
+        assert_eq!(path.position_at(1.0), (100, 100));
+    }
+
+    #[test]
+    fn test_build_multi_touch_events_lifts_all_pointers() {
+        let touch = parse_touch_device(GETEVENT_SAMPLE).unwrap();
+        let pointers = [
+            PointerPath { points: vec![(100, 500), (300, 500)] },
+            PointerPath { points: vec![(900, 500), (700, 500)] },
+        ];
+        let events = build_multi_touch_events(&touch, (1080, 1920), &pointers, 4);
+        let downs = events.iter().flatten().filter(|e| e.1 == ABS_MT_TRACKING_ID && e.2 >= 0).count();
+        let ups = events.iter().flatten().filter(|e| e.1 == ABS_MT_TRACKING_ID && e.2 == -1).count();
+        assert_eq!(downs, 2);
+        assert_eq!(ups, 2);
+        assert_eq!(events.iter().filter(|e| e.is_none()).count(), 5);
+        assert_eq!(events.last(), Some(&Some((EV_SYN, 0, 0))));
+    }
+
+    // ===== Broadcast / service-call tests =====
+
+    #[test]
+    fn test_parse_broadcast_reply() {
+        let raw = "Broadcasting: Intent { act=com.example.PING flg=0x400000 }\nBroadcast completed: result=-1, data=\"pong\"\n";
+        let reply = parse_broadcast_reply(raw);
+        assert_eq!(reply.result, Some(-1));
+        assert_eq!(reply.data.as_deref(), Some("pong"));
+
+        let reply = parse_broadcast_reply("Broadcast completed: result=0\n");
+        assert_eq!(reply.result, Some(0));
+        assert_eq!(reply.data, None);
+    }
+
+    #[test]
+    fn test_parse_parcel_reply_int() {
+        let reply = parse_parcel_reply("Result: Parcel(00000000 00000001   '........')\n");
+        assert_eq!(reply.words, vec![0, 1]);
+        assert_eq!(reply.exception, Some(0));
+        assert_eq!(reply.int_value, Some(1));
+    }
+
+    #[test]
+    fn test_parse_parcel_reply_string16() {
+        let raw = "Result: Parcel(\n  0x00000000: 00000000 00000004 00650074 00740073 '........t.e.s.t.'\n  0x00000010: 00000000                            '....            ')\n";
+        let reply = parse_parcel_reply(raw);
+        assert_eq!(reply.words.len(), 5);
+        assert_eq!(reply.string_value.as_deref(), Some("test"));
+    }
+
+    #[test]
+    fn test_service_call_arg_parse() {
+        assert_eq!(ServiceCallArg::parse("i32:5").unwrap(), ServiceCallArg::I32(5));
+        assert_eq!(ServiceCallArg::parse("s16:a:b").unwrap(), ServiceCallArg::S16("a:b".into()));
+        assert_eq!(ServiceCallArg::parse("null").unwrap(), ServiceCallArg::Null);
+        assert!(ServiceCallArg::parse("i32:x").is_err());
+        assert!(ServiceCallArg::parse("blob:1").is_err());
+    }
+
+    #[test]
+    fn test_webview_devtools_helpers() {
+        let unix = "Num       RefCount Protocol Flags    Type St Inode Path\n\
+                    0000000000000000: 00000002 00000000 00010000 0001 01 52511 @webview_devtools_remote_4321\n\
+                    0000000000000000: 00000002 00000000 00010000 0001 01 52512 @chrome_devtools_remote\n\
+                    0000000000000000: 00000002 00000000 00010000 0001 01 12345 /dev/socket/zygote\n";
+        assert_eq!(devtools_sockets(unix, None, false), vec!["webview_devtools_remote_4321"]);
+        assert_eq!(devtools_sockets(unix, Some("4321"), false), vec!["webview_devtools_remote_4321"]);
+        assert!(devtools_sockets(unix, Some("99"), false).is_empty());
+        assert_eq!(devtools_sockets(unix, None, true), vec!["chrome_devtools_remote"]);
+
+        let frame = ws_text_frame(b"hi", [1, 2, 3, 4]);
+        assert_eq!(frame, vec![0x81, 0x82, 1, 2, 3, 4, b'h' ^ 1, b'i' ^ 2]);
+        assert_eq!(ws_text_frame(&[0; 300], [0; 4])[1..4], [0x80 | 126, 1, 44]);
+        // Server frames are unmasked; a ping in between is skipped and
+        // continuation frames are joined.
+        let mut wire: &[u8] = &[0x89, 0x00, 0x01, 0x03, b'{', b'"', b'a', 0x80, 0x04, b'"', b':', b'1', b'}'];
+        assert_eq!(ws_read_message(&mut wire).unwrap(), r#"{"a":1}"#);
+
+        let ok = serde_json::json!({"id": 1, "result": {"result": {"type": "boolean", "value": true}}});
+        assert_eq!(devtools_eval_value(&ok).unwrap(), serde_json::json!(true));
+        let thrown = serde_json::json!({"id": 1, "result": {"result": {}, "exceptionDetails": {"text": "Uncaught", "exception": {"description": "ReferenceError: x is not defined"}}}});
+        assert!(devtools_eval_value(&thrown).unwrap_err().to_string().contains("ReferenceError"));
+        assert_eq!(selector_exists_js(".error[data-x=\"1\"]"), r#"document.querySelector(".error[data-x=\"1\"]") !== null"#);
+    }
+
+    #[test]
+    fn test_wait_for_text_sources() {
+        let xml = r#"<node index="0" text="" content-desc="Sign in" bounds="[10,20][110,60]" /><node index="1" text="Other" bounds="[0,0][1,1]" />"#;
+        assert_eq!(hierarchy_text(xml, "SIGN"), (Some((10, 20, 110, 60)), true));
+        assert_eq!(hierarchy_text(xml, "missing"), (None, true));
+        let canvas = r#"<node index="0" text="" class="android.view.SurfaceView" bounds="[0,0][1080,2400]" />"#;
+        assert_eq!(hierarchy_text(canvas, "Sign in"), (None, false));
+        let escaped = r#"<node index="0" text="Say &quot;hi&quot; &lt;3 &amp; more" bounds="[1,2][3,4]" />"#;
+        assert_eq!(hierarchy_text(escaped, "\"hi\" <3 & more"), (Some((1, 2, 3, 4)), true));
+
+        let tsv = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext\n\
+                   4\t1\t1\t1\t1\t0\t40\t100\t300\t40\t-1\t\n\
+                   5\t1\t1\t1\t1\t1\t40\t100\t90\t40\t96\tWelcome\n\
+                   5\t1\t1\t1\t1\t2\t140\t102\t60\t38\t95\tback,\n\
+                   5\t1\t1\t1\t1\t3\t210\t100\t130\t40\t91\tDana\n\
+                   5\t1\t1\t1\t2\t1\t40\t200\t90\t40\t90\tback\n";
+        assert_eq!(ocr_text_bounds(tsv, "welcome back"), Some((40, 100, 200, 140)));
+        assert_eq!(ocr_text_bounds(tsv, "dana"), Some((210, 100, 340, 140)));
+        assert_eq!(ocr_text_bounds(tsv, "back dana"), None);
+        let m = TextMatch { bounds: (40, 100, 200, 140), source: TextSource::Ocr };
+        assert_eq!((m.center(), m.source.as_str()), ((120, 120), "ocr"));
+    }
+
+    #[test]
+    fn test_dialog_handler_policy() {
+        let node = |pkg: &str, id: &str, text: &str, bounds: &str| {
+            format!(r#"<node index="0" text="{}" resource-id="{}" package="{}" bounds="{}" />"#, text, id, pkg, bounds)
+        };
+        let anr = [
+            node("android", "android:id/alertTitle", "Maps isn't responding", "[0,0][100,50]"),
+            node("android", "android:id/aerr_close", "Close app", "[0,100][200,140]"),
+            node("android", "android:id/aerr_wait", "Wait", "[0,150][200,190]"),
+        ]
+        .concat();
+        let handler = DialogHandler::default();
+        let handled = handler.check(&anr).unwrap();
+        assert_eq!((handled.kind, handled.response, handled.button.as_str()), (SystemDialog::Anr, "wait", "Wait"));
+        assert_eq!(handled.tap, (100, 170));
+        let closing = DialogHandler { policy: DialogPolicy::parse("anr=close, permission=deny").unwrap() };
+        assert_eq!(closing.check(&anr).unwrap().button, "Close app");
+
+        let permission = [
+            node("com.android.permissioncontroller", "com.android.permissioncontroller:id/permission_allow_foreground_only_button", "While using the app", "[0,0][10,10]"),
+            node("com.android.permissioncontroller", "com.android.permissioncontroller:id/permission_deny_button", "Don't allow", "[0,20][10,30]"),
+        ]
+        .concat();
+        assert_eq!(handler.check(&permission).unwrap().button, "While using the app");
+        assert_eq!(closing.check(&permission).unwrap().button, "Don't allow");
+        let ignoring = DialogHandler { policy: DialogPolicy::parse("permission=ignore").unwrap() };
+        assert!(ignoring.check(&permission).is_none());
+
+        let update = [
+            node("com.android.vending", "", "Update available", "[0,0][10,10]"),
+            node("com.android.vending", "", "Not now", "[0,20][10,30]"),
+        ]
+        .concat();
+        assert_eq!(handler.check(&update).unwrap().kind, SystemDialog::Update);
+        assert!(handler.check(&node("com.example", "com.example:id/ok", "Not now", "[0,0][1,1]")).is_none());
+
+        assert!(DialogPolicy::parse("anr=allow").is_err());
+        assert!(DialogPolicy::parse("toast=ignore").is_err());
+    }
+
+    #[test]
+    fn test_dnd_modes() {
+        for mode in [DndMode::Off, DndMode::Priority, DndMode::Alarms, DndMode::Silence] {
+            assert_eq!(DndMode::parse(mode.as_str()).unwrap(), mode);
+        }
+        assert!(DndMode::parse("on").is_err());
+        assert_eq!(DndMode::from_zen_mode("3\n"), Some(DndMode::Alarms));
+        assert_eq!(DndMode::from_zen_mode("2"), Some(DndMode::Silence));
+        assert_eq!(DndMode::from_zen_mode("null"), None);
+    }
+
+    #[test]
+    fn test_parse_clipboard_replies() {
+        assert_eq!(parse_cmd_clipboard("hello world\n").as_deref(), Some("hello world"));
+        assert_eq!(parse_cmd_clipboard("null\n").as_deref(), Some(""));
+        assert_eq!(
+            parse_cmd_clipboard("ClipData { text/plain \"label\" {T(5):hello} }\n").as_deref(),
+            Some("hello")
+        );
+        assert_eq!(parse_cmd_clipboard("No shell command implementation.\n"), None);
+        assert_eq!(parse_cmd_clipboard("Unknown command: get-primary-clip\n"), None);
+
+        let legacy = "Result: Parcel(0x00000000: 00000000 00000002 'hi\\nthere')";
+        assert_eq!(parse_service_call_clip(legacy), "hi\nthere");
+        assert!(clipboard_unsupported("read", 33).to_string().contains("unsupported on this OS (Android API 33)"));
+    }
+
+    #[test]
+    fn test_validate_service_name() {
+        assert!(validate_service_name("clipboard").is_ok());
+        assert!(validate_service_name("SurfaceFlinger").is_ok());
+        assert!(validate_service_name("clip; reboot").is_err());
+    }
+
+    #[test]
+    fn test_has_selection_toolbar() {
+        let toolbar = r#"<hierarchy><node class="android.widget.Button" text="Copy" resource-id="" content-desc="" bounds="[10,10][110,60]" clickable="true" /></hierarchy>"#;
+        assert!(has_selection_toolbar(toolbar));
+        let by_id = r#"<hierarchy><node class="android.widget.LinearLayout" text="" resource-id="android:id/floating_toolbar_menu_item_text" content-desc="" bounds="[0,0][1,1]" /></hierarchy>"#;
+        assert!(has_selection_toolbar(by_id));
+        let plain = r#"<hierarchy><node class="android.widget.TextView" text="Hello world" resource-id="" content-desc="" bounds="[0,0][500,80]" /></hierarchy>"#;
+        assert!(!has_selection_toolbar(plain));
+    }
+
+    #[test]
+    fn test_parse_night_mode() {
+        assert_eq!(parse_night_mode("Night mode: yes\n").unwrap(), "yes");
+        assert_eq!(parse_night_mode("Night mode: no").unwrap(), "no");
+        assert_eq!(parse_night_mode("Night mode: custom_bedtime").unwrap(), "custom_bedtime");
+        // What night_mode returns goes back through as is.
+        assert_eq!(parse_night_mode("custom").unwrap(), "custom");
+        assert!(parse_night_mode("Night mode: ").is_err());
+        assert!(parse_night_mode("custom; reboot").is_err());
+    }
+
+    #[test]
+    fn test_watchdog_state_sustained_cpu_and_memory_cap() {
+        let limits = WatchdogLimits { cpu_percent: Some(90.0), cpu_window_ms: 10_000, memory_mb: Some(512.0) };
+        let fresh = || WatchdogState { limits, cpu_over_since: None, report: WatchdogReport::default() };
+
+        // A spike that drops back resets the window.
+        let mut s = fresh();
+        s.observe(0, 95.0, 100.0);
+        s.observe(6_000, 40.0, 110.0);
+        s.observe(12_000, 99.0, 120.0);
+        s.observe(20_000, 97.0, 120.0);
+        assert_eq!(s.report.violation, None);
+        s.observe(22_000, 96.0, 130.0);
+        assert!(s.report.violation.as_deref().unwrap().contains("CPU above 90% for 10.0s"));
+        assert_eq!((s.report.peak_cpu_percent, s.report.peak_memory_mb), (99.0, 130.0));
+
+        let mut s = fresh();
+        s.observe(0, 10.0, 600.0);
+        assert!(s.report.violation.as_deref().unwrap().contains("600.0MB exceeded the 512MB cap"));
+    }
+
+    #[test]
+    fn test_parse_cpu_ticks() {
+        let out = "4321\n4321 (com.example app) S 600 600 0 0 -1 1077952832 9000 0 12 0 250 75 0 0 10 -10 40 0 1234\n";
+        assert_eq!(parse_cpu_ticks(out), Some((4321, 325)));
+        assert_eq!(parse_cpu_ticks(""), None);
+        assert_eq!(parse_cpu_ticks("4321\n"), None);
+    }
+
+    #[test]
+    fn test_compare_heaps() {
+        let snap = |pairs: &[(&str, u64)]| HeapSnapshot {
+            package: "com.example".into(),
+            taken_at: 0,
+            instances: pairs.iter().map(|(c, n)| (c.to_string(), *n)).collect(),
+        };
+        let before = snap(&[("com.example.Activity", 1), ("byte[]", 500), ("com.example.Gone", 3)]);
+        let after = snap(&[("com.example.Activity", 6), ("byte[]", 502), ("com.example.Listener", 4)]);
+        let grown = compare_heaps(&before, &after);
+        let summary: Vec<(&str, u64)> = grown.iter().map(|g| (g.class.as_str(), g.delta())).collect();
+        assert_eq!(summary, [("com.example.Activity", 5), ("com.example.Listener", 4), ("byte[]", 2)]);
+    }
+
+    #[test]
+    fn test_adb_network_output_parsing() {
+        let t = "192.168.1.20:5555";
+        assert_eq!(parse_connect_output(t, "connected to 192.168.1.20:5555\n"), Ok(()));
+        assert_eq!(
+            parse_connect_output(t, "already connected to 192.168.1.20:5555\n"),
+            Err(AdbNetworkError::AlreadyConnected(t.into()))
+        );
+        assert_eq!(
+            parse_connect_output(t, "failed to connect to '192.168.1.20:5555': Connection refused\n"),
+            Err(AdbNetworkError::UnableToConnect { target: t.into(), reason: "Connection refused".into() })
+        );
+        assert_eq!(
+            parse_pair_output(t, "Failed: Wrong password or connection was dropped.\n"),
+            Err(AdbNetworkError::PairingFailed { target: t.into(), reason: "Wrong password or connection was dropped.".into() })
+        );
+        assert_eq!(parse_pair_output(t, "Successfully paired to 192.168.1.20:37123 [guid=adb-XYZ]\n"), Ok(()));
+        assert_eq!(parse_disconnect_output(t, "disconnected 192.168.1.20:5555\n"), Ok(()));
+        assert_eq!(
+            parse_disconnect_output(t, "error: no such device '192.168.1.20:5555'\n"),
+            Err(AdbNetworkError::NotConnected(t.into()))
+        );
+
+        assert_eq!(network_target("lab-pixel-7.local", 5555).unwrap(), "lab-pixel-7.local:5555");
+        assert_eq!(network_target("fe80::1", 5555).unwrap(), "[fe80::1]:5555");
+        assert!(network_target("host;reboot", 5555).is_err());
+    }
+
+    #[test]
+    fn test_adb_port_output_parsing() {
+        assert_eq!(parse_port_output("tcp:8080", true, ""), Ok(None));
+        assert_eq!(parse_port_output("tcp:0", true, "41235\n"), Ok(Some(41235)));
+        assert_eq!(
+            parse_port_output("tcp:8080", false, "adb: error: cannot bind listener: Address already in use\n"),
+            Err(AdbPortError::CannotBind { spec: "tcp:8080".into(), reason: "Address already in use".into() })
+        );
+        assert_eq!(
+            parse_port_output(
+                "tcp:8080",
+                false,
+                "error: cannot bind listener: cannot bind to 127.0.0.1:8080: Address already in use (98)\n"
+            ),
+            Err(AdbPortError::CannotBind { spec: "tcp:8080".into(), reason: "Address already in use (98)".into() })
+        );
+        assert_eq!(
+            parse_port_output("tcp:8080", false, "adb: error: more than one device/emulator\n"),
+            Err(AdbPortError::Failed("more than one device/emulator".into()))
+        );
+
+        assert!(validate_socket_spec("tcp:8080").is_ok());
+        assert!(validate_socket_spec("localabstract:chrome_devtools_remote").is_ok());
+        assert!(validate_socket_spec("tcp:http").is_err());
+        assert!(validate_socket_spec("--remove-all").is_err());
+        assert!(validate_socket_spec("localabstract:a b").is_err());
+    }
+
+    #[test]
+    fn test_parse_focused_package() {
+        let dump = "  mCurrentFocus=Window{3c1a9e u0 com.example.app/com.example.app.MainActivity}\n";
+        assert_eq!(parse_focused_package(dump).as_deref(), Some("com.example.app"));
+        assert_eq!(parse_focused_package("  mCurrentFocus=Window{3c1a9e u0 StatusBar}"), None);
+        assert_eq!(parse_focused_package("  mCurrentFocus=null"), None);
+    }
+
+    #[test]
+    fn test_aggregate_perf_uses_frame_deltas() {
+        let r = |mem: f64, frames: u64, janky: u64, battery: u8| PerfReading {
+            memory_mb: mem,
+            cpu_percent: 10.0,
+            total_frames: frames,
+            janky_frames: janky,
+            battery_level: battery,
+        };
+        // Counters start at 5000/400 from earlier app use; only 200/20 are ours.
+        let sample = aggregate_perf("com.example", &[r(100.0, 5000, 400, 80), r(120.0, 5100, 410, 80), r(110.0, 5200, 420, 79)], 3000);
+        assert_eq!(sample.frames_rendered, 200);
+        assert_eq!(sample.janky_frames, 20);
+        assert!((sample.janky_percent - 10.0).abs() < 1e-9);
+        assert_eq!(sample.battery_drop, 1);
+        assert_eq!(sample.memory_mb.max, 120.0);
+        assert!((sample.memory_mb.avg - 110.0).abs() < 1e-9);
+
+        // App restarted mid-window: counters reset, take the last reading.
+        let restarted = aggregate_perf("com.example", &[r(1.0, 5000, 400, 80), r(1.0, 30, 3, 80)], 1000);
+        assert_eq!((restarted.frames_rendered, restarted.janky_frames), (30, 3));
+    }
+
+    #[test]
+    fn test_parse_gfx_counters() {
+        let text = "Stats since: 1234ns\nTotal frames rendered: 812\nJanky frames: 37 (4.56%)\n";
+        assert_eq!(parse_gfx_counters(text), (812, 37));
+        assert_eq!(parse_gfx_counters(""), (0, 0));
+    }
+
+    #[test]
+    fn test_parse_secure_focus() {
+        let dump = "\
+WINDOW MANAGER WINDOWS (dumpsys window windows)
+  Window #1 Window{1a2b u0 com.android.systemui/StatusBar}:
+    mAttrs={(0,0)(fillx63) ty=STATUS_BAR fmt=TRANSLUCENT
+      fl=NOT_FOCUSABLE SPLIT_TOUCH
+  Window #2 Window{9f3e u0 com.bank.app/com.bank.app.LoginActivity}:
+    mAttrs={(0,0)(fillxfill) ty=BASE_APPLICATION fmt=TRANSPARENT
+      fl=LAYOUT_IN_SCREEN SECURE SPLIT_TOUCH HARDWARE_ACCELERATED
+  mCurrentFocus=Window{9f3e u0 com.bank.app/com.bank.app.LoginActivity}
+";
+        assert_eq!(
+            parse_secure_focus(dump).as_deref(),
+            Some("com.bank.app/com.bank.app.LoginActivity")
+        );
+        // Same window without the flag, and a dump with no focus at all.
+        assert_eq!(parse_secure_focus(&dump.replace(" SECURE", "")), None);
+        assert_eq!(parse_secure_focus("  Window #0 Window{1 u0 x}:\n      fl=SECURE\n"), None);
+    }
+
+    #[test]
+    fn test_parse_version_name() {
+        let dump = "\
+Queries:
+  Package [com.example.app] queries: versionName=bogus
+Packages:
+  Package [com.example.app] (4f1c2d):
+    userId=10231
+    versionCode=4021 minSdk=24 targetSdk=34
+    versionName=4.2.1
+  Package [com.other] (9a9a):
+    versionName=1.0
+";
+        assert_eq!(parse_version_name(dump, "com.example.app").as_deref(), Some("4.2.1"));
+        assert_eq!(parse_version_name(dump, "com.other").as_deref(), Some("1.0"));
+        assert_eq!(parse_version_name(dump, "com.missing"), None);
+    }
+
+    #[test]
+    fn test_parse_notifications_old_and_new_formats() {
+        let old = "\
+  NotificationRecord(0x0a1b2c3d: pkg=com.old user=UserHandle{0} id=7 tag=null score=0)
+    pkg=com.old
+    id=7
+    channel=Channel{id=reminders, name=Reminders, importance=3}
+      android.title=Standup
+      android.text=In 5 minutes
+";
+        let new = "\
+    NotificationRecord(0x0f: pkg=com.chat user=UserHandle{0} id=42 tag=msg importance=4 key=0|com.chat|42|msg|10123: Notification(channel=dm))
+      extras={
+        android.title=String (Alice)
+        android.text=SpannableString (See you at 5)
+      }
+";
+        let parsed = parse_notifications(&format!("{}{}", old, new));
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(
+            (parsed[0].package.as_str(), parsed[0].id.as_str(), parsed[0].title.as_str(), parsed[0].channel.as_str()),
+            ("com.old", "7", "Standup", "reminders")
+        );
+        assert_eq!(parsed[0].tag, "");
+        assert_eq!(
+            (parsed[1].package.as_str(), parsed[1].tag.as_str(), parsed[1].title.as_str(), parsed[1].text.as_str()),
+            ("com.chat", "msg", "Alice", "See you at 5")
+        );
+    }
+
+    #[test]
+    fn test_toggle_talkback_service_keeps_others() {
+        let other = "com.example/.A11yService";
+        let on = toggle_service(other, TALKBACK_SERVICE, true);
+        assert_eq!(on, format!("{}:{}", other, TALKBACK_SERVICE));
+        assert_eq!(toggle_service(&on, TALKBACK_SERVICE, true), on);
+        assert_eq!(toggle_service(&on, TALKBACK_SERVICE, false), other);
+        assert_eq!(toggle_service("", TALKBACK_SERVICE, false), "");
+
+        assert!(talkback_bound("  Bound services:{Service[label=TalkBack, feedbackType...]}"));
+        assert!(!talkback_bound("  Bound services:{}\n  Enabled services:{}"));
+    }
+
+    #[test]
+    fn test_parse_talkback_log() {
+        let log = "\
+V/TalkBack( 2311): SpeechControllerImpl: Speaking: \"Submit, Button\"
+D/talkback( 2311): FeedbackController: processUtterance text=[Email, Edit box]
+I/ActivityManager(  600): Start proc com.example
+V/TalkBack( 2311): AccessibilityEventProcessor: event TYPE_VIEW_FOCUSED
+";
+        assert_eq!(parse_talkback_log(log), ["Submit, Button", "Email, Edit box"]);
+    }
+
+    #[test]
+    fn test_parse_screen_and_lock_state() {
+        assert!(parse_screen_on("  mWakefulness=Awake\n"));
+        assert!(!parse_screen_on("  mWakefulness=Asleep\n  Display Power: state=OFF\n"));
+
+        assert_eq!(parse_keyguard_showing("    mShowingLockscreen=true mShowingDream=false"), Some(true));
+        assert_eq!(
+            parse_keyguard_showing("  mDreamingLockscreen=false isStatusBarKeyguard=false mKeyguardShowing=false"),
+            Some(false)
+        );
+        assert_eq!(parse_keyguard_showing("mCurrentFocus=Window{1 u0 com.example}"), None);
+
+        assert_eq!(parse_lock_type("User State:\n  User 0\n    CredentialType: PIN\n"), LockType::Pin);
+        assert_eq!(parse_lock_type("    CredentialType: Pattern"), LockType::Pattern);
+        assert_eq!(parse_lock_type("    Quality: 262144"), LockType::Password);
+        assert_eq!(parse_lock_type("    Quality: 0"), LockType::None);
+        assert_eq!(parse_lock_type(""), LockType::Unknown);
+
+        assert!(validate_lock_secret("1234").is_ok());
+        assert!(validate_lock_secret("12;4").is_err());
+        assert!(validate_lock_secret("12").is_err());
+    }
+
+    #[test]
+    fn test_parse_alarms() {
+        let dump = "\
+  Pending alarm batches: 2
+Batch{3f2a num=1 start=123 end=456 flgs=0x1}:
+    RTC_WAKEUP #0: Alarm{8e3c type 0 when 1700000000000 com.example}
+      tag=*walarm*:com.example/.ReminderReceiver
+      type=0 whenElapsed=+5m0s when=2023-11-14 22:13:20.000
+    ELAPSED #1: Alarm{91 type 3 origWhen 98765 whenElapsed 98765 android}
+      tag=*alarm*:android.intent.action.TIME_TICK
+";
+        let alarms = parse_alarms(dump);
+        assert_eq!(alarms.len(), 2);
+        assert_eq!(alarms[0].kind, "RTC_WAKEUP");
+        assert_eq!(alarms[0].package, "com.example");
+        assert_eq!(alarms[0].when, 1_700_000_000_000);
+        assert_eq!(alarms[0].tag.as_deref(), Some("*walarm*:com.example/.ReminderReceiver"));
+        assert_eq!((alarms[1].kind.as_str(), alarms[1].package.as_str(), alarms[1].when), ("ELAPSED", "android", 98765));
+    }
+
+    #[test]
+    fn test_standby_bucket_name() {
+        assert_eq!(standby_bucket_name("40\n"), "rare");
+        assert_eq!(standby_bucket_name("10"), "active");
+        assert_eq!(standby_bucket_name("5"), "5");
+        assert!(set_standby_bucket("com.example", "sometimes", None).is_err());
+    }
+
+    #[test]
+    fn test_network_serials_and_device_lost() {
+        assert!(is_network_serial("192.168.1.20:5555"));
+        assert!(is_network_serial("phone.local:37000"));
+        assert!(!is_network_serial("emulator-5554"));
+        assert!(!is_network_serial("R58M123ABC"));
+        assert!(!is_network_serial(":5555"));
+
+        let err: anyhow::Error = DeviceLost { serial: Some("emulator-5554".into()), waited: Duration::from_secs(90) }.into();
+        assert_eq!(err.to_string(), "Device emulator-5554 did not come back within 90s");
+        assert!(err.downcast_ref::<DeviceLost>().is_some());
+    }
+
+    #[test]
+    fn test_parse_feature_list() {
+        let out = "feature:reqGlEsVersion=0x30002\nfeature:android.hardware.nfc\nfeature:android.hardware.camera\n";
+        assert_eq!(parse_feature_list(out), vec!["reqGlEsVersion", "android.hardware.nfc", "android.hardware.camera"]);
+    }
+
+    #[test]
+    fn test_parse_okhttp_log_and_har() {
+        let log = "\
+I/okhttp.OkHttpClient(1234): --> POST https://api.example.com/login (42-byte body)
+I/okhttp.OkHttpClient(1234): --> GET https://api.example.com/feed?page=1
+I/okhttp.OkHttpClient(1234): <-- 200 OK https://api.example.com/feed?page=1 (87ms, 1.2kB body)
+D/Other(99): --> not a request
+I/okhttp.OkHttpClient(1234): <-- 401 https://api.example.com/login (120ms, unknown-length body)
+";
+        let reqs = parse_okhttp_log(log);
+        assert_eq!(reqs.len(), 2);
+        assert!(reqs[0].matches("post", "/login", Some(401)));
+        assert!(reqs[1].matches("*", "/feed", Some(200)));
+        assert!(!reqs[1].matches("POST", "/feed", None));
+
+        let har = r#"{"log":{"entries":[
+            {"request":{"method":"POST","url":"https://t.example.com/collect"},"response":{"status":204}},
+            {"request":{"method":"GET","url":"https://t.example.com/x"},"response":{"status":0}}]}}"#;
+        let reqs = parse_har(har).unwrap();
+        assert!(reqs[0].matches("POST", "/collect", Some(204)));
+        assert_eq!(reqs[1].status, None);
+        assert!(parse_har("{}").is_err());
+    }
+
+    #[test]
+    fn test_check_element() {
+        let xml = r#"<hierarchy><node index="0" text="Remember me" resource-id="com.x:id/remember" class="android.widget.CheckBox" content-desc="" checked="false" enabled="true" visible-to-user="true" bounds="[0,0][100,50]" /><node index="1" text="Pay" resource-id="com.x:id/pay" class="android.widget.Button" content-desc="" checked="false" enabled="false" visible-to-user="true" bounds="[0,60][100,110]" /></hierarchy>"#;
+        let check = |q: &str, a: ElementAssertion| check_element(xml, q, &a);
+
+        let pay = check("pay", ElementAssertion::Enabled(true));
+        assert!(pay.found && !pay.passed);
+        assert_eq!(pay.message(), "Element 'pay' found but expected enabled=true, actual enabled=false");
+        assert!(check("remember", ElementAssertion::Checked(false)).passed);
+        assert!(check("Remember", ElementAssertion::TextEquals("Remember me".into())).passed);
+        assert!(!check("Remember", ElementAssertion::TextContains("Forget".into())).passed);
+        assert!(check("pay", ElementAssertion::Visible(true)).passed);
+
+        let missing = check("Cancel", ElementAssertion::TextEquals("Cancel".into()));
+        assert!(!missing.found && !missing.passed && missing.actual.is_none());
+        assert!(missing.message().starts_with("Element 'Cancel' not found"));
+        assert!(check("Cancel", ElementAssertion::Visible(false)).passed);
+        assert!(ElementAssertion::parse("text-equals", None).is_err());
+        assert_eq!(ElementAssertion::parse("disabled", None).unwrap(), ElementAssertion::Enabled(false));
+
+        let escaped = r#"<node index="0" text="Terms &amp; Conditions" resource-id="" content-desc="" bounds="[0,0][10,10]" />"#;
+        assert!(check_element(escaped, "Terms & C", &ElementAssertion::TextEquals("Terms & Conditions".into())).passed);
+    }
+
+    #[test]
+    fn test_wm_output_and_density_for_width() {
+        let out = "Physical density: 420\nOverride density: 480\n";
+        assert_eq!(parse_wm_value(out, "Physical"), Some("420"));
+        assert_eq!(parse_wm_value(out, "Override"), Some("480"));
+        assert_eq!(parse_wm_value("Physical size: 1080x2400\n", "Physical"), Some("1080x2400"));
+        assert_eq!(parse_wm_value("Physical size: 1080x2400\n", "Override"), None);
+        assert_eq!(density_for_width(1080, 320), 540);
+        assert_eq!(density_for_width(1080, 411), 420);
+    }
+
+    #[test]
+    fn test_layout_setting_restore_cmd() {
+        assert_eq!(LayoutSetting::FontScale(String::new()).restore_cmd(), "settings delete system font_scale");
+        assert_eq!(LayoutSetting::FontScale("1.3".into()).restore_cmd(), "settings put system font_scale '1.3'");
+        assert_eq!(LayoutSetting::Density(None).restore_cmd(), "wm density reset");
+        assert_eq!(LayoutSetting::Density(Some(420)).restore_cmd(), "wm density 420");
+        assert_eq!(setting_cmd("secure", "enabled_accessibility_services", ""), "settings delete secure enabled_accessibility_services");
+    }
+
+    #[test]
+    fn test_locale_tags_and_app_locales() {
+        for ok in ["de", "de-DE", "pt-BR", "sr-Latn-RS", "es-419"] {
+            assert!(validate_locale_tag(ok).is_ok(), "{}", ok);
+        }
+        for bad in ["", "german", "de_DE", "de-de", "de-DE; reboot"] {
+            assert!(validate_locale_tag(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(parse_app_locales("Locales for com.x for user 0 are [de-DE,fr-FR]\n"), "de-DE,fr-FR");
+        assert_eq!(parse_app_locales("Locales for com.x for user 0 are []"), "");
+        assert_eq!(parse_app_locales(""), "");
+
+        assert_eq!(system_locale_cmd("de-DE").unwrap(), "setprop persist.sys.locale 'de-DE' ; setprop ctl.restart zygote");
+        // Restoring a device that had no locale set clears the property.
+        assert_eq!(system_locale_cmd("").unwrap(), "setprop persist.sys.locale '' ; setprop ctl.restart zygote");
+        assert!(system_locale_cmd("de DE").is_err());
+    }
+
+    #[test]
+    fn test_last_rgb_frame() {
+        // Two 2x1 frames plus a truncated third; the second one wins.
+        let raw = [1, 1, 1, 2, 2, 2, 9, 8, 7, 6, 5, 4, 0, 0];
+        let img = last_rgb_frame(&raw, 2, 1).unwrap().to_rgb8();
+        assert_eq!(img.get_pixel(0, 0).0, [9, 8, 7]);
+        assert_eq!(img.get_pixel(1, 0).0, [6, 5, 4]);
+        assert!(last_rgb_frame(&raw[..5], 2, 1).is_err());
+
+        assert_eq!(CaptureStrategy::parse("screenrecord").unwrap(), CaptureStrategy::ScreenRecord);
+        assert!(CaptureStrategy::parse("uiautomator").is_err());
+    }
+
+    #[test]
+    fn test_raw_frame_stream_decoding() {
+        let frame = |header_words: &[u32], px: [u8; 4]| {
+            let mut out: Vec<u8> = header_words.iter().flat_map(|w| w.to_le_bytes()).collect();
+            for _ in 0..4 {
+                out.extend_from_slice(&px);
+            }
+            out
+        };
+        // 2x2 RGBX frame with the Android 9+ colour-space word.
+        let first = frame(&[2, 2, RAW_FORMAT_RGBX_8888, 1], [10, 20, 30, 0]);
+        assert_eq!(raw_header_len(&first).unwrap(), 16);
+        assert_eq!(raw_header_len(&frame(&[2, 2, 1], [0; 4])).unwrap(), 12);
+        assert!(raw_header_len(&first[..20]).is_err());
+
+        let mut stream = first.clone();
+        stream.extend(frame(&[2, 2, RAW_FORMAT_BGRA_8888, 1], [30, 20, 10, 255]));
+        let mut reader = std::io::Cursor::new(stream);
+        for _ in 0..2 {
+            let img = read_raw_frame(&mut reader, 16).unwrap().unwrap().to_rgba8();
+            assert_eq!(img.get_pixel(1, 1).0, [10, 20, 30, 255]);
+        }
+        assert!(read_raw_frame(&mut reader, 16).unwrap().is_none());
+
+        // Truncated mid-frame is an error, not a clean end.
+        let mut short = std::io::Cursor::new(first[..18].to_vec());
+        assert!(read_raw_frame(&mut short, 16).is_err());
+        assert!(decode_raw_frame(1, 1, 4, vec![0; 4]).is_err());
+    }
+
+    #[test]
+    fn test_slow_text_chunks_paste_non_ascii_runs() {
+        use SlowTextChunk::*;
+        assert_eq!(slow_text_chunks("a b"), vec![Typed('a'), Typed(' '), Typed('b')]);
+        assert_eq!(
+            slow_text_chunks("Zoë café日本!"),
+            vec![Typed('Z'), Typed('o'), Pasted("ë".into()), Typed(' '), Typed('c'), Typed('a'), Typed('f'), Pasted("é日本".into()), Typed('!')]
+        );
+        assert!(slow_text_chunks("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_toast_event() {
+        let toast = "EventType: TYPE_NOTIFICATION_STATE_CHANGED; EventTime: 2141; PackageName: com.example; \
+            MovementGranularity: 0; Action: 0; ContentChangeTypes: []; WindowChangeTypes: [] [ ClassName: android.widget.Toast$TN; \
+            Text: [Saved to drafts]; ContentDescription: null; ItemCount: -1 ]";
+        assert_eq!(parse_toast_event(toast).as_deref(), Some("Saved to drafts"));
+        let notification = toast.replace("android.widget.Toast$TN", "android.app.Notification");
+        assert_eq!(parse_toast_event(&notification), None);
+        let click = toast.replace("TYPE_NOTIFICATION_STATE_CHANGED", "TYPE_VIEW_CLICKED");
+        assert_eq!(parse_toast_event(&click), None);
+        assert_eq!(parse_toast_event(&toast.replace("[Saved to drafts]", "[]")), None);
+    }
+
+    #[test]
+    fn test_tree_snapshot_and_compare() {
+        let xml = r#"<?xml version='1.0' encoding='UTF-8' standalone='yes' ?><hierarchy rotation="0">
+            <node index="0" text="" resource-id="" class="android.widget.FrameLayout" enabled="true" bounds="[0,0][1080,2400]">
+            <node index="0" text="Terms &amp; Conditions" resource-id="" class="android.widget.TextView" enabled="true" bounds="[0,0][1080,100]" />
+            <node index="1" text="Sign in" resource-id="com.example:id/login" class="android.widget.Button" content-desc="Sign in" clickable="true" enabled="true" bounds="[40,200][1040,320]" />
+            </node></hierarchy>"#;
+        let baseline = parse_tree(xml, false).unwrap();
+        let root = &baseline.nodes[0];
+        assert_eq!((root.class.as_str(), root.children.len(), root.bounds), ("android.widget.FrameLayout", 2, None));
+        assert_eq!(root.children[0].text, "Terms & Conditions");
+        assert_eq!(root.children[1].flags, vec!["clickable".to_string()]);
+        assert_eq!(parse_tree(xml, true).unwrap().nodes[0].children[1].bounds, Some([40, 200, 1040, 320]));
+        assert!(compare_tree(&parse_tree(&xml.replace("[40,200]", "[50,210]"), false).unwrap(), &baseline).is_empty());
+
+        let unlabeled = xml.replace(r#"content-desc="Sign in" clickable="true" enabled="true""#, r#"clickable="true" enabled="false""#);
+        let changes = compare_tree(&parse_tree(&unlabeled, false).unwrap(), &baseline);
+        assert_eq!(changes.iter().map(|c| c.to_string()).collect::<Vec<_>>(), vec![
+            "changed: FrameLayout/Button#login desc 'Sign in' -> ''",
+            "changed: FrameLayout/Button#login flags 'clickable' -> 'clickable,disabled'",
+        ]);
+        let gone = xml.replace("com.example:id/login", "com.example:id/retry").replace("<node index=\"0\" text=\"Terms", "<node index=\"0\" text=\"\" class=\"android.widget.TextView\" /><node index=\"0\" text=\"Terms");
+        // The new unlabelled TextView comes first, yet "Terms" still matches
+        // its baseline rather than reading as changed.
+        assert_eq!(compare_tree(&parse_tree(&gone, false).unwrap(), &baseline), vec![
+            TreeChange::Missing("FrameLayout/Button#login".into()),
+            TreeChange::Added("FrameLayout/TextView".into()),
+            TreeChange::Added("FrameLayout/Button#retry".into()),
+        ]);
+        // With no content match left, twins pair up by position.
+        let renamed = xml.replace("Terms &amp; Conditions", "Terms of use");
+        assert_eq!(
+            compare_tree(&parse_tree(&renamed, false).unwrap(), &baseline),
+            vec![TreeChange::Changed {
+                path: "FrameLayout/TextView".into(),
+                field: "text",
+                baseline: "Terms & Conditions".into(),
+                actual: "Terms of use".into(),
+            }]
+        );
+        assert!(parse_tree("<node class=\"a\">", false).is_err());
+    }
+
+    #[test]
+    fn test_hardware_state_parsing() {
+        assert_eq!(camera_count("== Service global info: ==\n\nNumber of camera devices: 2\nNumber of normal camera devices: 2\n"), Some(2));
+        assert_eq!(camera_count("Permission Denial"), None);
+        let prints = r#"{"service":"Fingerprint Manager","prints":[{"id":0,"count":1,"accept":3},{"id":10,"count":2}]}"#;
+        assert_eq!(enrolled_count(prints), Some(3));
+        assert_eq!(enrolled_count(r#"{"service":"Face Manager","prints":[{"id":0,"count":0}]}"#), Some(0));
+        assert_eq!(enrolled_count("Can't find service: fingerprint"), None);
+        assert_eq!(nfc_enabled("mState=on\nmAlwaysOnState=off\n"), Some(true));
+        assert_eq!(nfc_enabled("  mState=off"), Some(false));
+        assert_eq!(location_enabled("3\n"), Some(true));
+        assert_eq!(location_enabled("0"), Some(false));
+        assert_eq!(location_enabled("null"), None);
+
+        assert_eq!(feature_name("nfc"), "android.hardware.nfc");
+        assert_eq!(feature_name("android.software.webview"), "android.software.webview");
+        assert!(!HardwareState::new(false, Some(true)).usable());
+        assert!(HardwareState::new(true, None).usable());
+        assert!(!HardwareState::new(true, Some(false)).usable());
+    }
 }
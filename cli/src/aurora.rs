@@ -309,7 +309,7 @@ pub fn list_apps(filter: Option<&str>, device: Option<&str>) -> Result<()> {
     let mut apps: Vec<&str> = stdout
         .lines()
         .filter(|line| {
-            filter.map_or(true, |f| line.to_lowercase().contains(&f.to_lowercase()))
+            filter.is_none_or(|f| line.to_lowercase().contains(&f.to_lowercase()))
         })
         .collect();
 
@@ -18,8 +18,8 @@ pub struct Cli {
 pub enum Commands {
     /// Take a screenshot and optionally compress it
     Screenshot {
-        /// Platform: android, ios, aurora, or desktop
-        #[arg(value_parser = ["android", "ios", "aurora", "desktop"])]
+        /// Platform: android, ios, aurora, desktop, or remote (a device-farm device; see remote-status)
+        #[arg(value_parser = ["android", "ios", "aurora", "desktop", "remote"])]
         platform: String,
 
         /// Output file path (default: stdout as base64)
@@ -34,8 +34,8 @@ pub enum Commands {
         #[arg(long, default_value = "540")]
         max_width: u32,
 
-        /// Max height for compression (default: 960)
-        #[arg(long, default_value = "960")]
+        /// Max height for compression; the image is scaled to fit both limits (default: none)
+        #[arg(long)]
         max_height: Option<u32>,
 
         /// JPEG quality for compression (1-100, default: 55)
@@ -54,9 +54,68 @@ pub enum Commands {
         #[arg(long)]
         companion_path: Option<String>,
 
-        /// Monitor index for desktop screenshot
-        #[arg(long)]
+        /// Desktop: capture only this monitor (index from get-monitors)
+        #[arg(long, conflicts_with_all = ["window", "region"])]
         monitor_index: Option<u32>,
+
+        /// Crop uniform-colour borders (letterboxing); detected from the corners
+        #[arg(long, default_value = "false")]
+        trim: bool,
+
+        /// Drop this many rows from the top (status bar) before anything else
+        #[arg(long, value_name = "PX")]
+        crop_status_bar: Option<u32>,
+
+        /// Overlay a coordinate grid with this spacing in device pixels (e.g. 100)
+        #[arg(long)]
+        grid: Option<u32>,
+
+        /// Label every N-th grid line with its coordinate (0 = no labels)
+        #[arg(long, default_value = "2")]
+        grid_label_every: u32,
+
+        /// Android: when the focused window is FLAG_SECURE, render a wireframe
+        /// from the UI hierarchy instead of failing
+        #[arg(long, default_value = "false")]
+        allow_secure_bypass: bool,
+
+        /// Android: auto tries screencap, then screenrecord if the frame is blank
+        /// (SurfaceView, Flutter, games)
+        #[arg(long, default_value = "auto",
+              value_parser = ["auto", "screencap", "screenrecord"])]
+        capture_strategy: String,
+
+        /// Desktop: capture only this window (id, or title/app-name substring)
+        #[arg(long, conflicts_with = "region")]
+        window: Option<String>,
+
+        /// Desktop: capture only this region, in logical coordinates: x,y,width,height
+        #[arg(long, value_name = "X,Y,W,H")]
+        region: Option<String>,
+
+        /// Burn device, timestamp and --test-id/--step-id into a strip added to the image
+        #[arg(long, default_value = "false")]
+        evidence: bool,
+
+        /// Font size of the --evidence strip in pixels
+        #[arg(long, default_value = "16", requires = "evidence")]
+        evidence_font_size: f32,
+
+        /// Put the --evidence strip above or below the capture
+        #[arg(long, default_value = "bottom", value_parser = ["top", "bottom"], requires = "evidence")]
+        evidence_position: String,
+
+        /// Test case id for the --evidence strip
+        #[arg(long, requires = "evidence")]
+        test_id: Option<String>,
+
+        /// Step id for the --evidence strip
+        #[arg(long, requires = "evidence")]
+        step_id: Option<String>,
+
+        /// Encode for this purpose: baseline (PNG), or report/model as set by the screenshot.report-format / screenshot.model-format config keys
+        #[arg(long, value_parser = ["baseline", "report", "model"], conflicts_with = "compress")]
+        purpose: Option<String>,
     },
 
     /// Take annotated screenshot with UI element bounds
@@ -78,6 +137,260 @@ pub enum Commands {
         device: Option<String>,
     },
 
+    /// Capture the screen in light and dark mode, side by side (Android only)
+    ThemePair {
+        /// Output file path for the composite PNG (default: stdout as base64)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Wait after each theme switch before capturing, in milliseconds
+        #[arg(long, default_value = "1500")]
+        settle: u64,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Heatmap of where a sequence of screenshots changes, drawn over the first
+    Heatmap {
+        /// Screenshots in capture order (at least two, all the same size)
+        #[arg(required = true, num_args = 2..)]
+        frames: Vec<String>,
+
+        /// Output file path for the PNG (default: stdout as base64)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Show, edit or suggest the ignore mask stored beside an assert-screen baseline
+    BaselineMask {
+        /// Baseline PNG (`<root>/<scenario>/<step id>/<platform>.png`)
+        baseline: String,
+
+        /// Ignore a region, as X,Y,WIDTH,HEIGHT (repeatable)
+        #[arg(long, value_name = "RECT")]
+        add: Vec<String>,
+
+        /// Remove the rects already in the mask first
+        #[arg(long, default_value = "false")]
+        clear: bool,
+
+        /// Add rects covering what changes across these captures of the screen (several runs)
+        #[arg(long, value_name = "FRAME", num_args = 2..)]
+        suggest: Vec<String>,
+    },
+
+    /// Screenshot with colour-blindness simulations next to the original
+    CvdPreview {
+        /// Platform: android, ios, aurora, or desktop
+        #[arg(value_parser = ["android", "ios", "aurora", "desktop"])]
+        platform: String,
+
+        /// Only this deficiency (default: all three)
+        #[arg(long, value_parser = ["protanopia", "deuteranopia", "tritanopia"])]
+        kind: Option<String>,
+
+        /// Output file path for the composite PNG (default: stdout as base64)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// iOS Simulator name (default: booted)
+        #[arg(long)]
+        simulator: Option<String>,
+
+        /// Android/Aurora device serial (default: first device)
+        #[arg(long)]
+        device: Option<String>,
+
+        /// Desktop companion app path
+        #[arg(long)]
+        companion_path: Option<String>,
+    },
+
+    /// Wait for a disconnected device to come back, reconnecting meanwhile (Android only)
+    WaitForDevice {
+        /// Give up after this many seconds
+        #[arg(long, default_value = "120")]
+        timeout: u64,
+
+        /// Wake the screen once the device is back
+        #[arg(long, default_value = "false")]
+        wake: bool,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Connect to a device over the network with `adb connect` (Android only)
+    AdbConnect {
+        /// Device IP address or host name
+        host: String,
+
+        #[arg(long, default_value = "5555")]
+        port: u16,
+    },
+
+    /// Drop an `adb connect` network connection (Android only)
+    AdbDisconnect {
+        /// Device IP address or host name
+        host: String,
+
+        #[arg(long, default_value = "5555")]
+        port: u16,
+    },
+
+    /// Pair with a device for Android 11+ wireless debugging
+    AdbPair {
+        /// Device IP address or host name
+        host: String,
+
+        /// Pairing port shown next to the code (not the connect port)
+        port: u16,
+
+        /// Six-digit pairing code
+        code: String,
+    },
+
+    /// Let the host reach a device port with `adb forward`, e.g. tcp:9222 tcp:9222 (Android only)
+    AdbForward {
+        /// Host socket, e.g. tcp:8080 (tcp:0 = any free port)
+        local: String,
+
+        /// Device socket, e.g. tcp:8080 or localabstract:name
+        #[arg(required_unless_present = "remove")]
+        remote: Option<String>,
+
+        /// Remove the forward listening on LOCAL instead
+        #[arg(long, default_value = "false")]
+        remove: bool,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Let the device reach a host port with `adb reverse`, e.g. for a mock server (Android only)
+    AdbReverse {
+        /// Device socket, e.g. tcp:8080 (tcp:0 = any free port)
+        remote: String,
+
+        /// Host socket, e.g. tcp:8080
+        #[arg(required_unless_present = "remove")]
+        local: Option<String>,
+
+        /// Remove the reverse listening on REMOTE instead
+        #[arg(long, default_value = "false")]
+        remove: bool,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Check that the device-farm API (DEVICE_FARM_URL) and a remote device are reachable
+    RemoteStatus {
+        /// Farm device id (default: DEVICE_FARM_DEVICE)
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Change the locale and wait for it to take (Android only)
+    SetLocale {
+        /// BCP 47 tag, e.g. de-DE or pt-BR
+        locale: String,
+
+        /// App to set a per-app locale for (Android 13+, no root) and relaunch;
+        /// without it the system locale changes, which needs root
+        #[arg(long)]
+        package: Option<String>,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Set the system font scale, e.g. 1.3 for large text, and check it took (Android only)
+    FontScale {
+        /// Scale factor (1.0 = default)
+        scale: f32,
+
+        /// App to relaunch so it lays out with the new scale
+        #[arg(long)]
+        package: Option<String>,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Change the display size to a smallest width in dp, or `reset` it (Android only)
+    DisplaySize {
+        /// Smallest width in dp (e.g. 320 for a small phone), or `reset`
+        width: String,
+
+        /// App to relaunch so it lays out with the new size
+        #[arg(long)]
+        package: Option<String>,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Install an app from the Play Store if missing and print its version (Android only)
+    EnsureInstalled {
+        /// Package name (e.g. com.example.app)
+        package: String,
+
+        /// Required versionName; fails if the store serves a different one
+        #[arg(long)]
+        version: Option<String>,
+
+        /// Give up if the install has not finished after this many seconds
+        #[arg(long, default_value = "300")]
+        timeout: u64,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Screenshot several Android devices at once into <serial>.png files (Android only)
+    ScreenshotAll {
+        /// Device serials, comma-separated (default: every connected device)
+        #[arg(long, value_delimiter = ',')]
+        devices: Vec<String>,
+
+        /// Directory for the PNG files
+        #[arg(short, long)]
+        output_dir: String,
+
+        /// Maximum captures running at the same time
+        #[arg(long, default_value = "4")]
+        concurrency: usize,
+    },
+
+    /// Capture a burst of frames over one long-lived adb pipeline (Android only)
+    FrameStream {
+        /// Number of frames to save
+        #[arg(long, default_value = "10")]
+        count: u32,
+
+        /// Delay between saved frames, in milliseconds
+        #[arg(long, default_value = "200")]
+        interval_ms: u64,
+
+        /// Directory for frame-NNN.png files
+        #[arg(short, long)]
+        output_dir: String,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
     /// Tap at coordinates
     Tap {
         /// Platform: android, ios, aurora, or desktop
@@ -132,9 +445,10 @@ pub enum Commands {
         /// Y coordinate
         y: i32,
 
-        /// Duration in milliseconds (default: 1000)
-        #[arg(short, long, default_value = "1000")]
-        duration: u32,
+        /// Duration in milliseconds (default: the system long-press threshold
+        /// plus a margin on Android, 1000 elsewhere)
+        #[arg(short, long)]
+        duration: Option<u32>,
 
         /// Long press by text (Android)
         #[arg(long)]
@@ -240,6 +554,70 @@ pub enum Commands {
         from_size: Option<String>,
     },
 
+    /// Two-finger pinch gesture via sendevent (Android only)
+    Pinch {
+        /// Center X
+        center_x: i32,
+
+        /// Center Y
+        center_y: i32,
+
+        /// Distance between fingers at the start, in pixels
+        #[arg(long, default_value = "200")]
+        start_dist: u32,
+
+        /// Distance between fingers at the end (larger = zoom in)
+        #[arg(long, default_value = "600")]
+        end_dist: u32,
+
+        /// Duration in milliseconds (default: 400)
+        #[arg(short, long, default_value = "400")]
+        duration: u32,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Simultaneous multi-pointer gesture via sendevent (Android only)
+    MultiTouch {
+        /// Pointer paths as JSON: one array of [x,y] waypoints per finger
+        /// (e.g. '[[[300,800],[300,400]],[[700,800],[700,400]]]')
+        pointers: String,
+
+        /// Duration in milliseconds (default: 400)
+        #[arg(short, long, default_value = "400")]
+        duration: u32,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Select text between two points: long-press at start, drag to end (Android only)
+    SelectText {
+        /// Start X (long-press point)
+        start_x: i32,
+        /// Start Y
+        start_y: i32,
+        /// End X (where the selection should end)
+        end_x: i32,
+        /// End Y
+        end_y: i32,
+
+        /// Long-press hold before dragging, in milliseconds
+        #[arg(long, default_value = "800")]
+        hold: u32,
+
+        /// Drag duration in milliseconds
+        #[arg(short, long, default_value = "500")]
+        duration: u32,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
     /// Input text
     Input {
         /// Platform: android, ios, aurora, or desktop
@@ -249,6 +627,10 @@ pub enum Commands {
         /// Text to input
         text: String,
 
+        /// Android: type one character at a time with this delay, for fields that drop fast input
+        #[arg(long, value_name = "MS")]
+        char_delay: Option<u64>,
+
         /// iOS Simulator name
         #[arg(long)]
         simulator: Option<String>,
@@ -548,6 +930,41 @@ pub enum Commands {
         device: Option<String>,
     },
 
+    /// Erase an iOS simulator to factory state (iOS only, destructive)
+    EraseSimulator {
+        /// Simulator name or UDID
+        simulator: String,
+
+        /// Confirm wiping all apps, data and settings on the simulator
+        #[arg(long, default_value = "false")]
+        yes: bool,
+
+        /// Shut the simulator down first if it is booted
+        #[arg(long, default_value = "false")]
+        shutdown: bool,
+    },
+
+    /// Switch the simulator between light and dark mode (iOS only)
+    Appearance {
+        /// light or dark
+        #[arg(value_parser = ["light", "dark"])]
+        mode: String,
+
+        /// iOS Simulator name (default: booted)
+        #[arg(long)]
+        simulator: Option<String>,
+    },
+
+    /// Set the Dynamic Type content size category, e.g. accessibility-large (iOS only)
+    ContentSize {
+        /// Category from extra-small to accessibility-extra-extra-extra-large
+        category: String,
+
+        /// iOS Simulator name (default: booted)
+        #[arg(long)]
+        simulator: Option<String>,
+    },
+
     /// Control screen power (Android only)
     Screen {
         /// Turn screen on or off
@@ -559,6 +976,125 @@ pub enum Commands {
         device: Option<String>,
     },
 
+    /// Wake the screen and dismiss the lock screen, entering a PIN if needed (Android only)
+    Unlock {
+        /// PIN or password for a secure lock screen
+        #[arg(long)]
+        pin: Option<String>,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Force deep Doze on or off and print the resulting idle state (Android only)
+    Doze {
+        /// on forces idle (and reports the battery unplugged); off restores both
+        #[arg(value_parser = ["on", "off"])]
+        state: String,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Answer the system dialog on screen (ANR, crash, permission, update) per a policy (Android only)
+    DismissDialogs {
+        /// KIND=RESPONSE pairs, e.g. anr=close,permission=deny (default: anr=wait,crash=close,permission=allow,update=dismiss)
+        #[arg(long, value_name = "POLICY")]
+        policy: Option<String>,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Show or set Do Not Disturb (Android only)
+    Dnd {
+        /// off, priority (priority only), alarms (alarms only) or none (total silence); omit to print the current mode
+        #[arg(value_parser = ["off", "priority", "alarms", "none"])]
+        mode: Option<String>,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Check an element's text or state in the UI hierarchy; fails if it does not hold (Android only)
+    AssertElement {
+        /// Text, resource-id or content-desc substring to match
+        query: String,
+
+        /// What to check
+        #[arg(value_parser = ["text-equals", "text-contains", "enabled", "disabled", "checked", "unchecked", "visible", "gone"])]
+        check: String,
+
+        /// Expected text for text-equals / text-contains
+        value: Option<String>,
+
+        /// Print the result (expected, actual, found) as JSON
+        #[arg(long, default_value = "false")]
+        json: bool,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Report camera, biometrics, NFC and GPS presence and availability, or check system features (Android only)
+    HardwareInfo {
+        /// Only check that these system features are declared (e.g. nfc, android.hardware.camera.flash); fails if one is missing
+        #[arg(long)]
+        feature: Vec<String>,
+
+        /// Print as JSON
+        #[arg(long, default_value = "false")]
+        json: bool,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Turn TalkBack on or off and wait until it is (un)bound (Android only)
+    Talkback {
+        #[arg(value_parser = ["on", "off"])]
+        state: String,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Print the toast messages shown during a time window (Android only)
+    Toasts {
+        /// Seconds to watch
+        #[arg(long, default_value = "5")]
+        duration: u64,
+
+        /// Fail unless a toast containing this text appears (returns as soon as it does)
+        #[arg(long)]
+        expect: Option<String>,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Put an app in an app-standby bucket and print the resulting bucket (Android only)
+    StandbyBucket {
+        /// Package name
+        package: String,
+
+        /// Bucket to move the app to
+        #[arg(value_parser = ["active", "working_set", "frequent", "rare", "restricted"])]
+        bucket: String,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
     /// Get screen resolution
     ScreenSize {
         /// Platform: android or ios
@@ -597,6 +1133,55 @@ pub enum Commands {
         device: Option<String>,
     },
 
+    /// Tap where a template image best matches the screen (Android only)
+    TapImage {
+        /// PNG/JPEG of the element to find, cropped from an earlier screenshot
+        template: String,
+
+        /// Minimum match score, 0.0-1.0
+        #[arg(long, default_value = "0.8")]
+        threshold: f32,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Dump an app's heap and save live instance counts per class (Android only)
+    HeapSnapshot {
+        /// App package name
+        package: String,
+
+        /// JSON file to write (default: stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Compare two heap-snapshot files and list classes whose instance count grew
+    HeapCompare {
+        /// Snapshot taken before the flow
+        before: String,
+
+        /// Snapshot taken after the flow
+        after: String,
+
+        /// Only consider classes whose name contains this (e.g. your package)
+        #[arg(long)]
+        filter: Option<String>,
+
+        /// Fail if any considered class grew by more than this many instances
+        #[arg(long)]
+        max_growth: Option<u64>,
+
+        /// How many classes to list
+        #[arg(long, default_value = "20")]
+        top: usize,
+    },
+
     /// Push file to device
     PushFile {
         /// Platform: android or aurora
@@ -771,6 +1356,66 @@ pub enum Commands {
         command: FlowCommands,
     },
 
+    /// Snapshot the normalized accessibility tree, or compare it with a saved one (Android only)
+    TreeSnapshot {
+        /// Write the snapshot to this JSON file instead of stdout
+        #[arg(short, long, conflicts_with = "compare")]
+        output: Option<String>,
+
+        /// Keep node coordinates (stripped by default, as they shift between devices)
+        #[arg(long, default_value = "false")]
+        bounds: bool,
+
+        /// Compare with this saved snapshot; fails if any node is missing, added or changed
+        #[arg(long)]
+        compare: Option<String>,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Wait for text to appear, in the UI hierarchy or, for custom-rendered UI, by OCR (Android only)
+    WaitForText {
+        /// Text to look for (case-insensitive partial match)
+        text: String,
+
+        /// Timeout in milliseconds
+        #[arg(long, default_value = "5000")]
+        timeout: u64,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Evaluate JavaScript in a debuggable WebView over the DevTools protocol (Android only)
+    WebviewEval {
+        /// JavaScript expression; its JSON value is printed
+        #[arg(required_unless_present = "exists", conflicts_with = "exists")]
+        expression: Option<String>,
+
+        /// Instead of an expression: fail unless an element matches this CSS selector
+        #[arg(long, value_name = "SELECTOR")]
+        exists: Option<String>,
+
+        /// Only WebViews of this app
+        #[arg(long)]
+        package: Option<String>,
+
+        /// Page whose URL or title contains this (default: the first page)
+        #[arg(long)]
+        page: Option<String>,
+
+        /// Evaluate in a Chrome tab instead of an app's WebView
+        #[arg(long, default_value = "false", conflicts_with = "package")]
+        chrome: bool,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
     /// Wait for a UI element to appear (polls every --interval ms up to --timeout ms)
     UiWait {
         /// Platform: android or ios
@@ -905,6 +1550,17 @@ pub enum Commands {
         device: Option<String>,
     },
 
+    /// List pending alarms from `dumpsys alarm` (Android only)
+    SensorAlarms {
+        /// Filter by package name
+        #[arg(long)]
+        package: Option<String>,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
     /// Override or reset thermal status (Android only)
     SensorThermal {
         /// Thermal status: none, light, moderate, severe, critical, emergency, shutdown
@@ -959,6 +1615,21 @@ pub enum Commands {
         device: Option<String>,
     },
 
+    /// List HTTP requests from the app's OkHttp log or a proxy HAR export (Android only)
+    NetworkRequests {
+        /// How far back to read the device log, in seconds
+        #[arg(long, default_value = "60")]
+        seconds: u64,
+
+        /// Read requests from this HAR file instead (e.g. exported by mitmproxy)
+        #[arg(long, value_name = "FILE")]
+        har: Option<String>,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
     /// Enable or disable airplane mode (Android only)
     NetworkAirplane {
         /// on or off
@@ -1079,13 +1750,29 @@ pub enum Commands {
         #[arg(long)]
         package: Option<String>,
 
-        /// Target component (pkg/.ReceiverClass)
-        #[arg(long)]
-        component: Option<String>,
+        /// Target component (pkg/.ReceiverClass)
+        #[arg(long)]
+        component: Option<String>,
+
+        /// Extras as JSON object
+        #[arg(long)]
+        extras: Option<String>,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
+    /// Call a binder service method via `service call` and decode the Parcel reply (Android only)
+    ServiceCall {
+        /// Service name (e.g. clipboard, activity)
+        service: String,
 
-        /// Extras as JSON object
-        #[arg(long)]
-        extras: Option<String>,
+        /// Transaction code
+        code: u32,
+
+        /// Arguments as TYPE:VALUE (i32:1, i64:2, f:1.5, d:2.5, s16:text) or null
+        args: Vec<String>,
 
         /// Android device serial
         #[arg(long)]
@@ -1267,6 +1954,24 @@ pub enum Commands {
         device: Option<String>,
     },
 
+    /// Sample perf stats over a time window, with frame counts relative to the window start (Android only)
+    PerfCapture {
+        /// Package name (e.g. com.example.app)
+        package: String,
+
+        /// Capture duration in milliseconds
+        #[arg(long, default_value = "5000")]
+        duration_ms: u64,
+
+        /// Interval between samples in milliseconds
+        #[arg(long, default_value = "1000")]
+        interval_ms: u64,
+
+        /// Android device serial
+        #[arg(long)]
+        device: Option<String>,
+    },
+
     /// Extract recent crashes and ANRs from logcat (Android only)
     PerfCrashes {
         /// Filter by package name
@@ -1298,7 +2003,7 @@ pub enum Commands {
     /// Record, manage and replay automation scenarios
     Recorder {
         #[command(subcommand)]
-        command: RecorderCommands,
+        command: Box<RecorderCommands>,
     },
 
     /// Coordinated multi-device testing (sync groups)
@@ -1651,6 +2356,8 @@ pub enum RuStoreCommands {
 
 // -- Recorder subcommands -----------------------------------------------------
 
+// Parsed once per invocation, so boxing the big Play/Suite variants buys nothing.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum RecorderCommands {
     /// Start a new recording session (creates /tmp/claude-mobile-recording-<name>.json)
@@ -1670,6 +2377,18 @@ pub enum RecorderCommands {
         /// Comma-separated tags (e.g. smoke,login)
         #[arg(long)]
         tags: Option<String>,
+
+        /// Custom metadata field, e.g. jira_id=SHOP-12 (repeatable)
+        #[arg(long, value_name = "KEY=VALUE")]
+        meta: Vec<String>,
+
+        /// Impact of a failure, independent of run order
+        #[arg(long, value_parser = ["blocker", "critical", "major", "minor", "trivial"])]
+        severity: Option<String>,
+
+        /// Product feature the case covers (see `recorder coverage`)
+        #[arg(long)]
+        feature: Option<String>,
     },
 
     /// Stop the active recording and save the scenario (or discard it)
@@ -1694,6 +2413,30 @@ pub enum RecorderCommands {
         /// Optional human-readable label for this step
         #[arg(long)]
         label: Option<String>,
+
+        /// The step verifies an error path: it passes only when the action fails
+        #[arg(long, default_value = "false")]
+        expect_failure: bool,
+
+        /// Soft assertion: a failure fails the case but later steps still run
+        #[arg(long, default_value = "false")]
+        soft: bool,
+
+        /// Stable step id, unique within the scenario; reports key results by it
+        #[arg(long)]
+        id: Option<String>,
+
+        /// Run only if an earlier step had this result: <ID>.passed or <ID>.failed
+        #[arg(long, value_name = "ID.passed|ID.failed")]
+        when: Option<String>,
+
+        /// Section label shared with neighbouring steps, e.g. "Login"
+        #[arg(long)]
+        group: Option<String>,
+
+        /// A person performs and judges this step; replay pauses for their verdict
+        #[arg(long, default_value = "false")]
+        manual: bool,
     },
 
     /// Remove a step from the active recording by 1-based index
@@ -1711,6 +2454,56 @@ pub enum RecorderCommands {
         /// Filter by tag
         #[arg(long)]
         tag: Option<String>,
+
+        /// Boolean tag expression, e.g. "smoke AND android AND NOT flaky"
+        #[arg(long, value_name = "EXPR")]
+        tags: Option<String>,
+
+        /// Filter by metadata field; all given pairs must match (repeatable)
+        #[arg(long, value_name = "KEY=VALUE")]
+        meta: Vec<String>,
+
+        /// Only scenarios with one of these severities (repeatable)
+        #[arg(long, value_parser = ["blocker", "critical", "major", "minor", "trivial"])]
+        severity: Vec<String>,
+
+        /// Print scenarios as a JSON array (includes metadata)
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+
+    /// Find scenarios by free text (name, description, tags, metadata) plus filters, best match first
+    Search {
+        /// Words to look for; every word must match somewhere (case-insensitive)
+        text: Vec<String>,
+
+        /// Only scenarios for this platform
+        #[arg(short, long)]
+        platform: Option<String>,
+
+        /// Boolean tag expression, e.g. "smoke AND NOT flaky"
+        #[arg(long, value_name = "EXPR")]
+        tags: Option<String>,
+
+        /// Filter by metadata field; all given pairs must match (repeatable)
+        #[arg(long, value_name = "KEY=VALUE")]
+        meta: Vec<String>,
+
+        /// Only scenarios with one of these severities (repeatable)
+        #[arg(long, value_parser = ["blocker", "critical", "major", "minor", "trivial"])]
+        severity: Vec<String>,
+
+        /// Only scenarios whose `author` metadata contains this (case-insensitive)
+        #[arg(long)]
+        author: Option<String>,
+
+        /// Directory to scan recursively (default: ~/.claude-mobile/scenarios)
+        #[arg(long)]
+        dir: Option<String>,
+
+        /// Print results as a JSON array
+        #[arg(long, default_value = "false")]
+        json: bool,
     },
 
     /// Display the full contents of a saved scenario
@@ -1723,6 +2516,25 @@ pub enum RecorderCommands {
         platform: String,
     },
 
+    /// Check a saved scenario for step-order mistakes (warnings only)
+    Validate {
+        /// Scenario name
+        name: String,
+
+        /// Platform the scenario belongs to
+        #[arg(short, long, default_value = "android")]
+        platform: String,
+
+        /// Also check that every `@key:` reference exists in each locale of this catalog
+        #[arg(long, value_name = "FILE")]
+        strings: Option<String>,
+
+        /// Fail on authoring lints too: missing or non-kebab tags, platform, timestamps, unplayable steps
+        /// (with a did-you-mean for mistyped actions; manual steps may stay prose)
+        #[arg(long, default_value = "false")]
+        strict: bool,
+    },
+
     /// Delete a saved scenario
     Delete {
         /// Scenario name
@@ -1733,6 +2545,27 @@ pub enum RecorderCommands {
         platform: String,
     },
 
+    /// Record the scenario's content hash; play and suite refuse it once it changes
+    Approve {
+        /// Scenario name
+        name: String,
+
+        /// Platform the scenario belongs to
+        #[arg(short, long, default_value = "android")]
+        platform: String,
+    },
+
+    /// Rewrite scenario files in canonical key order and layout
+    Format {
+        /// Directory to scan recursively (default: ~/.claude-mobile/scenarios)
+        #[arg(long)]
+        dir: Option<String>,
+
+        /// Only list files that are not canonical, and fail if there are any (for CI)
+        #[arg(long, default_value = "false")]
+        check: bool,
+    },
+
     /// Replay a saved scenario
     Play {
         /// Scenario name
@@ -1742,6 +2575,10 @@ pub enum RecorderCommands {
         #[arg(short, long, default_value = "android")]
         platform: String,
 
+        /// Replay a cross-platform scenario (stored under --platform) once per entry in its `platforms`
+        #[arg(long, default_value = "false")]
+        all_platforms: bool,
+
         /// Playback speed multiplier (default: 1.0)
         #[arg(long, default_value = "1.0")]
         speed: f64,
@@ -1769,6 +2606,322 @@ pub enum RecorderCommands {
         /// Print steps without executing them
         #[arg(long, default_value = "false")]
         dry_run: bool,
+
+        /// Skip manual steps instead of prompting (automatic when stdin is not a terminal)
+        #[arg(long, default_value = "false")]
+        no_prompt: bool,
+
+        /// Forward actions to this device-farm device (needs DEVICE_FARM_URL; see `screenshot remote`)
+        #[arg(long, value_name = "ID")]
+        remote_device: Option<String>,
+
+        /// Run the scenarios' `before`/`after` hooks: shell commands executed on this machine, not the device
+        #[arg(long, default_value = "false")]
+        allow_hooks: bool,
+
+        /// Android: answer system dialogs (ANR, crash, permission, update) before each step and after a failed one, then retry it once
+        #[arg(long, default_value = "false")]
+        dismiss_dialogs: bool,
+
+        /// With --dismiss-dialogs: KIND=RESPONSE pairs, e.g. anr=close,permission=deny (default: anr=wait,crash=close,permission=allow,update=dismiss)
+        #[arg(long, value_name = "POLICY", requires = "dismiss_dialogs")]
+        dialog_policy: Option<String>,
+
+        /// Value for `${config.KEY}` references in step text (repeatable)
+        #[arg(long, value_name = "KEY=VALUE")]
+        config: Vec<String>,
+
+        /// Flat JSON object with `${config.KEY}` values (e.g. per-environment settings)
+        #[arg(long)]
+        config_file: Option<String>,
+
+        /// Sample perf stats (memory, CPU, jank, battery) for this Android package during replay
+        #[arg(long, value_name = "PACKAGE")]
+        perf: Option<String>,
+
+        /// Perf sampling interval in milliseconds
+        #[arg(long, default_value = "1000")]
+        perf_interval: u64,
+
+        /// Strings catalog for `@key:NAME` args: {"<locale>": {"<key>": "<text>"}}
+        #[arg(long, value_name = "FILE")]
+        strings: Option<String>,
+
+        /// Catalog locale to resolve `@key:` args with; Android devices are switched to it
+        /// for the run (default: the only one in the catalog)
+        #[arg(long)]
+        locale: Option<String>,
+
+        /// Root of the shared baseline tree for assert-screen steps (default: ~/.claude-mobile/baselines)
+        #[arg(long, value_name = "DIR")]
+        baselines: Option<String>,
+
+        /// Save the current screen as the baseline of every assert-screen step instead of comparing
+        #[arg(long, default_value = "false")]
+        update_baselines: bool,
+
+        /// Android: fail a step whose app CPU stays above this percentage for --watchdog-cpu-window
+        #[arg(long, value_name = "PERCENT")]
+        watchdog_cpu: Option<f64>,
+
+        /// Seconds the CPU must stay above --watchdog-cpu before the step fails
+        #[arg(long, default_value = "10", value_name = "SECS")]
+        watchdog_cpu_window: u64,
+
+        /// Android: fail a step once the app's memory (total PSS, sampled every 5s) exceeds this many MB
+        #[arg(long, value_name = "MB")]
+        watchdog_memory: Option<f64>,
+
+        /// App the watchdog monitors (default: the --perf package, else the foreground app)
+        #[arg(long, value_name = "PACKAGE")]
+        watchdog_package: Option<String>,
+    },
+
+    /// Run one step of a scenario, by its id, against the current screen (for debugging)
+    RunStep {
+        /// Scenario name
+        name: String,
+
+        /// The step's `id`
+        step_id: String,
+
+        /// Platform the scenario belongs to
+        #[arg(short, long, default_value = "android")]
+        platform: String,
+
+        /// Timeout in milliseconds (omit for no timeout)
+        #[arg(long)]
+        step_timeout: Option<u64>,
+
+        /// Value for `${config.KEY}` references in step text (repeatable)
+        #[arg(long, value_name = "KEY=VALUE")]
+        config: Vec<String>,
+
+        /// Flat JSON object with `${config.KEY}` values
+        #[arg(long)]
+        config_file: Option<String>,
+
+        /// Strings catalog for `@key:NAME` args: {"<locale>": {"<key>": "<text>"}}
+        #[arg(long, value_name = "FILE")]
+        strings: Option<String>,
+
+        /// Catalog locale to resolve `@key:` args with; Android devices are switched to it
+        /// for the run (default: the only one in the catalog)
+        #[arg(long)]
+        locale: Option<String>,
+
+        /// Root of the shared baseline tree for assert-screen steps (default: ~/.claude-mobile/baselines)
+        #[arg(long, value_name = "DIR")]
+        baselines: Option<String>,
+
+        /// Save the current screen as the step's baseline instead of comparing
+        #[arg(long, default_value = "false")]
+        update_baselines: bool,
+
+        /// Run the scenarios' `before`/`after` hooks: shell commands executed on this machine, not the device
+        #[arg(long, default_value = "false")]
+        allow_hooks: bool,
+
+        /// Run the case's precondition fixtures before the step
+        #[arg(long, default_value = "false")]
+        with_fixtures: bool,
+    },
+
+    /// Play every saved scenario for a platform (optionally filtered) as a suite
+    Suite {
+        /// Platform whose scenarios make up the suite
+        #[arg(short, long, default_value = "android")]
+        platform: String,
+
+        /// Only run scenarios with this tag
+        #[arg(long)]
+        tag: Option<String>,
+
+        /// Only run scenarios matching a tag expression, e.g. "smoke AND NOT flaky"
+        #[arg(long, value_name = "EXPR")]
+        tags: Option<String>,
+
+        /// Only run scenarios whose metadata matches (repeatable)
+        #[arg(long, value_name = "KEY=VALUE")]
+        meta: Vec<String>,
+
+        /// Only run scenarios with one of these severities (repeatable)
+        #[arg(long, value_parser = ["blocker", "critical", "major", "minor", "trivial"])]
+        severity: Vec<String>,
+
+        /// fail-fast: stop after the first failed case; continue: run everything
+        #[arg(long, default_value = "continue",
+              value_parser = ["fail-fast", "continue"])]
+        failure_mode: String,
+
+        /// Rerun failed cases up to N more times at the end; one that then passes is reported as flaky
+        #[arg(long, default_value = "0", value_name = "N")]
+        rerun_failed: u32,
+
+        /// Scenario to play after the suite, even when cases failed
+        #[arg(long)]
+        teardown: Option<String>,
+
+        /// Write a JSON report to this path (may use {date}, {time}, {platform}, {suite}, {run_id})
+        #[arg(long)]
+        report: Option<String>,
+
+        /// Only run scenarios added/changed since this git ref (scenario dir must be in a git repo)
+        #[arg(long, value_name = "REF")]
+        changed_since: Option<String>,
+
+        /// Also save a timestamped copy of the JSON report in this directory (for `recorder trend`; tokens as for --report except {date}, {time} and {run_id})
+        #[arg(long, value_name = "DIR")]
+        history: Option<String>,
+
+        /// Place relative --report/--history/--diagnostics/--video/--screenshots paths here; absolute ones must be inside it and none may use ..
+        #[arg(long, value_name = "DIR")]
+        output_root: Option<String>,
+
+        /// Value of the {suite} token in output paths
+        #[arg(long, default_value = "suite", value_name = "NAME")]
+        suite_name: String,
+
+        /// Directory template for the capture a failed assert-screen leaves, e.g. shots/{case_id}/{step_id} (default: screenshots/{run_id}/{case_id}/{step_id}; never the baseline tree)
+        #[arg(long, value_name = "TEMPLATE")]
+        screenshots: Option<String>,
+
+        /// Run every case once per combination of these values, e.g. --matrix locale=en,ru --matrix theme=light,dark
+        #[arg(long, value_name = "KEY=V1,V2")]
+        matrix: Vec<String>,
+
+        /// Most --matrix combinations allowed
+        #[arg(long, default_value = "32", value_name = "N")]
+        matrix_limit: usize,
+
+        /// Before each case, wait up to this many seconds for a disconnected Android device (0 = off)
+        #[arg(long, default_value = "120", value_name = "SECS")]
+        device_timeout: u64,
+
+        /// Stop starting cases after this many seconds; the rest are reported as not executed
+        #[arg(long, value_name = "SECS")]
+        suite_timeout: Option<u64>,
+
+        /// With --suite-timeout: how long the case in flight may keep running before it is aborted
+        #[arg(long, default_value = "30", value_name = "SECS", requires = "suite_timeout")]
+        timeout_grace: u64,
+
+        /// Run only shard i of N (1-based, e.g. 2/4); with --history, shards are balanced by past durations
+        #[arg(long, value_name = "i/N")]
+        shard: Option<String>,
+
+        /// Run a severity-weighted random subset whose estimated time fits this many seconds
+        #[arg(long, value_name = "SECS")]
+        sample_budget: Option<u64>,
+
+        /// Seed for --sample-budget (default: derived from the current git commit; outside git, a new seed each run)
+        #[arg(long, requires = "sample_budget")]
+        seed: Option<u64>,
+
+        /// Android: save logcat, activity/window dumps, UI hierarchy and a screenshot as <DIR>/<case>.zip for each failed case (DIR may use {case_id})
+        #[arg(long, value_name = "DIR")]
+        diagnostics: Option<String>,
+
+        /// With --diagnostics, also take a full `adb bugreport` per failed case (slow, large)
+        #[arg(long, default_value = "false", requires = "diagnostics")]
+        bugreport: bool,
+
+        /// iOS: record each case's screen to <DIR>/<case>.mp4 and list it in the report (DIR may use {case_id})
+        #[arg(long, value_name = "DIR")]
+        video: Option<String>,
+
+        /// No per-case progress on stderr (by default a live line on a terminal, one line per finished case otherwise)
+        #[arg(short, long, default_value = "false")]
+        quiet: bool,
+
+        /// Prune case artifacts after the run: keep-failures-only (drops the recordings and screenshots of passing cases), keep-all, or keep-last-N (runs in --history)
+        #[arg(long, default_value = "keep-failures-only", value_name = "POLICY")]
+        retention: String,
+
+        /// Per-step timeout in milliseconds (omit for no timeout)
+        #[arg(long)]
+        step_timeout: Option<u64>,
+
+        /// Print steps without executing them
+        #[arg(long, default_value = "false")]
+        dry_run: bool,
+
+        /// Skip manual steps instead of prompting (automatic when stdin is not a terminal)
+        #[arg(long, default_value = "false")]
+        no_prompt: bool,
+
+        /// Forward actions to this device-farm device (needs DEVICE_FARM_URL; see `screenshot remote`)
+        #[arg(long, value_name = "ID")]
+        remote_device: Option<String>,
+
+        /// Run the scenarios' `before`/`after` hooks: shell commands executed on this machine, not the device
+        #[arg(long, default_value = "false")]
+        allow_hooks: bool,
+
+        /// Android: answer system dialogs (ANR, crash, permission, update) before each step and after a failed one, then retry it once
+        #[arg(long, default_value = "false")]
+        dismiss_dialogs: bool,
+
+        /// With --dismiss-dialogs: KIND=RESPONSE pairs, e.g. anr=close,permission=deny (default: anr=wait,crash=close,permission=allow,update=dismiss)
+        #[arg(long, value_name = "POLICY", requires = "dismiss_dialogs")]
+        dialog_policy: Option<String>,
+
+        /// Host shell command to run before the suite (repeatable; needs --allow-hooks; counts toward --suite-timeout, killed after 10 min)
+        #[arg(long, value_name = "CMD", requires = "allow_hooks")]
+        before: Vec<String>,
+
+        /// Host shell command to run after the suite, whatever its outcome (repeatable; needs --allow-hooks)
+        #[arg(long, value_name = "CMD", requires = "allow_hooks")]
+        after: Vec<String>,
+
+        /// Value for `${config.KEY}` references in step text (repeatable)
+        #[arg(long, value_name = "KEY=VALUE")]
+        config: Vec<String>,
+
+        /// Flat JSON object with `${config.KEY}` values
+        #[arg(long)]
+        config_file: Option<String>,
+
+        /// Sample perf stats (memory, CPU, jank, battery) for this Android package during replay
+        #[arg(long, value_name = "PACKAGE")]
+        perf: Option<String>,
+
+        /// Perf sampling interval in milliseconds
+        #[arg(long, default_value = "1000")]
+        perf_interval: u64,
+
+        /// Strings catalog for `@key:NAME` args: {"<locale>": {"<key>": "<text>"}}
+        #[arg(long, value_name = "FILE")]
+        strings: Option<String>,
+
+        /// Catalog locale to resolve `@key:` args with; Android devices are switched to it
+        /// for the run (default: the only one in the catalog)
+        #[arg(long)]
+        locale: Option<String>,
+
+        /// Root of the shared baseline tree for assert-screen steps (default: ~/.claude-mobile/baselines)
+        #[arg(long, value_name = "DIR")]
+        baselines: Option<String>,
+
+        /// Save the current screen as the baseline of every assert-screen step instead of comparing
+        #[arg(long, default_value = "false")]
+        update_baselines: bool,
+
+        /// Android: fail a step whose app CPU stays above this percentage for --watchdog-cpu-window
+        #[arg(long, value_name = "PERCENT")]
+        watchdog_cpu: Option<f64>,
+
+        /// Seconds the CPU must stay above --watchdog-cpu before the step fails
+        #[arg(long, default_value = "10", value_name = "SECS")]
+        watchdog_cpu_window: u64,
+
+        /// Android: fail a step once the app's memory (total PSS, sampled every 5s) exceeds this many MB
+        #[arg(long, value_name = "MB")]
+        watchdog_memory: Option<f64>,
+
+        /// App the watchdog monitors (default: the --perf package, else the foreground app)
+        #[arg(long, value_name = "PACKAGE")]
+        watchdog_package: Option<String>,
     },
 
     /// Export a scenario as flow_steps JSON or markdown
@@ -1785,6 +2938,118 @@ pub enum RecorderCommands {
               value_parser = ["flow_steps", "markdown"])]
         format: String,
     },
+
+    /// Export every scenario as one CSV for TestRail/Xray import
+    ExportCatalog {
+        /// CSV file to write
+        output: String,
+
+        /// Directory to scan recursively (default: ~/.claude-mobile/scenarios)
+        #[arg(long)]
+        dir: Option<String>,
+
+        /// JSON column mapping: header per field (id, title, description,
+        /// priority, steps, labels; null drops it) and `priorities`
+        #[arg(long, value_name = "FILE")]
+        columns: Option<String>,
+    },
+
+    /// Write a new case skeleton with a fresh id, ready to fill in
+    Scaffold {
+        /// Case title; the id is derived from it
+        name: String,
+
+        /// Target platform (android, ios, aurora, desktop)
+        #[arg(short, long, default_value = "android")]
+        platform: String,
+
+        /// Directory to write to (default: ~/.claude-mobile/scenarios/<platform>)
+        #[arg(long)]
+        dir: Option<String>,
+    },
+
+    /// Create a scenario from a Markdown test case with an Action | Expected steps table
+    Import {
+        /// Markdown file
+        file: String,
+
+        /// Platform to save under (default: the document's Platform field, else android)
+        #[arg(short, long)]
+        platform: Option<String>,
+
+        /// Scenario name (default: the document title, else the file name)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Overwrite an existing scenario with the same name
+        #[arg(long, default_value = "false")]
+        force: bool,
+    },
+
+    /// Render an HTML pass-rate/regression/flakiness summary of saved suite reports
+    Trend {
+        /// Directory of suite JSON reports (see `suite --history`)
+        history_dir: String,
+
+        /// HTML file to write
+        #[arg(short, long, default_value = "trend.html")]
+        output: String,
+    },
+
+    /// Count cases per linked feature and platform, highlighting features untested on a platform
+    Coverage {
+        /// Directory to scan recursively (default: ~/.claude-mobile/scenarios)
+        #[arg(long)]
+        dir: Option<String>,
+
+        /// Platform columns to show and check (repeatable; default: every platform with a case)
+        #[arg(short, long)]
+        platform: Vec<String>,
+
+        /// Output format
+        #[arg(long, default_value = "markdown", value_parser = ["markdown", "html"])]
+        format: String,
+
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Map requirement ids in case metadata to the cases covering them, as a Markdown audit table
+    Traceability {
+        /// Directory to scan recursively (default: ~/.claude-mobile/scenarios)
+        #[arg(long)]
+        dir: Option<String>,
+
+        /// File of requirement ids, one per line; ids no case covers are flagged
+        #[arg(long, value_name = "FILE")]
+        requirements: Option<String>,
+
+        /// Metadata key holding requirement ids (repeatable; default: requirement, jira)
+        #[arg(long)]
+        key: Vec<String>,
+
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Compare two suite reports: regressions, fixes, new/removed cases and duration changes
+    DiffRuns {
+        /// Earlier suite JSON report (e.g. from the base branch)
+        before: String,
+
+        /// Later suite JSON report
+        after: String,
+
+        /// Print the diff as JSON instead of Markdown
+        #[arg(long, default_value = "false")]
+        json: bool,
+
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
 }
 
 // -- Sync subcommands ---------------------------------------------------------
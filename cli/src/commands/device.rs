@@ -3,32 +3,60 @@
 //! Each public function here corresponds to a CLI subcommand that interacts
 //! with a physical or emulated device (Android, iOS, Aurora, Desktop).
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
 use crate::utils::shell_gate;
 use crate::{android, aurora, desktop, ios, screenshot, scale};
 
 // -- Screenshot / Annotate ----------------------------------------------------
 
+#[allow(clippy::too_many_arguments)]
 pub fn screenshot(
     platform: &str,
     output: Option<&str>,
-    compress: bool,
-    max_width: u32,
-    quality: u8,
+    compress: Option<screenshot::CompressOptions>,
+    grid: Option<screenshot::GridOverlay>,
+    trim: Option<screenshot::TrimOptions>,
+    android_options: screenshot::AndroidCaptureOptions,
+    desktop_scope: screenshot::DesktopScope,
+    evidence: Option<screenshot::EvidenceFooter>,
+    purpose: Option<screenshot::CapturePurpose>,
     simulator: Option<&str>,
     device: Option<&str>,
     companion_path: Option<&str>,
 ) -> Result<()> {
-    if platform == "desktop" {
-        let data = desktop::screenshot(companion_path)?;
-        return write_or_base64(output, &data);
-    }
-    if platform == "aurora" {
-        let data = aurora::screenshot(device)?;
-        return write_or_base64(output, &data);
-    }
-    screenshot::take_screenshot(platform, output, compress, max_width, quality, simulator, device)
+    let backend: Box<dyn screenshot::CaptureBackend> = match desktop_scope {
+        screenshot::DesktopScope::Screen => {
+            screenshot::backend_for(platform, simulator, device, companion_path, android_options)?
+        }
+        _ if platform != "desktop" => anyhow::bail!("--window and --region are only supported on desktop"),
+        scope => Box::new(screenshot::DesktopCapture { companion_path: companion_path.map(str::to_owned), scope }),
+    };
+    let evidence = match evidence {
+        Some(mut footer) => {
+            let who = match platform {
+                "android" => {
+                    let (serial, model) = android::device_identity(device)?;
+                    format!("{} · {}", serial, model)
+                }
+                "ios" => format!("iOS simulator {}", simulator.unwrap_or("booted")),
+                "desktop" => "desktop".to_string(),
+                other => format!("{} {}", other, device.unwrap_or("(default device)")),
+            };
+            footer.lines.insert(0, format!("{} · {}", who, crate::commands::recorder::now_iso8601()));
+            Some(footer)
+        }
+        None => None,
+    };
+    let format = match purpose {
+        Some(purpose) => {
+            let config = crate::commands::config::load_config();
+            let lookup = |key: &str| config.get(key).map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_owned));
+            Some(screenshot::CaptureEncodings::from_config(lookup)?.format(purpose))
+        }
+        None => None,
+    };
+    screenshot::take_screenshot(backend.as_ref(), output, compress, grid, trim, evidence.as_ref(), format)
 }
 
 pub fn annotate(
@@ -40,8 +68,187 @@ pub fn annotate(
     screenshot::take_annotated_screenshot(platform, output, device, simulator)
 }
 
+pub fn theme_pair(output: Option<&str>, settle: u64, device: Option<&str>) -> Result<()> {
+    let original = android::night_mode(device)?;
+    let pair = screenshot::capture_theme_pair(
+        || android::screenshot(device),
+        |theme| android::set_night_mode(Some(theme == screenshot::Theme::Dark), device),
+        settle,
+    );
+    // Put the device back the way we found it, even if the capture failed.
+    android::restore_night_mode(&original, device)?;
+    let pair = pair?;
+
+    let composite = screenshot::compose_side_by_side(&pair.light, &pair.dark, 16);
+    write_image_or_base64(output, &image::DynamicImage::ImageRgba8(composite))
+}
+
+pub fn cvd_preview(
+    platform: &str,
+    kind: Option<&str>,
+    output: Option<&str>,
+    simulator: Option<&str>,
+    device: Option<&str>,
+    companion_path: Option<&str>,
+) -> Result<()> {
+    let kinds = match kind {
+        Some(k) => vec![k.parse::<screenshot::CvdType>()?],
+        None => screenshot::CvdType::ALL.to_vec(),
+    };
+    let backend = screenshot::backend_for(
+        platform,
+        simulator,
+        device,
+        companion_path,
+        screenshot::AndroidCaptureOptions::default(),
+    )?;
+    let strip = screenshot::compose_cvd_strip(&backend.capture()?, &kinds);
+    write_image_or_base64(output, &image::DynamicImage::ImageRgba8(strip))
+}
+
+pub fn heatmap(frames: &[String], output: Option<&str>) -> Result<()> {
+    let images = frames
+        .iter()
+        .map(|path| image::open(path).with_context(|| format!("Cannot read {}", path)))
+        .collect::<Result<Vec<_>>>()?;
+    let map = screenshot::change_heatmap(&images)?;
+    write_image_or_base64(output, &image::DynamicImage::ImageRgba8(map))
+}
+
+pub fn baseline_mask(baseline: &str, add: &[String], clear: bool, suggest: &[String]) -> Result<()> {
+    let baseline = std::path::Path::new(baseline);
+    let mut rects = add.iter().map(|r| screenshot::MaskRect::parse(r)).collect::<Result<Vec<_>>>()?;
+    if !suggest.is_empty() {
+        let frames = suggest
+            .iter()
+            .map(|path| image::open(path).with_context(|| format!("Cannot read {}", path)))
+            .collect::<Result<Vec<_>>>()?;
+        let suggested = screenshot::suggest_mask(&frames, 16)?;
+        println!("Suggested {} region(s) that change between captures", suggested.len());
+        rects.extend(suggested);
+    }
+    let mask = if rects.is_empty() && !clear {
+        screenshot::IgnoreMask::load(baseline)?.unwrap_or_default()
+    } else {
+        screenshot::edit_mask(baseline, &rects, clear)?
+    };
+    if mask.rects.is_empty() {
+        println!("No mask rects for {}", baseline.display());
+    }
+    for r in &mask.rects {
+        println!("{},{},{},{}", r.x, r.y, r.width, r.height);
+    }
+    Ok(())
+}
+
+pub fn wait_for_device(timeout: u64, wake: bool, device: Option<&str>) -> Result<()> {
+    android::wait_for_device(device, std::time::Duration::from_secs(timeout), wake)?;
+    println!("Device ready");
+    Ok(())
+}
+
+/// Change the locale and leave it in place (the CLI call does not restore it).
+pub fn set_locale(locale: &str, package: Option<&str>, device: Option<&str>) -> Result<()> {
+    android::set_locale(locale, package, device)?.keep();
+    Ok(())
+}
+
+/// Change the font scale and leave it in place (the CLI call does not restore it).
+pub fn font_scale(scale: f32, package: Option<&str>, device: Option<&str>) -> Result<()> {
+    android::set_font_scale(scale, package, device)?.keep();
+    println!("Font scale set to {}", scale);
+    Ok(())
+}
+
+/// Change the display size and leave it in place (the CLI call does not restore it).
+pub fn display_size(width: &str, package: Option<&str>, device: Option<&str>) -> Result<()> {
+    let dp = match width {
+        "reset" => None,
+        w => Some(w.parse::<u32>().map_err(|_| anyhow::anyhow!("Invalid width '{}': expected dp, e.g. 320, or reset", w))?),
+    };
+    android::set_display_size(dp, package, device)?.keep();
+    match dp {
+        Some(dp) => println!("Display size set to {}dp wide", dp),
+        None => println!("Display size reset"),
+    }
+    Ok(())
+}
+
+pub fn ensure_installed(package: &str, version: Option<&str>, timeout: u64, device: Option<&str>) -> Result<()> {
+    let installed = android::ensure_installed(
+        package,
+        version,
+        std::time::Duration::from_secs(timeout),
+        device,
+    )?;
+    println!("{} {}", package, installed);
+    Ok(())
+}
+
+pub fn screenshot_all(devices: &[String], output_dir: &str, concurrency: usize) -> Result<()> {
+    let serials: Vec<String> = if devices.is_empty() {
+        android::list_devices()?
+            .into_iter()
+            .filter(|d| d.state == "device")
+            .map(|d| d.serial)
+            .collect()
+    } else {
+        devices.to_vec()
+    };
+    if serials.is_empty() {
+        anyhow::bail!("No connected Android devices");
+    }
+    std::fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create {}", output_dir))?;
+
+    let targets: Vec<screenshot::CaptureTarget> = serials
+        .into_iter()
+        .map(|serial| screenshot::CaptureTarget {
+            backend: Box::new(screenshot::AndroidCapture {
+                device: Some(serial.clone()),
+                options: screenshot::AndroidCaptureOptions::default(),
+            }),
+            id: serial,
+        })
+        .collect();
+    let mut failed = 0;
+    for (serial, result) in screenshot::capture_all(&targets, concurrency)? {
+        let path = std::path::Path::new(output_dir).join(format!("{}.png", serial.replace([':', '/'], "_")));
+        match result.and_then(|img| img.save_png(&path).map(|()| img.dimensions())) {
+            Ok((w, h)) => println!("{}  {} ({}x{})", serial, path.display(), w, h),
+            Err(e) => {
+                failed += 1;
+                println!("{}  FAILED: {:#}", serial, e);
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} device(s) failed to capture", failed, targets.len());
+    }
+    Ok(())
+}
+
+pub fn frame_stream(count: u32, interval_ms: u64, output_dir: &str, device: Option<&str>) -> Result<()> {
+    std::fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create {}", output_dir))?;
+    let started = std::time::Instant::now();
+    let stream = android::FrameStream::start(device)?;
+    for i in 0..count {
+        if i > 0 {
+            std::thread::sleep(std::time::Duration::from_millis(interval_ms));
+        }
+        let frame = stream.latest()?;
+        let path = std::path::Path::new(output_dir).join(format!("frame-{:03}.png", i + 1));
+        frame.save(&path).with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("{} ({} ms)", path.display(), started.elapsed().as_millis());
+    }
+    println!("Decoded {} frames, saved {}", stream.frame_count(), count);
+    Ok(())
+}
+
 // -- Tap / Long press ---------------------------------------------------------
 
+#[allow(clippy::too_many_arguments)]
 pub fn tap(
     platform: &str,
     x: i32,
@@ -72,21 +279,18 @@ pub fn long_press(
     platform: &str,
     x: i32,
     y: i32,
-    duration: u32,
+    duration: Option<u32>,
     text: Option<&str>,
     simulator: Option<&str>,
     device: Option<&str>,
 ) -> Result<()> {
     if let Some(t) = text {
-        if let Some((cx, cy)) = android::find_element(t, device)? {
-            return android::long_press(cx, cy, duration, device);
-        }
-        anyhow::bail!("Element '{}' not found for long press", t);
+        return android::long_press_element(t, duration, device);
     }
     match platform {
-        "android" => android::long_press(x, y, duration, device),
-        "ios" => ios::long_press(x, y, duration, simulator),
-        "aurora" => aurora::long_press(x, y, duration, device),
+        "android" => android::long_press(x, y, android::long_press_duration(duration, device), device),
+        "ios" => ios::long_press(x, y, duration.unwrap_or(1000), simulator),
+        "aurora" => aurora::long_press(x, y, duration.unwrap_or(1000), device),
         _ => unreachable!(),
     }
 }
@@ -132,6 +336,7 @@ pub fn shell(
 
 // -- Swipe --------------------------------------------------------------------
 
+#[allow(clippy::too_many_arguments)]
 pub fn swipe(
     platform: &str,
     mut x1: i32,
@@ -165,15 +370,57 @@ pub fn swipe(
     }
 }
 
+// -- Multi-touch (Android-only) -----------------------------------------------
+
+pub fn pinch(
+    center_x: i32,
+    center_y: i32,
+    start_dist: u32,
+    end_dist: u32,
+    duration: u32,
+    device: Option<&str>,
+) -> Result<()> {
+    android::pinch(center_x, center_y, start_dist, end_dist, duration, device)
+}
+
+pub fn multi_touch(pointers_json: &str, duration: u32, device: Option<&str>) -> Result<()> {
+    let raw: Vec<Vec<(i32, i32)>> = serde_json::from_str(pointers_json)
+        .map_err(|e| anyhow::anyhow!("pointers must be a JSON array of [[x,y],…] paths: {}", e))?;
+    let pointers: Vec<android::PointerPath> = raw
+        .into_iter()
+        .map(|points| android::PointerPath { points })
+        .collect();
+    android::multi_touch(&pointers, duration, device)
+}
+
+pub fn select_text(
+    start_x: i32,
+    start_y: i32,
+    end_x: i32,
+    end_y: i32,
+    hold: u32,
+    duration: u32,
+    device: Option<&str>,
+) -> Result<()> {
+    android::select_text_range((start_x, start_y), (end_x, end_y), hold, duration, device)
+}
+
 // -- Text input / key press ---------------------------------------------------
 
 pub fn input(
     platform: &str,
     text: &str,
+    char_delay_ms: Option<u64>,
     simulator: Option<&str>,
     device: Option<&str>,
     companion_path: Option<&str>,
 ) -> Result<()> {
+    if let Some(ms) = char_delay_ms {
+        if platform != "android" {
+            anyhow::bail!("--char-delay is only supported on android");
+        }
+        return android::type_text_slow(text, std::time::Duration::from_millis(ms), device);
+    }
     match platform {
         "android" => android::input_text(text, device),
         "ios" => ios::input_text(text, simulator),
@@ -403,11 +650,266 @@ pub fn reboot(
     }
 }
 
+pub fn erase_simulator(simulator: &str, yes: bool, shutdown: bool) -> Result<()> {
+    if !yes {
+        anyhow::bail!(
+            "Erasing '{}' deletes all of its apps, data and settings; re-run with --yes to confirm",
+            simulator
+        );
+    }
+    ios::erase(simulator, shutdown)
+}
+
+pub fn appearance(mode: &str, simulator: Option<&str>) -> Result<()> {
+    ios::set_appearance(ios::Appearance::parse(mode)?, simulator)
+}
+
+pub fn content_size(category: &str, simulator: Option<&str>) -> Result<()> {
+    ios::set_content_size(category, simulator)
+}
+
 pub fn screen(state: &str, device: Option<&str>) -> Result<()> {
     let on = state == "on";
     android::screen_power(on, device)
 }
 
+pub fn doze(state: &str, device: Option<&str>) -> Result<()> {
+    let idle = android::set_doze(state == "on", device)?;
+    println!("Deep idle state: {}", idle);
+    Ok(())
+}
+
+pub fn dismiss_dialogs(policy: Option<&str>, device: Option<&str>) -> Result<()> {
+    let policy = policy.map(android::DialogPolicy::parse).transpose()?.unwrap_or_default();
+    let handled = android::DialogHandler { policy }.clear(device)?;
+    if handled.is_empty() {
+        println!("No system dialog to handle");
+    }
+    for dialog in handled {
+        println!("Handled {} dialog: {} (\"{}\")", dialog.kind.as_str(), dialog.response, dialog.button);
+    }
+    Ok(())
+}
+
+pub fn dnd(mode: Option<&str>, device: Option<&str>) -> Result<()> {
+    let mode = match mode {
+        Some(m) => android::set_dnd(android::DndMode::parse(m)?, device)?,
+        None => android::get_dnd(device)?,
+    };
+    println!("Do Not Disturb: {}", mode.as_str());
+    Ok(())
+}
+
+/// "Already connected" is reported but not an error.
+pub fn adb_connect(host: &str, port: u16) -> Result<()> {
+    match android::connect(host, port) {
+        Ok(target) => println!("Connected to {}", target),
+        Err(e) => match e.downcast_ref::<android::AdbNetworkError>() {
+            Some(already @ android::AdbNetworkError::AlreadyConnected(_)) => println!("{}", already),
+            _ => return Err(e),
+        },
+    }
+    Ok(())
+}
+
+pub fn adb_disconnect(host: &str, port: u16) -> Result<()> {
+    println!("Disconnected {}", android::disconnect(host, port)?);
+    Ok(())
+}
+
+pub fn adb_pair(host: &str, port: u16, code: &str) -> Result<()> {
+    let target = android::pair(host, port, code)?;
+    println!("Paired with {}; now run adb-connect with the device's connect port", target);
+    Ok(())
+}
+
+/// Set up (or with `remove`, tear down) a port mapping and leave it in
+/// place after the command exits. Only the listening side is needed to remove.
+pub fn adb_port(
+    direction: android::PortDirection,
+    local: Option<&str>,
+    remote: Option<&str>,
+    remove: bool,
+    device: Option<&str>,
+) -> Result<()> {
+    let (listener, target) = match direction {
+        android::PortDirection::Forward => (local, remote),
+        android::PortDirection::Reverse => (remote, local),
+    };
+    let listener = listener.context("Missing listening socket")?;
+    if remove {
+        android::remove_port_mapping(direction, listener, device)?;
+        println!("Removed {}", listener);
+        return Ok(());
+    }
+    let target = target.context("Missing target socket")?;
+    let mapping = match direction {
+        android::PortDirection::Forward => android::forward(listener, target, device)?,
+        android::PortDirection::Reverse => android::reverse(listener, target, device)?,
+    };
+    match direction {
+        android::PortDirection::Forward => println!("Forwarding host {} -> device {}", mapping.local(), mapping.remote()),
+        android::PortDirection::Reverse => println!("Reversing device {} -> host {}", mapping.remote(), mapping.local()),
+    }
+    mapping.keep();
+    Ok(())
+}
+
+pub fn remote_status(device: Option<&str>) -> Result<()> {
+    let remote = crate::remote::RemoteDevice::connect(device)?;
+    let health = remote.health()?;
+    println!("Remote device '{}' is {}", remote.id(), health.state);
+    if let Some(platform) = health.platform {
+        println!("  Platform: {}", platform);
+    }
+    if let Some(model) = health.model {
+        println!("  Model: {}", model);
+    }
+    Ok(())
+}
+
+pub fn tap_image(template: &str, threshold: f32, device: Option<&str>) -> Result<()> {
+    if !(0.0..=1.0).contains(&threshold) {
+        anyhow::bail!("--threshold must be between 0.0 and 1.0");
+    }
+    let template = image::open(template).with_context(|| format!("Cannot read template {}", template))?;
+    let found = android::tap_image(&template, threshold, device)?;
+    let (x, y) = found.center();
+    println!("Tapped at ({}, {}) (match score {:.3})", x, y, found.score);
+    Ok(())
+}
+
+pub fn heap_snapshot(package: &str, output: Option<&str>, device: Option<&str>) -> Result<()> {
+    let snapshot = android::heap_snapshot(package, device)?;
+    let json = serde_json::to_string_pretty(&snapshot)?;
+    match output {
+        Some(path) => {
+            std::fs::write(path, json).with_context(|| format!("Cannot write {}", path))?;
+            let total: u64 = snapshot.instances.values().sum();
+            println!("Heap snapshot of {} saved to {} ({} objects, {} classes)", package, path, total, snapshot.instances.len());
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+pub fn heap_compare(before: &str, after: &str, filter: Option<&str>, max_growth: Option<u64>, top: usize) -> Result<()> {
+    let load = |path: &str| -> Result<android::HeapSnapshot> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Cannot read {}", path))?;
+        serde_json::from_str(&text).with_context(|| format!("{} is not a heap snapshot", path))
+    };
+    let (before, after) = (load(before)?, load(after)?);
+    let grown: Vec<_> = android::compare_heaps(&before, &after)
+        .into_iter()
+        .filter(|g| filter.is_none_or(|f| g.class.contains(f)))
+        .collect();
+    if grown.is_empty() {
+        println!("No class grew");
+    }
+    for g in grown.iter().take(top) {
+        println!("  +{:<8} {} ({} -> {})", g.delta(), g.class, g.before, g.after);
+    }
+    if let Some(max) = max_growth {
+        let over: Vec<_> = grown.iter().filter(|g| g.delta() > max).collect();
+        if !over.is_empty() {
+            anyhow::bail!(
+                "{} class(es) grew by more than {} instance(s), worst: {} (+{})",
+                over.len(),
+                max,
+                over[0].class,
+                over[0].delta()
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn unlock(pin: Option<&str>, device: Option<&str>) -> Result<()> {
+    if android::unlock(pin, device)? {
+        println!("Device unlocked");
+    } else {
+        println!("Device was already unlocked");
+    }
+    Ok(())
+}
+
+pub fn hardware_info(features: &[String], json: bool, device: Option<&str>) -> Result<()> {
+    if !features.is_empty() {
+        let mut missing = Vec::new();
+        for feature in features {
+            let present = android::has_feature(device, feature)?;
+            println!("{}: {}", android::feature_name(feature), if present { "yes" } else { "no" });
+            if !present {
+                missing.push(android::feature_name(feature));
+            }
+        }
+        if !missing.is_empty() {
+            anyhow::bail!("Missing feature(s): {}", missing.join(", "));
+        }
+        return Ok(());
+    }
+    let info = android::hardware_info(device)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+    for name in android::HardwareInfo::NAMES {
+        let Some(state) = info.get(name) else { continue };
+        let status = match (state.present, state.available) {
+            (false, _) => "absent",
+            (true, Some(true)) => "present, available",
+            (true, Some(false)) => "present, not available",
+            (true, None) => "present, state unknown",
+        };
+        println!("{:<12} {}", name, status);
+    }
+    Ok(())
+}
+
+/// Switch TalkBack and leave it that way (the CLI call does not restore it).
+pub fn talkback(state: &str, device: Option<&str>) -> Result<()> {
+    android::set_talkback(state == "on", device)?.keep();
+    println!("TalkBack {}", state);
+    Ok(())
+}
+
+pub fn assert_element(query: &str, check: &str, value: Option<&str>, json: bool, device: Option<&str>) -> Result<()> {
+    let assertion = android::ElementAssertion::parse(check, value)?;
+    let result = android::assert_element(query, &assertion, device)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else if result.passed {
+        println!("{}", result.message());
+    }
+    if !result.passed {
+        anyhow::bail!("{}", result.message());
+    }
+    Ok(())
+}
+
+pub fn toasts(duration_secs: u64, expect: Option<&str>, device: Option<&str>) -> Result<()> {
+    let duration = std::time::Duration::from_secs(duration_secs);
+    if let Some(needle) = expect {
+        let toast = android::ToastWatch::start(device)?.wait_for(needle, duration)?;
+        println!("Toast: {}", toast);
+        return Ok(());
+    }
+    let toasts = android::capture_toasts(duration, device)?;
+    if toasts.is_empty() {
+        println!("No toasts in {}s", duration_secs);
+    }
+    for toast in toasts {
+        println!("Toast: {}", toast);
+    }
+    Ok(())
+}
+
+pub fn standby_bucket(package: &str, bucket: &str, device: Option<&str>) -> Result<()> {
+    let actual = android::set_standby_bucket(package, bucket, device)?;
+    println!("{} standby bucket: {}", package, actual);
+    Ok(())
+}
+
 pub fn screen_size(
     platform: &str,
     simulator: Option<&str>,
@@ -433,11 +935,68 @@ pub fn wait(ms: u64) -> Result<()> {
 
 // -- UI inspection commands ---------------------------------------------------
 
+pub fn tree_snapshot(output: Option<&str>, bounds: bool, compare: Option<&str>, device: Option<&str>) -> Result<()> {
+    let snapshot = android::snapshot_tree(device, bounds)?;
+    if let Some(path) = compare {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Cannot read {}", path))?;
+        let baseline: android::AccessibilityTree =
+            serde_json::from_str(&text).with_context(|| format!("{} is not a tree snapshot", path))?;
+        let changes = android::compare_tree(&snapshot, &baseline);
+        for change in &changes {
+            println!("{}", change);
+        }
+        if !changes.is_empty() {
+            anyhow::bail!("Accessibility tree differs from {} ({} change(s))", path, changes.len());
+        }
+        println!("Accessibility tree matches {}", path);
+        return Ok(());
+    }
+    let json = serde_json::to_string_pretty(&snapshot)?;
+    match output {
+        Some(path) => {
+            std::fs::write(path, json + "\n").with_context(|| format!("Cannot write {}", path))?;
+            println!("Tree snapshot saved to {}", path);
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+pub fn wait_for_text(text: &str, timeout_ms: u64, device: Option<&str>) -> Result<()> {
+    let found = android::wait_for_text(device, text, std::time::Duration::from_millis(timeout_ms))?;
+    let (x, y) = found.center();
+    let (l, t, r, b) = found.bounds;
+    println!("Found '{}' at ({}, {}) via {}, bounds [{},{}][{},{}]", text, x, y, found.source.as_str(), l, t, r, b);
+    Ok(())
+}
+
+pub fn webview_eval(
+    expression: Option<&str>,
+    exists: Option<&str>,
+    package: Option<&str>,
+    page: Option<&str>,
+    chrome: bool,
+    device: Option<&str>,
+) -> Result<()> {
+    if let Some(selector) = exists {
+        let found = android::eval_js(device, &android::selector_exists_js(selector), package, page, chrome)?;
+        if found != serde_json::Value::Bool(true) {
+            anyhow::bail!("No element matches '{}' in the WebView", selector);
+        }
+        println!("Element '{}' present", selector);
+        return Ok(());
+    }
+    let value = android::eval_js(device, expression.unwrap_or_default(), package, page, chrome)?;
+    println!("{}", serde_json::to_string_pretty(&value)?);
+    Ok(())
+}
+
 /// Poll UI hierarchy until a matching element appears or timeout expires.
 ///
 /// Returns `Ok(())` when the element is found.
 /// Returns an error (which propagates as exit code 1 via main) if the timeout
 /// expires before the element becomes visible.
+#[allow(clippy::too_many_arguments)]
 pub fn ui_wait(
     platform: &str,
     text: Option<&str>,
@@ -681,6 +1240,10 @@ pub fn sensor_notifications(package: Option<&str>, device: Option<&str>) -> Resu
     android::sensor_notifications(package, device)
 }
 
+pub fn sensor_alarms(package: Option<&str>, device: Option<&str>) -> Result<()> {
+    android::print_alarms(package, device)
+}
+
 pub fn sensor_thermal(status: Option<&str>, reset: bool, device: Option<&str>) -> Result<()> {
     android::sensor_thermal(status, reset, device)
 }
@@ -704,6 +1267,10 @@ pub fn network_proxy(
     android::network_proxy(host, port, clear, device)
 }
 
+pub fn network_requests(seconds: u64, har: Option<&str>, device: Option<&str>) -> Result<()> {
+    android::network_requests(seconds, har, device)
+}
+
 pub fn network_airplane(enabled: bool, device: Option<&str>) -> Result<()> {
     android::network_airplane(enabled, device)
 }
@@ -819,6 +1386,16 @@ pub fn intent_broadcast(
     android::intent_broadcast(action, package, component, extras, device)
 }
 
+pub fn service_call(service: &str, code: u32, args: &[String], device: Option<&str>) -> Result<()> {
+    let args = args
+        .iter()
+        .map(|a| android::ServiceCallArg::parse(a))
+        .collect::<Result<Vec<_>>>()?;
+    let reply = android::service_call(service, code, &args, device)?;
+    println!("{}", serde_json::to_string_pretty(&reply)?);
+    Ok(())
+}
+
 pub fn intent_deeplink(
     platform: &str,
     uri: &str,
@@ -925,6 +1502,23 @@ pub fn perf_monitor(
     android::perf_monitor(package, count, interval_ms, device)
 }
 
+/// Sample perf stats for a package over a time window.
+pub fn perf_capture(
+    package: &str,
+    duration_ms: u64,
+    interval_ms: u64,
+    device: Option<&str>,
+) -> Result<()> {
+    let sample = android::capture_perf(
+        package,
+        std::time::Duration::from_millis(duration_ms),
+        interval_ms,
+        device,
+    )?;
+    println!("{}", serde_json::to_string_pretty(&sample)?);
+    Ok(())
+}
+
 /// Extract recent crashes and ANRs from logcat.
 pub fn perf_crashes(package: Option<&str>, lines: usize, device: Option<&str>) -> Result<()> {
     android::perf_crashes(package, lines, device)
@@ -937,17 +1531,25 @@ pub fn perf_framestats(package: &str, device: Option<&str>) -> Result<()> {
 
 // -- Helpers ------------------------------------------------------------------
 
-/// Write raw bytes to a file, or encode as base64 and print to stdout.
-fn write_or_base64(output: Option<&str>, data: &[u8]) -> Result<()> {
+/// Write an image as PNG to a file, or print it base64-encoded to stdout.
+/// The PNG is streamed either way, so large composites are never held in
+/// memory in encoded form.
+fn write_image_or_base64(output: Option<&str>, img: &image::DynamicImage) -> Result<()> {
+    use std::io::Write;
+
+    let png = screenshot::EncodeFormat::Png;
     if let Some(path) = output {
-        std::fs::write(path, data)?;
+        let file = std::fs::File::create(path).with_context(|| format!("Cannot create {}", path))?;
+        screenshot::encode_to_writer(img, std::io::BufWriter::new(file), png)?;
         eprintln!("Screenshot saved to: {}", path);
     } else {
-        let b64 = base64::Engine::encode(
-            &base64::engine::general_purpose::STANDARD,
-            data,
-        );
-        println!("{}", b64);
+        let mut stdout = std::io::stdout().lock();
+        {
+            let b64 = base64::write::EncoderWriter::new(&mut stdout, &base64::engine::general_purpose::STANDARD);
+            screenshot::encode_to_writer(img, b64, png)?;
+        }
+        writeln!(stdout)?;
     }
     Ok(())
 }
+
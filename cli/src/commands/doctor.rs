@@ -49,6 +49,7 @@ fn run_output(program: &str, args: &[&str]) -> Option<String> {
 }
 
 /// Run a command, returning `true` if it exits with status 0.
+#[allow(dead_code)]
 fn run_ok(program: &str, args: &[&str]) -> bool {
     Command::new(program)
         .args(args)
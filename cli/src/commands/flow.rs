@@ -11,6 +11,7 @@ use std::time::Instant;
 use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 
+use crate::platform::{NavAction, Platform};
 use crate::utils::device_shell::DeviceShellCmd;
 use crate::{android, aurora, desktop, ios};
 
@@ -33,8 +34,8 @@ const BLOCKED_ACTIONS: &[&str] = &["shell", "system_shell"];
 /// All recognised action names — anything else is rejected.
 const ALLOWED_ACTIONS: &[&str] = &[
     // core interaction
-    "tap", "tap-text", "input", "swipe", "find", "key", "launch", "stop",
-    "screenshot", "wait", "ui-dump", "open-url",
+    "tap", "tap-text", "long-press", "input", "swipe", "find", "key", "launch", "stop",
+    "screenshot", "wait", "ui-dump", "open-url", "home", "back",
     // Batch 1/2 — sensor
     "sensor-location", "sensor-battery", "sensor-notifications", "sensor-thermal",
     // Batch 1/2 — network
@@ -192,10 +193,10 @@ pub fn run(
         // Check total duration budget
         if total_start.elapsed().as_millis() as u64 >= max_duration {
             // Record remaining steps as skipped
-            for j in i..steps.len() {
+            for (j, skipped) in steps.iter().enumerate().skip(i) {
                 results.push(StepResult {
                     step: j + 1,
-                    action: steps[j].action.clone(),
+                    action: skipped.action.clone(),
                     success: false,
                     message: "Skipped: max duration exceeded".into(),
                     ms: 0,
@@ -280,10 +281,10 @@ pub fn run(
         // Decide whether to continue
         if !success && step.on_error == OnError::Stop {
             // Record remaining as skipped
-            for j in (i + 1)..steps.len() {
+            for (j, skipped) in steps.iter().enumerate().skip(i + 1) {
                 results.push(StepResult {
                     step: j + 1,
-                    action: steps[j].action.clone(),
+                    action: skipped.action.clone(),
                     success: false,
                     message: "Skipped: previous step failed (on_error=stop)".into(),
                     ms: 0,
@@ -486,10 +487,10 @@ pub fn batch(
         });
 
         if !success && step.on_error == OnError::Stop {
-            for j in (i + 1)..steps.len() {
+            for (j, skipped) in steps.iter().enumerate().skip(i + 1) {
                 results.push(StepResult {
                     step: j + 1,
-                    action: steps[j].action.clone(),
+                    action: skipped.action.clone(),
                     success: false,
                     message: "Skipped: previous command failed (stop_on_error=true)".into(),
                     ms: 0,
@@ -614,10 +615,10 @@ pub fn parallel(
 
         'steps: for (i, step) in steps.iter().enumerate() {
             if total_start.elapsed().as_millis() as u64 >= max_duration {
-                for j in i..steps.len() {
+                for (j, skipped) in steps.iter().enumerate().skip(i) {
                     results.push(StepResult {
                         step: j + 1,
-                        action: steps[j].action.clone(),
+                        action: skipped.action.clone(),
                         success: false,
                         message: "Skipped: max duration exceeded".into(),
                         ms: 0,
@@ -694,10 +695,10 @@ pub fn parallel(
             });
 
             if !success && step.on_error == OnError::Stop {
-                for j in (i + 1)..steps.len() {
+                for (j, skipped) in steps.iter().enumerate().skip(i + 1) {
                     results.push(StepResult {
                         step: j + 1,
-                        action: steps[j].action.clone(),
+                        action: skipped.action.clone(),
                         success: false,
                         message: "Skipped: previous step failed (on_error=stop)".into(),
                         ms: 0,
@@ -746,10 +747,13 @@ fn execute_step(ctx: &PlatformCtx<'_>, step: &FlowStep) -> Result<String> {
         // core
         "tap" => step_tap(ctx, &step.args),
         "tap-text" => step_tap_text(ctx, &step.args),
+        "long-press" => step_long_press(ctx, &step.args),
         "input" => step_input(ctx, &step.args),
         "swipe" => step_swipe(ctx, &step.args),
         "find" => step_find(ctx, &step.args),
         "key" => step_key(ctx, &step.args),
+        "home" => step_nav(ctx, NavAction::Home),
+        "back" => step_nav(ctx, NavAction::Back),
         "launch" => step_launch(ctx, &step.args),
         "stop" => step_stop(ctx, &step.args),
         "screenshot" => step_screenshot(ctx, &step.args),
@@ -833,6 +837,19 @@ fn step_tap_text(ctx: &PlatformCtx<'_>, args: &[String]) -> Result<String> {
 fn step_input(ctx: &PlatformCtx<'_>, args: &[String]) -> Result<String> {
     require_args(args, 1, "input")?;
     let text = &args[0];
+    // Optional second arg: per-character delay in ms (Android), for fields
+    // that drop fast input.
+    let char_delay: Option<u64> = match args.get(1) {
+        Some(ms) => Some(ms.parse().map_err(|_| anyhow::anyhow!("Invalid per-character delay '{}'", ms))?),
+        None => None,
+    };
+    if let Some(ms) = char_delay {
+        if ctx.platform != "android" {
+            bail!("Per-character input delay is only supported on android");
+        }
+        android::type_text_slow(text, std::time::Duration::from_millis(ms), ctx.device)?;
+        return Ok(format!("Typed \"{}\" at {} ms per character", text, ms));
+    }
     match ctx.platform {
         "android" => android::input_text(text, ctx.device)?,
         "ios" => ios::input_text(text, ctx.simulator)?,
@@ -843,6 +860,20 @@ fn step_input(ctx: &PlatformCtx<'_>, args: &[String]) -> Result<String> {
     Ok(format!("Typed \"{}\"", text))
 }
 
+fn step_long_press(ctx: &PlatformCtx<'_>, args: &[String]) -> Result<String> {
+    require_args(args, 2, "long-press")?;
+    let x: i32 = args[0].parse().map_err(|_| anyhow::anyhow!("Invalid x coordinate"))?;
+    let y: i32 = args[1].parse().map_err(|_| anyhow::anyhow!("Invalid y coordinate"))?;
+    let duration: Option<u32> = args.get(2).map(|s| s.parse()).transpose().map_err(|_| anyhow::anyhow!("Invalid duration"))?;
+    match ctx.platform {
+        "android" => android::long_press(x, y, android::long_press_duration(duration, ctx.device), ctx.device)?,
+        "ios" => ios::long_press(x, y, duration.unwrap_or(1000), ctx.simulator)?,
+        "aurora" => aurora::long_press(x, y, duration.unwrap_or(1000), ctx.device)?,
+        _ => bail!("Unsupported platform for long-press"),
+    }
+    Ok(format!("Long pressed at ({}, {})", x, y))
+}
+
 fn step_swipe(ctx: &PlatformCtx<'_>, args: &[String]) -> Result<String> {
     require_args(args, 4, "swipe")?;
     let x1: i32 = args[0].parse().map_err(|_| anyhow::anyhow!("Invalid x1"))?;
@@ -886,6 +917,22 @@ fn step_key(ctx: &PlatformCtx<'_>, args: &[String]) -> Result<String> {
     Ok(format!("Pressed key \"{}\"", key))
 }
 
+/// Portable home/back: dispatches per platform, fails with `UnsupportedAction`
+/// where there is no equivalent (back on iOS, anything on desktop).
+fn step_nav(ctx: &PlatformCtx<'_>, action: NavAction) -> Result<String> {
+    let platform: Platform = ctx.platform.parse()?;
+    let target = match platform {
+        Platform::Ios => ctx.simulator,
+        Platform::Desktop => ctx.companion_path,
+        Platform::Android | Platform::Aurora | Platform::Remote => ctx.device,
+    };
+    match action {
+        NavAction::Home => platform.go_home(target)?,
+        NavAction::Back => platform.go_back(target)?,
+    }
+    Ok(format!("Navigated {}", action.as_str()))
+}
+
 fn step_launch(ctx: &PlatformCtx<'_>, args: &[String]) -> Result<String> {
     require_args(args, 1, "launch")?;
     let package = &args[0];
@@ -970,7 +1017,7 @@ fn step_sensor_battery(ctx: &PlatformCtx<'_>, args: &[String]) -> Result<String>
     let status = args.get(1).map(|s| s.as_str());
     let plugged = args.get(2).map(|s| s.as_str());
     android::sensor_battery(level, status, plugged, false, ctx.device)?;
-    Ok(format!("Battery override applied"))
+    Ok("Battery override applied".to_string())
 }
 
 fn step_sensor_notifications(ctx: &PlatformCtx<'_>, args: &[String]) -> Result<String> {
@@ -983,7 +1030,7 @@ fn step_sensor_thermal(ctx: &PlatformCtx<'_>, args: &[String]) -> Result<String>
     let reset = args.first().map(|s| s == "reset").unwrap_or(false);
     let status = if reset { None } else { args.first().map(|s| s.as_str()) };
     android::sensor_thermal(status, reset, ctx.device)?;
-    Ok(if reset { "Thermal status reset".into() } else { format!("Thermal status set") })
+    Ok(if reset { "Thermal status reset".into() } else { "Thermal status set".to_string() })
 }
 
 // ---------------------------------------------------------------------------
@@ -1298,7 +1345,7 @@ fn compact_ui_dump(ctx: &PlatformCtx<'_>) -> Result<String> {
                 .iter()
                 .take(20) // cap to avoid huge output
                 .map(|e| {
-                    let short_class = e.class.split('.').last().unwrap_or(&e.class);
+                    let short_class = e.class.split('.').next_back().unwrap_or(&e.class);
                     if !e.text.is_empty() {
                         format!("{} \"{}\"", short_class, e.text)
                     } else if !e.content_desc.is_empty() {
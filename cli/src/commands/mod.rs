@@ -7,10 +7,12 @@ pub mod config;
 mod device;
 mod doctor;
 mod flow;
+mod path_template;
 pub mod recorder;
 mod setup;
 mod store;
 pub mod sync;
+mod tag_expr;
 
 use anyhow::Result;
 
@@ -25,18 +27,62 @@ pub fn run(command: Commands) -> Result<()> {
             output,
             compress,
             max_width,
-            max_height: _,
+            max_height,
             quality,
             simulator,
             device,
             companion_path,
-            monitor_index: _,
+            monitor_index,
+            trim,
+            crop_status_bar,
+            grid,
+            grid_label_every,
+            allow_secure_bypass,
+            capture_strategy,
+            window,
+            region,
+            evidence,
+            evidence_font_size,
+            evidence_position,
+            test_id,
+            step_id,
+            purpose,
         } => device::screenshot(
             &platform,
             output.as_deref(),
-            compress,
-            max_width,
-            quality,
+            compress.then_some(crate::screenshot::CompressOptions { max_width, max_height, quality }),
+            grid.map(|spacing_px| crate::screenshot::GridOverlay {
+                spacing_px,
+                label_every: grid_label_every,
+            }),
+            (trim || crop_status_bar.is_some()).then_some(crate::screenshot::TrimOptions {
+                status_bar_px: crop_status_bar.unwrap_or(0),
+                borders: trim,
+            }),
+            crate::screenshot::AndroidCaptureOptions {
+                allow_secure_bypass,
+                strategy: match capture_strategy.as_str() {
+                    "auto" => None,
+                    s => Some(crate::android::CaptureStrategy::parse(s)?),
+                },
+            },
+            match (window, region, monitor_index) {
+                (Some(w), _, _) => crate::screenshot::DesktopScope::Window(w),
+                (_, Some(r), _) => crate::screenshot::DesktopScope::Region(r.parse()?),
+                (_, _, Some(i)) => crate::screenshot::DesktopScope::Monitor(i),
+                _ => crate::screenshot::DesktopScope::Screen,
+            },
+            evidence
+                .then(|| -> anyhow::Result<_> {
+                    let ids = [("test", test_id), ("step", step_id)];
+                    Ok(crate::screenshot::EvidenceFooter {
+                        lines: ids.into_iter().filter_map(|(k, v)| v.map(|v| format!("{}: {}", k, v))).collect(),
+                        font_px: evidence_font_size,
+                        position: crate::screenshot::FooterPosition::parse(&evidence_position)?,
+                    })
+                })
+                .transpose()?,
+            purpose.as_deref().map(crate::screenshot::CapturePurpose::parse).transpose()?,
             simulator.as_deref(),
             device.as_deref(),
             companion_path.as_deref(),
@@ -49,6 +95,50 @@ pub fn run(command: Commands) -> Result<()> {
             device,
         } => device::annotate(&platform, output.as_deref(), simulator.as_deref(), device.as_deref()),
 
+        Commands::Heatmap { frames, output } => device::heatmap(&frames, output.as_deref()),
+        Commands::BaselineMask { baseline, add, clear, suggest } => device::baseline_mask(&baseline, &add, clear, &suggest),
+
+        Commands::CvdPreview { platform, kind, output, simulator, device, companion_path } => {
+            device::cvd_preview(
+                &platform,
+                kind.as_deref(),
+                output.as_deref(),
+                simulator.as_deref(),
+                device.as_deref(),
+                companion_path.as_deref(),
+            )
+        }
+
+        Commands::ThemePair { output, settle, device } => {
+            device::theme_pair(output.as_deref(), settle, device.as_deref())
+        }
+
+        Commands::WaitForDevice { timeout, wake, device } => {
+            device::wait_for_device(timeout, wake, device.as_deref())
+        }
+
+        Commands::FontScale { scale, package, device } => {
+            device::font_scale(scale, package.as_deref(), device.as_deref())
+        }
+        Commands::DisplaySize { width, package, device } => {
+            device::display_size(&width, package.as_deref(), device.as_deref())
+        }
+        Commands::SetLocale { locale, package, device } => {
+            device::set_locale(&locale, package.as_deref(), device.as_deref())
+        }
+
+        Commands::EnsureInstalled { package, version, timeout, device } => {
+            device::ensure_installed(&package, version.as_deref(), timeout, device.as_deref())
+        }
+
+        Commands::ScreenshotAll { devices, output_dir, concurrency } => {
+            device::screenshot_all(&devices, &output_dir, concurrency)
+        }
+
+        Commands::FrameStream { count, interval_ms, output_dir, device } => {
+            device::frame_stream(count, interval_ms, &output_dir, device.as_deref())
+        }
+
         Commands::Tap {
             platform,
             x,
@@ -136,15 +226,28 @@ pub fn run(command: Commands) -> Result<()> {
             from_size.as_deref(),
         ),
 
+        Commands::Pinch { center_x, center_y, start_dist, end_dist, duration, device } => {
+            device::pinch(center_x, center_y, start_dist, end_dist, duration, device.as_deref())
+        }
+
+        Commands::MultiTouch { pointers, duration, device } => {
+            device::multi_touch(&pointers, duration, device.as_deref())
+        }
+        Commands::SelectText { start_x, start_y, end_x, end_y, hold, duration, device } => {
+            device::select_text(start_x, start_y, end_x, end_y, hold, duration, device.as_deref())
+        }
+
         Commands::Input {
             platform,
             text,
+            char_delay,
             simulator,
             device,
             companion_path,
         } => device::input(
             &platform,
             &text,
+            char_delay,
             simulator.as_deref(),
             device.as_deref(),
             companion_path.as_deref(),
@@ -273,6 +376,16 @@ pub fn run(command: Commands) -> Result<()> {
             device,
         } => device::current_activity(&platform, simulator.as_deref(), device.as_deref()),
 
+        Commands::EraseSimulator { simulator, yes, shutdown } => {
+            device::erase_simulator(&simulator, yes, shutdown)
+        }
+
+        Commands::Appearance { mode, simulator } => device::appearance(&mode, simulator.as_deref()),
+
+        Commands::ContentSize { category, simulator } => {
+            device::content_size(&category, simulator.as_deref())
+        }
+
         Commands::Reboot {
             platform,
             simulator,
@@ -281,6 +394,44 @@ pub fn run(command: Commands) -> Result<()> {
 
         Commands::Screen { state, device } => device::screen(&state, device.as_deref()),
 
+        Commands::Doze { state, device } => device::doze(&state, device.as_deref()),
+
+        Commands::Dnd { mode, device } => device::dnd(mode.as_deref(), device.as_deref()),
+
+        Commands::DismissDialogs { policy, device } => device::dismiss_dialogs(policy.as_deref(), device.as_deref()),
+
+        Commands::AdbConnect { host, port } => device::adb_connect(&host, port),
+
+        Commands::AdbDisconnect { host, port } => device::adb_disconnect(&host, port),
+
+        Commands::AdbPair { host, port, code } => device::adb_pair(&host, port, &code),
+
+        Commands::AdbForward { local, remote, remove, device } => {
+            device::adb_port(crate::android::PortDirection::Forward, Some(&local), remote.as_deref(), remove, device.as_deref())
+        }
+
+        Commands::AdbReverse { remote, local, remove, device } => {
+            device::adb_port(crate::android::PortDirection::Reverse, local.as_deref(), Some(&remote), remove, device.as_deref())
+        }
+
+        Commands::RemoteStatus { device } => device::remote_status(device.as_deref()),
+
+        Commands::Unlock { pin, device } => device::unlock(pin.as_deref(), device.as_deref()),
+
+        Commands::HardwareInfo { feature, json, device } => device::hardware_info(&feature, json, device.as_deref()),
+        Commands::Talkback { state, device } => device::talkback(&state, device.as_deref()),
+
+        Commands::AssertElement { query, check, value, json, device } => {
+            device::assert_element(&query, &check, value.as_deref(), json, device.as_deref())
+        }
+        Commands::Toasts { duration, expect, device } => {
+            device::toasts(duration, expect.as_deref(), device.as_deref())
+        }
+
+        Commands::StandbyBucket { package, bucket, device } => {
+            device::standby_bucket(&package, &bucket, device.as_deref())
+        }
+
         Commands::ScreenSize {
             platform,
             simulator,
@@ -295,6 +446,18 @@ pub fn run(command: Commands) -> Result<()> {
             device,
         } => device::find_and_tap(&description, min_confidence, device.as_deref()),
 
+        Commands::TapImage { template, threshold, device } => {
+            device::tap_image(&template, threshold, device.as_deref())
+        }
+
+        Commands::HeapSnapshot { package, output, device } => {
+            device::heap_snapshot(&package, output.as_deref(), device.as_deref())
+        }
+
+        Commands::HeapCompare { before, after, filter, max_growth, top } => {
+            device::heap_compare(&before, &after, filter.as_deref(), max_growth, top)
+        }
+
         Commands::PushFile {
             platform,
             local,
@@ -352,6 +515,18 @@ pub fn run(command: Commands) -> Result<()> {
             device::resize_window(&window_id, width, height, companion_path.as_deref())
         }
 
+        Commands::TreeSnapshot { output, bounds, compare, device } => {
+            device::tree_snapshot(output.as_deref(), bounds, compare.as_deref(), device.as_deref())
+        }
+        Commands::WaitForText { text, timeout, device } => device::wait_for_text(&text, timeout, device.as_deref()),
+        Commands::WebviewEval { expression, exists, package, page, chrome, device } => device::webview_eval(
+            expression.as_deref(),
+            exists.as_deref(),
+            package.as_deref(),
+            page.as_deref(),
+            chrome,
+            device.as_deref(),
+        ),
         Commands::UiWait {
             platform,
             text,
@@ -419,6 +594,10 @@ pub fn run(command: Commands) -> Result<()> {
             device::sensor_notifications(package.as_deref(), device.as_deref())
         }
 
+        Commands::SensorAlarms { package, device } => {
+            device::sensor_alarms(package.as_deref(), device.as_deref())
+        }
+
         Commands::SensorThermal { status, reset, device } => {
             device::sensor_thermal(status.as_deref(), reset, device.as_deref())
         }
@@ -436,6 +615,10 @@ pub fn run(command: Commands) -> Result<()> {
             device::network_proxy(host.as_deref(), port, clear, device.as_deref())
         }
 
+        Commands::NetworkRequests { seconds, har, device } => {
+            device::network_requests(seconds, har.as_deref(), device.as_deref())
+        }
+
         Commands::NetworkAirplane { state, device } => {
             device::network_airplane(state == "on", device.as_deref())
         }
@@ -501,6 +684,10 @@ pub fn run(command: Commands) -> Result<()> {
             )
         }
 
+        Commands::ServiceCall { service, code, args, device } => {
+            device::service_call(&service, code, &args, device.as_deref())
+        }
+
         Commands::IntentDeeplink { platform, uri, package, simulator, device } => {
             device::intent_deeplink(
                 &platform,
@@ -628,6 +815,10 @@ pub fn run(command: Commands) -> Result<()> {
             device::perf_monitor(&package, count, interval_ms, device.as_deref())
         }
 
+        Commands::PerfCapture { package, duration_ms, interval_ms, device } => {
+            device::perf_capture(&package, duration_ms, interval_ms, device.as_deref())
+        }
+
         Commands::PerfCrashes { package, lines, device } => {
             device::perf_crashes(package.as_deref(), lines, device.as_deref())
         }
@@ -640,7 +831,7 @@ pub fn run(command: Commands) -> Result<()> {
         Commands::Doctor => doctor::run(),
 
         // -- Recorder commands ------------------------------------------------
-        Commands::Recorder { command } => recorder::run(command),
+        Commands::Recorder { command } => recorder::run(*command),
 
         // -- Sync commands ----------------------------------------------------
         Commands::Sync { command } => sync::run(command),
@@ -656,6 +847,6 @@ pub fn run(command: Commands) -> Result<()> {
         }
 
         // -- REPL supervisor (long-lived JSON-RPC stdio loop) ----------------
-        Commands::ReplSupervisor => crate::plugins::repl::bridge::run_supervisor_loop(),
+        Commands::ReplSupervisor => claude_in_mobile::plugins::repl::bridge::run_supervisor_loop(),
     }
 }
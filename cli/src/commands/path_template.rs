@@ -0,0 +1,171 @@
+//! Output path templates, e.g. `reports/{date}/{suite}/{case_id}/`, so suite
+//! reports and artifacts can follow a team's existing layout.
+//!
+//! Tokens are `{date}` (`YYYY-MM-DD`), `{time}` (`HHMMSS`), `{platform}`,
+//! `{suite}`, `{run_id}`, `{case_id}` and `{step_id}`; the last two are only
+//! known where a path belongs to a case or step. Values have path separators
+//! replaced so they stay one path component. With a root, a template may not
+//! contain `..` and an absolute template must lie inside it.
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+const TOKENS: &[&str] = &["date", "time", "platform", "suite", "run_id", "case_id", "step_id"];
+
+/// Tokens whose value changes from one run to the next.
+const RUN_TOKENS: &[&str] = &["date", "time", "run_id"];
+
+/// A parsed template; see the module docs for the tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTemplate {
+    template: String,
+}
+
+/// Token values for [`PathTemplate::expand`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathVars(BTreeMap<&'static str, String>);
+
+impl PathVars {
+    /// Run-wide values, from the run's RFC 3339 start time
+    /// (`2026-05-27T12:00:00Z`).
+    pub fn run(started_at: &str, platform: &str, suite: &str) -> Self {
+        let (date, time) = started_at.split_once('T').unwrap_or((started_at, ""));
+        let time: String = time.chars().filter(char::is_ascii_digit).collect();
+        let run_id: String = started_at.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        let mut vars = Self::default();
+        for (token, value) in [("date", date), ("time", time.as_str()), ("platform", platform), ("suite", suite), ("run_id", &run_id)] {
+            vars.0.insert(token, value.to_owned());
+        }
+        vars
+    }
+
+    /// These values plus `token`, which must be one of the template tokens.
+    fn with(&self, token: &'static str, value: &str) -> Self {
+        debug_assert!(TOKENS.contains(&token));
+        let mut vars = self.clone();
+        vars.0.insert(token, value.to_owned());
+        vars
+    }
+}
+
+/// Where a run's outputs go: its run-wide token values and optional root.
+#[derive(Debug, Clone)]
+pub struct OutputLayout {
+    vars: PathVars,
+    root: Option<PathBuf>,
+}
+
+impl OutputLayout {
+    pub fn new(vars: PathVars, root: Option<PathBuf>) -> Self {
+        Self { vars, root }
+    }
+
+    /// Expand `template` for the whole run, or for a case or step.
+    pub fn path(&self, template: &PathTemplate, case_id: Option<&str>, step_id: Option<&str>) -> Result<PathBuf> {
+        let mut vars = self.vars.clone();
+        if let Some(case_id) = case_id {
+            vars = vars.with("case_id", case_id);
+        }
+        if let Some(step_id) = step_id {
+            vars = vars.with("step_id", step_id);
+        }
+        template.expand(&vars, self.root.as_deref())
+    }
+}
+
+/// A token value as a single path component.
+fn component(value: &str) -> String {
+    match value.replace(['/', '\\'], "_") {
+        v if v == ".." || v == "." || v.is_empty() => "_".to_string(),
+        v => v,
+    }
+}
+
+impl PathTemplate {
+    /// Check the template's tokens.
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            let Some(len) = rest[open..].find('}') else {
+                bail!("Unclosed '{{' in path template '{}'", template);
+            };
+            let token = &rest[open + 1..open + len];
+            if !TOKENS.contains(&token) {
+                bail!("Unknown token {{{}}} in path template '{}'. Supported: {{{}}}", token, template, TOKENS.join("}, {"));
+            }
+            rest = &rest[open + len + 1..];
+        }
+        Ok(Self { template: template.to_owned() })
+    }
+
+    /// Whether the expanded path differs between runs (`{date}`, `{time}`,
+    /// `{run_id}`), so it cannot name something runs share.
+    pub fn varies_per_run(&self) -> bool {
+        RUN_TOKENS.iter().any(|t| self.template.contains(&format!("{{{}}}", t)))
+    }
+
+    /// Fill in the tokens from `vars`; fails on a token `vars` does not
+    /// have. A relative result is placed under `root`; an absolute one must
+    /// already be inside it, and neither may use `..`.
+    pub fn expand(&self, vars: &PathVars, root: Option<&Path>) -> Result<PathBuf> {
+        if root.is_some() && Path::new(&self.template).components().any(|c| c == Component::ParentDir) {
+            bail!("Path template '{}' may not contain '..' with an output root", self.template);
+        }
+        let mut path = String::new();
+        let mut rest = self.template.as_str();
+        while let Some(open) = rest.find('{') {
+            let close = open + rest[open..].find('}').unwrap_or(rest.len() - open);
+            let token = &rest[open + 1..close];
+            let Some(value) = vars.0.get(token) else {
+                bail!("Path template '{}' uses {{{}}}, which is not known for this path", self.template, token);
+            };
+            path.push_str(&rest[..open]);
+            path.push_str(&component(value));
+            rest = &rest[close + 1..];
+        }
+        path.push_str(rest);
+        let path = PathBuf::from(path);
+        match root {
+            Some(root) if path.is_absolute() && !path.starts_with(root) => {
+                bail!("Path '{}' is outside the output root {}", path.display(), root.display())
+            }
+            Some(root) if !path.is_absolute() => Ok(root.join(path)),
+            _ => Ok(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_and_validate() {
+        let vars = PathVars::run("2026-05-27T08:15:00Z", "android", "smoke");
+        let template = PathTemplate::parse("reports/{date}/{suite}/{case_id}/").unwrap();
+        let case = vars.with("case_id", "login/../../etc");
+        assert_eq!(
+            template.expand(&case, Some(Path::new("/ci/out"))).unwrap(),
+            PathBuf::from("/ci/out/reports/2026-05-27/smoke/login_.._.._etc/")
+        );
+        let run = PathTemplate::parse("{platform}-{time}-{run_id}.json").unwrap();
+        assert_eq!(run.expand(&vars, None).unwrap(), PathBuf::from("android-081500-20260527T081500Z.json"));
+        assert!(format!("{:#}", template.expand(&vars, None).unwrap_err()).contains("uses {case_id}"));
+        let layout = OutputLayout::new(vars.clone(), None);
+        let step = PathTemplate::parse("shots/{case_id}/{step_id}").unwrap();
+        assert_eq!(layout.path(&step, Some("login"), Some("submit")).unwrap(), PathBuf::from("shots/login/submit"));
+
+        assert!(PathTemplate::parse("reports/{build}/").is_err());
+        assert!(PathTemplate::parse("reports/{date").is_err());
+        let parent = PathTemplate::parse("../out/{date}").unwrap();
+        assert_eq!(parent.expand(&vars, None).unwrap(), PathBuf::from("../out/2026-05-27"));
+        assert!(parent.expand(&vars, Some(Path::new("/ci/out"))).is_err());
+        assert!(parent.varies_per_run());
+        assert!(!PathTemplate::parse("history/{platform}").unwrap().varies_per_run());
+        let absolute = PathTemplate::parse("/tmp/{date}").unwrap();
+        assert!(absolute.expand(&vars, Some(Path::new("/ci/out"))).is_err());
+        assert_eq!(absolute.expand(&vars, Some(Path::new("/tmp"))).unwrap(), PathBuf::from("/tmp/2026-05-27"));
+    }
+}
@@ -2,22 +2,199 @@
 //!
 //! Scenarios are stored as JSON files under `~/.claude-mobile/scenarios/<platform>/`.
 //! An active recording session is tracked in `/tmp/claude-mobile-recording-<name>.json`.
+//!
+//! The format is plain JSON, so there are no YAML-style anchors or aliases:
+//! repeated steps are stored expanded, and what `recorder show` prints is
+//! exactly what is on disk. Since scenario files may come from other people,
+//! loading caps the file size and relies on serde_json's nesting limit.
+//!
+//! A platform directory may hold a `_defaults.json` with `severity` (alias
+//! `priority`), `tags` and `platform`. A scenario that omits one of those
+//! fields inherits it from the defaults before it is validated; a field the
+//! scenario sets itself, even to an empty list, always wins.
+//!
+//! `recorder approve` stores a SHA-256 of the scenario's content in
+//! `approvedHash`; play and suite refuse to run a scenario that no longer
+//! matches it. The hash covers the scenario after defaults are applied, so
+//! editing `_defaults.json` also invalidates approvals.
+//!
+//! `recorder scaffold "Login with email"` writes a new case skeleton with an
+//! id derived from the name (`login-with-email`, `-2` appended on a clash),
+//! the git author in `metadata`, and a manual placeholder step to replace.
+//!
+//! `recorder format` rewrites scenario files in canonical form (see
+//! [`normalized_json`]) so hand edits diff cleanly; `--check` only reports.
+//! Layout is not part of the content hash, so formatting keeps approvals.
+//!
+//! `assert-screen` steps compare against a shared baseline tree laid out as
+//! `<root>/<scenario>/<step id>/<platform>.png`; `--update-baselines`
+//! rewrites every baseline a run touches, and nothing else writes there. A
+//! failed comparison leaves a `<platform>.onion.gif` cross-fade from
+//! baseline to actual, plus the actual screen as `<platform>.actual.<ext>`,
+//! in the run's screenshot directory (`--screenshots`, default
+//! [`DEFAULT_SCREENSHOTS`]) and names both in the step error. Baselines are
+//! always PNG and comparisons use the lossless capture; the actual copy is
+//! encoded per the `screenshot.report-format` config key (`png`, `webp`,
+//! `jpeg[:QUALITY]`, default `jpeg:85`).
+//!
+//! `assert-tree [bounds]` (Android) compares the accessibility tree with
+//! `<platform>.tree.json` in the same folder, catching a button that left
+//! the hierarchy or lost its label while the pixels look the same. Node
+//! coordinates are stripped unless `bounds` is given; `--update-baselines`
+//! rewrites the tree baseline too.
+//!
+//! A baseline may have an ignore mask beside it: `<platform>.mask.json`
+//! (an array of `{"x","y","width","height"}` rects) and/or
+//! `<platform>.mask.png`, where light pixels are ignored. Masked pixels are
+//! left out of the difference and counted in the step message; see the
+//! `baseline-mask` command for editing and suggesting masks.
+//!
+//! A scenario may list `platforms` to share one flow across platforms;
+//! steps with `onlyOn` are skipped elsewhere, and `play --all-platforms`
+//! runs the file once per listed platform.
+//!
+//! Steps marked `manual` are for a person: play shows the action and the
+//! expected result (the label) and waits for pass/fail/skip. With
+//! `--no-prompt`, or when stdin is not a terminal, they are skipped and the
+//! case is reported as needing manual verification.
+//!
+//! With `--watchdog-cpu`/`--watchdog-memory` each Android step runs under a
+//! CPU/memory watchdog; its peaks land in the step result either way.
+//!
+//! `preconditions` mixes prose with `{"fixture": "<name>"}` entries. A
+//! fixture is a reusable block of setup steps (login, seed data, permission
+//! grants) stored as `_fixtures/<name>.json` next to the scenarios, holding
+//! `name`, optional `description` and `steps`. Play runs the referenced
+//! fixtures in order before the case, unless it starts at `--from-step`,
+//! and reports them apart from the case's own steps (`run-step` runs them
+//! only with `--with-fixtures`). A failing fixture fails the case without
+//! running it; a missing fixture fails the load.
+//!
+//! `checks` names assertions used in several steps, each an action and its
+//! args (`"logged_in": ["assert-element", "avatar", "enabled"]`); a step
+//! with action `@check:logged_in` runs it, labelled with the check name
+//! unless it has its own label. `_defaults.json` may hold `checks` shared by
+//! the directory, which a scenario's own check of the same name overrides.
+//! Loading fails on a reference to an undefined check.
+//!
+//! `dependsOn` lists cases that must pass first (create an account before
+//! testing its settings). The suite runs cases in dependency order, keeping
+//! the selection order otherwise, and skips a case whose dependency failed or
+//! was skipped, or that the selection (shard, tags, budget) left out. A
+//! dependency naming no case, like a cycle, is an error.
+//!
+//! `before` and `after` list shell commands run on the host (not the
+//! device) around a case: start a mock server, reset a database. They only
+//! run with `--allow-hooks`; otherwise a case that has hooks fails. A
+//! non-zero `before` hook fails the case without running its steps, `after`
+//! hooks always run, and each hook's exit code and output tail go into the
+//! report. `suite --before/--after CMD` wrap the whole suite the same way.
+//!
+//! On Android, `--dismiss-dialogs` answers system dialogs (ANR, crash,
+//! runtime permission, Play Store update) before each step and after one
+//! fails, and retries a failed step once unless replaying it would repeat
+//! a side effect (`input`, `unlock`, `assert-request`). `--dialog-policy`
+//! picks the answers, e.g. `anr=close,permission=deny`; every dialog
+//! handled is listed with its step in the report.
+//!
+//! `suite --suite-timeout SECS` stops starting cases once the suite has run
+//! that long. The case in flight may finish within `--timeout-grace` (default
+//! 30s); past that its running step is abandoned and the case fails as
+//! aborted. Teardown and `--after` hooks still run, and cases that never
+//! started are reported as `not-executed`, apart from `skipped` and `failed`;
+//! the suite then exits non-zero.
+//!
+//! Suite output paths may be templates such as
+//! `--diagnostics 'reports/{date}/{suite}/{case_id}/'`, to fit an existing
+//! artifact layout: `--report` and `--history` take the run tokens (`{date}`,
+//! `{time}`, `{platform}`, `{suite}` from `--suite-name`, `{run_id}`),
+//! `--diagnostics` and `--video` add `{case_id}`, and `--screenshots`, where a
+//! failed `assert-screen` leaves its capture instead of beside the baseline,
+//! adds `{step_id}`. Templates are checked before the first case. With
+//! `--output-root`, relative paths go under it, `..` is rejected and absolute
+//! ones must stay inside it; without one, paths are used as given.
+//!
+//! `suite --sample-budget SECS` runs a smoke subset: cases are drawn at
+//! random, weighted toward higher severity, until their estimated time
+//! (`estimatedDurationSecs`, else the last `--history` duration, else the
+//! step count) fills the budget. `--seed` fixes the draw; by default it is
+//! taken from the current git commit, so a commit always gets the same
+//! sample. Outside git each run draws afresh; the seed is printed so the
+//! draw can be repeated.
+//!
+//! While a suite runs, progress goes to stderr (`[12/87] LOGIN-001 ... pass`):
+//! one status line redrawn in place on a terminal, with step output printed
+//! above it, one line per finished case otherwise. `--quiet` turns it off.
+//! [`run_suite_cases`] takes any [`ProgressReporter`], so other front ends
+//! can drive the same runner with their own.
+//!
+//! `suite --retention` prunes case artifacts (`--video` recordings,
+//! diagnostics, `assert-screen` review captures) once the run is over:
+//! `keep-failures-only` (the default) deletes those of cases that did not
+//! fail, `keep-all` keeps everything, and `keep-last-N` deletes files only
+//! referenced by reports older than the newest N in `--history`. The report
+//! lists only the files that were kept.
+//!
+//! `assert-toast TEXT [TIMEOUT_MS]` passes once a toast containing TEXT has
+//! been shown. Android only; toasts are watched from the step before it,
+//! which is usually the one that raises the toast. A `tap-text` there finds
+//! its element before watching starts, since the watch blocks UI dumps.
+//!
+//! `assert-text TEXT [TIMEOUT_MS]` waits (default 5000 ms) for TEXT in the
+//! hierarchy, or by OCR when the hierarchy has no text (custom-rendered UI);
+//! the step message says which found it. Android only.
+//!
+//! `assert-element QUERY CHECK [VALUE]` checks the first element whose
+//! text, resource-id or content-desc contains QUERY: `text-equals`,
+//! `text-contains`, `enabled`, `disabled`, `checked`, `unchecked`,
+//! `visible` or `gone`. Android only; read from the UI hierarchy.
+//!
+//! `--remote-device <id>` forwards the common actions (tap, input, swipe,
+//! key, launch, …) to a device-farm device via [`crate::remote`]; play
+//! checks the device is online and refuses steps that need a local one.
+//!
+//! `recorder coverage` counts cases per `linkedFeature` and platform, as a
+//! Markdown or HTML grid that marks features with no case on a platform.
+//! Cases without a feature are counted in an "uncategorized" row.
+//!
+//! `recorder traceability` maps the requirement ids in each case's
+//! `requirement` and `jira` metadata (comma-separated for several) to the
+//! cases that cover them, as a Markdown table for audits. With
+//! `--requirements FILE` (one id per line) listed ids no case covers are
+//! flagged, as are ids cases use that the list lacks.
+//!
+//! `recorder diff-runs <before.json> <after.json>` compares two suite
+//! reports: regressions, fixes, new and removed cases, and notable duration
+//! changes, as Markdown for a PR comment (or `--json`).
+//!
+//! `recorder export-catalog` writes every case under a directory to one
+//! CSV (one row per case, steps as a multi-line cell) for TestRail/Xray
+//! bulk import; `--columns` renames or drops columns.
+//!
+//! Markdown exports give each step an anchor `<scenario>-step-<n>`, so links
+//! stay valid when several exported cases are combined into one document.
 
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{bail, Context, Result};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 use crate::cli::RecorderCommands;
+use crate::commands::path_template::{OutputLayout, PathTemplate, PathVars};
+use crate::commands::tag_expr::TagExpr;
 
 // ---------------------------------------------------------------------------
 // Data types
 // ---------------------------------------------------------------------------
 
 /// A single recorded step inside a scenario.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ScenarioStep {
     /// Zero-based index inside the scenario.
     pub index: usize,
@@ -38,6 +215,191 @@ pub struct ScenarioStep {
     /// Optional human-readable label.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub label: Option<String>,
+    /// When `true`, the step verifies an error path: it passes only if the
+    /// action fails, and an unexpected success is reported as a failure.
+    #[serde(rename = "expectFailure", default, skip_serializing_if = "Option::is_none")]
+    pub expect_failure: Option<bool>,
+    /// Soft assertion: a failure is recorded and fails the case, but later
+    /// steps still run, even with `--stop-on-fail`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soft: Option<bool>,
+    /// Stable identifier, unique within the scenario. Reports key results by
+    /// it, so per-step history survives inserting or reordering steps.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Run only if an earlier step had the given result (`<id>.passed` or
+    /// `<id>.failed`); otherwise the step is skipped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    /// Section label shared by consecutive steps ("Login", "Checkout");
+    /// reports render each section as a collapsible block.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// In a cross-platform scenario, run this step only on these platforms.
+    #[serde(rename = "onlyOn", default, skip_serializing_if = "Option::is_none")]
+    pub only_on: Option<Vec<String>>,
+    /// A person performs and judges the step (e.g. "confirm haptic feedback
+    /// fired"); replay shows it and waits for pass/fail/skip.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manual: Option<bool>,
+}
+
+impl ScenarioStep {
+    /// False when `onlyOn` excludes `platform`.
+    fn runs_on(&self, platform: &str) -> bool {
+        self.only_on.as_ref().is_none_or(|p| p.iter().any(|x| x == platform))
+    }
+
+    /// Report key: the step id, or `#<n>` (1-based position) without one.
+    fn key(&self) -> String {
+        self.id.clone().unwrap_or_else(|| format!("#{}", self.index + 1))
+    }
+}
+
+/// Outcome of a single replayed step after applying `expectFailure`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepOutcome {
+    /// Action succeeded as expected.
+    Passed,
+    /// Action failed and the step was marked `expectFailure`.
+    ExpectedFailure,
+    /// Action succeeded although the step was marked `expectFailure`.
+    UnexpectedPass,
+    /// Action failed.
+    Failed,
+}
+
+impl StepOutcome {
+    fn classify(succeeded: bool, expect_failure: bool) -> Self {
+        match (succeeded, expect_failure) {
+            (true, false) => StepOutcome::Passed,
+            (false, true) => StepOutcome::ExpectedFailure,
+            (true, true) => StepOutcome::UnexpectedPass,
+            (false, false) => StepOutcome::Failed,
+        }
+    }
+
+    fn is_pass(self) -> bool {
+        matches!(self, StepOutcome::Passed | StepOutcome::ExpectedFailure)
+    }
+}
+
+/// Impact of a scenario failing, independent of the order cases run in.
+/// Names follow the usual test-management levels (TestRail, Allure, Jira).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Blocker,
+    Critical,
+    Major,
+    Minor,
+    Trivial,
+}
+
+impl Severity {
+    const ALL: [Severity; 5] =
+        [Severity::Blocker, Severity::Critical, Severity::Major, Severity::Minor, Severity::Trivial];
+
+    fn parse(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|sev| sev.as_str() == s)
+            .with_context(|| {
+                format!("Unknown severity '{}'. Supported: blocker, critical, major, minor, trivial", s)
+            })
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Severity::Blocker => "blocker",
+            Severity::Critical => "critical",
+            Severity::Major => "major",
+            Severity::Minor => "minor",
+            Severity::Trivial => "trivial",
+        }
+    }
+}
+
+/// Device capabilities a scenario needs (checked on Android only).
+///
+/// Features are `pm list features` names; a bare name such as `nfc` or
+/// `camera.flash` means `android.hardware.<name>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceRequirements {
+    #[serde(rename = "minSdk", default, skip_serializing_if = "Option::is_none")]
+    pub min_sdk: Option<u32>,
+    /// Minimum screen size in pixels, checked in either orientation.
+    #[serde(rename = "minWidth", default, skip_serializing_if = "Option::is_none")]
+    pub min_width: Option<u32>,
+    #[serde(rename = "minHeight", default, skip_serializing_if = "Option::is_none")]
+    pub min_height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// Hardware that must be present and ready (see [`crate::android::HardwareInfo::NAMES`]),
+    /// e.g. `fingerprint` needs one enrolled and `nfc` needs NFC on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hardware: Vec<String>,
+}
+
+impl DeviceRequirements {
+    /// Human-readable list of the requirements `caps` does not meet.
+    fn unmet(&self, caps: &crate::android::DeviceCapabilities) -> Vec<String> {
+        let mut unmet = Vec::new();
+        if let Some(min) = self.min_sdk.filter(|min| caps.sdk < *min) {
+            unmet.push(format!("SDK {} < {}", caps.sdk, min));
+        }
+        let (w, h) = (self.min_width.unwrap_or(0), self.min_height.unwrap_or(0));
+        let fits = |cw: u32, ch: u32| cw >= w && ch >= h;
+        if !fits(caps.width, caps.height) && !fits(caps.height, caps.width) {
+            unmet.push(format!(
+                "screen {}x{} < {}x{}",
+                caps.width,
+                caps.height,
+                self.min_width.unwrap_or(0),
+                self.min_height.unwrap_or(0)
+            ));
+        }
+        for feature in &self.features {
+            let full = crate::android::feature_name(feature);
+            if !caps.features.contains(&full) {
+                unmet.push(format!("missing feature {}", full));
+            }
+        }
+        for name in &self.hardware {
+            match caps.hardware.get(name) {
+                Some(state) if state.usable() => {}
+                Some(state) if state.present => unmet.push(format!("{} not available", name)),
+                _ => unmet.push(format!("no {}", name)),
+            }
+        }
+        unmet
+    }
+
+    fn validate(&self) -> Result<()> {
+        let names = crate::android::HardwareInfo::NAMES;
+        if let Some(name) = self.hardware.iter().find(|h| !names.contains(&h.as_str())) {
+            bail!("Unknown hardware '{}' in requires. Supported: {}", name, names.join(", "));
+        }
+        Ok(())
+    }
+}
+
+/// One `preconditions` entry: prose for the reader, or a fixture to run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Precondition {
+    Text(String),
+    Fixture { fixture: String },
+}
+
+/// Reusable setup steps (`<scenarios>/<platform>/_fixtures/<name>.json`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub steps: Vec<ScenarioStep>,
 }
 
 /// A saved scenario file (`~/.claude-mobile/scenarios/<platform>/<name>.json`).
@@ -50,6 +412,45 @@ pub struct Scenario {
     pub description: Option<String>,
     #[serde(default)]
     pub tags: Vec<String>,
+    /// Free-form org-specific fields (jira_id, test_rail_id, risk_level, …).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, String>>,
+    /// Product feature the case covers, for `recorder coverage`.
+    #[serde(rename = "linkedFeature", default, skip_serializing_if = "Option::is_none")]
+    pub linked_feature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    /// Device the scenario needs; suites skip it on devices that fall short.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requires: Option<DeviceRequirements>,
+    /// Rough run time, for `suite --sample-budget`; falls back to suite
+    /// history, then to the step count.
+    #[serde(rename = "estimatedDurationSecs", default, skip_serializing_if = "Option::is_none")]
+    pub estimated_duration_secs: Option<u64>,
+    /// State the case starts from; fixture entries are run before it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preconditions: Vec<Precondition>,
+    /// Cases that must pass before this one runs in a suite.
+    #[serde(rename = "dependsOn", default, skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<Vec<String>>,
+    /// Host shell commands run before the case (needs `--allow-hooks`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<Vec<String>>,
+    /// Host shell commands run after the case, whatever its outcome.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<Vec<String>>,
+    /// Named assertions, each an action followed by its args; a step whose
+    /// action is `@check:<name>` runs that assertion.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checks: Option<std::collections::BTreeMap<String, Vec<String>>>,
+    /// [`content_hash`] recorded by `recorder approve`; runs refuse to start
+    /// when the scenario no longer hashes to it.
+    #[serde(rename = "approvedHash", default, skip_serializing_if = "Option::is_none")]
+    pub approved_hash: Option<String>,
+    /// Platforms a shared flow runs on; steps narrow it with `onlyOn`. The
+    /// file still lives in its `platform` directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platforms: Option<Vec<String>>,
     #[serde(default)]
     pub steps: Vec<ScenarioStep>,
     #[serde(rename = "createdAt")]
@@ -67,6 +468,12 @@ struct RecordingState {
     pub description: Option<String>,
     #[serde(default)]
     pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, String>>,
+    #[serde(rename = "linkedFeature", default, skip_serializing_if = "Option::is_none")]
+    pub linked_feature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
     #[serde(default)]
     pub steps: Vec<ScenarioStep>,
     #[serde(rename = "startedAt")]
@@ -86,6 +493,33 @@ fn scenario_path(platform: &str, name: &str) -> Result<PathBuf> {
     Ok(scenarios_dir(platform)?.join(format!("{}.json", name)))
 }
 
+/// Default root for `assert-screen` baselines, unless `--baselines` is given.
+fn baselines_root(dir: Option<String>) -> Result<PathBuf> {
+    match dir {
+        Some(d) => Ok(PathBuf::from(d)),
+        None => Ok(dirs_home()?.join(".claude-mobile").join("baselines")),
+    }
+}
+
+/// Expected accessibility tree for an `assert-tree` step, beside the
+/// step's image baseline: `<platform>.tree.json`.
+fn tree_baseline_path(baseline: &Path) -> PathBuf {
+    baseline.with_extension("tree.json")
+}
+
+/// Expected screenshot for a step: `<root>/<case_id>/<step_id>/<platform>.png`.
+///
+/// Keeping the platform in the file name lets one step carry a different
+/// baseline per platform. Path separators in the ids are replaced so an id
+/// cannot point outside its own folder.
+pub fn baseline_path(root: &Path, case_id: &str, step_id: &str, platform: &str) -> PathBuf {
+    let part = |s: &str| match s.replace(['/', '\\'], "_") {
+        p if p == ".." || p == "." || p.is_empty() => "_".to_string(),
+        p => p,
+    };
+    root.join(part(case_id)).join(part(step_id)).join(format!("{}.png", part(platform)))
+}
+
 fn recording_tmp_path(name: &str) -> PathBuf {
     PathBuf::from(format!("/tmp/claude-mobile-recording-{}.json", name))
 }
@@ -102,7 +536,7 @@ fn dirs_home() -> Result<PathBuf> {
 // Time helpers
 // ---------------------------------------------------------------------------
 
-fn now_iso8601() -> String {
+pub(crate) fn now_iso8601() -> String {
     // Simple RFC 3339 timestamp using SystemTime (no extra crates).
     let secs = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -157,7 +591,7 @@ fn epoch_to_datetime(secs: u64) -> (u64, u64, u64, u64, u64, u64) {
 }
 
 fn is_leap(y: u64) -> bool {
-    (y % 4 == 0 && y % 100 != 0) || y % 400 == 0
+    (y.is_multiple_of(4) && !y.is_multiple_of(100)) || y.is_multiple_of(400)
 }
 
 // ---------------------------------------------------------------------------
@@ -178,11 +612,223 @@ fn write_recording(state: &RecordingState) -> Result<()> {
     fs::write(&path, text).with_context(|| format!("Cannot write recording to {}", path.display()))
 }
 
+/// Largest scenario file we are willing to parse.
+const MAX_SCENARIO_BYTES: usize = 4 * 1024 * 1024;
+
+/// Per-directory defaults file; not a scenario itself.
+const DEFAULTS_FILE: &str = "_defaults.json";
+
+/// Subdirectory of a platform directory holding [`Fixture`] files.
+const FIXTURES_DIR: &str = "_fixtures";
+
+/// Fields inherited by scenarios in the same directory that omit them.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioDefaults {
+    #[serde(default, alias = "priority")]
+    severity: Option<Severity>,
+    #[serde(default)]
+    tags: Option<Vec<String>>,
+    #[serde(default)]
+    platform: Option<String>,
+    /// Named checks shared by the directory; a scenario's own check of the
+    /// same name wins.
+    #[serde(default)]
+    checks: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Read `dir/_defaults.json`; a missing file means no defaults.
+fn load_defaults(dir: &Path) -> Result<ScenarioDefaults> {
+    let path = dir.join(DEFAULTS_FILE);
+    match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).with_context(|| format!("Invalid defaults file {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ScenarioDefaults::default()),
+        Err(e) => Err(e).with_context(|| format!("Cannot read {}", path.display())),
+    }
+}
+
+/// Parse scenario JSON, rejecting oversized input before deserializing, and
+/// fill fields the scenario omits from `defaults`.
+fn parse_scenario(text: &str, defaults: &ScenarioDefaults) -> Result<Scenario> {
+    if text.len() > MAX_SCENARIO_BYTES {
+        bail!(
+            "Scenario file is {} bytes, over the {} byte limit",
+            text.len(),
+            MAX_SCENARIO_BYTES
+        );
+    }
+    let mut value: serde_json::Value = serde_json::from_str(text).context("Corrupt scenario file")?;
+    if let Some(obj) = value.as_object_mut() {
+        let inherited = [
+            ("severity", defaults.severity.map(|s| serde_json::Value::from(s.as_str()))),
+            ("tags", defaults.tags.as_ref().map(|t| serde_json::Value::from(t.clone()))),
+            ("platform", defaults.platform.as_ref().map(|p| serde_json::Value::from(p.as_str()))),
+        ];
+        for (key, default) in inherited {
+            if let Some(default) = default {
+                obj.entry(key).or_insert(default);
+            }
+        }
+        if let Some(shared) = &defaults.checks {
+            let checks = obj.entry("checks").or_insert_with(|| serde_json::Value::Object(Default::default()));
+            if let Some(checks) = checks.as_object_mut() {
+                for (name, check) in shared {
+                    checks.entry(name.clone()).or_insert_with(|| check.clone());
+                }
+            }
+        }
+    }
+    let scenario: Scenario = serde_json::from_value(value).context("Corrupt scenario file")?;
+    validate_steps(&scenario.steps)
+        .and_then(|()| validate_platforms(&scenario))
+        .and_then(|()| validate_checks(&scenario))
+        .and_then(|()| scenario.requires.as_ref().map_or(Ok(()), DeviceRequirements::validate))
+        .with_context(|| format!("Invalid scenario '{}'", scenario.name))?;
+    Ok(scenario)
+}
+
+/// Step action naming a check in the scenario's `checks`.
+const CHECK_PREFIX: &str = "@check:";
+
+/// Every check has an action that is not itself a check reference, and
+/// every `@check:` step names a defined check.
+fn validate_checks(scenario: &Scenario) -> Result<()> {
+    for (name, check) in scenario.checks.iter().flatten() {
+        match check.first() {
+            None => bail!("Check '{}' is empty; give an action and its args, e.g. [\"assert-element\", \"avatar\", \"enabled\"]", name),
+            Some(action) if action.starts_with(CHECK_PREFIX) => bail!("Check '{}' refers to another check", name),
+            Some(_) => {}
+        }
+    }
+    for (i, step) in scenario.steps.iter().enumerate() {
+        let Some(name) = step.action.strip_prefix(CHECK_PREFIX) else { continue };
+        if !scenario.checks.as_ref().is_some_and(|c| c.contains_key(name)) {
+            let known: Vec<&str> = scenario.checks.iter().flatten().map(|(k, _)| k.as_str()).collect();
+            bail!("Step {}: unknown check '{}' (defined: {})", i + 1, name, if known.is_empty() { "none".to_owned() } else { known.join(", ") });
+        }
+    }
+    Ok(())
+}
+
+/// Replace `@check:<name>` steps with the check's action and args; the
+/// check name becomes the label of a step that has none.
+fn expand_checks(steps: &mut [ScenarioStep], checks: Option<&std::collections::BTreeMap<String, Vec<String>>>) {
+    for step in steps {
+        let Some(check) = step.action.strip_prefix(CHECK_PREFIX).and_then(|name| checks?.get(name)) else { continue };
+        if step.label.is_none() {
+            step.label = Some(step.action[CHECK_PREFIX.len()..].to_owned());
+        }
+        step.action = check[0].clone();
+        step.args = check[1..].to_vec();
+    }
+}
+
+/// `platforms` and every `onlyOn` name known platforms, and no step is
+/// limited to a platform the scenario doesn't run on.
+fn validate_platforms(scenario: &Scenario) -> Result<()> {
+    let known = |p: &String, what: &str| -> Result<()> {
+        if !PLATFORMS.contains(&p.as_str()) {
+            bail!("unknown platform '{}' in {} (expected one of {})", p, what, PLATFORMS.join(", "));
+        }
+        Ok(())
+    };
+    for p in scenario.platforms.iter().flatten() {
+        known(p, "platforms")?;
+    }
+    for (i, step) in scenario.steps.iter().enumerate() {
+        for p in step.only_on.iter().flatten() {
+            known(p, &format!("step {} onlyOn", i + 1))?;
+            if scenario.platforms.as_ref().is_some_and(|all| !all.contains(p)) {
+                bail!("Step {}: onlyOn '{}' is not one of the scenario's platforms", i + 1, p);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that step ids are well-formed and unique, and that every `when`
+/// refers to the id of an earlier step.
+fn validate_steps(steps: &[ScenarioStep]) -> Result<()> {
+    let mut seen = std::collections::HashSet::new();
+    for (i, step) in steps.iter().enumerate() {
+        if let Some(cond) = &step.when {
+            let (id, _) = parse_step_condition(cond)?;
+            if !seen.contains(id) {
+                bail!("Step {}: `when` refers to '{}', which is not an earlier step id", i + 1, id);
+            }
+        }
+        if let Some(id) = &step.id {
+            if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                bail!("Step {}: invalid id '{}' (use letters, digits, '-' and '_')", i + 1, id);
+            }
+            if !seen.insert(id.as_str()) {
+                bail!("Step {}: duplicate step id '{}'", i + 1, id);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Split `<id>.passed` / `<id>.failed` into the id and the wanted outcome.
+fn parse_step_condition(cond: &str) -> Result<(&str, bool)> {
+    match cond.rsplit_once('.') {
+        Some((id, "passed")) if !id.is_empty() => Ok((id, true)),
+        Some((id, "failed")) if !id.is_empty() => Ok((id, false)),
+        _ => bail!("Invalid step condition '{}': expected '<id>.passed' or '<id>.failed'", cond),
+    }
+}
+
 fn read_scenario(platform: &str, name: &str) -> Result<Scenario> {
     let path = scenario_path(platform, name)?;
     let text = fs::read_to_string(&path)
         .with_context(|| format!("Scenario '{}' not found for platform '{}'", name, platform))?;
-    serde_json::from_str(&text).context("Corrupt scenario file")
+    let dir = scenarios_dir(platform)?;
+    let scenario = parse_scenario(&text, &load_defaults(&dir)?)?;
+    load_fixtures(&scenario, &dir).with_context(|| format!("Invalid scenario '{}'", scenario.name))?;
+    Ok(scenario)
+}
+
+/// Names of the fixtures `preconditions` asks for, in order.
+fn fixture_names(scenario: &Scenario) -> Vec<&str> {
+    scenario
+        .preconditions
+        .iter()
+        .filter_map(|p| match p {
+            Precondition::Fixture { fixture } => Some(fixture.as_str()),
+            Precondition::Text(_) => None,
+        })
+        .collect()
+}
+
+/// Read `dir/_fixtures/<name>.json`.
+fn load_fixture(dir: &Path, name: &str) -> Result<Fixture> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        bail!("invalid fixture name '{}' (use letters, digits, '-' and '_')", name);
+    }
+    let path = dir.join(FIXTURES_DIR).join(format!("{}.json", name));
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            bail!("precondition fixture '{}' not found (expected {})", name, path.display())
+        }
+        Err(e) => return Err(e).with_context(|| format!("Cannot read {}", path.display())),
+    };
+    if text.len() > MAX_SCENARIO_BYTES {
+        bail!("Fixture file {} is over the {} byte limit", path.display(), MAX_SCENARIO_BYTES);
+    }
+    let fixture: Fixture =
+        serde_json::from_str(&text).with_context(|| format!("Corrupt fixture file {}", path.display()))?;
+    validate_steps(&fixture.steps).with_context(|| format!("Invalid fixture '{}'", name))?;
+    if let Some(i) = fixture.steps.iter().position(|s| s.when.is_some()) {
+        bail!("Invalid fixture '{}': step {} has `when`; fixtures run straight through", name, i + 1);
+    }
+    Ok(fixture)
+}
+
+/// Every fixture the scenario references, loaded from `dir` (its platform
+/// directory).
+fn load_fixtures(scenario: &Scenario, dir: &Path) -> Result<Vec<Fixture>> {
+    fixture_names(scenario).into_iter().map(|name| load_fixture(dir, name)).collect()
 }
 
 fn write_scenario(scenario: &Scenario) -> Result<()> {
@@ -221,23 +867,61 @@ fn find_active_recording() -> Option<RecordingState> {
 /// Dispatch a [`RecorderCommands`] variant to its handler.
 pub fn run(command: RecorderCommands) -> Result<()> {
     match command {
-        RecorderCommands::Start { name, platform, description, tags } => {
-            cmd_start(&name, &platform, description.as_deref(), tags.as_deref())
+        RecorderCommands::Start { name, platform, description, tags, meta, severity, feature } => {
+            let severity = severity.as_deref().map(Severity::parse).transpose()?;
+            cmd_start(&name, &platform, description.as_deref(), tags.as_deref(), &meta, severity, feature)
         }
         RecorderCommands::Stop { discard } => cmd_stop(discard),
         RecorderCommands::Status => cmd_status(),
-        RecorderCommands::AddStep { action_name, args, label } => {
-            cmd_add_step(&action_name, args.as_deref(), label.as_deref())
+        RecorderCommands::AddStep { action_name, args, label, expect_failure, soft, id, when, group, manual } => {
+            cmd_add_step(
+                &action_name,
+                args.as_deref(),
+                label.as_deref(),
+                expect_failure,
+                soft,
+                id.as_deref(),
+                when.as_deref(),
+                group.as_deref(),
+                manual,
+            )
         }
         RecorderCommands::RemoveStep { step_index } => cmd_remove_step(step_index),
-        RecorderCommands::List { platform, tag } => {
-            cmd_list(platform.as_deref(), tag.as_deref())
+        RecorderCommands::List { platform, tag, tags, meta, severity, json } => {
+            let filter = ScenarioFilter::new(tag, tags.as_deref(), &meta, &severity)?;
+            cmd_list(platform.as_deref(), &filter, json)
+        }
+        RecorderCommands::Search { text, platform, tags, meta, severity, author, dir, json } => {
+            let query = SearchQuery {
+                text: text.join(" "),
+                platform,
+                filter: ScenarioFilter::new(None, tags.as_deref(), &meta, &severity)?,
+                author,
+            };
+            let dir = match dir {
+                Some(d) => PathBuf::from(d),
+                None => dirs_home()?.join(".claude-mobile").join("scenarios"),
+            };
+            cmd_search(&dir, &query, json)
         }
         RecorderCommands::Show { name, platform } => cmd_show(&name, &platform),
+        RecorderCommands::Validate { name, platform, strings, strict } => {
+            let mode = if strict { ValidationMode::Strict } else { ValidationMode::Lenient };
+            cmd_validate(&name, &platform, strings.as_deref(), mode)
+        }
         RecorderCommands::Delete { name, platform } => cmd_delete(&name, &platform),
+        RecorderCommands::Approve { name, platform } => cmd_approve(&name, &platform),
+        RecorderCommands::Format { dir, check } => {
+            let dir = match dir {
+                Some(d) => PathBuf::from(d),
+                None => dirs_home()?.join(".claude-mobile").join("scenarios"),
+            };
+            cmd_format(&dir, check)
+        }
         RecorderCommands::Play {
             name,
             platform,
+            all_platforms,
             speed,
             stop_on_fail,
             step_timeout,
@@ -245,20 +929,246 @@ pub fn run(command: RecorderCommands) -> Result<()> {
             from_step,
             to_step,
             dry_run,
-        } => cmd_play(
-            &name,
-            &platform,
-            speed,
-            stop_on_fail,
+            config,
+            config_file,
+            perf,
+            perf_interval,
+            strings,
+            locale,
+            baselines,
+            update_baselines,
+            watchdog_cpu,
+            watchdog_cpu_window,
+            watchdog_memory,
+            watchdog_package,
+            no_prompt,
+            remote_device,
+            allow_hooks,
+            dismiss_dialogs,
+            dialog_policy,
+        } => {
+            let watchdog = WatchdogOptions::from_flags(
+                watchdog_cpu,
+                watchdog_cpu_window,
+                watchdog_memory,
+                watchdog_package.or_else(|| perf.clone()),
+            );
+            let opts = PlayOptions {
+                speed,
+                stop_on_fail,
+                step_timeout,
+                max_duration,
+                from_step,
+                to_step,
+                // They set up the start state, so a run resumed mid-way skips them.
+                fixtures: from_step.unwrap_or(1) <= 1,
+                dry_run,
+                config: build_play_config(&config, config_file.as_deref())?,
+                perf_package: perf,
+                perf_interval_ms: perf_interval,
+                strings: strings
+                    .as_deref()
+                    .map(|path| load_string_table(path, locale.as_deref()))
+                    .transpose()?,
+                baselines: baselines_root(baselines)?,
+                update_baselines,
+                watchdog,
+                interactive: !no_prompt && std::io::stdin().is_terminal(),
+                remote_device,
+                allow_hooks,
+                dialogs: dialog_handler(dismiss_dialogs, dialog_policy.as_deref())?,
+                deadline: None,
+                screenshots: Some(default_screenshots(&platform)?),
+            };
+            cmd_play(&name, &platform, all_platforms, &opts)
+        }
+        RecorderCommands::RunStep {
+            name,
+            step_id,
+            platform,
+            step_timeout,
+            config,
+            config_file,
+            strings,
+            locale,
+            baselines,
+            update_baselines,
+            allow_hooks,
+            with_fixtures,
+        } => {
+            let opts = PlayOptions {
+                speed: 1.0,
+                stop_on_fail: true,
+                step_timeout,
+                max_duration: None,
+                from_step: None,
+                to_step: None,
+                fixtures: with_fixtures,
+                dry_run: false,
+                config: build_play_config(&config, config_file.as_deref())?,
+                perf_package: None,
+                perf_interval_ms: 1000,
+                strings: strings
+                    .as_deref()
+                    .map(|path| load_string_table(path, locale.as_deref()))
+                    .transpose()?,
+                baselines: baselines_root(baselines)?,
+                update_baselines,
+                watchdog: None,
+                interactive: std::io::stdin().is_terminal(),
+                remote_device: None,
+                allow_hooks,
+                dialogs: None,
+                deadline: None,
+                screenshots: Some(default_screenshots(&platform)?),
+            };
+            cmd_run_step(&name, &platform, &step_id, opts)
+        }
+        RecorderCommands::Suite {
+            platform,
+            tag,
+            tags,
+            meta,
+            severity,
+            failure_mode,
+            rerun_failed,
+            teardown,
+            report,
+            changed_since,
+            history,
+            output_root,
+            suite_name,
+            screenshots,
+            matrix,
+            matrix_limit,
+            device_timeout,
+            suite_timeout,
+            timeout_grace,
+            shard,
+            sample_budget,
+            seed,
+            diagnostics,
+            bugreport,
+            video,
+            quiet,
+            retention,
             step_timeout,
-            max_duration,
-            from_step,
-            to_step,
             dry_run,
-        ),
+            config,
+            config_file,
+            perf,
+            perf_interval,
+            strings,
+            locale,
+            baselines,
+            update_baselines,
+            watchdog_cpu,
+            watchdog_cpu_window,
+            watchdog_memory,
+            watchdog_package,
+            no_prompt,
+            remote_device,
+            allow_hooks,
+            dismiss_dialogs,
+            dialog_policy,
+            before,
+            after,
+        } => {
+            let watchdog = WatchdogOptions::from_flags(
+                watchdog_cpu,
+                watchdog_cpu_window,
+                watchdog_memory,
+                watchdog_package.or_else(|| perf.clone()),
+            );
+            let suite_opts = SuiteOptions {
+                failure_mode: FailureMode::parse(&failure_mode)?,
+                rerun_failed,
+                teardown,
+                report,
+                changed_since,
+                history,
+                output_root: output_root.map(PathBuf::from),
+                suite_name,
+                screenshots,
+                matrix: matrix_runs(&matrix, matrix_limit, strings.as_deref())?,
+                device_timeout_secs: device_timeout,
+                suite_timeout: suite_timeout.map(std::time::Duration::from_secs),
+                timeout_grace: std::time::Duration::from_secs(timeout_grace),
+                shard: shard.as_deref().map(parse_shard).transpose()?,
+                sample_budget: sample_budget.map(std::time::Duration::from_secs),
+                seed,
+                diagnostics,
+                bugreport,
+                video,
+                quiet,
+                retention: RetentionPolicy::parse(&retention)?,
+                before,
+                after,
+            };
+            let play_opts = PlayOptions {
+                speed: 1.0,
+                stop_on_fail: suite_opts.failure_mode == FailureMode::FailFast,
+                step_timeout,
+                max_duration: None,
+                from_step: None,
+                to_step: None,
+                fixtures: true,
+                dry_run,
+                config: build_play_config(&config, config_file.as_deref())?,
+                perf_package: perf,
+                perf_interval_ms: perf_interval,
+                strings: strings
+                    .as_deref()
+                    .map(|path| load_string_table(path, locale.as_deref()))
+                    .transpose()?,
+                baselines: baselines_root(baselines)?,
+                update_baselines,
+                watchdog,
+                interactive: !no_prompt && std::io::stdin().is_terminal(),
+                remote_device,
+                allow_hooks,
+                dialogs: dialog_handler(dismiss_dialogs, dialog_policy.as_deref())?,
+                deadline: None,
+                screenshots: None,
+            };
+            let filter = ScenarioFilter::new(tag, tags.as_deref(), &meta, &severity)?;
+            cmd_suite(&platform, &filter, &play_opts, &suite_opts)
+        }
         RecorderCommands::Export { name, platform, format } => {
             cmd_export(&name, &platform, &format)
         }
+        RecorderCommands::ExportCatalog { output, dir, columns } => {
+            let dir = match dir {
+                Some(d) => PathBuf::from(d),
+                None => dirs_home()?.join(".claude-mobile").join("scenarios"),
+            };
+            let columns = columns.as_deref().map(CsvColumns::load).transpose()?.unwrap_or_default();
+            let rows = export_catalog_csv(&dir, Path::new(&output), &columns)?;
+            println!("Exported {} case(s) to {}", rows, output);
+            Ok(())
+        }
+        RecorderCommands::Scaffold { name, platform, dir } => cmd_scaffold(&name, &platform, dir.as_deref()),
+        RecorderCommands::Import { file, platform, name, force } => {
+            cmd_import(&file, platform.as_deref(), name.as_deref(), force)
+        }
+        RecorderCommands::Trend { history_dir, output } => write_trend_report(&history_dir, &output),
+        RecorderCommands::Coverage { dir, platform, format, output } => {
+            let dir = match dir {
+                Some(d) => PathBuf::from(d),
+                None => dirs_home()?.join(".claude-mobile").join("scenarios"),
+            };
+            cmd_coverage(&dir, &platform, &format, output.as_deref())
+        }
+        RecorderCommands::Traceability { dir, requirements, key, output } => {
+            let dir = match dir {
+                Some(d) => PathBuf::from(d),
+                None => dirs_home()?.join(".claude-mobile").join("scenarios"),
+            };
+            cmd_traceability(&dir, requirements.as_deref(), &key, output.as_deref())
+        }
+        RecorderCommands::DiffRuns { before, after, json, output } => {
+            cmd_diff_runs(&before, &after, json, output.as_deref())
+        }
     }
 }
 
@@ -271,6 +1181,9 @@ fn cmd_start(
     platform: &str,
     description: Option<&str>,
     tags: Option<&str>,
+    meta: &[String],
+    severity: Option<Severity>,
+    linked_feature: Option<String>,
 ) -> Result<()> {
     let tmp_path = recording_tmp_path(name);
     if tmp_path.exists() {
@@ -287,11 +1200,16 @@ fn cmd_start(
         .filter(|t| !t.is_empty())
         .collect();
 
+    let metadata: HashMap<String, String> = parse_meta_pairs(meta)?.into_iter().collect();
+
     let state = RecordingState {
         name: name.to_owned(),
         platform: platform.to_owned(),
         description: description.map(str::to_owned),
         tags: tags_list,
+        metadata: if metadata.is_empty() { None } else { Some(metadata) },
+        linked_feature,
+        severity,
         steps: Vec::new(),
         started_at: now_iso8601(),
     };
@@ -329,11 +1247,24 @@ fn cmd_stop(discard: bool) -> Result<()> {
         platform: state.platform.clone(),
         description: state.description.clone(),
         tags: state.tags.clone(),
+        metadata: state.metadata.clone(),
+        severity: state.severity,
+        requires: None,
+        approved_hash: None,
+        estimated_duration_secs: None,
+        linked_feature: state.linked_feature.clone(),
+        platforms: None,
+        preconditions: Vec::new(),
+        depends_on: None,
+        before: None,
+        after: None,
+        checks: None,
         steps: state.steps.clone(),
         created_at: state.started_at.clone(),
         updated_at: now_iso8601(),
     };
 
+    print_coherence_warnings(&validate_coherence(&scenario, &[]));
     write_scenario(&scenario)?;
     fs::remove_file(&tmp_path).ok();
 
@@ -381,10 +1312,17 @@ fn cmd_status() -> Result<()> {
 // recorder add-step
 // ---------------------------------------------------------------------------
 
+#[allow(clippy::too_many_arguments)]
 fn cmd_add_step(
     action_name: &str,
     args_json: Option<&str>,
     label: Option<&str>,
+    expect_failure: bool,
+    soft: bool,
+    id: Option<&str>,
+    when: Option<&str>,
+    group: Option<&str>,
+    manual: bool,
 ) -> Result<()> {
     let mut state = find_active_recording()
         .ok_or_else(|| anyhow::anyhow!("No active recording. Start one with `recorder start`."))?;
@@ -404,14 +1342,25 @@ fn cmd_add_step(
         timestamp_ms: now_ms(),
         delay_before_ms: 0,
         label: label.map(str::to_owned),
+        expect_failure: expect_failure.then_some(true),
+        soft: soft.then_some(true),
+        id: id.map(str::to_owned),
+        when: when.map(str::to_owned),
+        group: group.map(str::to_owned),
+        only_on: None,
+        manual: manual.then_some(true),
     });
+    validate_steps(&state.steps)?;
 
     write_recording(&state)?;
     println!(
-        "Step {} added: {} {:?}",
+        "Step {} added: {} {:?}{}{}{}",
         index + 1,
         action_name,
-        args
+        args,
+        if expect_failure { " (expect failure)" } else { "" },
+        if soft { " (soft)" } else { "" },
+        if manual { " (manual)" } else { "" }
     );
     Ok(())
 }
@@ -433,6 +1382,9 @@ fn cmd_remove_step(step_index: usize) -> Result<()> {
     }
 
     let removed = state.steps.remove(step_index - 1);
+    validate_steps(&state.steps).with_context(|| {
+        format!("Cannot remove step {}: a later step depends on it", step_index)
+    })?;
 
     // Re-index remaining steps.
     for (i, step) in state.steps.iter_mut().enumerate() {
@@ -451,17 +1403,99 @@ fn cmd_remove_step(step_index: usize) -> Result<()> {
 // recorder list
 // ---------------------------------------------------------------------------
 
-fn cmd_list(platform: Option<&str>, tag: Option<&str>) -> Result<()> {
+fn cmd_list(platform: Option<&str>, filter: &ScenarioFilter, json: bool) -> Result<()> {
+    let found = collect_scenarios(platform, filter)?;
+
+    if json {
+        let entries: Vec<serde_json::Value> = found
+            .iter()
+            .map(|(plat, scenario)| {
+                serde_json::json!({
+                    "platform": plat,
+                    "name": scenario.name,
+                    "steps": scenario.steps.len(),
+                    "tags": scenario.tags,
+                    "metadata": scenario.metadata,
+                    "severity": scenario.severity,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    if found.is_empty() {
+        println!("No scenarios found.");
+    }
+    for (plat, scenario) in &found {
+        let tags_str = if scenario.tags.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", scenario.tags.join(", "))
+        };
+        let severity_str = scenario
+            .severity
+            .map(|s| format!(" ({})", s.as_str()))
+            .unwrap_or_default();
+        println!(
+            "{}/{} — {} steps{}{}",
+            plat,
+            scenario.name,
+            scenario.steps.len(),
+            severity_str,
+            tags_str
+        );
+    }
+    Ok(())
+}
+
+/// Selection criteria shared by `recorder list` and `recorder suite`.
+#[derive(Default)]
+struct ScenarioFilter {
+    tag: Option<String>,
+    expr: Option<TagExpr>,
+    meta: Vec<(String, String)>,
+    /// Match any of these severities; empty matches all.
+    severities: Vec<Severity>,
+}
+
+impl ScenarioFilter {
+    fn new(tag: Option<String>, tags: Option<&str>, meta: &[String], severities: &[String]) -> Result<Self> {
+        Ok(ScenarioFilter {
+            tag,
+            expr: tags.map(TagExpr::parse).transpose()?,
+            meta: parse_meta_pairs(meta)?,
+            severities: severities.iter().map(|s| Severity::parse(s)).collect::<Result<_>>()?,
+        })
+    }
+
+    fn matches(&self, platform: &str, scenario: &Scenario) -> bool {
+        if self.tag.as_ref().is_some_and(|t| !scenario.tags.contains(t)) {
+            return false;
+        }
+        if self.expr.as_ref().is_some_and(|e| !e.matches(&scenario.tags, platform)) {
+            return false;
+        }
+        if !self.severities.is_empty()
+            && !scenario.severity.is_some_and(|s| self.severities.contains(&s))
+        {
+            return false;
+        }
+        matches_meta(scenario, &self.meta)
+    }
+}
+
+/// Load saved scenarios as `(platform, scenario)` pairs, filtered by platform
+/// and `filter`. Unreadable or malformed files are skipped.
+fn collect_scenarios(platform: Option<&str>, filter: &ScenarioFilter) -> Result<Vec<(String, Scenario)>> {
     let home = dirs_home()?;
     let base = home.join(".claude-mobile").join("scenarios");
 
+    let mut found: Vec<(String, Scenario)> = Vec::new();
     if !base.exists() {
-        println!("No scenarios found.");
-        return Ok(());
+        return Ok(found);
     }
 
-    let mut found = false;
-
     let platforms: Vec<String> = if let Some(p) = platform {
         vec![p.to_owned()]
     } else {
@@ -478,608 +1512,6906 @@ fn cmd_list(platform: Option<&str>, tag: Option<&str>) -> Result<()> {
         if !dir.exists() {
             continue;
         }
+        let defaults = load_defaults(&dir)?;
         for entry in fs::read_dir(&dir).context("Cannot read platform directory")?.flatten() {
             let file_name = entry.file_name();
             let file_str = file_name.to_string_lossy();
-            if !file_str.ends_with(".json") {
+            if !file_str.ends_with(".json") || file_str == DEFAULTS_FILE {
                 continue;
             }
             if let Ok(text) = fs::read_to_string(entry.path()) {
-                if let Ok(scenario) = serde_json::from_str::<Scenario>(&text) {
-                    // Filter by tag if provided.
-                    if let Some(filter_tag) = tag {
-                        if !scenario.tags.iter().any(|t| t == filter_tag) {
-                            continue;
-                        }
+                if let Ok(scenario) = parse_scenario(&text, &defaults) {
+                    if filter.matches(plat, &scenario) {
+                        found.push((plat.clone(), scenario));
                     }
-                    let tags_str = if scenario.tags.is_empty() {
-                        String::new()
-                    } else {
-                        format!(" [{}]", scenario.tags.join(", "))
-                    };
-                    println!(
-                        "{}/{} — {} steps{}",
-                        plat,
-                        scenario.name,
-                        scenario.steps.len(),
-                        tags_str
-                    );
-                    found = true;
                 }
             }
         }
     }
-
-    if !found {
-        println!("No scenarios found.");
-    }
-    Ok(())
+    found.sort_by(|a, b| (&a.0, &a.1.name).cmp(&(&b.0, &b.1.name)));
+    Ok(found)
 }
 
 // ---------------------------------------------------------------------------
-// recorder show
+// recorder search
 // ---------------------------------------------------------------------------
 
-fn cmd_show(name: &str, platform: &str) -> Result<()> {
-    let scenario = read_scenario(platform, name)?;
-    let json = serde_json::to_string_pretty(&scenario)?;
-    println!("{}", json);
-    Ok(())
+/// Free text plus structured filters for [`search`].
+#[derive(Default)]
+struct SearchQuery {
+    /// Matched word by word against name, description, tags and metadata.
+    text: String,
+    platform: Option<String>,
+    filter: ScenarioFilter,
+    /// Substring of the `author` metadata field.
+    author: Option<String>,
 }
 
-// ---------------------------------------------------------------------------
-// recorder delete
-// ---------------------------------------------------------------------------
+/// One search hit, best matches first.
+#[derive(Debug, Serialize)]
+struct ScenarioSummary {
+    platform: String,
+    name: String,
+    path: PathBuf,
+    score: u32,
+    steps: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    severity: Option<Severity>,
+}
 
-fn cmd_delete(name: &str, platform: &str) -> Result<()> {
-    let path = scenario_path(platform, name)?;
-    if !path.exists() {
-        bail!("Scenario '{}' not found for platform '{}'", name, platform);
-    }
-    fs::remove_file(&path)
-        .with_context(|| format!("Cannot delete {}", path.display()))?;
-    println!("Deleted scenario '{}/{}'.", platform, name);
-    Ok(())
+/// Lowercase alphanumeric words.
+fn search_tokens(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
 }
 
-// ---------------------------------------------------------------------------
-// recorder play
-// ---------------------------------------------------------------------------
+/// How strongly `word` matches the scenario; 0 means nowhere. Whole words
+/// beat substrings, and the name outranks tags, description and metadata.
+fn search_score(scenario: &Scenario, word: &str) -> u32 {
+    let field = |text: &str, whole: u32, part: u32| {
+        let tokens = search_tokens(text);
+        if tokens.iter().any(|t| t == word) {
+            whole
+        } else if tokens.iter().any(|t| t.contains(word)) {
+            part
+        } else {
+            0
+        }
+    };
+    let mut score = field(&scenario.name, 10, 5);
+    score += scenario.tags.iter().map(|t| field(t, 4, 2)).max().unwrap_or(0);
+    score += scenario.description.as_deref().map_or(0, |d| field(d, 3, 1));
+    score += scenario.metadata.iter().flatten().map(|(_, v)| field(v, 2, 1)).max().unwrap_or(0);
+    score
+}
 
-#[allow(clippy::too_many_arguments)]
-fn cmd_play(
-    name: &str,
-    platform: &str,
-    speed: f64,
-    stop_on_fail: bool,
-    step_timeout: Option<u64>,
-    max_duration: Option<u64>,
-    from_step: Option<usize>,
-    to_step: Option<usize>,
-    dry_run: bool,
-) -> Result<()> {
-    let scenario = read_scenario(platform, name)?;
+fn scenario_author(scenario: &Scenario) -> Option<&str> {
+    let meta = scenario.metadata.as_ref()?;
+    meta.iter().find(|(k, _)| k.eq_ignore_ascii_case("author")).map(|(_, v)| v.as_str())
+}
 
-    let from = from_step.unwrap_or(1).saturating_sub(1);
-    let to = to_step.unwrap_or(scenario.steps.len()).min(scenario.steps.len());
+/// Score a scenario against `query`; `None` when it doesn't match.
+fn match_scenario(scenario: &Scenario, query: &SearchQuery) -> Option<u32> {
+    if query.platform.as_ref().is_some_and(|p| *p != scenario.platform) {
+        return None;
+    }
+    if !query.filter.matches(&scenario.platform, scenario) {
+        return None;
+    }
+    if let Some(author) = &query.author {
+        let got = scenario_author(scenario)?;
+        if !got.to_lowercase().contains(&author.to_lowercase()) {
+            return None;
+        }
+    }
+    search_tokens(&query.text)
+        .iter()
+        .map(|word| Some(search_score(scenario, word)).filter(|s| *s > 0))
+        .sum()
+}
 
-    if from >= to {
-        bail!("--from-step ({}) must be less than --to-step ({})", from + 1, to);
+/// Every scenario file under `dir`, recursively, parsed with its
+/// directory's defaults. Files that fail to parse come back as `Err`.
+fn walk_scenarios(dir: &Path) -> Vec<(PathBuf, Result<Scenario>)> {
+    let mut found = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else { continue };
+        let defaults = load_defaults(&dir).unwrap_or_default();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                if entry.file_name() != FIXTURES_DIR {
+                    pending.push(path);
+                }
+                continue;
+            }
+            if path.extension().is_none_or(|e| e != "json") || entry.file_name() == DEFAULTS_FILE {
+                continue;
+            }
+            let scenario = fs::read_to_string(&path)
+                .with_context(|| format!("Cannot read {}", path.display()))
+                .and_then(|text| parse_scenario(&text, &defaults));
+            found.push((path, scenario));
+        }
     }
+    found
+}
 
-    let steps_to_run: Vec<&ScenarioStep> = scenario.steps[from..to].iter().collect();
-    let max_dur_ms = max_duration.unwrap_or(u64::MAX);
-    let start = std::time::Instant::now();
+/// Scan `dir` recursively for scenario files and return the ones matching
+/// `query`, ranked by match strength. Each directory's `_defaults.json`
+/// applies to the files in it; unreadable or malformed files are skipped.
+fn search(dir: &Path, query: &SearchQuery) -> Result<Vec<ScenarioSummary>> {
+    let mut hits = Vec::new();
+    for (path, scenario) in walk_scenarios(dir) {
+        let Ok(scenario) = scenario else { continue };
+        if let Some(score) = match_scenario(&scenario, query) {
+            hits.push(ScenarioSummary {
+                platform: scenario.platform,
+                name: scenario.name,
+                path,
+                score,
+                steps: scenario.steps.len(),
+                description: scenario.description,
+                tags: scenario.tags,
+                severity: scenario.severity,
+            });
+        }
+    }
+    hits.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| (&a.platform, &a.name).cmp(&(&b.platform, &b.name))));
+    Ok(hits)
+}
 
-    println!(
-        "Playing scenario '{}' on '{}' ({} steps, speed={}, dry_run={})…",
-        name,
-        platform,
-        steps_to_run.len(),
-        speed,
-        dry_run
-    );
+fn cmd_search(dir: &Path, query: &SearchQuery, json: bool) -> Result<()> {
+    let hits = search(dir, query)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&hits)?);
+        return Ok(());
+    }
+    if hits.is_empty() {
+        println!("No scenarios found.");
+    }
+    for hit in &hits {
+        let desc = hit.description.as_deref().map(|d| format!(" — {}", d)).unwrap_or_default();
+        println!("{}/{} ({} steps, score {}){}", hit.platform, hit.name, hit.steps, hit.score, desc);
+    }
+    Ok(())
+}
 
-    let mut passed = 0usize;
-    let mut failed = 0usize;
+/// Parse repeatable `--meta KEY=VALUE` flags.
+fn parse_meta_pairs(pairs: &[String]) -> Result<Vec<(String, String)>> {
+    pairs
+        .iter()
+        .map(|pair| {
+            let (k, v) = pair
+                .split_once('=')
+                .with_context(|| format!("--meta expects KEY=VALUE, got '{}'", pair))?;
+            let k = k.trim();
+            if k.is_empty() {
+                bail!("--meta key must not be empty in '{}'", pair);
+            }
+            Ok((k.to_owned(), v.to_owned()))
+        })
+        .collect()
+}
 
-    for (i, step) in steps_to_run.iter().enumerate() {
-        if start.elapsed().as_millis() as u64 >= max_dur_ms {
-            println!("Max duration reached, stopping.");
-            break;
+/// True when every `key=value` filter is present in the scenario metadata.
+fn matches_meta(scenario: &Scenario, filter: &[(String, String)]) -> bool {
+    filter.iter().all(|(k, v)| {
+        scenario
+            .metadata
+            .as_ref()
+            .and_then(|m| m.get(k))
+            .is_some_and(|actual| actual == v)
+    })
+}
+
+/// Metadata entries sorted by key, for stable report output.
+fn sorted_metadata(scenario: &Scenario) -> Vec<(&String, &String)> {
+    let mut entries: Vec<_> = scenario.metadata.iter().flatten().collect();
+    entries.sort();
+    entries
+}
+
+// ---------------------------------------------------------------------------
+// recorder show
+// ---------------------------------------------------------------------------
+
+fn cmd_show(name: &str, platform: &str) -> Result<()> {
+    let scenario = read_scenario(platform, name)?;
+    let json = serde_json::to_string_pretty(&scenario)?;
+    println!("{}", json);
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// recorder validate
+// ---------------------------------------------------------------------------
+
+/// An authoring problem that is legal JSON but likely to fail at run time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CoherenceWarning {
+    /// Zero-based step index, as in [`ScenarioStep::index`].
+    index: usize,
+    message: String,
+}
+
+fn key_is(step: &ScenarioStep, names: &[&str]) -> bool {
+    step.action == "key"
+        && step.args.first().is_some_and(|k| names.iter().any(|n| k.eq_ignore_ascii_case(n)))
+}
+
+/// Advisory checks on step order: pasting with nothing copied, typing
+/// without first focusing a field, driving the UI of a stopped app, and
+/// repeating a precondition fixture (`fixtures`, as loaded for the
+/// scenario) as its first steps. Warnings never block saving; edge cases
+/// (text copied outside the scenario, auto-focused fields) are legitimate.
+fn validate_coherence(scenario: &Scenario, fixtures: &[Fixture]) -> Vec<CoherenceWarning> {
+    const COPY_KEYS: &[&str] = &["copy", "cut", "keycode_copy", "keycode_cut", "278", "277"];
+    const PASTE_KEYS: &[&str] = &["paste", "keycode_paste", "279"];
+    // Actions after which the previously focused field is gone.
+    const SCREEN_CHANGES: &[&str] = &["launch", "stop", "home", "back", "open-url", "intent-start", "intent-deeplink"];
+    const UI_ACTIONS: &[&str] = &["tap", "tap-text", "input", "swipe", "key"];
+
+    let mut warnings = Vec::new();
+    let mut start = 0;
+    for fixture in fixtures {
+        let len = fixture.steps.len();
+        if len > 0 && repeats_steps(&scenario.steps[start..], &fixture.steps) {
+            warnings.push(CoherenceWarning {
+                index: start,
+                message: format!(
+                    "steps {}-{} repeat precondition fixture '{}', which already runs before the scenario",
+                    start + 1,
+                    start + len,
+                    fixture.name
+                ),
+            });
+            start += len;
         }
+    }
 
-        // Apply inter-step delay scaled by speed.
-        if step.delay_before_ms > 0 && !dry_run {
-            let delay = (step.delay_before_ms as f64 / speed) as u64;
-            std::thread::sleep(std::time::Duration::from_millis(delay));
+    let mut copied = false;
+    let mut field_focused = false;
+    let mut stopped_app: Option<&str> = None;
+    for (i, step) in scenario.steps.iter().enumerate() {
+        let mut warn = |message: String| warnings.push(CoherenceWarning { index: i, message });
+        if key_is(step, COPY_KEYS) {
+            copied = true;
+        } else if key_is(step, PASTE_KEYS) && !copied {
+            warn("pastes, but no earlier step copies or cuts anything".to_owned());
         }
 
-        let step_label = step.label.as_deref().unwrap_or(&step.action);
-        print!("  Step {}/{}: {} {:?} … ", i + 1, steps_to_run.len(), step_label, step.args);
+        if step.action == "input" && !field_focused {
+            warn("types text, but no earlier step taps a field on this screen".to_owned());
+        }
 
-        if dry_run {
-            println!("[dry-run]");
-            passed += 1;
-            continue;
+        if let Some(pkg) = stopped_app {
+            if UI_ACTIONS.contains(&step.action.as_str()) {
+                warn(format!("interacts with the UI after '{}' was stopped and not relaunched", pkg));
+                stopped_app = None;
+            }
         }
 
-        // Build a FlowStep and delegate to flow::execute_step.
-        let flow_step = crate::commands::flow::FlowStep {
-            action: step.action.clone(),
-            args: step.args.clone(),
-            on_error: crate::commands::flow::OnError::Stop,
-        };
+        match step.action.as_str() {
+            "tap" | "tap-text" | "input" => field_focused = true,
+            a if SCREEN_CHANGES.contains(&a) => field_focused = false,
+            _ => {}
+        }
+        match step.action.as_str() {
+            "stop" => stopped_app = step.args.first().map(String::as_str),
+            "launch" => stopped_app = None,
+            _ => {}
+        }
+    }
+    for (index, group) in split_groups(&scenario.steps) {
+        warnings.push(CoherenceWarning {
+            index,
+            message: format!("group '{}' continues here after other steps; it will be shown in more than one section", group),
+        });
+    }
+    warnings
+}
 
-        // Apply optional per-step timeout.
-        let ctx = FlowCtx {
-            platform: platform.to_owned(),
-            device: None,
-            simulator: None,
-            companion_path: None,
-        };
+/// Whether `steps` begins with the same actions and arguments as `original`.
+fn repeats_steps(steps: &[ScenarioStep], original: &[ScenarioStep]) -> bool {
+    steps.len() >= original.len()
+        && steps.iter().zip(original).all(|(step, orig)| step.action == orig.action && step.args == orig.args)
+}
+
+/// Consecutive runs of steps with the same `group` (`None` = ungrouped).
+fn group_runs(steps: &[ScenarioStep]) -> Vec<(Option<&str>, Vec<&ScenarioStep>)> {
+    let mut runs: Vec<(Option<&str>, Vec<&ScenarioStep>)> = Vec::new();
+    for step in steps {
+        match runs.last_mut() {
+            Some((group, members)) if *group == step.group.as_deref() => members.push(step),
+            _ => runs.push((step.group.as_deref(), vec![step])),
+        }
+    }
+    runs
+}
 
-        let result = if let Some(timeout_ms) = step_timeout {
-            run_with_timeout(&ctx, &flow_step, timeout_ms)
+/// Groups that resume after other steps: (index of the resuming step, group).
+/// Groups need not be contiguous, but readers should know when they aren't.
+fn split_groups(steps: &[ScenarioStep]) -> Vec<(usize, &str)> {
+    let mut seen: Vec<&str> = Vec::new();
+    let mut split = Vec::new();
+    for (group, members) in group_runs(steps) {
+        let Some(group) = group else { continue };
+        if seen.contains(&group) {
+            split.push((members[0].index, group));
         } else {
-            run_step(&ctx, &flow_step)
-        };
+            seen.push(group);
+        }
+    }
+    split
+}
 
-        match result {
-            Ok(msg) => {
-                println!("OK  {}", msg);
-                passed += 1;
-            }
-            Err(e) => {
-                println!("FAIL  {}", e);
-                failed += 1;
-                if stop_on_fail {
-                    println!("Stopping on failure (--stop-on-fail).");
-                    break;
-                }
+fn print_coherence_warnings(warnings: &[CoherenceWarning]) {
+    for w in warnings {
+        eprintln!("Warning: step {}: {}", w.index + 1, w.message);
+    }
+}
+
+/// How much `recorder validate` insists on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValidationMode {
+    /// Only what a scenario needs to load and play; saving always uses this.
+    Lenient,
+    /// Also fail on authoring lints (see [`strict_lints`]), for CI.
+    Strict,
+}
+
+const PLATFORMS: &[&str] = &["android", "ios", "desktop", "aurora"];
+
+/// `2026-01-31T09:30:00Z`, as [`now_iso8601`] writes it.
+fn is_iso8601_utc(ts: &str) -> bool {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"^\d{4}-(0[1-9]|1[0-2])-(0[1-9]|[12]\d|3[01])T([01]\d|2[0-3]):[0-5]\d:[0-5]\d(\.\d+)?Z$").unwrap());
+    re.is_match(ts)
+}
+
+/// Why an assertion step's args would fail at play time, if they would.
+fn expectation_problem(step: &ScenarioStep) -> Option<String> {
+    let args = &step.args;
+    match step.action.as_str() {
+        "assert-request" if args.len() < 2 => Some("assert-request needs METHOD URL-SUBSTRING [STATUS]".into()),
+        "assert-request" => args
+            .get(2)
+            .filter(|s| s.parse::<u16>().is_err())
+            .map(|s| format!("assert-request status '{}' is not a number", s)),
+        "assert-notification" | "assert-alarm" | "assert-announced" | "assert-toast" | "assert-text" if args.is_empty() => {
+            Some(format!("{} needs at least 1 arg", step.action))
+        }
+        "assert-element" if args.len() < 2 => Some("assert-element needs QUERY CHECK [VALUE]".into()),
+        "assert-element" => crate::android::ElementAssertion::parse(&args[1], args.get(2).map(String::as_str))
+            .err()
+            .map(|e| e.to_string()),
+        "assert-toast" => args
+            .get(1)
+            .filter(|s| s.parse::<u64>().is_err())
+            .map(|s| format!("assert-toast timeout '{}' is not a number of ms", s)),
+        "assert-text" => args
+            .get(1)
+            .filter(|s| s.parse::<u64>().is_err())
+            .map(|s| format!("assert-text timeout '{}' is not a number of ms", s)),
+        "assert-tree" if step.id.is_none() => Some("assert-tree needs a step id to locate its baseline".into()),
+        "assert-tree" => args
+            .first()
+            .filter(|s| s.as_str() != "bounds")
+            .map(|s| format!("assert-tree takes only 'bounds', not '{}'", s)),
+        "assert-screen" if step.id.is_none() => Some("assert-screen needs a step id to locate its baseline".into()),
+        "assert-screen" => args
+            .first()
+            .filter(|s| s.parse::<f64>().is_err())
+            .map(|s| format!("assert-screen max diff '{}' is not a number", s)),
+        _ => None,
+    }
+}
+
+/// Edit distance counting an adjacent swap as one edit, so "tpa" is one
+/// away from "tap".
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
             }
         }
     }
+    d[a.len()][b.len()]
+}
 
-    println!(
-        "\nDone: {} passed, {} failed ({}ms total).",
-        passed,
-        failed,
-        start.elapsed().as_millis()
-    );
+/// The playable action closest to a mistyped one, if any is close enough
+/// to be a typo rather than a different word.
+fn suggest_action(action: &str) -> Option<&'static str> {
+    let action = action.trim().to_ascii_lowercase();
+    let max = 1 + action.chars().count() / 4;
+    PLAY_ACTIONS
+        .iter()
+        .map(|&known| (edit_distance(&action, known), known))
+        .filter(|&(d, _)| d <= max)
+        .min_by_key(|&(d, _)| d)
+        .map(|(_, known)| known)
+}
 
-    if failed > 0 {
-        bail!("Scenario '{}' finished with {} failure(s)", name, failed);
+/// Team-standard checks that `--strict` turns into errors: tags present and
+/// lowercase-kebab (the tag taxonomy), a known platform matching the
+/// directory, RFC 3339 timestamps, and steps that play can parse.
+fn strict_lints(scenario: &Scenario, platform: &str) -> Vec<String> {
+    let mut lints = Vec::new();
+    if scenario.tags.is_empty() {
+        lints.push("no tags".to_owned());
+    }
+    for tag in &scenario.tags {
+        let kebab = !tag.is_empty()
+            && !tag.starts_with('-')
+            && tag.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if !kebab {
+            lints.push(format!("tag '{}' is not lowercase-kebab (e.g. 'smoke', 'checkout-flow')", tag));
+        }
+    }
+    if !PLATFORMS.contains(&scenario.platform.as_str()) {
+        lints.push(format!("unknown platform '{}' (expected one of {})", scenario.platform, PLATFORMS.join(", ")));
+    } else if scenario.platform != platform {
+        lints.push(format!("platform '{}' does not match its directory '{}'", scenario.platform, platform));
+    }
+    for (field, ts) in [("createdAt", &scenario.created_at), ("updatedAt", &scenario.updated_at)] {
+        if !is_iso8601_utc(ts) {
+            lints.push(format!("{} '{}' is not an RFC 3339 UTC timestamp", field, ts));
+        }
     }
+    // Manual steps are instructions for a person, not replayable actions.
+    let mut steps = scenario.steps.clone();
+    expand_checks(&mut steps, scenario.checks.as_ref());
+    for (i, step) in steps.iter().enumerate().filter(|(_, s)| !s.manual.unwrap_or(false)) {
+        if !PLAY_ACTIONS.contains(&step.action.as_str()) {
+            let hint = suggest_action(&step.action).map(|a| format!(" (did you mean '{}'?)", a)).unwrap_or_default();
+            lints.push(format!("step {}: '{}' cannot be replayed{}", i + 1, step.action, hint));
+        } else if let Err(e) = crate::platform::Action::parse(&step.action, &step.args) {
+            lints.push(format!("step {}: {}", i + 1, e));
+        } else if let Some(problem) = expectation_problem(step) {
+            lints.push(format!("step {}: {}", i + 1, problem));
+        }
+    }
+    lints
+}
+
+fn cmd_validate(name: &str, platform: &str, strings: Option<&str>, mode: ValidationMode) -> Result<()> {
+    // Structural problems (ids, `when`, severity) already fail the load.
+    let scenario = read_scenario(platform, name)?;
+    if let Some(path) = strings {
+        for (locale, table) in load_catalog(path)? {
+            let table = StringTable { locale, strings: table };
+            check_string_keys(&scenario.steps, &table)?;
+        }
+    }
+    let fixtures = load_fixtures(&scenario, &scenarios_dir(platform)?)?;
+    let warnings = validate_coherence(&scenario, &fixtures);
+    print_coherence_warnings(&warnings);
+    if mode == ValidationMode::Strict {
+        let lints = strict_lints(&scenario, platform);
+        for lint in &lints {
+            eprintln!("Error: {}", lint);
+        }
+        if !lints.is_empty() {
+            bail!("Scenario '{}' fails strict validation with {} error(s)", name, lints.len());
+        }
+    }
+    println!("Scenario '{}': {} warning(s)", name, warnings.len());
     Ok(())
 }
 
-/// Minimal context type for replay — mirrors `flow::PlatformCtx` but owned.
-struct FlowCtx {
-    platform: String,
-    device: Option<String>,
-    simulator: Option<String>,
-    companion_path: Option<String>,
+// ---------------------------------------------------------------------------
+// recorder delete
+// ---------------------------------------------------------------------------
+
+fn cmd_delete(name: &str, platform: &str) -> Result<()> {
+    let path = scenario_path(platform, name)?;
+    if !path.exists() {
+        bail!("Scenario '{}' not found for platform '{}'", name, platform);
+    }
+    fs::remove_file(&path)
+        .with_context(|| format!("Cannot delete {}", path.display()))?;
+    println!("Deleted scenario '{}/{}'.", platform, name);
+    Ok(())
 }
 
-/// Execute a single FlowStep using flow's public `execute_step`.
-fn run_step(ctx: &FlowCtx, step: &crate::commands::flow::FlowStep) -> Result<String> {
-    use crate::{android, aurora, desktop, ios};
+// ---------------------------------------------------------------------------
+// recorder format
+// ---------------------------------------------------------------------------
 
-    let platform = ctx.platform.as_str();
-    let device = ctx.device.as_deref();
-    let simulator = ctx.simulator.as_deref();
-    let companion_path = ctx.companion_path.as_deref();
+/// Field order of a [`Scenario`] as serde writes it, which is the order
+/// `recorder format` puts keys in.
+const SCENARIO_KEY_ORDER: &[&str] = &[
+    "version", "name", "platform", "description", "tags", "metadata", "linkedFeature", "severity", "requires",
+    "estimatedDurationSecs", "preconditions", "dependsOn", "before", "after", "checks",
+    "approvedHash", "platforms", "steps", "createdAt", "updatedAt",
+];
 
-    // Delegate to the same helpers used by flow.rs.
-    match step.action.as_str() {
-        "tap" => {
-            if step.args.len() < 2 {
-                bail!("tap requires 2 args");
-            }
-            let x: i32 = step.args[0].parse()?;
-            let y: i32 = step.args[1].parse()?;
-            match platform {
-                "android" => android::tap(x, y, device)?,
-                "ios" => ios::tap(x, y, simulator)?,
-                "aurora" => aurora::tap(x, y, device)?,
-                "desktop" => desktop::tap(x, y, companion_path)?,
-                _ => bail!("Unsupported platform"),
+/// Field order of a [`ScenarioStep`].
+const STEP_KEY_ORDER: &[&str] = &[
+    "index", "type", "action", "args", "timestampMs", "delayBeforeMs", "label", "expectFailure", "soft", "id",
+    "when", "group", "onlyOn", "manual",
+];
+
+/// Field order of [`DeviceRequirements`].
+const REQUIRES_KEY_ORDER: &[&str] = &["minSdk", "minWidth", "minHeight", "features", "hardware"];
+
+/// Re-serialize a scenario file in canonical form: keys in the order the
+/// recorder writes them (unknown keys after, sorted; metadata sorted) and
+/// serde_json's pretty layout.
+///
+/// Works on the file as written rather than the parsed [`Scenario`], so an
+/// absent field stays absent and an explicitly empty one stays empty, and
+/// nothing inherited from `_defaults.json` is baked in. A file the recorder
+/// saved is already canonical apart from the order of `metadata` keys,
+/// which the recorder does not sort.
+pub fn normalized_json(text: &str) -> Result<String> {
+    let value: serde_json::Value = serde_json::from_str(text).context("Corrupt scenario file")?;
+    let mut out = String::with_capacity(text.len());
+    write_canonical(&mut out, &value, SCENARIO_KEY_ORDER, 0);
+    Ok(out)
+}
+
+fn write_canonical(out: &mut String, value: &serde_json::Value, order: &[&str], indent: usize) {
+    use serde_json::Value;
+
+    let pad = "  ".repeat(indent + 1);
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort_by_key(|k| (order.iter().position(|o| o == k).unwrap_or(order.len()), k.as_str()));
+            out.push_str("{\n");
+            for (i, key) in keys.iter().enumerate() {
+                let nested = match key.as_str() {
+                    "steps" => STEP_KEY_ORDER,
+                    "requires" => REQUIRES_KEY_ORDER,
+                    _ => &[],
+                };
+                out.push_str(&format!("{}{}: ", pad, Value::from(key.as_str())));
+                write_canonical(out, &map[key.as_str()], nested, indent + 1);
+                out.push_str(if i + 1 < keys.len() { ",\n" } else { "\n" });
             }
-            Ok(format!("Tapped ({}, {})", x, y))
+            out.push_str(&"  ".repeat(indent));
+            out.push('}');
         }
-        "tap-text" => {
-            if step.args.is_empty() {
-                bail!("tap-text requires 1 arg");
+        Value::Array(items) if !items.is_empty() => {
+            out.push_str("[\n");
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(&pad);
+                write_canonical(out, item, order, indent + 1);
+                out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
             }
-            let query = &step.args[0];
-            match platform {
-                "android" => android::tap_element(query, device)?,
-                "ios" => ios::tap_element(query, simulator)?,
-                "desktop" => desktop::tap_by_text(query, companion_path)?,
-                _ => bail!("Unsupported platform"),
-            }
-            Ok(format!("Tapped \"{}\"", query))
+            out.push_str(&"  ".repeat(indent));
+            out.push(']');
         }
-        "input" => {
-            if step.args.is_empty() {
-                bail!("input requires 1 arg");
-            }
-            let text = &step.args[0];
-            match platform {
-                "android" => android::input_text(text, device)?,
-                "ios" => ios::input_text(text, simulator)?,
-                "aurora" => aurora::input_text(text, device)?,
-                "desktop" => desktop::input_text(text, companion_path)?,
-                _ => bail!("Unsupported platform"),
-            }
-            Ok(format!("Typed \"{}\"", text))
-        }
-        "swipe" => {
-            if step.args.len() < 4 {
-                bail!("swipe requires 4 args");
-            }
-            let x1: i32 = step.args[0].parse()?;
-            let y1: i32 = step.args[1].parse()?;
-            let x2: i32 = step.args[2].parse()?;
-            let y2: i32 = step.args[3].parse()?;
-            let dur: u32 = step.args.get(4).and_then(|s| s.parse().ok()).unwrap_or(300);
-            match platform {
-                "android" => android::swipe(x1, y1, x2, y2, dur, device)?,
-                "ios" => ios::swipe(x1, y1, x2, y2, dur, simulator)?,
-                "aurora" => aurora::swipe(x1, y1, x2, y2, dur, device)?,
-                _ => bail!("Unsupported platform"),
-            }
-            Ok(format!("Swiped ({},{}) -> ({},{})", x1, y1, x2, y2))
-        }
-        "key" => {
-            if step.args.is_empty() {
-                bail!("key requires 1 arg");
-            }
-            let key = &step.args[0];
-            match platform {
-                "android" => android::press_key(key, device)?,
-                "ios" => ios::press_key(key, simulator)?,
-                "aurora" => aurora::press_key(key, device)?,
-                "desktop" => desktop::press_key(key, companion_path)?,
-                _ => bail!("Unsupported platform"),
-            }
-            Ok(format!("Pressed key \"{}\"", key))
+        _ => out.push_str(&value.to_string()),
+    }
+}
+
+/// Rewrite every scenario under `dir` in canonical form, or with `check`
+/// only list the ones that are not and fail. Files that do not load are
+/// left alone.
+fn cmd_format(dir: &Path, check: bool) -> Result<()> {
+    let (mut changed, mut total) = (0, 0);
+    let mut files = walk_scenarios(dir);
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    for (path, scenario) in files {
+        if let Err(e) = scenario {
+            eprintln!("Warning: skipping {}: {:#}", path.display(), e);
+            continue;
         }
-        "wait" => {
-            if step.args.is_empty() {
-                bail!("wait requires 1 arg");
-            }
-            let ms: u64 = step.args[0].parse()?;
-            std::thread::sleep(std::time::Duration::from_millis(ms));
-            Ok(format!("Waited {}ms", ms))
+        total += 1;
+        let text = fs::read_to_string(&path).with_context(|| format!("Cannot read {}", path.display()))?;
+        let canonical = normalized_json(&text)?;
+        if canonical == text {
+            continue;
         }
-        "launch" => {
-            if step.args.is_empty() {
-                bail!("launch requires 1 arg");
-            }
-            let pkg = &step.args[0];
-            match platform {
-                "android" => android::launch_app(pkg, device)?,
-                "ios" => ios::launch_app(pkg, simulator)?,
-                "aurora" => aurora::launch_app(pkg, device)?,
-                "desktop" => desktop::launch_app(pkg, companion_path)?,
-                _ => bail!("Unsupported platform"),
-            }
-            Ok(format!("Launched \"{}\"", pkg))
+        changed += 1;
+        if check {
+            println!("Would reformat {}", path.display());
+        } else {
+            fs::write(&path, canonical).with_context(|| format!("Cannot write {}", path.display()))?;
+            println!("Reformatted {}", path.display());
         }
-        "stop" => {
-            if step.args.is_empty() {
-                bail!("stop requires 1 arg");
-            }
-            let pkg = &step.args[0];
-            match platform {
-                "android" => android::stop_app(pkg, device)?,
-                "ios" => ios::stop_app(pkg, simulator)?,
-                "aurora" => aurora::stop_app(pkg, device)?,
-                "desktop" => desktop::stop_app(pkg, companion_path)?,
-                _ => bail!("Unsupported platform"),
-            }
-            Ok(format!("Stopped \"{}\"", pkg))
+    }
+    if check && changed > 0 {
+        bail!("{} of {} scenario(s) are not in canonical form; run `recorder format`", changed, total);
+    }
+    println!("{} scenario(s), {} {}", total, changed, if check { "to reformat" } else { "reformatted" });
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// recorder approve
+// ---------------------------------------------------------------------------
+
+/// Hex SHA-256 over the scenario's content, independent of key order and
+/// whitespace in the file.
+///
+/// The scenario is re-serialized with sorted keys; the approval itself and
+/// the created/updated timestamps are left out, so re-saving an unchanged
+/// scenario keeps its hash.
+pub fn content_hash(scenario: &Scenario) -> Result<String> {
+    use rsa::sha2::{Digest, Sha256};
+
+    let mut value = serde_json::to_value(scenario)?;
+    if let Some(obj) = value.as_object_mut() {
+        for key in ["approvedHash", "createdAt", "updatedAt"] {
+            obj.remove(key);
         }
-        other => bail!("Unsupported action '{}' in recorder play", other),
     }
+    // serde_json's Map is ordered by key, so this is canonical.
+    let canonical = serde_json::to_string(&value)?;
+    Ok(Sha256::digest(canonical.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect())
 }
 
-/// Run a step with a wall-clock timeout via a dedicated thread.
-fn run_with_timeout(
-    ctx: &FlowCtx,
-    step: &crate::commands::flow::FlowStep,
-    timeout_ms: u64,
-) -> Result<String> {
-    use std::sync::mpsc;
+/// Fail when the scenario was changed after `recorder approve`.
+fn verify_approval(scenario: &Scenario) -> Result<()> {
+    let Some(approved) = &scenario.approved_hash else { return Ok(()) };
+    let actual = content_hash(scenario)?;
+    if !actual.eq_ignore_ascii_case(approved) {
+        bail!(
+            "Scenario '{}' was modified since approval (approved {}, now {}); review it and run `recorder approve` again",
+            scenario.name,
+            approved,
+            actual
+        );
+    }
+    Ok(())
+}
 
-    // Clone data needed for the worker thread.
-    let ctx_owned = FlowCtx {
-        platform: ctx.platform.clone(),
-        device: ctx.device.clone(),
-        simulator: ctx.simulator.clone(),
-        companion_path: ctx.companion_path.clone(),
-    };
-    let step_owned = crate::commands::flow::FlowStep {
-        action: step.action.clone(),
-        args: step.args.clone(),
-        on_error: step.on_error,
-    };
+fn cmd_approve(name: &str, platform: &str) -> Result<()> {
+    let mut scenario = read_scenario(platform, name)?;
+    let hash = content_hash(&scenario)?;
+    scenario.approved_hash = Some(hash.clone());
+    write_scenario(&scenario)?;
+    println!("Approved '{}/{}': {}", platform, name, hash);
+    Ok(())
+}
 
-    let (tx, rx) = mpsc::channel::<Result<String>>();
-    std::thread::spawn(move || {
-        let result = run_step(&ctx_owned, &step_owned);
-        let _ = tx.send(result);
-    });
+// ---------------------------------------------------------------------------
+// Variable resolution
+// ---------------------------------------------------------------------------
+
+fn var_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\$\{([^}]*)\}").unwrap())
+}
+
+/// Build the `${config.<key>}` lookup table for a replay.
+///
+/// Precedence (lowest to highest): the global `~/.claude-mobile/config.json`,
+/// the flat JSON object in `--config-file`, then individual `--config KEY=VALUE`
+/// flags.
+fn build_play_config(pairs: &[String], config_file: Option<&str>) -> Result<HashMap<String, String>> {
+    fn stringify(v: serde_json::Value) -> String {
+        match v {
+            serde_json::Value::String(s) => s,
+            other => other.to_string(),
+        }
+    }
+
+    let mut config: HashMap<String, String> = crate::commands::config::load_config()
+        .into_iter()
+        .map(|(k, v)| (k, stringify(v)))
+        .collect();
+
+    if let Some(path) = config_file {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Cannot read config file '{}'", path))?;
+        let map: HashMap<String, serde_json::Value> = serde_json::from_str(&text)
+            .with_context(|| format!("Config file '{}' must be a flat JSON object", path))?;
+        config.extend(map.into_iter().map(|(k, v)| (k, stringify(v))));
+    }
+
+    for pair in pairs {
+        let (k, v) = pair
+            .split_once('=')
+            .with_context(|| format!("--config expects KEY=VALUE, got '{}'", pair))?;
+        config.insert(k.trim().to_owned(), v.to_owned());
+    }
+    Ok(config)
+}
+
+/// Expand `${ENV_VAR}`, `${config.key}` and `${NAME:-default}` references.
+///
+/// Environment variables come from the process environment; `config.`-prefixed
+/// names come from `config`. A reference that is unset and has no default is
+/// an error.
+fn resolve_vars(text: &str, config: &HashMap<String, String>) -> Result<String> {
+    resolve_vars_with(text, config, |name| std::env::var(name).ok())
+}
+
+/// [`resolve_vars`] with `env` standing in for the process environment.
+fn resolve_vars_with(text: &str, config: &HashMap<String, String>, env: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for caps in var_regex().captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        let expr = &caps[1];
+        let (name, default) = match expr.split_once(":-") {
+            Some((n, d)) => (n, Some(d)),
+            None => (expr, None),
+        };
+        let value = match name.strip_prefix("config.") {
+            Some(key) => config.get(key).cloned(),
+            None => env(name),
+        };
+        let value = match (value, default) {
+            (Some(v), _) => v,
+            (None, Some(d)) => d.to_owned(),
+            (None, None) => match name.strip_prefix("config.") {
+                Some(key) => bail!(
+                    "Variable '${{{}}}' is not set: pass --config {}=<value> or use ${{{}:-default}}",
+                    name, key, name
+                ),
+                None => bail!(
+                    "Variable '${{{}}}' is not set in the environment and has no default (use ${{{}:-default}})",
+                    name, name
+                ),
+            },
+        };
+        out.push_str(&text[last..whole.start()]);
+        out.push_str(&value);
+        last = whole.end();
+    }
+    out.push_str(&text[last..]);
+    Ok(out)
+}
+
+/// Actions whose args are secrets (the `unlock` PIN), never printed.
+const SECRET_ARGS: &[&str] = &["unlock"];
+
+/// A step's args as progress output shows them, with secrets masked. Args
+/// are printed after `${VAR}` resolution, so a PIN kept in config would
+/// otherwise end up in CI logs.
+fn shown_args(step: &ScenarioStep) -> Vec<&str> {
+    let secret = SECRET_ARGS.contains(&step.action.as_str());
+    step.args.iter().map(|a| if secret { "***" } else { a.as_str() }).collect()
+}
+
+/// Resolve variables in a step's args and label in place.
+fn resolve_step_vars(step: &mut ScenarioStep, config: &HashMap<String, String>) -> Result<()> {
+    for arg in step.args.iter_mut() {
+        *arg = resolve_vars(arg, config).with_context(|| format!("Step {}", step.index + 1))?;
+    }
+    if let Some(label) = step.label.as_mut() {
+        *label = resolve_vars(label, config).with_context(|| format!("Step {}", step.index + 1))?;
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Strings catalog
+// ---------------------------------------------------------------------------
+
+/// A step arg of the form `@key:<name>` is replaced by that catalog entry.
+const STRING_KEY_PREFIX: &str = "@key:";
+
+/// Catalog entries for the locale a run uses.
+#[derive(Debug, Clone)]
+struct StringTable {
+    locale: String,
+    strings: HashMap<String, String>,
+}
+
+/// Read a catalog file: `{"<locale>": {"<key>": "<text>", …}, …}`.
+fn load_catalog(path: &str) -> Result<std::collections::BTreeMap<String, HashMap<String, String>>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Cannot read strings catalog '{}'", path))?;
+    serde_json::from_str(&text).with_context(|| {
+        format!("Strings catalog '{}' must map locales to flat key/text objects", path)
+    })
+}
+
+/// Load one locale from a catalog; `locale` may be omitted when the catalog
+/// has exactly one.
+fn load_string_table(path: &str, locale: Option<&str>) -> Result<StringTable> {
+    let mut catalog = load_catalog(path)?;
+    let locale = match locale {
+        Some(l) => l.to_owned(),
+        None if catalog.len() == 1 => catalog.keys().next().cloned().unwrap_or_default(),
+        None => bail!(
+            "Strings catalog '{}' has {} locales; pick one with --locale ({})",
+            path,
+            catalog.len(),
+            catalog.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+    };
+    let strings = catalog.remove(&locale).with_context(|| {
+        format!("Locale '{}' is not in strings catalog '{}'", locale, path)
+    })?;
+    Ok(StringTable { locale, strings })
+}
+
+/// Fail listing every `@key:` reference the table cannot resolve.
+fn check_string_keys(steps: &[ScenarioStep], table: &StringTable) -> Result<()> {
+    let missing: Vec<String> = steps
+        .iter()
+        .flat_map(|step| step.args.iter().map(move |a| (step, a)))
+        .filter_map(|(step, arg)| arg.strip_prefix(STRING_KEY_PREFIX).map(|key| (step, key)))
+        .filter(|(_, key)| !table.strings.contains_key(*key))
+        .map(|(step, key)| format!("{} (step {})", key, step.index + 1))
+        .collect();
+    if !missing.is_empty() {
+        bail!("Missing string keys for locale '{}': {}", table.locale, missing.join(", "));
+    }
+    Ok(())
+}
+
+/// Replace `@key:` args with catalog text. Without a table, any reference
+/// is an error rather than being typed or matched literally.
+fn resolve_string_keys(steps: &mut [ScenarioStep], table: Option<&StringTable>) -> Result<()> {
+    let Some(table) = table else {
+        if let Some(step) = steps.iter().find(|s| s.args.iter().any(|a| a.starts_with(STRING_KEY_PREFIX))) {
+            bail!("Step {} uses an @key: reference; pass --strings <catalog>", step.index + 1);
+        }
+        return Ok(());
+    };
+    check_string_keys(steps, table)?;
+    for arg in steps.iter_mut().flat_map(|s| s.args.iter_mut()) {
+        if let Some(key) = arg.strip_prefix(STRING_KEY_PREFIX) {
+            *arg = table.strings[key].clone();
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// recorder play
+// ---------------------------------------------------------------------------
+
+/// Replay settings for `recorder play`.
+#[derive(Clone)]
+struct PlayOptions {
+    speed: f64,
+    stop_on_fail: bool,
+    step_timeout: Option<u64>,
+    max_duration: Option<u64>,
+    from_step: Option<usize>,
+    to_step: Option<usize>,
+    /// Run the precondition fixtures before the steps.
+    fixtures: bool,
+    dry_run: bool,
+    /// Values for `${config.<key>}` references in step text.
+    config: HashMap<String, String>,
+    /// Android package to sample perf stats for while the scenario runs.
+    perf_package: Option<String>,
+    perf_interval_ms: u64,
+    /// Localized text for `@key:` args.
+    strings: Option<StringTable>,
+    /// Root of the shared baseline tree for `assert-screen` (see [`baseline_path`]).
+    baselines: PathBuf,
+    /// Overwrite baselines with the current screen instead of comparing.
+    update_baselines: bool,
+    /// Per-step CPU/memory limits (Android only).
+    watchdog: Option<WatchdogOptions>,
+    /// Prompt for manual steps; otherwise they are skipped and the case is
+    /// marked as needing manual verification.
+    interactive: bool,
+    /// Device-farm device to forward actions to instead of a local device.
+    remote_device: Option<String>,
+    /// Run scenario `before`/`after` hooks; without it a scenario that has
+    /// hooks fails instead of running them.
+    allow_hooks: bool,
+    /// Answer system dialogs around each step (Android only).
+    dialogs: Option<crate::android::DialogHandler>,
+    /// Abort the scenario at this point, cutting a running step short
+    /// (suite timeout plus grace).
+    deadline: Option<std::time::Instant>,
+    /// Directory template for the files a failed `assert-screen` leaves
+    /// (see [`DEFAULT_SCREENSHOTS`]).
+    screenshots: Option<(PathTemplate, OutputLayout)>,
+}
+
+/// `--watchdog-*` settings: limits plus the app to watch.
+#[derive(Debug, Clone)]
+struct WatchdogOptions {
+    /// `None` watches whichever app is in the foreground when a step starts.
+    package: Option<String>,
+    limits: crate::android::WatchdogLimits,
+}
+
+impl WatchdogOptions {
+    /// `None` unless at least one limit is set.
+    fn from_flags(
+        cpu_percent: Option<f64>,
+        cpu_window_secs: u64,
+        memory_mb: Option<f64>,
+        package: Option<String>,
+    ) -> Option<Self> {
+        (cpu_percent.is_some() || memory_mb.is_some()).then(|| WatchdogOptions {
+            package,
+            limits: crate::android::WatchdogLimits {
+                cpu_percent,
+                cpu_window_ms: cpu_window_secs * 1000,
+                memory_mb,
+            },
+        })
+    }
+}
+
+/// When a hook runs relative to its case or suite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum HookPhase {
+    Before,
+    After,
+}
+
+/// Hook output kept in the report, from the end, where errors usually are.
+const HOOK_OUTPUT_LIMIT: usize = 16 * 1024;
+
+/// One host shell hook as it ran.
+#[derive(Debug, Clone, Serialize)]
+struct HookResult {
+    phase: HookPhase,
+    command: String,
+    /// `None` when the command could not be started or was killed by a signal.
+    #[serde(rename = "exitCode")]
+    exit_code: Option<i32>,
+    #[serde(rename = "durationMs")]
+    duration_ms: u64,
+    /// Combined stdout and stderr, trimmed to the last [`HOOK_OUTPUT_LIMIT`] bytes.
+    output: String,
+}
+
+impl HookResult {
+    fn passed(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    fn error(&self) -> String {
+        match self.exit_code {
+            Some(code) => format!("{} hook `{}` exited with {}", self.phase_name(), self.command, code),
+            None => format!(
+                "{} hook `{}` did not finish: {}",
+                self.phase_name(),
+                self.command,
+                self.output.trim().lines().last().unwrap_or("killed")
+            ),
+        }
+    }
+
+    fn phase_name(&self) -> &'static str {
+        match self.phase {
+            HookPhase::Before => "before",
+            HookPhase::After => "after",
+        }
+    }
+}
+
+/// The last `limit` bytes of `text`, cut at a character boundary.
+fn output_tail(text: &str, limit: usize) -> &str {
+    let mut start = text.len().saturating_sub(limit);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
+
+/// Longest a hook may run, however much time the suite has left.
+const HOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(600);
+
+/// Run one hook through the host shell (`sh -c`, or `cmd /C` on Windows),
+/// killing it at `deadline` or after [`HOOK_TIMEOUT`]. Output goes to an
+/// unnamed temp file rather than a pipe, so a hook that leaves a server
+/// running in the background does not keep us waiting for EOF.
+fn run_hook(phase: HookPhase, command: &str, deadline: Option<std::time::Instant>) -> HookResult {
+    let start = std::time::Instant::now();
+    let limit = deadline.map_or(start + HOOK_TIMEOUT, |d| d.min(start + HOOK_TIMEOUT));
+    let hook = |exit_code, output| HookResult {
+        phase,
+        command: command.to_owned(),
+        exit_code,
+        duration_ms: start.elapsed().as_millis() as u64,
+        output,
+    };
+    let mut shell = match cfg!(windows) {
+        true => std::process::Command::new("cmd"),
+        false => std::process::Command::new("sh"),
+    };
+    shell.args([if cfg!(windows) { "/C" } else { "-c" }, command]);
+    // Its own process group, so a timeout also stops what the hook started.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut shell, 0);
+    let spawned = tempfile::tempfile().and_then(|log| {
+        let child = shell
+            .stdin(std::process::Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log.try_clone()?)
+            .spawn()?;
+        Ok((child, log))
+    });
+    let (mut child, mut log) = match spawned {
+        Ok(spawned) => spawned,
+        Err(e) => return hook(None, format!("cannot start shell: {}", e)),
+    };
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if std::time::Instant::now() < limit => std::thread::sleep(std::time::Duration::from_millis(50)),
+            _ => {
+                #[cfg(unix)]
+                let _ = std::process::Command::new("kill")
+                    .args(["-KILL", "--", &format!("-{}", child.id())])
+                    .stderr(std::process::Stdio::null())
+                    .status();
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+        }
+    };
+    let mut text = String::new();
+    {
+        use std::io::{Read, Seek};
+        let _ = log.rewind().and_then(|_| log.read_to_string(&mut text));
+    }
+    let mut output = output_tail(&text, HOOK_OUTPUT_LIMIT).to_owned();
+    if status.is_none() {
+        output.push_str(&format!("\ntimed out after {}s", limit.saturating_duration_since(start).as_secs()));
+    }
+    hook(status.and_then(|s| s.code()), output)
+}
+
+/// `print!` for output while a case plays; see [`step_output`].
+macro_rules! step_print {
+    ($($arg:tt)*) => {
+        step_output(format_args!($($arg)*), false)
+    };
+}
+
+/// `println!` for output while a case plays; see [`step_output`].
+macro_rules! step_println {
+    () => {
+        step_output(format_args!(""), true)
+    };
+    ($($arg:tt)*) => {
+        step_output(format_args!($($arg)*), true)
+    };
+}
+
+/// Run `commands` in order, each stopped at `deadline`. `before` hooks stop
+/// at the first failure; `after` hooks all run, since each usually cleans
+/// up something different.
+fn run_hooks(phase: HookPhase, commands: &[String], deadline: Option<std::time::Instant>) -> Vec<HookResult> {
+    let mut results = Vec::with_capacity(commands.len());
+    for command in commands {
+        let result = run_hook(phase, command, deadline);
+        let status = if result.passed() { "ok".to_string() } else { result.error() };
+        step_println!("  {} hook: {} ({}ms) {}", result.phase_name(), command, result.duration_ms, status);
+        let failed = !result.passed();
+        results.push(result);
+        if failed && phase == HookPhase::Before {
+            break;
+        }
+    }
+    results
+}
+
+/// Outcome of one scenario replay.
+struct PlayRun {
+    failed: usize,
+    /// Manual steps nobody passed or failed (skipped, or no prompt).
+    unverified_manual: usize,
+    steps: Vec<StepResult>,
+    perf: Option<crate::android::PerfSample>,
+    fixtures: Vec<FixtureResult>,
+    hooks: Vec<HookResult>,
+    /// Files the steps wrote for the report.
+    artifacts: Vec<String>,
+    /// Stopped at [`PlayOptions::deadline`].
+    aborted: bool,
+}
+
+fn cmd_play(name: &str, platform: &str, all_platforms: bool, opts: &PlayOptions) -> Result<()> {
+    if all_platforms {
+        return play_all_platforms(name, platform, opts);
+    }
+    let run = play_scenario(name, platform, opts)?;
+    if run.failed > 0 {
+        bail!("Scenario '{}' finished with {} failure(s)", name, run.failed);
+    }
+    Ok(())
+}
+
+/// Play a cross-platform scenario (stored under `dir_platform`) once per
+/// entry in its `platforms`, each against that platform's default device.
+fn play_all_platforms(name: &str, dir_platform: &str, opts: &PlayOptions) -> Result<()> {
+    let targets = read_scenario(dir_platform, name)?
+        .platforms
+        .with_context(|| format!("Scenario '{}' has no `platforms` list to run on", name))?;
+    let mut outcomes: Vec<(String, Result<PlayRun>)> = Vec::new();
+    for target in targets {
+        println!("\n=== {} ===", target);
+        let run = read_scenario(dir_platform, name).and_then(|s| play_loaded(s, &target, opts));
+        outcomes.push((target, run));
+    }
+    println!("\nPer-platform results:");
+    let mut failed = 0;
+    for (target, run) in &outcomes {
+        match run {
+            Ok(r) if r.failed == 0 => println!("  PASS  {}", target),
+            Ok(r) => {
+                failed += 1;
+                println!("  FAIL  {} ({} step failure(s))", target, r.failed);
+            }
+            Err(e) => {
+                failed += 1;
+                println!("  FAIL  {} ({:#})", target, e);
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("Scenario '{}' failed on {} of {} platform(s)", name, failed, outcomes.len());
+    }
+    Ok(())
+}
+
+/// Replay a scenario; setup errors are `Err`, failing steps are counted.
+fn play_scenario(name: &str, platform: &str, opts: &PlayOptions) -> Result<PlayRun> {
+    play_loaded(read_scenario(platform, name)?, platform, opts)
+}
+
+/// Play a scenario between its `before` and `after` hooks. A failing
+/// `before` hook fails the run without playing any step; `after` hooks run
+/// whatever happened.
+fn play_loaded(mut scenario: Scenario, platform: &str, opts: &PlayOptions) -> Result<PlayRun> {
+    verify_approval(&scenario)?;
+    if let Some(targets) = scenario.platforms.as_ref().filter(|t| !t.iter().any(|p| p == platform)) {
+        bail!("Scenario '{}' does not run on {} (platforms: {})", scenario.name, platform, targets.join(", "));
+    }
+    let before = scenario.before.take().unwrap_or_default();
+    let after = scenario.after.take().unwrap_or_default();
+    if before.is_empty() && after.is_empty() {
+        return play_steps(scenario, platform, opts);
+    }
+    if !opts.allow_hooks {
+        bail!(
+            "Scenario '{}' has before/after hooks, which run shell commands on this machine; pass --allow-hooks to run them",
+            scenario.name
+        );
+    }
+    if opts.dry_run {
+        for (phase, cmd) in before.iter().map(|c| ("before", c)).chain(after.iter().map(|c| ("after", c))) {
+            step_println!("  [dry-run] {} hook: {}", phase, cmd);
+        }
+        return play_steps(scenario, platform, opts);
+    }
+
+    let mut hooks = run_hooks(HookPhase::Before, &before, opts.deadline);
+    let run = if hooks.iter().any(|h| !h.passed()) {
+        step_println!("\nBefore hook failed; the case's steps were not run.");
+        Ok(PlayRun {
+            failed: 1,
+            unverified_manual: 0,
+            steps: Vec::new(),
+            perf: None,
+            fixtures: Vec::new(),
+            hooks: Vec::new(),
+            artifacts: Vec::new(),
+            aborted: false,
+        })
+    } else {
+        play_steps(scenario, platform, opts)
+    };
+    hooks.extend(run_hooks(HookPhase::After, &after, None));
+    run.map(|run| PlayRun { hooks, ..run })
+}
+
+fn play_steps(mut scenario: Scenario, platform: &str, opts: &PlayOptions) -> Result<PlayRun> {
+    let PlayOptions { speed, stop_on_fail, step_timeout, max_duration, dry_run, .. } = *opts;
+    let name = scenario.name.clone();
+    // A bad capture-format setting fails the run before any step does.
+    report_format(opts)?;
+
+    let from = opts.from_step.unwrap_or(1).saturating_sub(1);
+    let to = opts.to_step.unwrap_or(scenario.steps.len()).min(scenario.steps.len());
+
+    if from >= to {
+        bail!("--from-step ({}) must be less than --to-step ({})", from + 1, to);
+    }
+
+    let mut fixtures = if opts.fixtures && !fixture_names(&scenario).is_empty() {
+        load_fixtures(&scenario, &scenarios_dir(&scenario.platform)?)?
+    } else {
+        Vec::new()
+    };
+
+    expand_checks(&mut scenario.steps[from..to], scenario.checks.as_ref());
+    // Resolve ${…} references up front so a missing variable aborts the run
+    // before any step touches the device.
+    for step in scenario.steps[from..to].iter_mut() {
+        resolve_step_vars(step, &opts.config)?;
+    }
+    resolve_string_keys(&mut scenario.steps[from..to], opts.strings.as_ref())?;
+
+    let steps_to_run: Vec<&ScenarioStep> = scenario.steps[from..to].iter().collect();
+    let max_dur_ms = max_duration.unwrap_or(u64::MAX);
+    let start = std::time::Instant::now();
+
+    step_println!(
+        "Playing scenario '{}' on '{}' ({} steps, speed={}, dry_run={})…",
+        name,
+        platform,
+        steps_to_run.len(),
+        speed,
+        dry_run
+    );
+    let meta = sorted_metadata(&scenario);
+    if !meta.is_empty() {
+        let pairs: Vec<String> = meta.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        step_println!("  Metadata: {}", pairs.join(", "));
+    }
+    if let Some(table) = &opts.strings {
+        step_println!("  Locale: {}", table.locale);
+    }
+    // Restores the device's locale when the run ends.
+    let _locale = match &opts.strings {
+        Some(table) if !dry_run && platform == "android" && opts.remote_device.is_none() => {
+            Some(crate::android::set_locale(&table.locale, launched_package(&scenario.steps), None)?)
+        }
+        _ => None,
+    };
+
+    if let Some(id) = opts.remote_device.as_deref().filter(|_| !dry_run) {
+        check_remote_run(id, &steps_to_run, &fixtures, opts)?;
+    }
+
+    let artifacts = ArtifactLog::default();
+    let mut fixture_results = Vec::with_capacity(fixtures.len());
+    for fixture in &mut fixtures {
+        let result = run_fixture(fixture, platform, opts, &artifacts)?;
+        let ok = result.status == CaseStatus::Passed;
+        fixture_results.push(result);
+        if !ok {
+            step_println!("\nSetup failed; the case's own steps were not run.");
+            return Ok(PlayRun {
+                failed: 1,
+                unverified_manual: 0,
+                steps: Vec::new(),
+                perf: None,
+                fixtures: fixture_results,
+                hooks: Vec::new(),
+                artifacts: artifacts.take(),
+                aborted: false,
+            });
+        }
+    }
+
+    // Perf is read over adb from the device the steps drive, which a
+    // device-farm run does not go through.
+    let sampler = match &opts.perf_package {
+        Some(_) if !dry_run && opts.remote_device.is_some() => bail!("--perf needs a local device, not --remote-device"),
+        Some(pkg) if !dry_run && platform == "android" => {
+            Some(crate::android::PerfSampler::start(pkg, opts.perf_interval_ms, None)?)
+        }
+        Some(_) if !dry_run => bail!("--perf is only supported on android"),
+        _ => None,
+    };
+    if opts.watchdog.is_some() && !dry_run && platform != "android" {
+        bail!("--watchdog-cpu/--watchdog-memory are only supported on android");
+    }
+
+    // Requests are matched from scenario start, so an `assert-request` step
+    // also sees calls fired by earlier steps.
+    let network = if !dry_run && platform == "android" && steps_to_run.iter().any(|s| s.action == "assert-request") {
+        Some(crate::android::NetworkCapture::start(None)?)
+    } else {
+        None
+    };
+
+    let mut captures = AssertCaptures::default();
+
+    let mut passed = 0usize;
+    let mut failed = 0usize;
+    let mut expected_failures = 0usize;
+    let mut unverified_manual = 0usize;
+    // (step key, message) of failed soft assertions, reported at the end.
+    let mut soft_failures: Vec<(String, String)> = Vec::new();
+    // Soft failures and unverified manual checks in fixtures count against
+    // the case like its own.
+    for fixture in &fixture_results {
+        for step in &fixture.steps {
+            if step.soft && step.status == CaseStatus::Failed {
+                failed += 1;
+                soft_failures.push((format!("{}/{}", fixture.name, step.key), step.error.clone().unwrap_or_default()));
+            } else if step.manual && step.status == CaseStatus::Skipped {
+                unverified_manual += 1;
+            }
+        }
+    }
+    let mut results: Vec<StepResult> = Vec::with_capacity(steps_to_run.len());
+    // Outcome by step id, for `when` conditions.
+    let mut outcomes: HashMap<&str, bool> = HashMap::new();
+    let mut current_group: Option<&str> = None;
+    let dialog_handler = opts.dialogs.as_ref().filter(|_| !dry_run && platform == "android" && opts.remote_device.is_none());
+    let mut aborted = false;
+
+    for (i, step) in steps_to_run.iter().enumerate() {
+        if step.group.as_deref() != current_group {
+            current_group = step.group.as_deref();
+            if let Some(g) = current_group {
+                step_println!("  ▸ {}", g);
+            }
+        }
+        if start.elapsed().as_millis() as u64 >= max_dur_ms {
+            step_println!("Max duration reached, stopping.");
+            break;
+        }
+        if aborted || opts.deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+            step_println!("Suite timeout grace period is over, aborting the case.");
+            aborted = true;
+            break;
+        }
+
+        // Apply inter-step delay scaled by speed.
+        if step.delay_before_ms > 0 && !dry_run {
+            let delay = (step.delay_before_ms as f64 / speed) as u64;
+            std::thread::sleep(std::time::Duration::from_millis(delay));
+        }
+
+        let step_label = step.label.as_deref().unwrap_or(&step.action);
+        let id_note = step.id.as_deref().map(|id| format!(" [{}]", id)).unwrap_or_default();
+        step_print!("  Step {}/{}{}: {} {:?} … ", i + 1, steps_to_run.len(), id_note, step_label, shown_args(step));
+
+        if !step.runs_on(platform) {
+            step_println!("SKIP  (only on {})", step.only_on.as_deref().unwrap_or_default().join(", "));
+            results.push(StepResult {
+                key: step.key(),
+                status: CaseStatus::Skipped,
+                duration_ms: 0,
+                error: None,
+                soft: false,
+                group: step.group.clone(),
+                watchdog: None,
+                manual: false,
+                dialogs: Vec::new(),
+            });
+            continue;
+        }
+        if let Some(cond) = &step.when {
+            let (id, want_pass) = parse_step_condition(cond)?;
+            if outcomes.get(id) != Some(&want_pass) {
+                step_println!("SKIP  (when {})", cond);
+                results.push(StepResult {
+                    key: step.key(),
+                    status: CaseStatus::Skipped,
+                    duration_ms: 0,
+                    error: None,
+                    soft: false,
+                    group: step.group.clone(),
+                    watchdog: None,
+                    manual: false,
+                    dialogs: Vec::new(),
+                });
+                continue;
+            }
+        }
+
+        if dry_run {
+            step_println!("[dry-run]");
+            passed += 1;
+            if let Some(id) = &step.id {
+                outcomes.insert(id, true);
+            }
+            results.push(StepResult {
+                key: step.key(),
+                status: CaseStatus::Passed,
+                duration_ms: 0,
+                error: None,
+                soft: false,
+                group: step.group.clone(),
+                watchdog: None,
+                manual: step.manual.unwrap_or(false),
+                dialogs: Vec::new(),
+            });
+            continue;
+        }
+        let step_start = std::time::Instant::now();
+
+        if step.manual.unwrap_or(false) {
+            let verdict = if opts.interactive {
+                let stdin = std::io::stdin();
+                prompt_manual(&mut stdin.lock(), &mut std::io::stdout(), step)?
+            } else {
+                step_println!("SKIP  (manual step; needs manual verification)");
+                ManualVerdict::Skip
+            };
+            let (status, error) = match &verdict {
+                ManualVerdict::Pass => (CaseStatus::Passed, None),
+                ManualVerdict::Fail(note) => (CaseStatus::Failed, Some(format!("Manual check failed: {}", note))),
+                ManualVerdict::Skip => (CaseStatus::Skipped, None),
+            };
+            match verdict {
+                ManualVerdict::Pass => passed += 1,
+                ManualVerdict::Fail(_) => failed += 1,
+                ManualVerdict::Skip => unverified_manual += 1,
+            }
+            if let Some(id) = step.id.as_deref().filter(|_| status != CaseStatus::Skipped) {
+                outcomes.insert(id, status == CaseStatus::Passed);
+            }
+            results.push(StepResult {
+                key: step.key(),
+                status,
+                duration_ms: step_start.elapsed().as_millis() as u64,
+                error,
+                soft: false,
+                group: step.group.clone(),
+                watchdog: None,
+                manual: true,
+                dialogs: Vec::new(),
+            });
+            if status == CaseStatus::Failed && stop_on_fail {
+                step_println!("Stopping on failure (--stop-on-fail).");
+                break;
+            }
+            continue;
+        }
+
+        // A dialog left over from the previous step would swallow this one's
+        // taps. The check dumps the UI, which a running toast watch blocks.
+        let mut dialogs = match dialog_handler {
+            Some(handler) if captures.toasts.is_none() => clear_dialogs(handler, None),
+            _ => Vec::new(),
+        };
+
+        // Build a FlowStep and delegate to flow::execute_step.
+        let mut flow_step = crate::commands::flow::FlowStep {
+            action: step.action.clone(),
+            args: step.args.clone(),
+            on_error: crate::commands::flow::OnError::Stop,
+        };
+        let next = steps_to_run.get(i + 1).copied();
+        captures.before_step(step, next, &mut flow_step, platform, opts)?;
+        let ctx = step_ctx(&name, step, platform, opts, network.as_ref(), &captures, &artifacts)?;
+
+        let watchdog = opts.watchdog.as_ref().map(start_watchdog).transpose()?.flatten();
+        // At the deadline a running step is abandoned like a timed-out one.
+        let timeout_ms = match opts.deadline {
+            Some(d) => {
+                let left = d.saturating_duration_since(std::time::Instant::now()).as_millis() as u64;
+                Some(step_timeout.map_or(left, |t| t.min(left)))
+            }
+            None => step_timeout,
+        };
+        let mut result = if timeout_ms.is_some() || watchdog.is_some() {
+            run_with_timeout(&ctx, &flow_step, timeout_ms, watchdog.as_ref())
+        } else {
+            run_step(&ctx, &flow_step)
+        };
+        aborted = result.is_err() && opts.deadline.is_some_and(|d| std::time::Instant::now() >= d);
+        // Peaks are reported whatever the outcome; a limit crossed just as
+        // the step finished still fails it.
+        let peak = watchdog.map(crate::android::Watchdog::finish);
+        // A dialog that popped up mid-step is answered, and the step gets one
+        // retry if running it again can't repeat a side effect.
+        if let (Err(_), Some(handler)) = (&result, dialog_handler) {
+            let handled = clear_dialogs(handler, ctx.device.as_deref());
+            if !handled.is_empty() && !NOT_REPLAYED.contains(&flow_step.action.as_str()) {
+                result = run_step(&ctx, &flow_step);
+            }
+            dialogs.extend(handled);
+        }
+        captures.after_step(step, next)?;
+        if let Some(report) = &peak {
+            if let (Ok(_), Some(v)) = (&result, &report.violation) {
+                result = Err(anyhow::anyhow!("Watchdog: {}", v));
+            }
+        }
+
+        let outcome = StepOutcome::classify(result.is_ok(), step.expect_failure.unwrap_or(false));
+        let soft = step.soft.unwrap_or(false);
+        let detail = match &result {
+            Ok(msg) => msg.clone(),
+            Err(e) => e.to_string(),
+        };
+        let detail = match &peak {
+            Some(p) => format!("{} (peak cpu {:.1}%, mem {:.1}MB)", detail, p.peak_cpu_percent, p.peak_memory_mb),
+            None => detail,
+        };
+        let detail = if dialogs.is_empty() {
+            detail
+        } else {
+            let answered: Vec<String> = dialogs.iter().map(|d| format!("{} {}", d.kind.as_str(), d.response)).collect();
+            format!("{} (dialogs: {})", detail, answered.join(", "))
+        };
+        match outcome {
+            StepOutcome::Passed => step_println!("OK  {}", detail),
+            StepOutcome::ExpectedFailure => {
+                step_println!("XFAIL  {} (failed as expected)", detail);
+                expected_failures += 1;
+            }
+            StepOutcome::UnexpectedPass => {
+                step_println!("XPASS  {} (expected failure, but step succeeded)", detail);
+            }
+            StepOutcome::Failed if soft => step_println!("SOFT FAIL  {}", detail),
+            StepOutcome::Failed => step_println!("FAIL  {}", detail),
+        }
+
+        if let Some(id) = &step.id {
+            outcomes.insert(id, outcome.is_pass());
+        }
+        results.push(StepResult {
+            key: step.key(),
+            status: if outcome.is_pass() { CaseStatus::Passed } else { CaseStatus::Failed },
+            duration_ms: step_start.elapsed().as_millis() as u64,
+            error: (!outcome.is_pass()).then(|| detail.clone()),
+            soft,
+            group: step.group.clone(),
+            watchdog: peak,
+            manual: false,
+            dialogs,
+        });
+
+        if outcome.is_pass() {
+            passed += 1;
+        } else if soft {
+            failed += 1;
+            soft_failures.push((step.key(), detail));
+        } else {
+            failed += 1;
+            if stop_on_fail {
+                step_println!("Stopping on failure (--stop-on-fail).");
+                break;
+            }
+        }
+    }
+
+    let xfail_note = if expected_failures > 0 {
+        format!(" ({} expected failure(s))", expected_failures)
+    } else {
+        String::new()
+    };
+    step_println!(
+        "\nDone: {} passed{}, {} failed ({}ms total).",
+        passed,
+        xfail_note,
+        failed,
+        start.elapsed().as_millis()
+    );
+    if unverified_manual > 0 {
+        step_println!("{} manual step(s) not verified; the case needs manual verification.", unverified_manual);
+    }
+    if !soft_failures.is_empty() {
+        step_println!("Soft assertion failures ({}):", soft_failures.len());
+        for (key, message) in &soft_failures {
+            step_println!("  {}: {}", key, message);
+        }
+    }
+
+    let perf = sampler.map(|s| s.finish()).transpose()?;
+    if let Some(p) = &perf {
+        step_println!(
+            "Perf ({}): mem avg {:.1}MB / max {:.1}MB, cpu avg {:.1}%, {} frames ({:.1}% janky), battery -{}%",
+            p.package,
+            p.memory_mb.avg,
+            p.memory_mb.max,
+            p.cpu_percent.avg,
+            p.frames_rendered,
+            p.janky_percent,
+            p.battery_drop
+        );
+    }
+
+    Ok(PlayRun {
+        failed,
+        unverified_manual,
+        steps: results,
+        perf,
+        fixtures: fixture_results,
+        hooks: Vec::new(),
+        artifacts: artifacts.take(),
+        aborted,
+    })
+}
+
+/// What the operator decided for a manual step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ManualVerdict {
+    Pass,
+    /// With the operator's note, if any.
+    Fail(String),
+    Skip,
+}
+
+/// Show a manual step and read `p`/`f [note]`/`s` until one is given; end
+/// of input counts as skip.
+fn prompt_manual(input: &mut impl std::io::BufRead, out: &mut impl std::io::Write, step: &ScenarioStep) -> Result<ManualVerdict> {
+    writeln!(out, "MANUAL")?;
+    let args = if step.args.is_empty() { String::new() } else { format!(" {}", step.args.join(" ")) };
+    writeln!(out, "    Do:       {}{}", step.action, args)?;
+    if let Some(expected) = &step.label {
+        writeln!(out, "    Expected: {}", expected)?;
+    }
+    loop {
+        write!(out, "    Result? [p]ass / [f]ail [note] / [s]kip: ")?;
+        out.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            writeln!(out)?;
+            return Ok(ManualVerdict::Skip);
+        }
+        let line = line.trim();
+        let (word, note) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match word.to_ascii_lowercase().as_str() {
+            "p" | "pass" => return Ok(ManualVerdict::Pass),
+            "f" | "fail" => {
+                let note = if note.trim().is_empty() { "marked failed by operator" } else { note.trim() };
+                return Ok(ManualVerdict::Fail(note.to_string()));
+            }
+            "s" | "skip" => return Ok(ManualVerdict::Skip),
+            _ => writeln!(out, "    Please answer p, f or s.")?,
+        }
+    }
+}
+
+/// Fail before the first step when a remote run cannot work: the farm or
+/// device is down, or a step needs a local device (assertions backed by
+/// adb, perf sampling, watchdogs).
+fn check_remote_run(id: &str, steps: &[&ScenarioStep], fixtures: &[Fixture], opts: &PlayOptions) -> Result<()> {
+    if opts.perf_package.is_some() || opts.watchdog.is_some() {
+        bail!("--perf and --watchdog-* need a local Android device; they cannot be used with --remote-device");
+    }
+    let all_steps = fixtures.iter().flat_map(|f| f.steps.iter()).chain(steps.iter().copied());
+    for step in all_steps.filter(|s| s.manual != Some(true)) {
+        if crate::platform::Action::parse(&step.action, &step.args)?.is_none() {
+            bail!("Step {} ('{}') cannot run on a remote device", step.key(), step.action);
+        }
+    }
+    let device = crate::remote::RemoteDevice::connect(Some(id))?;
+    let health = device.health()?;
+    println!(
+        "  Remote device: {} ({})",
+        device.id(),
+        [health.platform, health.model].into_iter().flatten().collect::<Vec<_>>().join(", ")
+    );
+    Ok(())
+}
+
+/// Captures an assertion reads, kept running from the step that should
+/// raise the toast or announcement (the one before the assertion) through
+/// the assertion itself. Shared by case and fixture steps.
+#[derive(Default)]
+struct AssertCaptures {
+    /// Toasts vanish within seconds, so they are watched only that long.
+    toasts: Option<crate::android::ToastWatch>,
+    /// Start the toast watch once the current step has read the hierarchy.
+    toasts_after: bool,
+    /// `assert-announced` needs TalkBack speaking. With TalkBack on, a tap
+    /// only moves accessibility focus; the guard restores the previous
+    /// services.
+    talkback: Option<(crate::android::TalkBackGuard, crate::android::AnnouncementCapture)>,
+    /// Turn TalkBack on once the current step, which triggers the
+    /// announcement, has run, so its taps still activate.
+    talkback_after: bool,
+}
+
+impl AssertCaptures {
+    /// Start what `step` or the `next` one asserts on. May turn a
+    /// `tap-text` in `flow_step` into a plain tap, see below.
+    fn before_step(
+        &mut self,
+        step: &ScenarioStep,
+        next: Option<&ScenarioStep>,
+        flow_step: &mut crate::commands::flow::FlowStep,
+        platform: &str,
+        opts: &PlayOptions,
+    ) -> Result<()> {
+        if platform != "android" || opts.dry_run {
+            return Ok(());
+        }
+        if self.talkback.is_none() {
+            if step.action == "assert-announced" {
+                self.start_talkback()?;
+            } else {
+                self.talkback_after = next.is_some_and(|s| s.action == "assert-announced");
+            }
+        }
+
+        // The toast watch holds uiautomator, so a step that dumps the UI
+        // must do that before the watch starts: a tap-text is resolved to
+        // a plain tap first, and other hierarchy reads (or a tap-text that
+        // could not be resolved) start it afterwards, picking up a toast
+        // the step already raised from the screen.
+        let toast_next = next.is_some_and(|s| s.action == "assert-toast");
+        let watch_toasts = self.toasts.is_none()
+            && opts.remote_device.is_none()
+            && (toast_next || step.action == "assert-toast");
+        if watch_toasts && step.action == "tap-text" {
+            if let Some(query) = step.args.first() {
+                // A failed dump leaves the tap-text to do its own lookup.
+                if let Some((x, y)) = crate::android::find_element(query, None).unwrap_or(None) {
+                    flow_step.action = "tap".to_owned();
+                    flow_step.args = vec![x.to_string(), y.to_string()];
+                }
+            }
+        }
+        self.toasts_after = watch_toasts && READS_HIERARCHY.contains(&flow_step.action.as_str());
+        if watch_toasts && !self.toasts_after {
+            self.toasts = Some(crate::android::ToastWatch::start(None)?);
+        }
+        Ok(())
+    }
+
+    /// Start a deferred toast watch, and stop what no later step needs.
+    fn after_step(&mut self, step: &ScenarioStep, next: Option<&ScenarioStep>) -> Result<()> {
+        if std::mem::take(&mut self.toasts_after) {
+            self.toasts = Some(crate::android::ToastWatch::start_after_dump(None)?);
+        }
+        if std::mem::take(&mut self.talkback_after) {
+            self.start_talkback()?;
+        }
+        if step.action == "assert-toast" && next.is_none_or(|s| s.action != "assert-toast") {
+            self.toasts = None;
+        }
+        if step.action == "assert-announced" && next.is_none_or(|s| s.action != "assert-announced") {
+            self.talkback = None;
+        }
+        Ok(())
+    }
+
+    fn start_talkback(&mut self) -> Result<()> {
+        let guard = crate::android::set_talkback(true, None)?;
+        self.talkback = Some((guard, crate::android::AnnouncementCapture::start(None)?));
+        Ok(())
+    }
+}
+
+/// Context for one step of the case (or fixture) `name`.
+fn step_ctx(
+    name: &str,
+    step: &ScenarioStep,
+    platform: &str,
+    opts: &PlayOptions,
+    network: Option<&crate::android::NetworkCapture>,
+    captures: &AssertCaptures,
+    artifacts: &ArtifactLog,
+) -> Result<FlowCtx> {
+    Ok(FlowCtx {
+        platform: platform.to_owned(),
+        remote: opts.remote_device.is_some(),
+        device: opts.remote_device.clone(),
+        simulator: None,
+        companion_path: None,
+        network: network.cloned(),
+        announcements: captures.talkback.as_ref().map(|(_, capture)| capture.clone()),
+        toasts: captures.toasts.clone(),
+        baseline: step.id.as_deref().map(|id| baseline_path(&opts.baselines, name, id, platform)),
+        update_baselines: opts.update_baselines,
+        report_format: report_format(opts)?,
+        screenshot_dir: match (&opts.screenshots, &step.id) {
+            (Some((template, layout)), Some(id)) => Some(layout.path(template, Some(name), Some(id))?),
+            _ => None,
+        },
+        artifacts: artifacts.clone(),
+    })
+}
+
+/// Files a case's steps wrote for the report, so `suite --retention` can
+/// prune them with the case's other artifacts. Shared with the worker
+/// thread of a step that has a timeout.
+#[derive(Debug, Clone, Default)]
+struct ArtifactLog(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+impl ArtifactLog {
+    fn record(&self, path: &Path) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push(path.display().to_string());
+    }
+
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// App of the first `launch` step: with `--strings` on Android 13+ only its
+/// per-app locale is switched, which needs no root.
+fn launched_package(steps: &[ScenarioStep]) -> Option<&str> {
+    steps.iter().find(|s| s.action == "launch").and_then(|s| s.args.first()).map(String::as_str)
+}
+
+/// Run a fixture's steps with the case's step context, stopping at the
+/// first failure that is not soft.
+fn run_fixture(fixture: &mut Fixture, platform: &str, opts: &PlayOptions, artifacts: &ArtifactLog) -> Result<FixtureResult> {
+    for step in fixture.steps.iter_mut() {
+        resolve_step_vars(step, &opts.config)?;
+    }
+    resolve_string_keys(&mut fixture.steps, opts.strings.as_ref())?;
+
+    step_println!("  Fixture '{}' ({} steps)", fixture.name, fixture.steps.len());
+    let start = std::time::Instant::now();
+    let to_run: Vec<&ScenarioStep> = fixture.steps.iter().filter(|s| s.runs_on(platform)).collect();
+    // A fixture's requests are its own; the case starts a capture of its own.
+    let network = if !opts.dry_run && platform == "android" && to_run.iter().any(|s| s.action == "assert-request") {
+        Some(crate::android::NetworkCapture::start(None)?)
+    } else {
+        None
+    };
+    let mut captures = AssertCaptures::default();
+    let mut steps = Vec::with_capacity(to_run.len());
+    let mut error = None;
+    for (i, step) in to_run.iter().enumerate() {
+        if step.delay_before_ms > 0 && !opts.dry_run {
+            let delay = (step.delay_before_ms as f64 / opts.speed) as u64;
+            std::thread::sleep(std::time::Duration::from_millis(delay));
+        }
+        step_print!("    {}: {} {:?} … ", step.key(), step.label.as_deref().unwrap_or(&step.action), shown_args(step));
+        let step_start = std::time::Instant::now();
+        let manual = step.manual.unwrap_or(false);
+        // As in the case itself, a manual verdict is never soft.
+        let soft = step.soft.unwrap_or(false) && !manual;
+
+        let (status, detail) = if opts.dry_run {
+            step_println!("[dry-run]");
+            (CaseStatus::Passed, None)
+        } else if manual {
+            let verdict = if opts.interactive {
+                let stdin = std::io::stdin();
+                prompt_manual(&mut stdin.lock(), &mut std::io::stdout(), step)?
+            } else {
+                step_println!("SKIP  (manual step; needs manual verification)");
+                ManualVerdict::Skip
+            };
+            match verdict {
+                ManualVerdict::Pass => (CaseStatus::Passed, None),
+                ManualVerdict::Fail(note) => (CaseStatus::Failed, Some(format!("Manual check failed: {}", note))),
+                ManualVerdict::Skip => (CaseStatus::Skipped, None),
+            }
+        } else {
+            let mut flow_step = crate::commands::flow::FlowStep {
+                action: step.action.clone(),
+                args: step.args.clone(),
+                on_error: crate::commands::flow::OnError::Stop,
+            };
+            let next = to_run.get(i + 1).copied();
+            captures.before_step(step, next, &mut flow_step, platform, opts)?;
+            let ctx = step_ctx(&fixture.name, step, platform, opts, network.as_ref(), &captures, artifacts)?;
+            let result = match opts.step_timeout {
+                Some(_) => run_with_timeout(&ctx, &flow_step, opts.step_timeout, None),
+                None => run_step(&ctx, &flow_step),
+            };
+            captures.after_step(step, next)?;
+            let pass = StepOutcome::classify(result.is_ok(), step.expect_failure.unwrap_or(false)).is_pass();
+            let detail = match &result {
+                Ok(msg) => msg.clone(),
+                Err(e) => e.to_string(),
+            };
+            let verdict = match (pass, soft) {
+                (true, _) => "OK",
+                (false, true) => "SOFT FAIL",
+                (false, false) => "FAIL",
+            };
+            step_println!("{}  {}", verdict, detail);
+            if pass {
+                (CaseStatus::Passed, None)
+            } else {
+                (CaseStatus::Failed, Some(detail))
+            }
+        };
+
+        let hard_failure = status == CaseStatus::Failed && !soft;
+        if hard_failure {
+            let detail = detail.as_deref().unwrap_or_default();
+            error = Some(format!("fixture '{}' failed at step {}: {}", fixture.name, step.key(), detail));
+        }
+        steps.push(StepResult {
+            key: step.key(),
+            status,
+            duration_ms: step_start.elapsed().as_millis() as u64,
+            error: detail,
+            soft,
+            group: step.group.clone(),
+            watchdog: None,
+            manual,
+            dialogs: Vec::new(),
+        });
+        if hard_failure {
+            break;
+        }
+    }
+    Ok(FixtureResult {
+        name: fixture.name.clone(),
+        status: if error.is_some() { CaseStatus::Failed } else { CaseStatus::Passed },
+        duration_ms: start.elapsed().as_millis() as u64,
+        error,
+        steps,
+    })
+}
+
+/// Position of the step with `id`, with the known ids in the error.
+fn step_position(scenario: &Scenario, id: &str) -> Result<usize> {
+    if let Some(pos) = scenario.steps.iter().position(|s| s.id.as_deref() == Some(id)) {
+        return Ok(pos);
+    }
+    let ids: Vec<&str> = scenario.steps.iter().filter_map(|s| s.id.as_deref()).collect();
+    if ids.is_empty() {
+        bail!("Scenario '{}' has no step ids; set one with `recorder add-step --id`", scenario.name);
+    }
+    bail!("Scenario '{}' has no step '{}' (ids: {})", scenario.name, id, ids.join(", "))
+}
+
+/// Play just the step with `step_id` on the device as it is now. Earlier
+/// steps are not replayed and its `when` condition is ignored.
+fn cmd_run_step(name: &str, platform: &str, step_id: &str, opts: PlayOptions) -> Result<()> {
+    let mut scenario = read_scenario(platform, name)?;
+    let pos = step_position(&scenario, step_id)?;
+    // Check the approval against the file as saved, then drop it so that
+    // clearing `when` below doesn't look like an edit.
+    verify_approval(&scenario)?;
+    scenario.approved_hash = None;
+    scenario.steps[pos].when = None;
+    eprintln!(
+        "Warning: running step {} ('{}') alone; the state earlier steps would set up is not recreated, \
+         so the result may differ from a full run{}.",
+        pos + 1,
+        step_id,
+        if opts.fixtures { "" } else { " (--with-fixtures runs the case's fixtures first)" }
+    );
+    let opts = PlayOptions { from_step: Some(pos + 1), to_step: Some(pos + 1), ..opts };
+    let run = play_loaded(scenario, platform, &opts)?;
+    if run.failed > 0 {
+        bail!("Step '{}' failed", step_id);
+    }
+    Ok(())
+}
+
+/// Minimal context type for replay — mirrors `flow::PlatformCtx` but owned.
+struct FlowCtx {
+    platform: String,
+    /// Forward the common actions to a device-farm device (`device` is its id).
+    remote: bool,
+    device: Option<String>,
+    simulator: Option<String>,
+    companion_path: Option<String>,
+    /// Network capture for `assert-request` (Android only).
+    network: Option<crate::android::NetworkCapture>,
+    /// TalkBack utterances for `assert-announced` (Android only).
+    announcements: Option<crate::android::AnnouncementCapture>,
+    /// Toasts for `assert-toast`, watched from the step before it (Android only).
+    toasts: Option<crate::android::ToastWatch>,
+    /// Baseline image for an `assert-screen` step (the step needs an `id`).
+    baseline: Option<PathBuf>,
+    update_baselines: bool,
+    /// Encoding of the capture a failed `assert-screen` leaves for the report.
+    report_format: crate::screenshot::EncodeFormat,
+    /// Where that capture and the cross-fade go, never the baseline tree.
+    screenshot_dir: Option<PathBuf>,
+    artifacts: ArtifactLog,
+}
+
+/// Actions that dump the UI hierarchy, which fails while a toast watch runs.
+const READS_HIERARCHY: &[&str] = &["tap-text", "assert-element", "assert-text", "assert-tree"];
+
+/// Actions a dialog retry must not replay: typing, the PIN entry and the
+/// network-log check would each run twice.
+const NOT_REPLAYED: &[&str] = &["input", "unlock", "assert-request"];
+
+/// How long `assert-toast` waits by default; a long toast shows for 3.5s.
+const TOAST_TIMEOUT_MS: u64 = 4000;
+
+/// Actions `run_step` knows how to replay.
+const PLAY_ACTIONS: &[&str] = &[
+    "tap", "tap-text", "long-press", "input", "swipe", "key", "home", "back", "wait", "launch", "stop", "assert-request",
+    "assert-notification", "assert-alarm", "assert-announced", "unlock", "assert-screen",
+    "assert-toast", "assert-element", "assert-text", "assert-tree",
+];
+
+fn dialog_handler(enabled: bool, policy: Option<&str>) -> Result<Option<crate::android::DialogHandler>> {
+    if !enabled {
+        return Ok(None);
+    }
+    let policy = policy.map(crate::android::DialogPolicy::parse).transpose()?.unwrap_or_default();
+    Ok(Some(crate::android::DialogHandler { policy }))
+}
+
+/// Answer system dialogs with `handler`; failures only warn, since the
+/// step itself decides the outcome.
+fn clear_dialogs(handler: &crate::android::DialogHandler, device: Option<&str>) -> Vec<crate::android::HandledDialog> {
+    handler.clear(device).unwrap_or_else(|e| {
+        eprintln!("Warning: system dialog check failed: {:#}", e);
+        Vec::new()
+    })
+}
+
+/// Where a failed `assert-screen` leaves its review files when no
+/// `--screenshots` template is given; relative to the output root, if any.
+const DEFAULT_SCREENSHOTS: &str = "screenshots/{run_id}/{case_id}/{step_id}";
+
+/// [`DEFAULT_SCREENSHOTS`] for a `play` or `run-step`, which have no output
+/// root: under the working directory, one directory per invocation.
+fn default_screenshots(platform: &str) -> Result<(PathTemplate, OutputLayout)> {
+    let layout = OutputLayout::new(PathVars::run(&now_iso8601(), platform, "play"), None);
+    Ok((PathTemplate::parse(DEFAULT_SCREENSHOTS)?, layout))
+}
+
+/// Report encoding from the play config (`screenshot.report-format`).
+fn report_format(opts: &PlayOptions) -> Result<crate::screenshot::EncodeFormat> {
+    let encodings = crate::screenshot::CaptureEncodings::from_config(|key| opts.config.get(key).cloned())?;
+    Ok(encodings.format(crate::screenshot::CapturePurpose::Report))
+}
+
+/// Write a baseline → actual cross-fade to `gif`. Captures of a different
+/// size (rotation, another device) get none; the size mismatch is the
+/// finding there.
+fn write_onion_skin(gif: &Path, expected: &image::DynamicImage, actual: &image::DynamicImage) -> Result<bool> {
+    use image::GenericImageView;
+
+    if expected.dimensions() != actual.dimensions() {
+        return Ok(false);
+    }
+    let frames = crate::screenshot::onion_skin(expected, actual, 8)?;
+    fs::write(gif, crate::screenshot::encode_gif(&frames, 150)?).with_context(|| format!("Cannot write {}", gif.display()))?;
+    Ok(true)
+}
+
+/// Write the cross-fade and the actual capture of a failed `assert-screen`
+/// into `dir`, named after the baseline; returns the error-message suffix
+/// that points at them and records them in `artifacts`. Failures only
+/// warn: the step fails either way.
+fn write_screen_review(
+    dir: &Path,
+    baseline: &Path,
+    expected: &image::DynamicImage,
+    actual: &image::DynamicImage,
+    format: crate::screenshot::EncodeFormat,
+    artifacts: &ArtifactLog,
+) -> String {
+    let stem = baseline.file_stem().unwrap_or_default().to_string_lossy();
+    if let Err(e) = fs::create_dir_all(dir) {
+        eprintln!("Warning: cannot create {}: {}", dir.display(), e);
+        return String::new();
+    }
+    let mut review = String::new();
+    let gif = dir.join(format!("{}.onion.gif", stem));
+    match write_onion_skin(&gif, expected, actual) {
+        Ok(true) => {
+            artifacts.record(&gif);
+            review.push_str(&format!("; cross-fade: {}", gif.display()));
+        }
+        Ok(false) => {}
+        Err(e) => eprintln!("Warning: cannot write cross-fade {}: {:#}", gif.display(), e),
+    }
+    let copy = dir.join(format!("{}.actual.{}", stem, format.extension()));
+    let written = crate::screenshot::encode(actual, format).and_then(|data| fs::write(&copy, data).map_err(Into::into));
+    match written {
+        Ok(()) => {
+            artifacts.record(&copy);
+            review.push_str(&format!("; actual: {}", copy.display()));
+        }
+        Err(e) => eprintln!("Warning: cannot write {}: {:#}", copy.display(), e),
+    }
+    review
+}
+
+/// Execute a single FlowStep using flow's public `execute_step`.
+fn run_step(ctx: &FlowCtx, step: &crate::commands::flow::FlowStep) -> Result<String> {
+    use crate::android;
+    use crate::platform::{Action, Platform};
+
+    let platform = ctx.platform.as_str();
+    let device = ctx.device.as_deref();
+    let simulator = ctx.simulator.as_deref();
+    let companion_path = ctx.companion_path.as_deref();
+
+    // The common subset goes through the platform dispatch layer.
+    if let Some(action) = Action::parse(&step.action, &step.args)? {
+        let plat: Platform = if ctx.remote { Platform::Remote } else { platform.parse()? };
+        let target = match plat {
+            Platform::Ios => simulator,
+            Platform::Desktop => companion_path,
+            Platform::Android | Platform::Aurora | Platform::Remote => device,
+        };
+        return Ok(plat.execute(target, &action)?.message);
+    }
+    if ctx.remote {
+        bail!("'{}' cannot run on a remote device", step.action);
+    }
+
+    match step.action.as_str() {
+        "assert-request" => {
+            // assert-request <METHOD|*> <url substring> [status]
+            if step.args.len() < 2 {
+                bail!("assert-request requires 2 args: METHOD URL-SUBSTRING [STATUS]");
+            }
+            let capture = ctx.network.as_ref().context("assert-request is only supported on android")?;
+            let status: Option<u16> = step.args.get(2).map(|s| s.parse()).transpose()?;
+            let requests = capture.captured_requests()?;
+            let (method, url) = (&step.args[0], &step.args[1]);
+            if !requests.iter().any(|r| r.matches(method, url, status)) {
+                let seen: Vec<String> = requests.iter().map(|r| format!("{} {}", r.method, r.url)).collect();
+                bail!(
+                    "No {} request to '{}'{} was made (saw {} request(s){}{})",
+                    method,
+                    url,
+                    status.map(|s| format!(" with status {}", s)).unwrap_or_default(),
+                    seen.len(),
+                    if seen.is_empty() { "" } else { ": " },
+                    seen.join(", ")
+                );
+            }
+            Ok(format!("Saw {} request to '{}'", method, url))
+        }
+        "assert-element" => {
+            // assert-element <query> <check> [value] — hierarchy check, see android::ElementAssertion
+            if step.args.len() < 2 {
+                bail!("assert-element requires 2 args: QUERY CHECK [VALUE]");
+            }
+            if platform != "android" {
+                bail!("assert-element is only supported on android");
+            }
+            let assertion = android::ElementAssertion::parse(&step.args[1], step.args.get(2).map(String::as_str))?;
+            let result = android::assert_element(&step.args[0], &assertion, device)?;
+            if !result.passed {
+                bail!("{}", result.message());
+            }
+            Ok(result.message())
+        }
+        "assert-notification" => {
+            // assert-notification <title or text substring> [package]
+            if step.args.is_empty() {
+                bail!("assert-notification requires 1 arg: TEXT [PACKAGE]");
+            }
+            if platform != "android" {
+                bail!("assert-notification is only supported on android");
+            }
+            let (needle, package) = (&step.args[0], step.args.get(1));
+            let found = android::active_notifications(device)?.into_iter().find(|n| {
+                package.is_none_or(|p| &n.package == p) && (n.title.contains(needle.as_str()) || n.text.contains(needle.as_str()))
+            });
+            match found {
+                Some(n) => Ok(format!("Notification \"{}\" from {}", n.title, n.package)),
+                None => bail!("No notification containing '{}' is posted", needle),
+            }
+        }
+        "assert-alarm" => {
+            // assert-alarm <package> [tag substring]
+            if step.args.is_empty() {
+                bail!("assert-alarm requires 1 arg: PACKAGE [TAG]");
+            }
+            if platform != "android" {
+                bail!("assert-alarm is only supported on android");
+            }
+            let (package, tag) = (&step.args[0], step.args.get(1));
+            let count = android::scheduled_alarms(device)?
+                .iter()
+                .filter(|a| &a.package == package)
+                .filter(|a| tag.is_none_or(|t| a.tag.as_deref().is_some_and(|at| at.contains(t.as_str()))))
+                .count();
+            if count == 0 {
+                bail!("No alarm scheduled by {}{}", package, tag.map(|t| format!(" with tag '{}'", t)).unwrap_or_default());
+            }
+            Ok(format!("{} alarm(s) scheduled by {}", count, package))
+        }
+        "assert-tree" => {
+            // assert-tree [bounds] — compares the accessibility tree with the step's shared tree baseline
+            if platform != "android" {
+                bail!("assert-tree is only supported on android");
+            }
+            let path = tree_baseline_path(ctx.baseline.as_ref().context("assert-tree needs a step id to locate its baseline")?);
+            let snapshot = android::snapshot_tree(device, step.args.first().is_some_and(|a| a == "bounds"))?;
+            if ctx.update_baselines {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir).with_context(|| format!("Cannot create {}", dir.display()))?;
+                }
+                fs::write(&path, serde_json::to_string_pretty(&snapshot)? + "\n")
+                    .with_context(|| format!("Cannot write baseline {}", path.display()))?;
+                return Ok(format!("Tree baseline updated: {}", path.display()));
+            }
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("No tree baseline at {}; run with --update-baselines to create it", path.display()))?;
+            let baseline: android::AccessibilityTree =
+                serde_json::from_str(&text).with_context(|| format!("Invalid tree baseline {}", path.display()))?;
+            let changes = android::compare_tree(&snapshot, &baseline);
+            if !changes.is_empty() {
+                let listed: Vec<String> = changes.iter().take(5).map(|c| c.to_string()).collect();
+                let more = changes.len().saturating_sub(listed.len());
+                bail!(
+                    "Accessibility tree differs from {}: {}{}",
+                    path.display(),
+                    listed.join("; "),
+                    if more > 0 { format!("; and {} more", more) } else { String::new() }
+                );
+            }
+            Ok(format!("Accessibility tree matches {}", path.display()))
+        }
+        "assert-screen" => {
+            // assert-screen [MAX_DIFF_PERCENT] — compares against the step's shared baseline
+            let path = ctx.baseline.as_ref().context("assert-screen needs a step id to locate its baseline")?;
+            let max_diff: f64 = step.args.first().map(|s| s.parse()).transpose()?.unwrap_or(0.5);
+            let backend = crate::screenshot::backend_for(
+                platform,
+                simulator,
+                device,
+                companion_path,
+                crate::screenshot::AndroidCaptureOptions::default(),
+            )?;
+            let actual = backend.capture()?;
+            if ctx.update_baselines {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir).with_context(|| format!("Cannot create {}", dir.display()))?;
+                }
+                // Baselines stay lossless whatever the report format is.
+                let png = crate::screenshot::encode(&actual, crate::screenshot::EncodeFormat::Png)?;
+                fs::write(path, png).with_context(|| format!("Cannot write baseline {}", path.display()))?;
+                return Ok(format!("Baseline updated: {}", path.display()));
+            }
+            if !path.exists() {
+                bail!("No baseline at {}; run with --update-baselines to create it", path.display());
+            }
+            // Compare the lossless capture; the report copy is only for viewing.
+            let comparison = crate::screenshot::compare_to_baseline(path, &actual, 16)?;
+            let (expected, diff) = (comparison.baseline, comparison.diff_fraction * 100.0);
+            let masked = match comparison.masked_pixels {
+                0 => String::new(),
+                n => format!(", {} px masked", n),
+            };
+            if diff > max_diff {
+                let review = match &ctx.screenshot_dir {
+                    Some(dir) => write_screen_review(dir, path, &expected, &actual, ctx.report_format, &ctx.artifacts),
+                    None => String::new(),
+                };
+                bail!("Screen differs from baseline {} by {:.2}% (max {}%{}){}", path.display(), diff, max_diff, masked, review);
+            }
+            Ok(format!("Matches baseline ({:.2}% different{})", diff, masked))
+        }
+        "unlock" => {
+            // unlock [PIN] — usually `unlock ${DEVICE_PIN}` so the PIN stays in config
+            if platform != "android" {
+                bail!("unlock is only supported on android");
+            }
+            let unlocked = android::unlock(step.args.first().map(String::as_str), device)?;
+            Ok(if unlocked { "Unlocked".to_string() } else { "Already unlocked".to_string() })
+        }
+        "assert-toast" => {
+            // assert-toast <text substring> [timeout ms]
+            if step.args.is_empty() {
+                bail!("assert-toast requires 1 arg: TEXT [TIMEOUT_MS]");
+            }
+            let watch = ctx.toasts.as_ref().context("assert-toast is only supported on android")?;
+            let timeout: u64 = step.args.get(1).map(|s| s.parse()).transpose()?.unwrap_or(TOAST_TIMEOUT_MS);
+            let toast = watch.wait_for(&step.args[0], std::time::Duration::from_millis(timeout))?;
+            Ok(format!("Toast \"{}\"", toast))
+        }
+        "assert-text" => {
+            // assert-text <text substring> [timeout ms] — hierarchy first, OCR for custom-rendered UI
+            if step.args.is_empty() {
+                bail!("assert-text requires 1 arg: TEXT [TIMEOUT_MS]");
+            }
+            if platform != "android" {
+                bail!("assert-text is only supported on android");
+            }
+            let timeout: u64 = step.args.get(1).map(|s| s.parse()).transpose()?.unwrap_or(5000);
+            let found = android::wait_for_text(device, &step.args[0], std::time::Duration::from_millis(timeout))?;
+            let (x, y) = found.center();
+            Ok(format!("Text \"{}\" at ({}, {}) via {}", step.args[0], x, y, found.source.as_str()))
+        }
+        "assert-announced" => {
+            // assert-announced <text substring>
+            if step.args.is_empty() {
+                bail!("assert-announced requires 1 arg: TEXT");
+            }
+            let capture = ctx.announcements.as_ref().context("assert-announced is only supported on android")?;
+            let spoken = capture.announcements()?;
+            let needle = &step.args[0];
+            if !spoken.iter().any(|s| s.contains(needle.as_str())) {
+                bail!(
+                    "TalkBack did not announce '{}' (heard {} utterance(s){}{})",
+                    needle,
+                    spoken.len(),
+                    if spoken.is_empty() { "" } else { ": " },
+                    spoken.join(" | ")
+                );
+            }
+            Ok(format!("Announced \"{}\"", needle))
+        }
+        other => bail!("Unsupported action '{}' in recorder play", other),
+    }
+}
+
+/// Start the watchdog for one step; `None` when no app is in the foreground
+/// to watch.
+fn start_watchdog(opts: &WatchdogOptions) -> Result<Option<crate::android::Watchdog>> {
+    let package = match &opts.package {
+        Some(p) => p.clone(),
+        None => match crate::android::foreground_package(None)? {
+            Some(p) => p,
+            None => return Ok(None),
+        },
+    };
+    crate::android::Watchdog::start(&package, opts.limits, WATCHDOG_INTERVAL_MS, None).map(Some)
+}
+
+const WATCHDOG_INTERVAL_MS: u64 = 1000;
+
+/// Run a step on a worker thread, giving up when `timeout_ms` passes or the
+/// watchdog reports a breached limit. The abandoned worker is left to finish
+/// on its own.
+fn run_with_timeout(
+    ctx: &FlowCtx,
+    step: &crate::commands::flow::FlowStep,
+    timeout_ms: Option<u64>,
+    watchdog: Option<&crate::android::Watchdog>,
+) -> Result<String> {
+    use std::sync::mpsc;
+
+    // Clone data needed for the worker thread.
+    let ctx_owned = FlowCtx {
+        platform: ctx.platform.clone(),
+        remote: ctx.remote,
+        device: ctx.device.clone(),
+        simulator: ctx.simulator.clone(),
+        companion_path: ctx.companion_path.clone(),
+        network: ctx.network.clone(),
+        announcements: ctx.announcements.clone(),
+        toasts: ctx.toasts.clone(),
+        baseline: ctx.baseline.clone(),
+        update_baselines: ctx.update_baselines,
+        report_format: ctx.report_format,
+        screenshot_dir: ctx.screenshot_dir.clone(),
+        artifacts: ctx.artifacts.clone(),
+    };
+    let step_owned = crate::commands::flow::FlowStep {
+        action: step.action.clone(),
+        args: step.args.clone(),
+        on_error: step.on_error,
+    };
+
+    let (tx, rx) = mpsc::channel::<Result<String>>();
+    std::thread::spawn(move || {
+        let result = run_step(&ctx_owned, &step_owned);
+        let _ = tx.send(result);
+    });
+
+    let deadline = timeout_ms.map(|ms| std::time::Instant::now() + std::time::Duration::from_millis(ms));
+    loop {
+        match rx.recv_timeout(std::time::Duration::from_millis(200)) {
+            Ok(result) => return result,
+            Err(mpsc::RecvTimeoutError::Disconnected) => bail!("Step worker stopped without a result"),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
+        if let Some(v) = watchdog.and_then(crate::android::Watchdog::violation) {
+            bail!("Watchdog: {}", v);
+        }
+        if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+            bail!("Step timed out after {}ms", timeout_ms.unwrap_or_default());
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// recorder suite
+// ---------------------------------------------------------------------------
+
+/// What the suite runner does after a case fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureMode {
+    /// Stop after the first failed case; remaining cases are reported as skipped.
+    FailFast,
+    /// Run every case and report all failures (the nightly default).
+    ContinueOnFailure,
+}
+
+impl FailureMode {
+    fn parse(s: &str) -> Result<Self> {
+        match s {
+            "fail-fast" => Ok(FailureMode::FailFast),
+            "continue" | "continue-on-failure" => Ok(FailureMode::ContinueOnFailure),
+            other => bail!("Unknown failure mode '{}'. Supported: fail-fast, continue", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseStatus {
+    Passed,
+    Failed,
+    Skipped,
+    /// Never started: the suite ran out of time (`--suite-timeout`).
+    #[serde(rename = "not-executed")]
+    NotExecuted,
+}
+
+impl CaseStatus {
+    /// Passed or failed, so its duration and outcome mean something.
+    fn ran(self) -> bool {
+        matches!(self, CaseStatus::Passed | CaseStatus::Failed)
+    }
+}
+
+/// Outcome of one suite case. Runner-internal details (steps, fixtures,
+/// artifacts, …) are filled in by the recorder's own suite.
+#[derive(Debug, Serialize)]
+pub struct CaseResult {
+    pub name: String,
+    pub status: CaseStatus,
+    #[serde(rename = "durationMs")]
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Why a skipped case did not run.
+    #[serde(rename = "skipReason", skip_serializing_if = "Option::is_none")]
+    pub skip_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    severity: Option<Severity>,
+    /// Strings-catalog locale the case ran under.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    locale: Option<String>,
+    /// `--matrix` values of this run; the name is then labelled with them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matrix: Option<std::collections::BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    perf: Option<crate::android::PerfSample>,
+    /// Precondition fixtures, run before `steps`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fixtures: Vec<FixtureResult>,
+    /// Host shell hooks run around the case.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hooks: Vec<HookResult>,
+    /// Some manual steps were skipped, so a person still has to check them.
+    #[serde(rename = "needsManualVerification", skip_serializing_if = "std::ops::Not::not")]
+    needs_manual_verification: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    steps: Vec<StepResult>,
+    /// Files attached to the case: its screen recording, and diagnostics
+    /// bundles when it failed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    artifacts: Vec<String>,
+    /// Failed earlier attempts when the case was rerun (`--rerun-failed`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attempts: Vec<CaseAttempt>,
+    /// Passed on a rerun after failing.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    flaky: bool,
+}
+
+impl CaseResult {
+    /// A case with just a name and status; the duration is zero.
+    pub fn new(name: &str, status: CaseStatus) -> Self {
+        CaseResult {
+            name: name.to_owned(),
+            status,
+            duration_ms: 0,
+            error: None,
+            skip_reason: None,
+            severity: None,
+            locale: None,
+            matrix: None,
+            perf: None,
+            fixtures: Vec::new(),
+            hooks: Vec::new(),
+            needs_manual_verification: false,
+            steps: Vec::new(),
+            artifacts: Vec::new(),
+            attempts: Vec::new(),
+            flaky: false,
+        }
+    }
+
+    /// Skipped because a case it depends on failed or did not run.
+    fn skipped_for_dependency(&self) -> bool {
+        self.status == CaseStatus::Skipped && self.skip_reason.as_deref().is_some_and(|r| r.starts_with(DEPENDENCY_SKIP))
+    }
+}
+
+/// A failed attempt at a case that was rerun.
+#[derive(Debug, Clone, Serialize)]
+struct CaseAttempt {
+    #[serde(rename = "durationMs")]
+    duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Result of one precondition fixture run before a case.
+#[derive(Debug, Clone, Serialize)]
+struct FixtureResult {
+    name: String,
+    status: CaseStatus,
+    #[serde(rename = "durationMs")]
+    duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    steps: Vec<StepResult>,
+}
+
+/// Result of one step in a case, keyed by [`ScenarioStep::key`].
+#[derive(Debug, Clone, Serialize)]
+struct StepResult {
+    key: String,
+    status: CaseStatus,
+    #[serde(rename = "durationMs")]
+    duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Failed as a soft assertion (the case kept running).
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    soft: bool,
+    /// The step's section, for grouped rendering.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    /// CPU/memory peaks when the run had a watchdog.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    watchdog: Option<crate::android::WatchdogReport>,
+    /// Judged by a person; `skipped` here means nobody did.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    manual: bool,
+    /// System dialogs answered around the step (`--dismiss-dialogs`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    dialogs: Vec<crate::android::HandledDialog>,
+}
+
+#[derive(Debug, Serialize)]
+struct SuiteReport {
+    platform: String,
+    #[serde(rename = "startedAt")]
+    started_at: String,
+    #[serde(rename = "failureMode")]
+    failure_mode: FailureMode,
+    cases: Vec<CaseResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    teardown: Option<CaseResult>,
+    /// Suite-level `--before`/`--after` hooks.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hooks: Vec<HookResult>,
+    passed: usize,
+    /// Of `passed`, the cases that only passed on a rerun.
+    flaky: usize,
+    failed: usize,
+    skipped: usize,
+    /// Cases cut by `--suite-timeout` before they started.
+    #[serde(rename = "notExecuted")]
+    not_executed: usize,
+}
+
+struct SuiteOptions {
+    failure_mode: FailureMode,
+    /// Rerun failed cases up to this many times after the others.
+    rerun_failed: u32,
+    /// Scenario played after all cases, whatever their outcome.
+    teardown: Option<String>,
+    /// Where to write the JSON report.
+    report: Option<String>,
+    /// Only run scenarios changed since this git ref.
+    changed_since: Option<String>,
+    /// Directory that keeps a timestamped copy of every report, for `recorder trend`.
+    history: Option<String>,
+    /// Root that relative output paths go under and absolute ones must stay in.
+    output_root: Option<PathBuf>,
+    /// Value of `{suite}` in output path templates.
+    suite_name: String,
+    /// Directory template for failed `assert-screen` captures.
+    screenshots: Option<String>,
+    /// Configurations every case runs under; empty runs each case once.
+    matrix: Vec<MatrixRun>,
+    /// Wait this long for a lost Android device before each case (0 = don't check).
+    device_timeout_secs: u64,
+    /// No case starts once the suite has run this long.
+    suite_timeout: Option<std::time::Duration>,
+    /// How long past `suite_timeout` the case in flight may run.
+    timeout_grace: std::time::Duration,
+    /// Run only this slice of the cases: (zero-based index, shard count).
+    shard: Option<(usize, usize)>,
+    /// Run a severity-weighted random subset that fits this budget.
+    sample_budget: Option<std::time::Duration>,
+    /// Seed for the sample (default: derived from the current git commit).
+    seed: Option<u64>,
+    /// Directory for a diagnostics zip per failed Android case.
+    diagnostics: Option<String>,
+    /// Also take a full `adb bugreport` for each failed case (slow).
+    bugreport: bool,
+    /// Directory for a screen recording per iOS case.
+    video: Option<String>,
+    /// No per-case progress lines.
+    quiet: bool,
+    /// Which case artifacts to keep once the run is over.
+    retention: RetentionPolicy,
+    /// Host shell hooks around the whole suite (`--allow-hooks` only).
+    before: Vec<String>,
+    after: Vec<String>,
+}
+
+/// Which case artifacts survive a suite run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RetentionPolicy {
+    All,
+    /// Delete the artifacts of cases that did not fail.
+    FailuresOnly,
+    /// Keep what the newest N reports in `--history` reference.
+    LastRuns(usize),
+}
+
+impl RetentionPolicy {
+    fn parse(s: &str) -> Result<Self> {
+        match s {
+            "keep-all" => Ok(RetentionPolicy::All),
+            "keep-failures-only" => Ok(RetentionPolicy::FailuresOnly),
+            other => match other.strip_prefix("keep-last-").map(str::parse::<usize>) {
+                Some(Ok(n)) if n > 0 => Ok(RetentionPolicy::LastRuns(n)),
+                _ => bail!(
+                    "Unknown retention policy '{}'. Supported: keep-all, keep-failures-only, keep-last-N (N >= 1)",
+                    other
+                ),
+            },
+        }
+    }
+}
+
+/// Delete the artifacts of cases that did not fail and drop them from the
+/// results; returns how many files were removed.
+fn prune_passed_artifacts<'a>(cases: impl IntoIterator<Item = &'a mut CaseResult>) -> usize {
+    let mut removed = 0;
+    for case in cases {
+        // A flaky pass keeps the evidence of its failed attempts.
+        if case.status == CaseStatus::Failed || case.flaky {
+            continue;
+        }
+        for artifact in case.artifacts.drain(..) {
+            match fs::remove_file(&artifact) {
+                Ok(()) => removed += 1,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => eprintln!("Warning: cannot remove {}: {}", artifact, e),
+            }
+        }
+    }
+    removed
+}
+
+/// Artifacts referenced by `runs` (oldest first) before the newest `keep`,
+/// minus any a newer run still references.
+fn expired_artifacts(runs: &[HistoryRun], keep: usize) -> Vec<String> {
+    let split = runs.len().saturating_sub(keep);
+    let kept: std::collections::HashSet<&str> = runs[split..]
+        .iter()
+        .flat_map(|r| r.cases.iter().flat_map(|c| c.artifacts.iter().map(String::as_str)))
+        .collect();
+    let mut expired: Vec<String> = runs[..split]
+        .iter()
+        .flat_map(|r| r.cases.iter().flat_map(|c| c.artifacts.iter()))
+        .filter(|a| !kept.contains(a.as_str()))
+        .cloned()
+        .collect();
+    expired.sort();
+    expired.dedup();
+    expired
+}
+
+/// Create this run's report file in the history directory, named after its
+/// start time to the millisecond. Runs started in the same millisecond
+/// (parallel shards) get a counter rather than overwriting each other.
+fn create_history_file(dir: &Path, started_at: &str, millis: u64, platform: &str) -> Result<(PathBuf, fs::File)> {
+    let stamp = format!("{}.{:03}Z", started_at.trim_end_matches('Z').replace(':', ""), millis);
+    for n in 1.. {
+        let suffix = if n == 1 { String::new() } else { format!("-{}", n) };
+        let file = dir.join(format!("{}-{}{}.json", stamp, platform, suffix));
+        match fs::OpenOptions::new().write(true).create_new(true).open(&file) {
+            Ok(out) => return Ok((file, out)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).with_context(|| format!("Cannot create {}", file.display())),
+        }
+    }
+    unreachable!("the counter is unbounded")
+}
+
+/// Delete the artifacts of runs in `history_dir` older than the newest
+/// `keep`; returns how many files were removed.
+fn prune_history_artifacts(history_dir: &Path, keep: usize) -> Result<usize> {
+    let mut removed = 0;
+    for artifact in expired_artifacts(&load_history(history_dir)?, keep) {
+        match fs::remove_file(&artifact) {
+            Ok(()) => removed += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!("Warning: cannot remove {}: {}", artifact, e),
+        }
+    }
+    Ok(removed)
+}
+
+/// Capture diagnostics for a failed case into `dir`; returns the files written.
+///
+/// Failures here only warn: the case already failed, and a missing bundle
+/// must not hide that.
+fn collect_failure_artifacts(template: &PathTemplate, layout: &OutputLayout, case: &str, attempt: u32, bugreport: bool) -> Vec<String> {
+    let mut written = Vec::new();
+    let dir = match layout.path(template, Some(case), None) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("Warning: no diagnostics dir for '{}': {:#}", case, e);
+            return written;
+        }
+    };
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("Warning: cannot create diagnostics dir {}: {}", dir.display(), e);
+        return written;
+    }
+    let stem = attempt_stem(case, attempt);
+    let zip = dir.join(format!("{}.zip", stem));
+    match crate::android::capture_diagnostics(None, &zip) {
+        Ok(()) => written.push(zip.display().to_string()),
+        Err(e) => eprintln!("Warning: diagnostics for '{}' failed: {:#}", case, e),
+    }
+    if bugreport {
+        let path = dir.join(format!("{}-bugreport.zip", stem)).display().to_string();
+        println!("Capturing bugreport for '{}' (this takes a while)…", case);
+        match crate::android::capture_bugreport(None, &path) {
+            Ok(()) => written.push(path),
+            Err(e) => eprintln!("Warning: bugreport for '{}' failed: {:#}", case, e),
+        }
+    }
+    written
+}
+
+/// Start recording an iOS case to `<dir>/<case>.mp4` (`<case>.attempt2.mp4`
+/// and so on for reruns). A recording that
+/// cannot start only warns, so the case still runs.
+fn start_case_recording(template: &PathTemplate, layout: &OutputLayout, case: &str, attempt: u32) -> Option<crate::ios::RecordingHandle> {
+    let dir = layout.path(template, Some(case), None).map_err(|e| eprintln!("Warning: no video dir for '{}': {:#}", case, e)).ok()?;
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("Warning: cannot create video dir {}: {}", dir.display(), e);
+        return None;
+    }
+    crate::ios::record_video(None, &dir.join(format!("{}.mp4", attempt_stem(case, attempt))))
+        .map_err(|e| eprintln!("Warning: cannot record '{}': {:#}", case, e))
+        .ok()
+}
+
+/// One `--matrix` configuration: its values, which cases see as
+/// `${config.<key>}`, and the strings table for its `locale`, if any.
+#[derive(Debug, Clone)]
+struct MatrixRun {
+    values: std::collections::BTreeMap<String, String>,
+    strings: Option<StringTable>,
+}
+
+impl MatrixRun {
+    fn play_options(&self, base: &PlayOptions) -> PlayOptions {
+        let mut opts = base.clone();
+        opts.config.extend(self.values.iter().map(|(k, v)| (k.clone(), v.clone())));
+        if self.strings.is_some() {
+            opts.strings = self.strings.clone();
+        }
+        opts
+    }
+}
+
+/// Combinations above this count get a warning before the run.
+const MATRIX_WARN_AT: usize = 8;
+
+/// Every combination of `--matrix KEY=V1,V2` values, keys in name order and
+/// values in the order given. More than `limit` is an error. With a strings
+/// catalog, a `locale` key also picks each run's strings table.
+fn matrix_runs(specs: &[String], limit: usize, strings: Option<&str>) -> Result<Vec<MatrixRun>> {
+    let mut axes: std::collections::BTreeMap<String, Vec<String>> = std::collections::BTreeMap::new();
+    for spec in specs {
+        let (key, values) = spec
+            .split_once('=')
+            .with_context(|| format!("--matrix expects KEY=V1,V2,..., got '{}'", spec))?;
+        let values: Vec<String> = values.split(',').map(|v| v.trim().to_owned()).filter(|v| !v.is_empty()).collect();
+        let key = key.trim();
+        if key.is_empty() || values.is_empty() {
+            bail!("--matrix expects KEY=V1,V2,..., got '{}'", spec);
+        }
+        if axes.insert(key.to_owned(), values).is_some() {
+            bail!("--matrix key '{}' is given twice", key);
+        }
+    }
+    if axes.is_empty() {
+        return Ok(Vec::new());
+    }
+    let total = axes.values().try_fold(1usize, |n, v| n.checked_mul(v.len())).unwrap_or(usize::MAX);
+    if total > limit {
+        bail!("--matrix expands to {} combinations, over the limit of {} (raise --matrix-limit or drop values)", total, limit);
+    }
+    if total > MATRIX_WARN_AT {
+        eprintln!("Warning: --matrix expands to {} combinations; every case runs {} times", total, total);
+    }
+    let mut combos = vec![std::collections::BTreeMap::new()];
+    for (key, values) in &axes {
+        combos = combos
+            .into_iter()
+            .flat_map(|combo: std::collections::BTreeMap<String, String>| {
+                values.iter().map(move |v| {
+                    let mut combo = combo.clone();
+                    combo.insert(key.clone(), v.clone());
+                    combo
+                })
+            })
+            .collect();
+    }
+    combos
+        .into_iter()
+        .map(|values| {
+            let strings = match (strings, values.get("locale")) {
+                (Some(path), Some(locale)) => Some(load_string_table(path, Some(locale))?),
+                _ => None,
+            };
+            Ok(MatrixRun { values, strings })
+        })
+        .collect()
+}
+
+/// `login[locale=ru,theme=dark]`: a case name with its configuration.
+fn matrix_label(name: &str, values: Option<&std::collections::BTreeMap<String, String>>) -> String {
+    match values {
+        Some(values) => {
+            let parts: Vec<String> = values.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            format!("{}[{}]", name, parts.join(","))
+        }
+        None => name.to_owned(),
+    }
+}
+
+/// A suite's output locations with their templates expanded: run-wide
+/// paths directly, per-case and per-step ones as a template to expand later.
+struct SuiteOutputs {
+    report: Option<PathBuf>,
+    history: Option<PathBuf>,
+    diagnostics: Option<(PathTemplate, OutputLayout)>,
+    video: Option<(PathTemplate, OutputLayout)>,
+    screenshots: Option<(PathTemplate, OutputLayout)>,
+}
+
+/// Parse the suite's output templates (see [`PathTemplate`]) and check that
+/// each only uses the tokens known where it is expanded.
+fn suite_outputs(suite_opts: &SuiteOptions, started_at: &str, platform: &str) -> Result<SuiteOutputs> {
+    let layout = OutputLayout::new(PathVars::run(started_at, platform, &suite_opts.suite_name), suite_opts.output_root.clone());
+    let run_path = |template: &Option<String>| -> Result<Option<PathBuf>> {
+        template.as_deref().map(|t| layout.path(&PathTemplate::parse(t)?, None, None)).transpose()
+    };
+    let later = |template: &Option<String>, step: Option<&str>| -> Result<Option<(PathTemplate, OutputLayout)>> {
+        let Some(template) = template.as_deref() else { return Ok(None) };
+        let template = PathTemplate::parse(template)?;
+        layout.path(&template, Some("case"), step)?;
+        Ok(Some((template, layout.clone())))
+    };
+    // Every run must find the earlier ones for trend, retention and sharding.
+    if let Some(history) = &suite_opts.history {
+        if PathTemplate::parse(history)?.varies_per_run() {
+            bail!("--history '{}' may not use {{date}}, {{time}} or {{run_id}}: runs must share one directory", history);
+        }
+    }
+    Ok(SuiteOutputs {
+        report: run_path(&suite_opts.report)?,
+        history: run_path(&suite_opts.history)?,
+        diagnostics: later(&suite_opts.diagnostics, None)?,
+        video: later(&suite_opts.video, None)?,
+        screenshots: later(&suite_opts.screenshots.clone().or_else(|| Some(DEFAULT_SCREENSHOTS.to_string())), Some("step"))?,
+    })
+}
+
+/// Parse `--shard i/N` (1-based, as CI node indexes usually are) into a
+/// zero-based index and the shard count.
+fn parse_shard(spec: &str) -> Result<(usize, usize)> {
+    let parsed = spec
+        .split_once('/')
+        .and_then(|(i, n)| Some((i.trim().parse::<usize>().ok()?, n.trim().parse::<usize>().ok()?)));
+    match parsed {
+        Some((i, n)) if n > 0 && (1..=n).contains(&i) => Ok((i - 1, n)),
+        _ => bail!("Invalid shard '{}': expected i/N with 1 <= i <= N, e.g. 2/4", spec),
+    }
+}
+
+/// Cases for shard `shard_index` (zero-based) of `total_shards`.
+///
+/// Ids are sorted and dealt round-robin, so every agent computes the same
+/// split from the same case list: shards are disjoint and together cover
+/// every id exactly once.
+pub fn shard(ids: &[String], total_shards: usize, shard_index: usize) -> Vec<String> {
+    let mut sorted: Vec<&String> = ids.iter().collect();
+    sorted.sort();
+    sorted.dedup();
+    sorted
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| total_shards > 0 && i % total_shards == shard_index)
+        .map(|(_, id)| id.clone())
+        .collect()
+}
+
+/// Like [`shard`], but balances estimated run time: longest cases first,
+/// each onto the shard with the least work so far. Cases without an
+/// estimate count as the average of the known ones.
+fn shard_weighted(ids: &[String], durations_ms: &HashMap<String, u64>, total_shards: usize, shard_index: usize) -> Vec<String> {
+    let known: Vec<u64> = ids.iter().filter_map(|id| durations_ms.get(id).copied()).collect();
+    let fallback = if known.is_empty() { 1 } else { known.iter().sum::<u64>() / known.len() as u64 };
+    let mut weighted: Vec<(u64, &String)> =
+        ids.iter().map(|id| (durations_ms.get(id).copied().unwrap_or(fallback).max(1), id)).collect();
+    weighted.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+    weighted.dedup_by(|a, b| a.1 == b.1);
+
+    let mut load = vec![0u64; total_shards.max(1)];
+    let mut mine = Vec::new();
+    for (weight, id) in weighted {
+        // Ties go to the lowest index so the split is deterministic.
+        let target = (0..load.len()).min_by_key(|&s| (load[s], s)).unwrap_or(0);
+        load[target] += weight;
+        if target == shard_index {
+            mine.push(id.clone());
+        }
+    }
+    mine.sort();
+    mine
+}
+
+/// Most recent duration of every case in the history directory.
+fn latest_durations(dir: &Path) -> Result<HashMap<String, u64>> {
+    let mut durations = HashMap::new();
+    for run in load_history(dir)? {
+        for case in run.cases.into_iter().filter(|c| c.status.ran()) {
+            durations.insert(case.name, case.duration_ms);
+        }
+    }
+    Ok(durations)
+}
+
+/// Estimated run time of a case in seconds: its own
+/// `estimatedDurationSecs`, else its last duration in `durations_ms`, else
+/// two seconds per step plus the recorded delays.
+fn estimated_secs(scenario: &Scenario, durations_ms: &HashMap<String, u64>) -> u64 {
+    if let Some(secs) = scenario.estimated_duration_secs {
+        return secs;
+    }
+    if let Some(ms) = durations_ms.get(&scenario.name) {
+        return ms.div_ceil(1000);
+    }
+    let delays: u64 = scenario.steps.iter().map(|s| s.delay_before_ms).sum();
+    (scenario.steps.len() as u64 * 2 + delays / 1000).max(1)
+}
+
+/// Relative chance of a case being drawn; unset severity ranks as major.
+fn sample_weight(severity: Option<Severity>) -> f64 {
+    match severity.unwrap_or(Severity::Major) {
+        Severity::Blocker => 16.0,
+        Severity::Critical => 8.0,
+        Severity::Major => 4.0,
+        Severity::Minor => 2.0,
+        Severity::Trivial => 1.0,
+    }
+}
+
+/// SplitMix64: small, seedable and stable across builds, which is all a
+/// reproducible sample needs.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in (0, 1].
+    fn next_f64(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Draw cases that roughly fill `budget`, favouring higher severities.
+///
+/// Cases are put in a weighted random order (each gets the key `u^(1/w)`)
+/// and taken greedily while they still fit. The same `scenarios`, history
+/// and `seed` always give the same sample. Returns the picked names in input
+/// order with their estimates in seconds.
+fn sample_suite(
+    scenarios: &[&Scenario],
+    durations_ms: &HashMap<String, u64>,
+    budget: std::time::Duration,
+    seed: u64,
+) -> Vec<(String, u64)> {
+    let mut rng = SplitMix64(seed);
+    let mut keyed: Vec<(f64, usize)> = scenarios
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (rng.next_f64().powf(1.0 / sample_weight(s.severity)), i))
+        .collect();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let mut left = budget.as_secs();
+    let mut picked = Vec::new();
+    for (_, i) in keyed {
+        let secs = estimated_secs(scenarios[i], durations_ms);
+        if secs <= left {
+            left -= secs;
+            picked.push(i);
+        }
+    }
+    picked.sort();
+    picked.into_iter().map(|i| (scenarios[i].name.clone(), estimated_secs(scenarios[i], durations_ms))).collect()
+}
+
+/// Default sample seed: the commit checked out in `dir`, so reruns of one
+/// commit draw the same cases and the next commit draws a fresh set.
+/// `None` when `dir` is not in a git repository (or git is missing).
+fn commit_seed(dir: &Path) -> Option<u64> {
+    let output = std::process::Command::new("git").arg("-C").arg(dir).args(["rev-parse", "HEAD"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let sha = String::from_utf8_lossy(&output.stdout);
+    sha.get(..16).and_then(|hex| u64::from_str_radix(hex, 16).ok())
+}
+
+/// Names of scenarios under `dir` that were added, modified or renamed since
+/// `base_ref`, including files not yet committed.
+///
+/// `dir` must be inside a git work tree (e.g. a repo-tracked copy of the
+/// scenarios directory). Deleted scenarios are ignored.
+fn changed_cases(dir: &Path, base_ref: &str) -> Result<Vec<String>> {
+    if base_ref.is_empty() || base_ref.starts_with('-') {
+        bail!("Invalid git ref '{}'", base_ref);
+    }
+    let git = |args: &[&str]| -> Result<String> {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .context("Failed to run git")?;
+        if !output.status.success() {
+            bail!(
+                "git {} failed in {}: {}",
+                args.join(" "),
+                dir.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+
+    // `-z` keeps paths with spaces or non-ASCII characters unquoted.
+    let diff = git(&["diff", "--name-status", "-z", "-M", "--relative", base_ref, "--", "."])?;
+    let untracked = git(&["ls-files", "-z", "--others", "--exclude-standard", "--", "."])?;
+
+    let mut paths = parse_name_status(&diff);
+    paths.extend(untracked.split('\0').filter(|p| !p.is_empty()).map(str::to_owned));
+    paths.sort();
+    paths.dedup();
+
+    let defaults = load_defaults(dir)?;
+    let mut names = Vec::new();
+    let is_scenario = |p: &&String| p.ends_with(".json") && !p.ends_with(DEFAULTS_FILE);
+    for path in paths.iter().filter(is_scenario) {
+        let full = dir.join(path);
+        let text = fs::read_to_string(&full)
+            .with_context(|| format!("Cannot read changed scenario {}", full.display()))?;
+        let scenario = parse_scenario(&text, &defaults)
+            .with_context(|| format!("Invalid scenario in {}", full.display()))?;
+        names.push(scenario.name);
+    }
+    Ok(names)
+}
+
+/// Extract current paths from `git diff --name-status -z -M` output, where
+/// every status and path ends with a NUL.
+///
+/// Renames (`R100\0old\0new\0`) and copies yield the new path; deletions
+/// are dropped.
+fn parse_name_status(out: &str) -> Vec<String> {
+    let mut fields = out.split('\0').filter(|f| !f.is_empty());
+    let mut paths = Vec::new();
+    while let Some(status) = fields.next() {
+        match status.chars().next() {
+            Some('D') => {
+                fields.next();
+            }
+            Some('R' | 'C') => paths.extend(fields.nth(1).map(str::to_owned)),
+            _ => paths.extend(fields.next().map(str::to_owned)),
+        }
+    }
+    paths
+}
+
+fn play_case(name: &str, platform: &str, opts: &PlayOptions) -> CaseResult {
+    let start = std::time::Instant::now();
+    let (status, error, run) = match play_scenario(name, platform, opts) {
+        Ok(run) if run.aborted => {
+            (CaseStatus::Failed, Some("aborted: ran past the suite timeout and its grace period".into()), Some(run))
+        }
+        Ok(run) if run.failed == 0 => (CaseStatus::Passed, None, Some(run)),
+        Ok(run) => {
+            let hook = run.hooks.iter().find(|h| h.phase == HookPhase::Before && !h.passed()).map(HookResult::error);
+            let setup = hook.or_else(|| run.fixtures.iter().find_map(|f| f.error.clone()));
+            let error = setup.unwrap_or_else(|| format!("{} step(s) failed", run.failed));
+            (CaseStatus::Failed, Some(error), Some(run))
+        }
+        Err(e) => (CaseStatus::Failed, Some(format!("{:#}", e)), None),
+    };
+    let (perf, steps, fixtures, hooks, unverified, artifacts) = match run {
+        Some(run) => (run.perf, run.steps, run.fixtures, run.hooks, run.unverified_manual, run.artifacts),
+        None => (None, Vec::new(), Vec::new(), Vec::new(), 0, Vec::new()),
+    };
+    CaseResult {
+        name: name.to_owned(),
+        status,
+        duration_ms: start.elapsed().as_millis() as u64,
+        error,
+        skip_reason: None,
+        severity: None,
+        locale: opts.strings.as_ref().map(|t| t.locale.clone()),
+        matrix: None,
+        perf,
+        fixtures,
+        hooks,
+        needs_manual_verification: unverified > 0,
+        steps,
+        artifacts,
+        attempts: Vec::new(),
+        flaky: false,
+    }
+}
+
+/// Failed-case counts per severity, most severe first; cases without a
+/// severity are counted as "unset".
+fn failures_by_severity(cases: &[CaseResult]) -> Vec<(&'static str, usize)> {
+    let mut counts: std::collections::BTreeMap<Option<Severity>, usize> = Default::default();
+    for case in cases.iter().filter(|c| c.status == CaseStatus::Failed) {
+        *counts.entry(case.severity).or_default() += 1;
+    }
+    // `None` sorts first in a BTreeMap; move it to the end.
+    let mut out: Vec<_> = counts
+        .iter()
+        .filter_map(|(s, n)| s.map(|s| (s.as_str(), *n)))
+        .collect();
+    if let Some(n) = counts.get(&None) {
+        out.push(("unset", *n));
+    }
+    out
+}
+
+/// Play cases in order under `failure_mode`, then always play `teardown`.
+/// Checks scenario requirements against the suite's device, querying it once.
+struct RequirementGate {
+    platform: String,
+    caps: Option<Result<crate::android::DeviceCapabilities, String>>,
+}
+
+impl RequirementGate {
+    fn new(platform: &str) -> Self {
+        Self { platform: platform.to_owned(), caps: None }
+    }
+
+    /// `Some(reason)` when the case should be skipped. Requirements that
+    /// cannot be checked (other platforms, device query failing) do not skip.
+    fn skip_reason(&mut self, req: &DeviceRequirements) -> Option<String> {
+        if self.platform != "android" {
+            eprintln!("Warning: device requirements are only checked on android");
+            return None;
+        }
+        let caps = self
+            .caps
+            .get_or_insert_with(|| crate::android::device_capabilities(None).map_err(|e| format!("{:#}", e)));
+        match caps {
+            Ok(caps) => {
+                let unmet = req.unmet(caps);
+                (!unmet.is_empty()).then(|| format!("requirements not met: {}", unmet.join(", ")))
+            }
+            Err(e) => {
+                eprintln!("Warning: cannot check device requirements: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Told about each suite case as it starts and finishes (not the teardown).
+/// `index` is zero-based; `outcome` reads like `pass (1.2s)`, `fail (3.0s)`
+/// or `skip (reason)`.
+pub trait ProgressReporter {
+    fn case_started(&mut self, index: usize, total: usize, name: &str);
+    fn case_finished(&mut self, index: usize, total: usize, name: &str, outcome: &str);
+}
+
+/// A case's outcome for [`ProgressReporter::case_finished`].
+fn progress_outcome(case: &CaseResult) -> String {
+    match case.status {
+        CaseStatus::Passed => format!("pass ({:.1}s)", case.duration_ms as f64 / 1000.0),
+        CaseStatus::Failed => format!("fail ({:.1}s)", case.duration_ms as f64 / 1000.0),
+        CaseStatus::Skipped => format!("skip ({})", case.skip_reason.as_deref().unwrap_or("skipped")),
+        CaseStatus::NotExecuted => "not executed (suite timeout)".to_owned(),
+    }
+}
+
+/// One line per finished case, for logs and CI.
+pub struct LineProgress<W: Write> {
+    out: W,
+}
+
+impl<W: Write> LineProgress<W> {
+    pub fn new(out: W) -> Self {
+        LineProgress { out }
+    }
+}
+
+impl<W: Write> ProgressReporter for LineProgress<W> {
+    fn case_started(&mut self, _: usize, _: usize, _: &str) {}
+
+    fn case_finished(&mut self, index: usize, total: usize, name: &str, outcome: &str) {
+        let _ = writeln!(self.out, "[{}/{}] {} ... {}", index + 1, total, name, outcome);
+    }
+}
+
+/// The live progress line while a suite case runs on a terminal. Step
+/// output goes through [`step_output`], which takes the line off the screen
+/// first and draws it again after each complete line, so the two never
+/// share a row.
+pub struct StatusLine {
+    text: Option<String>,
+    on_screen: bool,
+}
+
+impl StatusLine {
+    pub const fn new() -> Self {
+        StatusLine { text: None, on_screen: false }
+    }
+
+    fn clear(&mut self, out: &mut dyn Write) {
+        if self.on_screen {
+            let _ = write!(out, "\r\x1b[2K");
+            let _ = out.flush();
+            self.on_screen = false;
+        }
+    }
+
+    fn draw(&mut self, out: &mut dyn Write) {
+        if let (Some(text), false) = (&self.text, self.on_screen) {
+            let _ = write!(out, "\r\x1b[2K{}", text);
+            let _ = out.flush();
+            self.on_screen = true;
+        }
+    }
+}
+
+impl Default for StatusLine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The status line [`progress_reporter`] draws on stderr.
+static STATUS_LINE: std::sync::Mutex<StatusLine> = std::sync::Mutex::new(StatusLine::new());
+
+/// Print step output to stdout around the live progress line. Output that
+/// does not end a line (a step's `… ` before its verdict) leaves the status
+/// line off the screen until the line is finished.
+fn step_output(text: std::fmt::Arguments, newline: bool) {
+    let mut line = STATUS_LINE.lock().unwrap_or_else(|e| e.into_inner());
+    line.clear(&mut std::io::stderr());
+    let mut out = std::io::stdout().lock();
+    let _ = out.write_fmt(text);
+    if newline {
+        let _ = writeln!(out);
+    }
+    let _ = out.flush();
+    drop(out);
+    if newline {
+        line.draw(&mut std::io::stderr());
+    }
+}
+
+/// On a terminal: one `... running` line redrawn in place with the case's
+/// outcome when it finishes; step output is printed above it.
+pub struct LiveProgress<W: Write> {
+    out: W,
+    line: &'static std::sync::Mutex<StatusLine>,
+}
+
+impl<W: Write> LiveProgress<W> {
+    /// Draw on `out`, keeping its state in `line`. Step output only routes
+    /// around [`STATUS_LINE`], the line [`progress_reporter`] uses.
+    pub fn new(out: W, line: &'static std::sync::Mutex<StatusLine>) -> Self {
+        LiveProgress { out, line }
+    }
+}
+
+impl<W: Write> ProgressReporter for LiveProgress<W> {
+    fn case_started(&mut self, index: usize, total: usize, name: &str) {
+        let mut line = self.line.lock().unwrap_or_else(|e| e.into_inner());
+        line.clear(&mut self.out);
+        line.text = Some(format!("[{}/{}] {} ... running", index + 1, total, name));
+        line.draw(&mut self.out);
+    }
+
+    fn case_finished(&mut self, index: usize, total: usize, name: &str, outcome: &str) {
+        let mut line = self.line.lock().unwrap_or_else(|e| e.into_inner());
+        line.clear(&mut self.out);
+        line.text = None;
+        let _ = writeln!(self.out, "[{}/{}] {} ... {}", index + 1, total, name, outcome);
+    }
+}
+
+pub struct NoProgress;
+
+impl ProgressReporter for NoProgress {
+    fn case_started(&mut self, _: usize, _: usize, _: &str) {}
+    fn case_finished(&mut self, _: usize, _: usize, _: &str, _: &str) {}
+}
+
+/// Progress on stderr: a live line on a terminal, outcome lines otherwise,
+/// none with `quiet`.
+pub fn progress_reporter(quiet: bool) -> Box<dyn ProgressReporter> {
+    if quiet {
+        Box::new(NoProgress)
+    } else if std::io::stderr().is_terminal() {
+        Box::new(LiveProgress::new(std::io::stderr(), &STATUS_LINE))
+    } else {
+        Box::new(LineProgress::new(std::io::stderr()))
+    }
+}
+
+/// Order `names` so each case comes after the cases it depends on, keeping
+/// the given order otherwise. Dependencies outside `names` are ignored.
+fn dependency_order(names: &[String], depends_on: &HashMap<&str, &[String]>) -> Result<Vec<String>> {
+    let in_run: std::collections::HashSet<&str> = names.iter().map(String::as_str).collect();
+    let deps_of = |name: &str| -> Vec<&str> {
+        depends_on
+            .get(name)
+            .map(|deps| deps.iter().map(String::as_str).filter(|d| in_run.contains(d)).collect())
+            .unwrap_or_default()
+    };
+    let mut placed = std::collections::HashSet::new();
+    let mut order = Vec::with_capacity(names.len());
+    while order.len() < names.len() {
+        let pending = || names.iter().map(String::as_str).filter(|n| !placed.contains(n));
+        if let Some(next) = pending().find(|n| deps_of(n).iter().all(|d| placed.contains(d))) {
+            placed.insert(next);
+            order.push(next.to_owned());
+            continue;
+        }
+        // Every pending case waits on another pending one: walk until a repeat.
+        let Some(start) = pending().next() else { break };
+        let mut path = vec![start];
+        loop {
+            let current = path[path.len() - 1];
+            let Some(dep) = deps_of(current).into_iter().find(|d| !placed.contains(d)) else { break };
+            if let Some(pos) = path.iter().position(|p| *p == dep) {
+                let mut cycle = path[pos..].to_vec();
+                cycle.push(dep);
+                bail!("Dependency cycle between cases: {} (each depends on the next)", cycle.join(" -> "));
+            }
+            path.push(dep);
+        }
+        bail!("Cannot order cases by dependency around '{}'", start);
+    }
+    Ok(order)
+}
+
+/// Fail on a `depends_on` entry that names no case at all: a typo there
+/// would otherwise silently drop the ordering.
+fn check_dependencies<'a>(cases: impl IntoIterator<Item = &'a str>, depends_on: &HashMap<&str, &[String]>) -> Result<()> {
+    let known: std::collections::HashSet<&str> = cases.into_iter().collect();
+    let mut dependents: Vec<(&&str, &&[String])> = depends_on.iter().collect();
+    dependents.sort();
+    for (name, deps) in dependents {
+        if let Some(dep) = deps.iter().find(|d| !known.contains(d.as_str())) {
+            bail!("Case '{}' depends on unknown case '{}'", name, dep);
+        }
+    }
+    Ok(())
+}
+
+/// Start of every skip reason from [`dependency_skip_reason`].
+const DEPENDENCY_SKIP: &str = "dependency";
+
+/// Why a case cannot run given the outcomes so far: its first dependency
+/// that failed or was skipped, or that `runs` left out (shard, tags,
+/// budget). Dependencies in the run but not played yet are not checked.
+fn dependency_skip_reason(deps: &[String], outcomes: &HashMap<String, CaseStatus>, runs: &[String]) -> Option<String> {
+    deps.iter().find_map(|dep| match outcomes.get(dep) {
+        None if !runs.contains(dep) => Some(format!("{} '{}' not in run", DEPENDENCY_SKIP, dep)),
+        Some(CaseStatus::Failed) => Some(format!("{} '{}' failed", DEPENDENCY_SKIP, dep)),
+        Some(CaseStatus::Skipped) => Some(format!("{} '{}' was skipped", DEPENDENCY_SKIP, dep)),
+        Some(CaseStatus::NotExecuted) => Some(format!("{} '{}' was not executed", DEPENDENCY_SKIP, dep)),
+        Some(CaseStatus::Passed) | None => None,
+    })
+}
+
+/// Play `names` in order with `play`, reporting each case to `progress`.
+/// Under `FailFast` the cases after a failure are skipped; once `deadline`
+/// passes the rest are not executed. Failed cases (and the ones skipped for
+/// depending on them) are played again up to `reruns` times, and `teardown`,
+/// if any, is played last whatever happened.
+pub fn run_suite_cases(
+    names: &[String],
+    failure_mode: FailureMode,
+    reruns: u32,
+    mut play: impl FnMut(&str) -> CaseResult,
+    teardown: Option<&str>,
+    deadline: Option<std::time::Instant>,
+    progress: &mut dyn ProgressReporter,
+) -> (Vec<CaseResult>, Option<CaseResult>) {
+    let mut cases = Vec::with_capacity(names.len());
+    let mut stopped = false;
+    let total = names.len();
+    for (index, name) in names.iter().enumerate() {
+        let timed_out = deadline.is_some_and(|d| std::time::Instant::now() >= d);
+        if stopped || timed_out {
+            let (status, reason) = match timed_out {
+                true => (CaseStatus::NotExecuted, "suite timeout reached"),
+                false => (CaseStatus::Skipped, "earlier case failed (fail-fast)"),
+            };
+            cases.push(CaseResult { skip_reason: Some(reason.into()), ..CaseResult::new(name, status) });
+            progress.case_finished(index, total, name, &progress_outcome(&cases[index]));
+            continue;
+        }
+        progress.case_started(index, total, name);
+        let result = play(name);
+        if result.status == CaseStatus::Failed && failure_mode == FailureMode::FailFast {
+            stopped = true;
+        }
+        progress.case_finished(index, total, name, &progress_outcome(&result));
+        cases.push(result);
+    }
+    // Failed cases get another go, in order, after everything else has run,
+    // along with the cases they kept from running.
+    for _ in 0..reruns {
+        if !cases.iter().any(|c| c.status == CaseStatus::Failed) {
+            break;
+        }
+        let again: Vec<usize> = (0..cases.len())
+            .filter(|&i| cases[i].status == CaseStatus::Failed || cases[i].skipped_for_dependency())
+            .collect();
+        for index in again {
+            if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+                break;
+            }
+            progress.case_started(index, total, &names[index]);
+            let retry = play(&names[index]);
+            progress.case_finished(index, total, &names[index], &progress_outcome(&retry));
+            rerun_case(&mut cases[index], retry);
+        }
+    }
+    let teardown = teardown.map(&mut play);
+    (cases, teardown)
+}
+
+/// Replace `case` with its rerun, keeping a failure as an attempt. A rerun
+/// that did not get to play (skipped) leaves it as is.
+fn rerun_case(case: &mut CaseResult, retry: CaseResult) {
+    if !retry.status.ran() {
+        return;
+    }
+    let earlier = std::mem::replace(case, retry);
+    if earlier.status != CaseStatus::Failed {
+        return;
+    }
+    case.attempts = earlier.attempts;
+    case.attempts.push(CaseAttempt { duration_ms: earlier.duration_ms, error: earlier.error });
+    case.flaky = case.status == CaseStatus::Passed;
+    // Diagnostics from the failed attempt stay with the case.
+    let mut artifacts = earlier.artifacts;
+    artifacts.append(&mut case.artifacts);
+    case.artifacts = artifacts;
+}
+
+/// File name stem for a case's artifacts: `<case>`, then `<case>.attempt2`
+/// and so on for reruns, so each attempt keeps its own files.
+fn attempt_stem(case: &str, attempt: u32) -> String {
+    match attempt {
+        0 | 1 => case.to_owned(),
+        n => format!("{}.attempt{}", case, n),
+    }
+}
+
+/// Write `report` as JSON to `path`, creating its directory.
+fn write_suite_report(path: &Path, report: &SuiteReport) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("Cannot create {}", dir.display()))?;
+    }
+    let json = serde_json::to_string_pretty(report)?;
+    fs::write(path, json).with_context(|| format!("Cannot write report {}", path.display()))?;
+    println!("Report written to {}", path.display());
+    Ok(())
+}
+
+fn cmd_suite(
+    platform: &str,
+    filter: &ScenarioFilter,
+    play_opts: &PlayOptions,
+    suite_opts: &SuiteOptions,
+) -> Result<()> {
+    if suite_opts.video.is_some() && platform != "ios" {
+        bail!("--video is only supported on ios");
+    }
+    if matches!(suite_opts.retention, RetentionPolicy::LastRuns(_)) && suite_opts.history.is_none() {
+        bail!("--retention keep-last-N needs --history to know the earlier runs");
+    }
+    let started_at = now_iso8601();
+    let started_ms = now_ms();
+    // Output templates are expanded (or rejected) before any case runs.
+    let SuiteOutputs { report: report_path, history, diagnostics, video, screenshots } = suite_outputs(suite_opts, &started_at, platform)?;
+    let play_opts = &PlayOptions { screenshots, ..play_opts.clone() };
+    let found = collect_scenarios(Some(platform), filter)?;
+    let severity_of: HashMap<&str, Severity> = found
+        .iter()
+        .filter_map(|(_, s)| s.severity.map(|sev| (s.name.as_str(), sev)))
+        .collect();
+    let requires_of: HashMap<&str, &DeviceRequirements> = found
+        .iter()
+        .filter_map(|(_, s)| s.requires.as_ref().map(|r| (s.name.as_str(), r)))
+        .collect();
+    let depends_of: HashMap<&str, &[String]> = found
+        .iter()
+        .filter_map(|(_, s)| s.depends_on.as_deref().map(|d| (s.name.as_str(), d)))
+        .collect();
+    check_dependencies(found.iter().map(|(_, s)| s.name.as_str()), &depends_of)?;
+    let mut outcomes: HashMap<String, CaseStatus> = HashMap::new();
+    let mut gate = RequirementGate::new(platform);
+    let check_device = platform == "android"
+        && !play_opts.dry_run
+        && play_opts.remote_device.is_none()
+        && suite_opts.device_timeout_secs > 0;
+    let mut device_lost: Option<String> = None;
+    let mut names: Vec<String> = found
+        .iter()
+        .map(|(_, s)| s.name.clone())
+        .filter(|n| Some(n.as_str()) != suite_opts.teardown.as_deref())
+        .collect();
+    if let Some(base_ref) = &suite_opts.changed_since {
+        let changed = changed_cases(&scenarios_dir(platform)?, base_ref)?;
+        names.retain(|n| changed.contains(n));
+        if names.is_empty() {
+            println!("No scenarios changed since '{}'; nothing to run.", base_ref);
+            return Ok(());
+        }
+    }
+    if names.is_empty() {
+        bail!("No scenarios matched for platform '{}'", platform);
+    }
+    if let Some(budget) = suite_opts.sample_budget {
+        let seed = match suite_opts.seed {
+            Some(seed) => seed,
+            None => commit_seed(&scenarios_dir(platform)?).unwrap_or_else(now_ms),
+        };
+        let durations = match &history {
+            Some(dir) if dir.is_dir() => latest_durations(dir)?,
+            _ => HashMap::new(),
+        };
+        let candidates: Vec<&Scenario> = found.iter().map(|(_, s)| s).filter(|s| names.contains(&s.name)).collect();
+        let sample = sample_suite(&candidates, &durations, budget, seed);
+        let total: u64 = sample.iter().map(|(_, secs)| secs).sum();
+        println!(
+            "Sample (seed {}): {} of {} case(s), ~{}s of {}s budget",
+            seed,
+            sample.len(),
+            names.len(),
+            total,
+            budget.as_secs()
+        );
+        for (name, secs) in &sample {
+            let severity = severity_of.get(name.as_str()).map_or("-", |s| s.as_str());
+            println!("  {:<40} ~{:>5}s  {}", name, secs, severity);
+        }
+        if sample.is_empty() {
+            bail!("No case fits in a {}s budget", budget.as_secs());
+        }
+        names = sample.into_iter().map(|(name, _)| name).collect();
+    }
+    if let Some((index, total)) = suite_opts.shard {
+        // With history, balance by past durations; every agent must then
+        // see the same history directory to agree on the split.
+        names = match &history {
+            Some(dir) if dir.is_dir() => shard_weighted(&names, &latest_durations(dir)?, total, index),
+            _ => shard(&names, total, index),
+        };
+        println!("Shard {}/{}: {} case(s)", index + 1, total, names.len());
+        if names.is_empty() {
+            return Ok(());
+        }
+    }
+
+    let names = dependency_order(&names, &depends_of)?;
+
+    let configurations = match suite_opts.matrix.len() {
+        0 => String::new(),
+        n => format!(" x {} configuration(s)", n),
+    };
+    println!(
+        "Running suite: {} case(s){} on '{}', failure mode: {}",
+        names.len(),
+        configurations,
+        platform,
+        match suite_opts.failure_mode {
+            FailureMode::FailFast => "fail-fast",
+            FailureMode::ContinueOnFailure => "continue",
+        }
+    );
+
+    // Cases start until the suite timeout, which counts the before hooks too;
+    // the case in flight then has the grace period to finish. Teardown runs
+    // without a deadline.
+    let suite_start = std::time::Instant::now();
+    let deadline = suite_opts.suite_timeout.map(|t| suite_start + t);
+
+    let mut hooks = if play_opts.dry_run { Vec::new() } else { run_hooks(HookPhase::Before, &suite_opts.before, deadline) };
+    if let Some(failed) = hooks.iter().find(|h| !h.passed()) {
+        let error = failed.error();
+        hooks.extend(run_hooks(HookPhase::After, &suite_opts.after, None));
+        if let Some(path) = &report_path {
+            let report = SuiteReport {
+                platform: platform.to_owned(),
+                started_at,
+                failure_mode: suite_opts.failure_mode,
+                cases: Vec::new(),
+                teardown: None,
+                hooks,
+                passed: 0,
+                flaky: 0,
+                failed: 0,
+                skipped: 0,
+                not_executed: 0,
+            };
+            write_suite_report(path, &report)?;
+        }
+        bail!("Suite not run: {}", error);
+    }
+
+    let case_opts = PlayOptions { deadline: deadline.map(|d| d + suite_opts.timeout_grace), ..play_opts.clone() };
+    // Under --matrix the whole ordered list runs once per configuration, so
+    // dependencies are met within the same configuration.
+    let matrix_opts: Vec<PlayOptions> = suite_opts.matrix.iter().map(|m| m.play_options(&case_opts)).collect();
+    let mut run_of: HashMap<String, (&str, Option<usize>)> = HashMap::new();
+    for (i, m) in suite_opts.matrix.iter().enumerate() {
+        for name in &names {
+            run_of.insert(matrix_label(name, Some(&m.values)), (name.as_str(), Some(i)));
+        }
+    }
+    let runs: Vec<String> = match suite_opts.matrix.is_empty() {
+        true => names.clone(),
+        false => suite_opts.matrix.iter().flat_map(|m| names.iter().map(|n| matrix_label(n, Some(&m.values)))).collect(),
+    };
+    // Plays of each run so far, so a rerun's artifacts get their own names.
+    let mut attempts: HashMap<String, u32> = HashMap::new();
+    let (mut cases, mut teardown) = run_suite_cases(
+        &runs,
+        suite_opts.failure_mode,
+        suite_opts.rerun_failed,
+        |run| {
+            // `run` is the case name, labelled with its configuration under --matrix.
+            let (name, combo) = run_of.get(run).copied().unwrap_or((run, None));
+            let play_opts = match combo {
+                _ if Some(run) == suite_opts.teardown.as_deref() => play_opts,
+                Some(i) => &matrix_opts[i],
+                None => &case_opts,
+            };
+            let values = combo.map(|i| &suite_opts.matrix[i].values);
+            step_println!();
+            if device_lost.is_none() && check_device {
+                let timeout = std::time::Duration::from_secs(suite_opts.device_timeout_secs);
+                if let Err(e) = crate::android::wait_for_device(None, timeout, true) {
+                    device_lost = Some(format!("{:#}", e));
+                }
+            }
+            let dependency = depends_of.get(name).and_then(|deps| {
+                let deps: Vec<String> = deps.iter().map(|d| matrix_label(d, values)).collect();
+                dependency_skip_reason(&deps, &outcomes, &runs)
+            });
+            let skip = match requires_of.get(name) {
+                _ if device_lost.is_some() => device_lost.as_ref().map(|e| format!("device lost: {}", e)),
+                _ if dependency.is_some() => dependency,
+                // Requirements are probed over adb, so a remote run can't check them.
+                Some(req) if !play_opts.dry_run && play_opts.remote_device.is_none() => gate.skip_reason(req),
+                _ => None,
+            };
+            if let Some(reason) = skip {
+                step_println!("Skipping '{}': {}", run, reason);
+                outcomes.insert(run.to_owned(), CaseStatus::Skipped);
+                return CaseResult {
+                    skip_reason: Some(reason),
+                    severity: severity_of.get(name).copied(),
+                    matrix: values.cloned(),
+                    ..CaseResult::new(run, CaseStatus::Skipped)
+                };
+            }
+            let attempt = {
+                let played = attempts.entry(run.to_owned()).or_insert(0);
+                *played += 1;
+                *played
+            };
+            let recording = match &video {
+                Some((template, layout)) if !play_opts.dry_run => start_case_recording(template, layout, run, attempt),
+                _ => None,
+            };
+            let mut case = CaseResult {
+                name: run.to_owned(),
+                severity: severity_of.get(name).copied(),
+                matrix: values.cloned(),
+                ..play_case(name, platform, play_opts)
+            };
+            if let Some(handle) = recording {
+                match handle.stop() {
+                    Ok(path) => case.artifacts.push(path.display().to_string()),
+                    Err(e) => eprintln!("Warning: recording of '{}': {:#}", run, e),
+                }
+            }
+            if case.status == CaseStatus::Failed && platform == "android" && !play_opts.dry_run {
+                if let Some((template, layout)) = &diagnostics {
+                    case.artifacts.extend(collect_failure_artifacts(template, layout, run, attempt, suite_opts.bugreport));
+                }
+            }
+            outcomes.insert(run.to_owned(), case.status);
+            case
+        },
+        suite_opts.teardown.as_deref(),
+        deadline,
+        progress_reporter(suite_opts.quiet).as_mut(),
+    );
+
+    if !play_opts.dry_run {
+        hooks.extend(run_hooks(HookPhase::After, &suite_opts.after, None));
+    }
+    if suite_opts.retention == RetentionPolicy::FailuresOnly {
+        let removed = prune_passed_artifacts(cases.iter_mut().chain(teardown.as_mut()));
+        if removed > 0 {
+            println!("Retention: removed {} artifact(s) of passing cases", removed);
+        }
+    }
+
+    let count = |status| cases.iter().filter(|c| c.status == status).count();
+    let report = SuiteReport {
+        platform: platform.to_owned(),
+        started_at,
+        failure_mode: suite_opts.failure_mode,
+        passed: count(CaseStatus::Passed),
+        flaky: cases.iter().filter(|c| c.flaky).count(),
+        failed: count(CaseStatus::Failed),
+        skipped: count(CaseStatus::Skipped),
+        not_executed: count(CaseStatus::NotExecuted),
+        cases,
+        teardown,
+        hooks,
+    };
+
+    println!("\nSuite summary:");
+    for case in &report.cases {
+        let status = match case.status {
+            CaseStatus::Passed if case.flaky => "FLAKY",
+            CaseStatus::Passed => "PASS",
+            CaseStatus::Failed => "FAIL",
+            CaseStatus::Skipped => "SKIP",
+            CaseStatus::NotExecuted => "NOT RUN",
+        };
+        let severity = case.severity.map(|s| format!(" [{}]", s.as_str())).unwrap_or_default();
+        let reason = case.skip_reason.as_deref().map(|r| format!(" - {}", r)).unwrap_or_default();
+        let manual = if case.needs_manual_verification { " - needs manual verification" } else { "" };
+        let attempts = match case.attempts.len() {
+            0 => String::new(),
+            n => format!(" - {} attempt(s)", n + 1),
+        };
+        println!("  {}  {} ({}ms){}{}{}{}", status, case.name, case.duration_ms, severity, reason, manual, attempts);
+        for attempt in &case.attempts {
+            println!("        earlier failure: {}", attempt.error.as_deref().unwrap_or("failed"));
+        }
+        for artifact in &case.artifacts {
+            println!("        artifact: {}", artifact);
+        }
+    }
+    if let Some(td) = &report.teardown {
+        let status = if td.status == CaseStatus::Passed { "PASS" } else { "FAIL" };
+        println!("  {}  {} (teardown)", status, td.name);
+    }
+    let flaky = match report.flaky {
+        0 => String::new(),
+        n => format!(" ({} flaky)", n),
+    };
+    println!(
+        "{} passed{}, {} failed, {} skipped.",
+        report.passed, flaky, report.failed, report.skipped
+    );
+    if report.not_executed > 0 {
+        println!("{} case(s) not executed: suite timeout reached.", report.not_executed);
+    }
+    let unverified = report.cases.iter().filter(|c| c.needs_manual_verification).count();
+    if unverified > 0 {
+        println!("{} case(s) need manual verification.", unverified);
+    }
+    let by_severity = failures_by_severity(&report.cases);
+    if !by_severity.is_empty() {
+        let parts: Vec<String> = by_severity.iter().map(|(s, n)| format!("{} {}", n, s)).collect();
+        println!("Failures by severity: {}", parts.join(", "));
+    }
+
+    if let Some(path) = &report_path {
+        write_suite_report(path, &report)?;
+    }
+    if let Some(dir) = &history {
+        fs::create_dir_all(dir).with_context(|| format!("Cannot create history dir {}", dir.display()))?;
+        let (file, mut out) = create_history_file(dir, &report.started_at, started_ms % 1000, platform)?;
+        out.write_all(serde_json::to_string_pretty(&report)?.as_bytes())
+            .with_context(|| format!("Cannot write {}", file.display()))?;
+        if let RetentionPolicy::LastRuns(keep) = suite_opts.retention {
+            let removed = prune_history_artifacts(dir, keep)?;
+            if removed > 0 {
+                println!("Retention: removed {} artifact(s) of runs before the last {}", removed, keep);
+            }
+        }
+    }
+
+    if let Some(e) = device_lost {
+        bail!("Suite stopped: {}", e);
+    }
+    if let Some(limit) = suite_opts.suite_timeout.filter(|_| report.not_executed > 0) {
+        bail!("Suite timed out after {}s with {} case(s) not executed", limit.as_secs(), report.not_executed);
+    }
+    if report.failed > 0 {
+        bail!("Suite finished with {} failed case(s)", report.failed);
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// recorder trend
+// ---------------------------------------------------------------------------
+
+/// The parts of a saved [`SuiteReport`] the trend report reads.
+#[derive(Debug, Deserialize)]
+struct HistoryRun {
+    #[serde(rename = "startedAt", default)]
+    started_at: String,
+    cases: Vec<HistoryCase>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryCase {
+    name: String,
+    status: CaseStatus,
+    #[serde(rename = "durationMs", default)]
+    duration_ms: u64,
+    #[serde(default)]
+    artifacts: Vec<String>,
+}
+
+/// Per-case outcomes across runs, oldest first; `None` = not in that run.
+#[derive(Debug, Default)]
+struct Trend {
+    runs: Vec<String>,
+    /// Pass rate of each run over passed + failed cases.
+    pass_rates: Vec<Option<f64>>,
+    cases: std::collections::BTreeMap<String, Vec<Option<CaseStatus>>>,
+}
+
+impl Trend {
+    fn new(runs: &[HistoryRun]) -> Self {
+        let mut trend = Trend::default();
+        for (i, run) in runs.iter().enumerate() {
+            trend.runs.push(run.started_at.clone());
+            let passed = run.cases.iter().filter(|c| c.status == CaseStatus::Passed).count();
+            let failed = run.cases.iter().filter(|c| c.status == CaseStatus::Failed).count();
+            trend.pass_rates.push((passed + failed > 0).then(|| passed as f64 / (passed + failed) as f64));
+            for case in &run.cases {
+                let series = trend.cases.entry(case.name.clone()).or_insert_with(|| vec![None; runs.len()]);
+                series[i] = Some(case.status);
+            }
+        }
+        trend
+    }
+
+    /// Cases whose latest result flipped between passed and failed compared to
+    /// the previous run that ran them: `(regressions, fixes)`.
+    fn changes(&self) -> (Vec<&str>, Vec<&str>) {
+        let mut regressions = Vec::new();
+        let mut fixes = Vec::new();
+        for (name, series) in &self.cases {
+            let mut outcomes = series.iter().flatten().filter(|s| s.ran());
+            let (Some(last), Some(prev)) = (outcomes.next_back(), outcomes.next_back()) else {
+                continue;
+            };
+            // Only count a change when the case was part of the latest run.
+            if series.last().copied().flatten() != Some(*last) {
+                continue;
+            }
+            match (prev, last) {
+                (CaseStatus::Passed, CaseStatus::Failed) => regressions.push(name.as_str()),
+                (CaseStatus::Failed, CaseStatus::Passed) => fixes.push(name.as_str()),
+                _ => {}
+            }
+        }
+        (regressions, fixes)
+    }
+
+    /// Cases that flipped between passed and failed at least once, most flips first.
+    fn flakiest(&self, limit: usize) -> Vec<(&str, usize, usize)> {
+        let mut flaky: Vec<(&str, usize, usize)> = self
+            .cases
+            .iter()
+            .filter_map(|(name, series)| {
+                let outcomes: Vec<_> = series.iter().flatten().filter(|s| s.ran()).collect();
+                let flips = outcomes.windows(2).filter(|w| w[0] != w[1]).count();
+                (flips > 0).then_some((name.as_str(), flips, outcomes.len()))
+            })
+            .collect();
+        flaky.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        flaky.truncate(limit);
+        flaky
+    }
+}
+
+/// Load every `*.json` suite report in `dir`, oldest first. Unreadable or
+/// malformed files are skipped with a warning.
+fn load_history(dir: &Path) -> Result<Vec<HistoryRun>> {
+    let mut runs = Vec::new();
+    let entries = fs::read_dir(dir).with_context(|| format!("Cannot read history dir {}", dir.display()))?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let parsed = fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| serde_json::from_str::<HistoryRun>(&text).map_err(anyhow::Error::from));
+        match parsed {
+            Ok(mut run) => {
+                if run.started_at.is_empty() {
+                    run.started_at = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+                }
+                runs.push(run);
+            }
+            Err(e) => eprintln!("Warning: skipping {}: {}", path.display(), e),
+        }
+    }
+    runs.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+    Ok(runs)
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Pass-rate line over all runs as an inline SVG polyline.
+fn pass_rate_svg(rates: &[Option<f64>]) -> String {
+    let (w, h) = (600.0, 80.0);
+    let step = if rates.len() > 1 { w / (rates.len() - 1) as f64 } else { 0.0 };
+    let points: Vec<String> = rates
+        .iter()
+        .enumerate()
+        .filter_map(|(i, r)| r.map(|r| format!("{:.1},{:.1}", i as f64 * step, h - r * h)))
+        .collect();
+    format!(
+        "<svg width=\"{w}\" height=\"{h}\" viewBox=\"-4 -4 {} {}\"><polyline fill=\"none\" stroke=\"#2a7\" stroke-width=\"2\" points=\"{}\"/></svg>",
+        w + 8.0,
+        h + 8.0,
+        points.join(" ")
+    )
+}
+
+/// One coloured cell per run: green passed, red failed, grey skipped, blank absent.
+fn case_sparkline(series: &[Option<CaseStatus>]) -> String {
+    series
+        .iter()
+        .map(|s| {
+            let class = match s {
+                Some(CaseStatus::Passed) => "p",
+                Some(CaseStatus::Failed) => "f",
+                Some(CaseStatus::Skipped) => "s",
+                Some(CaseStatus::NotExecuted) | None => "n",
+            };
+            format!("<i class=\"{}\"></i>", class)
+        })
+        .collect()
+}
+
+fn render_trend_html(trend: &Trend) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Suite trend</title><style>\n\
+         body{font-family:sans-serif;margin:2em}td,th{padding:2px 8px;text-align:left}\n\
+         i{display:inline-block;width:8px;height:16px;margin-right:1px}\n\
+         i.p{background:#2a7}i.f{background:#d33}i.s{background:#bbb}i.n{background:#eee}\n\
+         </style></head><body>\n",
+    );
+    let latest = trend.pass_rates.iter().rev().flatten().next();
+    html.push_str(&format!(
+        "<h1>Suite trend</h1>\n<p>{} run(s), {} to {}. Latest pass rate: {}.</p>\n",
+        trend.runs.len(),
+        html_escape(trend.runs.first().map(String::as_str).unwrap_or("-")),
+        html_escape(trend.runs.last().map(String::as_str).unwrap_or("-")),
+        latest.map(|r| format!("{:.0}%", r * 100.0)).unwrap_or_else(|| "n/a".into())
+    ));
+    html.push_str("<h2>Pass rate</h2>\n");
+    html.push_str(&pass_rate_svg(&trend.pass_rates));
+    html.push('\n');
+
+    let (regressions, fixes) = trend.changes();
+    for (title, names) in [("Regressions", &regressions), ("Fixes", &fixes)] {
+        html.push_str(&format!("<h2>{} ({})</h2>\n", title, names.len()));
+        if names.is_empty() {
+            html.push_str("<p>None.</p>\n");
+        } else {
+            html.push_str("<ul>\n");
+            for name in names {
+                html.push_str(&format!("<li>{}</li>\n", html_escape(name)));
+            }
+            html.push_str("</ul>\n");
+        }
+    }
+
+    let flaky = trend.flakiest(10);
+    html.push_str("<h2>Flakiest cases</h2>\n");
+    if flaky.is_empty() {
+        html.push_str("<p>None.</p>\n");
+    } else {
+        html.push_str("<table><tr><th>Case</th><th>Flips</th><th>Runs</th></tr>\n");
+        for (name, flips, runs) in &flaky {
+            html.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n", html_escape(name), flips, runs));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>All cases</h2>\n<table><tr><th>Case</th><th>History (oldest first)</th></tr>\n");
+    for (name, series) in &trend.cases {
+        html.push_str(&format!("<tr><td>{}</td><td>{}</td></tr>\n", html_escape(name), case_sparkline(series)));
+    }
+    html.push_str("</table>\n</body></html>\n");
+    html
+}
+
+/// Summarise the suite reports in `history_dir` (see `recorder suite --history`)
+/// as an HTML dashboard at `out_path`.
+fn write_trend_report(history_dir: &str, out_path: &str) -> Result<()> {
+    let runs = load_history(Path::new(history_dir))?;
+    if runs.is_empty() {
+        bail!("No readable suite reports in {}", history_dir);
+    }
+    let trend = Trend::new(&runs);
+    fs::write(out_path, render_trend_html(&trend)).with_context(|| format!("Cannot write {}", out_path))?;
+    let (regressions, fixes) = trend.changes();
+    println!(
+        "Trend over {} run(s) written to {} ({} regression(s), {} fix(es))",
+        runs.len(),
+        out_path,
+        regressions.len(),
+        fixes.len()
+    );
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// recorder diff-runs
+// ---------------------------------------------------------------------------
+
+/// A case whose status differs between two runs.
+#[derive(Debug, PartialEq, Serialize)]
+struct CaseChange {
+    name: String,
+    before: CaseStatus,
+    after: CaseStatus,
+}
+
+/// A case's run time in both runs.
+#[derive(Debug, PartialEq, Serialize)]
+struct DurationDelta {
+    name: String,
+    #[serde(rename = "beforeMs")]
+    before_ms: u64,
+    #[serde(rename = "afterMs")]
+    after_ms: u64,
+}
+
+impl DurationDelta {
+    fn delta_ms(&self) -> i64 {
+        self.after_ms as i64 - self.before_ms as i64
+    }
+}
+
+/// What changed between two suite runs, cases keyed by name.
+#[derive(Debug, Default, Serialize)]
+struct RunDiff {
+    changed: Vec<CaseChange>,
+    added: Vec<CaseChange>,
+    removed: Vec<CaseChange>,
+    /// Cases that ran (passed or failed) in both, largest change first.
+    durations: Vec<DurationDelta>,
+}
+
+impl RunDiff {
+    fn regressions(&self) -> impl Iterator<Item = &CaseChange> {
+        self.changed.iter().filter(|c| c.before == CaseStatus::Passed && c.after == CaseStatus::Failed)
+    }
+
+    fn fixes(&self) -> impl Iterator<Item = &CaseChange> {
+        self.changed.iter().filter(|c| c.before == CaseStatus::Failed && c.after == CaseStatus::Passed)
+    }
+}
+
+/// Compare run `b` against the earlier run `a`. Added and removed cases
+/// carry their status in the run that has them in both fields.
+fn diff_runs(a: &HistoryRun, b: &HistoryRun) -> RunDiff {
+    let before: HashMap<&str, &HistoryCase> = a.cases.iter().map(|c| (c.name.as_str(), c)).collect();
+    let after: HashMap<&str, &HistoryCase> = b.cases.iter().map(|c| (c.name.as_str(), c)).collect();
+    let mut diff = RunDiff::default();
+    for case in &b.cases {
+        let Some(old) = before.get(case.name.as_str()) else {
+            diff.added.push(CaseChange { name: case.name.clone(), before: case.status, after: case.status });
+            continue;
+        };
+        if old.status != case.status {
+            diff.changed.push(CaseChange { name: case.name.clone(), before: old.status, after: case.status });
+        }
+        if old.status.ran() && case.status.ran() {
+            diff.durations.push(DurationDelta {
+                name: case.name.clone(),
+                before_ms: old.duration_ms,
+                after_ms: case.duration_ms,
+            });
+        }
+    }
+    for case in a.cases.iter().filter(|c| !after.contains_key(c.name.as_str())) {
+        diff.removed.push(CaseChange { name: case.name.clone(), before: case.status, after: case.status });
+    }
+    diff.durations.sort_by(|x, y| y.delta_ms().abs().cmp(&x.delta_ms().abs()).then(x.name.cmp(&y.name)));
+    diff
+}
+
+fn status_word(status: CaseStatus) -> &'static str {
+    match status {
+        CaseStatus::Passed => "passed",
+        CaseStatus::Failed => "failed",
+        CaseStatus::Skipped => "skipped",
+        CaseStatus::NotExecuted => "not executed",
+    }
+}
+
+/// Markdown summary for a PR comment. Duration changes are listed only when
+/// a case moved by at least 20% and one second, at most `max_durations` of them.
+fn render_run_diff(diff: &RunDiff, max_durations: usize) -> String {
+    let regressions: Vec<&CaseChange> = diff.regressions().collect();
+    let fixes: Vec<&CaseChange> = diff.fixes().collect();
+    let mut md = format!(
+        "**Suite diff:** {} regression(s), {} fix(es), {} new, {} removed\n",
+        regressions.len(),
+        fixes.len(),
+        diff.added.len(),
+        diff.removed.len()
+    );
+    let other: Vec<&CaseChange> = diff.changed.iter().filter(|c| !regressions.contains(c) && !fixes.contains(c)).collect();
+    let sections = [
+        ("Regressions", regressions),
+        ("Fixes", fixes),
+        ("Other status changes", other),
+        ("New cases", diff.added.iter().collect()),
+        ("Removed cases", diff.removed.iter().collect()),
+    ];
+    for (title, cases) in sections {
+        if cases.is_empty() {
+            continue;
+        }
+        md.push_str(&format!("\n### {}\n\n", title));
+        for c in cases {
+            if c.before == c.after {
+                md.push_str(&format!("- `{}` ({})\n", c.name, status_word(c.after)));
+            } else {
+                md.push_str(&format!("- `{}`: {} → {}\n", c.name, status_word(c.before), status_word(c.after)));
+            }
+        }
+    }
+    let notable: Vec<&DurationDelta> = diff
+        .durations
+        .iter()
+        .filter(|d| d.delta_ms().unsigned_abs() >= 1000 && d.delta_ms().unsigned_abs() * 5 >= d.before_ms)
+        .take(max_durations)
+        .collect();
+    if !notable.is_empty() {
+        md.push_str("\n### Duration changes\n\n| Case | Before | After | Δ |\n|---|---|---|---|\n");
+        for d in notable {
+            md.push_str(&format!(
+                "| `{}` | {:.1}s | {:.1}s | {:+.1}s |\n",
+                d.name,
+                d.before_ms as f64 / 1000.0,
+                d.after_ms as f64 / 1000.0,
+                d.delta_ms() as f64 / 1000.0
+            ));
+        }
+    }
+    md
+}
+
+fn load_run(path: &str) -> Result<HistoryRun> {
+    let text = fs::read_to_string(path).with_context(|| format!("Cannot read suite report {}", path))?;
+    serde_json::from_str(&text).with_context(|| format!("Invalid suite report {}", path))
+}
+
+fn cmd_diff_runs(before: &str, after: &str, json: bool, output: Option<&str>) -> Result<()> {
+    let diff = diff_runs(&load_run(before)?, &load_run(after)?);
+    let text = if json { serde_json::to_string_pretty(&diff)? + "\n" } else { render_run_diff(&diff, 10) };
+    match output {
+        Some(path) => {
+            fs::write(path, &text).with_context(|| format!("Cannot write {}", path))?;
+            println!("Run diff written to {}", path);
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// recorder coverage
+// ---------------------------------------------------------------------------
+
+/// Case counts per feature and platform.
+#[derive(Debug, Default)]
+struct CoverageMatrix {
+    /// Column order: every platform some case runs on, or the requested set.
+    platforms: Vec<String>,
+    /// feature → platform → cases.
+    rows: std::collections::BTreeMap<String, HashMap<String, usize>>,
+    /// platform → cases without a `linkedFeature`. Kept apart from `rows`,
+    /// so a feature that happens to be named "uncategorized" stays a feature.
+    uncategorized: HashMap<String, usize>,
+}
+
+impl CoverageMatrix {
+    /// Cases of `feature` (`None` for uncategorized ones) on `platform`.
+    fn count(&self, feature: Option<&str>, platform: &str) -> usize {
+        let row = match feature {
+            Some(f) => self.rows.get(f),
+            None => Some(&self.uncategorized),
+        };
+        row.and_then(|r| r.get(platform)).copied().unwrap_or(0)
+    }
+
+    /// Features with no case on a platform. Uncategorized cases are not a
+    /// feature, so they never count as a gap.
+    fn gaps(&self) -> Vec<(&str, &str)> {
+        self.features()
+            .flat_map(|f| self.platforms.iter().map(move |p| (f, p.as_str())))
+            .filter(|(f, p)| self.count(Some(f), p) == 0)
+            .collect()
+    }
+
+    fn features(&self) -> impl Iterator<Item = &str> {
+        self.rows.keys().map(String::as_str)
+    }
+
+    /// Named features first, then `None` for the uncategorized row if it
+    /// has cases.
+    fn row_names(&self) -> Vec<Option<&str>> {
+        let mut names: Vec<Option<&str>> = self.features().map(Some).collect();
+        if !self.uncategorized.is_empty() {
+            names.push(None);
+        }
+        names
+    }
+
+    fn is_empty(&self) -> bool {
+        self.rows.is_empty() && self.uncategorized.is_empty()
+    }
+}
+
+/// Cross-tabulate every case under `dir` by `linkedFeature` and platform.
+///
+/// A case counts on each of its `platforms`, or on its own `platform`.
+/// With `platforms` given, only those columns are shown and checked for gaps.
+fn coverage_matrix(dir: &Path, platforms: Option<&[String]>) -> CoverageMatrix {
+    let mut matrix = CoverageMatrix::default();
+    let mut seen_platforms: Vec<String> = Vec::new();
+    for (path, scenario) in walk_scenarios(dir) {
+        let scenario = match scenario {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Warning: skipping {}: {:#}", path.display(), e);
+                continue;
+            }
+        };
+        let feature = scenario.linked_feature.as_deref().map(str::trim).filter(|f| !f.is_empty());
+        let row = match feature {
+            Some(f) => matrix.rows.entry(f.to_string()).or_default(),
+            None => &mut matrix.uncategorized,
+        };
+        for platform in scenario.platforms.clone().unwrap_or_else(|| vec![scenario.platform.clone()]) {
+            *row.entry(platform.clone()).or_default() += 1;
+            if !seen_platforms.contains(&platform) {
+                seen_platforms.push(platform);
+            }
+        }
+    }
+    matrix.platforms = match platforms {
+        Some(list) => list.to_vec(),
+        None => {
+            // Known platforms in their usual order, then anything else.
+            let mut ordered: Vec<String> =
+                PLATFORMS.iter().filter(|p| seen_platforms.iter().any(|s| s == *p)).map(|p| p.to_string()).collect();
+            seen_platforms.sort();
+            ordered.extend(seen_platforms.into_iter().filter(|p| !PLATFORMS.contains(&p.as_str())));
+            ordered
+        }
+    };
+    matrix
+}
+
+fn render_coverage_markdown(matrix: &CoverageMatrix) -> String {
+    let gaps = matrix.gaps();
+    let mut md = format!(
+        "# Coverage\n\n{} feature(s) × {} platform(s), {} gap(s). Gaps are marked **0**.\n\n",
+        matrix.features().count(),
+        matrix.platforms.len(),
+        gaps.len()
+    );
+    md.push_str(&format!("| Feature | {} |\n|---|{}\n", matrix.platforms.join(" | "), "---|".repeat(matrix.platforms.len())));
+    for feature in matrix.row_names() {
+        let cells: Vec<String> = matrix
+            .platforms
+            .iter()
+            .map(|p| match matrix.count(feature, p) {
+                0 if feature.is_some() => "**0**".to_string(),
+                n => n.to_string(),
+            })
+            .collect();
+        let label = feature.map_or_else(|| "_uncategorized_".to_string(), str::to_string);
+        md.push_str(&format!("| {} | {} |\n", label, cells.join(" | ")));
+    }
+    if !gaps.is_empty() {
+        md.push_str("\n## Gaps\n\n");
+        for (feature, platform) in gaps {
+            md.push_str(&format!("- {} on {}\n", feature, platform));
+        }
+    }
+    md
+}
+
+fn render_coverage_html(matrix: &CoverageMatrix) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Coverage</title><style>\n\
+         body{font-family:sans-serif;margin:2em}table{border-collapse:collapse}\n\
+         td,th{padding:4px 12px;border:1px solid #ddd;text-align:center}td:first-child,th:first-child{text-align:left}\n\
+         td.gap{background:#fdd;color:#a00;font-weight:bold}tr.uncategorized td{color:#777;font-style:italic}\n\
+         </style></head><body>\n",
+    );
+    html.push_str(&format!(
+        "<h1>Coverage</h1>\n<p>{} feature(s) × {} platform(s), {} gap(s).</p>\n<table><tr><th>Feature</th>",
+        matrix.features().count(),
+        matrix.platforms.len(),
+        matrix.gaps().len()
+    ));
+    for platform in &matrix.platforms {
+        html.push_str(&format!("<th>{}</th>", html_escape(platform)));
+    }
+    html.push_str("</tr>\n");
+    for feature in matrix.row_names() {
+        let uncategorized = feature.is_none();
+        html.push_str(if uncategorized { "<tr class=\"uncategorized\">" } else { "<tr>" });
+        html.push_str(&format!("<td>{}</td>", html_escape(feature.unwrap_or("uncategorized"))));
+        for platform in &matrix.platforms {
+            match matrix.count(feature, platform) {
+                0 if !uncategorized => html.push_str("<td class=\"gap\">0</td>"),
+                n => html.push_str(&format!("<td>{}</td>", n)),
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n</body></html>\n");
+    html
+}
+
+fn cmd_coverage(dir: &Path, platforms: &[String], format: &str, output: Option<&str>) -> Result<()> {
+    let matrix = coverage_matrix(dir, (!platforms.is_empty()).then_some(platforms));
+    if matrix.is_empty() {
+        bail!("No scenarios found under {}", dir.display());
+    }
+    let text = match format {
+        "html" => render_coverage_html(&matrix),
+        _ => render_coverage_markdown(&matrix),
+    };
+    match output {
+        Some(path) => {
+            fs::write(path, &text).with_context(|| format!("Cannot write {}", path))?;
+            println!("Coverage matrix written to {} ({} gap(s))", path, matrix.gaps().len());
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// recorder traceability
+// ---------------------------------------------------------------------------
+
+/// Metadata keys read for requirement ids unless `--key` is given.
+const TRACE_KEYS: &[&str] = &["requirement", "jira"];
+
+/// Which cases cover which requirement ids.
+#[derive(Debug, Default)]
+struct TraceReport {
+    /// requirement id → cases (`platform/name`) that reference it. Ids from
+    /// the requirement list are present even with no case.
+    requirements: std::collections::BTreeMap<String, Vec<String>>,
+    /// The requirement list, when one was given.
+    listed: Option<Vec<String>>,
+    /// Cases that reference no requirement.
+    untraced: Vec<String>,
+}
+
+impl TraceReport {
+    /// Listed requirements no case covers.
+    fn uncovered(&self) -> Vec<&str> {
+        self.requirements.iter().filter(|(_, cases)| cases.is_empty()).map(|(id, _)| id.as_str()).collect()
+    }
+
+    /// Ids that cases reference but the requirement list lacks (stale or
+    /// mistyped ids).
+    fn unlisted(&self) -> Vec<&str> {
+        let Some(listed) = &self.listed else { return Vec::new() };
+        self.requirements.keys().filter(|id| !listed.contains(id)).map(String::as_str).collect()
+    }
+}
+
+/// Requirement ids in a case's `keys` metadata values; one value may list
+/// several, comma-separated.
+fn requirement_ids(scenario: &Scenario, keys: &[String]) -> Vec<String> {
+    let mut ids: Vec<String> = sorted_metadata(scenario)
+        .into_iter()
+        .filter(|(k, _)| keys.iter().any(|key| key.eq_ignore_ascii_case(k)))
+        .flat_map(|(_, v)| v.split(','))
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .collect();
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+/// Map the requirement ids in every case under `dir` to the cases that
+/// reference them. With `required`, those ids are reported even when no
+/// case covers them.
+fn traceability_report(dir: &Path, keys: &[String], required: Option<Vec<String>>) -> TraceReport {
+    let mut report = TraceReport::default();
+    for id in required.iter().flatten() {
+        report.requirements.entry(id.clone()).or_default();
+    }
+    report.listed = required;
+    for (path, scenario) in walk_scenarios(dir) {
+        let scenario = match scenario {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Warning: skipping {}: {:#}", path.display(), e);
+                continue;
+            }
+        };
+        let case = format!("{}/{}", scenario.platform, scenario.name);
+        let ids = requirement_ids(&scenario, keys);
+        if ids.is_empty() {
+            report.untraced.push(case.clone());
+        }
+        for id in ids {
+            report.requirements.entry(id).or_default().push(case.clone());
+        }
+    }
+    for cases in report.requirements.values_mut() {
+        cases.sort();
+    }
+    report.untraced.sort();
+    report
+}
+
+/// Requirement ids from a file, one per line; blank lines and `#`
+/// comments are ignored.
+fn load_requirement_list(path: &str) -> Result<Vec<String>> {
+    let text = fs::read_to_string(path).with_context(|| format!("Cannot read requirement list {}", path))?;
+    let mut ids: Vec<String> = text
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or("").trim())
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect();
+    let mut seen = std::collections::HashSet::new();
+    ids.retain(|id| seen.insert(id.clone()));
+    Ok(ids)
+}
+
+/// Escape `|` so text from scenario metadata can't split a table cell.
+fn md_cell(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+fn render_traceability_markdown(report: &TraceReport) -> String {
+    let uncovered = report.uncovered();
+    let mut md = format!(
+        "# Requirement traceability\n\n{} requirement(s), {} uncovered. Uncovered requirements are marked **0**.\n\n\
+         | Requirement | Cases | Covered by |\n|---|---|---|\n",
+        report.requirements.len(),
+        uncovered.len()
+    );
+    for (id, cases) in &report.requirements {
+        let count = if cases.is_empty() { "**0**".to_string() } else { cases.len().to_string() };
+        let by = if cases.is_empty() { "—".to_string() } else { cases.join(", ") };
+        md.push_str(&format!("| {} | {} | {} |\n", md_cell(id), count, md_cell(&by)));
+    }
+    let mut section = |title: &str, items: &[&str]| {
+        if !items.is_empty() {
+            md.push_str(&format!("\n## {} ({})\n\n", title, items.len()));
+            for item in items {
+                md.push_str(&format!("- {}\n", item));
+            }
+        }
+    };
+    section("Uncovered requirements", &uncovered);
+    section("Not in the requirement list", &report.unlisted());
+    let untraced: Vec<&str> = report.untraced.iter().map(String::as_str).collect();
+    section("Cases without requirement ids", &untraced);
+    md
+}
+
+fn cmd_traceability(dir: &Path, requirements: Option<&str>, keys: &[String], output: Option<&str>) -> Result<()> {
+    let keys: Vec<String> = if keys.is_empty() { TRACE_KEYS.iter().map(|k| k.to_string()).collect() } else { keys.to_vec() };
+    let required = requirements.map(load_requirement_list).transpose()?;
+    let report = traceability_report(dir, &keys, required);
+    if report.requirements.is_empty() && report.untraced.is_empty() {
+        bail!("No scenarios found under {}", dir.display());
+    }
+    let text = render_traceability_markdown(&report);
+    match output {
+        Some(path) => {
+            fs::write(path, &text).with_context(|| format!("Cannot write {}", path))?;
+            println!("Traceability report written to {} ({} uncovered)", path, report.uncovered().len());
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// recorder export
+// ---------------------------------------------------------------------------
+
+fn cmd_export(name: &str, platform: &str, format: &str) -> Result<()> {
+    let scenario = read_scenario(platform, name)?;
+
+    match format {
+        "flow_steps" => export_flow_steps(&scenario),
+        "markdown" => export_markdown(&scenario),
+        other => bail!(
+            "Unknown export format '{}'. Supported: flow_steps, markdown",
+            other
+        ),
+    }
+}
+
+fn export_flow_steps(scenario: &Scenario) -> Result<()> {
+    #[derive(Serialize)]
+    struct FlowStepExport<'a> {
+        action: &'a str,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        args: &'a Vec<String>,
+    }
+
+    let steps: Vec<FlowStepExport<'_>> = scenario
+        .steps
+        .iter()
+        .map(|s| FlowStepExport {
+            action: &s.action,
+            args: &s.args,
+        })
+        .collect();
+
+    println!("{}", serde_json::to_string_pretty(&steps)?);
+    Ok(())
+}
+
+fn export_markdown(scenario: &Scenario) -> Result<()> {
+    print!("{}", render_markdown(scenario));
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// recorder export-catalog
+// ---------------------------------------------------------------------------
+
+/// Column mapping for [`export_catalog_csv`]: the header each field is
+/// written under, `null` to leave a column out, and how severities are
+/// named in the target tool. Read from JSON; keys left out keep the
+/// TestRail-style defaults.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CsvColumns {
+    /// `<platform>/<name>`.
+    id: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    priority: Option<String>,
+    /// All steps as one numbered, multi-line block.
+    steps: Option<String>,
+    /// Tags, comma-separated.
+    labels: Option<String>,
+    /// Severity → priority name; unmapped severities are written as is.
+    priorities: HashMap<String, String>,
+}
+
+impl Default for CsvColumns {
+    fn default() -> Self {
+        let priorities = [("blocker", "Critical"), ("critical", "High"), ("major", "Medium"), ("minor", "Low"), ("trivial", "Low")];
+        CsvColumns {
+            id: Some("ID".into()),
+            title: Some("Title".into()),
+            description: Some("Description".into()),
+            priority: Some("Priority".into()),
+            steps: Some("Steps".into()),
+            labels: Some("Labels".into()),
+            priorities: priorities.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        }
+    }
+}
+
+impl CsvColumns {
+    fn load(path: &str) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Cannot read column mapping {}", path))?;
+        serde_json::from_str(&text).with_context(|| format!("Invalid column mapping {}", path))
+    }
+
+    /// Headers of the columns that are kept, in order.
+    fn headers(&self) -> Vec<&str> {
+        [&self.id, &self.title, &self.description, &self.priority, &self.steps, &self.labels]
+            .into_iter()
+            .filter_map(|h| h.as_deref())
+            .collect()
+    }
+
+    /// (header, value) pairs for one case, in [`Self::headers`] order.
+    fn row(&self, scenario: &Scenario) -> Vec<(&str, String)> {
+        let priority = scenario
+            .severity
+            .map(|s| self.priorities.get(s.as_str()).cloned().unwrap_or_else(|| s.as_str().to_string()))
+            .unwrap_or_default();
+        let steps: Vec<String> =
+            scenario.steps.iter().enumerate().map(|(i, s)| format!("{}. {}", i + 1, step_title(s))).collect();
+        [
+            (&self.id, format!("{}/{}", scenario.platform, scenario.name)),
+            (&self.title, scenario.name.clone()),
+            (&self.description, scenario.description.clone().unwrap_or_default()),
+            (&self.priority, priority),
+            (&self.steps, steps.join("\n")),
+            (&self.labels, scenario.tags.join(",")),
+        ]
+        .into_iter()
+        .filter_map(|(header, value)| header.as_deref().map(|h| (h, value)))
+        .collect()
+    }
+}
+
+/// RFC 4180 field: quoted when it holds a comma, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn render_catalog_csv(scenarios: &[Scenario], columns: &CsvColumns) -> String {
+    let mut out = String::new();
+    let headers: Vec<String> = columns.headers().into_iter().map(csv_field).collect();
+    out.push_str(&headers.join(","));
+    out.push_str("\r\n");
+    for scenario in scenarios {
+        let cells: Vec<String> = columns.row(scenario).iter().map(|(_, v)| csv_field(v)).collect();
+        out.push_str(&cells.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// Write every case under `dir` to one CSV for a TestRail/Xray bulk
+/// import, one row per case. Files that don't parse are skipped with a
+/// warning. Returns the number of rows.
+fn export_catalog_csv(dir: &Path, out_path: &Path, columns: &CsvColumns) -> Result<usize> {
+    let mut scenarios = Vec::new();
+    for (path, scenario) in walk_scenarios(dir) {
+        match scenario {
+            Ok(s) => scenarios.push(s),
+            Err(e) => eprintln!("Warning: skipping {}: {:#}", path.display(), e),
+        }
+    }
+    scenarios.sort_by(|a, b| (&a.platform, &a.name).cmp(&(&b.platform, &b.name)));
+    fs::write(out_path, render_catalog_csv(&scenarios, columns))
+        .with_context(|| format!("Cannot write {}", out_path.display()))?;
+    Ok(scenarios.len())
+}
+
+/// Cases with at least this many steps get a table of contents.
+const MARKDOWN_TOC_MIN_STEPS: usize = 10;
+
+/// Anchor for a step, namespaced by scenario so that several exported
+/// cases can be concatenated into one document without collisions.
+fn markdown_step_anchor(scenario: &str, step: &ScenarioStep) -> String {
+    let ns: String = scenario
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    format!("{}-step-{}", ns.trim_matches('-'), step.index + 1)
+}
+
+fn step_title(step: &ScenarioStep) -> String {
+    match &step.label {
+        Some(l) => format!("{} — {}", step.action, l),
+        None if step.args.is_empty() => step.action.clone(),
+        None => format!("{} {}", step.action, step.args.join(" ")),
+    }
+}
+
+fn render_markdown(scenario: &Scenario) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::new();
+    let _ = writeln!(out, "# Scenario: {}\n", scenario.name);
+    let _ = writeln!(out, "**Platform:** {}", scenario.platform);
+    if let Some(targets) = &scenario.platforms {
+        let _ = writeln!(out, "**Runs on:** {}", targets.join(", "));
+    }
+    if let Some(desc) = &scenario.description {
+        let _ = writeln!(out, "**Description:** {}", desc);
+    }
+    if !scenario.tags.is_empty() {
+        let _ = writeln!(out, "**Tags:** {}", scenario.tags.join(", "));
+    }
+    if let Some(severity) = scenario.severity {
+        let _ = writeln!(out, "**Severity:** {}", severity.as_str());
+    }
+    let meta = sorted_metadata(scenario);
+    if !meta.is_empty() {
+        let _ = writeln!(out, "\n| Field | Value |\n|-------|-------|");
+        for (k, v) in meta {
+            let _ = writeln!(out, "| {} | {} |", k, v);
+        }
+    }
+    if !scenario.preconditions.is_empty() {
+        let _ = writeln!(out, "\n## Preconditions\n");
+        for p in &scenario.preconditions {
+            match p {
+                Precondition::Text(text) => {
+                    let _ = writeln!(out, "- {}", text);
+                }
+                Precondition::Fixture { fixture } => {
+                    let _ = writeln!(out, "- fixture `{}`", fixture);
+                }
+            }
+        }
+    }
+    let runs = group_runs(&scenario.steps);
+    if scenario.steps.len() >= MARKDOWN_TOC_MIN_STEPS {
+        let _ = writeln!(out, "\n## Contents\n");
+        for (group, steps) in &runs {
+            let indent = if let Some(g) = group {
+                let _ = writeln!(out, "- **{}**", html_escape(g));
+                "  "
+            } else {
+                ""
+            };
+            for step in steps {
+                let anchor = markdown_step_anchor(&scenario.name, step);
+                let _ = writeln!(out, "{}- [{}. {}](#{})", indent, step.index + 1, step_title(step), anchor);
+            }
+        }
+    }
+    let _ = writeln!(out, "\n## Steps\n");
+    for (index, group) in split_groups(&scenario.steps) {
+        let _ = writeln!(out, "> Note: group '{}' resumes at step {} after other steps.\n", html_escape(group), index + 1);
+    }
+    // Group names are free text placed in HTML tags; escape them so a name
+    // like `Login <beta>` cannot break the section markup.
+    for (group, steps) in runs {
+        if let Some(g) = group {
+            let plural = if steps.len() == 1 { "" } else { "s" };
+            let _ = writeln!(
+                out,
+                "<details open>\n<summary><b>{}</b> ({} step{})</summary>\n",
+                html_escape(g),
+                steps.len(),
+                plural
+            );
+        }
+        for step in steps {
+            render_markdown_step(&mut out, &scenario.name, step);
+        }
+        if group.is_some() {
+            let _ = writeln!(out, "\n</details>\n");
+        }
+    }
+    out
+}
+
+fn render_markdown_step(out: &mut String, scenario: &str, step: &ScenarioStep) {
+    use std::fmt::Write;
+
+    let label = step
+        .label
+        .as_deref()
+        .map(|l| format!(" — {}", l))
+        .unwrap_or_default();
+    let args_str = if step.args.is_empty() {
+        String::new()
+    } else {
+        format!(" `{}`", step.args.join(", "))
+    };
+    let xfail = if step.expect_failure.unwrap_or(false) {
+        " _(expected failure)_"
+    } else {
+        ""
+    };
+    let soft = if step.soft.unwrap_or(false) { " _(soft)_" } else { "" };
+    let manual = if step.manual.unwrap_or(false) { " _(manual)_" } else { "" };
+    let id = step.id.as_deref().map(|id| format!(" `[{}]`", id)).unwrap_or_default();
+    let when = step.when.as_deref().map(|c| format!(" _(only when {})_", c)).unwrap_or_default();
+    let only_on = step.only_on.as_ref().map(|p| format!(" _(only on {})_", p.join(", "))).unwrap_or_default();
+    // The anchor sits inside the list item so the numbering stays intact.
+    let _ = writeln!(
+        out,
+        "{}. <a id=\"{}\"></a>**{}**{}{}{}{}{}{}{}{}",
+        step.index + 1,
+        markdown_step_anchor(scenario, step),
+        step.action,
+        args_str,
+        id,
+        label,
+        xfail,
+        soft,
+        manual,
+        when,
+        only_on
+    );
+}
+
+// ---------------------------------------------------------------------------
+// recorder import
+// ---------------------------------------------------------------------------
+
+/// Cells of a `| a | b |` table row, or `None` for non-table lines.
+fn table_cells(line: &str) -> Option<Vec<String>> {
+    let inner = line.trim().strip_prefix('|')?;
+    let inner = inner.strip_suffix('|').unwrap_or(inner);
+    Some(inner.split('|').map(|c| c.trim().to_owned()).collect())
+}
+
+fn is_separator_row(cells: &[String]) -> bool {
+    cells.iter().all(|c| !c.is_empty() && c.chars().all(|ch| matches!(ch, '-' | ':' | ' ')))
+}
+
+/// Drop bold/italic/code markers around a cell or value.
+fn strip_markdown(s: &str) -> String {
+    s.replace("**", "").replace('`', "").trim().trim_matches('_').trim().to_owned()
+}
+
+/// `**Key:** value` or `Key: value` outside tables.
+fn metadata_line(line: &str) -> Option<(String, String)> {
+    let line = line.trim().trim_start_matches("- ");
+    let (key, value) = line.split_once(':')?;
+    let key = strip_markdown(key);
+    let value = strip_markdown(value);
+    let plain_key = !key.is_empty() && key.len() <= 40 && key.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '_' | '-'));
+    (plain_key && !value.is_empty()).then_some((key, value))
+}
+
+/// Split the Action cell into action name and args. Coordinates-style
+/// actions take whitespace/comma separated args; others take the rest verbatim.
+fn parse_action_cell(cell: &str, args_cell: Option<&str>) -> (String, Vec<String>) {
+    let cell = strip_markdown(cell);
+    let (action, rest) = cell.split_once(char::is_whitespace).unwrap_or((&cell, ""));
+    let action = action.to_ascii_lowercase();
+    let split_list = |s: &str| -> Vec<String> {
+        s.split(',').map(|a| a.trim().trim_matches('"').to_owned()).filter(|a| !a.is_empty()).collect()
+    };
+    let args = match args_cell.map(strip_markdown) {
+        Some(args) if !args.is_empty() => split_list(&args),
+        _ if rest.trim().is_empty() => Vec::new(),
+        _ if matches!(action.as_str(), "tap" | "swipe" | "wait") => {
+            rest.split(|c: char| c.is_whitespace() || c == ',').filter(|a| !a.is_empty()).map(str::to_owned).collect()
+        }
+        _ => vec![rest.trim().trim_matches('"').to_owned()],
+    };
+    (action, args)
+}
+
+/// Build a scenario from a Markdown test case: metadata lines or a
+/// `| Field | Value |` table, then a steps table with an `Action` column.
+///
+/// Column and key names are matched case-insensitively and unknown columns
+/// are ignored. `Expected` becomes the step label; `Args` and `Id` are
+/// optional. Unknown metadata keys go into `metadata`.
+fn parse_markdown_scenario(text: &str, fallback_name: &str) -> Result<Scenario> {
+    let mut name: Option<String> = None;
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut steps: Vec<ScenarioStep> = Vec::new();
+    let mut found_steps_table = false;
+
+    let lines: Vec<&str> = text.lines().collect();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim();
+        if let Some(title) = line.strip_prefix("# ") {
+            let title = strip_markdown(title);
+            let title = title.split_once(':').filter(|(k, _)| k.eq_ignore_ascii_case("scenario")).map_or(title.as_str(), |(_, t)| t).trim();
+            name.get_or_insert_with(|| title.to_owned());
+            i += 1;
+            continue;
+        }
+        let Some(header) = table_cells(line) else {
+            if !line.starts_with('#') {
+                if let Some(kv) = metadata_line(line) {
+                    fields.push(kv);
+                }
+            }
+            i += 1;
+            continue;
+        };
+
+        // Collect the table body.
+        let header: Vec<String> = header.iter().map(|h| strip_markdown(h).to_ascii_lowercase()).collect();
+        let mut rows: Vec<(usize, Vec<String>)> = Vec::new();
+        i += 1;
+        while i < lines.len() {
+            let Some(cells) = table_cells(lines[i]) else { break };
+            if !is_separator_row(&cells) {
+                rows.push((i + 1, cells));
+            }
+            i += 1;
+        }
+
+        let col = |names: &[&str]| header.iter().position(|h| names.contains(&h.as_str()));
+        let Some(action_col) = col(&["action", "actions", "step action"]) else {
+            // A two-column table before the steps is metadata.
+            if header.len() >= 2 && !found_steps_table {
+                fields.extend(rows.into_iter().map(|(_, r)| (strip_markdown(&r[0]), strip_markdown(r.get(1).map_or("", String::as_str)))));
+            }
+            continue;
+        };
+        if found_steps_table {
+            bail!("More than one steps table (a table with an Action column)");
+        }
+        found_steps_table = true;
+        let expected_col = col(&["expected", "expected result", "expected results", "expectation"]);
+        let args_col = col(&["args", "arguments"]);
+        let id_col = col(&["id"]);
+        for (line_no, row) in rows {
+            let cell = |c: Option<usize>| c.and_then(|c| row.get(c)).map(String::as_str);
+            let Some(action_cell) = cell(Some(action_col)).filter(|c| !strip_markdown(c).is_empty()) else {
+                continue;
+            };
+            let (action, args) = parse_action_cell(action_cell, cell(args_col));
+            if !PLAY_ACTIONS.contains(&action.as_str()) {
+                bail!(
+                    "Line {}: unknown action '{}'. Supported: {}",
+                    line_no,
+                    action,
+                    PLAY_ACTIONS.join(", ")
+                );
+            }
+            steps.push(ScenarioStep {
+                index: steps.len(),
+                step_type: "gesture".to_owned(),
+                action,
+                args,
+                label: cell(expected_col).map(strip_markdown).filter(|e| !e.is_empty()),
+                id: cell(id_col).map(strip_markdown).filter(|e| !e.is_empty()),
+                ..Default::default()
+            });
+        }
+    }
+
+    if !found_steps_table {
+        bail!("No steps table found: expected a Markdown table with an 'Action' column");
+    }
+    if steps.is_empty() {
+        bail!("The steps table has no rows");
+    }
+    validate_steps(&steps)?;
+
+    let mut scenario = Scenario {
+        version: 1,
+        name: name.unwrap_or_else(|| fallback_name.to_owned()),
+        platform: "android".to_owned(),
+        description: None,
+        tags: Vec::new(),
+        metadata: None,
+        severity: None,
+        requires: None,
+        approved_hash: None,
+        estimated_duration_secs: None,
+        linked_feature: None,
+        platforms: None,
+        preconditions: Vec::new(),
+        depends_on: None,
+        before: None,
+        after: None,
+        checks: None,
+        steps,
+        created_at: now_iso8601(),
+        updated_at: now_iso8601(),
+    };
+    let mut metadata = HashMap::new();
+    for (key, value) in fields {
+        match key.to_ascii_lowercase().as_str() {
+            "name" | "scenario" => scenario.name = value,
+            "platform" => scenario.platform = value.to_ascii_lowercase(),
+            "description" => scenario.description = Some(value),
+            "tags" => scenario.tags = value.split(',').map(|t| t.trim().to_owned()).filter(|t| !t.is_empty()).collect(),
+            "severity" | "priority" => scenario.severity = Some(Severity::parse(&value.to_ascii_lowercase())?),
+            "" => {}
+            _ => {
+                metadata.insert(key, value);
+            }
+        }
+    }
+    scenario.metadata = (!metadata.is_empty()).then_some(metadata);
+    Ok(scenario)
+}
+
+/// Read a Markdown test case (see [`parse_markdown_scenario`]).
+fn import_markdown(path: &Path) -> Result<Scenario> {
+    let text = fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    parse_markdown_scenario(&text, &stem).with_context(|| format!("Cannot import {}", path.display()))
+}
+
+fn cmd_import(file: &str, platform: Option<&str>, name: Option<&str>, force: bool) -> Result<()> {
+    let mut scenario = import_markdown(Path::new(file))?;
+    if let Some(p) = platform {
+        scenario.platform = p.to_owned();
+    }
+    if let Some(n) = name {
+        scenario.name = n.to_owned();
+    }
+    if !["android", "ios", "aurora", "desktop"].contains(&scenario.platform.as_str()) {
+        bail!("Unsupported platform '{}'", scenario.platform);
+    }
+    if scenario.name.is_empty() || scenario.name.contains(['/', '\\']) || scenario.name.starts_with('.') {
+        bail!("Invalid scenario name '{}'; pass --name", scenario.name);
+    }
+    let path = scenario_path(&scenario.platform, &scenario.name)?;
+    if path.exists() && !force {
+        bail!("Scenario '{}' already exists for '{}'; use --force to overwrite", scenario.name, scenario.platform);
+    }
+    print_coherence_warnings(&validate_coherence(&scenario, &[]));
+    write_scenario(&scenario)?;
+    println!("Imported '{}' ({} steps) -> {}", scenario.name, scenario.steps.len(), path.display());
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// recorder scaffold
+// ---------------------------------------------------------------------------
+
+/// Case id for `title`: lowercase kebab, keeping letters and digits of any
+/// script, with `-2`, `-3`, … appended while it is in `taken`.
+fn case_id(title: &str, taken: &HashSet<String>) -> String {
+    let mut base = String::new();
+    for c in title.trim().chars() {
+        if c.is_alphanumeric() {
+            base.extend(c.to_lowercase());
+        } else if !base.is_empty() && !base.ends_with('-') {
+            base.push('-');
+        }
+    }
+    let base = match base.trim_end_matches('-') {
+        "" => "case".to_owned(),
+        b => b.to_owned(),
+    };
+    (1..)
+        .map(|n| if n == 1 { base.clone() } else { format!("{}-{}", base, n) })
+        .find(|id| !taken.contains(id))
+        .unwrap_or(base)
+}
+
+/// `git config user.name`, else the login name.
+fn detect_author() -> Option<String> {
+    let git = std::process::Command::new("git")
+        .args(["config", "user.name"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_owned());
+    git.into_iter()
+        .chain(["USER", "USERNAME"].iter().filter_map(|v| std::env::var(v).ok()))
+        .find(|name| !name.trim().is_empty())
+}
+
+/// A new case for `platform` titled `name`, with an id not yet used in
+/// `dir`, the author in `metadata` and one manual placeholder step. The
+/// skeleton goes through the same checks as a loaded scenario.
+fn new_scenario(platform: &str, name: &str, dir: &Path, author: Option<String>) -> Result<Scenario> {
+    if !PLATFORMS.contains(&platform) {
+        bail!("Unsupported platform '{}' (expected one of {})", platform, PLATFORMS.join(", "));
+    }
+    if name.trim().is_empty() {
+        bail!("Case name must not be empty");
+    }
+    // Ids are unique across the whole catalog, subfolders included.
+    let taken: HashSet<String> = walk_scenarios(dir)
+        .into_iter()
+        .flat_map(|(path, scenario)| {
+            let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned());
+            stem.into_iter().chain(scenario.ok().map(|s| s.name))
+        })
+        .collect();
+    let now = now_iso8601();
+    let scenario = Scenario {
+        version: 1,
+        name: case_id(name, &taken),
+        platform: platform.to_owned(),
+        description: Some(name.trim().to_owned()),
+        tags: Vec::new(),
+        metadata: author.map(|a| HashMap::from([("author".to_owned(), a)])),
+        severity: None,
+        requires: None,
+        approved_hash: None,
+        estimated_duration_secs: None,
+        linked_feature: None,
+        platforms: None,
+        preconditions: Vec::new(),
+        depends_on: None,
+        before: None,
+        after: None,
+        checks: None,
+        steps: vec![ScenarioStep {
+            index: 0,
+            step_type: "gesture".to_owned(),
+            action: "tap-text".to_owned(),
+            args: vec!["TODO".to_owned()],
+            timestamp_ms: 0,
+            delay_before_ms: 0,
+            label: Some("TODO: replace with the case's steps and expected results".to_owned()),
+            expect_failure: None,
+            soft: None,
+            id: None,
+            when: None,
+            group: None,
+            only_on: None,
+            manual: Some(true),
+        }],
+        created_at: now.clone(),
+        updated_at: now,
+    };
+    parse_scenario(&serde_json::to_string(&scenario)?, &ScenarioDefaults::default())
+        .context("Generated skeleton is not a valid scenario")
+}
+
+fn cmd_scaffold(name: &str, platform: &str, dir: Option<&str>) -> Result<()> {
+    let dir = match dir {
+        Some(d) => PathBuf::from(d),
+        None => scenarios_dir(platform)?,
+    };
+    let scenario = new_scenario(platform, name, &dir, detect_author())?;
+    fs::create_dir_all(&dir).with_context(|| format!("Cannot create scenario directory {}", dir.display()))?;
+    let path = dir.join(format!("{}.json", scenario.name));
+    let text = normalized_json(&serde_json::to_string(&scenario)?)?;
+    fs::write(&path, text).with_context(|| format!("Cannot write scenario to {}", path.display()))?;
+    println!("Created '{}' -> {}", scenario.name, path.display());
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Options for a plain local play; tests override what they exercise.
+    fn play_options() -> PlayOptions {
+        PlayOptions {
+            speed: 1.0,
+            stop_on_fail: false,
+            step_timeout: None,
+            max_duration: None,
+            from_step: None,
+            to_step: None,
+            fixtures: true,
+            dry_run: false,
+            config: HashMap::new(),
+            perf_package: None,
+            perf_interval_ms: 1000,
+            strings: None,
+            baselines: PathBuf::new(),
+            update_baselines: false,
+            watchdog: None,
+            interactive: false,
+            remote_device: None,
+            allow_hooks: false,
+            dialogs: None,
+            deadline: None,
+            screenshots: None,
+        }
+    }
+
+    #[test]
+    fn test_scenario_serialization_round_trip() {
+        let scenario = Scenario {
+            version: 1,
+            name: "login-flow".into(),
+            platform: "android".into(),
+            description: Some("Login test".into()),
+            tags: vec!["smoke".into()],
+            metadata: None,
+            severity: None,
+            requires: None,
+            approved_hash: None,
+            estimated_duration_secs: None,
+            linked_feature: None,
+            platforms: None,
+            preconditions: Vec::new(),
+            depends_on: None,
+            before: None,
+            after: None,
+            checks: None,
+            steps: vec![ScenarioStep {
+                index: 0,
+                step_type: "gesture".into(),
+                action: "tap".into(),
+                args: vec!["100".into(), "200".into()],
+                timestamp_ms: 1_000_000,
+                delay_before_ms: 0,
+                label: None,
+                expect_failure: None,
+                soft: None,
+                id: None,
+                when: None,
+                group: None,
+                only_on: None,
+                manual: None,
+            }],
+            created_at: "2026-05-27T12:00:00Z".into(),
+            updated_at: "2026-05-27T12:00:00Z".into(),
+        };
+
+        let json = serde_json::to_string(&scenario).unwrap();
+        let parsed: Scenario = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed.name, "login-flow");
+        assert_eq!(parsed.platform, "android");
+        assert_eq!(parsed.steps.len(), 1);
+        assert_eq!(parsed.steps[0].action, "tap");
+        assert_eq!(parsed.steps[0].args, vec!["100", "200"]);
+        assert_eq!(parsed.version, 1);
+    }
+
+    #[test]
+    fn test_recording_state_serialization() {
+        let state = RecordingState {
+            name: "test-rec".into(),
+            platform: "ios".into(),
+            description: None,
+            tags: vec![],
+            metadata: None,
+            linked_feature: None,
+            severity: None,
+            steps: vec![],
+            started_at: "2026-05-27T10:00:00Z".into(),
+        };
+
+        let json = serde_json::to_string(&state).unwrap();
+        let parsed: RecordingState = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.name, "test-rec");
+        assert_eq!(parsed.platform, "ios");
+        assert!(parsed.steps.is_empty());
+    }
+
+    #[test]
+    fn test_now_iso8601_format() {
+        let ts = now_iso8601();
+        // Should match "YYYY-MM-DDTHH:MM:SSZ"
+        assert_eq!(ts.len(), 20);
+        assert!(ts.ends_with('Z'));
+        assert!(ts.contains('T'));
+    }
+
+    #[test]
+    fn test_epoch_to_datetime_unix_epoch() {
+        let (y, mo, d, h, mi, s) = epoch_to_datetime(0);
+        assert_eq!(y, 1970);
+        assert_eq!(mo, 1);
+        assert_eq!(d, 1);
+        assert_eq!(h, 0);
+        assert_eq!(mi, 0);
+        assert_eq!(s, 0);
+    }
+
+    #[test]
+    fn test_epoch_to_datetime_known_date() {
+        // 2026-05-27T00:00:00Z = 1_779_840_000 seconds (UTC)
+        let secs = 1_779_840_000u64;
+        let (y, mo, d, _h, _mi, _s) = epoch_to_datetime(secs);
+        assert_eq!(y, 2026);
+        assert_eq!(mo, 5);
+        assert_eq!(d, 27);
+    }
+
+    #[test]
+    fn test_is_leap_year() {
+        assert!(is_leap(2000));
+        assert!(is_leap(2024));
+        assert!(!is_leap(1900));
+        assert!(!is_leap(2023));
+    }
+
+    #[test]
+    fn test_remove_step_reindex() {
+        let steps = vec![
+            ScenarioStep {
+                index: 0,
+                step_type: "gesture".into(),
+                action: "tap".into(),
+                args: vec![],
+                timestamp_ms: 0,
+                delay_before_ms: 0,
+                label: None,
+                expect_failure: None,
+                soft: None,
+                id: None,
+                when: None,
+                group: None,
+                only_on: None,
+                manual: None,
+            },
+            ScenarioStep {
+                index: 1,
+                step_type: "gesture".into(),
+                action: "swipe".into(),
+                args: vec![],
+                timestamp_ms: 0,
+                delay_before_ms: 0,
+                label: None,
+                expect_failure: None,
+                soft: None,
+                id: None,
+                when: None,
+                group: None,
+                only_on: None,
+                manual: None,
+            },
+            ScenarioStep {
+                index: 2,
+                step_type: "input".into(),
+                action: "input".into(),
+                args: vec![],
+                timestamp_ms: 0,
+                delay_before_ms: 0,
+                label: None,
+                expect_failure: None,
+                soft: None,
+                id: None,
+                when: None,
+                group: None,
+                only_on: None,
+                manual: None,
+            },
+        ];
+
+        let mut modified = steps;
+        modified.remove(1); // remove "swipe"
+        for (i, step) in modified.iter_mut().enumerate() {
+            step.index = i;
+        }
+
+        assert_eq!(modified.len(), 2);
+        assert_eq!(modified[0].action, "tap");
+        assert_eq!(modified[0].index, 0);
+        assert_eq!(modified[1].action, "input");
+        assert_eq!(modified[1].index, 1);
+    }
+
+    #[test]
+    fn test_tags_parsing() {
+        let tags_str = "smoke, regression, login";
+        let tags: Vec<String> = tags_str
+            .split(',')
+            .map(|t| t.trim().to_owned())
+            .filter(|t| !t.is_empty())
+            .collect();
+        assert_eq!(tags, vec!["smoke", "regression", "login"]);
+    }
+
+    #[test]
+    fn test_recording_tmp_path() {
+        let path = recording_tmp_path("my-flow");
+        assert_eq!(
+            path,
+            PathBuf::from("/tmp/claude-mobile-recording-my-flow.json")
+        );
+    }
+
+    #[test]
+    fn test_play_step_range_validation() {
+        // from >= to should fail
+        let from = 5usize.saturating_sub(1); // 4
+        let to = 3usize;
+        assert!(from >= to);
+    }
+
+    #[test]
+    fn test_step_outcome_classify() {
+        assert_eq!(StepOutcome::classify(true, false), StepOutcome::Passed);
+        assert_eq!(StepOutcome::classify(false, true), StepOutcome::ExpectedFailure);
+        assert_eq!(StepOutcome::classify(true, true), StepOutcome::UnexpectedPass);
+        assert_eq!(StepOutcome::classify(false, false), StepOutcome::Failed);
+        assert!(StepOutcome::ExpectedFailure.is_pass());
+        assert!(!StepOutcome::UnexpectedPass.is_pass());
+    }
+
+    #[test]
+    fn test_expect_failure_serialization() {
+        let step: ScenarioStep = serde_json::from_str(
+            r#"{"index":0,"type":"assertion","action":"tap-text","args":["Delete"],"timestampMs":0,"expectFailure":true}"#,
+        )
+        .unwrap();
+        assert_eq!(step.expect_failure, Some(true));
+
+        let plain = ScenarioStep { action: "tap".into(), ..Default::default() };
+        let json = serde_json::to_string(&plain).unwrap();
+        assert!(!json.contains("expectFailure"));
+    }
+
+    #[test]
+    fn test_step_ids_and_conditions() {
+        let step = |index: usize, id: Option<&str>, when: Option<&str>| ScenarioStep {
+            index,
+            action: "tap".into(),
+            id: id.map(Into::into),
+            when: when.map(Into::into),
+            ..Default::default()
+        };
+        let ok = [step(0, Some("login"), None), step(1, None, Some("login.failed")), step(2, Some("retry_2"), None)];
+        validate_steps(&ok).unwrap();
+        assert_eq!(ok[0].key(), "login");
+        assert_eq!(ok[1].key(), "#2");
+
+        let dup = [step(0, Some("a"), None), step(1, Some("a"), None)];
+        assert!(validate_steps(&dup).unwrap_err().to_string().contains("duplicate step id 'a'"));
+        // References must point backwards, to an existing id.
+        let forward = [step(0, None, Some("b.passed")), step(1, Some("b"), None)];
+        assert!(validate_steps(&forward).unwrap_err().to_string().contains("not an earlier step id"));
+        assert!(validate_steps(&[step(0, Some("has space"), None)]).is_err());
+
+        assert_eq!(parse_step_condition("open.menu.passed").unwrap(), ("open.menu", true));
+        assert!(parse_step_condition("login").is_err());
+        assert!(parse_step_condition(".failed").is_err());
+
+        let json = serde_json::to_string(&step(0, None, None)).unwrap();
+        assert!(!json.contains("\"id\"") && !json.contains("when"));
+    }
+
+    #[test]
+    fn test_resolve_vars_env_config_and_default() {
+        let env = |name: &str| (name == "BASE_URL").then(|| "https://staging.example.com".to_owned());
+        let mut config = HashMap::new();
+        config.insert("user".to_owned(), "qa@example.com".to_owned());
+
+        assert_eq!(resolve_vars_with("${BASE_URL}/login", &config, env).unwrap(), "https://staging.example.com/login");
+        assert!(resolve_vars_with("${HOME_URL}", &config, env).is_err());
+        assert_eq!(resolve_vars("${config.user}", &config).unwrap(), "qa@example.com");
+        assert_eq!(resolve_vars("${CIM_TEST_UNSET_VAR:-fallback}", &config).unwrap(), "fallback");
+        assert_eq!(resolve_vars("${config.missing:-}", &config).unwrap(), "");
+        assert_eq!(resolve_vars("no refs here", &config).unwrap(), "no refs here");
+    }
+
+    #[test]
+    fn test_resolve_vars_unset_fails_fast() {
+        let config = HashMap::new();
+        let err = resolve_vars("${CIM_TEST_UNSET_VAR}", &config).unwrap_err();
+        assert!(err.to_string().contains("CIM_TEST_UNSET_VAR"));
+        let err = resolve_vars("${config.password}", &config).unwrap_err();
+        assert!(err.to_string().contains("--config password=<value>"));
+    }
+
+    #[test]
+    fn test_shown_args_masks_unlock_pin() {
+        let step = |action: &str, args: &[&str]| ScenarioStep {
+            action: action.into(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        };
+        assert_eq!(shown_args(&step("unlock", &["1234"])), ["***"]);
+        assert!(shown_args(&step("unlock", &[])).is_empty());
+        assert_eq!(shown_args(&step("tap-text", &["1234"])), ["1234"]);
+    }
+
+    #[test]
+    fn test_resolve_step_vars_reports_step_index() {
+        let mut step = ScenarioStep {
+            index: 2,
+            action: "input".into(),
+            args: vec!["${CIM_TEST_UNSET_VAR}".into()],
+            ..Default::default()
+        };
+        let err = resolve_step_vars(&mut step, &HashMap::new()).unwrap_err();
+        assert!(err.to_string().starts_with("Step 3"));
+    }
+
+    #[test]
+    fn test_export_flow_steps_format() {
+        let scenario = Scenario {
+            version: 1,
+            name: "test".into(),
+            platform: "android".into(),
+            description: None,
+            tags: vec![],
+            metadata: None,
+            severity: None,
+            requires: None,
+            approved_hash: None,
+            estimated_duration_secs: None,
+            linked_feature: None,
+            platforms: None,
+            preconditions: Vec::new(),
+            depends_on: None,
+            before: None,
+            after: None,
+            checks: None,
+            steps: vec![
+                ScenarioStep {
+                    index: 0,
+                    step_type: "gesture".into(),
+                    action: "tap".into(),
+                    args: vec!["10".into(), "20".into()],
+                    timestamp_ms: 0,
+                    delay_before_ms: 0,
+                    label: None,
+                    expect_failure: None,
+                    soft: None,
+                    id: None,
+                    when: None,
+                    group: None,
+                    only_on: None,
+                    manual: None,
+                },
+            ],
+            created_at: "2026-05-27T00:00:00Z".into(),
+            updated_at: "2026-05-27T00:00:00Z".into(),
+        };
+        // Just verify export_flow_steps doesn't panic on valid data.
+        // It prints to stdout so we can't capture it in a unit test easily,
+        // but structural correctness is covered by serialization tests.
+        assert_eq!(scenario.steps[0].action, "tap");
+    }
+
+    #[test]
+    fn test_metadata_passthrough_and_filter() {
+        let json = r#"{"version":1,"name":"checkout","platform":"android",
+            "metadata":{"jira_id":"SHOP-12","risk_level":"high"},
+            "createdAt":"2026-05-27T12:00:00Z","updatedAt":"2026-05-27T12:00:00Z"}"#;
+        let scenario: Scenario = serde_json::from_str(json).unwrap();
+        let meta = scenario.metadata.as_ref().unwrap();
+        assert_eq!(meta.get("jira_id").map(String::as_str), Some("SHOP-12"));
+
+        let reserialized = serde_json::to_string(&scenario).unwrap();
+        assert!(reserialized.contains("\"risk_level\":\"high\""));
+
+        let filter = parse_meta_pairs(&["risk_level=high".into()]).unwrap();
+        assert!(matches_meta(&scenario, &filter));
+        let filter = parse_meta_pairs(&["risk_level=low".into()]).unwrap();
+        assert!(!matches_meta(&scenario, &filter));
+        assert!(parse_meta_pairs(&["no-equals".into()]).is_err());
+
+        // Metadata stays optional: absent on input, absent on output.
+        let bare: Scenario = serde_json::from_str(
+            r#"{"version":1,"name":"a","platform":"ios","createdAt":"x","updatedAt":"x"}"#,
+        )
+        .unwrap();
+        assert!(bare.metadata.is_none());
+        assert!(!serde_json::to_string(&bare).unwrap().contains("metadata"));
+        assert!(matches_meta(&bare, &[]));
+    }
+
+    #[test]
+    fn test_severity_filter_and_failure_counts() {
+        let json = r#"{"version":1,"name":"pay","platform":"android","severity":"critical",
+            "createdAt":"x","updatedAt":"x"}"#;
+        let scenario: Scenario = serde_json::from_str(json).unwrap();
+        assert_eq!(scenario.severity, Some(Severity::Critical));
+        let bad = json.replace("critical", "urgent");
+        assert!(parse_scenario(&bad, &ScenarioDefaults::default()).is_err());
+
+        let filter = ScenarioFilter::new(None, None, &[], &["blocker".into(), "critical".into()]).unwrap();
+        assert!(filter.matches("android", &scenario));
+        let filter = ScenarioFilter::new(None, None, &[], &["minor".into()]).unwrap();
+        assert!(!filter.matches("android", &scenario));
+        assert!(ScenarioFilter::default().matches("android", &scenario));
+        assert!(ScenarioFilter::new(None, None, &[], &["p1".into()]).is_err());
+
+        let case = |status, severity| CaseResult { severity, ..fake_case("x", status) };
+        let cases = [
+            case(CaseStatus::Failed, None),
+            case(CaseStatus::Failed, Some(Severity::Minor)),
+            case(CaseStatus::Failed, Some(Severity::Blocker)),
+            case(CaseStatus::Passed, Some(Severity::Blocker)),
+        ];
+        assert_eq!(failures_by_severity(&cases), vec![("blocker", 1), ("minor", 1), ("unset", 1)]);
+    }
+
+    #[test]
+    fn test_validate_coherence_warnings() {
+        let step = |index: usize, action: &str, args: &[&str]| ScenarioStep {
+            index,
+            action: action.into(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        };
+        let scenario = |steps: Vec<ScenarioStep>| Scenario {
+            version: 1,
+            name: "s".into(),
+            platform: "android".into(),
+            description: None,
+            tags: vec![],
+            metadata: None,
+            severity: None,
+            requires: None,
+            approved_hash: None,
+            estimated_duration_secs: None,
+            linked_feature: None,
+            platforms: None,
+            preconditions: Vec::new(),
+            depends_on: None,
+            before: None,
+            after: None,
+            checks: None,
+            steps,
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+
+        let good = scenario(vec![
+            step(0, "launch", &["com.example"]),
+            step(1, "tap-text", &["Email"]),
+            step(2, "input", &["a@b.c"]),
+            step(3, "key", &["KEYCODE_COPY"]),
+            step(4, "key", &["paste"]),
+        ]);
+        assert!(validate_coherence(&good, &[]).is_empty());
+
+        let bad = scenario(vec![
+            step(0, "key", &["PASTE"]),
+            step(1, "tap-text", &["Email"]),
+            step(2, "back", &[]),
+            step(3, "input", &["lost"]),
+            step(4, "stop", &["com.example"]),
+            step(5, "tap", &["1", "2"]),
+        ]);
+        let warnings = validate_coherence(&bad, &[]);
+        let indices: Vec<usize> = warnings.iter().map(|w| w.index).collect();
+        assert_eq!(indices, vec![0, 3, 5]);
+        assert!(warnings[2].message.contains("com.example"));
+
+        let login = Fixture {
+            name: "login".into(),
+            description: None,
+            steps: vec![step(0, "launch", &["com.example"]), step(1, "tap-text", &["Sign in"])],
+        };
+        let repeated = scenario(vec![
+            step(0, "launch", &["com.example"]),
+            step(1, "tap-text", &["Sign in"]),
+            step(2, "tap-text", &["Cart"]),
+        ]);
+        let warnings = validate_coherence(&repeated, std::slice::from_ref(&login));
+        assert_eq!(
+            warnings,
+            [CoherenceWarning {
+                index: 0,
+                message: "steps 1-2 repeat precondition fixture 'login', which already runs before the scenario".into(),
+            }]
+        );
+        // A fixture longer than the scenario, or differing in its arguments, is not a repeat.
+        let short = scenario(vec![step(0, "launch", &["com.example"])]);
+        assert!(validate_coherence(&short, std::slice::from_ref(&login)).is_empty());
+        let other = scenario(vec![step(0, "launch", &["com.example"]), step(1, "tap-text", &["Sign up"])]);
+        assert!(validate_coherence(&other, &[login]).is_empty());
+    }
+
+    #[test]
+    fn test_string_keys_resolve_per_locale() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let path = dir.join("strings.json");
+        fs::write(
+            &path,
+            r#"{"en":{"login.welcome":"Welcome"},"de":{"login.welcome":"Willkommen"},"fr":{}}"#,
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+
+        let steps = || {
+            vec![ScenarioStep {
+                index: 0,
+                action: "tap-text".into(),
+                args: vec!["@key:login.welcome".into()],
+                ..Default::default()
+            }]
+        };
+        let de = load_string_table(path, Some("de")).unwrap();
+        let mut resolved = steps();
+        resolve_string_keys(&mut resolved, Some(&de)).unwrap();
+        assert_eq!(resolved[0].args, vec!["Willkommen"]);
+
+        let fr = load_string_table(path, Some("fr")).unwrap();
+        let err = resolve_string_keys(&mut steps(), Some(&fr)).unwrap_err().to_string();
+        assert!(err.contains("login.welcome (step 1)"), "{}", err);
+        assert!(resolve_string_keys(&mut steps(), None).is_err());
+        assert!(load_string_table(path, None).unwrap_err().to_string().contains("--locale"));
+        assert!(load_string_table(path, Some("ja")).is_err());
+
+        let launch = ScenarioStep { action: "launch".into(), args: vec!["com.example".into()], ..Default::default() };
+        assert_eq!(launched_package(&[steps().remove(0), launch]), Some("com.example"));
+        assert_eq!(launched_package(&steps()), None);
+    }
+
+    fn fake_case(name: &str, status: CaseStatus) -> CaseResult {
+        CaseResult { duration_ms: 1, ..CaseResult::new(name, status) }
+    }
+
+    #[test]
+    fn test_suite_fail_fast_skips_rest_but_runs_teardown() {
+        let names: Vec<String> = vec!["a".into(), "b".into(), "c".into()];
+        let mut played = Vec::new();
+        let (cases, teardown) = run_suite_cases(
+            &names,
+            FailureMode::FailFast,
+            0,
+            |n| {
+                played.push(n.to_owned());
+                fake_case(n, if n == "a" { CaseStatus::Failed } else { CaseStatus::Passed })
+            },
+            Some("cleanup"),
+            None,
+            &mut NoProgress,
+        );
+        assert_eq!(played, vec!["a", "cleanup"]);
+        assert_eq!(cases[0].status, CaseStatus::Failed);
+        assert_eq!(cases[1].status, CaseStatus::Skipped);
+        assert_eq!(cases[2].status, CaseStatus::Skipped);
+        assert_eq!(teardown.unwrap().name, "cleanup");
+    }
+
+    #[test]
+    fn test_suite_reruns_failed_cases_before_teardown() {
+        let names: Vec<String> = vec!["stable".into(), "flaky".into(), "broken".into()];
+        let mut played: Vec<String> = Vec::new();
+        let (cases, _) = run_suite_cases(
+            &names,
+            FailureMode::ContinueOnFailure,
+            2,
+            |n| {
+                played.push(n.to_owned());
+                let tries = played.iter().filter(|p| *p == n).count();
+                let status = match n {
+                    "stable" | "cleanup" => CaseStatus::Passed,
+                    "flaky" if tries > 1 => CaseStatus::Passed,
+                    _ => CaseStatus::Failed,
+                };
+                CaseResult { error: Some(format!("try {}", tries)), ..fake_case(n, status) }
+            },
+            Some("cleanup"),
+            None,
+            &mut NoProgress,
+        );
+        assert_eq!(played, ["stable", "flaky", "broken", "flaky", "broken", "broken", "cleanup"]);
+        assert!(!cases[0].flaky && cases[0].attempts.is_empty());
+        assert_eq!(cases[1].status, CaseStatus::Passed);
+        assert!(cases[1].flaky);
+        assert_eq!(cases[1].attempts.len(), 1);
+        assert_eq!(cases[2].status, CaseStatus::Failed);
+        assert!(!cases[2].flaky);
+        let errors: Vec<_> = cases[2].attempts.iter().map(|a| a.error.as_deref().unwrap()).collect();
+        assert_eq!(errors, ["try 1", "try 2"]);
+        assert_eq!(cases[2].error.as_deref(), Some("try 3"));
+        assert_eq!(attempt_stem("login", 1), "login");
+        assert_eq!(attempt_stem("login", 2), "login.attempt2");
+    }
+
+    #[test]
+    fn test_suite_rerun_replays_cases_skipped_for_a_dependency() {
+        let names: Vec<String> = vec!["signup".into(), "login".into()];
+        let mut outcomes: HashMap<String, CaseStatus> = HashMap::new();
+        let mut signups = 0;
+        let (cases, _) = run_suite_cases(
+            &names,
+            FailureMode::ContinueOnFailure,
+            1,
+            |n| {
+                if let Some(reason) = dependency_skip_reason(&["signup".to_string()], &outcomes, &names).filter(|_| n == "login") {
+                    return CaseResult { skip_reason: Some(reason), ..fake_case(n, CaseStatus::Skipped) };
+                }
+                if n == "signup" {
+                    signups += 1;
+                }
+                let status = if n == "signup" && signups == 1 { CaseStatus::Failed } else { CaseStatus::Passed };
+                outcomes.insert(n.to_owned(), status);
+                fake_case(n, status)
+            },
+            None,
+            None,
+            &mut NoProgress,
+        );
+        assert!(cases[0].flaky);
+        assert_eq!(cases[1].status, CaseStatus::Passed);
+        assert!(!cases[1].flaky && cases[1].attempts.is_empty());
+    }
+
+    #[test]
+    fn test_step_position_by_id() {
+        let empty = r#"{"version":1,"name":"checkout","platform":"android","createdAt":"","updatedAt":""}"#;
+        let mut scenario = parse_scenario(empty, &ScenarioDefaults::default()).unwrap();
+        let err = step_position(&scenario, "pay").unwrap_err().to_string();
+        assert!(err.contains("has no step ids"), "{}", err);
+
+        scenario.steps = ["open", "", "pay"]
+            .iter()
+            .enumerate()
+            .map(|(index, id)| ScenarioStep { index, id: (!id.is_empty()).then(|| id.to_string()), ..Default::default() })
+            .collect();
+        assert_eq!(step_position(&scenario, "pay").unwrap(), 2);
+        let err = step_position(&scenario, "refund").unwrap_err().to_string();
+        assert!(err.contains("no step 'refund' (ids: open, pay)"), "{}", err);
+    }
+
+    #[test]
+    fn test_group_runs_and_split_groups() {
+        let step = |index: usize, group: Option<&str>| ScenarioStep {
+            index,
+            action: "wait".into(),
+            group: group.map(str::to_owned),
+            ..Default::default()
+        };
+        let steps = vec![
+            step(0, Some("Login")),
+            step(1, Some("Login")),
+            step(2, None),
+            step(3, Some("Checkout")),
+            step(4, Some("Login")),
+        ];
+        let runs: Vec<(Option<&str>, usize)> = group_runs(&steps).iter().map(|(g, m)| (*g, m.len())).collect();
+        assert_eq!(runs, [(Some("Login"), 2), (None, 1), (Some("Checkout"), 1), (Some("Login"), 1)]);
+        assert_eq!(split_groups(&steps), [(4, "Login")]);
+
+        let empty = r#"{"version":1,"name":"g","platform":"android","createdAt":"","updatedAt":""}"#;
+        let scenario = Scenario { steps, ..parse_scenario(empty, &ScenarioDefaults::default()).unwrap() };
+        let warnings = validate_coherence(&scenario, &[]);
+        assert!(warnings.iter().any(|w| w.index == 4 && w.message.contains("group 'Login'")));
+    }
+
+    #[test]
+    fn test_strict_lints() {
+        let good = r#"{"version":1,"name":"login","platform":"android","tags":["smoke","checkout-flow"],
+            "createdAt":"2026-03-01T10:00:00Z","updatedAt":"2026-03-02T11:30:00Z",
+            "steps":[{"index":0,"action":"tap","args":["10","20"],"type":"action","timestampMs":0},
+                     {"index":1,"action":"assert-screen","args":["1.5"],"id":"home","type":"action","timestampMs":0}]}"#;
+        let scenario = parse_scenario(good, &ScenarioDefaults::default()).unwrap();
+        assert!(strict_lints(&scenario, "android").is_empty());
+        assert_eq!(strict_lints(&scenario, "ios"), ["platform 'android' does not match its directory 'ios'"]);
+
+        let bad = r#"{"version":1,"name":"x","platform":"android","tags":["Smoke"],"createdAt":"yesterday","updatedAt":"2026-03-02T11:30:00Z",
+            "steps":[{"index":0,"action":"tap","args":["10"],"type":"action","timestampMs":0},
+                     {"index":1,"action":"assert-request","args":["GET","/api","ok"],"type":"action","timestampMs":0},
+                     {"index":2,"action":"screenshot","type":"action","timestampMs":0}]}"#;
+        let lints = strict_lints(&parse_scenario(bad, &ScenarioDefaults::default()).unwrap(), "android");
+        assert_eq!(lints.len(), 5, "{:?}", lints);
+        assert!(lints[0].starts_with("tag 'Smoke'"));
+        assert!(lints[1].starts_with("createdAt 'yesterday'"));
+        assert!(lints[2].starts_with("step 1: tap requires 2 args"));
+        assert_eq!(lints[3], "step 2: assert-request status 'ok' is not a number");
+        assert_eq!(lints[4], "step 3: 'screenshot' cannot be replayed");
+    }
+
+    #[test]
+    fn test_suggest_action_for_typos() {
+        assert_eq!(edit_distance("tpa", "tap"), 1);
+        assert_eq!(suggest_action("tpa"), Some("tap"));
+        assert_eq!(suggest_action("Swpie"), Some("swipe"));
+        assert_eq!(suggest_action("tap-txt"), Some("tap-text"));
+        assert_eq!(suggest_action("asert-toast"), Some("assert-toast"));
+        assert_eq!(suggest_action("screenshot"), None);
+        assert_eq!(suggest_action("Open the settings app"), None);
+    }
+
+    #[test]
+    fn test_markdown_anchors_and_contents() {
+        let empty = r#"{"version":1,"name":"Login Flow","platform":"android","createdAt":"","updatedAt":""}"#;
+        let base = || parse_scenario(empty, &ScenarioDefaults::default()).unwrap();
+        let steps: Vec<ScenarioStep> = (0..12)
+            .map(|index| ScenarioStep {
+                index,
+                action: "tap".into(),
+                args: vec!["1".into(), "2".into()],
+                group: (index < 3).then(|| "Setup".to_string()),
+                ..Default::default()
+            })
+            .collect();
+        let md = render_markdown(&Scenario { steps: steps.clone(), ..base() });
+        assert!(md.contains("## Contents"));
+        assert!(md.contains("- **Setup**\n  - [1. tap 1 2](#login-flow-step-1)"));
+        assert!(md.contains("- [12. tap 1 2](#login-flow-step-12)"));
+        assert!(md.contains("3. <a id=\"login-flow-step-3\"></a>**tap** `1, 2`"));
+
+        // Short cases skip the table of contents but keep their anchors.
+        let md = render_markdown(&Scenario { steps: steps[..2].to_vec(), ..base() });
+        assert!(!md.contains("## Contents"));
+        assert!(md.contains("<a id=\"login-flow-step-2\"></a>"));
+
+        let mut odd = steps[..2].to_vec();
+        odd[0].group = Some("Login <beta> & \"more\"".into());
+        let md = render_markdown(&Scenario { steps: odd, ..base() });
+        assert!(md.contains("<summary><b>Login &lt;beta&gt; &amp; &quot;more&quot;</b> (1 step)</summary>"));
+    }
+
+    #[test]
+    fn test_baseline_path_layout() {
+        let root = Path::new("/baselines");
+        assert_eq!(
+            baseline_path(root, "login", "after-submit", "ios"),
+            PathBuf::from("/baselines/login/after-submit/ios.png")
+        );
+        assert_ne!(baseline_path(root, "login", "s1", "ios"), baseline_path(root, "login", "s1", "android"));
+        assert_eq!(baseline_path(root, "..", "a/../../b", "android"), PathBuf::from("/baselines/_/a_.._.._b/android.png"));
+    }
+
+    #[test]
+    fn test_screen_review_stays_out_of_baselines() {
+        let tmp = tempfile::tempdir().unwrap();
+        let baseline = baseline_path(&tmp.path().join("baselines"), "login", "home", "android");
+        fs::create_dir_all(baseline.parent().unwrap()).unwrap();
+        let shots = tmp.path().join("screenshots/run/login/home");
+        let expected = image::DynamicImage::new_rgb8(8, 8);
+        let actual = image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(8, 8, image::Rgb([255, 0, 0])));
+
+        let artifacts = ArtifactLog::default();
+        let png = crate::screenshot::EncodeFormat::Png;
+        let review = write_screen_review(&shots, &baseline, &expected, &actual, png, &artifacts);
+        assert!(review.contains("android.onion.gif") && review.contains("android.actual.png"), "{}", review);
+        let written: Vec<PathBuf> = artifacts.take().iter().map(PathBuf::from).collect();
+        assert_eq!(written, [shots.join("android.onion.gif"), shots.join("android.actual.png")]);
+        assert!(shots.join("android.onion.gif").exists());
+        assert!(shots.join("android.actual.png").exists());
+        assert_eq!(fs::read_dir(baseline.parent().unwrap()).unwrap().count(), 0);
+        assert!(PathTemplate::parse(DEFAULT_SCREENSHOTS).unwrap().varies_per_run(), "each run gets its own directory");
+    }
+
+    #[test]
+    fn test_shards_are_disjoint_and_complete() {
+        let ids: Vec<String> = ["e", "a", "d", "b", "c", "f", "g"].iter().map(|s| s.to_string()).collect();
+        for total in 1..=4 {
+            let mut union: Vec<String> = (0..total).flat_map(|i| shard(&ids, total, i)).collect();
+            union.sort();
+            assert_eq!(union, ["a", "b", "c", "d", "e", "f", "g"]);
+        }
+        assert_eq!(shard(&ids, 3, 0), ["a", "d", "g"]);
+        // Input order does not matter.
+        let mut reversed = ids.clone();
+        reversed.reverse();
+        assert_eq!(shard(&reversed, 3, 1), shard(&ids, 3, 1));
+
+        assert_eq!(parse_shard("2/4").unwrap(), (1, 4));
+        assert!(parse_shard("0/4").is_err());
+        assert!(parse_shard("5/4").is_err());
+        assert!(parse_shard("2").is_err());
+    }
+
+    #[test]
+    fn test_weighted_shards_balance_durations() {
+        let mut ids: Vec<String> = ["slow", "a", "b", "c"].iter().map(|s| s.to_string()).collect();
+        let durations: HashMap<String, u64> =
+            [("slow", 90_000), ("a", 30_000), ("b", 30_000), ("c", 30_000)].iter().map(|(k, v)| (k.to_string(), *v)).collect();
+        assert_eq!(shard_weighted(&ids, &durations, 2, 0), ["slow"]);
+        assert_eq!(shard_weighted(&ids, &durations, 2, 1), ["a", "b", "c"]);
+
+        // A case with no history still lands on exactly one shard.
+        ids.push("new".into());
+        let mut union: Vec<String> = (0..2).flat_map(|i| shard_weighted(&ids, &durations, 2, i)).collect();
+        union.sort();
+        assert_eq!(union, ["a", "b", "c", "new", "slow"]);
+    }
+
+    #[test]
+    fn test_commit_seed_is_optional_outside_git() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(commit_seed(tmp.path()), None);
+    }
+
+    #[test]
+    fn test_sample_suite_fills_budget_by_severity() {
+        let case = |name: &str, severity: &str, secs: u64| {
+            let json = format!(
+                r#"{{"version":1,"name":"{}","platform":"android","severity":"{}","estimatedDurationSecs":{},
+                    "createdAt":"x","updatedAt":"x"}}"#,
+                name, severity, secs
+            );
+            parse_scenario(&json, &ScenarioDefaults::default()).unwrap()
+        };
+        let mut scenarios: Vec<Scenario> = (0..20).map(|i| case(&format!("minor{}", i), "trivial", 60)).collect();
+        scenarios.push(case("login", "blocker", 60));
+        scenarios.push(case("huge", "blocker", 10_000));
+        let refs: Vec<&Scenario> = scenarios.iter().collect();
+        let budget = std::time::Duration::from_secs(300);
+
+        let blocker_hits = (0..50)
+            .filter(|&seed| {
+                let sample = sample_suite(&refs, &HashMap::new(), budget, seed);
+                assert!(sample.iter().map(|(_, s)| s).sum::<u64>() <= 300);
+                assert_eq!(sample.len(), 5);
+                assert!(sample.iter().all(|(n, _)| n != "huge"));
+                sample.iter().any(|(n, _)| n == "login")
+            })
+            .count();
+        // 5 of 21 fitting cases, but weighted 16:1 the blocker is nearly always in.
+        assert!(blocker_hits > 40, "blocker drawn {} of 50 times", blocker_hits);
+        assert_eq!(sample_suite(&refs, &HashMap::new(), budget, 7), sample_suite(&refs, &HashMap::new(), budget, 7));
+
+        // Estimates fall back to history, then to the step count.
+        let mut untimed = case("untimed", "major", 1);
+        untimed.estimated_duration_secs = None;
+        let durations: HashMap<String, u64> = [("untimed".to_string(), 4_500)].into_iter().collect();
+        assert_eq!(estimated_secs(&untimed, &durations), 5);
+        assert_eq!(estimated_secs(&untimed, &HashMap::new()), 1);
+    }
+
+    #[test]
+    fn test_line_progress_reports_each_case() {
+        let names: Vec<String> = vec!["LOGIN-001".into(), "PAY-002".into()];
+        let mut progress = LineProgress::new(Vec::new());
+        run_suite_cases(
+            &names,
+            FailureMode::FailFast,
+            0,
+            |n| CaseResult { duration_ms: 1250, ..fake_case(n, CaseStatus::Failed) },
+            None,
+            None,
+            &mut progress,
+        );
+        assert_eq!(
+            String::from_utf8(progress.out).unwrap(),
+            "[1/2] LOGIN-001 ... fail (1.2s)\n[2/2] PAY-002 ... skip (earlier case failed (fail-fast))\n"
+        );
+
+        static LINE: std::sync::Mutex<StatusLine> = std::sync::Mutex::new(StatusLine::new());
+        let mut live = LiveProgress::new(Vec::new(), &LINE);
+        live.case_started(0, 3, "A");
+        live.case_started(1, 3, "B");
+        live.case_finished(1, 3, "B", "pass (0.1s)");
+        assert_eq!(
+            String::from_utf8(live.out).unwrap(),
+            "\r\x1b[2K[1/3] A ... running\r\x1b[2K\r\x1b[2K[2/3] B ... running\r\x1b[2K[2/3] B ... pass (0.1s)\n"
+        );
+        assert!(LINE.lock().unwrap().text.is_none());
+    }
+
+    #[test]
+    fn test_suite_timeout_marks_rest_not_executed() {
+        let names: Vec<String> = vec!["a".into(), "b".into(), "c".into()];
+        let mut played = Vec::new();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_millis(20);
+        let (cases, teardown) = run_suite_cases(
+            &names,
+            FailureMode::ContinueOnFailure,
+            0,
+            |n| {
+                played.push(n.to_owned());
+                std::thread::sleep(std::time::Duration::from_millis(40));
+                fake_case(n, CaseStatus::Passed)
+            },
+            Some("cleanup"),
+            Some(deadline),
+            &mut NoProgress,
+        );
+        // The case in flight finishes, later ones never start, teardown still runs.
+        assert_eq!(played, vec!["a", "cleanup"]);
+        assert_eq!(cases[0].status, CaseStatus::Passed);
+        assert!(cases[1..].iter().all(|c| c.status == CaseStatus::NotExecuted));
+        assert_eq!(cases[2].skip_reason.as_deref(), Some("suite timeout reached"));
+        assert!(teardown.is_some());
+        assert_eq!(serde_json::to_string(&CaseStatus::NotExecuted).unwrap(), "\"not-executed\"");
+        assert!(!CaseStatus::NotExecuted.ran() && !CaseStatus::Skipped.ran());
+    }
+
+    #[test]
+    fn test_suite_continue_runs_everything() {
+        let names: Vec<String> = vec!["a".into(), "b".into()];
+        let (cases, teardown) = run_suite_cases(
+            &names,
+            FailureMode::ContinueOnFailure,
+            0,
+            |n| fake_case(n, CaseStatus::Failed),
+            None,
+            None,
+            &mut NoProgress,
+        );
+        assert!(cases.iter().all(|c| c.status == CaseStatus::Failed));
+        assert!(teardown.is_none());
+
+        assert_eq!(FailureMode::parse("continue").unwrap(), FailureMode::ContinueOnFailure);
+        assert_eq!(FailureMode::parse("fail-fast").unwrap(), FailureMode::FailFast);
+        assert!(FailureMode::parse("sometimes").is_err());
+        let json = serde_json::to_string(&FailureMode::ContinueOnFailure).unwrap();
+        assert_eq!(json, "\"continue-on-failure\"");
+    }
+
+    #[test]
+    fn test_parse_name_status_handles_renames_and_deletes() {
+        let out = "M\0login.json\0A\0check out.json\0R087\0old-name.json\0вход.json\0D\0gone.json\0M\0last.json\0";
+        assert_eq!(
+            parse_name_status(out),
+            vec!["login.json", "check out.json", "вход.json", "last.json"]
+        );
+        assert!(parse_name_status("").is_empty());
+    }
+
+    #[test]
+    fn test_changed_cases_rejects_option_like_ref() {
+        let err = changed_cases(Path::new("."), "--output=/tmp/x").unwrap_err();
+        assert!(err.to_string().contains("Invalid git ref"));
+    }
+
+    #[test]
+    fn test_parse_scenario_limits_untrusted_input() {
+        let ok = r#"{"version":1,"name":"a","platform":"android","createdAt":"x","updatedAt":"x"}"#;
+        assert_eq!(parse_scenario(ok, &ScenarioDefaults::default()).unwrap().name, "a");
+
+        let oversized = format!("{}{}", ok, " ".repeat(MAX_SCENARIO_BYTES));
+        assert!(parse_scenario(&oversized, &ScenarioDefaults::default()).unwrap_err().to_string().contains("byte limit"));
+
+        // Deeply nested input hits serde_json's recursion limit
+        // instead of blowing the stack.
+        let deep = format!(
+            r#"{{"version":1,"name":"a","platform":"android","createdAt":"x","updatedAt":"x","description":{}{}}}"#,
+            "[".repeat(10_000),
+            "]".repeat(10_000)
+        );
+        assert!(parse_scenario(&deep, &ScenarioDefaults::default()).is_err());
+    }
+
+    #[test]
+    fn test_content_hash_ignores_layout_and_detects_edits() {
+        let a = r#"{"version":1,"name":"a","platform":"android","tags":["x"],
+            "metadata":{"jira":"J-1","risk":"high"},
+            "steps":[{"index":0,"type":"gesture","action":"tap","args":["1","2"],"timestampMs":5}],
+            "createdAt":"2026-01-01","updatedAt":"2026-01-01"}"#;
+        let b = r#"{ "updatedAt": "2026-02-02", "createdAt": "2026-02-02",
+            "steps": [ { "timestampMs": 5, "args": ["1", "2"], "action": "tap", "type": "gesture", "index": 0 } ],
+            "metadata": { "risk": "high", "jira": "J-1" }, "tags": ["x"], "platform": "android", "name": "a", "version": 1 }"#;
+        let parse = |t: &str| parse_scenario(t, &ScenarioDefaults::default()).unwrap();
+        let mut scenario = parse(a);
+        let hash = content_hash(&scenario).unwrap();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(content_hash(&parse(b)).unwrap(), hash);
+
+        scenario.approved_hash = Some(hash.clone());
+        assert!(verify_approval(&scenario).is_ok());
+        scenario.steps[0].args[1] = "3".into();
+        let err = verify_approval(&scenario).unwrap_err().to_string();
+        assert!(err.contains("modified since approval"), "{}", err);
+    }
+
+    #[test]
+    fn test_normalized_json_is_stable_and_keeps_empty_fields() {
+        let text = r#"{"version":1,"name":"a","platform":"android","tags":["x"],"requires":{"features":["nfc"],"minSdk":30},
+            "metadata":{"jira":"J-1"},"approvedHash":"abc","platforms":["android","ios"],"estimatedDurationSecs":30,
+            "preconditions":["Logged out",{"fixture":"login"}],"severity":"major","linkedFeature":"auth",
+            "steps":[{"index":0,"type":"gesture","action":"tap","args":["1","2"],"timestampMs":5,"delayBeforeMs":0,
+                "label":"go","expectFailure":false,"soft":true,"id":"s1","group":"g","onlyOn":["ios"],"manual":false}],
+            "createdAt":"2026-01-01","updatedAt":"2026-01-01","description":"d"}"#;
+        let scenario = parse_scenario(text, &ScenarioDefaults::default()).unwrap();
+        let saved = serde_json::to_string_pretty(&scenario).unwrap();
+        assert_eq!(normalized_json(text).unwrap(), saved);
+        assert_eq!(normalized_json(&saved).unwrap(), saved);
+
+        let sparse = r#"{ "updatedAt": "x", "tags": [], "name": "b", "version": 1, "platform": "ios", "createdAt": "x", "custom": {},
+            "metadata": { "risk": "high", "jira": "J-1" } }"#;
+        let normal = normalized_json(sparse).unwrap();
+        assert_eq!(
+            normal,
+            "{\n  \"version\": 1,\n  \"name\": \"b\",\n  \"platform\": \"ios\",\n  \"tags\": [],\n  \
+             \"metadata\": {\n    \"jira\": \"J-1\",\n    \"risk\": \"high\"\n  },\n  \"createdAt\": \"x\",\n  \"updatedAt\": \"x\",\n  \"custom\": {}\n}"
+        );
+        assert!(!normal.contains("steps"));
+    }
+
+    #[test]
+    fn test_soft_failures_do_not_stop_the_case() {
+        let step = |id: &str, ms: &str, soft: bool| ScenarioStep {
+            action: "wait".into(),
+            args: vec![ms.into()],
+            id: Some(id.into()),
+            soft: soft.then_some(true),
+            ..Default::default()
+        };
+        let scenario = Scenario {
+            version: 1,
+            name: "soft".into(),
+            platform: "android".into(),
+            description: None,
+            tags: vec![],
+            metadata: None,
+            severity: None,
+            requires: None,
+            approved_hash: None,
+            estimated_duration_secs: None,
+            linked_feature: None,
+            platforms: None,
+            preconditions: Vec::new(),
+            depends_on: None,
+            before: None,
+            after: None,
+            checks: None,
+            steps: vec![step("a", "0", false), step("check", "not-a-number", true), step("b", "0", false)],
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+        let opts = PlayOptions { stop_on_fail: true, ..play_options() };
+        let run = play_loaded(scenario, "android", &opts).unwrap();
+        assert_eq!(run.failed, 1);
+        let statuses: Vec<(&str, CaseStatus, bool)> =
+            run.steps.iter().map(|s| (s.key.as_str(), s.status, s.soft)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("a", CaseStatus::Passed, false),
+                ("check", CaseStatus::Failed, true),
+                ("b", CaseStatus::Passed, false)
+            ]
+        );
+        assert!(run.steps[1].error.is_some());
+    }
+
+    #[test]
+    fn test_cross_platform_only_on() {
+        let text = r#"{"version":1,"name":"share","platform":"android","platforms":["android","ios"],"createdAt":"","updatedAt":"",
+            "steps":[{"index":0,"type":"action","action":"wait","args":["0"],"timestampMs":0},
+                     {"index":1,"type":"action","action":"back","id":"android-back","onlyOn":["android"],"timestampMs":0}]}"#;
+        let scenario = || parse_scenario(text, &ScenarioDefaults::default()).unwrap();
+        assert!(scenario().steps[1].runs_on("android") && !scenario().steps[1].runs_on("ios"));
+
+        let opts = PlayOptions { dry_run: true, ..play_options() };
+        let statuses = |platform: &str| -> Vec<CaseStatus> {
+            play_loaded(scenario(), platform, &opts).unwrap().steps.iter().map(|s| s.status).collect()
+        };
+        assert_eq!(statuses("android"), [CaseStatus::Passed, CaseStatus::Passed]);
+        assert_eq!(statuses("ios"), [CaseStatus::Passed, CaseStatus::Skipped]);
+        let err = play_loaded(scenario(), "desktop", &opts).err().unwrap().to_string();
+        assert!(err.contains("does not run on desktop"), "{}", err);
+
+        let bad = text.replace(r#""onlyOn":["android"]"#, r#""onlyOn":["aurora"]"#);
+        let err = format!("{:#}", parse_scenario(&bad, &ScenarioDefaults::default()).unwrap_err());
+        assert!(err.contains("onlyOn 'aurora' is not one of the scenario's platforms"), "{}", err);
+        let bad = text.replace(r#""platforms":["android","ios"]"#, r#""platforms":["android","watchos"]"#);
+        assert!(parse_scenario(&bad, &ScenarioDefaults::default()).is_err());
+    }
+
+    #[test]
+    fn test_manual_steps() {
+        let step = ScenarioStep {
+            action: "tap-text".into(),
+            args: vec!["Buy".into()],
+            label: Some("Phone vibrates once".into()),
+            manual: Some(true),
+            ..Default::default()
+        };
+        let verdict = |input: &str| {
+            let mut out = Vec::new();
+            let v = prompt_manual(&mut std::io::Cursor::new(input), &mut out, &step).unwrap();
+            (v, String::from_utf8(out).unwrap())
+        };
+        let (v, shown) = verdict("maybe\np\n");
+        assert_eq!(v, ManualVerdict::Pass);
+        assert!(shown.contains("Expected: Phone vibrates once") && shown.contains("Please answer"), "{}", shown);
+        assert_eq!(verdict("f  no buzz \n").0, ManualVerdict::Fail("no buzz".into()));
+        assert_eq!(verdict("").0, ManualVerdict::Skip);
+
+        // Without a prompt the step is skipped and flagged, not failed.
+        let text = r#"{"version":1,"name":"haptics","platform":"android","createdAt":"","updatedAt":"",
+            "steps":[{"index":0,"type":"manual","action":"confirm haptic feedback","manual":true,"timestampMs":0}]}"#;
+        let scenario = parse_scenario(text, &ScenarioDefaults::default()).unwrap();
+        let opts = play_options();
+        let run = play_loaded(scenario, "android", &opts).unwrap();
+        assert_eq!((run.failed, run.unverified_manual), (0, 1));
+        assert!(run.steps[0].manual && run.steps[0].status == CaseStatus::Skipped);
+        assert!(strict_lints(&parse_scenario(text, &ScenarioDefaults::default()).unwrap(), "android")
+            .iter()
+            .all(|l| !l.contains("cannot be replayed")));
+    }
+
+    #[test]
+    fn test_precondition_fixtures() {
+        let text = r#"{"version":1,"name":"checkout","platform":"android","createdAt":"","updatedAt":"",
+            "preconditions":["Cart has one item",{"fixture":"login"}]}"#;
+        let scenario = parse_scenario(text, &ScenarioDefaults::default()).unwrap();
+        assert_eq!(scenario.preconditions[0], Precondition::Text("Cart has one item".into()));
+        assert_eq!(fixture_names(&scenario), ["login"]);
+        assert!(render_markdown(&scenario).contains("## Preconditions\n\n- Cart has one item\n- fixture `login`\n"));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join(FIXTURES_DIR)).unwrap();
+        let err = format!("{:#}", load_fixtures(&scenario, dir).unwrap_err());
+        assert!(err.contains("fixture 'login' not found"), "{}", err);
 
-    match rx.recv_timeout(std::time::Duration::from_millis(timeout_ms)) {
-        Ok(result) => result,
-        Err(_) => bail!("Step timed out after {}ms", timeout_ms),
+        let login = r#"{"name":"login","steps":[
+            {"index":0,"type":"input","action":"input","args":["${config.user}"],"timestampMs":0},
+            {"index":1,"type":"action","action":"tap","args":["10","20"],"onlyOn":["ios"],"timestampMs":0}]}"#;
+        fs::write(dir.join(FIXTURES_DIR).join("login.json"), login).unwrap();
+        let mut fixtures = load_fixtures(&scenario, dir).unwrap();
+        let opts = PlayOptions {
+            dry_run: true,
+            config: HashMap::from([("user".to_string(), "qa".to_string())]),
+            ..play_options()
+        };
+        let result = run_fixture(&mut fixtures[0], "android", &opts, &ArtifactLog::default()).unwrap();
+        assert_eq!((result.status, result.steps.len()), (CaseStatus::Passed, 1));
+        assert_eq!(fixtures[0].steps[0].args, ["qa"]);
+
+        let conditional = login.replace(r#""timestampMs":0}]"#, r#""timestampMs":0,"when":"x.passed"}]"#);
+        fs::write(dir.join(FIXTURES_DIR).join("login.json"), conditional).unwrap();
+        assert!(load_fixture(dir, "login").is_err());
+        assert!(load_fixture(dir, "../login").is_err());
+
+        // Step flags work as in the case: the delay is waited (scaled by
+        // speed), a soft failure does not stop the fixture, and an
+        // unattended manual step is left unverified.
+        let step = |action: &str, args: &[&str]| ScenarioStep {
+            action: action.into(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        };
+        let mut flagged = Fixture {
+            name: "flagged".into(),
+            description: None,
+            steps: vec![
+                ScenarioStep { delay_before_ms: 100, ..step("wait", &["0"]) },
+                ScenarioStep { soft: Some(true), ..step("wait", &["not-a-number"]) },
+                ScenarioStep { manual: Some(true), ..step("check the banner", &[]) },
+                step("wait", &["0"]),
+            ],
+        };
+        let opts = PlayOptions { speed: 2.0, ..play_options() };
+        let result = run_fixture(&mut flagged, "android", &opts, &ArtifactLog::default()).unwrap();
+        assert_eq!(result.status, CaseStatus::Passed);
+        assert!(result.duration_ms >= 50);
+        let statuses: Vec<(CaseStatus, bool, bool)> = result.steps.iter().map(|s| (s.status, s.soft, s.manual)).collect();
+        assert_eq!(
+            statuses,
+            [
+                (CaseStatus::Passed, false, false),
+                (CaseStatus::Failed, true, false),
+                (CaseStatus::Skipped, false, true),
+                (CaseStatus::Passed, false, false)
+            ]
+        );
     }
-}
 
-// ---------------------------------------------------------------------------
-// recorder export
-// ---------------------------------------------------------------------------
+    #[test]
+    fn test_scenario_defaults_inherited_unless_set() {
+        let defaults: ScenarioDefaults =
+            serde_json::from_str(r#"{"priority":"major","tags":["regression"],"platform":"android"}"#).unwrap();
+        let bare = r#"{"version":1,"name":"a","createdAt":"","updatedAt":""}"#;
+        let s = parse_scenario(bare, &defaults).unwrap();
+        assert_eq!(s.severity, Some(Severity::Major));
+        assert_eq!(s.tags, vec!["regression"]);
+        assert_eq!(s.platform, "android");
 
-fn cmd_export(name: &str, platform: &str, format: &str) -> Result<()> {
-    let scenario = read_scenario(platform, name)?;
+        let explicit = r#"{"version":1,"name":"b","platform":"ios","severity":"blocker","tags":[],"createdAt":"","updatedAt":""}"#;
+        let s = parse_scenario(explicit, &defaults).unwrap();
+        assert_eq!((s.platform.as_str(), s.severity, s.tags.len()), ("ios", Some(Severity::Blocker), 0));
 
-    match format {
-        "flow_steps" => export_flow_steps(&scenario),
-        "markdown" => export_markdown(&scenario),
-        other => bail!(
-            "Unknown export format '{}'. Supported: flow_steps, markdown",
-            other
-        ),
+        // Without defaults a missing platform is still an error.
+        assert!(parse_scenario(bare, &ScenarioDefaults::default()).is_err());
+        assert!(serde_json::from_str::<ScenarioDefaults>(r#"{"prio":"major"}"#).is_err());
     }
-}
 
-fn export_flow_steps(scenario: &Scenario) -> Result<()> {
-    #[derive(Serialize)]
-    struct FlowStepExport<'a> {
-        action: &'a str,
-        #[serde(skip_serializing_if = "Vec::is_empty")]
-        args: &'a Vec<String>,
-    }
+    #[test]
+    fn test_import_markdown_table() {
+        let md = "\
+# Scenario: Login happy path
 
-    let steps: Vec<FlowStepExport<'_>> = scenario
-        .steps
-        .iter()
-        .map(|s| FlowStepExport {
-            action: &s.action,
-            args: &s.args,
-        })
-        .collect();
+**Platform:** Android
+**tags:** smoke, login
+Severity: Critical
 
-    println!("{}", serde_json::to_string_pretty(&steps)?);
-    Ok(())
-}
+| Field | Value |
+|-------|-------|
+| jira_id | APP-42 |
 
-fn export_markdown(scenario: &Scenario) -> Result<()> {
-    println!("# Scenario: {}", scenario.name);
-    println!();
-    println!("**Platform:** {}", scenario.platform);
-    if let Some(desc) = &scenario.description {
-        println!("**Description:** {}", desc);
+## STEPS
+
+| # | Action | Expected Result | Notes |
+|---|--------|-----------------|-------|
+| 1 | `launch` com.example | App opens | cold start |
+| 2 | tap-text Sign in | Login form shown | |
+| 3 | input \"user@example.com\" | | |
+| 4 | **tap** 540, 1200 | Dashboard | |
+";
+        let s = parse_markdown_scenario(md, "fallback").unwrap();
+        assert_eq!(s.name, "Login happy path");
+        assert_eq!(s.platform, "android");
+        assert_eq!(s.tags, vec!["smoke", "login"]);
+        assert_eq!(s.severity, Some(Severity::Critical));
+        assert_eq!(s.metadata.as_ref().unwrap()["jira_id"], "APP-42");
+        let steps: Vec<(&str, Vec<&str>, Option<&str>)> = s
+            .steps
+            .iter()
+            .map(|st| (st.action.as_str(), st.args.iter().map(String::as_str).collect(), st.label.as_deref()))
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                ("launch", vec!["com.example"], Some("App opens")),
+                ("tap-text", vec!["Sign in"], Some("Login form shown")),
+                ("input", vec!["user@example.com"], None),
+                ("tap", vec!["540", "1200"], Some("Dashboard")),
+            ]
+        );
+
+        let err = parse_markdown_scenario("# x\n\nJust prose.\n", "x").unwrap_err().to_string();
+        assert!(err.contains("No steps table"), "{}", err);
+        let err = parse_markdown_scenario("| Action |\n|---|\n| fly away |\n", "x").unwrap_err().to_string();
+        assert!(err.contains("Line 3: unknown action 'fly'"), "{}", err);
     }
-    if !scenario.tags.is_empty() {
-        println!("**Tags:** {}", scenario.tags.join(", "));
-    }
-    println!();
-    println!("## Steps");
-    println!();
-    for step in &scenario.steps {
-        let label = step
-            .label
-            .as_deref()
-            .map(|l| format!(" — {}", l))
-            .unwrap_or_default();
-        let args_str = if step.args.is_empty() {
-            String::new()
-        } else {
-            format!(" `{}`", step.args.join(", "))
+
+    #[test]
+    fn test_device_requirements_unmet() {
+        use crate::android::HardwareState;
+        let state = |present, available| HardwareState { present, available };
+        let caps = crate::android::DeviceCapabilities {
+            sdk: 30,
+            width: 1080,
+            height: 2400,
+            features: vec!["android.hardware.camera".into(), "android.hardware.nfc".into()],
+            hardware: crate::android::HardwareInfo {
+                camera: state(true, Some(true)),
+                fingerprint: state(true, Some(false)),
+                face: state(false, Some(false)),
+                nfc: state(true, None),
+                gps: state(false, Some(false)),
+            },
+        };
+        let req: DeviceRequirements = serde_json::from_str(
+            r#"{"minSdk":29,"minWidth":2400,"minHeight":1000,"features":["nfc","camera"],"hardware":["camera","nfc"]}"#,
+        )
+        .unwrap();
+        assert!(req.unmet(&caps).is_empty(), "size is checked in either orientation");
+
+        let req = DeviceRequirements {
+            min_sdk: Some(33),
+            min_width: Some(1440),
+            min_height: Some(2560),
+            features: vec!["android.hardware.telephony".into()],
+            hardware: vec!["fingerprint".into(), "gps".into()],
         };
-        println!("{}. **{}**{}{}", step.index + 1, step.action, args_str, label);
+        assert_eq!(
+            req.unmet(&caps),
+            vec![
+                "SDK 30 < 33",
+                "screen 1080x2400 < 1440x2560",
+                "missing feature android.hardware.telephony",
+                "fingerprint not available",
+                "no gps"
+            ]
+        );
+        let text = r#"{"version":1,"name":"a","platform":"android","requires":{"hardware":["lidar"]},"createdAt":"","updatedAt":""}"#;
+        let err = format!("{:#}", parse_scenario(text, &ScenarioDefaults::default()).unwrap_err());
+        assert!(err.contains("Unknown hardware 'lidar'"), "{}", err);
     }
-    Ok(())
-}
 
-// ---------------------------------------------------------------------------
-// Tests
-// ---------------------------------------------------------------------------
+    #[test]
+    fn test_search_ranks_and_filters_recursively() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("android/checkout")).unwrap();
+        let case = |name: &str, extra: &str| {
+            format!(r#"{{"version":1,"name":"{}","platform":"android","createdAt":"","updatedAt":""{}}}"#, name, extra)
+        };
+        fs::write(dir.join("android/login.json"), case("login", r#","description":"Sign in with email","tags":["smoke"],"metadata":{"author":"Dana"}"#)).unwrap();
+        fs::write(dir.join("android/checkout/pay.json"), case("pay-with-card", r#","description":"Checkout after login","metadata":{"Author":"sam"}"#)).unwrap();
+        fs::write(dir.join("android/checkout/_defaults.json"), r#"{"tags":["payments"]}"#).unwrap();
+        fs::write(dir.join("android/broken.json"), "{not json").unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let names = |q: &SearchQuery| search(dir, q).unwrap().into_iter().map(|h| h.name).collect::<Vec<_>>();
+        // The name match outranks a mention in another case's description.
+        assert_eq!(names(&SearchQuery { text: "LOGIN".into(), ..Default::default() }), ["login", "pay-with-card"]);
+        // Every word must match; "card" only matches as part of a name token.
+        assert_eq!(names(&SearchQuery { text: "login card".into(), ..Default::default() }), ["pay-with-card"]);
+        // Defaults from a nested directory apply to files in it.
+        let tagged = SearchQuery { filter: ScenarioFilter::new(None, Some("payments"), &[], &[]).unwrap(), ..Default::default() };
+        assert_eq!(names(&tagged), ["pay-with-card"]);
+        assert_eq!(names(&SearchQuery { author: Some("SAM".into()), ..Default::default() }), ["pay-with-card"]);
+        assert!(names(&SearchQuery { platform: Some("ios".into()), ..Default::default() }).is_empty());
+    }
 
     #[test]
-    fn test_scenario_serialization_round_trip() {
-        let scenario = Scenario {
-            version: 1,
-            name: "login-flow".into(),
-            platform: "android".into(),
-            description: Some("Login test".into()),
-            tags: vec!["smoke".into()],
-            steps: vec![ScenarioStep {
-                index: 0,
-                step_type: "gesture".into(),
-                action: "tap".into(),
-                args: vec!["100".into(), "200".into()],
-                timestamp_ms: 1_000_000,
-                delay_before_ms: 0,
-                label: None,
-            }],
-            created_at: "2026-05-27T12:00:00Z".into(),
-            updated_at: "2026-05-27T12:00:00Z".into(),
+    fn test_export_catalog_csv() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("ios")).unwrap();
+        fs::write(
+            dir.join("ios/login.json"),
+            r#"{"version":1,"name":"login","platform":"ios","severity":"blocker","tags":["smoke","auth"],
+                "description":"Sign in, \"happy\" path","createdAt":"","updatedAt":"",
+                "steps":[{"index":0,"type":"action","action":"tap-text","args":["Sign in"],"timestampMs":0},
+                         {"index":1,"type":"input","action":"input","args":["a@b.c"],"label":"email","timestampMs":0}]}"#,
+        )
+        .unwrap();
+        let out = dir.join("catalog.csv");
+        assert_eq!(export_catalog_csv(dir, &out, &CsvColumns::default()).unwrap(), 1);
+        let csv = fs::read_to_string(&out).unwrap();
+        assert_eq!(
+            csv,
+            "ID,Title,Description,Priority,Steps,Labels\r\n\
+             ios/login,login,\"Sign in, \"\"happy\"\" path\",Critical,\"1. tap-text Sign in\n2. input — email\",\"smoke,auth\"\r\n"
+        );
+
+        let columns: CsvColumns =
+            serde_json::from_str(r#"{"title":"Summary","description":null,"priorities":{"blocker":"Highest"}}"#).unwrap();
+        assert_eq!(columns.headers(), ["ID", "Summary", "Priority", "Steps", "Labels"]);
+        assert!(render_catalog_csv(&[], &columns).starts_with("ID,Summary,Priority"));
+    }
+
+    #[test]
+    fn test_trend_regressions_fixes_and_flaky() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let run = |at: &str, cases: &[(&str, &str)]| {
+            let cases: Vec<String> = cases
+                .iter()
+                .map(|(n, st)| format!(r#"{{"name":"{}","status":"{}","durationMs":1}}"#, n, st))
+                .collect();
+            format!(r#"{{"platform":"android","startedAt":"{}","cases":[{}]}}"#, at, cases.join(","))
         };
+        fs::write(dir.join("1.json"), run("2026-01-01T00:00:00Z", &[("login", "passed"), ("pay", "failed"), ("feed", "passed")])).unwrap();
+        fs::write(dir.join("2.json"), run("2026-01-02T00:00:00Z", &[("login", "failed"), ("pay", "passed"), ("feed", "failed")])).unwrap();
+        fs::write(dir.join("3.json"), run("2026-01-03T00:00:00Z", &[("login", "failed"), ("pay", "passed"), ("feed", "passed")])).unwrap();
+        fs::write(dir.join("broken.json"), "{not json").unwrap();
 
-        let json = serde_json::to_string(&scenario).unwrap();
-        let parsed: Scenario = serde_json::from_str(&json).unwrap();
+        let runs = load_history(dir).unwrap();
+        assert_eq!(runs.len(), 3, "corrupt entries are skipped");
+        let trend = Trend::new(&runs);
+        // login: passed→failed→failed is not new in the latest run; feed recovered.
+        assert_eq!(trend.changes(), (vec![], vec!["feed"]));
+        assert_eq!(trend.flakiest(10), vec![("feed", 2, 3), ("login", 1, 3), ("pay", 1, 3)]);
+        assert_eq!(trend.pass_rates[1], Some(1.0 / 3.0));
 
-        assert_eq!(parsed.name, "login-flow");
-        assert_eq!(parsed.platform, "android");
-        assert_eq!(parsed.steps.len(), 1);
-        assert_eq!(parsed.steps[0].action, "tap");
-        assert_eq!(parsed.steps[0].args, vec!["100", "200"]);
-        assert_eq!(parsed.version, 1);
+        let html = render_trend_html(&trend);
+        assert!(html.contains("<h2>Fixes (1)</h2>"));
+        assert!(html.contains("<i class=\"p\"></i><i class=\"f\"></i><i class=\"p\"></i>"));
     }
 
     #[test]
-    fn test_recording_state_serialization() {
-        let state = RecordingState {
-            name: "test-rec".into(),
-            platform: "ios".into(),
-            description: None,
-            tags: vec![],
-            steps: vec![],
-            started_at: "2026-05-27T10:00:00Z".into(),
+    fn test_history_files_do_not_overwrite() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let (first, _) = create_history_file(dir, "2026-01-01T10:00:00Z", 7, "android").unwrap();
+        let (second, _) = create_history_file(dir, "2026-01-01T10:00:00Z", 7, "android").unwrap();
+        let (later, _) = create_history_file(dir, "2026-01-01T10:00:00Z", 8, "android").unwrap();
+        assert_eq!(first, dir.join("2026-01-01T100000.007Z-android.json"));
+        assert_eq!(second, dir.join("2026-01-01T100000.007Z-android-2.json"));
+        assert_eq!(later, dir.join("2026-01-01T100000.008Z-android.json"));
+    }
+
+    #[test]
+    fn test_coverage_matrix_gaps_and_uncategorized() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        for platform in ["android", "ios"] {
+            fs::create_dir_all(dir.join(platform)).unwrap();
+        }
+        let write = |platform: &str, name: &str, extra: &str| {
+            let json = format!(
+                r#"{{"version":1,"name":"{}","platform":"{}"{},"createdAt":"x","updatedAt":"x"}}"#,
+                name, platform, extra
+            );
+            fs::write(dir.join(platform).join(format!("{}.json", name)), json).unwrap();
         };
+        write("android", "login", r#","linkedFeature":"auth""#);
+        write("android", "login2", r#","linkedFeature":"auth""#);
+        write("android", "pay", r#","linkedFeature":"checkout""#);
+        write("ios", "login", r#","linkedFeature":"auth""#);
+        write("ios", "misc", "");
+        write("android", "shared", r#","linkedFeature":"search","platforms":["android","ios"]"#);
 
-        let json = serde_json::to_string(&state).unwrap();
-        let parsed: RecordingState = serde_json::from_str(&json).unwrap();
-        assert_eq!(parsed.name, "test-rec");
-        assert_eq!(parsed.platform, "ios");
-        assert!(parsed.steps.is_empty());
+        let matrix = coverage_matrix(dir, None);
+        assert_eq!(matrix.platforms, ["android", "ios"]);
+        assert_eq!(matrix.count(Some("auth"), "android"), 2);
+        assert_eq!(matrix.count(Some("search"), "ios"), 1);
+        assert_eq!(matrix.count(None, "ios"), 1);
+        assert_eq!(matrix.gaps(), vec![("checkout", "ios")]);
+        assert_eq!(matrix.row_names().last(), Some(&None));
+
+        let md = render_coverage_markdown(&matrix);
+        assert!(md.contains("| checkout | 1 | **0** |"));
+        assert!(md.contains("| _uncategorized_ | 0 | 1 |"));
+        assert!(render_coverage_html(&matrix).contains("<td class=\"gap\">0</td>"));
+
+        let only_android = coverage_matrix(dir, Some(&["android".to_string()]));
+        assert!(only_android.gaps().is_empty());
+
+        // A feature literally named "uncategorized" is a feature, with gaps.
+        write("android", "sorting", r#","linkedFeature":"uncategorized""#);
+        let matrix = coverage_matrix(dir, None);
+        assert_eq!((matrix.count(Some("uncategorized"), "ios"), matrix.count(None, "ios")), (0, 1));
+        assert!(matrix.gaps().contains(&("uncategorized", "ios")));
+        let md = render_coverage_markdown(&matrix);
+        assert!(md.contains("| uncategorized | 1 | **0** |"));
+        assert!(md.contains("| _uncategorized_ | 0 | 1 |"));
     }
 
     #[test]
-    fn test_now_iso8601_format() {
-        let ts = now_iso8601();
-        // Should match "YYYY-MM-DDTHH:MM:SSZ"
-        assert_eq!(ts.len(), 20);
-        assert!(ts.ends_with('Z'));
-        assert!(ts.contains('T'));
+    fn test_traceability_report() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("android")).unwrap();
+        let write = |name: &str, meta: &str| {
+            let json = format!(
+                r#"{{"version":1,"name":"{}","platform":"android","metadata":{{{}}},"createdAt":"x","updatedAt":"x"}}"#,
+                name, meta
+            );
+            fs::write(dir.join("android").join(format!("{}.json", name)), json).unwrap();
+        };
+        write("login", r#""requirement":"REQ-1, REQ-2""#);
+        write("logout", r#""jira":"REQ-2""#);
+        write("misc", r#""author":"kim""#);
+
+        let keys: Vec<String> = TRACE_KEYS.iter().map(|k| k.to_string()).collect();
+        let listed = vec!["REQ-1".to_string(), "REQ-2".to_string(), "REQ-3".to_string()];
+        let report = traceability_report(dir, &keys, Some(listed));
+        assert_eq!(report.requirements["REQ-2"], ["android/login", "android/logout"]);
+        assert_eq!(report.uncovered(), ["REQ-3"]);
+        assert!(report.unlisted().is_empty());
+        assert_eq!(report.untraced, ["android/misc"]);
+
+        let md = render_traceability_markdown(&report);
+        assert!(md.contains("|---|---|---|\n| REQ-1 | 1 | android/login |\n"));
+        assert!(md.contains("| REQ-3 | **0** | — |"));
+        assert!(md.contains("## Cases without requirement ids (1)"));
+
+        let unlisted = traceability_report(dir, &keys, Some(vec!["REQ-1".to_string()]));
+        assert_eq!(unlisted.unlisted(), ["REQ-2"]);
+
+        write("pipe", r#""requirement":"REQ|9""#);
+        let md = render_traceability_markdown(&traceability_report(dir, &keys, None));
+        assert!(md.contains("| REQ\\|9 | 1 | android/pipe |\n"), "{}", md);
     }
 
     #[test]
-    fn test_epoch_to_datetime_unix_epoch() {
-        let (y, mo, d, h, mi, s) = epoch_to_datetime(0);
-        assert_eq!(y, 1970);
-        assert_eq!(mo, 1);
-        assert_eq!(d, 1);
-        assert_eq!(h, 0);
-        assert_eq!(mi, 0);
-        assert_eq!(s, 0);
+    fn test_diff_runs() {
+        let run = |cases: &[(&str, CaseStatus, u64)]| HistoryRun {
+            started_at: String::new(),
+            cases: cases
+                .iter()
+                .map(|(name, status, ms)| HistoryCase {
+                    name: name.to_string(),
+                    status: *status,
+                    duration_ms: *ms,
+                    artifacts: Vec::new(),
+                })
+                .collect(),
+        };
+        use CaseStatus::*;
+        let a = run(&[("login", Passed, 10_000), ("pay", Failed, 5_000), ("feed", Passed, 2_000), ("old", Passed, 1)]);
+        let b = run(&[("login", Failed, 30_000), ("pay", Passed, 5_200), ("feed", Skipped, 0), ("new", Passed, 1)]);
+        let diff = diff_runs(&a, &b);
+        assert_eq!(diff.regressions().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["login"]);
+        assert_eq!(diff.fixes().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["pay"]);
+        assert_eq!(diff.changed.len(), 3);
+        assert_eq!(diff.added[0].name, "new");
+        assert_eq!(diff.removed[0].name, "old");
+        // Skipped runs have no meaningful duration.
+        assert_eq!(diff.durations.iter().map(|d| d.name.as_str()).collect::<Vec<_>>(), ["login", "pay"]);
+
+        let md = render_run_diff(&diff, 10);
+        assert!(md.starts_with("**Suite diff:** 1 regression(s), 1 fix(es), 1 new, 1 removed"));
+        assert!(md.contains("- `feed`: passed → skipped"));
+        assert!(md.contains("| `login` | 10.0s | 30.0s | +20.0s |"));
+        assert!(!md.contains("| `pay`"), "a 4% change is noise");
     }
 
     #[test]
-    fn test_epoch_to_datetime_known_date() {
-        // 2026-05-27T00:00:00Z = 1_779_840_000 seconds (UTC)
-        let secs = 1_779_840_000u64;
-        let (y, mo, d, _h, _mi, _s) = epoch_to_datetime(secs);
-        assert_eq!(y, 2026);
-        assert_eq!(mo, 5);
-        assert_eq!(d, 27);
+    fn test_named_checks() {
+        let text = r#"{"version":1,"name":"profile","platform":"android","tags":["smoke"],
+            "checks":{"logged_in":["assert-element","avatar","enabled"]},
+            "steps":[{"index":0,"type":"assertion","action":"@check:logged_in","timestampMs":0},
+                     {"index":1,"type":"assertion","action":"@check:on_home","label":"home shown","timestampMs":0}],
+            "createdAt":"2026-05-27T12:00:00Z","updatedAt":"2026-05-27T12:00:00Z"}"#;
+        // `on_home` comes from the directory defaults.
+        let defaults: ScenarioDefaults = serde_json::from_str(
+            r#"{"checks":{"on_home":["assert-text","Home"],"logged_in":["assert-element","login","gone"]}}"#,
+        )
+        .unwrap();
+        let mut scenario = parse_scenario(text, &defaults).unwrap();
+        assert!(strict_lints(&scenario, "android").is_empty(), "{:?}", strict_lints(&scenario, "android"));
+        let checks = scenario.checks.clone();
+        expand_checks(&mut scenario.steps, checks.as_ref());
+        assert_eq!((scenario.steps[0].action.as_str(), scenario.steps[0].args.as_slice()), ("assert-element", &["avatar".to_owned(), "enabled".to_owned()][..]));
+        assert_eq!(scenario.steps[0].label.as_deref(), Some("logged_in"));
+        assert_eq!((scenario.steps[1].action.as_str(), scenario.steps[1].label.as_deref()), ("assert-text", Some("home shown")));
+
+        let err = parse_scenario(text, &ScenarioDefaults::default()).unwrap_err();
+        assert!(format!("{:#}", err).contains("unknown check 'on_home' (defined: logged_in)"), "{:#}", err);
+        let empty = text.replace(r#"["assert-element","avatar","enabled"]"#, "[]");
+        assert!(format!("{:#}", parse_scenario(&empty, &defaults).unwrap_err()).contains("Check 'logged_in' is empty"));
     }
 
     #[test]
-    fn test_is_leap_year() {
-        assert!(is_leap(2000));
-        assert!(is_leap(2024));
-        assert!(!is_leap(1900));
-        assert!(!is_leap(2023));
+    fn test_scaffold_new_case() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::write(dir.join("login-with-email.json"), "{}").unwrap();
+
+        let case = new_scenario("ios", "Login with e-mail!", dir, Some("Dana".into())).unwrap();
+        assert_eq!(case.name, "login-with-e-mail");
+        let case = new_scenario("ios", "  Login with email ", dir, Some("Dana".into())).unwrap();
+        assert_eq!(case.name, "login-with-email-2");
+        assert_eq!(case.description.as_deref(), Some("Login with email"));
+        assert_eq!(scenario_author(&case), Some("Dana"));
+        assert!(case.tags.is_empty());
+        assert!(is_iso8601_utc(&case.created_at));
+        assert_eq!(case.steps.len(), 1);
+        assert_eq!(case.steps[0].manual, Some(true));
+        assert_eq!(new_scenario("android", "?!", dir, None).unwrap().name, "case");
+        assert_eq!(new_scenario("android", "Вход по E-mail", dir, None).unwrap().name, "вход-по-e-mail");
+
+        // Cases in subfolders count too, by file name and by id.
+        fs::create_dir(dir.join("checkout")).unwrap();
+        fs::write(dir.join("checkout").join("pay-by-card.json"), "{}").unwrap();
+        assert_eq!(new_scenario("ios", "Pay by card", dir, None).unwrap().name, "pay-by-card-2");
+        assert!(new_scenario("windows", "x", dir, None).is_err());
+        assert!(new_scenario("ios", " ", dir, None).is_err());
     }
 
     #[test]
-    fn test_remove_step_reindex() {
-        let steps = vec![
-            ScenarioStep {
-                index: 0,
-                step_type: "gesture".into(),
-                action: "tap".into(),
-                args: vec![],
-                timestamp_ms: 0,
-                delay_before_ms: 0,
-                label: None,
-            },
-            ScenarioStep {
-                index: 1,
-                step_type: "gesture".into(),
-                action: "swipe".into(),
-                args: vec![],
-                timestamp_ms: 0,
-                delay_before_ms: 0,
-                label: None,
-            },
-            ScenarioStep {
-                index: 2,
-                step_type: "input".into(),
-                action: "input".into(),
-                args: vec![],
-                timestamp_ms: 0,
-                delay_before_ms: 0,
-                label: None,
-            },
-        ];
+    fn test_retention_policy() {
+        assert_eq!(RetentionPolicy::parse("keep-failures-only").unwrap(), RetentionPolicy::FailuresOnly);
+        assert_eq!(RetentionPolicy::parse("keep-last-5").unwrap(), RetentionPolicy::LastRuns(5));
+        assert!(RetentionPolicy::parse("keep-last-0").is_err());
+        assert!(RetentionPolicy::parse("keep-some").is_err());
 
-        let mut modified = steps;
-        modified.remove(1); // remove "swipe"
-        for (i, step) in modified.iter_mut().enumerate() {
-            step.index = i;
-        }
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let file = |name: &str| {
+            let path = dir.join(name);
+            fs::write(&path, b"x").unwrap();
+            path.display().to_string()
+        };
+        let mut passed = fake_case("ok", CaseStatus::Passed);
+        passed.artifacts.push(file("ok.mp4"));
+        let mut failed = fake_case("bad", CaseStatus::Failed);
+        failed.artifacts = vec![file("bad.mp4"), file("bad.zip")];
+        let mut flaky = CaseResult { flaky: true, ..fake_case("flaky", CaseStatus::Passed) };
+        flaky.artifacts.push(file("flaky.zip"));
+        let mut cases = [passed, failed, flaky];
+        assert_eq!(prune_passed_artifacts(cases.iter_mut()), 1);
+        assert!(cases[0].artifacts.is_empty() && !dir.join("ok.mp4").exists());
+        assert_eq!(cases[1].artifacts.len(), 2);
+        assert!(dir.join("bad.zip").exists());
+        assert!(dir.join("flaky.zip").exists());
 
-        assert_eq!(modified.len(), 2);
-        assert_eq!(modified[0].action, "tap");
-        assert_eq!(modified[0].index, 0);
-        assert_eq!(modified[1].action, "input");
-        assert_eq!(modified[1].index, 1);
+        let run = |artifacts: &[&str]| HistoryRun {
+            started_at: String::new(),
+            cases: vec![HistoryCase {
+                name: "c".to_string(),
+                status: CaseStatus::Failed,
+                duration_ms: 0,
+                artifacts: artifacts.iter().map(|a| a.to_string()).collect(),
+            }],
+        };
+        let runs = [run(&["a.zip", "c.zip"]), run(&["b.zip"]), run(&["c.zip"])];
+        assert_eq!(expired_artifacts(&runs, 1), ["a.zip", "b.zip"]);
+        assert_eq!(expired_artifacts(&runs, 2), ["a.zip"]);
+        assert!(expired_artifacts(&runs, 5).is_empty());
     }
 
     #[test]
-    fn test_tags_parsing() {
-        let tags_str = "smoke, regression, login";
-        let tags: Vec<String> = tags_str
-            .split(',')
-            .map(|t| t.trim().to_owned())
-            .filter(|t| !t.is_empty())
-            .collect();
-        assert_eq!(tags, vec!["smoke", "regression", "login"]);
+    fn test_dependency_order_and_skips() {
+        let names: Vec<String> = ["settings", "login", "signup", "feed"].iter().map(|s| s.to_string()).collect();
+        let settings_deps = vec!["signup".to_string(), "login".to_string()];
+        let login_deps = vec!["signup".to_string(), "not-in-run".to_string()];
+        let deps: HashMap<&str, &[String]> =
+            [("settings", settings_deps.as_slice()), ("login", login_deps.as_slice())].into_iter().collect();
+        assert_eq!(dependency_order(&names, &deps).unwrap(), ["signup", "login", "settings", "feed"]);
+
+        let a_deps = vec!["b".to_string()];
+        let b_deps = vec!["c".to_string()];
+        let c_deps = vec!["a".to_string()];
+        let cyclic: HashMap<&str, &[String]> =
+            [("a", a_deps.as_slice()), ("b", b_deps.as_slice()), ("c", c_deps.as_slice())].into_iter().collect();
+        let names: Vec<String> = ["x", "a", "b", "c"].iter().map(|s| s.to_string()).collect();
+        let err = dependency_order(&names, &cyclic).unwrap_err().to_string();
+        assert!(err.contains("a -> b -> c -> a"), "{}", err);
+
+        let outcomes: HashMap<String, CaseStatus> =
+            [("signup".to_string(), CaseStatus::Passed), ("login".to_string(), CaseStatus::Failed)].into_iter().collect();
+        let runs: Vec<String> = ["signup", "login", "settings", "b"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(dependency_skip_reason(&settings_deps, &outcomes, &runs).as_deref(), Some("dependency 'login' failed"));
+        assert_eq!(dependency_skip_reason(&a_deps, &outcomes, &runs), None);
+        assert_eq!(
+            dependency_skip_reason(&login_deps, &outcomes, &runs).as_deref(),
+            Some("dependency 'not-in-run' not in run")
+        );
+
+        let err = check_dependencies(["settings", "login", "signup"], &deps).unwrap_err().to_string();
+        assert_eq!(err, "Case 'login' depends on unknown case 'not-in-run'");
+        assert!(check_dependencies(["settings", "login", "signup", "not-in-run"], &deps).is_ok());
     }
 
     #[test]
-    fn test_recording_tmp_path() {
-        let path = recording_tmp_path("my-flow");
+    fn test_matrix_runs_expand_and_label() {
+        let specs = vec!["theme=light,dark".to_string(), "locale=en, ru".to_string()];
+        let runs = matrix_runs(&specs, 4, None).unwrap();
+        let labels: Vec<String> = runs.iter().map(|r| matrix_label("login", Some(&r.values))).collect();
         assert_eq!(
-            path,
-            PathBuf::from("/tmp/claude-mobile-recording-my-flow.json")
+            labels,
+            [
+                "login[locale=en,theme=light]",
+                "login[locale=en,theme=dark]",
+                "login[locale=ru,theme=light]",
+                "login[locale=ru,theme=dark]"
+            ]
         );
+        assert_eq!(matrix_label("login", None), "login");
+        assert!(runs.iter().all(|r| r.strings.is_none()));
+        let base = PlayOptions { config: HashMap::from([("theme".to_string(), "auto".to_string())]), ..play_options() };
+        let config = runs[1].play_options(&base).config;
+        assert_eq!((config["theme"].as_str(), config["locale"].as_str()), ("dark", "en"));
+
+        assert!(matrix_runs(&[], 1, None).unwrap().is_empty());
+        let err = matrix_runs(&specs, 3, None).unwrap_err().to_string();
+        assert!(err.contains("4 combinations"), "{}", err);
+        assert!(matrix_runs(&["locale".to_string()], 4, None).is_err());
+        assert!(matrix_runs(&["a=1".to_string(), "a=2".to_string()], 4, None).is_err());
     }
 
     #[test]
-    fn test_play_step_range_validation() {
-        // from >= to should fail
-        let from = 5usize.saturating_sub(1); // 4
-        let to = 3usize;
-        assert!(from >= to);
+    #[cfg(unix)]
+    fn test_hooks_run_on_host() {
+        let results = run_hooks(HookPhase::Before, &["echo ready".to_string(), "exit 3".to_string(), "echo never".to_string()], None);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].passed());
+        assert_eq!(results[0].output, "ready\n");
+        assert_eq!(results[1].error(), "before hook `exit 3` exited with 3");
+        let after = run_hooks(HookPhase::After, &["exit 1".to_string(), "echo cleaned".to_string()], None);
+        assert_eq!(after.len(), 2);
+
+        // A server left running in the background does not hold the hook up.
+        let start = std::time::Instant::now();
+        let server = run_hook(HookPhase::Before, "sleep 30 & echo started", None);
+        assert!(server.passed() && start.elapsed() < std::time::Duration::from_secs(10), "{:?}", server);
+        assert_eq!(server.output, "started\n");
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_millis(200);
+        let slow = run_hook(HookPhase::Before, "sleep 30", Some(deadline));
+        assert_eq!(slow.exit_code, None);
+        assert!(slow.error().contains("timed out"), "{}", slow.error());
+
+        // A timeout stops the hook's children too, not just the shell.
+        let tmp = tempfile::tempdir().unwrap();
+        let marker = tmp.path().join("late");
+        let deadline = std::time::Instant::now() + std::time::Duration::from_millis(200);
+        let command = format!("(sleep 1; touch '{}') & wait", marker.display());
+        assert_eq!(run_hook(HookPhase::Before, &command, Some(deadline)).exit_code, None);
+        std::thread::sleep(std::time::Duration::from_millis(1500));
+        assert!(!marker.exists());
     }
 
     #[test]
-    fn test_export_flow_steps_format() {
+    fn test_hooks_gate_the_case() {
+        assert_eq!(output_tail("héllo", 4), "llo");
+
         let scenario = Scenario {
-            version: 1,
-            name: "test".into(),
-            platform: "android".into(),
-            description: None,
-            tags: vec![],
-            steps: vec![
-                ScenarioStep {
-                    index: 0,
-                    step_type: "gesture".into(),
-                    action: "tap".into(),
-                    args: vec!["10".into(), "20".into()],
-                    timestamp_ms: 0,
-                    delay_before_ms: 0,
-                    label: None,
-                },
-            ],
-            created_at: "2026-05-27T00:00:00Z".into(),
-            updated_at: "2026-05-27T00:00:00Z".into(),
+            before: Some(vec!["true".to_string()]),
+            ..serde_json::from_str(r#"{"version":1,"name":"h","platform":"android","createdAt":"","updatedAt":""}"#).unwrap()
         };
-        // Just verify export_flow_steps doesn't panic on valid data.
-        // It prints to stdout so we can't capture it in a unit test easily,
-        // but structural correctness is covered by serialization tests.
-        assert_eq!(scenario.steps[0].action, "tap");
+        let opts = PlayOptions { stop_on_fail: true, dry_run: true, ..play_options() };
+        let err = play_loaded(scenario, "android", &opts).err().unwrap().to_string();
+        assert!(err.contains("pass --allow-hooks"), "{}", err);
     }
 }
@@ -1,7 +1,6 @@
 //! Integration setup commands.
 
 use std::env;
-use std::ffi::OsString;
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -229,7 +228,7 @@ fn home_dir() -> Result<PathBuf> {
     let drive = env::var_os("HOMEDRIVE");
     let path = env::var_os("HOMEPATH");
     if let (Some(drive), Some(path)) = (drive, path) {
-        let mut combined = OsString::from(drive);
+        let mut combined = drive;
         combined.push(path);
         return Ok(PathBuf::from(combined));
     }
@@ -114,7 +114,7 @@ fn epoch_to_datetime(secs: u64) -> (u64, u64, u64, u64, u64, u64) {
 }
 
 fn is_leap(y: u64) -> bool {
-    (y % 4 == 0 && y % 100 != 0) || y % 400 == 0
+    (y.is_multiple_of(4) && !y.is_multiple_of(100)) || y.is_multiple_of(400)
 }
 
 // ---------------------------------------------------------------------------
@@ -623,10 +623,8 @@ mod tests {
 
     #[test]
     fn test_duplicate_role_names_detected() {
-        let roles = vec![
-            DeviceRole { name: "sender".into(), device_id: "aaa".into() },
-            DeviceRole { name: "sender".into(), device_id: "bbb".into() },
-        ];
+        let roles = [DeviceRole { name: "sender".into(), device_id: "aaa".into() },
+            DeviceRole { name: "sender".into(), device_id: "bbb".into() }];
 
         let mut seen = std::collections::HashSet::new();
         let has_duplicate = roles.iter().any(|r| !seen.insert(&r.name));
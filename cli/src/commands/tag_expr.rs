@@ -0,0 +1,230 @@
+//! Boolean tag expressions for selecting scenarios, e.g.
+//! `smoke AND android AND NOT flaky` or `(login OR signup) AND NOT slow`.
+//!
+//! Operators are `AND`, `OR` and `NOT` (case-insensitive) with the usual
+//! precedence `NOT` > `AND` > `OR`; parentheses group. Every other word is a
+//! tag. The scenario's platform counts as a tag, so `android` matches any
+//! Android scenario without it being tagged explicitly.
+
+use anyhow::{bail, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagExpr {
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Box<TagExpr>, Box<TagExpr>),
+    Or(Box<TagExpr>, Box<TagExpr>),
+}
+
+impl TagExpr {
+    /// Parse an expression; errors name the offending token and its position.
+    pub fn parse(input: &str) -> Result<Self> {
+        let tokens = tokenize(input)?;
+        if tokens.is_empty() {
+            bail!("Empty tag expression");
+        }
+        let mut parser = Parser { tokens, pos: 0, input_len: input.len() };
+        let expr = parser.or()?;
+        if let Some(tok) = parser.peek() {
+            bail!(
+                "Unexpected '{}' at position {} in tag expression",
+                tok.text,
+                tok.offset + 1
+            );
+        }
+        Ok(expr)
+    }
+
+    /// Evaluate against a scenario's tags and platform.
+    pub fn matches(&self, tags: &[String], platform: &str) -> bool {
+        match self {
+            TagExpr::Tag(t) => t == platform || tags.iter().any(|x| x == t),
+            TagExpr::Not(e) => !e.matches(tags, platform),
+            TagExpr::And(a, b) => a.matches(tags, platform) && b.matches(tags, platform),
+            TagExpr::Or(a, b) => a.matches(tags, platform) || b.matches(tags, platform),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+    Word,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: Kind,
+    text: String,
+    /// Byte offset into the input.
+    offset: usize,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(i, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '(' || c == ')' {
+            chars.next();
+            let kind = if c == '(' { Kind::LParen } else { Kind::RParen };
+            tokens.push(Token { kind, text: c.to_string(), offset: i });
+            continue;
+        }
+        if !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':')) {
+            bail!("Unexpected character '{}' at position {} in tag expression", c, i + 1);
+        }
+        let mut word = String::new();
+        while let Some(&(_, c)) = chars.peek() {
+            if c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':') {
+                word.push(c);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        let kind = match word.to_ascii_uppercase().as_str() {
+            "AND" => Kind::And,
+            "OR" => Kind::Or,
+            "NOT" => Kind::Not,
+            _ => Kind::Word,
+        };
+        tokens.push(Token { kind, text: word, offset: i });
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    input_len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, kind: Kind) -> bool {
+        if self.peek().is_some_and(|t| t.kind == kind) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<TagExpr> {
+        let mut left = self.and()?;
+        while self.eat(Kind::Or) {
+            left = TagExpr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<TagExpr> {
+        let mut left = self.not()?;
+        while self.eat(Kind::And) {
+            left = TagExpr::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<TagExpr> {
+        if self.eat(Kind::Not) {
+            return Ok(TagExpr::Not(Box::new(self.not()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<TagExpr> {
+        let Some(tok) = self.peek().cloned() else {
+            bail!(
+                "Tag expression ends early at position {}: expected a tag or '('",
+                self.input_len + 1
+            );
+        };
+        self.pos += 1;
+        match tok.kind {
+            Kind::Word => Ok(TagExpr::Tag(tok.text)),
+            Kind::LParen => {
+                let inner = self.or()?;
+                if !self.eat(Kind::RParen) {
+                    match self.peek() {
+                        Some(t) => bail!(
+                            "Expected ')' but found '{}' at position {} in tag expression",
+                            t.text,
+                            t.offset + 1
+                        ),
+                        None => bail!(
+                            "Missing ')' for '(' at position {} in tag expression",
+                            tok.offset + 1
+                        ),
+                    }
+                }
+                Ok(inner)
+            }
+            _ => bail!(
+                "Expected a tag but found '{}' at position {} in tag expression",
+                tok.text,
+                tok.offset + 1
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_precedence_and_platform_tag() {
+        let expr = TagExpr::parse("smoke AND android AND NOT flaky").unwrap();
+        assert!(expr.matches(&tags(&["smoke"]), "android"));
+        assert!(!expr.matches(&tags(&["smoke", "flaky"]), "android"));
+        assert!(!expr.matches(&tags(&["smoke"]), "ios"));
+
+        // NOT binds tighter than AND, AND tighter than OR.
+        let expr = TagExpr::parse("a OR b and not c").unwrap();
+        assert!(expr.matches(&tags(&["a", "c"]), "ios"));
+        assert!(!expr.matches(&tags(&["b", "c"]), "ios"));
+    }
+
+    #[test]
+    fn test_parentheses() {
+        let expr = TagExpr::parse("(login OR signup) AND NOT slow").unwrap();
+        assert!(expr.matches(&tags(&["signup"]), "ios"));
+        assert!(!expr.matches(&tags(&["login", "slow"]), "ios"));
+    }
+
+    #[test]
+    fn test_parse_errors_point_at_token() {
+        let err = TagExpr::parse("smoke AND AND flaky").unwrap_err().to_string();
+        assert!(err.contains("'AND' at position 11"), "{}", err);
+
+        let err = TagExpr::parse("smoke flaky").unwrap_err().to_string();
+        assert!(err.contains("'flaky' at position 7"), "{}", err);
+
+        let err = TagExpr::parse("(smoke OR login").unwrap_err().to_string();
+        assert!(err.contains("Missing ')'"), "{}", err);
+
+        let err = TagExpr::parse("smoke AND").unwrap_err().to_string();
+        assert!(err.contains("ends early"), "{}", err);
+
+        let err = TagExpr::parse("smoke & fast").unwrap_err().to_string();
+        assert!(err.contains("'&' at position 7"), "{}", err);
+
+        assert!(TagExpr::parse("   ").is_err());
+    }
+}
@@ -3,6 +3,7 @@
 use std::io::{BufRead, BufReader, Write};
 use std::process::{Command, Stdio};
 use anyhow::{Result, Context, bail};
+use serde::Deserialize;
 use serde_json::{json, Value};
 use std::sync::atomic::{AtomicU64, Ordering};
 use base64::Engine as _;
@@ -72,6 +73,122 @@ pub fn screenshot(companion_path: Option<&str>) -> Result<Vec<u8>> {
     Ok(data)
 }
 
+/// A rectangle in logical (DPI-independent) screen coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl std::str::FromStr for Rect {
+    type Err = anyhow::Error;
+
+    /// `x,y,width,height`, e.g. `0,0,800,600`.
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        let [x, y, w, h] = parts[..] else {
+            bail!("Invalid region '{}': expected x,y,width,height", s);
+        };
+        let rect = Rect { x: x.parse()?, y: y.parse()?, width: w.parse()?, height: h.parse()? };
+        if rect.width == 0 || rect.height == 0 {
+            bail!("Invalid region '{}': width and height must be positive", s);
+        }
+        Ok(rect)
+    }
+}
+
+/// A top-level window as the companion's `get_window_info` reports it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Window {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub focused: bool,
+    #[serde(default)]
+    pub owner_name: Option<String>,
+}
+
+pub fn list_windows(companion_path: Option<&str>) -> Result<Vec<Window>> {
+    let path = get_companion_path(companion_path)?;
+    let result = rpc_call(&path, "get_window_info", json!({}))?;
+    serde_json::from_value(result["windows"].clone()).context("Unexpected get_window_info response")
+}
+
+/// Window by exact id, else by a case-insensitive title or owner substring.
+pub fn find_window(query: &str, companion_path: Option<&str>) -> Result<Window> {
+    pick_window(list_windows(companion_path)?, query)
+}
+
+fn pick_window(windows: Vec<Window>, query: &str) -> Result<Window> {
+    if let Some(w) = windows.iter().find(|w| w.id == query) {
+        return Ok(w.clone());
+    }
+    let needle = query.to_lowercase();
+    let mut matches: Vec<Window> = windows
+        .into_iter()
+        .filter(|w| {
+            w.title.to_lowercase().contains(&needle)
+                || w.owner_name.as_deref().is_some_and(|o| o.to_lowercase().contains(&needle))
+        })
+        .collect();
+    match matches.len() {
+        0 => bail!("No window matches '{}'", query),
+        1 => Ok(matches.remove(0)),
+        _ => {
+            // Prefer the focused one when the title is ambiguous.
+            if let Some(i) = matches.iter().position(|w| w.focused) {
+                return Ok(matches.remove(i));
+            }
+            let titles: Vec<String> = matches.iter().map(|w| format!("{} ({})", w.title, w.id)).collect();
+            bail!("'{}' matches several windows: {}; pass a window id", query, titles.join(", "))
+        }
+    }
+}
+
+/// Quality the region, window and monitor captures ask for: they are cropped
+/// to what the caller wants to look at, so keep JPEG artefacts out of them.
+const CAPTURE_QUALITY: u32 = 100;
+
+fn decode_capture(result: &Value) -> Result<image::DynamicImage> {
+    let b64 = result["base64"].as_str().context("No base64 in response")?;
+    image::load_from_memory(&base64::engine::general_purpose::STANDARD.decode(b64)?)
+        .context("Failed to decode desktop screenshot")
+}
+
+/// Capture a screen region given in logical coordinates.
+///
+/// The companion grabs just that rectangle off the display, so the pixels
+/// are not a crop of a downscaled full-screen shot, and coordinates on a
+/// secondary monitor (negative or past the primary screen) work as given.
+pub fn capture_region(rect: Rect, companion_path: Option<&str>) -> Result<image::DynamicImage> {
+    let path = get_companion_path(companion_path)?;
+    let result = rpc_call(&path, "screenshot_region", json!({
+        "x": rect.x,
+        "y": rect.y,
+        "width": rect.width,
+        "height": rect.height,
+        "quality": CAPTURE_QUALITY,
+    }))?;
+    decode_capture(&result)
+}
+
+/// Capture just one window; the companion looks up its bounds itself.
+pub fn capture_window(window: &Window, companion_path: Option<&str>) -> Result<image::DynamicImage> {
+    let path = get_companion_path(companion_path)?;
+    let result = rpc_call(&path, "screenshot", json!({"windowId": window.id, "quality": CAPTURE_QUALITY}))?;
+    decode_capture(&result)
+}
+
+/// Capture one monitor, by its index from [`get_monitors`].
+pub fn capture_monitor(index: u32, companion_path: Option<&str>) -> Result<image::DynamicImage> {
+    let path = get_companion_path(companion_path)?;
+    let result = rpc_call(&path, "screenshot", json!({"monitorIndex": index, "quality": CAPTURE_QUALITY}))?;
+    decode_capture(&result)
+}
+
 pub fn tap(x: i32, y: i32, companion_path: Option<&str>) -> Result<()> {
     let path = get_companion_path(companion_path)?;
     let result = rpc_call(&path, "tap", json!({"x": x, "y": y}))?;
@@ -173,3 +290,27 @@ pub fn get_monitors(companion_path: Option<&str>) -> Result<()> {
     println!("{}", serde_json::to_string_pretty(&result)?);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rect_parse_and_window_lookup() {
+        assert_eq!("0, 10, 800, 600".parse::<Rect>().unwrap(), Rect { x: 0, y: 10, width: 800, height: 600 });
+        assert!("0,0,800".parse::<Rect>().is_err());
+        assert!("0,0,0,600".parse::<Rect>().is_err());
+
+        let windows: Vec<Window> = serde_json::from_str(
+            r#"[{"id":"11","title":"Notes — Draft","bounds":{"x":0,"y":25,"width":900,"height":700},"focused":false,"ownerName":"Notes"},
+                {"id":"12","title":"Notes — Archive","bounds":{"x":40,"y":60,"width":900,"height":700},"focused":true},
+                {"id":"13","title":"Terminal","bounds":{"x":0,"y":0,"width":800,"height":500},"focused":false}]"#,
+        )
+        .unwrap();
+        assert_eq!(pick_window(windows.clone(), "12").unwrap().title, "Notes — Archive");
+        assert_eq!(pick_window(windows.clone(), "terminal").unwrap().id, "13");
+        // Ambiguous title: the focused window wins.
+        assert_eq!(pick_window(windows.clone(), "notes").unwrap().id, "12");
+        assert!(pick_window(windows, "browser").is_err());
+    }
+}
@@ -318,10 +318,8 @@ pub fn swipe(x1: i32, y1: i32, x2: i32, y2: i32, duration: u32, simulator: Optio
     // Use cliclick if available for reliable drag, otherwise AppleScript
     let cliclick = Command::new("which").arg("cliclick").output();
     if cliclick.is_ok() && cliclick.unwrap().status.success() {
-        let script = format!(
-            r#"tell application "Simulator" to activate
-delay 0.2"#
-        );
+        let script = r#"tell application "Simulator" to activate
+delay 0.2"#.to_string();
         let _ = Command::new("osascript").args(["-e", &script]).output();
 
         let _ = Command::new("cliclick")
@@ -545,8 +543,149 @@ end tell
     Ok(elements)
 }
 
+// ============== Accessibility hierarchy (idb) ==============
+//
+// `idb ui describe-all` reads the simulator's accessibility tree directly, so
+// unlike the AppleScript path above it does not need the Simulator window in
+// the foreground, and frames are in device points rather than Mac screen
+// coordinates. Elements are converted to the Android `UiElement` shape with
+// bounds scaled to screenshot pixels, so selector-based steps and taps work
+// the same on both platforms.
+
+/// True when the `idb` CLI is on PATH.
+fn idb_available() -> bool {
+    Command::new("which")
+        .arg("idb")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Resolve `simulator` to a concrete UDID (idb does not accept "booted").
+fn resolve_udid(simulator: Option<&str>) -> Result<String> {
+    let udid = get_simulator_udid(simulator)?;
+    if udid != "booted" {
+        return Ok(udid);
+    }
+    list_devices()?
+        .into_iter()
+        .find(|s| s.state == "Booted")
+        .map(|s| s.udid)
+        .context("No booted simulator found")
+}
+
+/// Raw accessibility hierarchy as JSON from `idb ui describe-all`.
+pub fn dump_hierarchy(simulator: Option<&str>) -> Result<String> {
+    let udid = resolve_udid(simulator)?;
+    let output = Command::new("idb")
+        .args(["ui", "describe-all", "--json", "--udid", &udid])
+        .output()
+        .context("Failed to run idb (install with `brew install facebook/fb/idb-companion` and `pip install fb-idb`)")?;
+    if !output.status.success() {
+        bail!("idb ui describe-all failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Element types that respond to taps.
+const IDB_TAPPABLE_TYPES: &[&str] = &[
+    "Button", "Cell", "Link", "TextField", "SecureTextField", "SearchField",
+    "Switch", "Slider", "Tab", "MenuItem", "Image", "CheckBox",
+];
+
+/// Parse `idb ui describe-all --json` into shared elements.
+///
+/// `scale` converts points to pixels (screenshot width / root frame width).
+fn parse_idb_elements(json: &str, scale: f64) -> Result<Vec<crate::android::UiElement>> {
+    let nodes: Vec<serde_json::Value> =
+        serde_json::from_str(json.trim()).context("Unexpected idb describe-all output")?;
+    let text_of = |v: &serde_json::Value, key: &str| v[key].as_str().unwrap_or("").to_string();
+
+    let mut elements = Vec::new();
+    for node in &nodes {
+        let frame = &node["frame"];
+        let (Some(x), Some(y), Some(w), Some(h)) = (
+            frame["x"].as_f64(),
+            frame["y"].as_f64(),
+            frame["width"].as_f64(),
+            frame["height"].as_f64(),
+        ) else {
+            continue;
+        };
+        let class = text_of(node, "type");
+        let px = |v: f64| (v * scale).round() as i32;
+        elements.push(crate::android::UiElement {
+            clickable: IDB_TAPPABLE_TYPES.contains(&class.as_str()),
+            class,
+            text: text_of(node, "AXLabel"),
+            resource_id: text_of(node, "AXUniqueId"),
+            content_desc: text_of(node, "AXValue"),
+            bounds: (px(x), px(y), px(x + w), px(y + h)),
+        });
+    }
+    Ok(elements)
+}
+
+/// Accessibility elements with bounds in screenshot pixels.
+pub fn hierarchy_elements(simulator: Option<&str>) -> Result<Vec<crate::android::UiElement>> {
+    let json = dump_hierarchy(simulator)?;
+    let root_width = serde_json::from_str::<Vec<serde_json::Value>>(json.trim())
+        .ok()
+        .and_then(|nodes| nodes.first().and_then(|n| n["frame"]["width"].as_f64()))
+        .filter(|w| *w > 0.0);
+    let scale = match root_width {
+        Some(w) => {
+            let png = screenshot(simulator)?;
+            image::load_from_memory(&png)?.width() as f64 / w
+        }
+        None => 1.0,
+    };
+    parse_idb_elements(&json, scale)
+}
+
+/// First element whose label, value or accessibility id contains `query`
+/// (case-insensitive). Its `center()` is a valid [`tap`] target.
+pub fn find_hierarchy_element(
+    query: &str,
+    simulator: Option<&str>,
+) -> Result<Option<crate::android::UiElement>> {
+    Ok(match_element(hierarchy_elements(simulator)?, query))
+}
+
+fn match_element(
+    elements: Vec<crate::android::UiElement>,
+    query: &str,
+) -> Option<crate::android::UiElement> {
+    let q = query.to_lowercase();
+    elements.into_iter().find(|e| {
+        e.width() > 0
+            && e.height() > 0
+            && [&e.text, &e.content_desc, &e.resource_id]
+                .iter()
+                .any(|f| f.to_lowercase().contains(&q))
+    })
+}
+
 /// Dump UI hierarchy via Accessibility
-pub fn ui_dump(format: &str, _simulator: Option<&str>) -> Result<()> {
+///
+/// Uses idb when installed (device-pixel bounds, shared element shape),
+/// otherwise falls back to AppleScript on the Simulator window.
+pub fn ui_dump(format: &str, simulator: Option<&str>) -> Result<()> {
+    if idb_available() {
+        let elements = hierarchy_elements(simulator)?;
+        if format == "json" {
+            println!("{}", serde_json::to_string_pretty(&elements)?);
+        } else {
+            for (i, e) in elements.iter().enumerate() {
+                println!(
+                    "[{}] {} \"{}\" [{},{}][{},{}]",
+                    i, e.class, e.label(), e.bounds.0, e.bounds.1, e.bounds.2, e.bounds.3
+                );
+            }
+        }
+        return Ok(());
+    }
+
     let elements = get_accessibility_elements()?;
 
     if elements.is_empty() {
@@ -576,7 +715,7 @@ pub fn ui_dump(format: &str, _simulator: Option<&str>) -> Result<()> {
     Ok(())
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Simulator {
     pub name: String,
     pub udid: String,
@@ -831,6 +970,12 @@ pub fn find_ui_element(
 
 /// Tap element by text
 pub fn tap_element(query: &str, simulator: Option<&str>) -> Result<()> {
+    if idb_available() {
+        let elem = find_hierarchy_element(query, simulator)?
+            .with_context(|| format!("Element '{}' not found", query))?;
+        let (x, y) = elem.center();
+        return tap(x, y, simulator);
+    }
     if let Some((x, y)) = find_element(query, simulator)? {
         // These are screen coordinates already (from AppleScript), tap directly
         let script = format!(
@@ -991,9 +1136,61 @@ pub fn reboot(simulator: Option<&str>) -> Result<()> {
     Ok(())
 }
 
+/// The simulator with UDID or name `query`. One name exists once per
+/// runtime, so a name that matches several is an error listing their UDIDs.
+fn find_simulator(simulators: Vec<Simulator>, query: &str) -> Result<Simulator> {
+    if let Some(sim) = simulators.iter().find(|s| s.udid == query) {
+        return Ok(sim.clone());
+    }
+    let mut named: Vec<Simulator> = simulators.into_iter().filter(|s| s.name == query).collect();
+    match named.len() {
+        0 => bail!("Simulator '{}' not found (pass a name or UDID)", query),
+        1 => Ok(named.remove(0)),
+        _ => {
+            let udids: Vec<String> = named.iter().map(|s| format!("{} ({})", s.udid, s.runtime)).collect();
+            bail!("Several simulators are named '{}'; pass a UDID instead: {}", query, udids.join(", "))
+        }
+    }
+}
+
+/// Wipe a simulator to factory state with `simctl erase`.
+///
+/// `simulator` is a name or UDID; "booted" is not accepted, since erasing
+/// should name its target. simctl refuses to erase a booted simulator, so
+/// one is only shut down first when `shutdown` is set.
+pub fn erase(simulator: &str, shutdown: bool) -> Result<()> {
+    let sim = find_simulator(list_devices()?, simulator)?;
+
+    if sim.state == "Booted" {
+        if !shutdown {
+            bail!(
+                "Simulator '{}' ({}) is booted and cannot be erased while running; pass --shutdown to shut it down first",
+                sim.name,
+                sim.udid
+            );
+        }
+        let output = simctl_exec(&["shutdown", &sim.udid])?;
+        if !output.status.success() {
+            bail!("Failed to shut down '{}': {}", sim.name, String::from_utf8_lossy(&output.stderr).trim());
+        }
+    }
+
+    let output = simctl_exec(&["erase", &sim.udid])?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("current state: Booted") {
+            bail!("Simulator '{}' booted again before it could be erased; shut it down and retry", sim.name);
+        }
+        bail!("Failed to erase '{}': {}", sim.name, stderr.trim());
+    }
+    println!("Erased simulator '{}' ({})", sim.name, sim.udid);
+    Ok(())
+}
+
 // ============== File Transfer ==============
 
 /// Push file to simulator (limited support)
+#[allow(dead_code)]
 pub fn push_file(local: &str, remote: &str, simulator: Option<&str>) -> Result<()> {
     let _udid = get_simulator_udid(simulator)?;
     println!("Note: File push to iOS simulator is not directly supported via simctl.");
@@ -1004,6 +1201,7 @@ pub fn push_file(local: &str, remote: &str, simulator: Option<&str>) -> Result<(
 }
 
 /// Pull file from simulator (limited support)
+#[allow(dead_code)]
 pub fn pull_file(remote: &str, local: &str, simulator: Option<&str>) -> Result<()> {
     let _udid = get_simulator_udid(simulator)?;
     println!("Note: File pull from iOS simulator is not directly supported via simctl.");
@@ -1040,16 +1238,280 @@ pub fn set_clipboard(text: &str, _simulator: Option<&str>) -> Result<()> {
     Ok(())
 }
 
+// ============== Appearance ==============
+
+/// Interface style set with `simctl ui appearance`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Appearance {
+    Light,
+    Dark,
+}
+
+impl Appearance {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "light" => Ok(Appearance::Light),
+            "dark" => Ok(Appearance::Dark),
+            other => bail!("Unknown appearance '{}'. Supported: light, dark", other),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Appearance::Light => "light",
+            Appearance::Dark => "dark",
+        }
+    }
+}
+
+/// Dynamic Type categories accepted by `simctl ui content_size`, smallest first.
+pub const CONTENT_SIZES: &[&str] = &[
+    "extra-small",
+    "small",
+    "medium",
+    "large",
+    "extra-large",
+    "extra-extra-large",
+    "extra-extra-extra-large",
+    "accessibility-medium",
+    "accessibility-large",
+    "accessibility-extra-large",
+    "accessibility-extra-extra-large",
+    "accessibility-extra-extra-extra-large",
+];
+
+/// Run `simctl ui <udid> <setting> [value]` and return its trimmed stdout.
+fn simctl_ui(udid: &str, setting: &str, value: Option<&str>) -> Result<String> {
+    let mut args = vec!["ui", udid, setting];
+    args.extend(value);
+    let output = simctl_exec(&args)?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        if stderr.contains("Unrecognized") || stderr.contains("not supported") || stderr.contains("Unknown") {
+            bail!("simctl ui {} is unsupported by this Xcode or simulator runtime: {}", setting, stderr.trim());
+        }
+        bail!("simctl ui {} failed: {}", setting, stderr.trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Compare what `simctl ui <setting>` reports after a change with what was asked for.
+fn check_ui_readback(setting: &str, wanted: &str, reported: &str) -> Result<()> {
+    match reported {
+        "unsupported" => bail!("{} is unsupported on this simulator runtime", setting),
+        // Older simctl only sets and prints nothing; nothing to verify against.
+        "" => Ok(()),
+        r if r == wanted => Ok(()),
+        r => bail!("{} did not apply: asked for {}, simulator reports {}", setting, wanted, r),
+    }
+}
+
+/// Switch the simulator between light and dark mode (iOS 13+).
+pub fn set_appearance(appearance: Appearance, simulator: Option<&str>) -> Result<()> {
+    let udid = get_simulator_udid(simulator)?;
+    simctl_ui(&udid, "appearance", Some(appearance.as_str()))?;
+    check_ui_readback("Appearance", appearance.as_str(), &simctl_ui(&udid, "appearance", None)?)?;
+    println!("Appearance: {}", appearance.as_str());
+    Ok(())
+}
+
+/// Set the Dynamic Type content size category, one of [`CONTENT_SIZES`].
+pub fn set_content_size(category: &str, simulator: Option<&str>) -> Result<()> {
+    if !CONTENT_SIZES.contains(&category) {
+        bail!("Unknown content size '{}'. Supported: {}", category, CONTENT_SIZES.join(", "));
+    }
+    let udid = get_simulator_udid(simulator)?;
+    simctl_ui(&udid, "content_size", Some(category))?;
+    check_ui_readback("Content size", category, &simctl_ui(&udid, "content_size", None)?)?;
+    println!("Content size: {}", category);
+    Ok(())
+}
+
+// ============== Screen Recording ==============
+
+/// How long simctl gets to write the mp4 trailer after SIGINT.
+const RECORDING_FINALIZE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// A running `simctl io recordVideo`. [`RecordingHandle::stop`] (or drop)
+/// sends SIGINT so simctl finalizes the file; killing it outright would
+/// leave an mp4 without its index, which most players refuse to open.
+pub struct RecordingHandle {
+    child: std::process::Child,
+    path: PathBuf,
+    stopped: bool,
+}
+
+/// Start recording the simulator screen to `out_path` (H.264 mp4).
+pub fn record_video(simulator: Option<&str>, out_path: &std::path::Path) -> Result<RecordingHandle> {
+    let udid = get_simulator_udid(simulator)?;
+    let mut child = Command::new("xcrun")
+        .args(["simctl", "io", &udid, "recordVideo", "--codec=h264", "--force"])
+        .arg(out_path)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start simctl recordVideo")?;
+
+    // A missing or shut-down simulator makes simctl exit right away.
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    if let Some(status) = child.try_wait()? {
+        bail!("simctl recordVideo exited immediately ({}): {}", status, child_stderr(&mut child));
+    }
+    Ok(RecordingHandle { child, path: out_path.to_path_buf(), stopped: false })
+}
+
+fn child_stderr(child: &mut std::process::Child) -> String {
+    use std::io::Read;
+    let mut err = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_string(&mut err);
+    }
+    err.trim().to_string()
+}
+
+impl RecordingHandle {
+    /// Stop recording and wait for the file to be finalized.
+    pub fn stop(mut self) -> Result<PathBuf> {
+        self.finish()?;
+        Ok(self.path.clone())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.stopped {
+            return Ok(());
+        }
+        self.stopped = true;
+        // simctl already gone: the simulator crashed or shut down. try_wait
+        // reaps it, so no zombie is left; the file may be truncated.
+        if let Some(status) = self.child.try_wait()? {
+            bail!("Recording ended early ({}): {}", status, child_stderr(&mut self.child));
+        }
+        let pid = self.child.id().to_string();
+        let _ = Command::new("kill").args(["-INT", &pid]).status();
+        let deadline = std::time::Instant::now() + RECORDING_FINALIZE_TIMEOUT;
+        while std::time::Instant::now() < deadline {
+            if let Some(status) = self.child.try_wait()? {
+                if !status.success() {
+                    bail!("simctl recordVideo failed ({}): {}", status, child_stderr(&mut self.child));
+                }
+                return Ok(());
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        bail!("simctl recordVideo did not finish within {:?}; {} may be incomplete", RECORDING_FINALIZE_TIMEOUT, self.path.display())
+    }
+}
+
+impl Drop for RecordingHandle {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            eprintln!("Warning: {:#}", e);
+        }
+    }
+}
+
 // ============== Tests ==============
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_find_simulator_by_udid_or_unique_name() {
+        let sim = |name: &str, udid: &str, runtime: &str| Simulator {
+            name: name.into(),
+            udid: udid.into(),
+            state: "Shutdown".into(),
+            runtime: runtime.into(),
+        };
+        let sims = || vec![sim("iPhone 15", "A-1", "iOS-17-5"), sim("iPhone 15", "B-2", "iOS-18-0"), sim("iPad Air", "C-3", "iOS-18-0")];
+        assert_eq!(find_simulator(sims(), "B-2").unwrap().runtime, "iOS-18-0");
+        assert_eq!(find_simulator(sims(), "iPad Air").unwrap().udid, "C-3");
+        let err = find_simulator(sims(), "iPhone 15").unwrap_err().to_string();
+        assert!(err.contains("A-1 (iOS-17-5)") && err.contains("B-2 (iOS-18-0)"), "{}", err);
+        assert!(find_simulator(sims(), "Pixel").is_err());
+    }
+
+    #[test]
+    fn test_appearance_and_content_size_readback() {
+        assert_eq!(Appearance::parse("dark").unwrap(), Appearance::Dark);
+        assert!(Appearance::parse("sepia").is_err());
+        assert!(CONTENT_SIZES.contains(&"accessibility-extra-large"));
+
+        assert!(check_ui_readback("Appearance", "dark", "dark").is_ok());
+        assert!(check_ui_readback("Appearance", "dark", "").is_ok());
+        let err = check_ui_readback("Appearance", "dark", "unsupported").unwrap_err().to_string();
+        assert!(err.contains("unsupported on this simulator runtime"));
+        let err = check_ui_readback("Content size", "large", "medium").unwrap_err().to_string();
+        assert_eq!(err, "Content size did not apply: asked for large, simulator reports medium");
+    }
+
+    fn fake_recording(script: &str) -> RecordingHandle {
+        let child = Command::new("sh")
+            .args(["-c", script])
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        RecordingHandle { child, path: PathBuf::from("/tmp/fake.mp4"), stopped: false }
+    }
+
+    #[test]
+    fn test_recording_stop_sends_sigint() {
+        // Exits cleanly on SIGINT, like simctl after writing the trailer.
+        let handle = fake_recording("trap 'exit 0' INT; while :; do sleep 0.05; done");
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert_eq!(handle.stop().unwrap(), PathBuf::from("/tmp/fake.mp4"));
+    }
+
+    #[test]
+    fn test_recording_reports_early_exit() {
+        let mut handle = fake_recording("echo 'Simulator shut down' >&2; exit 3");
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        let err = handle.finish().unwrap_err().to_string();
+        assert!(err.contains("ended early") && err.contains("Simulator shut down"), "{}", err);
+        // Already reaped: a second stop (e.g. on drop) is a no-op.
+        assert!(handle.finish().is_ok());
+    }
+
     #[test]
     fn test_get_simulator_udid_booted() {
         let result = get_simulator_udid(None);
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "booted");
     }
+
+    const IDB_SAMPLE: &str = r#"[
+        {"type":"Application","AXLabel":"Settings","frame":{"x":0,"y":0,"width":390,"height":844}},
+        {"type":"Button","AXLabel":"General","AXUniqueId":"com.apple.settings.general","AXValue":null,
+         "frame":{"x":16,"y":300,"width":358,"height":44}},
+        {"type":"StaticText","AXLabel":"Version","AXValue":"17.2","frame":{"x":20,"y":400,"width":100,"height":20}},
+        {"type":"Other","AXLabel":"broken"}
+    ]"#;
+
+    #[test]
+    fn test_parse_idb_elements_scales_to_pixels() {
+        let elements = parse_idb_elements(IDB_SAMPLE, 3.0).unwrap();
+        assert_eq!(elements.len(), 3);
+
+        let general = &elements[1];
+        assert_eq!(general.class, "Button");
+        assert_eq!(general.resource_id, "com.apple.settings.general");
+        assert!(general.clickable);
+        assert_eq!(general.bounds, (48, 900, 1122, 1032));
+        assert_eq!(general.center(), (585, 966));
+
+        assert!(!elements[2].clickable);
+        assert_eq!(elements[2].content_desc, "17.2");
+    }
+
+    #[test]
+    fn test_match_element_by_label_value_or_id() {
+        let elements = parse_idb_elements(IDB_SAMPLE, 1.0).unwrap();
+        assert_eq!(match_element(elements.clone(), "general").unwrap().class, "Button");
+        assert_eq!(match_element(elements.clone(), "17.2").unwrap().text, "Version");
+        assert!(match_element(elements, "bluetooth").is_none());
+    }
 }
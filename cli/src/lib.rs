@@ -6,6 +6,7 @@ pub mod desktop;
 pub mod ios;
 pub mod kernel;
 pub mod plugins;
+pub mod remote;
 pub mod screenshot;
 pub mod platform;
 pub mod utils;
@@ -9,8 +9,8 @@ mod cli;
 mod commands;
 mod desktop;
 mod ios;
-mod kernel;
-mod plugins;
+mod platform;
+mod remote;
 mod scale;
 mod screenshot;
 mod store;
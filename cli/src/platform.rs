@@ -4,6 +4,8 @@ use std::fmt;
 use std::str::FromStr;
 use anyhow::{Result, bail};
 
+use crate::{android, aurora, desktop, ios, remote};
+
 /// Supported platforms
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Platform {
@@ -11,6 +13,8 @@ pub enum Platform {
     Ios,
     Desktop,
     Aurora,
+    /// A device-farm device driven over HTTP (see [`crate::remote`]).
+    Remote,
 }
 
 impl FromStr for Platform {
@@ -22,7 +26,8 @@ impl FromStr for Platform {
             "ios" => Ok(Platform::Ios),
             "desktop" => Ok(Platform::Desktop),
             "aurora" => Ok(Platform::Aurora),
-            _ => bail!("Unknown platform: {}. Use 'android', 'ios', 'desktop', or 'aurora'", s),
+            "remote" => Ok(Platform::Remote),
+            _ => bail!("Unknown platform: {}. Use 'android', 'ios', 'desktop', 'aurora', or 'remote'", s),
         }
     }
 }
@@ -34,24 +39,410 @@ impl fmt::Display for Platform {
             Platform::Ios => write!(f, "ios"),
             Platform::Desktop => write!(f, "desktop"),
             Platform::Aurora => write!(f, "aurora"),
+            Platform::Remote => write!(f, "remote"),
         }
     }
 }
 
 impl Platform {
+    #[allow(dead_code)]
     pub fn is_android(&self) -> bool {
         matches!(self, Platform::Android)
     }
 
+    #[allow(dead_code)]
     pub fn is_ios(&self) -> bool {
         matches!(self, Platform::Ios)
     }
 
+    #[allow(dead_code)]
     pub fn is_desktop(&self) -> bool {
         matches!(self, Platform::Desktop)
     }
 
+    #[allow(dead_code)]
     pub fn is_aurora(&self) -> bool {
         matches!(self, Platform::Aurora)
     }
+
+    /// Whether this platform has a native equivalent of `action`.
+    pub fn supports(&self, action: NavAction) -> bool {
+        match (self, action) {
+            (Platform::Android | Platform::Aurora | Platform::Remote, _) => true,
+            (Platform::Ios, NavAction::Home) => true,
+            (Platform::Ios, NavAction::Back) => false,
+            (Platform::Desktop, _) => false,
+        }
+    }
+
+    /// Return to the launcher / home screen.
+    ///
+    /// `target` is the platform's device handle: the adb/audb serial, the iOS
+    /// simulator name, the desktop companion path, or the farm device id.
+    pub fn go_home(&self, target: Option<&str>) -> Result<()> {
+        self.navigate(NavAction::Home, target)
+    }
+
+    /// Navigate back one screen (system back button).
+    pub fn go_back(&self, target: Option<&str>) -> Result<()> {
+        self.navigate(NavAction::Back, target)
+    }
+
+    fn navigate(&self, action: NavAction, target: Option<&str>) -> Result<()> {
+        if !self.supports(action) {
+            return Err(UnsupportedAction { platform: *self, action: action.as_str() }.into());
+        }
+        let key = action.as_str();
+        match self {
+            Platform::Android => android::press_key(key, target),
+            Platform::Ios => ios::press_key(key, target),
+            Platform::Aurora => aurora::press_key(key, target),
+            Platform::Desktop => desktop::press_key(key, target),
+            Platform::Remote => {
+                let action = if action == NavAction::Home { Action::Home } else { Action::Back };
+                remote::RemoteDevice::connect(target)?.execute(&action).map(|_| ())
+            }
+        }
+    }
+}
+
+impl Platform {
+    /// Whether this platform has a backend for `action`. Navigation defers
+    /// to [`Platform::supports`]; the rest reflects which modules implement it.
+    pub fn supports_action(&self, action: &Action) -> bool {
+        match (self, action) {
+            (_, Action::Home) => self.supports(NavAction::Home),
+            (_, Action::Back) => self.supports(NavAction::Back),
+            (Platform::Aurora, Action::TapText { .. }) => false,
+            (Platform::Ios | Platform::Aurora | Platform::Desktop, Action::Type { char_delay_ms: Some(_), .. }) => false,
+            (Platform::Desktop, Action::Swipe { .. } | Action::LongPress { .. }) => false,
+            _ => true,
+        }
+    }
+
+    /// Run a platform-agnostic action on `target` (see [`Platform::go_home`]
+    /// for what the target is on each platform).
+    ///
+    /// Actions without a backend fail with [`UnsupportedAction`].
+    pub fn execute(&self, target: Option<&str>, action: &Action) -> Result<ActionOutcome> {
+        if !self.supports_action(action) {
+            return Err(UnsupportedAction { platform: *self, action: action.name() }.into());
+        }
+        match (self, action) {
+            (Platform::Remote, _) => return remote::RemoteDevice::connect(target)?.execute(action),
+            (_, Action::Home) => self.navigate(NavAction::Home, target)?,
+            (_, Action::Back) => self.navigate(NavAction::Back, target)?,
+            (_, Action::Wait { ms }) => std::thread::sleep(std::time::Duration::from_millis(*ms)),
+
+            (Platform::Android, Action::Tap { x, y }) => android::tap(*x, *y, target)?,
+            (Platform::Ios, Action::Tap { x, y }) => ios::tap(*x, *y, target)?,
+            (Platform::Aurora, Action::Tap { x, y }) => aurora::tap(*x, *y, target)?,
+            (Platform::Desktop, Action::Tap { x, y }) => desktop::tap(*x, *y, target)?,
+
+            (Platform::Android, Action::TapText { query }) => android::tap_element(query, target)?,
+            (Platform::Ios, Action::TapText { query }) => ios::tap_element(query, target)?,
+            (Platform::Desktop, Action::TapText { query }) => desktop::tap_by_text(query, target)?,
+
+            (Platform::Android, Action::Type { text, char_delay_ms: Some(ms) }) => {
+                android::type_text_slow(text, std::time::Duration::from_millis(*ms), target)?
+            }
+            (Platform::Android, Action::Type { text, char_delay_ms: None }) => android::input_text(text, target)?,
+            (Platform::Ios, Action::Type { text, char_delay_ms: None }) => ios::input_text(text, target)?,
+            (Platform::Aurora, Action::Type { text, char_delay_ms: None }) => aurora::input_text(text, target)?,
+            (Platform::Desktop, Action::Type { text, char_delay_ms: None }) => desktop::input_text(text, target)?,
+
+            (Platform::Android, Action::Swipe { x1, y1, x2, y2, duration_ms }) => {
+                android::swipe(*x1, *y1, *x2, *y2, *duration_ms, target)?
+            }
+            (Platform::Ios, Action::Swipe { x1, y1, x2, y2, duration_ms }) => {
+                ios::swipe(*x1, *y1, *x2, *y2, *duration_ms, target)?
+            }
+            (Platform::Aurora, Action::Swipe { x1, y1, x2, y2, duration_ms }) => {
+                aurora::swipe(*x1, *y1, *x2, *y2, *duration_ms, target)?
+            }
+
+            (Platform::Android, Action::LongPress { x, y, duration_ms }) => {
+                android::long_press(*x, *y, android::long_press_duration(*duration_ms, target), target)?
+            }
+            (Platform::Ios, Action::LongPress { x, y, duration_ms }) => {
+                ios::long_press(*x, *y, duration_ms.unwrap_or(1000), target)?
+            }
+            (Platform::Aurora, Action::LongPress { x, y, duration_ms }) => {
+                aurora::long_press(*x, *y, duration_ms.unwrap_or(1000), target)?
+            }
+
+            (Platform::Android, Action::PressKey { key }) => android::press_key(key, target)?,
+            (Platform::Ios, Action::PressKey { key }) => ios::press_key(key, target)?,
+            (Platform::Aurora, Action::PressKey { key }) => aurora::press_key(key, target)?,
+            (Platform::Desktop, Action::PressKey { key }) => desktop::press_key(key, target)?,
+
+            (Platform::Android, Action::Launch { package }) => android::launch_app(package, target)?,
+            (Platform::Ios, Action::Launch { package }) => ios::launch_app(package, target)?,
+            (Platform::Aurora, Action::Launch { package }) => aurora::launch_app(package, target)?,
+            (Platform::Desktop, Action::Launch { package }) => desktop::launch_app(package, target)?,
+
+            (Platform::Android, Action::Stop { package }) => android::stop_app(package, target)?,
+            (Platform::Ios, Action::Stop { package }) => ios::stop_app(package, target)?,
+            (Platform::Aurora, Action::Stop { package }) => aurora::stop_app(package, target)?,
+            (Platform::Desktop, Action::Stop { package }) => desktop::stop_app(package, target)?,
+
+            // What `supports_action` turns away; kept as an error should the two drift apart.
+            (Platform::Aurora, Action::TapText { .. })
+            | (Platform::Ios | Platform::Aurora | Platform::Desktop, Action::Type { char_delay_ms: Some(_), .. })
+            | (Platform::Desktop, Action::Swipe { .. } | Action::LongPress { .. }) => {
+                return Err(UnsupportedAction { platform: *self, action: action.name() }.into());
+            }
+        }
+        let message = match action {
+            Action::Tap { x, y } => format!("Tapped ({}, {})", x, y),
+            Action::TapText { query } => format!("Tapped \"{}\"", query),
+            Action::Type { text, char_delay_ms: Some(ms) } => format!("Typed \"{}\" at {} ms per character", text, ms),
+            Action::Type { text, char_delay_ms: None } => format!("Typed \"{}\"", text),
+            Action::Swipe { x1, y1, x2, y2, .. } => format!("Swiped ({},{}) -> ({},{})", x1, y1, x2, y2),
+            Action::LongPress { x, y, .. } => format!("Long pressed ({}, {})", x, y),
+            Action::PressKey { key } => format!("Pressed key \"{}\"", key),
+            Action::Home => "Navigated home".to_string(),
+            Action::Back => "Navigated back".to_string(),
+            Action::Wait { ms } => format!("Waited {}ms", ms),
+            Action::Launch { package } => format!("Launched \"{}\"", package),
+            Action::Stop { package } => format!("Stopped \"{}\"", package),
+        };
+        Ok(ActionOutcome { message })
+    }
+}
+
+/// The common subset of step actions every backend is dispatched through.
+///
+/// Scenario steps keep their string form (`tap 100 200`); [`Action::parse`]
+/// turns that into a typed action so the same step runs on any platform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Tap { x: i32, y: i32 },
+    TapText { query: String },
+    /// `char_delay_ms` types one character at a time (Android only).
+    Type { text: String, char_delay_ms: Option<u64> },
+    Swipe { x1: i32, y1: i32, x2: i32, y2: i32, duration_ms: u32 },
+    /// `None` holds for the platform default (on Android, the system
+    /// long-press threshold).
+    LongPress { x: i32, y: i32, duration_ms: Option<u32> },
+    PressKey { key: String },
+    Home,
+    Back,
+    Wait { ms: u64 },
+    Launch { package: String },
+    Stop { package: String },
+}
+
+impl Action {
+    /// Parse a step's action name and arguments.
+    ///
+    /// Returns `Ok(None)` for actions outside the common subset (e.g. the
+    /// Android-only assertions), so callers can handle those themselves.
+    pub fn parse(name: &str, args: &[String]) -> Result<Option<Self>> {
+        let need = |n: usize, usage: &str| -> Result<()> {
+            if args.len() < n {
+                bail!("{} requires {} arg{}: {}", name, n, if n == 1 { "" } else { "s" }, usage);
+            }
+            Ok(())
+        };
+        let action = match name {
+            "tap" => {
+                need(2, "X Y")?;
+                Action::Tap { x: args[0].parse()?, y: args[1].parse()? }
+            }
+            "tap-text" => {
+                need(1, "TEXT")?;
+                Action::TapText { query: args[0].clone() }
+            }
+            "input" => {
+                need(1, "TEXT [CHAR_DELAY_MS]")?;
+                Action::Type { text: args[0].clone(), char_delay_ms: args.get(1).map(|s| s.parse()).transpose()? }
+            }
+            "swipe" => {
+                need(4, "X1 Y1 X2 Y2 [DURATION_MS]")?;
+                Action::Swipe {
+                    x1: args[0].parse()?,
+                    y1: args[1].parse()?,
+                    x2: args[2].parse()?,
+                    y2: args[3].parse()?,
+                    duration_ms: args.get(4).and_then(|s| s.parse().ok()).unwrap_or(300),
+                }
+            }
+            "long-press" => {
+                need(2, "X Y [DURATION_MS]")?;
+                Action::LongPress {
+                    x: args[0].parse()?,
+                    y: args[1].parse()?,
+                    duration_ms: args.get(2).map(|s| s.parse()).transpose()?,
+                }
+            }
+            "key" => {
+                need(1, "KEY")?;
+                Action::PressKey { key: args[0].clone() }
+            }
+            "home" => Action::Home,
+            "back" => Action::Back,
+            "wait" => {
+                need(1, "MS")?;
+                Action::Wait { ms: args[0].parse()? }
+            }
+            "launch" => {
+                need(1, "PACKAGE")?;
+                Action::Launch { package: args[0].clone() }
+            }
+            "stop" => {
+                need(1, "PACKAGE")?;
+                Action::Stop { package: args[0].clone() }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(action))
+    }
+
+    /// The step arguments, in the form [`Action::parse`] reads.
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            Action::Tap { x, y } => vec![x.to_string(), y.to_string()],
+            Action::TapText { query } => vec![query.clone()],
+            Action::Type { text, char_delay_ms } => {
+                std::iter::once(text.clone()).chain(char_delay_ms.map(|d| d.to_string())).collect()
+            }
+            Action::Swipe { x1, y1, x2, y2, duration_ms } => {
+                [x1, y1, x2, y2].iter().map(|v| v.to_string()).chain([duration_ms.to_string()]).collect()
+            }
+            Action::LongPress { x, y, duration_ms } => {
+                [x.to_string(), y.to_string()].into_iter().chain(duration_ms.map(|d| d.to_string())).collect()
+            }
+            Action::PressKey { key } => vec![key.clone()],
+            Action::Home | Action::Back => Vec::new(),
+            Action::Wait { ms } => vec![ms.to_string()],
+            Action::Launch { package } | Action::Stop { package } => vec![package.clone()],
+        }
+    }
+
+    /// The step action name this was parsed from.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Tap { .. } => "tap",
+            Action::TapText { .. } => "tap-text",
+            Action::Type { .. } => "input",
+            Action::Swipe { .. } => "swipe",
+            Action::LongPress { .. } => "long-press",
+            Action::PressKey { .. } => "key",
+            Action::Home => "home",
+            Action::Back => "back",
+            Action::Wait { .. } => "wait",
+            Action::Launch { .. } => "launch",
+            Action::Stop { .. } => "stop",
+        }
+    }
+}
+
+/// What [`Platform::execute`] did, as a one-line summary for step logs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionOutcome {
+    pub message: String,
+}
+
+/// Platform-agnostic navigation actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavAction {
+    Home,
+    Back,
+}
+
+impl NavAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NavAction::Home => "home",
+            NavAction::Back => "back",
+        }
+    }
+}
+
+/// An action that has no equivalent on the target platform (e.g. "back" on iOS).
+///
+/// Surfaced through `anyhow`; callers can `downcast_ref::<UnsupportedAction>()`
+/// to skip rather than fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedAction {
+    pub platform: Platform,
+    pub action: &'static str,
+}
+
+impl fmt::Display for UnsupportedAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Action '{}' is not supported on {}", self.action, self.platform)
+    }
+}
+
+impl std::error::Error for UnsupportedAction {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nav_support_matrix() {
+        assert!(Platform::Android.supports(NavAction::Back));
+        assert!(Platform::Ios.supports(NavAction::Home));
+        assert!(!Platform::Ios.supports(NavAction::Back));
+        assert!(!Platform::Desktop.supports(NavAction::Home));
+    }
+
+    #[test]
+    fn test_unsupported_action_is_downcastable() {
+        let err = Platform::Ios.go_back(None).unwrap_err();
+        let unsupported = err.downcast_ref::<UnsupportedAction>().unwrap();
+        assert_eq!(unsupported.platform, Platform::Ios);
+        assert_eq!(err.to_string(), "Action 'back' is not supported on ios");
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_action_parse() {
+        assert_eq!(Action::parse("tap", &args(&["10", "20"])).unwrap(), Some(Action::Tap { x: 10, y: 20 }));
+        assert_eq!(
+            Action::parse("swipe", &args(&["0", "1", "2", "3"])).unwrap(),
+            Some(Action::Swipe { x1: 0, y1: 1, x2: 2, y2: 3, duration_ms: 300 })
+        );
+        assert_eq!(Action::parse("home", &[]).unwrap(), Some(Action::Home));
+        assert_eq!(Action::parse("assert-request", &args(&["GET", "/x"])).unwrap(), None);
+
+        assert_eq!(
+            Action::parse("long-press", &args(&["5", "6"])).unwrap(),
+            Some(Action::LongPress { x: 5, y: 6, duration_ms: None })
+        );
+        let press = Action::parse("long-press", &args(&["5", "6", "800"])).unwrap().unwrap();
+        assert_eq!(Action::parse(press.name(), &press.args()).unwrap(), Some(press));
+
+        let slow = Action::parse("input", &args(&["1234", "120"])).unwrap().unwrap();
+        assert_eq!(slow, Action::Type { text: "1234".into(), char_delay_ms: Some(120) });
+        assert_eq!(Action::parse(slow.name(), &slow.args()).unwrap(), Some(slow));
+
+        let swipe = Action::parse("swipe", &args(&["0", "1", "2", "3", "150"])).unwrap().unwrap();
+        assert_eq!(Action::parse(swipe.name(), &swipe.args()).unwrap(), Some(swipe));
+        let err = Action::parse("tap", &args(&["10"])).unwrap_err().to_string();
+        assert_eq!(err, "tap requires 2 args: X Y");
+        assert!(Action::parse("wait", &args(&["soon"])).is_err());
+    }
+
+    #[test]
+    fn test_execute_rejects_missing_backends() {
+        let tap_text = Action::TapText { query: "OK".into() };
+        let err = Platform::Aurora.execute(None, &tap_text).unwrap_err();
+        assert_eq!(err.downcast_ref::<UnsupportedAction>().unwrap().action, "tap-text");
+
+        let swipe = Action::Swipe { x1: 0, y1: 0, x2: 1, y2: 1, duration_ms: 100 };
+        assert!(!Platform::Desktop.supports_action(&swipe));
+        assert!(!Platform::Ios.supports_action(&Action::Back));
+        assert!(Platform::Android.supports_action(&swipe));
+
+        let slow = Action::Type { text: "1234".into(), char_delay_ms: Some(120) };
+        assert!(Platform::Android.supports_action(&slow));
+        assert!(!Platform::Ios.supports_action(&slow));
+    }
 }
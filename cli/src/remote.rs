@@ -0,0 +1,246 @@
+//! Remote device backend: drive a device-farm device over HTTP.
+//!
+//! Configured from the environment:
+//!
+//! - `DEVICE_FARM_URL` — API base URL (required), e.g. `https://farm.example.com/api/v1`
+//! - `DEVICE_FARM_TOKEN` — sent as `Authorization: Bearer <token>`; only
+//!   over https, or plain http to localhost
+//! - `DEVICE_FARM_DEVICE` — device id used when no `--device` is given
+//! - `DEVICE_FARM_TIMEOUT_SECS` — per-request timeout (default 60)
+//!
+//! The farm is expected to expose, per device id:
+//!
+//! - `GET  <base>/devices/<id>` → `{"state": "online", "platform": "android", "model": "..."}`
+//! - `POST <base>/devices/<id>/actions` with `{"action": "tap", "args": ["10", "20"]}`
+//!   → `{"message": "..."}`; actions and args are the scenario step forms
+//! - `GET  <base>/devices/<id>/screenshot` → PNG bytes
+//!
+//! Error responses may carry `{"error": "..."}`, which is surfaced as the
+//! error message.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use image::DynamicImage;
+use serde::Deserialize;
+
+use crate::platform::{Action, ActionOutcome};
+use crate::screenshot::CaptureBackend;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+/// Connecting should be quick even when actions are slow; a farm that does
+/// not answer in this time is treated as down.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where the farm is and how to authenticate.
+#[derive(Debug, Clone)]
+pub struct RemoteConfig {
+    pub base_url: String,
+    pub token: Option<String>,
+    pub device: Option<String>,
+    pub timeout: Duration,
+}
+
+impl RemoteConfig {
+    pub fn from_env() -> Result<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+        let base_url = var("DEVICE_FARM_URL").context("DEVICE_FARM_URL is not set (device-farm API base URL)")?;
+        let timeout = match var("DEVICE_FARM_TIMEOUT_SECS") {
+            Some(secs) => secs.trim().parse().with_context(|| format!("Invalid DEVICE_FARM_TIMEOUT_SECS '{}'", secs))?,
+            None => DEFAULT_TIMEOUT_SECS,
+        };
+        Self::new(&base_url, var("DEVICE_FARM_TOKEN"), var("DEVICE_FARM_DEVICE"), Duration::from_secs(timeout))
+    }
+
+    fn new(base_url: &str, token: Option<String>, device: Option<String>, timeout: Duration) -> Result<Self> {
+        let base_url = base_url.trim().trim_end_matches('/').to_string();
+        if !base_url.starts_with("https://") && !base_url.starts_with("http://") {
+            bail!("Device-farm URL must start with http:// or https://, got '{}'", base_url);
+        }
+        if token.is_some() && base_url.starts_with("http://") && !is_loopback(&base_url) {
+            bail!(
+                "Refusing to send DEVICE_FARM_TOKEN in clear text to {}; use https:// (plain http is only allowed for localhost)",
+                base_url
+            );
+        }
+        Ok(Self { base_url, token, device, timeout })
+    }
+}
+
+fn is_loopback(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_owned))
+        .is_some_and(|host| matches!(host.as_str(), "localhost" | "127.0.0.1" | "[::1]"))
+}
+
+/// One client for the whole process, so every action reuses the pooled
+/// connection (and TLS session) to the farm. Request timeouts come from
+/// each device's [`RemoteConfig`].
+fn shared_client() -> Result<reqwest::blocking::Client> {
+    static CLIENT: OnceLock<reqwest::blocking::Client> = OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client.clone());
+    }
+    let client = reqwest::blocking::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .build()
+        .context("Failed to build HTTP client")?;
+    Ok(CLIENT.get_or_init(|| client).clone())
+}
+
+/// What the farm reports about a device.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RemoteHealth {
+    pub state: String,
+    #[serde(default)]
+    pub platform: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// One device on the farm.
+pub struct RemoteDevice {
+    config: RemoteConfig,
+    device_id: String,
+    client: reqwest::blocking::Client,
+}
+
+/// Device ids go into URL paths, so keep them to a plain token.
+fn validate_device_id(id: &str) -> Result<()> {
+    if id.is_empty() || id.len() > 128 || !id.chars().all(|c| c.is_ascii_alphanumeric() || "._:-".contains(c)) {
+        bail!("Invalid remote device id '{}': use letters, digits, '.', '_', ':' or '-'", id);
+    }
+    Ok(())
+}
+
+impl RemoteDevice {
+    /// Configure from the environment; `device` overrides `DEVICE_FARM_DEVICE`.
+    pub fn connect(device: Option<&str>) -> Result<Self> {
+        Self::with_config(RemoteConfig::from_env()?, device)
+    }
+
+    fn with_config(config: RemoteConfig, device: Option<&str>) -> Result<Self> {
+        let device_id = device
+            .map(str::to_owned)
+            .or_else(|| config.device.clone())
+            .context("No remote device: pass --device or set DEVICE_FARM_DEVICE")?;
+        validate_device_id(&device_id)?;
+        Ok(Self { config, device_id, client: shared_client()? })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.device_id
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/devices/{}{}", self.config.base_url, self.device_id, path)
+    }
+
+    fn send(&self, request: reqwest::blocking::RequestBuilder, what: &str) -> Result<reqwest::blocking::Response> {
+        let request = request.timeout(self.config.timeout);
+        let request = match &self.config.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        let response = request.send().map_err(|e| {
+            let kind = if e.is_timeout() { "timed out" } else { "failed" };
+            anyhow::anyhow!("Remote {} on '{}' {}: {}", what, self.device_id, kind, e)
+        })?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().unwrap_or_default();
+            bail!("Remote {} on '{}' failed ({}): {}", what, self.device_id, status, error_message(&body));
+        }
+        Ok(response)
+    }
+
+    /// Ask the farm about the device; fails unless it reports `online`.
+    pub fn health(&self) -> Result<RemoteHealth> {
+        let health: RemoteHealth = self
+            .send(self.client.get(self.url("")), "health check")?
+            .json()
+            .context("Unexpected health response from device farm")?;
+        if !health.state.eq_ignore_ascii_case("online") {
+            bail!("Remote device '{}' is {}, not online", self.device_id, health.state);
+        }
+        Ok(health)
+    }
+
+    /// Forward an action; `wait` runs locally.
+    pub fn execute(&self, action: &Action) -> Result<ActionOutcome> {
+        if let Action::Wait { ms } = action {
+            std::thread::sleep(Duration::from_millis(*ms));
+            return Ok(ActionOutcome { message: format!("Waited {}ms", ms) });
+        }
+        let body = serde_json::json!({ "action": action.name(), "args": action.args() });
+        let reply: serde_json::Value = self
+            .send(self.client.post(self.url("/actions")).json(&body), action.name())?
+            .json()
+            .unwrap_or_default();
+        let message = reply["message"].as_str().map(str::to_owned);
+        Ok(ActionOutcome {
+            message: message.unwrap_or_else(|| format!("{} on remote device '{}'", action.name(), self.device_id)),
+        })
+    }
+
+    pub fn screenshot_png(&self) -> Result<Vec<u8>> {
+        let bytes = self.send(self.client.get(self.url("/screenshot")), "screenshot")?.bytes()?;
+        if !bytes.starts_with(b"\x89PNG") {
+            bail!("Remote screenshot from '{}' is not a PNG", self.device_id);
+        }
+        Ok(bytes.to_vec())
+    }
+}
+
+/// The `error` field of a JSON error body, else the body itself.
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| v["error"].as_str().map(str::to_owned))
+        .unwrap_or_else(|| body.trim().chars().take(500).collect())
+}
+
+/// [`CaptureBackend`] for a farm device.
+pub struct RemoteCapture {
+    pub device: RemoteDevice,
+}
+
+impl CaptureBackend for RemoteCapture {
+    fn capture(&self) -> Result<DynamicImage> {
+        Ok(image::load_from_memory(&self.capture_png()?)?)
+    }
+
+    fn capture_png(&self) -> Result<Vec<u8>> {
+        self.device.screenshot_png()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_and_device_id() {
+        let config = RemoteConfig::new("https://farm.example.com/api/", None, Some("pixel-8".into()), Duration::from_secs(5)).unwrap();
+        assert_eq!(config.base_url, "https://farm.example.com/api");
+        assert!(RemoteConfig::new("farm.example.com", None, None, Duration::from_secs(5)).is_err());
+        let token = || Some("secret".to_string());
+        let err = RemoteConfig::new("http://farm.example.com", token(), None, Duration::from_secs(5)).unwrap_err();
+        assert!(err.to_string().contains("clear text"));
+        assert!(RemoteConfig::new("http://farm.example.com", None, None, Duration::from_secs(5)).is_ok());
+        assert!(RemoteConfig::new("http://localhost:8080/api", token(), None, Duration::from_secs(5)).is_ok());
+        assert!(RemoteConfig::new("http://[::1]:8080", token(), None, Duration::from_secs(5)).is_ok());
+
+        let device = RemoteDevice::with_config(config.clone(), None).unwrap();
+        assert_eq!(device.url("/actions"), "https://farm.example.com/api/devices/pixel-8/actions");
+        assert_eq!(RemoteDevice::with_config(config.clone(), Some("s23:1")).unwrap().id(), "s23:1");
+        assert!(RemoteDevice::with_config(config.clone(), Some("../admin")).is_err());
+        let no_device = RemoteConfig { device: None, ..config };
+        assert!(RemoteDevice::with_config(no_device, None).is_err());
+
+        assert_eq!(error_message(r#"{"error":"device busy"}"#), "device busy");
+        assert_eq!(error_message("Bad Gateway\n"), "Bad Gateway");
+    }
+}
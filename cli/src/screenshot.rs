@@ -8,32 +8,483 @@ use imageproc::drawing::{draw_hollow_rect_mut, draw_text_mut};
 use imageproc::rect::Rect;
 use ab_glyph::{FontArc, PxScale};
 
-use crate::{android, ios};
+use crate::{android, aurora, desktop, ios};
 
-/// Take screenshot with optional compression
-pub fn take_screenshot(
+/// Reference-grid settings for [`overlay_grid`].
+#[derive(Debug, Clone, Copy)]
+pub struct GridOverlay {
+    /// Distance between grid lines in device pixels.
+    pub spacing_px: u32,
+    /// Label every N-th line with its coordinate (0 = no labels).
+    pub label_every: u32,
+}
+
+/// Where [`add_evidence_footer`] puts its strip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FooterPosition {
+    Top,
+    Bottom,
+}
+
+impl FooterPosition {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "top" => Ok(FooterPosition::Top),
+            "bottom" => Ok(FooterPosition::Bottom),
+            other => anyhow::bail!("Unknown footer position '{}'. Supported: top, bottom", other),
+        }
+    }
+}
+
+/// Identifying text burned into a capture by [`add_evidence_footer`].
+#[derive(Debug, Clone)]
+pub struct EvidenceFooter {
+    /// One line of text each (device, timestamp, test/step ids, …).
+    pub lines: Vec<String>,
+    pub font_px: f32,
+    pub position: FooterPosition,
+}
+
+/// Downscale + JPEG settings applied by [`compress_image`].
+#[derive(Debug, Clone, Copy)]
+pub struct CompressOptions {
+    pub max_width: u32,
+    /// Also fit within this height (aspect ratio kept), if set.
+    pub max_height: Option<u32>,
+    /// JPEG quality, 1-100.
+    pub quality: u8,
+}
+
+/// Cropping applied by [`apply_trim_png`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TrimOptions {
+    /// Rows to drop from the top (status bar), 0 to keep.
+    pub status_bar_px: u32,
+    /// Crop uniform-colour borders (letterboxing).
+    pub borders: bool,
+}
+
+/// Max per-channel difference for a pixel to count as border colour.
+const TRIM_TOLERANCE: u8 = 8;
+
+/// A source of screen captures.
+///
+/// The built-in backends wrap each platform's capture command; anything else
+/// (a cloud device farm, a recorded session) can implement `capture` and go
+/// through the same trim/grid/compress pipeline via [`take_screenshot`].
+pub trait CaptureBackend: Send + Sync {
+    /// Capture the current screen.
+    fn capture(&self) -> Result<DynamicImage>;
+
+    /// Capture as PNG bytes. Backends whose device already returns PNG
+    /// override this to skip a decode/encode round trip.
+    fn capture_png(&self) -> Result<Vec<u8>> {
+        encode(&self.capture()?, EncodeFormat::Png)
+    }
+
+    /// Capture as an [`Image`] that is decoded only if its pixels are used.
+    fn capture_image(&self) -> Result<Image> {
+        Image::from_png(self.capture_png()?)
+    }
+}
+
+/// Encoding for [`encode_to_writer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeFormat {
+    Png,
+    /// Quality 1-100; alpha is dropped.
+    Jpeg { quality: u8 },
+    /// Lossless WebP: usually well under the PNG size for UI screens.
+    WebP,
+}
+
+/// Quality of a bare `jpeg`, which is also the default report encoding.
+const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+impl EncodeFormat {
+    /// `png`, `webp`, `jpeg` or `jpeg:QUALITY` (default quality 85).
+    pub fn parse(s: &str) -> Result<Self> {
+        let (name, quality) = match s.split_once(':') {
+            Some((name, q)) => (name, Some(q)),
+            None => (s, None),
+        };
+        match (name, quality) {
+            ("png", None) => Ok(EncodeFormat::Png),
+            ("webp", None) => Ok(EncodeFormat::WebP),
+            ("jpeg" | "jpg", None) => Ok(EncodeFormat::Jpeg { quality: DEFAULT_JPEG_QUALITY }),
+            ("jpeg" | "jpg", Some(q)) => match q.parse::<u8>() {
+                Ok(quality @ 1..=100) => Ok(EncodeFormat::Jpeg { quality }),
+                _ => anyhow::bail!("JPEG quality must be 1-100, got '{}'", q),
+            },
+            _ => anyhow::bail!("Unknown image format '{}'. Supported: png, webp, jpeg[:QUALITY]", s),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            EncodeFormat::Png => "png",
+            EncodeFormat::Jpeg { .. } => "jpg",
+            EncodeFormat::WebP => "webp",
+        }
+    }
+}
+
+/// What a capture is for, which decides its encoding (see [`CaptureEncodings`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapturePurpose {
+    /// Stored and compared against; always lossless PNG.
+    Baseline,
+    /// Attached to a run report for people to look at.
+    Report,
+    /// Sent to a model, where size is cost.
+    Model,
+}
+
+impl CapturePurpose {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "baseline" => Ok(CapturePurpose::Baseline),
+            "report" => Ok(CapturePurpose::Report),
+            "model" => Ok(CapturePurpose::Model),
+            other => anyhow::bail!("Unknown capture purpose '{}'. Supported: baseline, report, model", other),
+        }
+    }
+}
+
+/// Per-purpose encodings, from the `screenshot.report-format` and
+/// `screenshot.model-format` config keys. Baselines are not configurable:
+/// a lossy baseline would turn compression noise into diffs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureEncodings {
+    pub report: EncodeFormat,
+    pub model: EncodeFormat,
+}
+
+impl Default for CaptureEncodings {
+    fn default() -> Self {
+        Self { report: EncodeFormat::Jpeg { quality: DEFAULT_JPEG_QUALITY }, model: EncodeFormat::Jpeg { quality: 70 } }
+    }
+}
+
+impl CaptureEncodings {
+    /// Read the config keys through `lookup`; missing keys keep the defaults.
+    pub fn from_config(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut encodings = Self::default();
+        if let Some(v) = lookup("screenshot.report-format") {
+            encodings.report = EncodeFormat::parse(&v).context("Invalid screenshot.report-format")?;
+        }
+        if let Some(v) = lookup("screenshot.model-format") {
+            encodings.model = EncodeFormat::parse(&v).context("Invalid screenshot.model-format")?;
+        }
+        Ok(encodings)
+    }
+
+    pub fn format(&self, purpose: CapturePurpose) -> EncodeFormat {
+        match purpose {
+            CapturePurpose::Baseline => EncodeFormat::Png,
+            CapturePurpose::Report => self.report,
+            CapturePurpose::Model => self.model,
+        }
+    }
+}
+
+/// Encode `img` straight into `writer`, so a large image can go to a file
+/// or socket without the whole encoded file sitting in memory as well.
+/// Wrap unbuffered writers (files, sockets) in a `BufWriter`.
+pub fn encode_to_writer(img: &DynamicImage, mut writer: impl std::io::Write, format: EncodeFormat) -> Result<()> {
+    match format {
+        EncodeFormat::Png => img
+            .write_with_encoder(image::codecs::png::PngEncoder::new(&mut writer))
+            .context("PNG encoding failed")?,
+        EncodeFormat::Jpeg { quality } => {
+            let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut writer, quality.clamp(1, 100));
+            DynamicImage::ImageRgb8(img.to_rgb8()).write_with_encoder(encoder).context("JPEG encoding failed")?
+        }
+        EncodeFormat::WebP => img
+            .write_with_encoder(image::codecs::webp::WebPEncoder::new_lossless(&mut writer))
+            .context("WebP encoding failed")?,
+    }
+    writer.flush().context("Failed to flush encoded image")
+}
+
+/// [`encode_to_writer`] into a buffer.
+pub fn encode(img: &DynamicImage, format: EncodeFormat) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    encode_to_writer(img, &mut out, format)?;
+    Ok(out)
+}
+
+fn decode_png(png: &[u8]) -> Result<DynamicImage> {
+    image::load_from_memory(png).context("Failed to decode screenshot")
+}
+
+/// Width and height from a PNG's IHDR chunk, without decoding it.
+fn png_dimensions(png: &[u8]) -> Result<(u32, u32)> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    if png.len() < 24 || !png.starts_with(SIGNATURE) || &png[12..16] != b"IHDR" {
+        anyhow::bail!("Not a PNG image");
+    }
+    let be = |at: usize| u32::from_be_bytes([png[at], png[at + 1], png[at + 2], png[at + 3]]);
+    Ok((be(16), be(20)))
+}
+
+/// A capture that stays PNG-encoded until its pixels are needed.
+///
+/// Saving or re-sending the PNG never decodes it, and [`Image::dimensions`]
+/// reads the header. The first [`Image::pixels`] call decodes once and keeps
+/// the result; the cache is a `OnceLock`, so an `Image` can be shared across
+/// capture threads.
+pub struct Image {
+    png: Option<Vec<u8>>,
+    dimensions: (u32, u32),
+    /// Decoded pixels, or the decode error, once asked for.
+    decoded: std::sync::OnceLock<std::result::Result<DynamicImage, String>>,
+}
+
+impl Image {
+    /// Wrap PNG bytes; only the header is read here.
+    pub fn from_png(png: Vec<u8>) -> Result<Self> {
+        let dimensions = png_dimensions(&png)?;
+        Ok(Self { png: Some(png), dimensions, decoded: std::sync::OnceLock::new() })
+    }
+
+    /// An image that is already decoded (e.g. rendered locally).
+    pub fn from_pixels(img: DynamicImage) -> Self {
+        let decoded = std::sync::OnceLock::new();
+        let dimensions = img.dimensions();
+        let _ = decoded.set(Ok(img));
+        Self { png: None, dimensions, decoded }
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    /// The pixels, decoding the PNG on first use.
+    pub fn pixels(&self) -> Result<&DynamicImage> {
+        let decoded = self.decoded.get_or_init(|| match &self.png {
+            Some(png) => decode_png(png).map_err(|e| format!("{:#}", e)),
+            None => Err("image has neither pixels nor PNG data".into()),
+        });
+        decoded.as_ref().map_err(|e| anyhow::anyhow!("{}", e))
+    }
+
+    /// PNG bytes: the original encoding if there is one.
+    pub fn png(&self) -> Result<std::borrow::Cow<'_, [u8]>> {
+        match &self.png {
+            Some(png) => Ok(std::borrow::Cow::Borrowed(png)),
+            None => Ok(std::borrow::Cow::Owned(encode(self.pixels()?, EncodeFormat::Png)?)),
+        }
+    }
+
+    /// Write the image as PNG to `path`.
+    pub fn save_png(&self, path: &std::path::Path) -> Result<()> {
+        std::fs::write(path, self.png()?).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Android-only capture settings.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AndroidCaptureOptions {
+    /// Render FLAG_SECURE windows from the UI hierarchy instead of failing.
+    pub allow_secure_bypass: bool,
+    /// Fixed strategy; `None` tries screencap and falls back to screenrecord
+    /// when the frame looks empty.
+    pub strategy: Option<android::CaptureStrategy>,
+}
+
+/// `adb exec-out screencap` (or screenrecord), with FLAG_SECURE detection.
+pub struct AndroidCapture {
+    pub device: Option<String>,
+    pub options: AndroidCaptureOptions,
+}
+
+/// `xcrun simctl io screenshot`.
+pub struct IosCapture {
+    pub simulator: Option<String>,
+}
+
+/// What part of the desktop [`DesktopCapture`] grabs.
+#[derive(Debug, Clone, Default)]
+pub enum DesktopScope {
+    #[default]
+    Screen,
+    /// A window by id or title, see [`desktop::find_window`].
+    Window(String),
+    /// A region in logical screen coordinates.
+    Region(desktop::Rect),
+    /// One monitor, by its index from `desktop get-monitors`.
+    Monitor(u32),
+}
+
+/// Desktop companion app.
+pub struct DesktopCapture {
+    pub companion_path: Option<String>,
+    pub scope: DesktopScope,
+}
+
+/// `audb exec-out screencap`.
+pub struct AuroraCapture {
+    pub device: Option<String>,
+}
+
+impl CaptureBackend for AndroidCapture {
+    fn capture(&self) -> Result<DynamicImage> {
+        decode_png(&self.capture_png()?)
+    }
+
+    fn capture_png(&self) -> Result<Vec<u8>> {
+        android_screenshot_checked(self.device.as_deref(), self.options)
+    }
+}
+
+impl CaptureBackend for IosCapture {
+    fn capture(&self) -> Result<DynamicImage> {
+        decode_png(&self.capture_png()?)
+    }
+
+    fn capture_png(&self) -> Result<Vec<u8>> {
+        ios::screenshot(self.simulator.as_deref())
+    }
+}
+
+impl CaptureBackend for DesktopCapture {
+    fn capture(&self) -> Result<DynamicImage> {
+        let companion = self.companion_path.as_deref();
+        match &self.scope {
+            DesktopScope::Screen => decode_png(&desktop::screenshot(companion)?),
+            DesktopScope::Window(query) => desktop::capture_window(&desktop::find_window(query, companion)?, companion),
+            DesktopScope::Region(rect) => desktop::capture_region(*rect, companion),
+            DesktopScope::Monitor(index) => desktop::capture_monitor(*index, companion),
+        }
+    }
+
+    /// Window, region and monitor captures arrive decoded; keep them that way.
+    fn capture_image(&self) -> Result<Image> {
+        Ok(Image::from_pixels(self.capture()?))
+    }
+}
+
+impl CaptureBackend for AuroraCapture {
+    fn capture(&self) -> Result<DynamicImage> {
+        decode_png(&self.capture_png()?)
+    }
+
+    fn capture_png(&self) -> Result<Vec<u8>> {
+        aurora::screenshot(self.device.as_deref())
+    }
+}
+
+/// Built-in backend for a platform name.
+pub fn backend_for(
     platform: &str,
-    output: Option<&str>,
-    compress: bool,
-    max_width: u32,
-    quality: u8,
     simulator: Option<&str>,
     device: Option<&str>,
-) -> Result<()> {
-    // Capture screenshot
-    let png_data = if platform == "android" {
-        android::screenshot(device)?
-    } else {
-        ios::screenshot(simulator)?
+    companion_path: Option<&str>,
+    android_options: AndroidCaptureOptions,
+) -> Result<Box<dyn CaptureBackend>> {
+    Ok(match platform {
+        "android" => Box::new(AndroidCapture { device: device.map(str::to_owned), options: android_options }),
+        "ios" => Box::new(IosCapture { simulator: simulator.map(str::to_owned) }),
+        "desktop" => Box::new(DesktopCapture {
+            companion_path: companion_path.map(str::to_owned),
+            scope: DesktopScope::Screen,
+        }),
+        "aurora" => Box::new(AuroraCapture { device: device.map(str::to_owned) }),
+        "remote" => Box::new(crate::remote::RemoteCapture { device: crate::remote::RemoteDevice::connect(device)? }),
+        other => anyhow::bail!("Unsupported platform for screenshot: {}", other),
+    })
+}
+
+/// One device in a [`capture_all`] batch.
+pub struct CaptureTarget {
+    /// Label reported back with the result (usually the device serial).
+    pub id: String,
+    pub backend: Box<dyn CaptureBackend>,
+}
+
+/// Capture every target, running up to `concurrency` captures at once.
+///
+/// Results come back in input order, one per target; a failing device only
+/// fails its own entry.
+pub fn capture_all(targets: &[CaptureTarget], concurrency: usize) -> Result<Vec<(String, Result<Image>)>> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    if concurrency == 0 {
+        anyhow::bail!("Capture concurrency must be at least 1");
+    }
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<Image>>>> =
+        Mutex::new(targets.iter().map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.min(targets.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(target) = targets.get(i) else { break };
+                let result = target.backend.capture_image();
+                results.lock().unwrap_or_else(|e| e.into_inner())[i] = Some(result);
+            });
+        }
+    });
+    let results = results.into_inner().unwrap_or_else(|e| e.into_inner());
+    Ok(targets
+        .iter()
+        .zip(results)
+        .map(|(t, r)| (t.id.clone(), r.unwrap_or_else(|| Err(anyhow::anyhow!("capture did not run")))))
+        .collect())
+}
+
+/// Capture from `backend` and apply trim, grid and compression, in that
+/// order. `format` re-encodes the PNG result; it is not combined with
+/// `compress`, which already produces JPEG.
+pub fn process_capture(
+    backend: &dyn CaptureBackend,
+    compress: Option<CompressOptions>,
+    grid: Option<GridOverlay>,
+    trim: Option<TrimOptions>,
+    footer: Option<&EvidenceFooter>,
+    format: Option<EncodeFormat>,
+) -> Result<Vec<u8>> {
+    let png_data = backend.capture_png()?;
+
+    let png_data = match trim {
+        Some(t) => apply_trim_png(&png_data, t)?,
+        None => png_data,
     };
 
-    // Process image
-    let final_data = if compress {
-        compress_image(&png_data, max_width, quality)?
-    } else {
-        png_data
+    // Grid goes on before compression so labels stay in device coordinates
+    let png_data = match grid {
+        Some(g) => apply_grid_png(&png_data, g)?,
+        None => png_data,
     };
 
+    // The footer extends the canvas, so it comes after anything measured in
+    // device pixels.
+    let png_data = match footer {
+        Some(f) => encode(&DynamicImage::ImageRgba8(add_evidence_footer(&decode_png(&png_data)?, f)?), EncodeFormat::Png)?,
+        None => png_data,
+    };
+
+    match (compress, format) {
+        (Some(c), _) => compress_image(&png_data, c),
+        (None, Some(EncodeFormat::Png) | None) => Ok(png_data),
+        (None, Some(f)) => encode(&decode_png(&png_data)?, f),
+    }
+}
+
+/// Take screenshot with optional compression
+pub fn take_screenshot(
+    backend: &dyn CaptureBackend,
+    output: Option<&str>,
+    compress: Option<CompressOptions>,
+    grid: Option<GridOverlay>,
+    trim: Option<TrimOptions>,
+    footer: Option<&EvidenceFooter>,
+    format: Option<EncodeFormat>,
+) -> Result<()> {
+    let final_data = process_capture(backend, compress, grid, trim, footer, format)?;
+
     // Output
     if let Some(path) = output {
         std::fs::write(path, &final_data)?;
@@ -48,8 +499,134 @@ pub fn take_screenshot(
     Ok(())
 }
 
+/// Share of near-black pixels above which a capture counts as blank.
+const BLANK_FRACTION: f64 = 0.995;
+
+/// Whether (almost) every pixel is black, as screencap returns for
+/// `FLAG_SECURE` windows.
+pub fn is_near_black(img: &DynamicImage) -> bool {
+    sampled_share(img, Some(Rgba([0, 0, 0, 255]))) >= BLANK_FRACTION
+}
+
+/// Whether (almost) every pixel has the same colour, e.g. a SurfaceView
+/// that screencap did not see.
+pub fn is_uniform(img: &DynamicImage) -> bool {
+    sampled_share(img, None) >= BLANK_FRACTION
+}
+
+/// Share of pixels close to `reference` (the top-left pixel when `None`).
+/// Samples a sparse grid to stay cheap on large frames.
+fn sampled_share(img: &DynamicImage, reference: Option<Rgba<u8>>) -> f64 {
+    let rgba = img.to_rgba8();
+    let (w, h) = rgba.dimensions();
+    if w == 0 || h == 0 {
+        return 1.0;
+    }
+    let reference = reference.unwrap_or(*rgba.get_pixel(0, 0));
+    let (step_x, step_y) = ((w / 64).max(1), (h / 64).max(1));
+    let (mut total, mut close) = (0u64, 0u64);
+    for y in (0..h).step_by(step_y as usize) {
+        for x in (0..w).step_by(step_x as usize) {
+            total += 1;
+            if near(*rgba.get_pixel(x, y), reference) {
+                close += 1;
+            }
+        }
+    }
+    close as f64 / total as f64
+}
+
+/// Android capture that refuses to hand back a misleading blank frame.
+///
+/// A black frame from a secure window errors with [`android::SecureWindow`],
+/// or with `allow_secure_bypass` becomes a wireframe of the UI hierarchy. Any
+/// other single-colour frame is retried with screenrecord unless a strategy
+/// was fixed, since screencap misses some SurfaceView/GPU content.
+fn android_screenshot_checked(device: Option<&str>, opts: AndroidCaptureOptions) -> Result<Vec<u8>> {
+    let strategy = opts.strategy.unwrap_or(android::CaptureStrategy::Screencap);
+    let png = android::screenshot_with(strategy, device)?;
+    let img = decode_png(&png)?;
+    if !is_uniform(&img) {
+        return Ok(png);
+    }
+    let black = is_near_black(&img);
+    let secure = if black { android::secure_focused_window(device)? } else { None };
+    let Some(window) = secure else {
+        if opts.strategy.is_none() {
+            eprintln!("Screencap looks empty; retrying with screenrecord");
+            // The screen may really be one colour (a splash screen), so a
+            // failed retry falls back to the screencap.
+            let retry = android::screenshot_with(android::CaptureStrategy::ScreenRecord, device)
+                .and_then(|png| Ok((!is_uniform(&decode_png(&png)?)).then_some(png)));
+            match retry {
+                Ok(Some(retry)) => return Ok(retry),
+                Ok(None) => {}
+                Err(e) => eprintln!("Warning: screenrecord retry failed, keeping the screencap: {:#}", e),
+            }
+        }
+        if black {
+            eprintln!("Warning: screenshot is entirely black (screen off or a black screen?)");
+        }
+        return Ok(png);
+    };
+    if !opts.allow_secure_bypass {
+        return Err(android::SecureWindow { window }.into());
+    }
+    eprintln!("Window '{}' is FLAG_SECURE; rendering from the UI hierarchy", window);
+    let elements = android::get_ui_elements(device)?;
+    let wireframe = render_wireframe(img.width(), img.height(), &elements)?;
+    let mut out = Vec::new();
+    wireframe.write_to(&mut Cursor::new(&mut out), image::ImageFormat::Png)?;
+    Ok(out)
+}
+
+/// Draw element bounds and labels on a blank canvas, a stand-in for screens
+/// that cannot be captured. Coordinates match the device, so taps still line up.
+pub fn render_wireframe(width: u32, height: u32, elements: &[android::UiElement]) -> Result<RgbaImage> {
+    let mut img = RgbaImage::from_pixel(width, height, Rgba([255, 255, 255, 255]));
+    let font = FontArc::try_from_slice(include_bytes!("../assets/DejaVuSans.ttf"))
+        .context("Failed to load font")?;
+    let text_color = Rgba([40u8, 40, 40, 255]);
+    for elem in elements {
+        let (x1, y1, x2, y2) = elem.bounds;
+        if x2 <= x1 || y2 <= y1 || x1 < 0 || y1 < 0 {
+            continue;
+        }
+        let color = if elem.clickable { Rgba([0u8, 150, 0, 255]) } else { Rgba([170u8, 170, 170, 255]) };
+        draw_hollow_rect_mut(&mut img, Rect::at(x1, y1).of_size((x2 - x1) as u32, (y2 - y1) as u32), color);
+        if !elem.text.is_empty() || !elem.content_desc.is_empty() {
+            draw_text_mut(&mut img, text_color, x1 + 4, y1 + 4, PxScale::from(28.0), &font, &elem.label());
+        }
+    }
+    draw_text_mut(
+        &mut img,
+        Rgba([200u8, 0, 0, 255]),
+        8,
+        8,
+        PxScale::from(24.0),
+        &font,
+        "FLAG_SECURE: reconstructed from UI hierarchy",
+    );
+    Ok(img)
+}
+
+/// Scale `img` down to fit within `max_width` x `max_height`, keeping the
+/// aspect ratio. Never upscales; an image that already fits is returned as is.
+///
+/// Lanczos3 keeps small UI text legible better than the cheaper filters.
+pub fn resize_to_fit(img: DynamicImage, max_width: u32, max_height: u32) -> DynamicImage {
+    let (width, height) = img.dimensions();
+    if width <= max_width && height <= max_height {
+        return img;
+    }
+    let scale = (max_width as f64 / width as f64).min(max_height as f64 / height as f64);
+    let new_width = ((width as f64 * scale).round() as u32).max(1);
+    let new_height = ((height as f64 * scale).round() as u32).max(1);
+    img.resize_exact(new_width, new_height, image::imageops::FilterType::Lanczos3)
+}
+
 /// Compress image for LLM processing
-fn compress_image(png_data: &[u8], max_width: u32, quality: u8) -> Result<Vec<u8>> {
+fn compress_image(png_data: &[u8], opts: CompressOptions) -> Result<Vec<u8>> {
     // Load image
     let img = image::load_from_memory(png_data)?;
     let (width, height) = img.dimensions();
@@ -57,27 +634,798 @@ fn compress_image(png_data: &[u8], max_width: u32, quality: u8) -> Result<Vec<u8
     eprintln!("Original: {}x{} ({} bytes)", width, height, png_data.len());
 
     // Resize if needed
-    let img = if width > max_width {
-        let new_height = (height as f32 * max_width as f32 / width as f32) as u32;
-        eprintln!("Resizing to: {}x{}", max_width, new_height);
-        img.resize(max_width, new_height, image::imageops::FilterType::Lanczos3)
-    } else {
-        img
-    };
+    let img = resize_to_fit(img, opts.max_width, opts.max_height.unwrap_or(u32::MAX));
+    if img.dimensions() != (width, height) {
+        eprintln!("Resizing to: {}x{}", img.width(), img.height());
+    }
 
     // Convert to JPEG for smaller size
-    let mut jpeg_data = Vec::new();
-    let mut cursor = Cursor::new(&mut jpeg_data);
-
-    // Use JPEG encoder with quality setting
-    let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut cursor, quality);
-    img.write_with_encoder(encoder)?;
+    let jpeg_data = encode(&img, EncodeFormat::Jpeg { quality: opts.quality })?;
 
     eprintln!("Compressed: {} bytes ({}% of original)", jpeg_data.len(), jpeg_data.len() * 100 / png_data.len());
 
     Ok(jpeg_data)
 }
 
+/// Draw a light coordinate grid over `img` to help the model pick tap targets.
+///
+/// Lines are blended at low opacity every `spacing_px` pixels so UI text stays
+/// readable; every `label_every`-th line gets its pixel coordinate printed along
+/// the top (x) and left (y) edges on a small translucent backing.
+pub fn overlay_grid(img: &DynamicImage, spacing_px: u32, label_every: u32) -> Result<RgbaImage> {
+    if spacing_px < 10 {
+        anyhow::bail!("Grid spacing must be at least 10px (got {})", spacing_px);
+    }
+    let mut out = img.to_rgba8();
+    let (width, height) = out.dimensions();
+
+    let color = Rgba([255u8, 0u8, 255u8, 255u8]);
+    let is_major = |n: u32| label_every > 0 && n.is_multiple_of(label_every);
+
+    let mut n = 1;
+    while n * spacing_px < width {
+        let x = n * spacing_px;
+        let alpha = if is_major(n) { 0.45 } else { 0.2 };
+        for y in 0..height {
+            blend_pixel(&mut out, x, y, color, alpha);
+        }
+        n += 1;
+    }
+    let mut n = 1;
+    while n * spacing_px < height {
+        let y = n * spacing_px;
+        let alpha = if is_major(n) { 0.45 } else { 0.2 };
+        for x in 0..width {
+            blend_pixel(&mut out, x, y, color, alpha);
+        }
+        n += 1;
+    }
+
+    if label_every == 0 {
+        return Ok(out);
+    }
+
+    let font_data = include_bytes!("../assets/DejaVuSans.ttf");
+    let font = FontArc::try_from_slice(font_data).context("Failed to load font")?;
+    let font_px = (width as f32 / 60.0).clamp(12.0, 28.0);
+    let scale = PxScale::from(font_px);
+    let text_color = Rgba([255u8, 255u8, 255u8, 255u8]);
+    let label_h = font_px as u32 + 4;
+
+    let step = spacing_px * label_every;
+    let mut x = step;
+    while x < width {
+        let label = x.to_string();
+        let label_w = label.len() as u32 * (font_px as u32 * 6 / 10) + 4;
+        shade_rect(&mut out, x.saturating_sub(label_w / 2), 0, label_w, label_h);
+        draw_text_mut(&mut out, text_color, x.saturating_sub(label_w / 2) as i32 + 2, 2, scale, &font, &label);
+        x += step;
+    }
+    let mut y = step;
+    while y < height {
+        let label = y.to_string();
+        let label_w = label.len() as u32 * (font_px as u32 * 6 / 10) + 4;
+        shade_rect(&mut out, 0, y.saturating_sub(label_h / 2), label_w, label_h);
+        draw_text_mut(&mut out, text_color, 2, y.saturating_sub(label_h / 2) as i32 + 2, scale, &font, &label);
+        y += step;
+    }
+
+    Ok(out)
+}
+
+/// Add a dark strip with `footer`'s lines above or below `img`.
+///
+/// The canvas grows by the strip's height, so no part of the screen is
+/// covered. Text that would not fit the width is drawn smaller.
+pub fn add_evidence_footer(img: &DynamicImage, footer: &EvidenceFooter) -> Result<RgbaImage> {
+    let font = FontArc::try_from_slice(include_bytes!("../assets/DejaVuSans.ttf")).context("Failed to load font")?;
+    let (width, height) = img.dimensions();
+    let mut font_px = footer.font_px.max(6.0);
+    let pad = (font_px / 2.0).ceil() as u32;
+    let widest = footer.lines.iter().map(|l| imageproc::drawing::text_size(PxScale::from(font_px), &font, l).0).max().unwrap_or(0);
+    let room = width.saturating_sub(2 * pad).max(1);
+    if widest > room {
+        font_px = (font_px * room as f32 / widest as f32).max(6.0);
+    }
+    let line_h = (font_px * 1.25).ceil() as u32;
+    let strip_h = line_h * footer.lines.len() as u32 + 2 * pad;
+
+    let mut out = RgbaImage::from_pixel(width, height + strip_h, Rgba([24, 24, 24, 255]));
+    let (img_y, strip_y) = match footer.position {
+        FooterPosition::Bottom => (0, height),
+        FooterPosition::Top => (strip_h, 0),
+    };
+    image::imageops::overlay(&mut out, &img.to_rgba8(), 0, img_y as i64);
+    let text_color = Rgba([255u8, 255, 255, 255]);
+    for (i, line) in footer.lines.iter().enumerate() {
+        let y = strip_y + pad + i as u32 * line_h;
+        draw_text_mut(&mut out, text_color, pad as i32, y as i32, PxScale::from(font_px), &font, line);
+    }
+    Ok(out)
+}
+
+/// Decode PNG bytes, apply [`overlay_grid`] and re-encode as PNG.
+pub fn apply_grid_png(png_data: &[u8], grid: GridOverlay) -> Result<Vec<u8>> {
+    let img = image::load_from_memory(png_data)?;
+    let gridded = overlay_grid(&img, grid.spacing_px, grid.label_every)?;
+    let mut out = Vec::new();
+    gridded.write_to(&mut Cursor::new(&mut out), image::ImageFormat::Png)?;
+    Ok(out)
+}
+
+/// Drop the top `height_px` rows (status bar). Clamped to leave one row.
+pub fn crop_status_bar(img: &DynamicImage, height_px: u32) -> DynamicImage {
+    let (w, h) = img.dimensions();
+    let cut = height_px.min(h.saturating_sub(1));
+    img.crop_imm(0, cut, w, h - cut)
+}
+
+/// Crop uniform-colour borders around the content.
+///
+/// `bg` is the border colour; when `None` it is taken from the corners, and
+/// only if all four agree. Returns the image unchanged when there is nothing
+/// to trim (no agreed background, or the whole image is background).
+pub fn trim_borders(img: &DynamicImage, bg: Option<Rgba<u8>>) -> DynamicImage {
+    let rgba = img.to_rgba8();
+    let (w, h) = rgba.dimensions();
+    if w == 0 || h == 0 {
+        return img.clone();
+    }
+    let bg = match bg.or_else(|| corner_background(&rgba)) {
+        Some(c) => c,
+        None => return img.clone(),
+    };
+
+    let is_bg = |x: u32, y: u32| near(*rgba.get_pixel(x, y), bg);
+    let row_is_bg = |y: u32| (0..w).all(|x| is_bg(x, y));
+    let col_is_bg = |x: u32, top: u32, bottom: u32| (top..bottom).all(|y| is_bg(x, y));
+
+    let Some(top) = (0..h).find(|&y| !row_is_bg(y)) else {
+        return img.clone();
+    };
+    let bottom = (0..h).rev().find(|&y| !row_is_bg(y)).unwrap_or(top) + 1;
+    let left = (0..w).find(|&x| !col_is_bg(x, top, bottom)).unwrap_or(0);
+    let right = (0..w).rev().find(|&x| !col_is_bg(x, top, bottom)).unwrap_or(w - 1) + 1;
+
+    if (left, top, right, bottom) == (0, 0, w, h) {
+        return img.clone();
+    }
+    img.crop_imm(left, top, right - left, bottom - top)
+}
+
+/// The shared corner colour, if all four corners agree.
+fn corner_background(img: &RgbaImage) -> Option<Rgba<u8>> {
+    let (w, h) = img.dimensions();
+    let c = *img.get_pixel(0, 0);
+    [(w - 1, 0), (0, h - 1), (w - 1, h - 1)]
+        .iter()
+        .all(|&(x, y)| near(*img.get_pixel(x, y), c))
+        .then_some(c)
+}
+
+fn near(a: Rgba<u8>, b: Rgba<u8>) -> bool {
+    (0..3).all(|i| a[i].abs_diff(b[i]) <= TRIM_TOLERANCE)
+}
+
+/// Decode PNG bytes, apply status-bar crop and border trim, re-encode as PNG.
+pub fn apply_trim_png(png_data: &[u8], trim: TrimOptions) -> Result<Vec<u8>> {
+    let mut img = image::load_from_memory(png_data)?;
+    if trim.status_bar_px > 0 {
+        img = crop_status_bar(&img, trim.status_bar_px);
+    }
+    if trim.borders {
+        img = trim_borders(&img, None);
+    }
+    encode(&img, EncodeFormat::Png)
+}
+
+/// Alpha-blend `color` into the pixel at `(x, y)`.
+fn blend_pixel(img: &mut RgbaImage, x: u32, y: u32, color: Rgba<u8>, alpha: f32) {
+    let p = img.get_pixel_mut(x, y);
+    for c in 0..3 {
+        p[c] = (p[c] as f32 * (1.0 - alpha) + color[c] as f32 * alpha).round() as u8;
+    }
+}
+
+/// Darken a rectangle so label text stays legible on any background.
+fn shade_rect(img: &mut RgbaImage, x: u32, y: u32, w: u32, h: u32) {
+    let (width, height) = img.dimensions();
+    for py in y..(y + h).min(height) {
+        for px in x..(x + w).min(width) {
+            blend_pixel(img, px, py, Rgba([0, 0, 0, 255]), 0.55);
+        }
+    }
+}
+
+/// UI theme for [`capture_theme_pair`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+/// The same screen captured once per theme.
+#[derive(Debug)]
+pub struct ThemePair {
+    pub light: DynamicImage,
+    pub dark: DynamicImage,
+}
+
+/// Fraction of differing pixels below which two captures count as identical.
+///
+/// Allows for a status-bar clock tick between captures.
+const THEME_MIN_CHANGED_FRACTION: f64 = 0.005;
+
+/// Capture the current screen in light and dark mode.
+///
+/// `set_theme` switches the device theme (e.g. `cmd uimode night yes|no` on
+/// Android) and `capture` returns PNG bytes. Waits `settle_ms` after each
+/// switch for the UI to redraw. Fails when both captures are effectively
+/// identical, which means the theme change did not take effect.
+pub fn capture_theme_pair(
+    mut capture: impl FnMut() -> Result<Vec<u8>>,
+    set_theme: impl Fn(Theme) -> Result<()>,
+    settle_ms: u64,
+) -> Result<ThemePair> {
+    let mut grab = |theme: Theme| -> Result<DynamicImage> {
+        set_theme(theme)?;
+        std::thread::sleep(std::time::Duration::from_millis(settle_ms));
+        let png = capture()?;
+        image::load_from_memory(&png).context("Failed to decode theme capture")
+    };
+    let light = grab(Theme::Light)?;
+    let dark = grab(Theme::Dark)?;
+
+    if changed_fraction(&light, &dark) < THEME_MIN_CHANGED_FRACTION {
+        anyhow::bail!("Light and dark captures are identical — the theme change did not take effect (does the app support dark mode?)");
+    }
+    Ok(ThemePair { light, dark })
+}
+
+/// Fraction of pixels that differ between two images (1.0 if sizes differ).
+fn changed_fraction(a: &DynamicImage, b: &DynamicImage) -> f64 {
+    diff_fraction(a, b, 0)
+}
+
+/// Fraction of pixels where some channel differs by more than
+/// `channel_tolerance` (1.0 if sizes differ). A small tolerance absorbs
+/// compression and anti-aliasing noise.
+pub fn diff_fraction(a: &DynamicImage, b: &DynamicImage, channel_tolerance: u8) -> f64 {
+    if a.dimensions() != b.dimensions() {
+        return 1.0;
+    }
+    let (a, b) = (a.to_rgba8(), b.to_rgba8());
+    let total = a.pixels().len().max(1);
+    let changed = a
+        .pixels()
+        .zip(b.pixels())
+        .filter(|(p, q)| p.0.iter().zip(q.0.iter()).any(|(x, y)| x.abs_diff(*y) > channel_tolerance))
+        .count();
+    changed as f64 / total as f64
+}
+
+/// Cross-fade from `baseline` to `actual` in `frames` steps (at least 2),
+/// so a reviewer can see what moved rather than just which pixels differ.
+pub fn onion_skin(baseline: &DynamicImage, actual: &DynamicImage, frames: usize) -> Result<Vec<RgbaImage>> {
+    if baseline.dimensions() != actual.dimensions() {
+        let (bw, bh) = baseline.dimensions();
+        let (aw, ah) = actual.dimensions();
+        anyhow::bail!("Cannot cross-fade a {}x{} baseline with a {}x{} capture", bw, bh, aw, ah);
+    }
+    let frames = frames.max(2);
+    let (from, to) = (baseline.to_rgba8(), actual.to_rgba8());
+    Ok((0..frames)
+        .map(|i| {
+            let t = i as f32 / (frames - 1) as f32;
+            let mut frame = from.clone();
+            for (p, q) in frame.pixels_mut().zip(to.pixels()) {
+                for (x, y) in p.0.iter_mut().zip(q.0.iter()) {
+                    *x = (*x as f32 * (1.0 - t) + *y as f32 * t).round() as u8;
+                }
+            }
+            frame
+        })
+        .collect())
+}
+
+/// Encode `frames` as a looping GIF, showing each for `delay_ms`.
+pub fn encode_gif(frames: &[RgbaImage], delay_ms: u32) -> Result<Vec<u8>> {
+    use image::codecs::gif::{GifEncoder, Repeat};
+
+    let mut out = Vec::new();
+    {
+        // Speed 10 of 30: full-size screens otherwise take seconds to quantize.
+        let mut encoder = GifEncoder::new_with_speed(&mut out, 10);
+        encoder.set_repeat(Repeat::Infinite)?;
+        let delay = image::Delay::from_numer_denom_ms(delay_ms, 1);
+        for frame in frames {
+            encoder.encode_frame(image::Frame::from_parts(frame.clone(), 0, 0, delay))?;
+        }
+    }
+    Ok(out)
+}
+
+/// Colour for heat `t` in 0..=1: blue through yellow to red.
+fn heat_color(t: f32) -> Rgba<u8> {
+    let (r, g, b) = if t < 0.5 {
+        let u = t * 2.0;
+        (u, u, 1.0 - u)
+    } else {
+        (1.0, 2.0 - t * 2.0, 0.0)
+    };
+    Rgba([(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8, 255])
+}
+
+/// Where a capture sequence changes: each pixel's largest channel
+/// difference is summed over consecutive frames, scaled to the busiest
+/// pixel and drawn as a heatmap over the first frame. Pixels that never
+/// change are left as they are. Regions that light up without the flow
+/// touching them (clocks, carousels, video) are candidates for ignore-masks.
+pub fn change_heatmap(frames: &[DynamicImage]) -> Result<RgbaImage> {
+    let (frames, heat) = accumulate_changes(frames)?;
+    let w = frames[0].width();
+    let mut out = frames[0].clone();
+    let peak = heat.iter().copied().max().unwrap_or(0);
+    if peak == 0 {
+        return Ok(out);
+    }
+    for (i, &v) in heat.iter().enumerate() {
+        if v > 0 {
+            let t = v as f32 / peak as f32;
+            let (x, y) = ((i % w as usize) as u32, (i / w as usize) as u32);
+            blend_pixel(&mut out, x, y, heat_color(t), 0.35 + 0.5 * t);
+        }
+    }
+    Ok(out)
+}
+
+/// The frames as RGBA and, per pixel, the largest channel difference summed
+/// over consecutive frames.
+fn accumulate_changes(frames: &[DynamicImage]) -> Result<(Vec<RgbaImage>, Vec<u32>)> {
+    let Some(first) = frames.first() else {
+        anyhow::bail!("Heatmap needs at least two frames, got none");
+    };
+    if frames.len() < 2 {
+        anyhow::bail!("Heatmap needs at least two frames, got 1");
+    }
+    let (w, h) = first.dimensions();
+    if let Some((i, f)) = frames.iter().enumerate().find(|(_, f)| f.dimensions() != (w, h)) {
+        let (fw, fh) = f.dimensions();
+        anyhow::bail!("Frame {} is {}x{}, but frame 1 is {}x{}", i + 1, fw, fh, w, h);
+    }
+
+    let mut heat = vec![0u32; (w as usize) * (h as usize)];
+    let frames: Vec<RgbaImage> = frames.iter().map(|f| f.to_rgba8()).collect();
+    for pair in frames.windows(2) {
+        for ((p, q), acc) in pair[0].pixels().zip(pair[1].pixels()).zip(heat.iter_mut()) {
+            *acc += p.0.iter().zip(q.0.iter()).map(|(x, y)| x.abs_diff(*y)).max().unwrap_or(0) as u32;
+        }
+    }
+    Ok((frames, heat))
+}
+
+/// A region left out of baseline comparisons, in screen pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MaskRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl MaskRect {
+    /// `X,Y,WIDTH,HEIGHT`.
+    pub fn parse(s: &str) -> Result<Self> {
+        let n: Vec<u32> = s
+            .split(',')
+            .map(|v| v.trim().parse())
+            .collect::<std::result::Result<_, _>>()
+            .with_context(|| format!("Invalid mask rect '{}': expected X,Y,WIDTH,HEIGHT", s))?;
+        match n[..] {
+            [x, y, width, height] if width > 0 && height > 0 => Ok(Self { x, y, width, height }),
+            _ => anyhow::bail!("Invalid mask rect '{}': expected X,Y,WIDTH,HEIGHT with a non-zero size", s),
+        }
+    }
+
+    fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && y >= self.y && x - self.x < self.width && y - self.y < self.height
+    }
+}
+
+/// Sidecar files beside a baseline (`<platform>.png`): `<platform>.mask.json`
+/// holds a JSON array of rects, `<platform>.mask.png` a picture the size of
+/// the baseline where light pixels are ignored. Either or both may exist.
+fn mask_paths(baseline: &std::path::Path) -> (std::path::PathBuf, std::path::PathBuf) {
+    (baseline.with_extension("mask.json"), baseline.with_extension("mask.png"))
+}
+
+/// Regions of a baseline that comparisons skip: clocks, ads, animations.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreMask {
+    pub rects: Vec<MaskRect>,
+    image: Option<image::GrayImage>,
+}
+
+impl IgnoreMask {
+    /// The sidecar mask of `baseline`, or `None` if it has neither file.
+    pub fn load(baseline: &std::path::Path) -> Result<Option<Self>> {
+        let (json, png) = mask_paths(baseline);
+        let rects = match std::fs::read_to_string(&json) {
+            Ok(text) => serde_json::from_str(&text).with_context(|| format!("Invalid mask {}", json.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).with_context(|| format!("Cannot read {}", json.display())),
+        };
+        let image = match png.exists() {
+            true => Some(image::open(&png).with_context(|| format!("Cannot read mask {}", png.display()))?.to_luma8()),
+            false => None,
+        };
+        if rects.is_empty() && image.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Self { rects, image }))
+    }
+
+    fn covers(&self, x: u32, y: u32) -> bool {
+        self.rects.iter().any(|r| r.contains(x, y))
+            || self.image.as_ref().is_some_and(|m| x < m.width() && y < m.height() && m.get_pixel(x, y).0[0] >= 128)
+    }
+}
+
+/// Add `add` to `baseline`'s rect mask (or start over with `clear`) and save it.
+pub fn edit_mask(baseline: &std::path::Path, add: &[MaskRect], clear: bool) -> Result<IgnoreMask> {
+    let mut mask = match clear {
+        true => IgnoreMask::default(),
+        false => IgnoreMask::load(baseline)?.unwrap_or_default(),
+    };
+    for rect in add {
+        if !mask.rects.contains(rect) {
+            mask.rects.push(*rect);
+        }
+    }
+    save_mask(baseline, &mask)?;
+    Ok(mask)
+}
+
+/// Write `mask`'s rects to `baseline`'s `.mask.json`, or remove the file when
+/// there are none. A `.mask.png` is left alone.
+pub fn save_mask(baseline: &std::path::Path, mask: &IgnoreMask) -> Result<std::path::PathBuf> {
+    let (json, _) = mask_paths(baseline);
+    if mask.rects.is_empty() {
+        return match std::fs::remove_file(&json) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Cannot remove {}", json.display()))
+            }
+            _ => Ok(json),
+        };
+    }
+    std::fs::write(&json, serde_json::to_string_pretty(&mask.rects)? + "\n")
+        .with_context(|| format!("Cannot write {}", json.display()))?;
+    Ok(json)
+}
+
+/// Result of [`compare_to_baseline`].
+pub struct BaselineComparison {
+    pub baseline: DynamicImage,
+    /// Fraction of the unmasked pixels that differ (1.0 if sizes differ).
+    pub diff_fraction: f64,
+    pub masked_pixels: u64,
+}
+
+/// Compare `actual` with the baseline image at `path`, skipping the
+/// pixels its sidecar mask covers. Differences are counted as in
+/// [`diff_fraction`].
+pub fn compare_to_baseline(path: &std::path::Path, actual: &DynamicImage, channel_tolerance: u8) -> Result<BaselineComparison> {
+    let baseline = image::open(path).with_context(|| format!("Cannot read baseline {}", path.display()))?;
+    let mask = IgnoreMask::load(path)?;
+    let Some(mask) = mask.filter(|_| baseline.dimensions() == actual.dimensions()) else {
+        let diff_fraction = diff_fraction(&baseline, actual, channel_tolerance);
+        return Ok(BaselineComparison { baseline, diff_fraction, masked_pixels: 0 });
+    };
+    let (a, b) = (baseline.to_rgba8(), actual.to_rgba8());
+    let (mut masked, mut changed) = (0u64, 0u64);
+    for ((x, y, p), q) in a.enumerate_pixels().zip(b.pixels()) {
+        if mask.covers(x, y) {
+            masked += 1;
+        } else if p.0.iter().zip(q.0.iter()).any(|(x, y)| x.abs_diff(*y) > channel_tolerance) {
+            changed += 1;
+        }
+    }
+    let compared = (a.width() as u64 * a.height() as u64 - masked).max(1);
+    Ok(BaselineComparison { baseline, diff_fraction: changed as f64 / compared as f64, masked_pixels: masked })
+}
+
+/// Grid cell size used when turning a heatmap into mask rects.
+const MASK_CELL_PX: u32 = 16;
+
+/// Mask rects covering what changes across `frames` (captures of the same
+/// screen from several runs): cells of [`MASK_CELL_PX`] where some pixel's
+/// summed change exceeds `channel_tolerance`, grouped into the bounding
+/// boxes of connected cells.
+pub fn suggest_mask(frames: &[DynamicImage], channel_tolerance: u8) -> Result<Vec<MaskRect>> {
+    let (frames, heat) = accumulate_changes(frames)?;
+    let (w, h) = frames[0].dimensions();
+    let (cols, rows) = (w.div_ceil(MASK_CELL_PX) as usize, h.div_ceil(MASK_CELL_PX) as usize);
+    let mut hot = vec![false; cols * rows];
+    for (i, _) in heat.iter().enumerate().filter(|(_, v)| **v > channel_tolerance as u32) {
+        let (x, y) = (i % w as usize, i / w as usize);
+        hot[(y / MASK_CELL_PX as usize) * cols + x / MASK_CELL_PX as usize] = true;
+    }
+
+    let mut rects = Vec::new();
+    let mut seen = vec![false; hot.len()];
+    for start in 0..hot.len() {
+        if !hot[start] || seen[start] {
+            continue;
+        }
+        let (mut min_c, mut min_r, mut max_c, mut max_r) = (cols, rows, 0, 0);
+        let mut stack = vec![start];
+        seen[start] = true;
+        while let Some(cell) = stack.pop() {
+            let (c, r) = (cell % cols, cell / cols);
+            (min_c, min_r, max_c, max_r) = (min_c.min(c), min_r.min(r), max_c.max(c), max_r.max(r));
+            let neighbours = [
+                (c > 0).then(|| cell - 1),
+                (c + 1 < cols).then(|| cell + 1),
+                (r > 0).then(|| cell - cols),
+                (r + 1 < rows).then(|| cell + cols),
+            ];
+            for n in neighbours.into_iter().flatten() {
+                if hot[n] && !seen[n] {
+                    seen[n] = true;
+                    stack.push(n);
+                }
+            }
+        }
+        let (x, y) = (min_c as u32 * MASK_CELL_PX, min_r as u32 * MASK_CELL_PX);
+        let right = ((max_c as u32 + 1) * MASK_CELL_PX).min(w);
+        let bottom = ((max_r as u32 + 1) * MASK_CELL_PX).min(h);
+        rects.push(MaskRect { x, y, width: right - x, height: bottom - y });
+    }
+    Ok(rects)
+}
+
+/// Where [`find_template`] found a template, in screen pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemplateMatch {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Zero-mean normalized cross-correlation, -1.0 to 1.0.
+    pub score: f32,
+}
+
+impl TemplateMatch {
+    pub fn center(&self) -> (i32, i32) {
+        ((self.x + self.width / 2) as i32, (self.y + self.height / 2) as i32)
+    }
+}
+
+/// Summed-area table of a grayscale image and of its squares, for O(1)
+/// window statistics.
+struct Integral {
+    width: usize,
+    sum: Vec<f64>,
+    sq: Vec<f64>,
+}
+
+impl Integral {
+    fn new(img: &image::GrayImage) -> Self {
+        let (w, h) = (img.width() as usize, img.height() as usize);
+        let mut sum = vec![0.0; (w + 1) * (h + 1)];
+        let mut sq = vec![0.0; (w + 1) * (h + 1)];
+        for y in 0..h {
+            let (mut row, mut row_sq) = (0.0, 0.0);
+            for x in 0..w {
+                let v = img.get_pixel(x as u32, y as u32).0[0] as f64;
+                row += v;
+                row_sq += v * v;
+                let i = (y + 1) * (w + 1) + x + 1;
+                sum[i] = sum[i - (w + 1)] + row;
+                sq[i] = sq[i - (w + 1)] + row_sq;
+            }
+        }
+        Integral { width: w + 1, sum, sq }
+    }
+
+    /// (sum, sum of squares) over the `w`x`h` window at (x, y).
+    fn window(&self, x: usize, y: usize, w: usize, h: usize) -> (f64, f64) {
+        let at = |t: &[f64], x: usize, y: usize| t[y * self.width + x];
+        let area = |t: &[f64]| at(t, x + w, y + h) - at(t, x, y + h) - at(t, x + w, y) + at(t, x, y);
+        (area(&self.sum), area(&self.sq))
+    }
+}
+
+/// Zero-mean NCC of `tpl` against `img` at each `(x, y)` in `positions`;
+/// returns the best. Zero-mean scoring ignores uniform brightness shifts
+/// and keeps flat screen areas from matching everything.
+fn best_zncc(
+    img: &image::GrayImage,
+    tpl: &image::GrayImage,
+    positions: impl Iterator<Item = (u32, u32)>,
+) -> Option<(u32, u32, f32)> {
+    let (tw, th) = (tpl.width() as usize, tpl.height() as usize);
+    let n = (tw * th) as f64;
+    let mean = tpl.pixels().map(|p| p.0[0] as f64).sum::<f64>() / n;
+    let centered: Vec<f64> = tpl.pixels().map(|p| p.0[0] as f64 - mean).collect();
+    let tpl_norm = centered.iter().map(|v| v * v).sum::<f64>().sqrt();
+    if tpl_norm < 1e-6 {
+        return None;
+    }
+    let integral = Integral::new(img);
+    let raw = img.as_raw();
+    let stride = img.width() as usize;
+    let mut best: Option<(u32, u32, f32)> = None;
+    for (x, y) in positions {
+        let (sum, sq) = integral.window(x as usize, y as usize, tw, th);
+        let var = sq - sum * sum / n;
+        if var < 1e-6 {
+            continue;
+        }
+        let mut cross = 0.0;
+        for ty in 0..th {
+            let row = &raw[(y as usize + ty) * stride + x as usize..][..tw];
+            let tpl_row = &centered[ty * tw..][..tw];
+            cross += row.iter().zip(tpl_row).map(|(p, t)| *p as f64 * t).sum::<f64>();
+        }
+        let score = (cross / (var.sqrt() * tpl_norm)) as f32;
+        if best.is_none_or(|(_, _, b)| score > b) {
+            best = Some((x, y, score));
+        }
+    }
+    best
+}
+
+/// Best match of `template` in `screen`, or `None` when the template is
+/// larger than the screen or has no contrast to match on.
+///
+/// Matching runs on grayscale, first on downscaled copies to find the
+/// neighbourhood, then at full resolution around it, so a full-HD screen
+/// takes well under a second.
+pub fn find_template(screen: &DynamicImage, template: &DynamicImage) -> Option<TemplateMatch> {
+    let (sw, sh) = screen.dimensions();
+    let (tw, th) = template.dimensions();
+    if tw == 0 || th == 0 || tw > sw || th > sh {
+        return None;
+    }
+    let (screen, template) = (screen.to_luma8(), template.to_luma8());
+
+    // Keep at least ~12px of template detail at the coarse level.
+    let factor = (tw.min(th) / 12).clamp(1, 8);
+    let (cx, cy) = if factor == 1 {
+        (0, 0)
+    } else {
+        let small = |img: &image::GrayImage, w: u32, h: u32| {
+            image::imageops::resize(img, (w / factor).max(1), (h / factor).max(1), image::imageops::FilterType::Triangle)
+        };
+        let (ss, st) = (small(&screen, sw, sh), small(&template, tw, th));
+        let (w, h, stw, sth) = (ss.width(), ss.height(), st.width(), st.height());
+        let coarse = (0..=h - sth).flat_map(|y| (0..=w - stw).map(move |x| (x, y)));
+        let (x, y, _) = best_zncc(&ss, &st, coarse)?;
+        (x * factor, y * factor)
+    };
+    let (x, y, score) = if factor == 1 {
+        best_zncc(&screen, &template, (0..=sh - th).flat_map(|y| (0..=sw - tw).map(move |x| (x, y))))?
+    } else {
+        let r = factor * 2;
+        let xs = cx.saturating_sub(r)..=(cx + r).min(sw - tw);
+        let ys = cy.saturating_sub(r)..=(cy + r).min(sh - th);
+        best_zncc(&screen, &template, ys.flat_map(|y| xs.clone().map(move |x| (x, y))))?
+    };
+    Some(TemplateMatch { x, y, width: tw, height: th, score })
+}
+
+/// Place two images side by side (left, right) with a neutral gap between.
+pub fn compose_side_by_side(left: &DynamicImage, right: &DynamicImage, gap: u32) -> RgbaImage {
+    let (lw, lh) = left.dimensions();
+    let (rw, rh) = right.dimensions();
+    let mut out = RgbaImage::from_pixel(lw + gap + rw, lh.max(rh), Rgba([128, 128, 128, 255]));
+    image::imageops::overlay(&mut out, &left.to_rgba8(), 0, 0);
+    image::imageops::overlay(&mut out, &right.to_rgba8(), (lw + gap) as i64, 0);
+    out
+}
+
+/// Colour-vision deficiency simulated by [`simulate_cvd`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CvdType {
+    /// No functioning L (red) cones.
+    Protanopia,
+    /// No functioning M (green) cones.
+    Deuteranopia,
+    /// No functioning S (blue) cones.
+    Tritanopia,
+}
+
+impl CvdType {
+    pub const ALL: [CvdType; 3] = [CvdType::Protanopia, CvdType::Deuteranopia, CvdType::Tritanopia];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CvdType::Protanopia => "protanopia",
+            CvdType::Deuteranopia => "deuteranopia",
+            CvdType::Tritanopia => "tritanopia",
+        }
+    }
+
+    /// LMS-space projection onto the plane the remaining two cone types can
+    /// still tell apart (Viénot, Brettel & Mollon 1999).
+    fn lms_projection(&self) -> [[f32; 3]; 3] {
+        match self {
+            CvdType::Protanopia => [[0.0, 2.02344, -2.52581], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            CvdType::Deuteranopia => [[1.0, 0.0, 0.0], [0.494207, 0.0, 1.24827], [0.0, 0.0, 1.0]],
+            CvdType::Tritanopia => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [-0.395913, 0.801109, 0.0]],
+        }
+    }
+}
+
+impl std::str::FromStr for CvdType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        CvdType::ALL
+            .into_iter()
+            .find(|k| k.as_str() == s.to_lowercase())
+            .with_context(|| format!("Unknown colour-vision deficiency '{}'. Use protanopia, deuteranopia or tritanopia", s))
+    }
+}
+
+/// Linear RGB to LMS cone response.
+const RGB_TO_LMS: [[f32; 3]; 3] = [
+    [17.8824, 43.5161, 4.11935],
+    [3.45565, 27.1554, 3.86714],
+    [0.0299566, 0.184309, 1.46709],
+];
+
+/// Inverse of [`RGB_TO_LMS`].
+const LMS_TO_RGB: [[f32; 3]; 3] = [
+    [0.080_944_45, -0.130_504_41, 0.116_721_07],
+    [-0.010_248_53, 0.054_019_33, -0.113_614_71],
+    [-0.000_365_297, -0.004_121_615, 0.693_511_4],
+];
+
+fn mat_mul(a: &[[f32; 3]; 3], b: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(c: f32) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let s = if c <= 0.003_130_8 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    (s * 255.0).round() as u8
+}
+
+/// Render `img` as it would look to someone with the given colour-vision
+/// deficiency, for accessibility review. Alpha is kept as is.
+pub fn simulate_cvd(img: &DynamicImage, kind: CvdType) -> DynamicImage {
+    let m = mat_mul(&LMS_TO_RGB, &mat_mul(&kind.lms_projection(), &RGB_TO_LMS));
+    let linear: Vec<f32> = (0..=255u8).map(srgb_to_linear).collect();
+    let mut out = img.to_rgba8();
+    for p in out.pixels_mut() {
+        let rgb = [linear[p[0] as usize], linear[p[1] as usize], linear[p[2] as usize]];
+        for (c, row) in m.iter().enumerate() {
+            p[c] = linear_to_srgb(row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2]);
+        }
+    }
+    DynamicImage::ImageRgba8(out)
+}
+
+/// The capture followed by one simulated variant per `kinds`, side by side.
+pub fn compose_cvd_strip(img: &DynamicImage, kinds: &[CvdType]) -> RgbaImage {
+    kinds.iter().fold(img.to_rgba8(), |strip, kind| {
+        compose_side_by_side(&DynamicImage::ImageRgba8(strip), &simulate_cvd(img, *kind), 16)
+    })
+}
+
 /// Take annotated screenshot with UI element bounds drawn
 pub fn take_annotated_screenshot(
     platform: &str,
@@ -172,7 +1520,7 @@ pub fn analyze_screenshot(data: &[u8]) -> Result<ScreenshotInfo> {
     let brightness = calculate_brightness(&img);
 
     // Detect if mostly text (high contrast)
-    let is_text_heavy = brightness > 200.0 || brightness < 50.0;
+    let is_text_heavy = !(50.0..=200.0).contains(&brightness);
 
     Ok(ScreenshotInfo {
         width,
@@ -215,3 +1563,393 @@ pub struct ScreenshotInfo {
     pub brightness: f32,
     pub is_text_heavy: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_baseline_ignore_mask() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let baseline = dir.join("android.png");
+        let black = DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 20, Rgba([0, 0, 0, 255])));
+        black.save(&baseline).unwrap();
+        let mut clock = black.to_rgba8();
+        for x in 0..10 {
+            clock.put_pixel(x, 0, Rgba([255, 255, 255, 255]));
+        }
+        let clock = DynamicImage::ImageRgba8(clock);
+
+        let unmasked = compare_to_baseline(&baseline, &clock, 16).unwrap();
+        assert_eq!((unmasked.masked_pixels, unmasked.diff_fraction), (0, 10.0 / 800.0));
+
+        let mask = edit_mask(&baseline, &[MaskRect::parse("0,0,20,2").unwrap()], false).unwrap();
+        assert_eq!(mask.rects.len(), 1);
+        assert!(dir.join("android.mask.json").exists());
+        let masked = compare_to_baseline(&baseline, &clock, 16).unwrap();
+        assert_eq!((masked.masked_pixels, masked.diff_fraction), (40, 0.0));
+
+        edit_mask(&baseline, &[], true).unwrap();
+        assert!(IgnoreMask::load(&baseline).unwrap().is_none());
+        assert!(MaskRect::parse("1,2,0,4").is_err());
+        assert!(MaskRect::parse("1,2,3").is_err());
+
+        // The clock changes between runs; the rest of the screen does not.
+        let suggested = suggest_mask(&[black.clone(), clock, black], 16).unwrap();
+        assert_eq!(suggested, vec![MaskRect { x: 0, y: 0, width: 16, height: 16 }]);
+    }
+
+    #[test]
+    fn test_change_heatmap() {
+        let frame = |lit: &[(u32, u32)]| {
+            let mut img = RgbaImage::from_pixel(4, 2, Rgba([0, 0, 0, 255]));
+            for &(x, y) in lit {
+                img.put_pixel(x, y, Rgba([200, 200, 200, 255]));
+            }
+            DynamicImage::ImageRgba8(img)
+        };
+        // (0,0) flips every frame, (3,1) once; the rest never changes.
+        let map = change_heatmap(&[frame(&[]), frame(&[(0, 0)]), frame(&[(3, 1)])]).unwrap();
+        assert_eq!(*map.get_pixel(1, 0), Rgba([0, 0, 0, 255]));
+        let hot = map.get_pixel(0, 0);
+        let warm = map.get_pixel(3, 1);
+        assert!(hot[0] > warm[0] && warm[2] == 0 && hot[2] == 0, "{:?} {:?}", hot, warm);
+
+        let err = change_heatmap(&[frame(&[]), DynamicImage::ImageRgba8(RgbaImage::new(2, 2))]).unwrap_err();
+        assert!(err.to_string().contains("Frame 2 is 2x2, but frame 1 is 4x2"), "{}", err);
+        assert!(change_heatmap(&[frame(&[])]).is_err());
+    }
+
+    fn blank(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(width, height, Rgba([255, 255, 255, 255])))
+    }
+
+    #[test]
+    fn test_overlay_grid_draws_lines_at_spacing() {
+        let out = overlay_grid(&blank(300, 300), 100, 0).unwrap();
+        assert_ne!(out.get_pixel(100, 50), &Rgba([255, 255, 255, 255]));
+        assert_ne!(out.get_pixel(50, 200), &Rgba([255, 255, 255, 255]));
+        assert_eq!(out.get_pixel(50, 50), &Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_overlay_grid_is_subtle() {
+        let out = overlay_grid(&blank(300, 300), 100, 0).unwrap();
+        // Minor lines keep most of the underlying pixel.
+        assert!(out.get_pixel(100, 50)[1] > 150);
+    }
+
+    #[test]
+    fn test_overlay_grid_rejects_tiny_spacing() {
+        assert!(overlay_grid(&blank(100, 100), 2, 0).is_err());
+    }
+
+    #[test]
+    fn test_capture_theme_pair_detects_unchanged_theme() {
+        let png = |color: [u8; 4]| {
+            let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(20, 20, Rgba(color)));
+            let mut out = Vec::new();
+            img.write_to(&mut Cursor::new(&mut out), image::ImageFormat::Png).unwrap();
+            out
+        };
+        let current = std::cell::Cell::new(Theme::Light);
+
+        let pair = capture_theme_pair(
+            || Ok(png(if current.get() == Theme::Dark { [0, 0, 0, 255] } else { [255; 4] })),
+            |t| {
+                current.set(t);
+                Ok(())
+            },
+            0,
+        )
+        .unwrap();
+        assert_eq!(pair.light.to_rgba8().get_pixel(0, 0)[0], 255);
+        assert_eq!(pair.dark.to_rgba8().get_pixel(0, 0)[0], 0);
+
+        let composite = compose_side_by_side(&pair.light, &pair.dark, 4);
+        assert_eq!(composite.dimensions(), (44, 20));
+
+        let err = capture_theme_pair(|| Ok(png([255; 4])), |_| Ok(()), 0).unwrap_err();
+        assert!(err.to_string().contains("identical"));
+    }
+
+    #[test]
+    fn test_trim_borders_crops_letterbox() {
+        let mut img = RgbaImage::from_pixel(100, 80, Rgba([0, 0, 0, 255]));
+        for y in 10..70 {
+            for x in 20..90 {
+                img.put_pixel(x, y, Rgba([200, 50, 50, 255]));
+            }
+        }
+        let trimmed = trim_borders(&DynamicImage::ImageRgba8(img.clone()), None);
+        assert_eq!(trimmed.dimensions(), (70, 60));
+
+        // Explicit background that doesn't match: nothing to trim.
+        let same = trim_borders(&DynamicImage::ImageRgba8(img), Some(Rgba([255, 255, 255, 255])));
+        assert_eq!(same.dimensions(), (100, 80));
+    }
+
+    #[test]
+    fn test_trim_borders_returns_unchanged_when_no_border() {
+        let mut img = RgbaImage::from_pixel(40, 40, Rgba([255, 255, 255, 255]));
+        img.put_pixel(0, 0, Rgba([0, 0, 0, 255])); // corners disagree
+        assert_eq!(trim_borders(&DynamicImage::ImageRgba8(img), None).dimensions(), (40, 40));
+
+        let blank = RgbaImage::from_pixel(40, 40, Rgba([10, 10, 10, 255]));
+        assert_eq!(trim_borders(&DynamicImage::ImageRgba8(blank), None).dimensions(), (40, 40));
+    }
+
+    #[test]
+    fn test_crop_status_bar() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::new(50, 100));
+        assert_eq!(crop_status_bar(&img, 24).dimensions(), (50, 76));
+        assert_eq!(crop_status_bar(&img, 500).dimensions(), (50, 1));
+    }
+
+    #[test]
+    fn test_is_near_black() {
+        let black = RgbaImage::from_pixel(1080, 1920, Rgba([0, 0, 0, 255]));
+        assert!(is_near_black(&DynamicImage::ImageRgba8(black.clone())));
+
+        // A dark theme still has some text on it.
+        let mut dark = black;
+        for x in 100..900 {
+            for y in 500..700 {
+                dark.put_pixel(x, y, Rgba([230, 230, 230, 255]));
+            }
+        }
+        assert!(!is_near_black(&DynamicImage::ImageRgba8(dark.clone())));
+        assert!(!is_uniform(&DynamicImage::ImageRgba8(dark)));
+
+        let white = RgbaImage::from_pixel(300, 500, Rgba([250, 250, 250, 255]));
+        assert!(is_uniform(&DynamicImage::ImageRgba8(white.clone())));
+        assert!(!is_near_black(&DynamicImage::ImageRgba8(white)));
+    }
+
+    #[test]
+    fn test_render_wireframe_keeps_device_size() {
+        let elem = android::UiElement {
+            class: "android.widget.Button".into(),
+            text: "Sign in".into(),
+            resource_id: String::new(),
+            content_desc: String::new(),
+            bounds: (100, 200, 500, 300),
+            clickable: true,
+        };
+        let img = render_wireframe(720, 1280, &[elem]).unwrap();
+        assert_eq!(img.dimensions(), (720, 1280));
+        assert_eq!(img.get_pixel(100, 250).0, [0, 150, 0, 255]);
+        assert!(!is_near_black(&DynamicImage::ImageRgba8(img)));
+    }
+
+    struct SolidBackend(Rgba<u8>);
+
+    impl CaptureBackend for SolidBackend {
+        fn capture(&self) -> Result<DynamicImage> {
+            let mut img = RgbaImage::from_pixel(60, 120, self.0);
+            for y in 40..80 {
+                for x in 10..50 {
+                    img.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+                }
+            }
+            Ok(DynamicImage::ImageRgba8(img))
+        }
+    }
+
+    struct FailingBackend;
+
+    impl CaptureBackend for FailingBackend {
+        fn capture(&self) -> Result<DynamicImage> {
+            anyhow::bail!("device offline")
+        }
+    }
+
+    #[test]
+    fn test_simulate_cvd_keeps_greys_and_merges_red_green() {
+        let mut img = RgbaImage::new(3, 1);
+        img.put_pixel(0, 0, Rgba([128, 128, 128, 255]));
+        img.put_pixel(1, 0, Rgba([220, 40, 40, 255]));
+        img.put_pixel(2, 0, Rgba([40, 160, 40, 200]));
+        let img = DynamicImage::ImageRgba8(img);
+
+        for kind in CvdType::ALL {
+            let grey = simulate_cvd(&img, kind).get_pixel(0, 0);
+            assert!(grey.0[..3].iter().all(|c| c.abs_diff(128) <= 2), "{:?}: {:?}", kind, grey);
+        }
+
+        let dist = |a: Rgba<u8>, b: Rgba<u8>| -> u32 { (0..3).map(|i| a[i].abs_diff(b[i]) as u32).sum() };
+        let sim = simulate_cvd(&img, CvdType::Deuteranopia);
+        let (red, green) = (sim.get_pixel(1, 0), sim.get_pixel(2, 0));
+        assert!(dist(red, green) < dist(img.get_pixel(1, 0), img.get_pixel(2, 0)));
+        assert_eq!(green[3], 200);
+
+        assert_eq!(compose_cvd_strip(&img, &CvdType::ALL).dimensions(), (3 * 4 + 16 * 3, 1));
+        assert_eq!("Tritanopia".parse::<CvdType>().unwrap(), CvdType::Tritanopia);
+        assert!("achromatopsia".parse::<CvdType>().is_err());
+    }
+
+    #[test]
+    fn test_image_decodes_lazily() {
+        let png = encode(&DynamicImage::ImageRgba8(RgbaImage::from_pixel(3, 2, Rgba([9, 8, 7, 255]))), EncodeFormat::Png).unwrap();
+        let img = Image::from_png(png.clone()).unwrap();
+        assert_eq!(img.dimensions(), (3, 2));
+        assert!(img.decoded.get().is_none());
+        assert_eq!(&*img.png().unwrap(), &png[..], "saving reuses the capture's bytes");
+        assert!(img.decoded.get().is_none());
+        assert_eq!(img.pixels().unwrap().get_pixel(2, 1), Rgba([9, 8, 7, 255]));
+        assert!(img.decoded.get().is_some());
+
+        let rendered = Image::from_pixels(DynamicImage::new_rgba8(4, 4));
+        assert_eq!(decode_png(&rendered.png().unwrap()).unwrap().dimensions(), (4, 4));
+        assert!(Image::from_png(b"GIF89a".to_vec()).is_err());
+        let mut corrupt = png[..33].to_vec();
+        corrupt.extend_from_slice(b"garbage");
+        let corrupt = Image::from_png(corrupt).unwrap();
+        assert!(corrupt.pixels().is_err() && corrupt.pixels().is_err(), "a failed decode stays failed");
+    }
+
+    #[test]
+    fn test_capture_all_keeps_order_and_isolates_failures() {
+        let targets: Vec<CaptureTarget> = (0..5)
+            .map(|i| CaptureTarget {
+                id: format!("dev{}", i),
+                backend: if i == 2 {
+                    Box::new(FailingBackend) as Box<dyn CaptureBackend>
+                } else {
+                    Box::new(SolidBackend(Rgba([i as u8, 0, 0, 255])))
+                },
+            })
+            .collect();
+        let results = capture_all(&targets, 2).unwrap();
+        let ids: Vec<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, ["dev0", "dev1", "dev2", "dev3", "dev4"]);
+        for (i, (_, r)) in results.iter().enumerate() {
+            match r {
+                Ok(img) => assert_eq!(img.pixels().unwrap().get_pixel(0, 0)[0], i as u8),
+                Err(e) => assert_eq!((i, e.to_string().as_str()), (2, "device offline")),
+            }
+        }
+        assert!(capture_all(&targets, 0).is_err());
+    }
+
+    #[test]
+    fn test_find_template_locates_patch_at_full_resolution() {
+        // Deterministic texture so every patch is distinctive.
+        let screen = RgbaImage::from_fn(480, 800, |x, y| {
+            let v = ((x * 7 + y * 13) ^ (x * y / 5)) % 251;
+            Rgba([v as u8, (v * 3 % 251) as u8, 40, 255])
+        });
+        let template = image::imageops::crop_imm(&screen, 203, 517, 72, 48).to_image();
+        let screen = DynamicImage::ImageRgba8(screen);
+
+        let found = find_template(&screen, &DynamicImage::ImageRgba8(template.clone())).unwrap();
+        assert_eq!((found.x, found.y, found.width, found.height), (203, 517, 72, 48));
+        assert!(found.score > 0.99, "{}", found.score);
+        assert_eq!(found.center(), (239, 541));
+
+        // Uniformly brighter template still matches the same spot.
+        let mut brighter = template;
+        for p in brighter.pixels_mut() {
+            p.0[2] = p.0[2].saturating_add(60);
+        }
+        let found = find_template(&screen, &DynamicImage::ImageRgba8(brighter)).unwrap();
+        assert_eq!((found.x, found.y), (203, 517));
+
+        // Flat or oversized templates cannot be matched.
+        assert!(find_template(&screen, &blank(20, 20)).is_none());
+        assert!(find_template(&blank(10, 10), &blank(20, 20)).is_none());
+    }
+
+    #[test]
+    fn test_encode_to_writer_png_and_jpeg() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(64, 32, |x, y| Rgba([x as u8 * 4, y as u8 * 8, 90, 200])));
+        let mut sink = std::io::BufWriter::new(Vec::new());
+        encode_to_writer(&img, &mut sink, EncodeFormat::Png).unwrap();
+        let png = sink.into_inner().unwrap();
+        assert_eq!(png, encode(&img, EncodeFormat::Png).unwrap());
+        assert_eq!(image::load_from_memory(&png).unwrap().to_rgba8(), img.to_rgba8());
+
+        let jpeg = encode(&img, EncodeFormat::Jpeg { quality: 80 }).unwrap();
+        assert_eq!(&jpeg[..2], &[0xFF, 0xD8]);
+        assert_eq!(image::load_from_memory(&jpeg).unwrap().dimensions(), (64, 32));
+
+        let webp = encode(&img, EncodeFormat::WebP).unwrap();
+        assert_eq!(&webp[8..12], b"WEBP");
+        assert_eq!(image::load_from_memory(&webp).unwrap().to_rgba8(), img.to_rgba8());
+    }
+
+    #[test]
+    fn test_capture_encodings_per_purpose() {
+        assert_eq!(EncodeFormat::parse("jpeg:60").unwrap(), EncodeFormat::Jpeg { quality: 60 });
+        assert_eq!(EncodeFormat::parse("jpeg").unwrap(), CaptureEncodings::default().report);
+        assert_eq!(EncodeFormat::parse("webp").unwrap().extension(), "webp");
+        assert!(EncodeFormat::parse("jpeg:0").is_err());
+        assert!(EncodeFormat::parse("gif").is_err());
+
+        let config = |key: &str| (key == "screenshot.report-format").then(|| "webp".to_string());
+        let encodings = CaptureEncodings::from_config(config).unwrap();
+        assert_eq!(encodings.format(CapturePurpose::Report), EncodeFormat::WebP);
+        assert_eq!(encodings.format(CapturePurpose::Model), EncodeFormat::Jpeg { quality: 70 });
+        assert_eq!(encodings.format(CapturePurpose::Baseline), EncodeFormat::Png);
+        assert!(CaptureEncodings::from_config(|_| Some("bmp".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_resize_to_fit_keeps_aspect_and_never_upscales() {
+        let fitted = resize_to_fit(blank(1080, 2400), 540, 960);
+        assert_eq!(fitted.dimensions(), (432, 960));
+        let fitted = resize_to_fit(blank(2400, 1080), 540, 960);
+        assert_eq!(fitted.dimensions(), (540, 243));
+        let small = resize_to_fit(blank(300, 200), 540, 960);
+        assert_eq!(small.dimensions(), (300, 200));
+    }
+
+    #[test]
+    fn test_onion_skin_fades_and_encodes() {
+        let black = DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 4, Rgba([0, 0, 0, 255])));
+        let white = blank(8, 4);
+        let frames = onion_skin(&black, &white, 5).unwrap();
+        assert_eq!(frames.len(), 5);
+        assert_eq!(frames[0].get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+        assert_eq!(frames[2].get_pixel(0, 0), &Rgba([128, 128, 128, 255]));
+        assert_eq!(frames[4].get_pixel(0, 0), &Rgba([255, 255, 255, 255]));
+        assert!(onion_skin(&black, &blank(4, 8), 5).is_err());
+
+        let gif = encode_gif(&frames, 100).unwrap();
+        assert!(gif.starts_with(b"GIF89a"));
+    }
+
+    #[test]
+    fn test_evidence_footer_extends_canvas() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(320, 200, Rgba([200, 10, 10, 255])));
+        let footer = EvidenceFooter {
+            lines: vec!["emulator-5554 · Pixel 8 · 2026-01-01T00:00:00Z".into(), "test: TC-42".into()],
+            font_px: 16.0,
+            position: FooterPosition::Bottom,
+        };
+        let out = add_evidence_footer(&img, &footer).unwrap();
+        assert_eq!(out.width(), 320);
+        assert!(out.height() > 200);
+        assert_eq!(out.get_pixel(0, 199), &Rgba([200, 10, 10, 255]));
+        assert!(out.enumerate_pixels().skip(320 * 200).any(|(_, _, p)| p[0] > 200 && p[2] > 200));
+
+        let top = add_evidence_footer(&img, &EvidenceFooter { position: FooterPosition::Top, ..footer }).unwrap();
+        assert_eq!(top.get_pixel(0, top.height() - 1), &Rgba([200, 10, 10, 255]));
+        assert_eq!(top.get_pixel(0, 0), &Rgba([24, 24, 24, 255]));
+        assert!(FooterPosition::parse("left").is_err());
+    }
+
+    #[test]
+    fn test_custom_backend_goes_through_pipeline() {
+        let backend = SolidBackend(Rgba([0, 0, 0, 255]));
+        let trim = TrimOptions { status_bar_px: 0, borders: true };
+        let png = process_capture(&backend, None, None, Some(trim), None, None).unwrap();
+        let out = image::load_from_memory(&png).unwrap();
+        assert_eq!(out.dimensions(), (40, 40));
+
+        let opts = AndroidCaptureOptions::default();
+        assert!(backend_for("android", None, None, None, opts).is_ok());
+        assert!(backend_for("windows", None, None, None, opts).is_err());
+    }
+}
@@ -16,6 +16,7 @@ const AUTH_URL: &str = "https://public-api.rustore.ru/public/auth";
 #[derive(Deserialize)]
 struct Credentials {
     #[serde(rename = "companyId")]
+    #[allow(dead_code)]
     company_id: String,
     #[serde(rename = "keyId")]
     key_id: String,
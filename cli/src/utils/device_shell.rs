@@ -319,7 +319,7 @@ mod tests {
     // ---------------------------------------------------------------------
 
     /// Each (label, payload) pair. The payload is wrapped in a fixed prefix
-    /// + suffix so we can be sure the tokenizer assembled a single token
+    /// and suffix so we can be sure the tokenizer assembled a single token
     /// containing exactly the payload (with surrounding markers).
     fn metachar_corpus() -> Vec<(&'static str, &'static str)> {
         vec![
@@ -0,0 +1,268 @@
+//! Instance counts per class from an HPROF heap dump.
+//!
+//! Only as much of the format is read as counting needs: strings and
+//! LOAD CLASS records for names, and heap dump segments for instances
+//! and arrays. Field values are skipped, not decoded. Android's extra
+//! sub-records (heap info, extra root kinds, arrays without data) are
+//! understood. When the dump names its heaps, only objects in the `app`
+//! heap are counted; zygote and image objects are shared framework state
+//! and would swamp the app's own classes.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+
+const TAG_STRING: u8 = 0x01;
+const TAG_LOAD_CLASS: u8 = 0x02;
+const TAG_HEAP_DUMP: u8 = 0x0C;
+const TAG_HEAP_DUMP_SEGMENT: u8 = 0x1C;
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    id_size: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|&e| e <= self.data.len());
+        let Some(end) = end else {
+            bail!("Truncated heap dump at byte {}", self.pos);
+        };
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn id(&mut self) -> Result<u64> {
+        Ok(self.take(self.id_size)?.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64))
+    }
+
+    fn skip(&mut self, n: usize) -> Result<()> {
+        self.take(n).map(|_| ())
+    }
+
+    /// Size of a value of HPROF basic type `ty`.
+    fn type_size(&self, ty: u8) -> Result<usize> {
+        Ok(match ty {
+            2 => self.id_size,
+            4 | 8 => 1,
+            5 | 9 => 2,
+            6 | 10 => 4,
+            7 | 11 => 8,
+            other => bail!("Unknown value type {} in heap dump", other),
+        })
+    }
+}
+
+/// Live instance (and array) counts per class name.
+pub fn count_instances(data: &[u8]) -> Result<HashMap<String, u64>> {
+    let nul = data.iter().position(|&b| b == 0).context("Not an HPROF file (no header)")?;
+    if !data[..nul].starts_with(b"JAVA PROFILE") {
+        bail!("Not an HPROF file");
+    }
+    let mut r = Reader { data, pos: nul + 1, id_size: 4 };
+    let id_size = r.u32()? as usize;
+    if !matches!(id_size, 4 | 8) {
+        bail!("Unsupported HPROF id size {}", id_size);
+    }
+    r.id_size = id_size;
+    r.skip(8)?; // timestamp
+
+    let mut strings: HashMap<u64, String> = HashMap::new();
+    let mut class_names: HashMap<u64, u64> = HashMap::new();
+    let mut counts: HashMap<u64, u64> = HashMap::new();
+    // Whether the dump names its heaps, and whether the current one is "app".
+    let mut named_heaps = false;
+    let mut in_app_heap = true;
+
+    while r.pos < data.len() {
+        let tag = r.u8()?;
+        r.skip(4)?; // time offset
+        let len = r.u32()? as usize;
+        let body_end = r.pos.checked_add(len).filter(|&e| e <= data.len()).context("Truncated heap dump record")?;
+        match tag {
+            TAG_STRING => {
+                let id = r.id()?;
+                let text = r.take(len.checked_sub(id_size).context("Bad string record")?)?;
+                strings.insert(id, String::from_utf8_lossy(text).into_owned());
+            }
+            TAG_LOAD_CLASS => {
+                r.skip(4)?;
+                let class = r.id()?;
+                r.skip(4)?;
+                class_names.insert(class, r.id()?);
+            }
+            TAG_HEAP_DUMP | TAG_HEAP_DUMP_SEGMENT => {
+                while r.pos < body_end {
+                    let sub = r.u8()?;
+                    let counted = !named_heaps || in_app_heap;
+                    match sub {
+                        // Roots: an id plus a fixed tail.
+                        0xFF | 0x05 | 0x07 | 0x89 | 0x8A | 0x8B | 0x8C | 0x8D | 0x90 => r.skip(id_size)?,
+                        0x01 => r.skip(2 * id_size)?,
+                        0x04 | 0x06 => r.skip(id_size + 4)?,
+                        0x02 | 0x03 | 0x08 | 0x8E => r.skip(id_size + 8)?,
+                        0x20 => {
+                            // CLASS DUMP
+                            r.skip(id_size + 4 + 6 * id_size + 4)?;
+                            for _ in 0..r.u16()? {
+                                r.skip(2)?;
+                                let ty = r.u8()?;
+                                r.skip(r.type_size(ty)?)?;
+                            }
+                            for _ in 0..r.u16()? {
+                                r.skip(id_size)?;
+                                let ty = r.u8()?;
+                                r.skip(r.type_size(ty)?)?;
+                            }
+                            let fields = r.u16()? as usize;
+                            r.skip(fields * (id_size + 1))?;
+                        }
+                        0x21 => {
+                            // INSTANCE DUMP
+                            r.skip(id_size + 4)?;
+                            let class = r.id()?;
+                            let n = r.u32()? as usize;
+                            r.skip(n)?;
+                            if counted {
+                                *counts.entry(class).or_default() += 1;
+                            }
+                        }
+                        0x22 => {
+                            // OBJECT ARRAY DUMP
+                            r.skip(id_size + 4)?;
+                            let n = r.u32()? as usize;
+                            let class = r.id()?;
+                            r.skip(n.checked_mul(id_size).context("Bad array length")?)?;
+                            if counted {
+                                *counts.entry(class).or_default() += 1;
+                            }
+                        }
+                        0x23 | 0xC3 => {
+                            // PRIMITIVE ARRAY DUMP; Android's 0xC3 has no data.
+                            r.skip(id_size + 4)?;
+                            let n = r.u32()? as usize;
+                            let ty = r.u8()?;
+                            if sub == 0x23 {
+                                r.skip(n.checked_mul(r.type_size(ty)?).context("Bad array length")?)?;
+                            }
+                            if counted {
+                                *counts.entry(PRIMITIVE_ARRAY_BASE + ty as u64).or_default() += 1;
+                            }
+                        }
+                        0xFE => {
+                            // HEAP DUMP INFO (Android): heap id, heap name
+                            r.skip(4)?;
+                            let name = r.id()?;
+                            named_heaps = true;
+                            in_app_heap = strings.get(&name).is_some_and(|n| n == "app");
+                        }
+                        other => bail!("Unknown heap dump sub-record 0x{:02x} at byte {}", other, r.pos - 1),
+                    }
+                }
+            }
+            _ => {}
+        }
+        r.pos = body_end;
+    }
+
+    Ok(counts
+        .into_iter()
+        .map(|(class, n)| {
+            let name = if class >= PRIMITIVE_ARRAY_BASE {
+                primitive_array_name((class - PRIMITIVE_ARRAY_BASE) as u8).to_string()
+            } else {
+                class_names
+                    .get(&class)
+                    .and_then(|s| strings.get(s))
+                    .cloned()
+                    .unwrap_or_else(|| format!("<class 0x{:x}>", class))
+            };
+            (name, n)
+        })
+        .fold(HashMap::new(), |mut acc, (name, n)| {
+            *acc.entry(name).or_default() += n;
+            acc
+        }))
+}
+
+/// Pseudo class ids for primitive arrays, above any real object id.
+const PRIMITIVE_ARRAY_BASE: u64 = u64::MAX - 0xFF;
+
+fn primitive_array_name(ty: u8) -> &'static str {
+    match ty {
+        4 => "boolean[]",
+        5 => "char[]",
+        6 => "float[]",
+        7 => "double[]",
+        8 => "byte[]",
+        9 => "short[]",
+        10 => "int[]",
+        11 => "long[]",
+        _ => "<primitive>[]",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal 4-byte-id dump: two strings, one class, and a heap segment.
+    fn dump(segment: &[u8]) -> Vec<u8> {
+        let mut d = b"JAVA PROFILE 1.0.3\0".to_vec();
+        d.extend_from_slice(&4u32.to_be_bytes());
+        d.extend_from_slice(&[0; 8]);
+        let mut record = |tag: u8, body: &[u8]| {
+            d.push(tag);
+            d.extend_from_slice(&[0; 4]);
+            d.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            d.extend_from_slice(body);
+        };
+        record(TAG_STRING, b"\0\0\0\x01com.example.Leaky");
+        record(TAG_STRING, b"\0\0\0\x02app");
+        record(TAG_LOAD_CLASS, &[0, 0, 0, 1, 0, 0, 0, 0x10, 0, 0, 0, 0, 0, 0, 0, 1]);
+        record(TAG_HEAP_DUMP_SEGMENT, segment);
+        d
+    }
+
+    fn instance(id: u8) -> Vec<u8> {
+        vec![0x21, 0, 0, 0, id, 0, 0, 0, 0, 0, 0, 0, 0x10, 0, 0, 0, 2, 0xAB, 0xCD]
+    }
+
+    #[test]
+    fn test_count_instances() {
+        let mut seg = vec![0xFF, 0, 0, 0, 9]; // root
+        seg.extend(instance(1));
+        seg.extend(instance(2));
+        seg.extend([0x23, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 2, 8, 1, 2]); // byte[2]
+        let counts = count_instances(&dump(&seg)).unwrap();
+        assert_eq!(counts["com.example.Leaky"], 2);
+        assert_eq!(counts["byte[]"], 1);
+
+        // With heap info, only the app heap counts.
+        let mut seg = vec![0xFE, 0, 0, 0, 1, 0, 0, 0, 7]; // heap "<unnamed 7>"
+        seg.extend(instance(1));
+        seg.extend([0xFE, 0, 0, 0, 2, 0, 0, 0, 2]); // heap "app"
+        seg.extend(instance(2));
+        assert_eq!(count_instances(&dump(&seg)).unwrap()["com.example.Leaky"], 1);
+
+        assert!(count_instances(b"PK\x03\x04").is_err());
+        let truncated = dump(&instance(1));
+        assert!(count_instances(&truncated[..truncated.len() - 3]).is_err());
+    }
+}
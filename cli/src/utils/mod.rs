@@ -1,5 +1,7 @@
 //! Shared CLI utilities (input validation, sanitisation, etc.).
 
 pub mod device_shell;
+pub mod hprof;
 pub mod shell_gate;
 pub mod validate;
+pub mod zip;
@@ -0,0 +1,97 @@
+//! Minimal ZIP writer for diagnostic bundles.
+//!
+//! Entries are stored uncompressed: the bundles are small text dumps plus
+//! an already-compressed PNG, and avoiding a deflate dependency keeps the
+//! archive readable by any unzip tool.
+
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+/// CRC-32 (IEEE) as ZIP requires, computed bitwise.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Build an archive with `(name, contents)` entries, in order.
+pub fn stored_zip(entries: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    let mut out: Vec<u8> = Vec::new();
+    let mut central: Vec<u8> = Vec::new();
+    for (name, data) in entries {
+        if name.len() > u16::MAX as usize || data.len() > u32::MAX as usize || out.len() > u32::MAX as usize {
+            bail!("ZIP entry '{}' is too large", name);
+        }
+        let (crc, size, offset) = (crc32(data), data.len() as u32, out.len() as u32);
+
+        // Local file header.
+        out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        out.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0]); // version, flags, method, time
+        out.extend_from_slice(&0x21u16.to_le_bytes()); // date: 1980-01-01
+        out.extend_from_slice(&crc.to_le_bytes());
+        out.extend_from_slice(&size.to_le_bytes());
+        out.extend_from_slice(&size.to_le_bytes());
+        out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(data);
+
+        // Central directory record.
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&[20, 0, 20, 0, 0, 0, 0, 0, 0, 0]); // made by, needed, flags, method, time
+        central.extend_from_slice(&0x21u16.to_le_bytes());
+        central.extend_from_slice(&crc.to_le_bytes());
+        central.extend_from_slice(&size.to_le_bytes());
+        central.extend_from_slice(&size.to_le_bytes());
+        central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        central.extend_from_slice(&[0; 12]); // extra, comment, disk, attributes
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+    let (cd_offset, cd_size, count) = (out.len() as u32, central.len() as u32, entries.len() as u16);
+    out.extend_from_slice(&central);
+
+    // End of central directory.
+    out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&cd_size.to_le_bytes());
+    out.extend_from_slice(&cd_offset.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    Ok(out)
+}
+
+pub fn write_stored_zip(path: &Path, entries: &[(String, Vec<u8>)]) -> Result<()> {
+    let data = stored_zip(entries)?;
+    let mut file = std::fs::File::create(path).with_context(|| format!("Cannot create {}", path.display()))?;
+    file.write_all(&data).with_context(|| format!("Cannot write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_stored_zip_layout() {
+        let zip = stored_zip(&[("a.txt".into(), b"hello".to_vec()), ("b/c.txt".into(), Vec::new())]).unwrap();
+        assert_eq!(&zip[..4], b"PK\x03\x04");
+        // Data follows the 30-byte header and the name, uncompressed.
+        assert_eq!(&zip[30 + 5..30 + 5 + 5], b"hello");
+        let eocd = &zip[zip.len() - 22..];
+        assert_eq!(&eocd[..4], b"PK\x05\x06");
+        assert_eq!(u16::from_le_bytes([eocd[10], eocd[11]]), 2);
+    }
+}
@@ -35,6 +35,16 @@ fun main() = runBlocking {
         screenCapture.capture(windowId, quality, monitorIndex)
     }
 
+    // Screenshot of a region in logical coordinates, straight off the display
+    server.registerTypedHandler("screenshot_region") { params ->
+        val x = params.intOrThrow("x")
+        val y = params.intOrThrow("y")
+        val width = params.intOrThrow("width")
+        val height = params.intOrThrow("height")
+        val quality = params.int("quality") ?: 80
+        screenCapture.captureRegion(x, y, width, height, quality)
+    }
+
     // Get list of monitors
     server.registerTypedHandler("get_monitors") { _ ->
         MonitorsResult(monitors = screenCapture.getMonitors())